@@ -47,6 +47,16 @@ fn main() -> anyhow::Result<()> {
                 "tests/wast/spec/proposals/threads",
                 wast_processor,
             )?;
+            test_directory_module(
+                spectests,
+                "tests/wast/spec/proposals/relaxed-simd",
+                wast_processor,
+            )?;
+            test_directory_module(
+                spectests,
+                "tests/wast/spec/proposals/extended-const",
+                wast_processor,
+            )?;
             // test_directory_module(spectests, "tests/wast/spec/proposals/bulk-memory-operations", wast_processor)?;
             Ok(())
         })?;
@@ -2,7 +2,7 @@
 
 use libfuzzer_sys::{arbitrary, arbitrary::Arbitrary, fuzz_target};
 use wasm_smith::{Config, ConfiguredModule};
-use wasmer::{CompilerConfig, Engine, EngineBuilder, Module, Store};
+use wasmer::{CompilerConfig, Engine, EngineBuilder, Features, Module, Store};
 use wasmer_compiler_cranelift::Cranelift;
 use wasmer_compiler_llvm::LLVM;
 use wasmer_compiler_singlepass::Singlepass;
@@ -65,4 +65,33 @@ fuzz_target!(|module: ConfiguredModule<NoImportsConfig>| {
         EngineBuilder::new(compiler.clone()).engine(),
         &wasm_bytes,
     );
+
+    // Deterministic engines canonicalize NaNs on their own.
+    let mut compiler = Cranelift::default();
+    compiler.enable_verifier();
+    compile_and_compare(
+        "universal-cranelift-deterministic",
+        EngineBuilder::new(compiler)
+            .set_features(Some(Features::deterministic()))
+            .engine(),
+        &wasm_bytes,
+    );
+
+    let mut compiler = LLVM::default();
+    compiler.enable_verifier();
+    compile_and_compare(
+        "universal-llvm-deterministic",
+        EngineBuilder::new(compiler)
+            .set_features(Some(Features::deterministic()))
+            .engine(),
+        &wasm_bytes,
+    );
+
+    compile_and_compare(
+        "universal-singlepass-deterministic",
+        EngineBuilder::new(Singlepass::default())
+            .set_features(Some(Features::deterministic()))
+            .engine(),
+        &wasm_bytes,
+    );
 });
@@ -4,7 +4,7 @@
 use anyhow::Result;
 use libfuzzer_sys::{arbitrary, arbitrary::Arbitrary, fuzz_target};
 use wasm_smith::{Config, ConfiguredModule};
-use wasmer::{imports, CompilerConfig, EngineBuilder, Instance, Module, Store, Val};
+use wasmer::{imports, CompilerConfig, EngineBuilder, Features, Instance, Module, Store, Val};
 #[cfg(feature = "cranelift")]
 use wasmer_compiler_cranelift::Cranelift;
 #[cfg(feature = "llvm")]
@@ -47,7 +47,11 @@ impl std::fmt::Debug for WasmSmithModule {
 #[cfg(feature = "singlepass")]
 fn maybe_instantiate_singlepass(wasm_bytes: &[u8]) -> Result<Option<Instance>> {
     let compiler = Singlepass::default();
-    let mut store = Store::new(compiler);
+    let mut store = Store::new(
+        EngineBuilder::new(compiler)
+            .set_features(Some(Features::deterministic()))
+            .engine(),
+    );
     let module = Module::new(&store, &wasm_bytes);
     let module = match module {
         Ok(m) => m,
@@ -66,9 +70,14 @@ fn maybe_instantiate_singlepass(wasm_bytes: &[u8]) -> Result<Option<Instance>> {
 #[cfg(feature = "cranelift")]
 fn maybe_instantiate_cranelift(wasm_bytes: &[u8]) -> Result<Option<Instance>> {
     let mut compiler = Cranelift::default();
-    compiler.canonicalize_nans(true);
     compiler.enable_verifier();
-    let mut store = Store::new(compiler);
+    // Deterministic engines canonicalize NaNs, so results can be compared
+    // bit for bit.
+    let mut store = Store::new(
+        EngineBuilder::new(compiler)
+            .set_features(Some(Features::deterministic()))
+            .engine(),
+    );
     let module = Module::new(&store, &wasm_bytes)?;
     let instance = Instance::new(&module, &imports! {})?;
     Ok(Some(instance))
@@ -77,9 +86,14 @@ fn maybe_instantiate_cranelift(wasm_bytes: &[u8]) -> Result<Option<Instance>> {
 #[cfg(feature = "llvm")]
 fn maybe_instantiate_llvm(wasm_bytes: &[u8]) -> Result<Option<Instance>> {
     let mut compiler = LLVM::default();
-    compiler.canonicalize_nans(true);
     compiler.enable_verifier();
-    let mut store = Store::new(compiler);
+    // Deterministic engines canonicalize NaNs, so results can be compared
+    // bit for bit.
+    let mut store = Store::new(
+        EngineBuilder::new(compiler)
+            .set_features(Some(Features::deterministic()))
+            .engine(),
+    );
     let module = Module::new(&store, &wasm_bytes)?;
     let instance = Instance::new(&module, &imports! {})?;
     Ok(Some(instance))
@@ -1,6 +1,6 @@
 use crate::sys::exports::{ExportError, Exportable};
 use crate::sys::externals::Extern;
-use crate::sys::store::{AsStoreMut, AsStoreRef};
+use crate::sys::store::{AsStoreMut, AsStoreRef, MemoryWriteLog};
 use crate::sys::MemoryType;
 use crate::MemoryAccessError;
 use std::convert::TryInto;
@@ -132,6 +132,54 @@ impl Memory {
         self.handle.get_mut(store.objects_mut()).grow(delta.into())
     }
 
+    /// Starts logging the writes made to this memory through its views,
+    /// and through the [`WasmRef`]s and [`WasmSlice`]s read from them,
+    /// until [`Memory::take_writes`] is called.
+    ///
+    /// Writes made by WebAssembly code, or through the raw pointers of a
+    /// view such as [`MemoryView::data_unchecked_mut`], are not logged.
+    ///
+    /// [`WasmRef`]: crate::WasmRef
+    /// [`WasmSlice`]: crate::WasmSlice
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Memory, MemoryType, Store};
+    /// # let mut store = Store::default();
+    /// #
+    /// let m = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+    /// m.log_writes(&mut store);
+    /// m.view(&store).write(8, &[1, 2]).unwrap();
+    ///
+    /// assert_eq!(m.take_writes(&mut store), vec![(8, vec![1, 2])]);
+    /// ```
+    pub fn log_writes(&self, store: &mut impl AsStoreMut) {
+        assert!(
+            self.is_from_store(store),
+            "the memory does not belong to this store"
+        );
+        let handle = self.handle.internal_handle();
+        let store = store.as_store_mut();
+        let logs = &mut store.inner.memory_write_logs;
+        if !logs.iter().any(|(memory, _)| *memory == handle) {
+            logs.push((handle, Default::default()));
+        }
+    }
+
+    /// Stops logging the writes made to this memory, and returns the ones
+    /// logged since [`Memory::log_writes`] was called, as offsets and
+    /// bytes, in the order they were made.
+    pub fn take_writes(&self, store: &mut impl AsStoreMut) -> Vec<(u64, Vec<u8>)> {
+        let handle = self.handle.internal_handle();
+        let store = store.as_store_mut();
+        let logs = &mut store.inner.memory_write_logs;
+        match logs.iter().position(|(memory, _)| *memory == handle) {
+            Some(index) => mem::take(&mut *logs.swap_remove(index).1.lock().unwrap()),
+            None => Vec::new(),
+        }
+    }
+
     pub(crate) fn from_vm_extern(
         store: &impl AsStoreRef,
         internal: InternalStoreHandle<VMMemory>,
@@ -182,6 +230,7 @@ impl<'a> Exportable<'a> for Memory {
 pub(crate) struct MemoryBuffer<'a> {
     pub(crate) base: *mut u8,
     pub(crate) len: usize,
+    pub(crate) log: Option<&'a MemoryWriteLog>,
     pub(crate) marker: PhantomData<&'a MemoryView<'a>>,
 }
 
@@ -249,6 +298,9 @@ impl<'a> MemoryBuffer<'a> {
         unsafe {
             volatile_memcpy_write(data.as_ptr(), self.base.add(offset as usize), data.len());
         }
+        if let Some(log) = self.log {
+            log.lock().unwrap().push((offset, data.to_vec()));
+        }
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use crate::sys::store::AsStoreRef;
+use crate::sys::store::{AsStoreRef, MemoryWriteLog};
 use crate::MemoryAccessError;
 use std::convert::TryInto;
 use std::marker::PhantomData;
@@ -20,11 +20,20 @@ use super::Memory;
 pub struct MemoryView<'a> {
     pub(crate) buffer: MemoryBuffer<'a>,
     pub(crate) size: Pages,
+    log: Option<MemoryWriteLog>,
 }
 
 impl<'a> MemoryView<'a> {
     pub(crate) fn new(memory: &'a Memory, store: &impl AsStoreRef) -> Self {
         let size = memory.handle.get(store.as_store_ref().objects()).size();
+        let handle = memory.handle.internal_handle();
+        let log = store
+            .as_store_ref()
+            .inner
+            .memory_write_logs
+            .iter()
+            .find(|(memory, _)| *memory == handle)
+            .map(|(_, log)| log.clone());
 
         let definition = memory.handle.get(store.as_store_ref().objects()).vmmemory();
         let def = unsafe { definition.as_ref() };
@@ -33,9 +42,11 @@ impl<'a> MemoryView<'a> {
             buffer: MemoryBuffer {
                 base: def.base,
                 len: def.current_length,
+                log: None,
                 marker: PhantomData,
             },
             size,
+            log,
         }
     }
 
@@ -96,8 +107,11 @@ impl<'a> MemoryView<'a> {
         self.size
     }
 
-    pub(crate) fn buffer(&'a self) -> MemoryBuffer<'a> {
-        self.buffer
+    pub(crate) fn buffer(&self) -> MemoryBuffer<'_> {
+        MemoryBuffer {
+            log: self.log.as_ref(),
+            ..self.buffer
+        }
     }
 
     /// Safely reads bytes from the memory at the given offset.
@@ -147,7 +161,7 @@ impl<'a> MemoryView<'a> {
     /// This method is guaranteed to be safe (from the host side) in the face of
     /// concurrent reads/writes.
     pub fn write(&self, offset: u64, data: &[u8]) -> Result<(), MemoryAccessError> {
-        self.buffer.write(offset, data)
+        self.buffer().write(offset, data)
     }
 
     /// Safely reads a single byte from memory at the given offset
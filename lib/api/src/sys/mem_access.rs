@@ -64,7 +64,7 @@ impl<'a, T: ValueType> WasmRef<'a, T> {
     #[inline]
     pub fn new(view: &'a MemoryView, offset: u64) -> Self {
         Self {
-            buffer: view.buffer,
+            buffer: view.buffer(),
             offset,
             marker: PhantomData,
         }
@@ -57,7 +57,7 @@ pub mod vm {
     //! The `vm` module re-exports wasmer-vm types.
 
     pub use wasmer_vm::{
-        MemoryError, MemoryStyle, TableStyle, VMExtern, VMMemory, VMMemoryDefinition,
+        LinearMemory, MemoryError, MemoryStyle, TableStyle, VMExtern, VMMemory, VMMemoryDefinition,
         VMOwnedMemory, VMSharedMemory, VMTable, VMTableDefinition,
     };
 }
@@ -513,6 +513,12 @@ impl Module {
     pub fn info(&self) -> &ModuleInfo {
         &self.module_info
     }
+
+    /// Returns the number of functions compiled so far, if the functions
+    /// of this module are compiled lazily.
+    pub fn num_lazily_compiled_functions(&self) -> Option<usize> {
+        self.artifact.num_lazily_compiled_functions()
+    }
 }
 
 impl fmt::Debug for Module {
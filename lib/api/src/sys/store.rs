@@ -1,11 +1,14 @@
 use crate::sys::tunables::BaseTunables;
 use std::fmt;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::{AsEngineRef, Engine, EngineBuilder, EngineRef, Tunables};
 use wasmer_vm::{init_traps, TrapHandler, TrapHandlerFn};
 
-use wasmer_vm::StoreObjects;
+use wasmer_vm::{InternalStoreHandle, StoreObjects, VMMemory};
+
+/// The writes made to a memory through its views, as offsets and bytes
+pub(crate) type MemoryWriteLog = Arc<Mutex<Vec<(u64, Vec<u8>)>>>;
 
 /// We require the context to have a fixed memory address for its lifetime since
 /// various bits of the VM have raw pointers that point back to it. Hence we
@@ -15,6 +18,7 @@ pub(crate) struct StoreInner {
     #[cfg(feature = "compiler")]
     pub(crate) engine: Engine,
     pub(crate) trap_handler: Option<Box<TrapHandlerFn<'static>>>,
+    pub(crate) memory_write_logs: Vec<(InternalStoreHandle<VMMemory>, MemoryWriteLog)>,
 }
 
 /// The store represents all global state that can be manipulated by
@@ -48,6 +52,7 @@ impl Store {
                 objects: Default::default(),
                 engine: engine.cloned(),
                 trap_handler: None,
+                memory_write_logs: Vec::new(),
             }),
             engine: engine.cloned(),
             trap_handler: Arc::new(RwLock::new(None)),
@@ -206,6 +206,29 @@ fn memory_grow() -> Result<(), String> {
     Ok(())
 }
 
+#[cfg(feature = "sys")]
+#[test]
+fn memory_log_writes() -> Result<(), String> {
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), None, false))
+        .map_err(|e| format!("{e:?}"))?;
+
+    memory.view(&store).write(0, &[1]).unwrap();
+    memory.log_writes(&mut store);
+    let view = memory.view(&store);
+    view.write(8, &[2, 3]).unwrap();
+    WasmPtr::<u32>::new(16).deref(&view).write(4).unwrap();
+    assert!(view.write(u64::MAX, &[5]).is_err());
+    assert_eq!(
+        memory.take_writes(&mut store),
+        vec![(8, vec![2, 3]), (16, vec![4, 0, 0, 0])]
+    );
+
+    memory.view(&store).write(32, &[6]).unwrap();
+    assert!(memory.take_writes(&mut store).is_empty());
+    Ok(())
+}
+
 #[universal_test]
 fn function_new() -> Result<(), String> {
     let mut store = Store::default();
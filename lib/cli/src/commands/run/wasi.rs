@@ -1,11 +1,19 @@
-use crate::utils::{parse_envvar, parse_mapdir};
+use crate::utils::{parse_dir, parse_envvar, parse_mapdir, DirOption};
 use anyhow::Result;
 use std::collections::BTreeSet;
 use std::path::PathBuf;
-use wasmer::{AsStoreMut, FunctionEnv, Instance, Module, RuntimeError, Value};
+use std::sync::Arc;
+use wasmer::{AsStoreMut, AsStoreRef, FunctionEnv, Imports, Instance, Module, RuntimeError, Value};
+use wasmer_vfs::overlay_fs::OverlayFileSystem;
+use wasmer_vfs::{host_fs, mem_fs, FileSystem};
+use wasmer_vnet::policy::{NetworkPolicy, NetworkRule, PolicyNetworking};
+#[cfg(unix)]
+use wasmer_wasi::HostTty;
 use wasmer_wasi::{
     get_wasi_versions, import_object_for_all_wasi_versions, is_wasix_module,
-    wasi_import_shared_memory, WasiEnv, WasiError, WasiState, WasiVersion,
+    wasi_import_shared_memory, wasi_import_wasmedge_sockets, FixedClock, FsAccess, FsPolicy,
+    LocalVirtualBus, PluggableRuntimeImplementation, SeededRandom, StepClock, StraceFormat,
+    SyscallTracer, UnsupportedVirtualNetworking, WasiEnv, WasiError, WasiState, WasiVersion,
 };
 
 use clap::Parser;
@@ -13,9 +21,17 @@ use clap::Parser;
 #[derive(Debug, Parser, Clone, Default)]
 /// WASI Options
 pub struct Wasi {
-    /// WASI pre-opened directory
-    #[clap(long = "dir", name = "DIR", group = "wasi")]
-    pub(crate) pre_opened_directories: Vec<PathBuf>,
+    /// WASI pre-opened directory, optionally followed by `::` and a
+    /// comma-separated list of options restricting it: `ro` or `rw`,
+    /// `deny=PATTERN` to hide the files matching a pattern like `*.key`,
+    /// and `max-size=BYTES` to limit the size of the files
+    #[clap(
+        long = "dir",
+        name = "DIR",
+        group = "wasi",
+        parse(try_from_str = parse_dir),
+    )]
+    pub(crate) pre_opened_directories: Vec<(PathBuf, Vec<DirOption>)>,
 
     /// Map a host directory to a different location for the Wasm module
     #[clap(
@@ -33,6 +49,53 @@ pub struct Wasi {
     )]
     pub(crate) env_vars: Vec<(String, String)>,
 
+    /// Keep the changes the module makes to its directories in memory,
+    /// on top of the host directories, which are left untouched
+    #[clap(long = "overlay")]
+    pub(crate) overlay: bool,
+
+    /// Only let the module reach the network through the addresses and
+    /// ports matching a rule, like `10.0.0.0/8`, `[::1]:8080`,
+    /// `example.com:443` or `*.example.com:8000-9000`
+    #[clap(long = "net-allow", name = "ALLOW_RULE")]
+    pub(crate) net_allow: Vec<NetworkRule>,
+
+    /// Deny the module the network accesses matching a rule, even if
+    /// allowed by `--net-allow`
+    #[clap(long = "net-deny", name = "DENY_RULE")]
+    pub(crate) net_deny: Vec<NetworkRule>,
+
+    /// Look up the commands spawned by the module, like the ones run by
+    /// a shell, as `<name>.wasm` or `<name>` files in this directory
+    #[clap(long = "command-dir", name = "COMMAND_DIR")]
+    pub(crate) command_dirs: Vec<PathBuf>,
+
+    /// Make the clocks read this time, in nanoseconds since the UNIX
+    /// epoch, instead of the time of the host
+    #[clap(long = "fake-time", name = "NANOSECONDS")]
+    pub(crate) fake_time: Option<u64>,
+
+    /// Move the fake time forward by this many nanoseconds every time
+    /// the module reads a clock
+    #[clap(long = "fake-time-step", name = "STEP", requires = "NANOSECONDS")]
+    pub(crate) fake_time_step: Option<u64>,
+
+    /// Make the random bytes read by the module come from a generator
+    /// with this seed, so that they are the same on every run
+    #[clap(long = "seed", name = "SEED")]
+    pub(crate) seed: Option<u64>,
+
+    /// Print the WASI syscalls made by the module to stderr, as text lines
+    /// or, with `--strace=json`, as JSON objects
+    #[clap(
+        long = "strace",
+        name = "FORMAT",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "text"
+    )]
+    pub(crate) strace: Option<StraceFormat>,
+
     /// Enable experimental IO devices
     #[cfg(feature = "experimental-io-devices")]
     #[cfg_attr(
@@ -88,9 +151,90 @@ impl Wasi {
         wasi_state_builder
             .args(args)
             .envs(self.env_vars.clone())
-            .preopen_dirs(self.pre_opened_directories.clone())?
+            .preopen_dirs(self.pre_opened_directories.iter().map(|(dir, _)| dir))?
             .map_dirs(self.mapped_dirs.clone())?;
 
+        let mut fs_policy = FsPolicy::new();
+        for (dir, options) in self.pre_opened_directories.iter() {
+            for option in options {
+                match option {
+                    DirOption::ReadOnly => fs_policy.access(dir, FsAccess::ReadOnly),
+                    DirOption::ReadWrite => fs_policy.access(dir, FsAccess::ReadWrite),
+                    DirOption::Deny(pattern) => fs_policy.deny(dir, pattern),
+                    DirOption::MaxSize(bytes) => fs_policy.max_file_size(dir, *bytes),
+                };
+            }
+        }
+        wasi_state_builder.fs_policy(fs_policy.clone());
+
+        match (self.fake_time, self.fake_time_step) {
+            (Some(time), Some(step)) => {
+                wasi_state_builder.clock(StepClock::new(time, step));
+            }
+            (Some(time), None) => {
+                wasi_state_builder.clock(FixedClock::new(time));
+            }
+            _ => {}
+        }
+        if let Some(seed) = self.seed {
+            wasi_state_builder.random(SeededRandom::new(seed));
+        }
+
+        // The filesystem is shared with the spawned processes
+        let fs: Arc<dyn FileSystem> = if self.overlay {
+            Arc::new(OverlayFileSystem::new(
+                mem_fs::FileSystem::default(),
+                host_fs::FileSystem::default(),
+            ))
+        } else {
+            Arc::new(host_fs::FileSystem::default())
+        };
+        wasi_state_builder.set_fs(Box::new(fs.clone()));
+
+        let policy = if self.net_allow.is_empty() && self.net_deny.is_empty() {
+            None
+        } else {
+            Some(NetworkPolicy::new(
+                self.net_allow.clone(),
+                self.net_deny.clone(),
+            ))
+        };
+        // The module, and the processes it spawns, see the terminal
+        // `wasmer` runs in
+        #[cfg(unix)]
+        let tty = HostTty::new();
+        let new_runtime = move || {
+            let mut runtime = PluggableRuntimeImplementation::default();
+            #[cfg(unix)]
+            runtime.set_tty_implementation(tty.clone());
+            if let Some(policy) = policy.as_ref() {
+                let networking = std::mem::replace(
+                    &mut runtime.networking,
+                    Box::new(UnsupportedVirtualNetworking::default()),
+                );
+                runtime.set_networking_implementation(PolicyNetworking::from_boxed(
+                    networking,
+                    policy.clone(),
+                ));
+            }
+            runtime
+        };
+        if !self.command_dirs.is_empty() {
+            // The spawned processes get the same filesystem, policies and
+            // terminal
+            let bus = LocalVirtualBus::new(store.as_store_ref().engine().clone());
+            for dir in self.command_dirs.iter() {
+                bus.add_search_path(dir);
+            }
+            bus.set_fs(fs, fs_policy);
+            bus.set_runtime_factory(new_runtime.clone());
+            let mut runtime = new_runtime();
+            runtime.set_bus_implementation(bus);
+            wasi_state_builder.runtime(runtime);
+        } else {
+            wasi_state_builder.runtime(new_runtime());
+        }
+
         #[cfg(feature = "experimental-io-devices")]
         {
             if self.enable_experimental_io_devices {
@@ -99,16 +243,19 @@ impl Wasi {
             }
         }
 
-        let wasi_env = wasi_state_builder.finalize(store)?;
+        let mut wasi_env = wasi_state_builder.finalize(store)?;
         wasi_env.env.as_mut(store).state.fs.is_wasix.store(
             is_wasix_module(module),
             std::sync::atomic::Ordering::Release,
         );
-        let mut import_object = import_object_for_all_wasi_versions(store, &wasi_env.env);
-        wasi_import_shared_memory(&mut import_object, module, store);
+        // The instances of the spawned threads get the same imports
+        let strace = self.strace;
+        wasi_env
+            .data_mut(store)
+            .set_thread_imports(move |store, env, module| Ok(imports(strace, store, env, module)));
+        let import_object = imports(self.strace, store, &wasi_env.env, module);
         let instance = Instance::new(store, module, &import_object)?;
-        let memory = instance.exports.get_memory("memory")?;
-        wasi_env.data_mut(store).set_memory(memory.clone());
+        wasi_env.initialize(store, &instance)?;
         Ok((wasi_env.env, instance))
     }
 
@@ -119,7 +266,10 @@ impl Wasi {
             Err(err) => {
                 let err: anyhow::Error = match err.downcast::<WasiError>() {
                     Ok(WasiError::Exit(exit_code)) => {
-                        // We should exit with the provided exit code
+                        // We should exit with the provided exit code, leaving
+                        // the terminal as the module found it
+                        #[cfg(unix)]
+                        HostTty::restore();
                         std::process::exit(exit_code as _);
                     }
                     Ok(err) => err.into(),
@@ -138,8 +288,25 @@ impl Wasi {
         Ok(Self {
             deny_multiple_wasi_versions: true,
             env_vars: env::vars().collect(),
-            pre_opened_directories: vec![dir],
+            pre_opened_directories: vec![(dir, Vec::new())],
             ..Self::default()
         })
     }
 }
+
+/// Builds the imports given to the module, tracing the syscalls with `strace`.
+fn imports(
+    strace: Option<StraceFormat>,
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    module: &Module,
+) -> Imports {
+    let mut import_object = import_object_for_all_wasi_versions(store, env);
+    wasi_import_shared_memory(&mut import_object, module, store);
+    wasi_import_wasmedge_sockets(&mut import_object, module, store, env);
+    if let Some(format) = strace {
+        import_object =
+            SyscallTracer::new(format, std::io::stderr()).wrap(store, env, &import_object);
+    }
+    import_object
+}
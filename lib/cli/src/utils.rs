@@ -40,6 +40,49 @@ pub fn parse_mapdir(entry: &str) -> Result<(String, PathBuf)> {
     }
 }
 
+/// An option restricting what a module can do with a directory given to
+/// `--dir`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirOption {
+    /// `ro`: the files can't be changed
+    ReadOnly,
+    /// `rw`: the files can be changed, the default
+    ReadWrite,
+    /// `deny=PATTERN`: the files matching the pattern can't be reached
+    Deny(String),
+    /// `max-size=BYTES`: the files can't grow larger than this
+    MaxSize(u64),
+}
+
+/// Parses a directory to preopen, optionally followed by `::` and a
+/// comma-separated list of options, like `data::ro,deny=*.key`
+pub fn parse_dir(entry: &str) -> Result<(PathBuf, Vec<DirOption>)> {
+    let (dir, options) = match entry.rsplit_once("::") {
+        Some((dir, options)) => (dir, options),
+        None => return Ok((PathBuf::from(entry), Vec::new())),
+    };
+    let options = options
+        .split(',')
+        .map(|option| match option.split_once('=') {
+            None if option == "ro" => Ok(DirOption::ReadOnly),
+            None if option == "rw" => Ok(DirOption::ReadWrite),
+            Some(("deny", pattern)) if !pattern.is_empty() => {
+                Ok(DirOption::Deny(pattern.to_string()))
+            }
+            Some(("max-size", bytes)) => match bytes.parse() {
+                Ok(bytes) => Ok(DirOption::MaxSize(bytes)),
+                Err(_) => bail!("Invalid size `{}` for directory `{}`", bytes, dir),
+            },
+            _ => bail!(
+                "Unknown option `{}` for directory `{}`, expected `ro`, `rw`, `deny=PATTERN` or `max-size=BYTES`",
+                option,
+                dir
+            ),
+        })
+        .collect::<Result<_>>()?;
+    Ok((PathBuf::from(dir), options))
+}
+
 /// Parses an environment variable.
 pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     let entry = entry.trim();
@@ -66,7 +109,7 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{parse_dir, parse_envvar, DirOption};
 
     #[test]
     fn test_parse_envvar() {
@@ -89,4 +132,28 @@ mod tests {
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_dir() {
+        assert_eq!(parse_dir("data").unwrap(), ("data".into(), vec![]));
+        assert_eq!(
+            parse_dir("/srv/data::ro,deny=*.key,max-size=1024").unwrap(),
+            (
+                "/srv/data".into(),
+                vec![
+                    DirOption::ReadOnly,
+                    DirOption::Deny("*.key".into()),
+                    DirOption::MaxSize(1024),
+                ]
+            )
+        );
+        assert_eq!(
+            parse_dir("data::rw,max-size=1k").unwrap_err().to_string(),
+            "Invalid size `1k` for directory `data`"
+        );
+        assert_eq!(
+            parse_dir("data::exec").unwrap_err().to_string(),
+            "Unknown option `exec` for directory `data`, expected `ro`, `rw`, `deny=PATTERN` or `max-size=BYTES`"
+        );
+    }
 }
@@ -13,6 +13,7 @@ use crate::translator::{
     signature_to_cranelift_ir, CraneliftUnwindInfo, FuncTranslator,
 };
 use cranelift_codegen::ir::ExternalName;
+use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{ir, MachReloc};
 use cranelift_codegen::{Context, MachTrap};
@@ -28,9 +29,9 @@ use wasmer_compiler::{
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     CallingConvention, Compilation, CompileError, CompileModuleInfo, CompiledFunction,
-    CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, Dwarf, FunctionBody, FunctionIndex,
-    LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex, SignatureIndex,
-    Target, TrapCode, TrapInformation,
+    CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, CustomSection, Dwarf, FunctionBody,
+    FunctionIndex, LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex,
+    SignatureIndex, Target, TrapCode, TrapInformation,
 };
 
 /// A compiler that compiles a WebAssembly module with Cranelift, translating the Wasm to Cranelift IR,
@@ -49,6 +50,141 @@ impl CraneliftCompiler {
     pub fn config(&self) -> &Cranelift {
         &self.config
     }
+
+    /// Compiles a single function, along with an `.eh_frame` section
+    /// holding its DWARF unwind information when the `target` uses them.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_single_function(
+        &self,
+        isa: &dyn TargetIsa,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        signatures: &PrimaryMap<SignatureIndex, ir::Signature>,
+        func_translator: &mut FuncTranslator,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        let module = &compile_info.module;
+        let func_index = module.func_index(index);
+        let mut context = Context::new();
+        let mut func_env = FuncEnvironment::new(
+            isa.frontend_config(),
+            module,
+            signatures,
+            &compile_info.memory_styles,
+            &compile_info.table_styles,
+        );
+        context.func.name = get_function_name(func_index);
+        context.func.signature = signatures[module.functions[func_index]].clone();
+        let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+        reader.set_middleware_chain(
+            self.config
+                .middlewares
+                .generate_function_middleware_chain(index),
+        );
+
+        func_translator.translate(
+            module_translation_state,
+            &mut reader,
+            &mut context.func,
+            &mut func_env,
+            index,
+        )?;
+
+        let mut code_buf: Vec<u8> = Vec::new();
+        context
+            .compile_and_emit(isa, &mut code_buf)
+            .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
+
+        let result = context.mach_compile_result.as_ref().unwrap();
+        let func_relocs = result
+            .buffer
+            .relocs()
+            .iter()
+            .map(|r| mach_reloc_to_reloc(module, r))
+            .collect::<Vec<_>>();
+
+        let traps = result
+            .buffer
+            .traps()
+            .iter()
+            .map(mach_trap_to_trap)
+            .collect::<Vec<_>>();
+
+        let (unwind_info, eh_frame) = function_unwind_info(isa, target, &context, index)?;
+
+        let range = reader.range();
+        let address_map = get_function_address_map(&context, range, code_buf.len());
+
+        Ok((
+            CompiledFunction {
+                body: FunctionBody {
+                    body: code_buf,
+                    unwind_info,
+                },
+                relocations: func_relocs,
+                frame_info: CompiledFunctionFrameInfo { address_map, traps },
+            },
+            eh_frame,
+        ))
+    }
+}
+
+/// The unwind information of a function compiled on its own: Windows
+/// unwind information is kept in the function body, and DWARF one is
+/// written to an `.eh_frame` section with its own CIE.
+#[cfg_attr(not(feature = "unwind"), allow(unused_variables))]
+fn function_unwind_info(
+    isa: &dyn TargetIsa,
+    target: &Target,
+    context: &Context,
+    index: LocalFunctionIndex,
+) -> Result<(Option<CompiledFunctionUnwindInfo>, Option<CustomSection>), CompileError> {
+    Ok(match compiled_function_unwind_info(isa, context)? {
+        #[cfg(feature = "unwind")]
+        CraneliftUnwindInfo::Fde(fde) => {
+            let cie = match target.triple().default_calling_convention() {
+                Ok(CallingConvention::SystemV) => isa.create_systemv_cie(),
+                _ => None,
+            };
+            match cie {
+                Some(cie) => {
+                    let mut frame_table = FrameTable::default();
+                    let cie_id = frame_table.add_cie(cie);
+                    frame_table.add_fde(
+                        cie_id,
+                        fde.to_fde(Address::Symbol {
+                            symbol: WriterRelocate::FUNCTION_SYMBOL,
+                            addend: index.index() as _,
+                        }),
+                    );
+                    let mut eh_frame =
+                        EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
+                    frame_table.write_eh_frame(&mut eh_frame).unwrap();
+                    (
+                        Some(CompiledFunctionUnwindInfo::Dwarf),
+                        Some(eh_frame.0.into_section()),
+                    )
+                }
+                None => (None, None),
+            }
+        }
+        other => (other.maybe_into_to_windows_unwind(), None),
+    })
+}
+
+/// Translates the signatures of a module to Cranelift IR.
+fn module_signatures(
+    isa: &dyn TargetIsa,
+    module: &ModuleInfo,
+) -> PrimaryMap<SignatureIndex, ir::Signature> {
+    let frontend_config = isa.frontend_config();
+    module
+        .signatures
+        .iter()
+        .map(|(_sig_index, func_type)| signature_to_cranelift_ir(func_type, frontend_config))
+        .collect()
 }
 
 impl Compiler for CraneliftCompiler {
@@ -57,6 +193,101 @@ impl Compiler for CraneliftCompiler {
         &self.config.middlewares
     }
 
+    fn supports_lazy_compilation(&self) -> bool {
+        true
+    }
+
+    /// Compile a single function using Cranelift, producing the compiled
+    /// function with associated relocations and its own `.eh_frame`
+    /// section.
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        let isa = self
+            .config()
+            .isa(target)
+            .map_err(|error| CompileError::Codegen(error.to_string()))?;
+        let signatures = module_signatures(&*isa, &compile_info.module);
+        self.compile_single_function(
+            &*isa,
+            target,
+            compile_info,
+            module_translation_state,
+            &signatures,
+            &mut FuncTranslator::new(),
+            index,
+            input,
+        )
+    }
+
+    /// Compile the given functions using Cranelift, in parallel, each
+    /// with its own `.eh_frame` section.
+    fn compile_functions(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        functions: &[(LocalFunctionIndex, FunctionBodyData<'_>)],
+    ) -> Result<Vec<(CompiledFunction, Option<CustomSection>)>, CompileError> {
+        let isa = self
+            .config()
+            .isa(target)
+            .map_err(|error| CompileError::Codegen(error.to_string()))?;
+        let signatures = module_signatures(&*isa, &compile_info.module);
+
+        #[cfg(not(feature = "rayon"))]
+        let mut func_translator = FuncTranslator::new();
+        #[cfg(not(feature = "rayon"))]
+        let functions = functions
+            .iter()
+            .map(|(i, input)| {
+                self.compile_single_function(
+                    &*isa,
+                    target,
+                    compile_info,
+                    module_translation_state,
+                    &signatures,
+                    &mut func_translator,
+                    *i,
+                    input,
+                )
+            })
+            .collect();
+        #[cfg(feature = "rayon")]
+        let functions = functions
+            .par_iter()
+            .map_init(FuncTranslator::new, |func_translator, (i, input)| {
+                self.compile_single_function(
+                    &*isa,
+                    target,
+                    compile_info,
+                    module_translation_state,
+                    &signatures,
+                    func_translator,
+                    *i,
+                    input,
+                )
+            })
+            .collect();
+        functions
+    }
+
+    fn function_cache_fingerprint(&self) -> Option<String> {
+        if !self.config.middlewares.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "cranelift {} {:?}",
+            env!("CARGO_PKG_VERSION"),
+            self.config
+        ))
+    }
+
     /// Compile the module using Cranelift, producing a compilation result with
     /// associated relocations.
     fn compile_module(
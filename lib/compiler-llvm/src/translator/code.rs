@@ -1224,6 +1224,47 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
         self.builder.position_at_end(continue_block);
     }
 
+    /// Compute the arguments of the `memory.atomic.wait*`/`notify` libcalls:
+    /// the memory index they expect (local for local memories) and the
+    /// effective address. Addresses that don't fit in 32 bits are clamped
+    /// to `u32::MAX` so that the libcall reports the out of bounds access.
+    fn atomic_wait_notify_args(
+        &self,
+        memarg: &MemoryImmediate,
+        var_offset: IntValue<'ctx>,
+    ) -> (IntValue<'ctx>, IntValue<'ctx>) {
+        let memory_index = MemoryIndex::from_u32(memarg.memory);
+        let memory_index = match self.wasm_module.local_memory_index(memory_index) {
+            Some(local_memory_index) => local_memory_index.as_u32(),
+            None => memarg.memory,
+        };
+        let memory_index = self.intrinsics.i32_ty.const_int(memory_index as u64, false);
+        if memarg.offset == 0 {
+            return (memory_index, var_offset);
+        }
+
+        let imm_offset = self
+            .intrinsics
+            .i64_ty
+            .const_int(memarg.offset as u64, false);
+        let var_offset = self
+            .builder
+            .build_int_z_extend(var_offset, self.intrinsics.i64_ty, "");
+        let offset = self.builder.build_int_add(var_offset, imm_offset, "");
+        let max_offset = self.intrinsics.i64_ty.const_int(u32::MAX as u64, false);
+        let overflows = self
+            .builder
+            .build_int_compare(IntPredicate::UGT, offset, max_offset, "");
+        let offset = self
+            .builder
+            .build_select(overflows, max_offset, offset, "")
+            .into_int_value();
+        let offset = self
+            .builder
+            .build_int_truncate(offset, self.intrinsics.i32_ty, "");
+        (memory_index, offset)
+    }
+
     fn finalize(&mut self, wasm_fn_type: &FunctionType) -> Result<(), CompileError> {
         let func_type = self.function.get_type();
 
@@ -3246,7 +3287,11 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_and(v1, v2, "");
                 self.state.push1(res);
             }
-            Operator::V128Bitselect => {
+            Operator::V128Bitselect
+            | Operator::I8x16LaneSelect
+            | Operator::I16x8LaneSelect
+            | Operator::I32x4LaneSelect
+            | Operator::I64x2LaneSelect => {
                 let ((v1, i1), (v2, i2), (cond, cond_info)) = self.state.pop3_extra()?;
                 let v1 = self.apply_pending_canonicalization(v1, i1);
                 let v2 = self.apply_pending_canonicalization(v2, i2);
@@ -4275,6 +4320,102 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     (i1.strip_pending() & i2.strip_pending()) | ExtraInfo::pending_f32_nan(),
                 );
             }
+            Operator::F32x4Fma | Operator::F32x4Fms => {
+                // The relaxed multiply-adds are never fused, see `Features::relaxed_simd`.
+                let ((v1, i1), (v2, i2), (v3, i3)) = self.state.pop3_extra()?;
+                let (v1, i1) = self.v128_into_f32x4(v1, i1);
+                let (v2, i2) = self.v128_into_f32x4(v2, i2);
+                let (v3, i3) = self.v128_into_f32x4(v3, i3);
+                let product = self
+                    .builder
+                    .build_call(
+                        self.intrinsics.mul_f32x4,
+                        &[
+                            v1.into(),
+                            v2.into(),
+                            self.intrinsics.fp_rounding_md,
+                            self.intrinsics.fp_exception_md,
+                        ],
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                let (function, lhs, rhs) = match op {
+                    Operator::F32x4Fma => (self.intrinsics.add_f32x4, product, v3.into()),
+                    Operator::F32x4Fms => (self.intrinsics.sub_f32x4, v3.into(), product),
+                    _ => unreachable!("Unhandled internal variant"),
+                };
+                let res = self
+                    .builder
+                    .build_call(
+                        function,
+                        &[
+                            lhs.into(),
+                            rhs.into(),
+                            self.intrinsics.fp_rounding_md,
+                            self.intrinsics.fp_exception_md,
+                        ],
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
+                self.state.push1_extra(
+                    res,
+                    (i1.strip_pending() & i2.strip_pending() & i3.strip_pending())
+                        | ExtraInfo::pending_f32_nan(),
+                );
+            }
+            Operator::F64x2Fma | Operator::F64x2Fms => {
+                // The relaxed multiply-adds are never fused, see `Features::relaxed_simd`.
+                let ((v1, i1), (v2, i2), (v3, i3)) = self.state.pop3_extra()?;
+                let (v1, i1) = self.v128_into_f64x2(v1, i1);
+                let (v2, i2) = self.v128_into_f64x2(v2, i2);
+                let (v3, i3) = self.v128_into_f64x2(v3, i3);
+                let product = self
+                    .builder
+                    .build_call(
+                        self.intrinsics.mul_f64x2,
+                        &[
+                            v1.into(),
+                            v2.into(),
+                            self.intrinsics.fp_rounding_md,
+                            self.intrinsics.fp_exception_md,
+                        ],
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                let (function, lhs, rhs) = match op {
+                    Operator::F64x2Fma => (self.intrinsics.add_f64x2, product, v3.into()),
+                    Operator::F64x2Fms => (self.intrinsics.sub_f64x2, v3.into(), product),
+                    _ => unreachable!("Unhandled internal variant"),
+                };
+                let res = self
+                    .builder
+                    .build_call(
+                        function,
+                        &[
+                            lhs.into(),
+                            rhs.into(),
+                            self.intrinsics.fp_rounding_md,
+                            self.intrinsics.fp_exception_md,
+                        ],
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
+                self.state.push1_extra(
+                    res,
+                    (i1.strip_pending() & i2.strip_pending() & i3.strip_pending())
+                        | ExtraInfo::pending_f64_nan(),
+                );
+            }
             Operator::F64x2Mul => {
                 let ((v1, i1), (v2, i2)) = self.state.pop2_extra()?;
                 let (v1, i1) = self.v128_into_f64x2(v1, i1);
@@ -4650,7 +4791,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
                 self.state.push1(res);
             }
-            Operator::F32x4Min => {
+            Operator::F32x4Min | Operator::F32x4RelaxedMin => {
                 // This implements the same logic as LLVM's @llvm.minimum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -4776,7 +4917,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
                 self.state.push1(res);
             }
-            Operator::F64x2Min => {
+            Operator::F64x2Min | Operator::F64x2RelaxedMin => {
                 // This implements the same logic as LLVM's @llvm.minimum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -5120,7 +5261,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
                 self.state.push1(res);
             }
-            Operator::F32x4Max => {
+            Operator::F32x4Max | Operator::F32x4RelaxedMax => {
                 // This implements the same logic as LLVM's @llvm.maximum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -5247,7 +5388,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
                 self.state.push1(res);
             }
-            Operator::F64x2Max => {
+            Operator::F64x2Max | Operator::F64x2RelaxedMax => {
                 // This implements the same logic as LLVM's @llvm.maximum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -7037,7 +7178,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
                 self.state.push1(res);
             }
-            Operator::I32x4TruncSatF32x4S => {
+            Operator::I32x4TruncSatF32x4S | Operator::I32x4RelaxedTruncSatF32x4S => {
                 let (v, i) = self.state.pop1_extra()?;
                 let v = self.apply_pending_canonicalization(v, i);
                 let v = v.into_int_value();
@@ -7052,7 +7193,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
                 self.state.push1(res);
             }
-            Operator::I32x4TruncSatF32x4U => {
+            Operator::I32x4TruncSatF32x4U | Operator::I32x4RelaxedTruncSatF32x4U => {
                 let (v, i) = self.state.pop1_extra()?;
                 let v = self.apply_pending_canonicalization(v, i);
                 let v = v.into_int_value();
@@ -7067,13 +7208,18 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
                 self.state.push1(res);
             }
-            Operator::I32x4TruncSatF64x2SZero | Operator::I32x4TruncSatF64x2UZero => {
+            Operator::I32x4TruncSatF64x2SZero
+            | Operator::I32x4TruncSatF64x2UZero
+            | Operator::I32x4RelaxedTruncSatF64x2SZero
+            | Operator::I32x4RelaxedTruncSatF64x2UZero => {
                 let ((min, max), (cmp_min, cmp_max)) = match op {
-                    Operator::I32x4TruncSatF64x2SZero => (
+                    Operator::I32x4TruncSatF64x2SZero
+                    | Operator::I32x4RelaxedTruncSatF64x2SZero => (
                         (std::i32::MIN as u64, std::i32::MAX as u64),
                         (LEF64_GEQ_I32_MIN, GEF64_LEQ_I32_MAX),
                     ),
-                    Operator::I32x4TruncSatF64x2UZero => (
+                    Operator::I32x4TruncSatF64x2UZero
+                    | Operator::I32x4RelaxedTruncSatF64x2UZero => (
                         (std::u32::MIN as u64, std::u32::MAX as u64),
                         (LEF64_GEQ_U32_MIN, GEF64_LEQ_U32_MAX),
                     ),
@@ -8596,7 +8742,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 };
                 self.state.push1_extra(res, info);
             }
-            Operator::I8x16Swizzle => {
+            Operator::I8x16Swizzle | Operator::I8x16RelaxedSwizzle => {
                 let ((v1, i1), (v2, i2)) = self.state.pop2_extra()?;
                 let v1 = self.apply_pending_canonicalization(v1, i1);
                 let v1 = self
@@ -11236,6 +11382,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::MemoryAtomicWait32 { memarg } => {
                 let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let (dst, val, timeout) = self.state.pop3()?;
+                let (index, dst) = self.atomic_wait_notify_args(&memarg, dst.into_int_value());
                 let wait32_fn_ptr = self.ctx.memory_wait32(memory_index, self.intrinsics);
                 let callable_func =
                     inkwell::values::CallableValue::try_from(wait32_fn_ptr).unwrap();
@@ -11243,10 +11390,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     callable_func,
                     &[
                         vmctx.as_basic_value_enum().into(),
-                        self.intrinsics
-                            .i32_ty
-                            .const_int(memarg.memory as u64, false)
-                            .into(),
+                        index.into(),
                         dst.into(),
                         val.into(),
                         timeout.into(),
@@ -11258,6 +11402,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::MemoryAtomicWait64 { memarg } => {
                 let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let (dst, val, timeout) = self.state.pop3()?;
+                let (index, dst) = self.atomic_wait_notify_args(&memarg, dst.into_int_value());
                 let wait64_fn_ptr = self.ctx.memory_wait64(memory_index, self.intrinsics);
                 let callable_func =
                     inkwell::values::CallableValue::try_from(wait64_fn_ptr).unwrap();
@@ -11265,10 +11410,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     callable_func,
                     &[
                         vmctx.as_basic_value_enum().into(),
-                        self.intrinsics
-                            .i32_ty
-                            .const_int(memarg.memory as u64, false)
-                            .into(),
+                        index.into(),
                         dst.into(),
                         val.into(),
                         timeout.into(),
@@ -11280,6 +11422,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::MemoryAtomicNotify { memarg } => {
                 let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let (dst, count) = self.state.pop2()?;
+                let (index, dst) = self.atomic_wait_notify_args(&memarg, dst.into_int_value());
                 let notify_fn_ptr = self.ctx.memory_notify(memory_index, self.intrinsics);
                 let callable_func =
                     inkwell::values::CallableValue::try_from(notify_fn_ptr).unwrap();
@@ -11287,10 +11430,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     callable_func,
                     &[
                         vmctx.as_basic_value_enum().into(),
-                        self.intrinsics
-                            .i32_ty
-                            .const_int(memarg.memory as u64, false)
-                            .into(),
+                        index.into(),
                         dst.into(),
                         count.into(),
                     ],
@@ -1252,6 +1252,9 @@ pub struct CtxType<'ctx, 'a> {
     cached_functions: HashMap<FunctionIndex, FunctionCache<'ctx>>,
     cached_memory_grow: HashMap<MemoryIndex, PointerValue<'ctx>>,
     cached_memory_size: HashMap<MemoryIndex, PointerValue<'ctx>>,
+    cached_memory_wait32: HashMap<MemoryIndex, PointerValue<'ctx>>,
+    cached_memory_wait64: HashMap<MemoryIndex, PointerValue<'ctx>>,
+    cached_memory_notify: HashMap<MemoryIndex, PointerValue<'ctx>>,
 
     offsets: VMOffsets,
 }
@@ -1277,6 +1280,9 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
             cached_functions: HashMap::new(),
             cached_memory_grow: HashMap::new(),
             cached_memory_size: HashMap::new(),
+            cached_memory_wait32: HashMap::new(),
+            cached_memory_wait64: HashMap::new(),
+            cached_memory_notify: HashMap::new(),
 
             // TODO: pointer width
             offsets: VMOffsets::new(8, wasm_module),
@@ -1817,14 +1823,14 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
         memory_index: MemoryIndex,
         intrinsics: &Intrinsics<'ctx>,
     ) -> PointerValue<'ctx> {
-        let (cached_memory_size, wasm_module, offsets, cache_builder, ctx_ptr_value) = (
-            &mut self.cached_memory_size,
+        let (cached_memory_wait32, wasm_module, offsets, cache_builder, ctx_ptr_value) = (
+            &mut self.cached_memory_wait32,
             &self.wasm_module,
             &self.offsets,
             &self.cache_builder,
             &self.ctx_ptr_value,
         );
-        *cached_memory_size.entry(memory_index).or_insert_with(|| {
+        *cached_memory_wait32.entry(memory_index).or_insert_with(|| {
             let (size_fn, size_fn_ty) = if wasm_module.local_memory_index(memory_index).is_some() {
                 (
                     VMBuiltinFunctionIndex::get_memory_atomic_wait32_index(),
@@ -1859,14 +1865,14 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
         memory_index: MemoryIndex,
         intrinsics: &Intrinsics<'ctx>,
     ) -> PointerValue<'ctx> {
-        let (cached_memory_size, wasm_module, offsets, cache_builder, ctx_ptr_value) = (
-            &mut self.cached_memory_size,
+        let (cached_memory_wait64, wasm_module, offsets, cache_builder, ctx_ptr_value) = (
+            &mut self.cached_memory_wait64,
             &self.wasm_module,
             &self.offsets,
             &self.cache_builder,
             &self.ctx_ptr_value,
         );
-        *cached_memory_size.entry(memory_index).or_insert_with(|| {
+        *cached_memory_wait64.entry(memory_index).or_insert_with(|| {
             let (size_fn, size_fn_ty) = if wasm_module.local_memory_index(memory_index).is_some() {
                 (
                     VMBuiltinFunctionIndex::get_memory_atomic_wait64_index(),
@@ -1901,14 +1907,14 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
         memory_index: MemoryIndex,
         intrinsics: &Intrinsics<'ctx>,
     ) -> PointerValue<'ctx> {
-        let (cached_memory_size, wasm_module, offsets, cache_builder, ctx_ptr_value) = (
-            &mut self.cached_memory_size,
+        let (cached_memory_notify, wasm_module, offsets, cache_builder, ctx_ptr_value) = (
+            &mut self.cached_memory_notify,
             &self.wasm_module,
             &self.offsets,
             &self.cache_builder,
             &self.ctx_ptr_value,
         );
-        *cached_memory_size.entry(memory_index).or_insert_with(|| {
+        *cached_memory_notify.entry(memory_index).or_insert_with(|| {
             let (size_fn, size_fn_ty) = if wasm_module.local_memory_index(memory_index).is_some() {
                 (
                     VMBuiltinFunctionIndex::get_memory_atomic_notify_index(),
@@ -411,6 +411,29 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         Ok(())
     }
 
+    /// Add the static offset of a `memory.atomic.wait*`/`notify` to its
+    /// address operand, trapping if the effective address overflows.
+    fn emit_atomic_wait_notify_addr(
+        &mut self,
+        offset: u32,
+        addr: Location<M::GPR, M::SIMD>,
+    ) -> Result<Location<M::GPR, M::SIMD>, CompileError> {
+        if offset == 0 {
+            return Ok(addr);
+        }
+        if let Location::Imm32(addr) = addr {
+            // An address that overflows is reported out of bounds by the libcall.
+            return Ok(Location::Imm32(
+                addr.checked_add(offset).unwrap_or(u32::MAX),
+            ));
+        }
+        self.machine
+            .location_add(Size::S32, Location::Imm32(offset), addr, true)?;
+        self.machine
+            .jmp_on_overflow(self.special_labels.heap_access_oob)?;
+        Ok(addr)
+    }
+
     fn release_locations_only_regs(
         &mut self,
         locs: &[Location<M::GPR, M::SIMD>],
@@ -6421,19 +6444,20 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let timeout = self.value_stack.pop().unwrap();
                 let val = self.value_stack.pop().unwrap();
                 let dst = self.value_stack.pop().unwrap();
+                let addr = self.emit_atomic_wait_notify_addr(memarg.offset as u32, dst)?;
                 self.release_locations_only_regs(&[timeout, val, dst])?;
 
                 let memory_index = MemoryIndex::new(memarg.memory as usize);
                 let (memory_atomic_wait32, memory_index) =
-                    if self.module.local_memory_index(memory_index).is_some() {
+                    if let Some(local_index) = self.module.local_memory_index(memory_index) {
                         (
                             VMBuiltinFunctionIndex::get_memory_atomic_wait32_index(),
-                            memory_index,
+                            local_index.as_u32(),
                         )
                     } else {
                         (
                             VMBuiltinFunctionIndex::get_imported_memory_atomic_wait32_index(),
-                            memory_index,
+                            memory_index.as_u32(),
                         )
                     };
 
@@ -6455,14 +6479,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                             .emit_call_register(this.machine.get_grp_for_call())
                     },
                     // [vmctx, memory_index, dst, src, timeout]
-                    [
-                        Location::Imm32(memory_index.index() as u32),
-                        dst,
-                        val,
-                        timeout,
-                    ]
-                    .iter()
-                    .cloned(),
+                    [Location::Imm32(memory_index), addr, val, timeout]
+                        .iter()
+                        .cloned(),
                     [WpType::I32, WpType::I32, WpType::I32, WpType::I64]
                         .iter()
                         .cloned(),
@@ -6483,19 +6502,20 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let timeout = self.value_stack.pop().unwrap();
                 let val = self.value_stack.pop().unwrap();
                 let dst = self.value_stack.pop().unwrap();
+                let addr = self.emit_atomic_wait_notify_addr(memarg.offset as u32, dst)?;
                 self.release_locations_only_regs(&[timeout, val, dst])?;
 
                 let memory_index = MemoryIndex::new(memarg.memory as usize);
                 let (memory_atomic_wait64, memory_index) =
-                    if self.module.local_memory_index(memory_index).is_some() {
+                    if let Some(local_index) = self.module.local_memory_index(memory_index) {
                         (
                             VMBuiltinFunctionIndex::get_memory_atomic_wait64_index(),
-                            memory_index,
+                            local_index.as_u32(),
                         )
                     } else {
                         (
                             VMBuiltinFunctionIndex::get_imported_memory_atomic_wait64_index(),
-                            memory_index,
+                            memory_index.as_u32(),
                         )
                     };
 
@@ -6517,14 +6537,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                             .emit_call_register(this.machine.get_grp_for_call())
                     },
                     // [vmctx, memory_index, dst, src, timeout]
-                    [
-                        Location::Imm32(memory_index.index() as u32),
-                        dst,
-                        val,
-                        timeout,
-                    ]
-                    .iter()
-                    .cloned(),
+                    [Location::Imm32(memory_index), addr, val, timeout]
+                        .iter()
+                        .cloned(),
                     [WpType::I32, WpType::I32, WpType::I64, WpType::I64]
                         .iter()
                         .cloned(),
@@ -6544,19 +6559,20 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             Operator::MemoryAtomicNotify { ref memarg } => {
                 let cnt = self.value_stack.pop().unwrap();
                 let dst = self.value_stack.pop().unwrap();
+                let addr = self.emit_atomic_wait_notify_addr(memarg.offset as u32, dst)?;
                 self.release_locations_only_regs(&[cnt, dst])?;
 
                 let memory_index = MemoryIndex::new(memarg.memory as usize);
                 let (memory_atomic_notify, memory_index) =
-                    if self.module.local_memory_index(memory_index).is_some() {
+                    if let Some(local_index) = self.module.local_memory_index(memory_index) {
                         (
                             VMBuiltinFunctionIndex::get_memory_atomic_notify_index(),
-                            memory_index,
+                            local_index.as_u32(),
                         )
                     } else {
                         (
                             VMBuiltinFunctionIndex::get_imported_memory_atomic_notify_index(),
-                            memory_index,
+                            memory_index.as_u32(),
                         )
                     };
 
@@ -6577,11 +6593,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                         this.machine
                             .emit_call_register(this.machine.get_grp_for_call())
                     },
-                    // [vmctx, memory_index, dst, src, timeout]
-                    [Location::Imm32(memory_index.index() as u32), dst]
-                        .iter()
-                        .cloned(),
-                    [WpType::I32, WpType::I32].iter().cloned(),
+                    // [vmctx, memory_index, dst, cnt]
+                    [Location::Imm32(memory_index), addr, cnt].iter().cloned(),
+                    [WpType::I32, WpType::I32, WpType::I32].iter().cloned(),
                 )?;
                 self.release_locations_only_stack(&[dst, cnt])?;
                 let ret = self.acquire_locations(
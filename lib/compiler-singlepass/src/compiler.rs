@@ -13,7 +13,8 @@ use crate::machine::{
 use crate::machine_arm64::MachineARM64;
 use crate::machine_x64::MachineX86_64;
 #[cfg(feature = "unwind")]
-use crate::unwind::{create_systemv_cie, UnwindFrame};
+use crate::unwind::create_systemv_cie;
+use crate::unwind::UnwindFrame;
 use enumset::EnumSet;
 #[cfg(feature = "unwind")]
 use gimli::write::{EhFrame, FrameTable};
@@ -27,7 +28,7 @@ use wasmer_compiler::{
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     Architecture, CallingConvention, Compilation, CompileError, CompileModuleInfo,
-    CompiledFunction, CpuFeature, Dwarf, FunctionBody, FunctionIndex, FunctionType,
+    CompiledFunction, CpuFeature, CustomSection, Dwarf, FunctionBody, FunctionIndex, FunctionType,
     LocalFunctionIndex, MemoryIndex, ModuleInfo, OperatingSystem, SectionIndex, TableIndex, Target,
     TrapCode, TrapInformation, VMOffsets,
 };
@@ -48,6 +49,131 @@ impl SinglepassCompiler {
     fn config(&self) -> &Singlepass {
         &self.config
     }
+
+    /// Compiles the body of a single local function.
+    fn compile_function_body(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        vmoffsets: &VMOffsets,
+        calling_convention: CallingConvention,
+        i: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<(CompiledFunction, Option<UnwindFrame>), CompileError> {
+        let memory_styles = &compile_info.memory_styles;
+        let table_styles = &compile_info.table_styles;
+        let module = &compile_info.module;
+        let middleware_chain = self
+            .config
+            .middlewares
+            .generate_function_middleware_chain(i);
+        let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+        reader.set_middleware_chain(middleware_chain);
+
+        // This local list excludes arguments.
+        let mut locals = vec![];
+        let num_locals = reader.read_local_count()?;
+        for _ in 0..num_locals {
+            let (count, ty) = reader.read_local_decl()?;
+            for _ in 0..count {
+                locals.push(ty);
+            }
+        }
+
+        match target.triple().architecture {
+            Architecture::X86_64 => {
+                let machine = MachineX86_64::new(Some(target.clone()))?;
+                let mut generator = FuncGen::new(
+                    module,
+                    &self.config,
+                    vmoffsets,
+                    memory_styles,
+                    table_styles,
+                    i,
+                    &locals,
+                    machine,
+                    calling_convention,
+                )?;
+                while generator.has_control_frames() {
+                    generator.set_srcloc(reader.original_position() as u32);
+                    let op = reader.read_operator()?;
+                    generator.feed_operator(op)?;
+                }
+
+                generator.finalize(input)
+            }
+            Architecture::Aarch64(_) => {
+                let machine = MachineARM64::new();
+                let mut generator = FuncGen::new(
+                    module,
+                    &self.config,
+                    vmoffsets,
+                    memory_styles,
+                    table_styles,
+                    i,
+                    &locals,
+                    machine,
+                    calling_convention,
+                )?;
+                while generator.has_control_frames() {
+                    generator.set_srcloc(reader.original_position() as u32);
+                    let op = reader.read_operator()?;
+                    generator.feed_operator(op)?;
+                }
+
+                generator.finalize(input)
+            }
+            _ => unimplemented!(),
+        }
+    }
+}
+
+/// Writes the unwind information of a single function to its own
+/// `.eh_frame` section, so it can be cached along with the function.
+#[cfg(feature = "unwind")]
+fn function_eh_frame(target: &Target, unwind_frame: Option<UnwindFrame>) -> Option<CustomSection> {
+    let cie = match target.triple().default_calling_convention() {
+        Ok(CallingConvention::SystemV) => create_systemv_cie(target.triple().architecture)?,
+        _ => return None,
+    };
+    let mut dwarf_frametable = FrameTable::default();
+    let cie_id = dwarf_frametable.add_cie(cie);
+    match unwind_frame? {
+        UnwindFrame::SystemV(fde) => dwarf_frametable.add_fde(cie_id, fde),
+    }
+    let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
+    dwarf_frametable.write_eh_frame(&mut eh_frame).unwrap();
+    Some(eh_frame.0.into_section())
+}
+
+#[cfg(not(feature = "unwind"))]
+fn function_eh_frame(
+    _target: &Target,
+    _unwind_frame: Option<UnwindFrame>,
+) -> Option<CustomSection> {
+    None
+}
+
+/// Gets the calling convention used by Singlepass for the given target.
+fn calling_convention_for_target(target: &Target) -> Result<CallingConvention, CompileError> {
+    match target.triple().architecture {
+        Architecture::X86_64 => {}
+        Architecture::Aarch64(_) => {}
+        _ => {
+            return Err(CompileError::UnsupportedTarget(
+                target.triple().architecture.to_string(),
+            ))
+        }
+    }
+
+    match target.triple().default_calling_convention() {
+        Ok(CallingConvention::WindowsFastcall) => Ok(CallingConvention::WindowsFastcall),
+        Ok(CallingConvention::SystemV) => Ok(CallingConvention::SystemV),
+        Ok(CallingConvention::AppleAarch64) => Ok(CallingConvention::AppleAarch64),
+        _ => Err(CompileError::UnsupportedTarget(
+            "Unsupported Calling convention for Singlepass compiler".to_string(),
+        )),
+    }
 }
 
 impl Compiler for SinglepassCompiler {
@@ -56,6 +182,77 @@ impl Compiler for SinglepassCompiler {
         &self.config.middlewares
     }
 
+    fn supports_lazy_compilation(&self) -> bool {
+        true
+    }
+
+    /// Compile a single function using Singlepass, producing the compiled
+    /// function with associated relocations and its own `.eh_frame`
+    /// section.
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        let calling_convention = calling_convention_for_target(target)?;
+        let vmoffsets = VMOffsets::new(8, &compile_info.module);
+        let (function, unwind_frame) = self.compile_function_body(
+            target,
+            compile_info,
+            &vmoffsets,
+            calling_convention,
+            index,
+            input,
+        )?;
+        let eh_frame = function_eh_frame(target, unwind_frame);
+        Ok((function, eh_frame))
+    }
+
+    /// Compile the given functions using Singlepass, in parallel.
+    fn compile_functions(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        functions: &[(LocalFunctionIndex, FunctionBodyData<'_>)],
+    ) -> Result<Vec<(CompiledFunction, Option<CustomSection>)>, CompileError> {
+        let calling_convention = calling_convention_for_target(target)?;
+        let vmoffsets = VMOffsets::new(8, &compile_info.module);
+        functions
+            .iter()
+            .collect::<Vec<_>>()
+            .into_par_iter_if_rayon()
+            .map(|(i, input)| {
+                self.compile_function_body(
+                    target,
+                    compile_info,
+                    &vmoffsets,
+                    calling_convention,
+                    *i,
+                    input,
+                )
+                .map(|(function, unwind_frame)| {
+                    let eh_frame = function_eh_frame(target, unwind_frame);
+                    (function, eh_frame)
+                })
+            })
+            .collect()
+    }
+
+    fn function_cache_fingerprint(&self) -> Option<String> {
+        if !self.config.middlewares.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "singlepass {} {:?}",
+            env!("CARGO_PKG_VERSION"),
+            self.config
+        ))
+    }
+
     /// Compile the module using Singlepass, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -65,26 +262,7 @@ impl Compiler for SinglepassCompiler {
         _module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
     ) -> Result<Compilation, CompileError> {
-        match target.triple().architecture {
-            Architecture::X86_64 => {}
-            Architecture::Aarch64(_) => {}
-            _ => {
-                return Err(CompileError::UnsupportedTarget(
-                    target.triple().architecture.to_string(),
-                ))
-            }
-        }
-
-        let calling_convention = match target.triple().default_calling_convention() {
-            Ok(CallingConvention::WindowsFastcall) => CallingConvention::WindowsFastcall,
-            Ok(CallingConvention::SystemV) => CallingConvention::SystemV,
-            Ok(CallingConvention::AppleAarch64) => CallingConvention::AppleAarch64,
-            _ => {
-                return Err(CompileError::UnsupportedTarget(
-                    "Unsupported Calling convention for Singlepass compiler".to_string(),
-                ))
-            }
-        };
+        let calling_convention = calling_convention_for_target(target)?;
 
         // Generate the frametable
         #[cfg(feature = "unwind")]
@@ -109,8 +287,6 @@ impl Compiler for SinglepassCompiler {
             }
         };
 
-        let memory_styles = &compile_info.memory_styles;
-        let table_styles = &compile_info.table_styles;
         let vmoffsets = VMOffsets::new(8, &compile_info.module);
         let module = &compile_info.module;
         let mut custom_sections: PrimaryMap<SectionIndex, _> = (0..module.num_imported_functions)
@@ -135,69 +311,14 @@ impl Compiler for SinglepassCompiler {
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .into_par_iter_if_rayon()
             .map(|(i, input)| {
-                let middleware_chain = self
-                    .config
-                    .middlewares
-                    .generate_function_middleware_chain(i);
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(middleware_chain);
-
-                // This local list excludes arguments.
-                let mut locals = vec![];
-                let num_locals = reader.read_local_count()?;
-                for _ in 0..num_locals {
-                    let (count, ty) = reader.read_local_decl()?;
-                    for _ in 0..count {
-                        locals.push(ty);
-                    }
-                }
-
-                match target.triple().architecture {
-                    Architecture::X86_64 => {
-                        let machine = MachineX86_64::new(Some(target.clone()))?;
-                        let mut generator = FuncGen::new(
-                            module,
-                            &self.config,
-                            &vmoffsets,
-                            memory_styles,
-                            table_styles,
-                            i,
-                            &locals,
-                            machine,
-                            calling_convention,
-                        )?;
-                        while generator.has_control_frames() {
-                            generator.set_srcloc(reader.original_position() as u32);
-                            let op = reader.read_operator()?;
-                            generator.feed_operator(op)?;
-                        }
-
-                        generator.finalize(input)
-                    }
-                    Architecture::Aarch64(_) => {
-                        let machine = MachineARM64::new();
-                        let mut generator = FuncGen::new(
-                            module,
-                            &self.config,
-                            &vmoffsets,
-                            memory_styles,
-                            table_styles,
-                            i,
-                            &locals,
-                            machine,
-                            calling_convention,
-                        )?;
-                        while generator.has_control_frames() {
-                            generator.set_srcloc(reader.original_position() as u32);
-                            let op = reader.read_operator()?;
-                            generator.feed_operator(op)?;
-                        }
-
-                        generator.finalize(input)
-                    }
-                    _ => unimplemented!(),
-                }
+                self.compile_function_body(
+                    target,
+                    compile_info,
+                    &vmoffsets,
+                    calling_convention,
+                    i,
+                    input,
+                )
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
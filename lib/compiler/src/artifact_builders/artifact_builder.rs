@@ -1,19 +1,32 @@
 //! Define `ArtifactBuild` to allow compiling and instantiating to be
 //! done as separate steps.
 
+#[cfg(feature = "compiler")]
+use super::function_cache::{
+    merge_eh_frames, relocate_eh_frame, relocate_source_locations, FunctionCache, FunctionCacheKeys,
+};
+use super::lazy::LazyFunctionInputs;
+#[cfg(feature = "compiler")]
+use super::lazy::{
+    lazy_compilation_supported, make_lazy_function_table, make_lazy_resolver, make_lazy_stub,
+    OwnedFunctionBodyData,
+};
 #[cfg(feature = "compiler")]
 use super::trampoline::{libcall_trampoline_len, make_libcall_trampolines};
 use crate::ArtifactCreate;
 use crate::EngineInner;
 use crate::Features;
+#[cfg(feature = "compiler")]
+use crate::{Compiler, FunctionBodyData, ModuleTranslationState};
 use crate::{ModuleEnvironment, ModuleMiddlewareChain};
 use enumset::EnumSet;
 use std::mem;
 use wasmer_types::entity::PrimaryMap;
-#[cfg(feature = "compiler")]
 use wasmer_types::CompileModuleInfo;
 use wasmer_types::MetadataHeader;
 use wasmer_types::SerializeError;
+#[cfg(feature = "compiler")]
+use wasmer_types::{Compilation, SerializableFunction};
 use wasmer_types::{
     CompileError, CpuFeature, CustomSection, Dwarf, FunctionIndex, LocalFunctionIndex, MemoryIndex,
     MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex, SignatureIndex,
@@ -26,6 +39,7 @@ use wasmer_types::{
 /// A compiled wasm module, ready to be instantiated.
 pub struct ArtifactBuild {
     serializable: SerializableModule,
+    lazy_inputs: Option<LazyFunctionInputs>,
 }
 
 impl ArtifactBuild {
@@ -65,16 +79,48 @@ impl ArtifactBuild {
             table_styles,
         };
 
-        // Compile the Module
-        let compilation = compiler.compile_module(
-            target,
-            &compile_info,
-            // SAFETY: Calling `unwrap` is correct since
-            // `environ.translate()` above will write some data into
-            // `module_translation_state`.
-            translation.module_translation_state.as_ref().unwrap(),
-            translation.function_body_inputs,
-        )?;
+        // SAFETY: Calling `unwrap` is correct since
+        // `environ.translate()` above will write some data into
+        // `module_translation_state`.
+        let module_translation = translation.module_translation_state.unwrap();
+
+        // When compiling lazily, only the trampolines and custom sections
+        // are compiled upfront, and the function bodies are kept around.
+        let lazy = inner_engine.lazy_compilation()
+            && lazy_compilation_supported(target)
+            && compiler.supports_lazy_compilation();
+        let (function_body_inputs, lazy_function_bodies) = if lazy {
+            let function_bodies = translation
+                .function_body_inputs
+                .values()
+                .map(OwnedFunctionBodyData::new)
+                .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+            (PrimaryMap::new(), Some(function_bodies))
+        } else {
+            (translation.function_body_inputs, None)
+        };
+
+        // Compile the Module, reusing the cached functions if possible
+        let compilation = match (
+            inner_engine.function_cache(),
+            compiler.function_cache_fingerprint(),
+        ) {
+            (Some(function_cache), Some(fingerprint)) if !lazy => Self::compile_with_cache(
+                compiler,
+                target,
+                &compile_info,
+                &module_translation,
+                function_body_inputs,
+                &**function_cache,
+                &fingerprint,
+            )?,
+            _ => compiler.compile_module(
+                target,
+                &compile_info,
+                &module_translation,
+                function_body_inputs,
+            )?,
+        };
 
         let data_initializers = translation
             .data_initializers
@@ -104,6 +150,27 @@ impl ArtifactBuild {
         let libcall_trampoline_len = libcall_trampoline_len(target) as u32;
         let cpu_features = compiler.get_cpu_features_used(target.cpu_features());
 
+        // Replace every function by a stub that compiles it on its first call.
+        let lazy_inputs = lazy_function_bodies.map(|lazy_function_bodies| {
+            let table_section = make_lazy_function_table(target, lazy_function_bodies.len());
+            custom_section_relocations.push(table_section.relocations.clone());
+            let table = custom_sections.push(table_section);
+            let resolver_section = make_lazy_resolver(target, table);
+            custom_section_relocations.push(resolver_section.relocations.clone());
+            let resolver = custom_sections.push(resolver_section);
+            for index in lazy_function_bodies.keys() {
+                let (body, relocations) = make_lazy_stub(target, index, table, resolver);
+                function_bodies.push(body);
+                function_relocations.push(relocations);
+                function_frame_info.push(CompiledFunctionFrameInfo::default());
+            }
+            LazyFunctionInputs {
+                module_translation,
+                function_bodies: lazy_function_bodies,
+                table,
+            }
+        });
+
         let serializable_compilation = SerializableCompilation {
             function_bodies,
             function_relocations,
@@ -122,7 +189,92 @@ impl ArtifactBuild {
             data_initializers,
             cpu_features: cpu_features.as_u64(),
         };
-        Ok(Self { serializable })
+        Ok(Self {
+            serializable,
+            lazy_inputs,
+        })
+    }
+
+    /// Compile a module, loading the functions that were already compiled
+    /// from the function cache, and storing the others into it.
+    ///
+    /// The missing functions are compiled through
+    /// [`Compiler::compile_functions`]. The `.eh_frame` sections of all
+    /// the functions, cached or not, are merged into the unwind
+    /// information of the module.
+    #[cfg(feature = "compiler")]
+    fn compile_with_cache(
+        compiler: &dyn Compiler,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        function_cache: &dyn FunctionCache,
+        fingerprint: &str,
+    ) -> Result<Compilation, CompileError> {
+        let keys = FunctionCacheKeys::new(fingerprint, target, compile_info);
+        let mut functions = PrimaryMap::with_capacity(function_body_inputs.len());
+        let mut eh_frames = PrimaryMap::with_capacity(function_body_inputs.len());
+        let mut missing = vec![];
+        for (index, function_body) in function_body_inputs.iter() {
+            let key = keys.key(index, function_body);
+            let function = function_cache.load(&key).map(|cached| {
+                let mut function = cached.function;
+                relocate_source_locations(
+                    &mut function,
+                    cached.module_offset as usize,
+                    function_body.module_offset,
+                );
+                let mut eh_frame = cached.eh_frame;
+                if let Some(eh_frame) = eh_frame.as_mut() {
+                    relocate_eh_frame(eh_frame, index);
+                }
+                (function, eh_frame)
+            });
+            let (function, eh_frame) = match function {
+                Some((function, eh_frame)) => (Some(function), eh_frame),
+                None => (None, None),
+            };
+            eh_frames.push(eh_frame);
+            if function.is_none() {
+                let function_body = FunctionBodyData {
+                    data: function_body.data,
+                    module_offset: function_body.module_offset,
+                };
+                missing.push((index, function_body));
+            }
+            functions.push(function);
+        }
+
+        let compiled =
+            compiler.compile_functions(target, compile_info, module_translation, &missing)?;
+        for ((index, function_body), (function, eh_frame)) in missing.iter().zip(compiled) {
+            let cached = SerializableFunction {
+                function,
+                module_offset: function_body.module_offset as u64,
+                eh_frame,
+            };
+            // A function that can't be stored is just compiled again the
+            // next time, so errors are ignored.
+            let _ = function_cache.store(&keys.key(*index, function_body), &cached);
+            functions[*index] = Some(cached.function);
+            eh_frames[*index] = cached.eh_frame;
+        }
+
+        // The trampolines and custom sections are compiled as usual, without
+        // the function bodies.
+        let mut compilation =
+            compiler.compile_module(target, compile_info, module_translation, PrimaryMap::new())?;
+        compilation.functions = functions
+            .into_iter()
+            .map(|(_, function)| function.expect("all the functions are compiled"))
+            .collect();
+        if compilation.debug.is_none() && eh_frames.values().any(Option::is_some) {
+            let eh_frame = merge_eh_frames(eh_frames.values().flatten());
+            let index = compilation.custom_sections.push(eh_frame);
+            compilation.debug = Some(Dwarf::new(index));
+        }
+        Ok(compilation)
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
@@ -142,7 +294,25 @@ impl ArtifactBuild {
 
     /// Create a new ArtifactBuild from a SerializableModule
     pub fn from_serializable(serializable: SerializableModule) -> Self {
-        Self { serializable }
+        Self {
+            serializable,
+            lazy_inputs: None,
+        }
+    }
+
+    /// Whether the functions of this artifact are compiled on their first call.
+    pub fn is_lazy(&self) -> bool {
+        self.lazy_inputs.is_some()
+    }
+
+    /// Take the inputs needed to compile the functions of a lazy artifact.
+    pub(crate) fn take_lazy_inputs(&mut self) -> Option<LazyFunctionInputs> {
+        self.lazy_inputs.take()
+    }
+
+    /// Get Compile Info ref
+    pub fn get_compile_info_ref(&self) -> &CompileModuleInfo {
+        &self.serializable.compile_info
     }
 
     /// Get Functions Bodies ref
@@ -222,6 +392,11 @@ impl ArtifactCreate for ArtifactBuild {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        if self.is_lazy() {
+            return Err(SerializeError::Generic(
+                "Lazily compiled artifacts can not be serialized".to_string(),
+            ));
+        }
         let serialized_data = self.serializable.serialize()?;
         assert!(mem::align_of::<SerializableModule>() <= MetadataHeader::ALIGN);
 
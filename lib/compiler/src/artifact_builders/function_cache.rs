@@ -0,0 +1,325 @@
+//! A cache of compiled functions, so that recompiling a module after a
+//! small change only compiles the functions that changed.
+//!
+//! Functions are keyed by a hash of their body, their signature, the
+//! signatures of the functions and types they reference, the compiler
+//! fingerprint, the target and the layout of the module they're compiled
+//! against (the number of signatures and imports, the tables, memories,
+//! globals and their styles). Their index isn't part of the key, so a
+//! function moved within its module is still found in the cache.
+
+use crate::FunctionBodyData;
+use std::collections::BTreeSet;
+use std::fmt::{self, Write as _};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{
+    CompileModuleInfo, CompiledFunction, CustomSection, CustomSectionProtection, FunctionIndex,
+    LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionBody,
+    SerializableFunction, SerializeError, SignatureIndex, SourceLoc, Target,
+};
+
+/// A key identifying a compiled function in a [`FunctionCache`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FunctionCacheKey([u8; 32]);
+
+impl FunctionCacheKey {
+    /// Creates a new key from 32 raw bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// The raw bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for FunctionCacheKey {
+    /// Formats the key as hexadecimal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// A cache of compiled functions, used by the engine to skip compiling
+/// functions that were already compiled.
+///
+/// # Safety
+///
+/// The functions returned by [`FunctionCache::load`] are executed as
+/// native code without any validation, so they must be functions that
+/// were previously passed to [`FunctionCache::store`] with the same key.
+pub unsafe trait FunctionCache: Send + Sync {
+    /// Loads the function stored with the given key, if any.
+    fn load(&self, key: &FunctionCacheKey) -> Option<SerializableFunction>;
+
+    /// Stores a function with the given key.
+    fn store(
+        &self,
+        key: &FunctionCacheKey,
+        function: &SerializableFunction,
+    ) -> Result<(), SerializeError>;
+}
+
+/// A [`FunctionCache`] storing each function in its own file, in a
+/// directory of the file system.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileSystemFunctionCache {
+    path: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileSystemFunctionCache {
+    /// Creates a new function cache in the given directory, creating the
+    /// directory if it doesn't exist.
+    ///
+    /// # Safety
+    ///
+    /// Functions are loaded from the directory without validation, so it
+    /// must only be written to by a `FileSystemFunctionCache`.
+    pub unsafe fn new(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    /// The directory holding the cached functions.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe impl FunctionCache for FileSystemFunctionCache {
+    fn load(&self, key: &FunctionCacheKey) -> Option<SerializableFunction> {
+        let bytes = std::fs::read(self.path.join(key.to_string())).ok()?;
+        // SAFETY: the directory is only written to by `store`, as required
+        // by `FileSystemFunctionCache::new`.
+        unsafe { SerializableFunction::deserialize(&bytes).ok() }
+    }
+
+    fn store(
+        &self,
+        key: &FunctionCacheKey,
+        function: &SerializableFunction,
+    ) -> Result<(), SerializeError> {
+        // Tells apart the temporary files of the threads of a process.
+        static NEXT_TMP_FILE: AtomicUsize = AtomicUsize::new(0);
+
+        let bytes = function.serialize()?;
+        // Write to a temporary file first, so that concurrent compilations
+        // never load a partially written function. Every store writes its
+        // own temporary file, even when they store the same function.
+        let path = self.path.join(key.to_string());
+        let tmp_path = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            NEXT_TMP_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&tmp_path, bytes)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+/// Computes the cache keys of the local functions of a module.
+pub(crate) struct FunctionCacheKeys<'a> {
+    module: &'a ModuleInfo,
+    module_hasher: blake3::Hasher,
+}
+
+impl<'a> FunctionCacheKeys<'a> {
+    /// Hashes everything in the module all the compiled functions depend
+    /// on.
+    ///
+    /// This covers the layout of the `VMContext`, which only depends on
+    /// the number of signatures and on the imports, tables, memories and
+    /// globals. The functions and signatures are hashed along with the
+    /// functions referencing them.
+    pub(crate) fn new(
+        fingerprint: &str,
+        target: &Target,
+        compile_info: &'a CompileModuleInfo,
+    ) -> Self {
+        let module = &compile_info.module;
+        let environment = format!(
+            "wasmer-compiler {}\n{}\n{}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{} {} {} {} {}\n",
+            crate::VERSION,
+            fingerprint,
+            target.triple(),
+            target.cpu_features(),
+            compile_info.features,
+            compile_info.memory_styles,
+            compile_info.table_styles,
+            module.tables,
+            module.memories,
+            module.globals,
+            module.signatures.len(),
+            module.num_imported_functions,
+            module.num_imported_tables,
+            module.num_imported_memories,
+            module.num_imported_globals,
+        );
+        let mut module_hasher = blake3::Hasher::new();
+        module_hasher.update(environment.as_bytes());
+        Self {
+            module,
+            module_hasher,
+        }
+    }
+
+    /// The key of the given local function.
+    ///
+    /// It covers the body and the signature of the function, and the
+    /// signatures of the functions and types it references, but not its
+    /// index.
+    pub(crate) fn key(
+        &self,
+        index: LocalFunctionIndex,
+        function_body: &FunctionBodyData<'_>,
+    ) -> FunctionCacheKey {
+        let module = self.module;
+        let signature = module.functions[module.func_index(index)];
+        let (functions, signatures) = references(function_body.data);
+        let mut references = format!("{:?}\n", module.signatures.get(signature));
+        for function in functions {
+            let signature = module.functions.get(function);
+            let signature = signature.and_then(|signature| module.signatures.get(*signature));
+            writeln!(references, "function {} {:?}", function.index(), signature).unwrap();
+        }
+        for signature in signatures {
+            let index = signature.index();
+            let signature = module.signatures.get(signature);
+            writeln!(references, "signature {} {:?}", index, signature).unwrap();
+        }
+
+        let mut hasher = self.module_hasher.clone();
+        hasher.update(references.as_bytes());
+        hasher.update(&(function_body.data.len() as u64).to_le_bytes());
+        hasher.update(function_body.data);
+        FunctionCacheKey::new(hasher.finalize().into())
+    }
+}
+
+/// The functions and the signatures referenced by the body of a function,
+/// through calls, function references and block types.
+///
+/// The references are collected until the body fails to parse, in which
+/// case it fails to compile as well.
+fn references(body: &[u8]) -> (BTreeSet<FunctionIndex>, BTreeSet<SignatureIndex>) {
+    use wasmparser::{Operator, TypeOrFuncType};
+
+    let mut functions = BTreeSet::new();
+    let mut signatures = BTreeSet::new();
+    let mut body = wasmparser::FunctionBody::new(0, body);
+    body.allow_memarg64(true);
+    let mut reader = match body.get_operators_reader() {
+        Ok(reader) => reader,
+        Err(_) => return (functions, signatures),
+    };
+    while !reader.eof() {
+        match reader.read() {
+            Ok(Operator::Call { function_index })
+            | Ok(Operator::ReturnCall { function_index })
+            | Ok(Operator::RefFunc { function_index }) => {
+                functions.insert(FunctionIndex::from_u32(function_index));
+            }
+            Ok(Operator::CallIndirect { index, .. })
+            | Ok(Operator::ReturnCallIndirect { index, .. })
+            | Ok(Operator::Block {
+                ty: TypeOrFuncType::FuncType(index),
+            })
+            | Ok(Operator::Loop {
+                ty: TypeOrFuncType::FuncType(index),
+            })
+            | Ok(Operator::If {
+                ty: TypeOrFuncType::FuncType(index),
+            })
+            | Ok(Operator::Try {
+                ty: TypeOrFuncType::FuncType(index),
+            }) => {
+                signatures.insert(SignatureIndex::from_u32(index));
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    (functions, signatures)
+}
+
+/// Points the `.eh_frame` section of a cached function to the index of
+/// the function in the module being compiled, which may differ from its
+/// index in the module it was cached from.
+pub(crate) fn relocate_eh_frame(eh_frame: &mut CustomSection, index: LocalFunctionIndex) {
+    for relocation in eh_frame.relocations.iter_mut() {
+        if let RelocationTarget::LocalFunc(_) = relocation.reloc_target {
+            relocation.reloc_target = RelocationTarget::LocalFunc(index);
+        }
+    }
+}
+
+/// Moves the source locations of a cached function to the offset of its
+/// body in the module being compiled.
+pub(crate) fn relocate_source_locations(
+    function: &mut CompiledFunction,
+    from_module_offset: usize,
+    to_module_offset: usize,
+) {
+    if from_module_offset == to_module_offset {
+        return;
+    }
+    let relocate = |srcloc: &mut SourceLoc| {
+        if !srcloc.is_default() {
+            *srcloc = SourceLoc::new(
+                srcloc
+                    .bits()
+                    .wrapping_sub(from_module_offset as u32)
+                    .wrapping_add(to_module_offset as u32),
+            );
+        }
+    };
+    let address_map = &mut function.frame_info.address_map;
+    relocate(&mut address_map.start_srcloc);
+    relocate(&mut address_map.end_srcloc);
+    for instruction in address_map.instructions.iter_mut() {
+        relocate(&mut instruction.srcloc);
+    }
+}
+
+/// Concatenates the `.eh_frame` sections of single functions into the
+/// `.eh_frame` section of a module.
+///
+/// Every section holds its own CIE, which its FDE points to with an offset
+/// relative to the FDE, so they stay valid once concatenated. Only the
+/// zero length terminating each section is dropped, and written once at
+/// the end.
+pub(crate) fn merge_eh_frames<'a>(
+    eh_frames: impl IntoIterator<Item = &'a CustomSection>,
+) -> CustomSection {
+    let mut bytes = Vec::new();
+    let mut relocations = Vec::new();
+    for eh_frame in eh_frames {
+        let data = eh_frame.bytes.as_slice();
+        let data = &data[..data.len().saturating_sub(4)];
+        relocations.extend(eh_frame.relocations.iter().map(|relocation| Relocation {
+            offset: relocation.offset + bytes.len() as u32,
+            ..relocation.clone()
+        }));
+        bytes.extend_from_slice(data);
+    }
+    bytes.extend_from_slice(&0u32.to_ne_bytes());
+
+    CustomSection {
+        protection: CustomSectionProtection::Read,
+        bytes: SectionBody::new_with_vec(bytes),
+        relocations,
+    }
+}
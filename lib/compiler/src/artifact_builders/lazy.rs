@@ -0,0 +1,244 @@
+//! Stubs for lazily compiled functions.
+//!
+//! Every local function of a lazily compiled artifact is replaced by a
+//! stub that jumps through a slot of the lazy function table. Slots
+//! initially point back into the stub, to an entry that loads the function
+//! index and jumps to a shared resolver. The resolver saves the argument
+//! registers, calls the `LazyCompile` libcall, restores the registers and
+//! jumps to the freshly compiled body.
+
+use crate::FunctionBodyData;
+use crate::ModuleTranslationState;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    Architecture, CallingConvention, CustomSection, CustomSectionProtection, FunctionBody, LibCall,
+    LocalFunctionIndex, Relocation, RelocationKind, RelocationTarget, SectionBody, SectionIndex,
+    Target,
+};
+
+/// The size of the header preceding the slots in the lazy function table.
+///
+/// It must match `wasmer_vm::LazyFunctionTable::HEADER_SIZE`.
+const LAZY_TABLE_HEADER_SIZE: usize = 8;
+
+// MOVABS RAX, <slot address>      48 b8 00 00 00 00 00 00 00 00
+// JMP [RAX]                       ff 20
+// MOV R11D, <function index>      41 bb 00 00 00 00
+// MOVABS RAX, <resolver address>  48 b8 00 00 00 00 00 00 00 00
+// JMP RAX                         ff e0
+const X86_64_STUB: [u8; 30] = [
+    0x48, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0x20, 0x41, 0xbb, 0, 0, 0, 0, 0x48, 0xb8, 0, 0, 0, 0,
+    0, 0, 0, 0, 0xff, 0xe0,
+];
+const X86_64_STUB_SLOT_OFFSET: usize = 2;
+const X86_64_STUB_RESOLVE_OFFSET: usize = 12;
+const X86_64_STUB_INDEX_OFFSET: usize = 14;
+const X86_64_STUB_RESOLVER_OFFSET: usize = 20;
+
+// PUSH RBP                        55
+// MOV RBP, RSP                    48 89 e5
+// PUSH RDI, RSI, RDX, RCX, R8, R9 57 56 52 51 41 50 41 51
+// SUB RSP, 0x80                   48 81 ec 80 00 00 00
+// MOVDQU [RSP + 0x10 * n], XMMn   f3 0f 7f xx 24 xx (n = 0..8)
+// MOVABS RDI, <table section>     48 bf 00 00 00 00 00 00 00 00
+// MOV RDI, [RDI]                  48 8b 3f
+// MOV ESI, R11D                   44 89 de
+// MOVABS RAX, <libcall address>   48 b8 00 00 00 00 00 00 00 00
+// CALL RAX                        ff d0
+// MOVDQU XMMn, [RSP + 0x10 * n]   f3 0f 6f xx 24 xx (n = 0..8)
+// ADD RSP, 0x80                   48 81 c4 80 00 00 00
+// POP R9, R8, RCX, RDX, RSI, RDI  41 59 41 58 59 5a 5e 5f
+// POP RBP                         5d
+// JMP RAX                         ff e0
+fn x86_64_resolver(code: &mut Vec<u8>, relocations: &mut Vec<Relocation>, table: SectionIndex) {
+    code.extend(&[0x55, 0x48, 0x89, 0xe5]);
+    code.extend(&[0x57, 0x56, 0x52, 0x51, 0x41, 0x50, 0x41, 0x51]);
+    code.extend(&[0x48, 0x81, 0xec, 0x80, 0x00, 0x00, 0x00]);
+    for xmm in 0..8u8 {
+        code.extend(&[0xf3, 0x0f, 0x7f, 0x44 | (xmm << 3), 0x24, xmm * 0x10]);
+    }
+    code.extend(&[0x48, 0xbf]);
+    relocations.push(Relocation {
+        kind: RelocationKind::Abs8,
+        reloc_target: RelocationTarget::CustomSection(table),
+        offset: code.len() as u32,
+        addend: 0,
+    });
+    code.extend(&[0; 8]);
+    // The header of the section points to the `LazyFunctionTable`
+    code.extend(&[0x48, 0x8b, 0x3f]);
+    code.extend(&[0x44, 0x89, 0xde]);
+    code.extend(&[0x48, 0xb8]);
+    relocations.push(Relocation {
+        kind: RelocationKind::Abs8,
+        reloc_target: RelocationTarget::LibCall(LibCall::LazyCompile),
+        offset: code.len() as u32,
+        addend: 0,
+    });
+    code.extend(&[0; 8]);
+    code.extend(&[0xff, 0xd0]);
+    for xmm in 0..8u8 {
+        code.extend(&[0xf3, 0x0f, 0x6f, 0x44 | (xmm << 3), 0x24, xmm * 0x10]);
+    }
+    code.extend(&[0x48, 0x81, 0xc4, 0x80, 0x00, 0x00, 0x00]);
+    code.extend(&[0x41, 0x59, 0x41, 0x58, 0x59, 0x5a, 0x5e, 0x5f]);
+    code.extend(&[0x5d, 0xff, 0xe0]);
+}
+
+// JMP [RIP + 2]   ff 25 02 00 00 00 [00 00]
+// 64-bit ADDR     00 00 00 00 00 00 00 00
+const X86_64_CALL_VENEER: [u8; 16] = [
+    0xff, 0x25, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The length of a call veneer.
+pub const CALL_VENEER_LEN: usize = X86_64_CALL_VENEER.len();
+
+/// Returns whether functions can be compiled lazily for the given target.
+///
+/// The stubs only preserve the argument registers of the System V calling
+/// convention on x86_64, so other targets are always compiled eagerly.
+pub fn lazy_compilation_supported(target: &Target) -> bool {
+    target.triple().architecture == Architecture::X86_64
+        && target.triple().default_calling_convention() == Ok(CallingConvention::SystemV)
+}
+
+/// A function body retained by a lazily compiled artifact, so it can be
+/// compiled on its first call.
+pub struct OwnedFunctionBodyData {
+    data: Box<[u8]>,
+    module_offset: usize,
+}
+
+impl OwnedFunctionBodyData {
+    /// Copies the given function body.
+    pub fn new(body: &FunctionBodyData<'_>) -> Self {
+        Self {
+            data: body.data.into(),
+            module_offset: body.module_offset,
+        }
+    }
+
+    /// Borrows the function body for compilation.
+    pub fn as_function_body_data(&self) -> FunctionBodyData<'_> {
+        FunctionBodyData {
+            data: &self.data,
+            module_offset: self.module_offset,
+        }
+    }
+}
+
+/// Everything a lazily compiled artifact needs to compile its functions
+/// after it has been instantiated.
+pub struct LazyFunctionInputs {
+    /// The translation state of the module.
+    pub module_translation: ModuleTranslationState,
+    /// The bodies of the local functions.
+    pub function_bodies: PrimaryMap<LocalFunctionIndex, OwnedFunctionBodyData>,
+    /// The read-write section holding the lazy function table.
+    pub table: SectionIndex,
+}
+
+/// Creates the read-write section holding the lazy function table.
+///
+/// Each slot is relocated to point at the resolve entry of its stub.
+pub fn make_lazy_function_table(target: &Target, num_functions: usize) -> CustomSection {
+    match target.triple().architecture {
+        Architecture::X86_64 => {}
+        arch => panic!("Unsupported architecture: {}", arch),
+    }
+    let relocations = (0..num_functions)
+        .map(|index| Relocation {
+            kind: RelocationKind::Abs8,
+            reloc_target: RelocationTarget::LocalFunc(LocalFunctionIndex::new(index)),
+            offset: (LAZY_TABLE_HEADER_SIZE + index * 8) as u32,
+            addend: X86_64_STUB_RESOLVE_OFFSET as _,
+        })
+        .collect();
+    CustomSection {
+        protection: CustomSectionProtection::ReadWrite,
+        bytes: SectionBody::new_with_vec(vec![0; LAZY_TABLE_HEADER_SIZE + num_functions * 8]),
+        relocations,
+    }
+}
+
+/// Creates the executable section holding the shared lazy resolver.
+pub fn make_lazy_resolver(target: &Target, table: SectionIndex) -> CustomSection {
+    let mut code = vec![];
+    let mut relocations = vec![];
+    match target.triple().architecture {
+        Architecture::X86_64 => x86_64_resolver(&mut code, &mut relocations, table),
+        arch => panic!("Unsupported architecture: {}", arch),
+    }
+    CustomSection {
+        protection: CustomSectionProtection::ReadExecute,
+        bytes: SectionBody::new_with_vec(code),
+        relocations,
+    }
+}
+
+/// Creates the veneers for the calls of a lazily compiled function.
+///
+/// Lazily compiled functions are allocated separately from the rest of
+/// the module, so their 32-bit relative calls are redirected through a
+/// veneer holding the full address of the callee. There is one veneer per
+/// `X86CallPCRel4` relocation, in the order of the relocations.
+pub fn make_call_veneers(relocations: &[Relocation]) -> CustomSection {
+    let mut code = vec![];
+    let mut veneer_relocations = vec![];
+    for r in relocations
+        .iter()
+        .filter(|r| r.kind == RelocationKind::X86CallPCRel4)
+    {
+        code.extend(&X86_64_CALL_VENEER);
+        veneer_relocations.push(Relocation {
+            kind: RelocationKind::Abs8,
+            reloc_target: r.reloc_target,
+            offset: code.len() as u32 - 8,
+            addend: 0,
+        });
+    }
+    CustomSection {
+        protection: CustomSectionProtection::ReadExecute,
+        bytes: SectionBody::new_with_vec(code),
+        relocations: veneer_relocations,
+    }
+}
+
+/// Creates the stub standing in for the given local function until it is
+/// compiled.
+pub fn make_lazy_stub(
+    target: &Target,
+    index: LocalFunctionIndex,
+    table: SectionIndex,
+    resolver: SectionIndex,
+) -> (FunctionBody, Vec<Relocation>) {
+    match target.triple().architecture {
+        Architecture::X86_64 => {}
+        arch => panic!("Unsupported architecture: {}", arch),
+    }
+    let mut body = X86_64_STUB.to_vec();
+    body[X86_64_STUB_INDEX_OFFSET..X86_64_STUB_INDEX_OFFSET + 4]
+        .copy_from_slice(&index.as_u32().to_le_bytes());
+    let relocations = vec![
+        Relocation {
+            kind: RelocationKind::Abs8,
+            reloc_target: RelocationTarget::CustomSection(table),
+            offset: X86_64_STUB_SLOT_OFFSET as u32,
+            addend: (LAZY_TABLE_HEADER_SIZE + index.index() * 8) as _,
+        },
+        Relocation {
+            kind: RelocationKind::Abs8,
+            reloc_target: RelocationTarget::CustomSection(resolver),
+            offset: X86_64_STUB_RESOLVER_OFFSET as u32,
+            addend: 0,
+        },
+    ];
+    (
+        FunctionBody {
+            body,
+            unwind_info: None,
+        },
+        relocations,
+    )
+}
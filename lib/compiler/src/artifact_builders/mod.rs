@@ -1,7 +1,13 @@
 //! Generic Artifact abstraction for Wasmer Engines.
 
 mod artifact_builder;
+#[cfg(feature = "compiler")]
+mod function_cache;
+mod lazy;
 mod trampoline;
 
 pub use self::artifact_builder::ArtifactBuild;
+#[cfg(feature = "compiler")]
+pub use self::function_cache::*;
+pub use self::lazy::*;
 pub use self::trampoline::*;
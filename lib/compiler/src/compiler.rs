@@ -7,13 +7,13 @@ use crate::translator::ModuleMiddleware;
 use crate::FunctionBodyData;
 use crate::ModuleTranslationState;
 use enumset::EnumSet;
-use wasmer_types::compilation::function::Compilation;
+use wasmer_types::compilation::function::{Compilation, CompiledFunction};
 use wasmer_types::compilation::module::CompileModuleInfo;
 use wasmer_types::compilation::symbols::SymbolRegistry;
 use wasmer_types::compilation::target::Target;
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::error::CompileError;
-use wasmer_types::{CpuFeature, Features, LocalFunctionIndex};
+use wasmer_types::{CpuFeature, CustomSection, Features, LocalFunctionIndex};
 use wasmparser::{Validator, WasmFeatures};
 
 /// The compiler configuration options.
@@ -88,9 +88,10 @@ pub trait Compiler: Send {
         data: &'data [u8],
     ) -> Result<(), CompileError> {
         let mut validator = Validator::new();
+        // The threads and relaxed SIMD proposals are not deterministic.
         let wasm_features = WasmFeatures {
             bulk_memory: features.bulk_memory,
-            threads: features.threads,
+            threads: features.threads && !features.deterministic,
             reference_types: features.reference_types,
             multi_value: features.multi_value,
             simd: features.simd,
@@ -101,7 +102,7 @@ pub trait Compiler: Send {
             exceptions: features.exceptions,
             deterministic_only: false,
             extended_const: features.extended_const,
-            relaxed_simd: features.relaxed_simd,
+            relaxed_simd: features.relaxed_simd && !features.deterministic,
             mutable_global: true,
             saturating_float_to_int: true,
             sign_extension: true,
@@ -125,6 +126,74 @@ pub trait Compiler: Send {
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError>;
 
+    /// Whether this compiler can compile functions one at a time through
+    /// [`Compiler::compile_function`].
+    ///
+    /// Engines configured for lazy compilation fall back to compiling the
+    /// whole module upfront when this returns `false`.
+    fn supports_lazy_compilation(&self) -> bool {
+        false
+    }
+
+    /// Compiles a single local function of a parsed module.
+    ///
+    /// This is used when functions are compiled lazily, on their first call.
+    /// Calls to other local functions in the resulting body must be emitted
+    /// as relocations, as the callees may not be compiled yet.
+    ///
+    /// It returns the [`CompiledFunction`] or a [`CompileError`]. A function
+    /// with DWARF unwind information comes with its own `.eh_frame`
+    /// section, holding a CIE and the FDE of the function, whose
+    /// relocations point to the function through its [`LocalFunctionIndex`].
+    fn compile_function<'data, 'module>(
+        &self,
+        _target: &Target,
+        _module: &'module CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        _index: LocalFunctionIndex,
+        _function_body: &FunctionBodyData<'data>,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        Err(CompileError::UnsupportedFeature(
+            "compiling functions lazily".to_string(),
+        ))
+    }
+
+    /// Compiles the given local functions of a parsed module.
+    ///
+    /// This is used to compile the functions that are missing from a
+    /// function cache. The default implementation compiles them one at a
+    /// time through [`Compiler::compile_function`], but compilers may
+    /// compile them in parallel.
+    ///
+    /// It returns the [`CompiledFunction`]s with their `.eh_frame`
+    /// sections, as [`Compiler::compile_function`] does, in the same order
+    /// as the inputs, or a [`CompileError`].
+    fn compile_functions<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        functions: &[(LocalFunctionIndex, FunctionBodyData<'data>)],
+    ) -> Result<Vec<(CompiledFunction, Option<CustomSection>)>, CompileError> {
+        functions
+            .iter()
+            .map(|(index, function_body)| {
+                self.compile_function(target, module, module_translation, *index, function_body)
+            })
+            .collect()
+    }
+
+    /// A string identifying the code generated by this compiler.
+    ///
+    /// Compiled functions are only reused from a function cache by a
+    /// compiler with the same fingerprint, so it must change whenever the
+    /// configuration of the compiler changes the generated code. Returns
+    /// `None` when the compiled functions can't be reused, for instance
+    /// when the compiler has middlewares.
+    fn function_cache_fingerprint(&self) -> Option<String> {
+        None
+    }
+
     /// Compiles a module into a native object file.
     ///
     /// It returns the bytes as a `&[u8]` or a [`CompileError`].
@@ -1,6 +1,8 @@
 //! Define `Artifact`, based on `ArtifactBuild`
 //! to allow compiling and instantiating to be done as separate steps.
 
+#[cfg(feature = "compiler")]
+use crate::engine::lazy::LazyCompilation;
 use crate::engine::link::link_module;
 use crate::ArtifactBuild;
 use crate::ArtifactCreate;
@@ -33,7 +35,10 @@ use wasmer_types::{
 };
 #[cfg(feature = "static-artifact-create")]
 use wasmer_types::{CompileModuleInfo, Target};
-use wasmer_vm::{FunctionBodyPtr, MemoryStyle, TableStyle, VMSharedSignatureIndex, VMTrampoline};
+use wasmer_vm::{
+    FunctionBodyPtr, LazyFunctionTable, MemoryStyle, TableStyle, VMSharedSignatureIndex,
+    VMTrampoline,
+};
 use wasmer_vm::{InstanceAllocator, InstanceHandle, StoreObjects, TrapHandlerFn, VMExtern};
 
 /// A compiled wasm module, ready to be instantiated.
@@ -46,6 +51,8 @@ pub struct Artifact {
     /// Some(_) only if this is not a deserialized static artifact
     frame_info_registration: Option<Mutex<Option<GlobalFrameInfoRegistration>>>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    /// Some(_) only if the functions of this artifact are compiled lazily
+    lazy_functions: Option<Box<LazyFunctionTable>>,
 }
 
 #[cfg(feature = "static-artifact-create")]
@@ -85,7 +92,7 @@ impl Artifact {
             table_styles,
         )?;
 
-        Self::from_parts(&mut inner_engine, artifact)
+        Self::from_parts_with_engine(&mut inner_engine, artifact, Some(engine))
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
@@ -134,6 +141,18 @@ impl Artifact {
         engine_inner: &mut EngineInner,
         artifact: ArtifactBuild,
     ) -> Result<Self, CompileError> {
+        Self::from_parts_with_engine(engine_inner, artifact, None)
+    }
+
+    /// Construct a `ArtifactBuild` from component parts, using `engine`
+    /// to compile the functions of a lazily compiled artifact.
+    #[allow(unused_variables)]
+    fn from_parts_with_engine(
+        engine_inner: &mut EngineInner,
+        mut artifact: ArtifactBuild,
+        engine: Option<&Engine>,
+    ) -> Result<Self, CompileError> {
+        let lazy_inputs = artifact.take_lazy_inputs();
         let module_info = artifact.create_module_info();
         let (
             finished_functions,
@@ -158,6 +177,40 @@ impl Artifact {
             artifact.get_libcall_trampoline_len(),
         );
 
+        let lazy_functions = match (lazy_inputs, engine) {
+            #[cfg(feature = "compiler")]
+            (Some(inputs), Some(engine)) => {
+                let table = custom_sections[inputs.table];
+                let num_functions = inputs.function_bodies.len();
+                let stubs = finished_functions
+                    .values()
+                    .map(|extent| FunctionExtent {
+                        ptr: extent.ptr,
+                        length: extent.length,
+                    })
+                    .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+                let compilation = LazyCompilation::new(
+                    engine.cloned(),
+                    artifact.get_compile_info_ref().clone(),
+                    inputs,
+                    stubs,
+                    custom_sections.clone(),
+                    artifact.get_libcall_trampolines(),
+                    artifact.get_libcall_trampoline_len(),
+                );
+                // SAFETY: the table section was allocated with one slot per
+                // function, and lives as long as the engine code memory.
+                Some(unsafe { LazyFunctionTable::new(table, num_functions, Box::new(compilation)) })
+            }
+            (Some(_), _) => {
+                return Err(CompileError::Codegen(
+                    "Lazily compiled artifacts need an engine to compile their functions"
+                        .to_string(),
+                ))
+            }
+            (None, _) => None,
+        };
+
         // Compute indices into the shared signature table.
         let signatures = {
             let signature_registry = engine_inner.signatures();
@@ -210,6 +263,7 @@ impl Artifact {
             signatures,
             frame_info_registration: Some(Mutex::new(None)),
             finished_function_lengths,
+            lazy_functions,
         })
     }
 
@@ -245,6 +299,11 @@ impl ArtifactCreate for Artifact {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        if self.lazy_functions.is_some() {
+            return Err(SerializeError::Generic(
+                "Lazily compiled artifacts can not be serialized".to_string(),
+            ));
+        }
         self.artifact.serialize()
     }
 }
@@ -299,6 +358,14 @@ impl Artifact {
         &self.finished_dynamic_function_trampolines
     }
 
+    /// Returns the number of functions compiled so far, if the functions
+    /// of this `Artifact` are compiled lazily.
+    pub fn num_lazily_compiled_functions(&self) -> Option<usize> {
+        self.lazy_functions
+            .as_ref()
+            .map(|lazy_functions| lazy_functions.num_compiled())
+    }
+
     /// Returns the associated VM signatures for this `Artifact`.
     pub fn signatures(&self) -> &BoxedSlice<SignatureIndex, VMSharedSignatureIndex> {
         &self.signatures
@@ -702,6 +769,7 @@ impl Artifact {
             signatures: signatures.into_boxed_slice(),
             finished_function_lengths,
             frame_info_registration: None,
+            lazy_functions: None,
         })
     }
 }
@@ -1,5 +1,9 @@
 use super::Engine;
 use crate::CompilerConfig;
+#[cfg(feature = "compiler")]
+use crate::FunctionCache;
+#[cfg(feature = "compiler")]
+use std::sync::Arc;
 use wasmer_types::{Features, Target};
 
 /// The Builder contents of `Engine`
@@ -10,6 +14,11 @@ pub struct EngineBuilder {
     target: Option<Target>,
     /// The features to compile the Wasm module with
     features: Option<Features>,
+    /// Whether local functions are compiled on their first call
+    lazy_compilation: bool,
+    /// The cache of compiled functions
+    #[cfg(feature = "compiler")]
+    function_cache: Option<Arc<dyn FunctionCache>>,
 }
 
 impl EngineBuilder {
@@ -22,6 +31,9 @@ impl EngineBuilder {
             compiler_config: Some(compiler_config.into()),
             target: None,
             features: None,
+            lazy_compilation: false,
+            #[cfg(feature = "compiler")]
+            function_cache: None,
         }
     }
 
@@ -31,6 +43,9 @@ impl EngineBuilder {
             compiler_config: None,
             target: None,
             features: None,
+            lazy_compilation: false,
+            #[cfg(feature = "compiler")]
+            function_cache: None,
         }
     }
 
@@ -46,6 +61,30 @@ impl EngineBuilder {
         self
     }
 
+    /// Compile local functions on their first call instead of upfront.
+    ///
+    /// This speeds up compiling big modules, where most of the functions
+    /// are never called. It's only supported by some compilers and
+    /// targets, modules are compiled eagerly otherwise.
+    pub fn set_lazy_compilation(mut self, lazy_compilation: bool) -> Self {
+        self.lazy_compilation = lazy_compilation;
+        self
+    }
+
+    /// Reuse compiled functions from the given cache.
+    ///
+    /// Functions are looked up by a hash of their body, their type, the
+    /// compiler configuration and the module environment, so recompiling a
+    /// module after a small change only compiles the functions that
+    /// changed. The missing functions are compiled in parallel and stored
+    /// in the cache. It's only supported by some compilers, modules are
+    /// compiled without the cache otherwise.
+    #[cfg(feature = "compiler")]
+    pub fn set_function_cache(mut self, function_cache: Option<Arc<dyn FunctionCache>>) -> Self {
+        self.function_cache = function_cache;
+        self
+    }
+
     /// Build the `Engine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> Engine {
@@ -54,7 +93,13 @@ impl EngineBuilder {
             let features = self
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
-            Engine::new(compiler_config, target, features)
+            let engine = Engine::new(compiler_config, target, features);
+            {
+                let mut inner = engine.inner_mut();
+                inner.set_lazy_compilation(self.lazy_compilation);
+                inner.set_function_cache(self.function_cache);
+            }
+            engine
         } else {
             Engine::headless()
         }
@@ -75,4 +120,9 @@ impl EngineBuilder {
     pub fn target(&self) -> Option<&Target> {
         self.target.as_ref()
     }
+
+    /// Whether local functions are compiled on their first call
+    pub fn lazy_compilation(&self) -> bool {
+        self.lazy_compilation
+    }
 }
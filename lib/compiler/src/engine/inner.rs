@@ -10,7 +10,7 @@ use crate::CodeMemory;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::{AsEngineRef, EngineRef};
 #[cfg(feature = "compiler")]
-use crate::{Compiler, CompilerConfig};
+use crate::{Compiler, CompilerConfig, FunctionCache};
 #[cfg(not(target_arch = "wasm32"))]
 use crate::{FunctionExtent, Tunables};
 #[cfg(not(target_arch = "wasm32"))]
@@ -46,18 +46,26 @@ pub struct Engine {
 
 impl Engine {
     /// Create a new `Engine` with the given config
+    ///
+    /// When the features are deterministic, NaN canonicalization is
+    /// enabled in the compiler.
     #[cfg(feature = "compiler")]
     pub fn new(
-        compiler_config: Box<dyn CompilerConfig>,
+        mut compiler_config: Box<dyn CompilerConfig>,
         target: Target,
         features: Features,
     ) -> Self {
+        if features.deterministic {
+            compiler_config.canonicalize_nans(true);
+        }
         #[cfg(not(target_arch = "wasm32"))]
         let tunables = BaseTunables::for_target(&target);
         Self {
             inner: Arc::new(Mutex::new(EngineInner {
                 compiler: Some(compiler_config.compiler()),
                 features,
+                lazy_compilation: false,
+                function_cache: None,
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
@@ -70,6 +78,21 @@ impl Engine {
         }
     }
 
+    /// Whether this engine runs modules deterministically.
+    ///
+    /// Headless engines don't compile modules, so they are never
+    /// deterministic.
+    pub fn is_deterministic(&self) -> bool {
+        #[cfg(feature = "compiler")]
+        {
+            self.inner().features().deterministic
+        }
+        #[cfg(not(feature = "compiler"))]
+        {
+            false
+        }
+    }
+
     /// Create a headless `Engine`
     ///
     /// A headless engine is an engine without any compiler attached.
@@ -93,6 +116,10 @@ impl Engine {
                 compiler: None,
                 #[cfg(feature = "compiler")]
                 features: Features::default(),
+                #[cfg(feature = "compiler")]
+                lazy_compilation: false,
+                #[cfg(feature = "compiler")]
+                function_cache: None,
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
@@ -231,6 +258,12 @@ pub struct EngineInner {
     #[cfg(feature = "compiler")]
     /// The compiler and cpu features
     features: Features,
+    #[cfg(feature = "compiler")]
+    /// Whether local functions are compiled on their first call
+    lazy_compilation: bool,
+    #[cfg(feature = "compiler")]
+    /// The cache of compiled functions
+    function_cache: Option<Arc<dyn FunctionCache>>,
     /// The code memory is responsible of publishing the compiled
     /// functions to memory.
     #[cfg(not(target_arch = "wasm32"))]
@@ -266,6 +299,37 @@ impl EngineInner {
         &self.features
     }
 
+    /// Whether local functions are compiled on their first call,
+    /// instead of when the module is compiled.
+    #[cfg(feature = "compiler")]
+    pub fn lazy_compilation(&self) -> bool {
+        self.lazy_compilation
+    }
+
+    /// Sets whether local functions are compiled on their first call.
+    ///
+    /// Lazy compilation is only used on targets and compilers that
+    /// support it, modules are compiled eagerly otherwise.
+    #[cfg(feature = "compiler")]
+    pub fn set_lazy_compilation(&mut self, lazy_compilation: bool) {
+        self.lazy_compilation = lazy_compilation;
+    }
+
+    /// The cache of compiled functions, if any.
+    #[cfg(feature = "compiler")]
+    pub fn function_cache(&self) -> Option<&Arc<dyn FunctionCache>> {
+        self.function_cache.as_ref()
+    }
+
+    /// Sets the cache compiled functions are loaded from and stored to.
+    ///
+    /// The cache is only used by compilers that can compile functions one
+    /// at a time, and is ignored for lazily compiled modules.
+    #[cfg(feature = "compiler")]
+    pub fn set_function_cache(&mut self, function_cache: Option<Arc<dyn FunctionCache>>) {
+        self.function_cache = function_cache;
+    }
+
     /// Allocate compiled functions into memory
     #[cfg(not(target_arch = "wasm32"))]
     #[allow(clippy::type_complexity)]
@@ -0,0 +1,134 @@
+//! Compilation of the functions of lazily compiled artifacts on their
+//! first call.
+
+use crate::engine::link::{link_function, link_function_eh_frame};
+use crate::{
+    make_call_veneers, register_function_frame_info, CodeMemory, Engine, FunctionExtent,
+    GlobalFrameInfoRegistration, LazyFunctionInputs,
+};
+use std::sync::{Arc, Mutex};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{CompileModuleInfo, LocalFunctionIndex, ModuleInfo, SectionIndex};
+use wasmer_vm::{FunctionBodyPtr, LazyFunctionCompiler, SectionBodyPtr};
+
+/// The code of a lazily compiled function.
+struct LazyFunction {
+    _code_memory: CodeMemory,
+    _frame_info_registration: Option<GlobalFrameInfoRegistration>,
+}
+
+/// Compiles the functions of a lazily compiled artifact with the compiler
+/// of the engine that created it.
+pub(crate) struct LazyCompilation {
+    engine: Engine,
+    compile_info: CompileModuleInfo,
+    module: Arc<ModuleInfo>,
+    inputs: LazyFunctionInputs,
+    stubs: PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    sections: PrimaryMap<SectionIndex, SectionBodyPtr>,
+    libcall_trampolines: SectionIndex,
+    libcall_trampoline_len: usize,
+    functions: Mutex<Vec<LazyFunction>>,
+}
+
+/// # Safety
+/// The section pointers refer to the artifact code memory, which outlives
+/// the lazy compilation, and they are only used to link new functions.
+unsafe impl Send for LazyCompilation {}
+/// # Safety
+/// The section pointers refer to the artifact code memory, which outlives
+/// the lazy compilation, and they are only used to link new functions.
+unsafe impl Sync for LazyCompilation {}
+
+impl LazyCompilation {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        engine: Engine,
+        compile_info: CompileModuleInfo,
+        inputs: LazyFunctionInputs,
+        stubs: PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+        sections: PrimaryMap<SectionIndex, SectionBodyPtr>,
+        libcall_trampolines: SectionIndex,
+        libcall_trampoline_len: usize,
+    ) -> Self {
+        let module = Arc::new(compile_info.module.clone());
+        Self {
+            engine,
+            compile_info,
+            module,
+            inputs,
+            stubs,
+            sections,
+            libcall_trampolines,
+            libcall_trampoline_len,
+            functions: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl LazyFunctionCompiler for LazyCompilation {
+    fn compile_function(&self, index: LocalFunctionIndex) -> Result<FunctionBodyPtr, String> {
+        let (function, eh_frame) = {
+            let engine_inner = self.engine.inner();
+            let compiler = engine_inner.compiler().map_err(|e| e.to_string())?;
+            compiler
+                .compile_function(
+                    self.engine.target(),
+                    &self.compile_info,
+                    &self.inputs.module_translation,
+                    index,
+                    &self.inputs.function_bodies[index].as_function_body_data(),
+                )
+                .map_err(|e| e.to_string())?
+        };
+        let veneers = make_call_veneers(&function.relocations);
+
+        let mut code_memory = CodeMemory::new();
+        let (body, veneers_body, eh_frame_body) = {
+            let (functions, executable_sections, data_sections) = code_memory.allocate(
+                &[&function.body],
+                &[&veneers],
+                &eh_frame.iter().collect::<Vec<_>>(),
+            )?;
+            (
+                FunctionExtent {
+                    ptr: FunctionBodyPtr(functions[0].as_ptr()),
+                    length: functions[0].len(),
+                },
+                executable_sections[0].as_ptr() as usize,
+                data_sections.first().map(|section| section.as_ptr()),
+            )
+        };
+
+        link_function(
+            *body.ptr as usize,
+            &function.relocations,
+            veneers_body,
+            &veneers.relocations,
+            &self.stubs,
+            &self.sections,
+            self.libcall_trampolines,
+            self.libcall_trampoline_len,
+        );
+        let eh_frame_body = eh_frame.as_ref().zip(eh_frame_body).map(|(eh_frame, ptr)| {
+            link_function_eh_frame(ptr as usize, &eh_frame.relocations, *body.ptr as usize);
+            unsafe { std::slice::from_raw_parts(ptr, eh_frame.bytes.len()) }
+        });
+        code_memory.publish();
+
+        // The unwind information is registered with the code memory holding
+        // it, and deregistered when it is dropped.
+        code_memory
+            .unwind_registry_mut()
+            .publish(eh_frame_body)
+            .map_err(|e| format!("Error while publishing the unwind code: {}", e))?;
+
+        let frame_info_registration =
+            register_function_frame_info(self.module.clone(), index, &body, function.frame_info);
+        self.functions.lock().unwrap().push(LazyFunction {
+            _code_memory: code_memory,
+            _frame_info_registration: frame_info_registration,
+        });
+        Ok(body.ptr)
+    }
+}
@@ -1,7 +1,7 @@
 //! Linking for Universal-compiled code.
 
 use crate::get_libcall_trampoline;
-use crate::FunctionExtent;
+use crate::{FunctionExtent, CALL_VENEER_LEN};
 use std::ptr::{read_unaligned, write_unaligned};
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{LocalFunctionIndex, ModuleInfo};
@@ -36,7 +36,10 @@ fn apply_relocation(
             *allocated_sections[custom_section] as usize
         }
     };
+    write_relocation(body, r, target_func_address);
+}
 
+fn write_relocation(body: usize, r: &Relocation, target_func_address: usize) {
     match r.kind {
         RelocationKind::Abs8 => unsafe {
             let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
@@ -138,3 +141,57 @@ pub fn link_module(
         }
     }
 }
+
+/// Links a function compiled after its module was allocated, such as a
+/// lazily compiled function.
+///
+/// The function may be allocated out of reach of a 32-bit displacement
+/// from its callees, so every call is redirected through its own veneer
+/// from the `veneers` section, which is linked as well.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn link_function(
+    body: usize,
+    relocations: &[Relocation],
+    veneers: usize,
+    veneer_relocations: &[Relocation],
+    allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    libcall_trampolines: SectionIndex,
+    trampoline_len: usize,
+) {
+    for r in veneer_relocations {
+        apply_relocation(
+            veneers,
+            r,
+            allocated_functions,
+            allocated_sections,
+            libcall_trampolines,
+            trampoline_len,
+        );
+    }
+    let mut next_veneer = veneers;
+    for r in relocations {
+        if r.kind == RelocationKind::X86CallPCRel4 {
+            write_relocation(body, r, next_veneer);
+            next_veneer += CALL_VENEER_LEN;
+        } else {
+            apply_relocation(
+                body,
+                r,
+                allocated_functions,
+                allocated_sections,
+                libcall_trampolines,
+                trampoline_len,
+            );
+        }
+    }
+}
+
+/// Links the `.eh_frame` section of a function compiled after its module
+/// was allocated, whose relocations point to the function itself.
+pub(crate) fn link_function_eh_frame(eh_frame: usize, relocations: &[Relocation], body: usize) {
+    for r in relocations {
+        debug_assert!(matches!(r.reloc_target, RelocationTarget::LocalFunc(_)));
+        write_relocation(eh_frame, r, body);
+    }
+}
@@ -21,6 +21,9 @@ mod builder;
 mod code_memory;
 #[cfg(feature = "translator")]
 mod inner;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+mod lazy;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 mod link;
@@ -13,7 +13,7 @@
 //! ```
 use std::cmp;
 use std::collections::BTreeMap;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{CompiledFunctionFrameInfo, SourceLoc, TrapInformation};
 use wasmer_types::{LocalFunctionIndex, ModuleInfo};
@@ -54,15 +54,10 @@ pub struct GlobalFrameInfoRegistration {
 struct ModuleInfoFrameInfo {
     start: usize,
     functions: BTreeMap<usize, FunctionInfo>,
-    module: ModuleInfo,
-    frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    module: Arc<ModuleInfo>,
 }
 
 impl ModuleInfoFrameInfo {
-    fn function_debug_info(&self, local_index: LocalFunctionIndex) -> &CompiledFunctionFrameInfo {
-        self.frame_infos.get(local_index).unwrap()
-    }
-
     /// Gets a function given a pc
     fn function_info(&self, pc: usize) -> Option<&FunctionInfo> {
         let (end, func) = self.functions.range(pc..).next()?;
@@ -78,6 +73,7 @@ impl ModuleInfoFrameInfo {
 struct FunctionInfo {
     start: usize,
     local_index: LocalFunctionIndex,
+    frame_info: CompiledFunctionFrameInfo,
 }
 
 impl GlobalFrameInfo {
@@ -93,7 +89,7 @@ impl GlobalFrameInfo {
         // machine instruction that corresponds to `pc`, which then allows us to
         // map that to a wasm original source location.
         let rel_pos = pc - func.start;
-        let instr_map = &module.function_debug_info(func.local_index).address_map;
+        let instr_map = &func.frame_info.address_map;
         let pos = match instr_map
             .instructions
             .binary_search_by_key(&rel_pos, |map| map.code_offset)
@@ -142,7 +138,7 @@ impl GlobalFrameInfo {
     pub fn lookup_trap_info(&self, pc: usize) -> Option<&TrapInformation> {
         let module = self.module_info(pc)?;
         let func = module.function_info(pc)?;
-        let traps = &module.function_debug_info(func.local_index).traps;
+        let traps = &func.frame_info.traps;
         let idx = traps
             .binary_search_by_key(&((pc - func.start) as u32), |info| info.code_offset)
             .ok()?;
@@ -190,17 +186,50 @@ pub fn register(
     module: ModuleInfo,
     finished_functions: &BoxedSlice<LocalFunctionIndex, FunctionExtent>,
     frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+) -> Option<GlobalFrameInfoRegistration> {
+    register_functions(
+        Arc::new(module),
+        finished_functions
+            .iter()
+            .zip(frame_infos.into_iter().map(|(_, frame_info)| frame_info)),
+    )
+}
+
+/// Registers the frame information of a single function of a module.
+///
+/// This is used for functions that are compiled lazily, after the rest of
+/// the module has been registered.
+pub fn register_function(
+    module: Arc<ModuleInfo>,
+    index: LocalFunctionIndex,
+    extent: &FunctionExtent,
+    frame_info: CompiledFunctionFrameInfo,
+) -> Option<GlobalFrameInfoRegistration> {
+    register_functions(module, std::iter::once(((index, extent), frame_info)))
+}
+
+fn register_functions<'a>(
+    module: Arc<ModuleInfo>,
+    finished_functions: impl Iterator<
+        Item = (
+            (LocalFunctionIndex, &'a FunctionExtent),
+            CompiledFunctionFrameInfo,
+        ),
+    >,
 ) -> Option<GlobalFrameInfoRegistration> {
     let mut min = usize::max_value();
     let mut max = 0;
     let mut functions = BTreeMap::new();
     for (
-        i,
-        FunctionExtent {
-            ptr: start,
-            length: len,
-        },
-    ) in finished_functions.iter()
+        (
+            i,
+            FunctionExtent {
+                ptr: start,
+                length: len,
+            },
+        ),
+        frame_info,
+    ) in finished_functions
     {
         let start = **start as usize;
         // end is "last byte" of the function code
@@ -210,6 +239,7 @@ pub fn register(
         let func = FunctionInfo {
             start,
             local_index: i,
+            frame_info,
         };
         assert!(functions.insert(end, func).is_none());
     }
@@ -234,7 +264,6 @@ pub fn register(
             start: min,
             functions,
             module,
-            frame_infos,
         },
     );
     assert!(prev.is_none());
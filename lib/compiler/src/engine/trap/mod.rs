@@ -2,6 +2,6 @@ mod error;
 mod frame_info;
 pub use error::RuntimeError;
 pub use frame_info::{
-    register as register_frame_info, FrameInfo, FunctionExtent, GlobalFrameInfoRegistration,
-    FRAME_INFO,
+    register as register_frame_info, register_function as register_function_frame_info, FrameInfo,
+    FunctionExtent, GlobalFrameInfoRegistration, FRAME_INFO,
 };
@@ -10,10 +10,10 @@ use wasmer_types::entity::PrimaryMap;
 use wasmer_types::FunctionType;
 use wasmer_types::WasmResult;
 use wasmer_types::{
-    CustomSectionIndex, DataIndex, DataInitializer, DataInitializerLocation, ElemIndex,
-    ExportIndex, FunctionIndex, GlobalIndex, GlobalInit, GlobalType, ImportIndex,
-    LocalFunctionIndex, MemoryIndex, MemoryType, ModuleInfo, SignatureIndex, TableIndex,
-    TableInitializer, TableType,
+    ConstExpr, ConstExprIndex, CustomSectionIndex, DataIndex, DataInitializer,
+    DataInitializerLocation, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, GlobalInit,
+    GlobalType, ImportIndex, LocalFunctionIndex, MemoryIndex, MemoryType, ModuleInfo,
+    SignatureIndex, TableIndex, TableInitializer, TableType,
 };
 
 /// Contains function data: bytecode and its offset in the module.
@@ -274,6 +274,10 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    pub(crate) fn declare_const_expr(&mut self, expr: ConstExpr) -> WasmResult<ConstExprIndex> {
+        Ok(self.module.const_exprs.push(expr))
+    }
+
     pub(crate) fn reserve_exports(&mut self, num: u32) -> WasmResult<()> {
         self.module.exports.reserve(usize::try_from(num).unwrap());
         Ok(())
@@ -329,12 +333,14 @@ impl<'data> ModuleEnvironment<'data> {
         table_index: TableIndex,
         base: Option<GlobalIndex>,
         offset: usize,
+        offset_expr: Option<ConstExpr>,
         elements: Box<[FunctionIndex]>,
     ) -> WasmResult<()> {
         self.module.table_initializers.push(TableInitializer {
             table_index,
             base,
             offset,
+            offset_expr,
             elements,
         });
         Ok(())
@@ -378,6 +384,7 @@ impl<'data> ModuleEnvironment<'data> {
         memory_index: MemoryIndex,
         base: Option<GlobalIndex>,
         offset: usize,
+        offset_expr: Option<ConstExpr>,
         data: &'data [u8],
     ) -> WasmResult<()> {
         self.data_initializers.push(DataInitializer {
@@ -385,6 +392,7 @@ impl<'data> ModuleEnvironment<'data> {
                 memory_index,
                 base,
                 offset,
+                offset_expr,
             },
             data,
         });
@@ -9,7 +9,8 @@
 //!
 //! The special case of the initialize expressions for table elements offsets or global variables
 //! is handled, according to the semantics of WebAssembly, to only specific expressions that are
-//! interpreted on the fly.
+//! interpreted on the fly. The longer expressions of the extended-const proposal are translated
+//! to a `ConstExpr`, evaluated when the module is instantiated.
 use super::environ::ModuleEnvironment;
 use super::error::from_binaryreadererror_wasmerror;
 use super::state::ModuleTranslationState;
@@ -21,16 +22,18 @@ use std::vec::Vec;
 use wasmer_types::entity::packed_option::ReservedValue;
 use wasmer_types::entity::EntityRef;
 use wasmer_types::{
-    DataIndex, ElemIndex, FunctionIndex, FunctionType, GlobalIndex, GlobalInit, GlobalType,
-    MemoryIndex, MemoryType, Pages, SignatureIndex, TableIndex, TableType, Type, V128,
+    ConstExpr, ConstOperator, DataIndex, ElemIndex, FunctionIndex, FunctionType, GlobalIndex,
+    GlobalInit, GlobalType, MemoryIndex, MemoryType, Pages, SignatureIndex, TableIndex, TableType,
+    Type, V128,
 };
 use wasmer_types::{WasmError, WasmResult};
 use wasmparser::{
     self, Data, DataKind, DataSectionReader, Element, ElementItem, ElementItems, ElementKind,
     ElementSectionReader, Export, ExportSectionReader, ExternalKind, FuncType as WPFunctionType,
     FunctionSectionReader, GlobalSectionReader, GlobalType as WPGlobalType, ImportSectionEntryType,
-    ImportSectionReader, MemorySectionReader, MemoryType as WPMemoryType, NameSectionReader,
-    Naming, NamingReader, Operator, TableSectionReader, TypeDef, TypeSectionReader,
+    ImportSectionReader, InitExpr, MemorySectionReader, MemoryType as WPMemoryType,
+    NameSectionReader, Naming, NamingReader, Operator, TableSectionReader, TypeDef,
+    TypeSectionReader,
 };
 
 /// Helper function translating wasmparser types to Wasm Type.
@@ -50,6 +53,53 @@ pub fn wptype_to_type(ty: wasmparser::Type) -> WasmResult<Type> {
     }
 }
 
+/// Reads an extended constant expression.
+///
+/// Returns `None` for the constant expressions made of a single operator,
+/// which are translated on the fly by each section.
+fn read_extended_const_expr(init_expr: &InitExpr, section: &str) -> WasmResult<Option<ConstExpr>> {
+    let mut init_expr_reader = init_expr.get_binary_reader();
+    let mut operators = Vec::new();
+    loop {
+        match init_expr_reader
+            .read_operator()
+            .map_err(from_binaryreadererror_wasmerror)?
+        {
+            Operator::End => break,
+            operator => operators.push(operator),
+        }
+    }
+    if operators.len() <= 1 {
+        return Ok(None);
+    }
+    let operators = operators
+        .into_iter()
+        .map(|operator| {
+            Ok(match operator {
+                Operator::I32Const { value } => ConstOperator::I32Const(value),
+                Operator::I64Const { value } => ConstOperator::I64Const(value),
+                Operator::GlobalGet { global_index } => {
+                    ConstOperator::GlobalGet(GlobalIndex::from_u32(global_index))
+                }
+                Operator::I32Add => ConstOperator::I32Add,
+                Operator::I32Sub => ConstOperator::I32Sub,
+                Operator::I32Mul => ConstOperator::I32Mul,
+                Operator::I64Add => ConstOperator::I64Add,
+                Operator::I64Sub => ConstOperator::I64Sub,
+                Operator::I64Mul => ConstOperator::I64Mul,
+                ref s => {
+                    return Err(wasm_unsupported!(
+                        "unsupported init expr in {} section: {:?}",
+                        section,
+                        s
+                    ))
+                }
+            })
+        })
+        .collect::<WasmResult<Vec<_>>>()?;
+    Ok(Some(ConstExpr::new(operators)))
+}
+
 /// Parses the Type section of the wasm module.
 pub fn parse_type_section(
     types: TypeSectionReader,
@@ -241,6 +291,15 @@ pub fn parse_global_section(
             },
             init_expr,
         } = entry.map_err(from_binaryreadererror_wasmerror)?;
+        if let Some(expr) = read_extended_const_expr(&init_expr, "global")? {
+            let global = GlobalType {
+                ty: wptype_to_type(content_type).unwrap(),
+                mutability: mutable.into(),
+            };
+            let expr = environ.declare_const_expr(expr)?;
+            environ.declare_global(global, GlobalInit::Expr(expr))?;
+            continue;
+        }
         let mut init_expr_reader = init_expr.get_binary_reader();
         let initializer = match init_expr_reader
             .read_operator()
@@ -377,11 +436,13 @@ pub fn parse_element_section<'data>(
                 table_index,
                 init_expr,
             } => {
+                let offset_expr = read_extended_const_expr(&init_expr, "element")?;
                 let mut init_expr_reader = init_expr.get_binary_reader();
                 let (base, offset) = match init_expr_reader
                     .read_operator()
                     .map_err(from_binaryreadererror_wasmerror)?
                 {
+                    _ if offset_expr.is_some() => (None, 0),
                     Operator::I32Const { value } => (None, value as u32 as usize),
                     Operator::GlobalGet { global_index } => {
                         (Some(GlobalIndex::from_u32(global_index)), 0)
@@ -397,6 +458,7 @@ pub fn parse_element_section<'data>(
                     TableIndex::from_u32(table_index),
                     base,
                     offset,
+                    offset_expr,
                     segments,
                 )?
             }
@@ -428,11 +490,13 @@ pub fn parse_data_section<'data>(
                 memory_index,
                 init_expr,
             } => {
+                let offset_expr = read_extended_const_expr(&init_expr, "data")?;
                 let mut init_expr_reader = init_expr.get_binary_reader();
                 let (base, offset) = match init_expr_reader
                     .read_operator()
                     .map_err(from_binaryreadererror_wasmerror)?
                 {
+                    _ if offset_expr.is_some() => (None, 0),
                     Operator::I32Const { value } => (None, value as u32 as usize),
                     Operator::GlobalGet { global_index } => {
                         (Some(GlobalIndex::from_u32(global_index)), 0)
@@ -448,6 +512,7 @@ pub fn parse_data_section<'data>(
                     MemoryIndex::from_u32(memory_index),
                     base,
                     offset,
+                    offset_expr,
                     data,
                 )?;
             }
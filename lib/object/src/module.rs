@@ -182,7 +182,9 @@ pub fn emit_compilation(
                     CustomSectionProtection::ReadExecute => {
                         (SymbolKind::Text, StandardSection::Text)
                     }
-                    CustomSectionProtection::Read => (SymbolKind::Data, StandardSection::Data),
+                    CustomSectionProtection::Read | CustomSectionProtection::ReadWrite => {
+                        (SymbolKind::Data, StandardSection::Data)
+                    }
                 };
                 let section_id = obj.section_id(standard_section);
                 let symbol_id = obj.add_symbol(ObjSymbol {
@@ -45,6 +45,9 @@ pub enum CustomSectionProtection {
 
     /// A custom section with read and execute permissions.
     ReadExecute,
+
+    /// A custom section with read and write permissions.
+    ReadWrite,
 }
 
 /// A Section for a `Compilation`.
@@ -35,6 +35,8 @@ pub struct Features {
     pub relaxed_simd: bool,
     /// Extended constant expressions proposal should be enabled
     pub extended_const: bool,
+    /// Modules should run deterministically
+    pub deterministic: bool,
 }
 
 impl Features {
@@ -57,6 +59,27 @@ impl Features {
             exceptions: false,
             relaxed_simd: false,
             extended_const: false,
+            deterministic: false,
+        }
+    }
+
+    /// Create the features for running modules deterministically.
+    ///
+    /// Engines created with these features canonicalize NaNs in every
+    /// compiler, and reject the proposals whose semantics are not
+    /// deterministic (threads and relaxed SIMD), even if they are enabled
+    /// afterwards. WASI environments created in such engines can't read the
+    /// host clocks or random source, unless a clock or random source is
+    /// injected.
+    ///
+    /// This is useful when running WebAssembly in consensus-critical
+    /// computations, where every node must get the same results.
+    pub fn deterministic() -> Self {
+        Self {
+            threads: false,
+            relaxed_simd: false,
+            deterministic: true,
+            ..Self::new()
         }
     }
 
@@ -232,6 +255,51 @@ impl Features {
         self.memory64 = enable;
         self
     }
+
+    /// Configures whether the WebAssembly relaxed SIMD proposal will be
+    /// enabled.
+    ///
+    /// The [WebAssembly relaxed SIMD proposal][proposal] is not currently
+    /// fully standardized and is undergoing development. Support for this
+    /// feature can be enabled through this method for appropriate
+    /// WebAssembly modules.
+    ///
+    /// This feature gates SIMD instructions whose results may depend on the
+    /// host. It is always disabled in [deterministic](Self::deterministic)
+    /// mode.
+    ///
+    /// Every compiler lowers the relaxed (negative) multiply-add to a
+    /// multiplication followed by an addition (subtraction), rounding twice
+    /// instead of once as a fused multiply-add would. The proposal allows
+    /// both, and this keeps the results the same across compilers and hosts.
+    ///
+    /// This is `false` by default.
+    ///
+    /// [proposal]: https://github.com/WebAssembly/relaxed-simd
+    pub fn relaxed_simd(&mut self, enable: bool) -> &mut Self {
+        self.relaxed_simd = enable;
+        self
+    }
+
+    /// Configures whether the WebAssembly extended constant expressions
+    /// proposal will be enabled.
+    ///
+    /// The [WebAssembly extended-const proposal][proposal] is not currently
+    /// fully standardized and is undergoing development. Support for this
+    /// feature can be enabled through this method for appropriate
+    /// WebAssembly modules.
+    ///
+    /// This feature allows `i32` and `i64` additions, subtractions and
+    /// multiplications in the initializers of globals and the offsets of
+    /// data and element segments.
+    ///
+    /// This is `false` by default.
+    ///
+    /// [proposal]: https://github.com/WebAssembly/extended-const
+    pub fn extended_const(&mut self, enable: bool) -> &mut Self {
+        self.extended_const = enable;
+        self
+    }
 }
 
 impl Default for Features {
@@ -261,10 +329,20 @@ mod test_features {
                 exceptions: false,
                 relaxed_simd: false,
                 extended_const: false,
+                deterministic: false,
             }
         );
     }
 
+    #[test]
+    fn deterministic_features() {
+        let features = Features::deterministic();
+        assert!(features.deterministic);
+        assert!(!features.threads);
+        assert!(!features.relaxed_simd);
+        assert!(features.simd);
+    }
+
     #[test]
     fn enable_threads() {
         let mut features = Features::new();
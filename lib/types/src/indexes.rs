@@ -207,6 +207,25 @@ entity_impl!(ElemIndex);
 pub struct CustomSectionIndex(u32);
 entity_impl!(CustomSectionIndex);
 
+/// Index type of an extended constant expression inside the WebAssembly module.
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Debug,
+    RkyvSerialize,
+    RkyvDeserialize,
+    Archive,
+)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[archive(as = "Self")]
+pub struct ConstExprIndex(u32);
+entity_impl!(ConstExprIndex);
+
 /// An entity to export.
 #[derive(
     Copy,
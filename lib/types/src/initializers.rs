@@ -1,5 +1,6 @@
 use crate::indexes::{FunctionIndex, GlobalIndex, MemoryIndex, TableIndex};
 use crate::lib::std::boxed::Box;
+use crate::lib::std::vec::Vec;
 
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 #[cfg(feature = "enable-serde")]
@@ -15,6 +16,9 @@ pub struct TableInitializer {
     pub base: Option<GlobalIndex>,
     /// The offset to add to the base.
     pub offset: usize,
+    /// An extended constant expression computing the offset, used instead
+    /// of `base` and `offset` when present.
+    pub offset_expr: Option<ConstExpr>,
     /// The values to write into the table elements.
     pub elements: Box<[FunctionIndex]>,
 }
@@ -32,6 +36,10 @@ pub struct DataInitializerLocation {
 
     /// A constant offset to initialize at.
     pub offset: usize,
+
+    /// An extended constant expression computing the offset, used instead
+    /// of `base` and `offset` when present.
+    pub offset_expr: Option<ConstExpr>,
 }
 
 /// A data initializer for linear memory.
@@ -66,3 +74,137 @@ impl OwnedDataInitializer {
         }
     }
 }
+
+/// An operator of an extended constant expression.
+///
+/// These are the operators allowed in constant expressions by the
+/// [extended-const proposal](https://github.com/WebAssembly/extended-const),
+/// besides the ones producing references and floats, which only appear in
+/// single-operator expressions.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum ConstOperator {
+    /// An `i32.const`.
+    I32Const(i32),
+    /// An `i64.const`.
+    I64Const(i64),
+    /// A `global.get` of an immutable global.
+    GlobalGet(GlobalIndex),
+    /// An `i32.add`.
+    I32Add,
+    /// An `i32.sub`.
+    I32Sub,
+    /// An `i32.mul`.
+    I32Mul,
+    /// An `i64.add`.
+    I64Add,
+    /// An `i64.sub`.
+    I64Sub,
+    /// An `i64.mul`.
+    I64Mul,
+}
+
+/// An extended constant expression, evaluated when a module is
+/// instantiated.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct ConstExpr {
+    operators: Box<[ConstOperator]>,
+}
+
+impl ConstExpr {
+    /// Creates a new constant expression from its operators, without the
+    /// final `end`.
+    pub fn new(operators: impl Into<Box<[ConstOperator]>>) -> Self {
+        Self {
+            operators: operators.into(),
+        }
+    }
+
+    /// The operators of the expression.
+    pub fn operators(&self) -> &[ConstOperator] {
+        &self.operators
+    }
+
+    /// Evaluates the expression, reading the value of globals with
+    /// `get_global`.
+    ///
+    /// Values are passed around as the raw bits of an `i64`; the result of
+    /// an expression of type `i32` is in the lower 32 bits. Returns `None`
+    /// if the expression is malformed, which can't happen for expressions
+    /// of a validated module.
+    pub fn eval(&self, mut get_global: impl FnMut(GlobalIndex) -> u64) -> Option<u64> {
+        let mut stack: Vec<u64> = Vec::with_capacity(self.operators.len());
+        for operator in self.operators.iter() {
+            let value = match *operator {
+                ConstOperator::I32Const(value) => value as u32 as u64,
+                ConstOperator::I64Const(value) => value as u64,
+                ConstOperator::GlobalGet(index) => get_global(index),
+                ConstOperator::I32Add | ConstOperator::I32Sub | ConstOperator::I32Mul => {
+                    let rhs = stack.pop()? as i32;
+                    let lhs = stack.pop()? as i32;
+                    let value = match *operator {
+                        ConstOperator::I32Add => lhs.wrapping_add(rhs),
+                        ConstOperator::I32Sub => lhs.wrapping_sub(rhs),
+                        _ => lhs.wrapping_mul(rhs),
+                    };
+                    value as u32 as u64
+                }
+                ConstOperator::I64Add | ConstOperator::I64Sub | ConstOperator::I64Mul => {
+                    let rhs = stack.pop()? as i64;
+                    let lhs = stack.pop()? as i64;
+                    let value = match *operator {
+                        ConstOperator::I64Add => lhs.wrapping_add(rhs),
+                        ConstOperator::I64Sub => lhs.wrapping_sub(rhs),
+                        _ => lhs.wrapping_mul(rhs),
+                    };
+                    value as u64
+                }
+            };
+            stack.push(value);
+        }
+        match stack.as_slice() {
+            [value] => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn const_expr_wraps_i32_arithmetic() {
+        let expr = ConstExpr::new(vec![
+            ConstOperator::I32Const(i32::MAX),
+            ConstOperator::GlobalGet(GlobalIndex::from_u32(0)),
+            ConstOperator::I32Add,
+            ConstOperator::I32Const(2),
+            ConstOperator::I32Mul,
+        ]);
+        // The upper bits of `i32` globals are ignored.
+        let value = expr.eval(|_| 0xdead_beef_0000_0001).unwrap();
+        assert_eq!(value, (i32::MIN.wrapping_mul(2)) as u32 as u64);
+    }
+
+    #[test]
+    fn const_expr_evaluates_i64_arithmetic() {
+        let expr = ConstExpr::new(vec![
+            ConstOperator::I64Const(10),
+            ConstOperator::GlobalGet(GlobalIndex::from_u32(3)),
+            ConstOperator::I64Sub,
+        ]);
+        let value = expr.eval(|index| index.as_u32() as u64 * 5).unwrap();
+        assert_eq!(value as i64, -5);
+    }
+
+    #[test]
+    fn malformed_const_expr() {
+        assert_eq!(
+            ConstExpr::new(vec![ConstOperator::I32Add]).eval(|_| 0),
+            None
+        );
+        assert_eq!(ConstExpr::new(vec![]).eval(|_| 0), None);
+    }
+}
@@ -133,6 +133,9 @@ pub enum LibCall {
 
     /// memory.atomic.botify for imported memories
     ImportedMemory32AtomicNotify,
+
+    /// Compiles a lazily compiled function on its first call
+    LazyCompile,
 }
 
 impl LibCall {
@@ -181,6 +184,7 @@ impl LibCall {
             Self::ImportedMemory32AtomicWait64 => "wasmer_vm_imported_memory32_atomic_wait64",
             Self::Memory32AtomicNotify => "wasmer_vm_memory32_atomic_notify",
             Self::ImportedMemory32AtomicNotify => "wasmer_vm_imported_memory32_atomic_notify",
+            Self::LazyCompile => "wasmer_vm_lazy_compile",
         }
     }
 }
@@ -6,10 +6,10 @@
 
 use crate::entity::{EntityRef, PrimaryMap};
 use crate::{
-    CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, ExportType, ExternType, FunctionIndex,
-    FunctionType, GlobalIndex, GlobalInit, GlobalType, ImportIndex, ImportType, LocalFunctionIndex,
-    LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType, SignatureIndex,
-    TableIndex, TableInitializer, TableType,
+    ConstExpr, ConstExprIndex, CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, ExportType,
+    ExternType, FunctionIndex, FunctionType, GlobalIndex, GlobalInit, GlobalType, ImportIndex,
+    ImportType, LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex,
+    MemoryIndex, MemoryType, SignatureIndex, TableIndex, TableInitializer, TableType,
 };
 use indexmap::IndexMap;
 use rkyv::{
@@ -109,6 +109,9 @@ pub struct ModuleInfo {
     /// WebAssembly global initializers.
     pub global_initializers: PrimaryMap<LocalGlobalIndex, GlobalInit>,
 
+    /// WebAssembly extended constant expressions initializing globals.
+    pub const_exprs: PrimaryMap<ConstExprIndex, ConstExpr>,
+
     /// WebAssembly function names.
     pub function_names: HashMap<FunctionIndex, String>,
 
@@ -157,6 +160,7 @@ pub struct ArchivableModuleInfo {
     passive_elements: BTreeMap<ElemIndex, Box<[FunctionIndex]>>,
     passive_data: BTreeMap<DataIndex, Box<[u8]>>,
     global_initializers: PrimaryMap<LocalGlobalIndex, GlobalInit>,
+    const_exprs: PrimaryMap<ConstExprIndex, ConstExpr>,
     function_names: BTreeMap<FunctionIndex, String>,
     signatures: PrimaryMap<SignatureIndex, FunctionType>,
     functions: PrimaryMap<FunctionIndex, SignatureIndex>,
@@ -182,6 +186,7 @@ impl From<ModuleInfo> for ArchivableModuleInfo {
             passive_elements: it.passive_elements.into_iter().collect(),
             passive_data: it.passive_data.into_iter().collect(),
             global_initializers: it.global_initializers,
+            const_exprs: it.const_exprs,
             function_names: it.function_names.into_iter().collect(),
             signatures: it.signatures,
             functions: it.functions,
@@ -210,6 +215,7 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
             passive_elements: it.passive_elements.into_iter().collect(),
             passive_data: it.passive_data.into_iter().collect(),
             global_initializers: it.global_initializers,
+            const_exprs: it.const_exprs,
             function_names: it.function_names.into_iter().collect(),
             signatures: it.signatures,
             functions: it.functions,
@@ -270,6 +276,7 @@ impl PartialEq for ModuleInfo {
             && self.passive_elements == other.passive_elements
             && self.passive_data == other.passive_data
             && self.global_initializers == other.global_initializers
+            && self.const_exprs == other.const_exprs
             && self.function_names == other.function_names
             && self.signatures == other.signatures
             && self.functions == other.functions
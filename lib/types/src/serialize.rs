@@ -1,9 +1,9 @@
 use crate::entity::PrimaryMap;
 use crate::{
-    compilation::target::CpuFeature, CompileModuleInfo, CompiledFunctionFrameInfo, CustomSection,
-    DeserializeError, Dwarf, Features, FunctionBody, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex,
-    SerializeError, SignatureIndex, TableIndex, TableStyle,
+    compilation::target::CpuFeature, CompileModuleInfo, CompiledFunction,
+    CompiledFunctionFrameInfo, CustomSection, DeserializeError, Dwarf, Features, FunctionBody,
+    FunctionIndex, LocalFunctionIndex, MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer,
+    Relocation, SectionIndex, SerializeError, SignatureIndex, TableIndex, TableStyle,
 };
 use enumset::EnumSet;
 use rkyv::{
@@ -165,6 +165,62 @@ impl SerializableModule {
     }
 }
 
+/// Serializable struct holding a single compiled function, so it can be
+/// reused across compilations of similar modules.
+#[derive(Archive, RkyvDeserialize, RkyvSerialize)]
+pub struct SerializableFunction {
+    /// The compiled function
+    pub function: CompiledFunction,
+    /// The offset of the function body in the module it was compiled from.
+    ///
+    /// The source locations of the function are relative to the start of
+    /// that module.
+    pub module_offset: u64,
+    /// The DWARF unwind information of the function: an `.eh_frame`
+    /// section holding a CIE and the FDE of the function, if the compiler
+    /// emits one.
+    pub eh_frame: Option<CustomSection>,
+}
+
+impl SerializableFunction {
+    /// Serialize a function into bytes
+    /// The bytes will have the following format:
+    /// RKYV serialization (any length) + POS (8 bytes)
+    pub fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        let mut serializer = AllocSerializer::<4096>::default();
+        let pos = serializer
+            .serialize_value(self)
+            .map_err(to_serialize_error)? as u64;
+        let mut serialized_data = serializer.into_serializer().into_inner();
+        serialized_data.extend_from_slice(&pos.to_le_bytes());
+        Ok(serialized_data.to_vec())
+    }
+
+    /// Deserialize a function from a slice.
+    /// The slice must have the following format:
+    /// RKYV serialization (any length) + POS (8 bytes)
+    ///
+    /// # Safety
+    ///
+    /// This method is unsafe since it deserializes data directly
+    /// from memory, without validating it.
+    /// Please check `SerializableModule::deserialize` for more details.
+    pub unsafe fn deserialize(slice: &[u8]) -> Result<Self, DeserializeError> {
+        if slice.len() < 8 {
+            return Err(DeserializeError::Incompatible(
+                "invalid serialized data".into(),
+            ));
+        }
+        let mut pos: [u8; 8] = Default::default();
+        pos.copy_from_slice(&slice[slice.len() - 8..slice.len()]);
+        let pos: u64 = u64::from_le_bytes(pos);
+        let archived = archived_value::<Self>(&slice[..slice.len() - 8], pos as usize);
+        let mut deserializer = SharedDeserializeMap::new();
+        RkyvDeserialize::deserialize(archived, &mut deserializer)
+            .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))
+    }
+}
+
 /// Metadata header which holds an ABI version and the length of the remaining
 /// metadata.
 #[repr(C)]
@@ -178,7 +234,7 @@ pub struct MetadataHeader {
 impl MetadataHeader {
     /// Current ABI version. Increment this any time breaking changes are made
     /// to the format of the serialized data.
-    const CURRENT_VERSION: u32 = 1;
+    const CURRENT_VERSION: u32 = 3;
 
     /// Magic number to identify wasmer metadata.
     const MAGIC: [u8; 8] = *b"WASMER\0\0";
@@ -1,4 +1,4 @@
-use crate::indexes::{FunctionIndex, GlobalIndex};
+use crate::indexes::{ConstExprIndex, FunctionIndex, GlobalIndex};
 use crate::lib::std::borrow::ToOwned;
 use crate::lib::std::fmt;
 use crate::lib::std::format;
@@ -430,6 +430,9 @@ pub enum GlobalInit {
     RefNullConst,
     /// A `ref.func <index>`.
     RefFunc(FunctionIndex),
+    /// An extended constant expression, stored in
+    /// [`ModuleInfo::const_exprs`](crate::ModuleInfo::const_exprs).
+    Expr(ConstExprIndex),
 }
 
 // Table Types
@@ -5,6 +5,7 @@ use thiserror::Error;
 
 pub use wasmer_vfs::FileDescriptor;
 pub use wasmer_vfs::StdioMode;
+use wasmer_vfs::VirtualFile;
 
 pub type Result<T> = std::result::Result<T, BusError>;
 
@@ -212,6 +213,24 @@ pub trait VirtualBusProcess:
 
     /// Returns a file descriptor used to write to STDERR
     fn stderr_fd(&self) -> Option<FileDescriptor>;
+
+    /// Takes the end of the STDIN pipe written by the caller, for the
+    /// processes which don't hand out file descriptors of the caller
+    fn take_stdin(&mut self) -> Option<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        None
+    }
+
+    /// Takes the end of the STDOUT pipe read by the caller, for the
+    /// processes which don't hand out file descriptors of the caller
+    fn take_stdout(&mut self) -> Option<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        None
+    }
+
+    /// Takes the end of the STDERR pipe read by the caller, for the
+    /// processes which don't hand out file descriptors of the caller
+    fn take_stderr(&mut self) -> Option<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        None
+    }
 }
 
 pub trait VirtualBusInvocation:
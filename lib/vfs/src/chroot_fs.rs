@@ -0,0 +1,330 @@
+//! A view of a directory of another file system as a whole file system.
+//!
+//! The paths are resolved component by component within the view: `..`
+//! stops at its root, and the symlinks are followed by the view itself,
+//! absolute targets starting from its root, so neither can lead out of
+//! it. The wrapped file system only ever sees paths below the root, free
+//! of symlinks except in the last component when an operation doesn't
+//! follow it.
+//!
+//! The path is resolved before the operation is made on the wrapped file
+//! system, in two steps, and the wrapped file system follows the
+//! symlinks again. A directory of the path replaced by a symlink in
+//! between is followed out of the view: by another process of the host,
+//! or by the module itself when it can create symlinks in the view and
+//! races the resolution from another thread. The view is only a boundary
+//! when nothing can create symlinks in it while it's used, like when it's
+//! read-only or when the module runs a single thread.
+
+use crate::{
+    DirEntry, FileOpener, FileSystem, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir,
+    Result, VirtualFile, MAX_SYMLINKS,
+};
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// A file system exposing the directory `root` of another file system,
+/// and nothing outside of it.
+///
+/// Typically used to confine a [`host_fs::FileSystem`](crate::host_fs::FileSystem)
+/// to a directory of the host:
+///
+/// ```
+/// # #[cfg(feature = "host-fs")]
+/// # {
+/// use wasmer_vfs::{chroot_fs::ChrootFileSystem, host_fs};
+///
+/// let fs = ChrootFileSystem::new("/srv/guest", host_fs::FileSystem);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChrootFileSystem {
+    root: PathBuf,
+    inner: Arc<dyn FileSystem>,
+}
+
+impl ChrootFileSystem {
+    /// Creates a file system exposing `root` of `inner` as its root.
+    pub fn new(root: impl Into<PathBuf>, inner: impl FileSystem) -> Self {
+        Self::from_boxed(root, Box::new(inner))
+    }
+
+    /// Like [`ChrootFileSystem::new`] with a boxed file system.
+    pub fn from_boxed(root: impl Into<PathBuf>, inner: Box<dyn FileSystem>) -> Self {
+        Self {
+            root: root.into(),
+            inner: Arc::from(inner),
+        }
+    }
+
+    /// The directory of the wrapped file system exposed as the root.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The wrapped file system.
+    pub fn inner(&self) -> &dyn FileSystem {
+        self.inner.as_ref()
+    }
+
+    /// The path in the wrapped file system of the resolved `path`.
+    fn inner_path(&self, path: &Path) -> PathBuf {
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Resolves `path` into an absolute path of the view without `.`, `..`
+    /// or symlinks, except a symlink in the last component unless
+    /// `follow_last` is set.
+    fn resolve(&self, path: &Path, follow_last: bool) -> Result<PathBuf> {
+        let mut resolved = PathBuf::from("/");
+        let mut steps = Step::of(path).collect::<VecDeque<_>>();
+        let mut followed = 0;
+
+        while let Some(step) = steps.pop_front() {
+            let name = match step {
+                Step::Parent => {
+                    resolved.pop();
+                    continue;
+                }
+                Step::Name(name) => name,
+            };
+
+            let candidate = resolved.join(name);
+            if steps.is_empty() && !follow_last {
+                resolved = candidate;
+                continue;
+            }
+
+            // File systems without symlinks fail to read them.
+            match self.inner.readlink(&self.inner_path(&candidate)) {
+                Ok(target) => {
+                    followed += 1;
+                    if followed > MAX_SYMLINKS {
+                        return Err(FsError::SymlinkLoop);
+                    }
+                    if target.has_root() {
+                        resolved = PathBuf::from("/");
+                    }
+                    for step in Step::of(&target).collect::<Vec<_>>().into_iter().rev() {
+                        steps.push_front(step);
+                    }
+                }
+                Err(_) => resolved = candidate,
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    fn resolve_inner(&self, path: &Path, follow_last: bool) -> Result<PathBuf> {
+        self.resolve(path, follow_last)
+            .map(|resolved| self.inner_path(&resolved))
+    }
+}
+
+/// A component of a path left to resolve.
+enum Step {
+    Parent,
+    Name(OsString),
+}
+
+impl Step {
+    fn of(path: &Path) -> impl Iterator<Item = Step> + '_ {
+        path.components().filter_map(|component| match component {
+            Component::Normal(name) => Some(Step::Name(name.to_os_string())),
+            Component::ParentDir => Some(Step::Parent),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => None,
+        })
+    }
+}
+
+impl FileSystem for ChrootFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let resolved = self.resolve(path, true)?;
+
+        let entries = self
+            .inner
+            .read_dir(&self.inner_path(&resolved))?
+            .map(|entry| {
+                let entry = entry?;
+                let name = entry.path.file_name().ok_or(FsError::InvalidData)?;
+
+                Ok(DirEntry {
+                    path: path.join(name),
+                    metadata: entry.metadata,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(&self.resolve_inner(path, false)?)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.inner.remove_dir(&self.resolve_inner(path, false)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(
+            &self.resolve_inner(from, false)?,
+            &self.resolve_inner(to, false)?,
+        )
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(&self.resolve_inner(path, true)?)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner
+            .symlink_metadata(&self.resolve_inner(path, false)?)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        // The target is kept as is, it's only ever followed by the view.
+        self.inner
+            .symlink(target, &self.resolve_inner(link, false)?)
+    }
+
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(&self.resolve_inner(path, false)?)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.inner.remove_file(&self.resolve_inner(path, false)?)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(ChrootFileOpener {
+            filesystem: self.clone(),
+        }))
+    }
+}
+
+/// Opens the files of a [`ChrootFileSystem`].
+#[derive(Debug, Clone)]
+pub struct ChrootFileOpener {
+    filesystem: ChrootFileSystem,
+}
+
+impl FileOpener for ChrootFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let fs = &self.filesystem;
+
+        fs.inner
+            .new_open_options()
+            .options(conf.clone())
+            .open(&fs.resolve_inner(path, true)?)
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_chroot_fs {
+    use super::*;
+    use crate::mem_fs;
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    fn chroot_fs() -> (mem_fs::FileSystem, ChrootFileSystem) {
+        let inner = mem_fs::FileSystem::default();
+        inner.create_dir(path!("/jail")).unwrap();
+        inner
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/secret.txt"))
+            .unwrap()
+            .write_all(b"secret")
+            .unwrap();
+        inner
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/jail/secret.txt"))
+            .unwrap()
+            .write_all(b"jailed")
+            .unwrap();
+
+        let fs = ChrootFileSystem::new("/jail", inner.clone());
+        (inner, fs)
+    }
+
+    fn read(fs: &dyn FileSystem, path: &Path) -> String {
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_parent_dir() {
+        let (inner, fs) = chroot_fs();
+
+        assert_eq!(read(&fs, path!("/../../secret.txt")), "jailed");
+        assert_eq!(read(&fs, path!("secret.txt")), "jailed");
+
+        fs.create_dir(path!("/../dir")).unwrap();
+        assert!(inner.metadata(path!("/jail/dir")).unwrap().is_dir());
+        assert!(inner
+            .read_dir(path!("/"))
+            .unwrap()
+            .all(|entry| entry.unwrap().path != Path::new("/dir")));
+
+        let entries = fs
+            .read_dir(path!("/"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .collect::<Vec<_>>();
+        assert!(entries.contains(&PathBuf::from("/dir")));
+        assert!(entries.contains(&PathBuf::from("/secret.txt")));
+    }
+
+    #[test]
+    fn test_symlinks() {
+        let (inner, fs) = chroot_fs();
+
+        fs.symlink(path!("/secret.txt"), path!("/absolute"))
+            .unwrap();
+        fs.symlink(path!("../../secret.txt"), path!("/relative"))
+            .unwrap();
+        inner
+            .symlink(path!("/secret.txt"), path!("/jail/planted"))
+            .unwrap();
+
+        assert_eq!(read(&fs, path!("/absolute")), "jailed");
+        assert_eq!(read(&fs, path!("/relative")), "jailed");
+        assert_eq!(
+            read(&fs, path!("/planted")),
+            "jailed",
+            "absolute targets start from the root of the view",
+        );
+        assert_eq!(
+            fs.readlink(path!("/relative")),
+            Ok(PathBuf::from("../../secret.txt")),
+        );
+
+        fs.symlink(path!("loop"), path!("/loop")).unwrap();
+        assert_eq!(
+            fs.metadata(path!("/loop")).unwrap_err(),
+            FsError::SymlinkLoop,
+        );
+    }
+}
@@ -113,6 +113,26 @@ impl crate::FileSystem for FileSystem {
             .and_then(TryInto::try_into)
             .map_err(Into::into)
     }
+
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(target, link).map_err(Into::into)
+        }
+        #[cfg(windows)]
+        {
+            std::os::windows::fs::symlink_file(target, link).map_err(Into::into)
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            let _ = (target, link);
+            Err(FsError::PermissionDenied)
+        }
+    }
+
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        fs::read_link(path).map_err(Into::into)
+    }
 }
 
 impl TryInto<Metadata> for fs::Metadata {
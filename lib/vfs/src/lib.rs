@@ -11,10 +11,15 @@ compile_error!("At least the `host-fs` or the `mem-fs` feature must be enabled.
 //#[cfg(all(feature = "mem-fs", feature = "enable-serde"))]
 //compile_warn!("`mem-fs` does not support `enable-serde` for the moment.");
 
+pub mod chroot_fs;
 #[cfg(feature = "host-fs")]
 pub mod host_fs;
 #[cfg(feature = "mem-fs")]
 pub mod mem_fs;
+pub mod mount_fs;
+pub mod overlay_fs;
+pub mod quota_fs;
+pub mod read_only_fs;
 #[cfg(feature = "static-fs")]
 pub mod static_fs;
 #[cfg(feature = "webc-fs")]
@@ -22,6 +27,10 @@ pub mod webc_fs;
 
 pub type Result<T> = std::result::Result<T, FsError>;
 
+/// The maximum number of symlinks followed while resolving a path,
+/// beyond which the path is considered to contain a loop.
+pub(crate) const MAX_SYMLINKS: usize = 40;
+
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct FileDescriptor(usize);
@@ -44,17 +53,65 @@ pub trait FileSystem: fmt::Debug + Send + Sync + 'static + Upcastable {
     fn remove_dir(&self, path: &Path) -> Result<()>;
     fn rename(&self, from: &Path, to: &Path) -> Result<()>;
     fn metadata(&self, path: &Path) -> Result<Metadata>;
-    /// This method gets metadata without following symlinks in the path.
-    /// Currently identical to `metadata` because symlinks aren't implemented
-    /// yet.
+    /// This method gets metadata without following a symlink in the last
+    /// component of the path. Identical to `metadata` by default, for file
+    /// systems that don't implement symlinks.
     fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
         self.metadata(path)
     }
+    /// Creates a symlink at `link` pointing to `target`. `target` is
+    /// stored as is, a relative target being resolved from the directory
+    /// containing the link.
+    fn symlink(&self, _target: &Path, _link: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+    /// Reads the target of the symlink at `path`.
+    fn readlink(&self, _path: &Path) -> Result<PathBuf> {
+        Err(FsError::InvalidInput)
+    }
     fn remove_file(&self, path: &Path) -> Result<()>;
 
     fn new_open_options(&self) -> OpenOptions;
 }
 
+/// A file system shared between several users, like the processes of a
+/// same sandbox.
+impl<T> FileSystem for std::sync::Arc<T>
+where
+    T: FileSystem + ?Sized,
+{
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        (**self).read_dir(path)
+    }
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        (**self).create_dir(path)
+    }
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        (**self).remove_dir(path)
+    }
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        (**self).rename(from, to)
+    }
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        (**self).metadata(path)
+    }
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        (**self).symlink_metadata(path)
+    }
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        (**self).symlink(target, link)
+    }
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        (**self).readlink(path)
+    }
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        (**self).remove_file(path)
+    }
+    fn new_open_options(&self) -> OpenOptions {
+        (**self).new_open_options()
+    }
+}
+
 impl dyn FileSystem + 'static {
     #[inline]
     pub fn downcast_ref<T: 'static>(&'_ self) -> Option<&'_ T> {
@@ -367,6 +424,18 @@ pub enum FsError {
     /// Directory not Empty
     #[error("directory not empty")]
     DirectoryNotEmpty,
+    /// Too many symlinks were encountered while resolving a path
+    #[error("too many levels of symbolic links")]
+    SymlinkLoop,
+    /// Some operation, like a rename, crosses two mounted file systems
+    #[error("cross-device link")]
+    CrossDevice,
+    /// Some quota on the bytes or the inodes of the file system is exceeded
+    #[error("disk quota exceeded")]
+    QuotaExceeded,
+    /// A file would grow beyond the largest size it is allowed to have
+    #[error("file too large")]
+    FileTooLarge,
     /// Some other unhandled error. If you see this, it's probably a bug.
     #[error("unknown error found")]
     UnknownError,
@@ -399,6 +468,26 @@ impl From<io::Error> for FsError {
     }
 }
 
+/// Normalizes `path` lexically into an absolute path, resolving `.` and
+/// `..` without looking at any file system.
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+
+    for component in path.components() {
+        match component {
+            std::path::Component::Normal(name) => normalized.push(name),
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            std::path::Component::RootDir
+            | std::path::Component::CurDir
+            | std::path::Component::Prefix(_) => {}
+        }
+    }
+
+    normalized
+}
+
 #[derive(Debug)]
 pub struct ReadDir {
     // TODO: to do this properly we need some kind of callback to the core FS abstraction
@@ -0,0 +1,319 @@
+//! Exporting a [`FileSystem`] to archives, and importing it back.
+
+use super::*;
+use crate::{normalize_path, FileSystem as FS, FsError, Metadata, Result};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+impl FileSystem {
+    /// Writes the whole file system as a tar archive to `writer`.
+    ///
+    /// Directories, files and symlinks are archived with their
+    /// modification times, with paths relative to the root.
+    pub fn export_tar<W: Write>(&self, writer: W) -> Result<()> {
+        let mut builder = tar::Builder::new(writer);
+
+        for (path, metadata) in self.walk()? {
+            let name = path.strip_prefix("/").map_err(|_| FsError::InvalidData)?;
+            let mut header = tar::Header::new_gnu();
+            header.set_mtime(metadata.modified);
+
+            if metadata.is_dir() {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, name, io::empty())?;
+            } else if metadata.file_type().is_symlink() {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                builder.append_link(&mut header, name, self.readlink(&path)?)?;
+            } else {
+                let contents = self.read_file(&path)?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(contents.len() as u64);
+                builder.append_data(&mut header, name, contents.as_slice())?;
+            }
+        }
+
+        builder.finish()?;
+
+        Ok(())
+    }
+
+    /// Reads the tar archive from `reader` into the file system.
+    ///
+    /// The entries are merged with the existing ones, the files of the
+    /// archive replacing the files at the same paths. Hard links and
+    /// special files are skipped, and the paths can't escape the root.
+    pub fn import_tar<R: Read>(&self, reader: R) -> Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        let mut modified = Vec::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = normalize_path(&entry.path()?);
+            if path == Path::new("/") {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                self.create_dir_all(parent)?;
+            }
+
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => self.create_dir_all(&path)?,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let mut file = self
+                        .new_open_options()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&path)?;
+                    io::copy(&mut entry, &mut file)?;
+                }
+                tar::EntryType::Symlink => {
+                    let target = entry.link_name()?.ok_or(FsError::InvalidData)?;
+                    if self.symlink_metadata(&path).is_ok() {
+                        self.remove_file(&path)?;
+                    }
+                    self.symlink(&target, &path)?;
+                }
+                _ => continue,
+            }
+
+            modified.push((path, entry.header().mtime()?));
+        }
+
+        // The times are set last, as adding entries to a directory
+        // touches it.
+        let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+        for (path, time) in modified.into_iter().rev() {
+            let inode = fs.inode_of_nofollow(&path)?;
+            let node = fs.storage.get_mut(inode).ok_or(FsError::UnknownError)?;
+            node.metadata_mut().modified = time;
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the whole file system as a webc volume.
+    ///
+    /// Webc volumes only contain directories and files: symlinks are
+    /// replaced by what they point to and the times are lost.
+    #[cfg(feature = "webc-fs")]
+    pub fn export_webc_volume(&self) -> Result<Vec<u8>> {
+        let mut files = std::collections::BTreeMap::new();
+
+        for (path, metadata) in self.walk()? {
+            let name = path
+                .strip_prefix("/")
+                .map_err(|_| FsError::InvalidData)?
+                .to_path_buf();
+
+            let metadata = if metadata.file_type().is_symlink() {
+                match self.metadata(&path) {
+                    Ok(metadata) => metadata,
+                    // Dangling symlinks are left out.
+                    Err(_) => continue,
+                }
+            } else {
+                metadata
+            };
+
+            if metadata.is_dir() {
+                files.insert(webc::DirOrFile::Dir(name), Vec::new());
+            } else {
+                files.insert(webc::DirOrFile::File(name), self.read_file(&path)?);
+            }
+        }
+
+        Ok(webc::Volume::serialize_files(files))
+    }
+
+    /// All the entries of the file system, parents first, without
+    /// following the symlinks.
+    fn walk(&self) -> Result<Vec<(PathBuf, Metadata)>> {
+        let mut entries = Vec::new();
+        let mut directories = vec![PathBuf::from("/")];
+
+        while let Some(directory) = directories.pop() {
+            let mut children = self
+                .read_dir(&directory)?
+                .map(|entry| {
+                    let path = entry?.path;
+                    let metadata = self.symlink_metadata(&path)?;
+                    Ok((path, metadata))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            children.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            for (path, metadata) in children.iter().rev() {
+                if metadata.is_dir() {
+                    directories.push(path.clone());
+                }
+            }
+            entries.extend(children);
+        }
+
+        Ok(entries)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        self.new_open_options()
+            .read(true)
+            .open(path)?
+            .read_to_end(&mut contents)?;
+
+        Ok(contents)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let mut ancestors = path.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            match self.metadata(ancestor) {
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) => return Err(FsError::BaseNotDirectory),
+                Err(_) => self.create_dir(ancestor)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_archive {
+    use crate::{mem_fs::*, FileSystem as FS};
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    #[test]
+    fn test_tar_round_trip() {
+        let fs = FileSystem::default();
+        fs.create_dir(path!("/foo")).unwrap();
+        fs.create_dir(path!("/foo/empty")).unwrap();
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap()
+            .write_all(b"bar")
+            .unwrap();
+        fs.symlink(path!("foo/bar.txt"), path!("/link")).unwrap();
+        {
+            let mut fs_inner = fs.inner.write().unwrap();
+            let inode = fs_inner.inode_of(path!("/foo/bar.txt")).unwrap();
+            fs_inner.storage[inode].metadata_mut().modified = 42;
+        }
+
+        let mut archive = Vec::new();
+        fs.export_tar(&mut archive).unwrap();
+
+        let imported = FileSystem::default();
+        imported.import_tar(archive.as_slice()).unwrap();
+
+        assert!(imported.metadata(path!("/foo/empty")).unwrap().is_dir());
+        let mut contents = String::new();
+        imported
+            .new_open_options()
+            .read(true)
+            .open(path!("/link"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "bar", "the symlink is restored");
+        assert_eq!(
+            imported.readlink(path!("/link")),
+            Ok(path!("foo/bar.txt").to_path_buf()),
+        );
+        assert_eq!(
+            imported.metadata(path!("/foo/bar.txt")).unwrap().modified,
+            42,
+            "the modification time is restored",
+        );
+    }
+
+    /// The paths of the entries of a webc volume, with the contents of
+    /// the files
+    #[cfg(feature = "webc-fs")]
+    fn volume_tree(
+        volume: &webc::Volume<'_>,
+        dir: &str,
+        tree: &mut Vec<(String, Option<Vec<u8>>)>,
+    ) {
+        for entry in volume.read_dir(dir).unwrap() {
+            let path = format!("{}/{}", dir.trim_end_matches('/'), &*entry.text);
+            if entry.fs_type == webc::FsEntryType::Dir {
+                tree.push((path.clone(), None));
+                volume_tree(volume, &path, tree);
+            } else {
+                let file = volume.get_file_entry(&path).unwrap();
+                let contents = volume.get_file_bytes(&file).unwrap().to_vec();
+                tree.push((path, Some(contents)));
+            }
+        }
+    }
+
+    #[cfg(feature = "webc-fs")]
+    #[test]
+    fn test_webc_volume_round_trip() {
+        let fs = FileSystem::default();
+        fs.create_dir(path!("/foo")).unwrap();
+        fs.create_dir(path!("/foo/empty")).unwrap();
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap()
+            .write_all(b"bar")
+            .unwrap();
+        fs.symlink(path!("foo/bar.txt"), path!("/link")).unwrap();
+        fs.symlink(path!("missing"), path!("/dangling")).unwrap();
+
+        let volume = fs.export_webc_volume().unwrap();
+        let volume = webc::Volume::parse(&volume).unwrap();
+        let mut tree = Vec::new();
+        volume_tree(&volume, "/", &mut tree);
+        tree.sort();
+
+        let file = |path: &str, contents: &[u8]| (path.to_string(), Some(contents.to_vec()));
+        let dir = |path: &str| (path.to_string(), None);
+        assert_eq!(
+            tree,
+            vec![
+                // The symlinks are replaced by what they point to, and the
+                // dangling one is left out
+                dir("/foo"),
+                file("/foo/bar.txt", b"bar"),
+                dir("/foo/empty"),
+                file("/link", b"bar"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tar_import_stays_in_root() {
+        let mut archive = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_entry_type(tar::EntryType::Regular);
+        // `append_data` refuses paths escaping the archive.
+        header.as_old_mut().name[..13].copy_from_slice(b"../escape.txt");
+        header.set_cksum();
+        archive.append(&header, &b"bar"[..]).unwrap();
+        let archive = archive.into_inner().unwrap();
+
+        let fs = FileSystem::default();
+        fs.import_tar(archive.as_slice()).unwrap();
+        assert!(fs.metadata(path!("/escape.txt")).unwrap().is_file());
+    }
+}
@@ -100,6 +100,10 @@ impl VirtualFile for FileHandle {
     }
 
     fn set_len(&mut self, new_size: u64) -> Result<()> {
+        if !self.writable {
+            return Err(FsError::PermissionDenied);
+        }
+
         let mut fs = self
             .filesystem
             .inner
@@ -193,7 +197,7 @@ impl VirtualFile for FileHandle {
 
 #[cfg(test)]
 mod test_virtual_file {
-    use crate::{mem_fs::*, FileDescriptor, FileSystem as FS};
+    use crate::{mem_fs::*, FileDescriptor, FileSystem as FS, FsError};
     use std::thread::sleep;
     use std::time::Duration;
 
@@ -302,6 +306,30 @@ mod test_virtual_file {
         assert_eq!(file.size(), 7, "file has a new length");
     }
 
+    #[test]
+    fn test_set_len_read_only() {
+        let fs = FileSystem::default();
+
+        let _ = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to open the file");
+
+        assert_eq!(
+            file.set_len(7),
+            Err(FsError::PermissionDenied),
+            "the file is not writable"
+        );
+        assert_eq!(file.size(), 0, "file has the same length");
+    }
+
     #[test]
     fn test_unlink() {
         let fs = FileSystem::default();
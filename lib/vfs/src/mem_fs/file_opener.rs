@@ -47,6 +47,15 @@ impl crate::FileOpener for FileOpener {
                 .try_read()
                 .map_err(|_| FsError::Lock)?;
 
+            // A _new_ file can't be created over a symlink, even a
+            // dangling one, as the last symlink is never followed then.
+            if create_new && fs.inode_of_nofollow(path).is_ok() {
+                return Err(FsError::AlreadyExists);
+            }
+
+            // Opening a symlink opens the file it points to.
+            let path = fs.follow_symlinks(path)?;
+
             // Check the path has a parent.
             let parent_of_path = path.parent().ok_or(FsError::BaseNotDirectory)?;
 
@@ -413,4 +422,31 @@ mod test_file_opener {
             "opening a file that already exists",
         );
     }
+
+    #[test]
+    fn test_creating_a_new_file_over_a_dangling_symlink() {
+        let fs = FileSystem::default();
+
+        assert_eq!(
+            fs.symlink(path!("missing.txt"), path!("/link.txt")),
+            Ok(()),
+            "creating a dangling symlink",
+        );
+
+        assert!(
+            matches!(
+                fs.new_open_options()
+                    .write(true)
+                    .create_new(true)
+                    .open(path!("/link.txt")),
+                Err(FsError::AlreadyExists),
+            ),
+            "creating a _new_ file over a dangling symlink",
+        );
+
+        assert!(
+            fs.metadata(path!("/missing.txt")).is_err(),
+            "the target of the symlink isn't created",
+        );
+    }
 }
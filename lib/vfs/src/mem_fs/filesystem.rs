@@ -254,6 +254,100 @@ impl crate::FileSystem for FileSystem {
             .clone())
     }
 
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        // Read lock.
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        Ok(fs
+            .storage
+            .get(fs.inode_of_nofollow(path)?)
+            .ok_or(FsError::UnknownError)?
+            .metadata()
+            .clone())
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        let (inode_of_parent, name_of_link) = {
+            // Read lock.
+            let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+            // Canonicalize the path without checking the path exists,
+            // because it's about to be created.
+            let path = fs.canonicalize_without_inode(link)?;
+
+            // Check the path has a parent.
+            let parent_of_path = path.parent().ok_or(FsError::BaseNotDirectory)?;
+
+            // Check the link name.
+            let name_of_link = path
+                .file_name()
+                .ok_or(FsError::InvalidInput)?
+                .to_os_string();
+
+            // Find the parent inode.
+            let inode_of_parent = fs.inode_of_parent(parent_of_path)?;
+
+            // Check nothing exists with the same name.
+            if fs
+                .as_parent_get_position_and_inode(inode_of_parent, &name_of_link)?
+                .is_some()
+            {
+                return Err(FsError::AlreadyExists);
+            }
+
+            (inode_of_parent, name_of_link)
+        };
+
+        {
+            // Write lock.
+            let mut fs = self.inner.try_write().map_err(|_| FsError::Lock)?;
+
+            // Creating the symlink in the storage.
+            let inode_of_link = fs.storage.vacant_entry().key();
+            let real_inode_of_link = fs.storage.insert(Node::Symlink {
+                inode: inode_of_link,
+                name: name_of_link,
+                target: target.to_path_buf(),
+                metadata: {
+                    let time = time();
+
+                    Metadata {
+                        ft: FileType {
+                            symlink: true,
+                            ..Default::default()
+                        },
+                        accessed: time,
+                        created: time,
+                        modified: time,
+                        len: target.as_os_str().len() as u64,
+                    }
+                },
+            });
+
+            assert_eq!(
+                inode_of_link, real_inode_of_link,
+                "new symlink inode should have been correctly calculated",
+            );
+
+            // Adding the new symlink to its parent.
+            fs.add_child_to_node(inode_of_parent, inode_of_link)?;
+        }
+
+        Ok(())
+    }
+
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        // Read lock.
+        let fs = self.inner.try_read().map_err(|_| FsError::Lock)?;
+
+        let path = fs.canonicalize_without_inode(path)?;
+
+        match fs.storage.get(fs.inode_of_nofollow(&path)?) {
+            Some(Node::Symlink { target, .. }) => Ok(target.clone()),
+            _ => Err(FsError::InvalidInput),
+        }
+    }
+
     fn remove_file(&self, path: &Path) -> Result<()> {
         let (inode_of_parent, position, inode_of_file) = {
             // Read lock.
@@ -320,18 +414,37 @@ pub(super) struct FileSystemInner {
 }
 
 impl FileSystemInner {
-    /// Get the inode associated to a path if it exists.
+    /// Get the inode associated to a path if it exists, following
+    /// symlinks.
     pub(super) fn inode_of(&self, path: &Path) -> Result<Inode> {
+        self.resolve(path, true, &mut 0)
+    }
+
+    /// Like `Self::inode_of` but a symlink in the last component of the
+    /// path is not followed, so its own inode is returned.
+    pub(super) fn inode_of_nofollow(&self, path: &Path) -> Result<Inode> {
+        self.resolve(path, false, &mut 0)
+    }
+
+    /// Walk `path` from the root. Symlinks in the intermediate
+    /// components are always followed, the one in the last component
+    /// only if `follow_last` is true. `followed` counts the symlinks
+    /// followed so far, to detect loops.
+    fn resolve(&self, path: &Path, follow_last: bool, followed: &mut usize) -> Result<Inode> {
         // SAFETY: The root node always exists, so it's safe to unwrap here.
         let mut node = self.storage.get(ROOT_INODE).unwrap();
-        let mut components = path.components();
+        let mut components = path.components().peekable();
 
         match components.next() {
             Some(Component::RootDir) | None => {}
             _ => return Err(FsError::BaseNotDirectory),
         }
 
-        for component in components {
+        // The path of the directory containing `node`, to resolve
+        // relative symlinks from.
+        let mut path_of_node = PathBuf::from("/");
+
+        while let Some(component) = components.next() {
             node = match node {
                 Node::Directory { children, .. } => children
                     .iter()
@@ -340,11 +453,51 @@ impl FileSystemInner {
                     .ok_or(FsError::NotAFile)?,
                 _ => return Err(FsError::BaseNotDirectory),
             };
+
+            match node {
+                Node::Symlink { target, .. } if follow_last || components.peek().is_some() => {
+                    *followed += 1;
+                    if *followed > MAX_SYMLINKS {
+                        return Err(FsError::SymlinkLoop);
+                    }
+
+                    // An absolute target replaces the path of the directory.
+                    let target = self.canonicalize_without_inode(&path_of_node.join(target))?;
+                    node = self
+                        .storage
+                        .get(self.resolve(&target, true, followed)?)
+                        .ok_or(FsError::UnknownError)?;
+                    path_of_node = target;
+                }
+                _ => path_of_node.push(component),
+            }
         }
 
         Ok(node.inode())
     }
 
+    /// Follow `path` as long as it names a symlink, and return the path
+    /// of the first node that isn't one (which may not exist).
+    pub(super) fn follow_symlinks(&self, path: &Path) -> Result<PathBuf> {
+        let mut path = path.to_path_buf();
+
+        for _ in 0..MAX_SYMLINKS {
+            let target = match self
+                .inode_of_nofollow(&path)
+                .ok()
+                .and_then(|inode| self.storage.get(inode))
+            {
+                Some(Node::Symlink { target, .. }) => target,
+                _ => return Ok(path),
+            };
+            let parent_of_path = path.parent().ok_or(FsError::BaseNotDirectory)?;
+
+            path = self.canonicalize_without_inode(&parent_of_path.join(target))?;
+        }
+
+        Err(FsError::SymlinkLoop)
+    }
+
     /// Get the inode associated to a “parent path”. The returned
     /// inode necessarily represents a directory.
     pub(super) fn inode_of_parent(&self, parent_path: &Path) -> Result<Inode> {
@@ -393,7 +546,8 @@ impl FileSystemInner {
     }
 
     /// From the inode of a parent node (so, a directory), returns the
-    /// child index of `name_of_file` along with its inode.
+    /// child index of `name_of_file` along with its inode. A symlink
+    /// counts as a file, it isn't followed.
     pub(super) fn as_parent_get_position_and_inode_of_file(
         &self,
         inode_of_parent: Inode,
@@ -405,7 +559,9 @@ impl FileSystemInner {
                 .enumerate()
                 .filter_map(|(nth, inode)| self.storage.get(*inode).map(|node| (nth, node)))
                 .find_map(|(nth, node)| match node {
-                    Node::File { inode, name, .. } if name.as_os_str() == name_of_file => {
+                    Node::File { inode, name, .. } | Node::Symlink { inode, name, .. }
+                        if name.as_os_str() == name_of_file =>
+                    {
                         Some(Some((nth, *inode)))
                     }
 
@@ -420,8 +576,8 @@ impl FileSystemInner {
 
     /// From the inode of a parent node (so, a directory), returns the
     /// child index of `name_of` along with its inode, whatever the
-    /// type of inode is (directory or file).
-    fn as_parent_get_position_and_inode(
+    /// type of inode is (directory, file or symlink).
+    pub(super) fn as_parent_get_position_and_inode(
         &self,
         inode_of_parent: Inode,
         name_of: &OsString,
@@ -432,7 +588,9 @@ impl FileSystemInner {
                 .enumerate()
                 .filter_map(|(nth, inode)| self.storage.get(*inode).map(|node| (nth, node)))
                 .find_map(|(nth, node)| match node {
-                    Node::File { inode, name, .. } | Node::Directory { inode, name, .. }
+                    Node::File { inode, name, .. }
+                    | Node::Directory { inode, name, .. }
+                    | Node::Symlink { inode, name, .. }
                         if name.as_os_str() == name_of =>
                     {
                         Some(Some((nth, *inode)))
@@ -586,6 +744,7 @@ impl fmt::Debug for FileSystemInner {
                     ty = match node {
                         Node::File { .. } => "file",
                         Node::Directory { .. } => "dir",
+                        Node::Symlink { .. } => "link",
                     },
                     name = node.name().to_string_lossy(),
                     indentation_symbol = " ",
@@ -1373,6 +1532,103 @@ mod test_filesystem {
             "canonicalizing a crazily stupid path name",
         );
     }
+
+    #[test]
+    fn test_symlink() {
+        let fs = FileSystem::default();
+
+        assert_eq!(fs.create_dir(path!("/foo")), Ok(()), "creating `foo`");
+        assert!(
+            matches!(
+                fs.new_open_options()
+                    .write(true)
+                    .create_new(true)
+                    .open(path!("/foo/hello.txt")),
+                Ok(_)
+            ),
+            "creating `hello.txt`",
+        );
+
+        assert_eq!(
+            fs.symlink(path!("foo"), path!("/bar")),
+            Ok(()),
+            "creating a relative symlink to a directory",
+        );
+        assert_eq!(
+            fs.symlink(path!("/foo/hello.txt"), path!("/foo/link.txt")),
+            Ok(()),
+            "creating an absolute symlink to a file",
+        );
+        assert_eq!(
+            fs.symlink(path!("foo"), path!("/bar")),
+            Err(FsError::AlreadyExists),
+            "creating a symlink that already exists",
+        );
+
+        assert_eq!(fs.readlink(path!("/bar")), Ok(path!(buf "foo")));
+        assert_eq!(
+            fs.readlink(path!("/foo")),
+            Err(FsError::InvalidInput),
+            "reading a directory as a symlink",
+        );
+
+        assert!(fs.metadata(path!("/bar")).unwrap().is_dir());
+        assert!(fs
+            .symlink_metadata(path!("/bar"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs.metadata(path!("/bar/link.txt")).unwrap().is_file());
+        assert!(fs
+            .symlink_metadata(path!("/bar/link.txt"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        assert!(
+            matches!(
+                fs.new_open_options()
+                    .read(true)
+                    .open(path!("/bar/link.txt")),
+                Ok(_)
+            ),
+            "opening a file through symlinks",
+        );
+
+        let mut entries = fs
+            .read_dir(path!("/bar"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![path!(buf "/bar/hello.txt"), path!(buf "/bar/link.txt")],
+            "reading a directory through a symlink",
+        );
+
+        assert_eq!(
+            fs.remove_file(path!("/foo/link.txt")),
+            Ok(()),
+            "removing a symlink",
+        );
+        assert!(
+            fs.metadata(path!("/foo/hello.txt")).is_ok(),
+            "the target of a removed symlink is left untouched",
+        );
+
+        assert_eq!(fs.symlink(path!("loop2"), path!("/loop1")), Ok(()));
+        assert_eq!(fs.symlink(path!("loop1"), path!("/loop2")), Ok(()));
+        assert_eq!(
+            fs.metadata(path!("/loop1")).unwrap_err(),
+            FsError::SymlinkLoop,
+            "following a symlink loop",
+        );
+        assert!(
+            fs.symlink_metadata(path!("/loop1")).is_ok(),
+            "a symlink loop can still be inspected",
+        );
+    }
 }
 
 #[allow(dead_code)] // The `No` variant.
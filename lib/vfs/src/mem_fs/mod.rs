@@ -1,3 +1,4 @@
+mod archive;
 mod file;
 mod file_opener;
 mod filesystem;
@@ -8,8 +9,9 @@ pub use file_opener::FileOpener;
 pub use filesystem::FileSystem;
 pub use stdio::{Stderr, Stdin, Stdout};
 
-use crate::Metadata;
+use crate::{Metadata, MAX_SYMLINKS};
 use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
 
 type Inode = usize;
 const ROOT_INODE: Inode = 0;
@@ -28,6 +30,12 @@ enum Node {
         children: Vec<Inode>,
         metadata: Metadata,
     },
+    Symlink {
+        inode: Inode,
+        name: OsString,
+        target: PathBuf,
+        metadata: Metadata,
+    },
 }
 
 impl Node {
@@ -35,6 +43,7 @@ impl Node {
         *match self {
             Self::File { inode, .. } => inode,
             Self::Directory { inode, .. } => inode,
+            Self::Symlink { inode, .. } => inode,
         }
     }
 
@@ -42,6 +51,7 @@ impl Node {
         match self {
             Self::File { name, .. } => name.as_os_str(),
             Self::Directory { name, .. } => name.as_os_str(),
+            Self::Symlink { name, .. } => name.as_os_str(),
         }
     }
 
@@ -49,6 +59,7 @@ impl Node {
         match self {
             Self::File { metadata, .. } => metadata,
             Self::Directory { metadata, .. } => metadata,
+            Self::Symlink { metadata, .. } => metadata,
         }
     }
 
@@ -56,6 +67,7 @@ impl Node {
         match self {
             Self::File { metadata, .. } => metadata,
             Self::Directory { metadata, .. } => metadata,
+            Self::Symlink { metadata, .. } => metadata,
         }
     }
 
@@ -63,6 +75,7 @@ impl Node {
         match self {
             Self::File { name, .. } => *name = new_name,
             Self::Directory { name, .. } => *name = new_name,
+            Self::Symlink { name, .. } => *name = new_name,
         }
     }
 }
@@ -0,0 +1,400 @@
+//! A file system made of other file systems mounted under path prefixes.
+//!
+//! Each path is dispatched to the file system mounted at its longest
+//! prefix, which sees the rest of the path as an absolute path of its own:
+//! with a file system mounted at `/tmp`, `/tmp/foo` is `/foo` in that file
+//! system. Directories that only exist as the ancestors of mount points
+//! are listed, but can't be modified.
+
+use crate::{
+    normalize_path, DirEntry, FileOpener, FileSystem, FileType, FsError, Metadata, OpenOptions,
+    OpenOptionsConfig, ReadDir, Result, VirtualFile,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// A file system dispatching every operation to the file system mounted
+/// at the longest prefix of its path.
+///
+/// ```
+/// # #[cfg(feature = "mem-fs")]
+/// # {
+/// use wasmer_vfs::{mem_fs, mount_fs::MountFileSystem};
+///
+/// let fs = MountFileSystem::new();
+/// fs.mount("/tmp", Box::new(mem_fs::FileSystem::default())).unwrap();
+/// fs.mount("/data", Box::new(mem_fs::FileSystem::default())).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MountFileSystem {
+    mounts: Arc<RwLock<BTreeMap<PathBuf, Arc<dyn FileSystem>>>>,
+}
+
+/// A path resolved to the file system mounted at its longest prefix.
+struct Resolved {
+    mount_point: PathBuf,
+    fs: Arc<dyn FileSystem>,
+    /// The path inside the mounted file system.
+    path: PathBuf,
+}
+
+impl MountFileSystem {
+    /// Creates a file system without anything mounted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mounts `fs` at `path`, which may be `/` to provide the directories
+    /// outside the other mount points.
+    ///
+    /// `fs` is seen from its root: `<path>/x` is `/x` in `fs`. A host file
+    /// system must be wrapped in a
+    /// [`ChrootFileSystem`](crate::chroot_fs::ChrootFileSystem) to only
+    /// expose one of its directories.
+    ///
+    /// Fails with [`FsError::AlreadyExists`] if something is already
+    /// mounted at `path`.
+    pub fn mount(&self, path: impl AsRef<Path>, fs: Box<dyn FileSystem>) -> Result<()> {
+        let mut mounts = self.mounts.write().map_err(|_| FsError::Lock)?;
+        let path = normalize_path(path.as_ref());
+        if mounts.contains_key(&path) {
+            return Err(FsError::AlreadyExists);
+        }
+        mounts.insert(path, Arc::from(fs));
+
+        Ok(())
+    }
+
+    /// Unmounts the file system mounted at `path` and returns it.
+    pub fn unmount(&self, path: impl AsRef<Path>) -> Result<Arc<dyn FileSystem>> {
+        self.mounts
+            .write()
+            .map_err(|_| FsError::Lock)?
+            .remove(&normalize_path(path.as_ref()))
+            .ok_or(FsError::EntityNotFound)
+    }
+
+    /// The mount points, in lexical order.
+    pub fn mount_points(&self) -> Vec<PathBuf> {
+        self.mounts
+            .read()
+            .map(|mounts| mounts.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn resolve(&self, path: &Path) -> Result<Resolved> {
+        let mounts = self.mounts.read().map_err(|_| FsError::Lock)?;
+        let path = normalize_path(path);
+
+        path.ancestors()
+            .find_map(|ancestor| {
+                let fs = mounts.get(ancestor)?;
+                let rest = path.strip_prefix(ancestor).ok()?;
+
+                Some(Resolved {
+                    mount_point: ancestor.to_path_buf(),
+                    fs: fs.clone(),
+                    path: Path::new("/").join(rest),
+                })
+            })
+            .ok_or(FsError::EntityNotFound)
+    }
+
+    /// The names of the entries of `path` that are mount points or lead to
+    /// one.
+    fn mount_entries(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mounts = self.mounts.read().map_err(|_| FsError::Lock)?;
+        let path = normalize_path(path);
+
+        let mut entries = mounts
+            .keys()
+            .filter_map(|mount_point| {
+                let rest = mount_point.strip_prefix(&path).ok()?;
+                rest.iter().next().map(PathBuf::from)
+            })
+            .collect::<Vec<_>>();
+        entries.dedup();
+
+        Ok(entries)
+    }
+
+    /// Whether `path` is a mount point or one of its ancestors, which
+    /// can't be removed or renamed.
+    fn is_mount_path(&self, path: &Path) -> Result<bool> {
+        let mounts = self.mounts.read().map_err(|_| FsError::Lock)?;
+        let path = normalize_path(path);
+
+        Ok(mounts
+            .keys()
+            .any(|mount_point| mount_point.starts_with(&path)))
+    }
+
+    fn mount_point_metadata() -> Metadata {
+        Metadata {
+            ft: FileType {
+                dir: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl FileSystem for MountFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let mount_entries = self.mount_entries(path)?;
+        let mut entries = BTreeMap::new();
+
+        match self.resolve(path) {
+            Ok(resolved) => match resolved.fs.read_dir(&resolved.path) {
+                Ok(read_dir) => {
+                    for entry in read_dir {
+                        let entry = entry?;
+                        let name = entry.path.file_name().ok_or(FsError::InvalidData)?;
+                        entries.insert(
+                            name.to_os_string(),
+                            DirEntry {
+                                path: path.join(name),
+                                metadata: entry.metadata,
+                            },
+                        );
+                    }
+                }
+                Err(error) if mount_entries.is_empty() => return Err(error),
+                Err(_) => {}
+            },
+            Err(error) if mount_entries.is_empty() => return Err(error),
+            Err(_) => {}
+        }
+
+        // Mount points shadow the entries of the file system they are on.
+        for name in mount_entries {
+            let path = path.join(&name);
+            let metadata = self.metadata(&path);
+            entries.insert(name.into_os_string(), DirEntry { path, metadata });
+        }
+
+        Ok(ReadDir::new(entries.into_values().collect()))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        if self.is_mount_path(path)? {
+            return Err(FsError::AlreadyExists);
+        }
+        let resolved = self.resolve(path)?;
+
+        resolved.fs.create_dir(&resolved.path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if self.is_mount_path(path)? {
+            return Err(FsError::PermissionDenied);
+        }
+        let resolved = self.resolve(path)?;
+
+        resolved.fs.remove_dir(&resolved.path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        if self.is_mount_path(from)? || self.is_mount_path(to)? {
+            return Err(FsError::PermissionDenied);
+        }
+        let from = self.resolve(from)?;
+        let to = self.resolve(to)?;
+        if from.mount_point != to.mount_point {
+            return Err(FsError::CrossDevice);
+        }
+
+        from.fs.rename(&from.path, &to.path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let metadata = self
+            .resolve(path)
+            .and_then(|resolved| resolved.fs.metadata(&resolved.path));
+
+        match metadata {
+            Err(_) if self.is_mount_path(path)? => Ok(Self::mount_point_metadata()),
+            metadata => metadata,
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        let metadata = self
+            .resolve(path)
+            .and_then(|resolved| resolved.fs.symlink_metadata(&resolved.path));
+
+        match metadata {
+            Err(_) if self.is_mount_path(path)? => Ok(Self::mount_point_metadata()),
+            metadata => metadata,
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        if self.is_mount_path(link)? {
+            return Err(FsError::AlreadyExists);
+        }
+        let resolved = self.resolve(link)?;
+
+        resolved.fs.symlink(target, &resolved.path)
+    }
+
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        let resolved = self.resolve(path)?;
+
+        resolved.fs.readlink(&resolved.path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.is_mount_path(path)? {
+            return Err(FsError::PermissionDenied);
+        }
+        let resolved = self.resolve(path)?;
+
+        resolved.fs.remove_file(&resolved.path)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(MountFileOpener {
+            filesystem: self.clone(),
+        }))
+    }
+}
+
+/// Opens the files of a [`MountFileSystem`] with the file system they are
+/// mounted on.
+#[derive(Debug, Clone)]
+pub struct MountFileOpener {
+    filesystem: MountFileSystem,
+}
+
+impl FileOpener for MountFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let resolved = self.filesystem.resolve(path)?;
+
+        resolved
+            .fs
+            .new_open_options()
+            .options(conf.clone())
+            .open(&resolved.path)
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_mount_fs {
+    use super::*;
+    use crate::mem_fs;
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    fn mount_fs() -> MountFileSystem {
+        let fs = MountFileSystem::new();
+        fs.mount("/", Box::new(mem_fs::FileSystem::default()))
+            .unwrap();
+        fs.mount("/tmp", Box::new(mem_fs::FileSystem::default()))
+            .unwrap();
+        fs.mount("/app/data", Box::new(mem_fs::FileSystem::default()))
+            .unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let fs = mount_fs();
+
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/tmp/foo.txt"))
+            .unwrap()
+            .write_all(b"foo")
+            .unwrap();
+        fs.create_dir(path!("/tmp/dir")).unwrap();
+
+        let tmp = fs.unmount("/tmp").unwrap();
+        assert!(
+            tmp.metadata(path!("/foo.txt")).unwrap().is_file(),
+            "the file is created in the file system mounted at `/tmp`",
+        );
+        assert!(tmp.metadata(path!("/dir")).unwrap().is_dir());
+        fs.mount("/tmp", Box::new(mem_fs::FileSystem::default()))
+            .unwrap();
+        assert_eq!(fs.read_dir(path!("/tmp")).unwrap().count(), 0);
+
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/app/data/bar.txt"))
+            .unwrap()
+            .write_all(b"bar")
+            .unwrap();
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/app/data/../data/bar.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "bar");
+        assert!(fs
+            .read_dir(path!("/"))
+            .unwrap()
+            .all(|entry| entry.unwrap().path != Path::new("/bar.txt")));
+    }
+
+    #[test]
+    fn test_mount_points() {
+        let fs = mount_fs();
+        fs.create_dir(path!("/etc")).unwrap();
+
+        let entries = fs
+            .read_dir(path!("/"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                PathBuf::from("/app"),
+                PathBuf::from("/etc"),
+                PathBuf::from("/tmp")
+            ],
+        );
+        assert!(fs.metadata(path!("/app")).unwrap().is_dir());
+
+        assert_eq!(
+            fs.remove_dir(path!("/tmp")),
+            Err(FsError::PermissionDenied),
+            "mount points can't be removed",
+        );
+        assert_eq!(fs.create_dir(path!("/app")), Err(FsError::AlreadyExists));
+        assert_eq!(
+            fs.mount("/tmp", Box::new(mem_fs::FileSystem::default())),
+            Err(FsError::AlreadyExists),
+        );
+    }
+
+    #[test]
+    fn test_rename_across_mounts() {
+        let fs = mount_fs();
+        fs.create_dir(path!("/tmp/foo")).unwrap();
+
+        assert_eq!(
+            fs.rename(path!("/tmp/foo"), path!("/app/data/foo")),
+            Err(FsError::CrossDevice),
+        );
+        assert_eq!(fs.rename(path!("/tmp/foo"), path!("/tmp/bar")), Ok(()));
+        assert!(fs.metadata(path!("/tmp/bar")).unwrap().is_dir());
+    }
+}
@@ -0,0 +1,720 @@
+//! A union of two file systems: a writable upper layer on top of a lower
+//! layer that is never modified.
+//!
+//! Lookups go to the upper layer first and fall back to the lower one.
+//! Modifying a file of the lower layer first copies it up to the upper
+//! layer, and deleting it records a whiteout that hides it (and, for a
+//! directory, everything below it) from the lower layer.
+//!
+//! Files of the lower layer opened without write access are never
+//! modified through their handle either: truncating or writing them is
+//! denied, and unlinking them records a whiteout.
+//!
+//! Relative paths are kept apart from absolute ones in the upper layer,
+//! under [`RELATIVE_ROOT`], since the lower layer (a host file system
+//! for instance) resolves them against a different directory. Their
+//! missing directories are created in the upper layer, and
+//! [`RELATIVE_ROOT`] can't be reached through absolute paths.
+
+use crate::read_only_fs::ReadOnlyFile;
+use crate::{
+    normalize_path, DirEntry, FileDescriptor, FileOpener, FileSystem, FsError, Metadata,
+    OpenOptions, OpenOptionsConfig, ReadDir, Result, VirtualFile,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// The directory of the upper layer holding the files created through
+/// relative paths. It is hidden from the listing of `/`, and absolute
+/// paths leading into it are denied.
+pub const RELATIVE_ROOT: &str = "/.overlay-relative";
+
+/// The path of `path` in the upper layer: normalized when absolute,
+/// and below [`RELATIVE_ROOT`] when relative.
+fn upper_path(path: &Path) -> Result<PathBuf> {
+    let normalized = normalize_path(path);
+
+    match normalized.strip_prefix("/") {
+        _ if path.has_root() && normalized.starts_with(RELATIVE_ROOT) => {
+            Err(FsError::PermissionDenied)
+        }
+        _ if path.has_root() => Ok(normalized),
+        Ok(relative) if relative.as_os_str().is_empty() => Ok(PathBuf::from(RELATIVE_ROOT)),
+        Ok(relative) => Ok(Path::new(RELATIVE_ROOT).join(relative)),
+        Err(_) => Ok(normalized),
+    }
+}
+
+/// A file system layering a writable `upper` file system on top of a
+/// read-only `lower` one.
+///
+/// Typically a [`mem_fs::FileSystem`](crate::mem_fs::FileSystem) on top
+/// of a packaged or host file system, so that a guest can modify files
+/// without touching the originals:
+///
+/// ```
+/// # #[cfg(all(feature = "host-fs", feature = "mem-fs"))]
+/// # {
+/// use wasmer_vfs::{host_fs, mem_fs, overlay_fs::OverlayFileSystem};
+///
+/// let fs = OverlayFileSystem::new(mem_fs::FileSystem::default(), host_fs::FileSystem);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OverlayFileSystem {
+    inner: Arc<OverlayFileSystemInner>,
+}
+
+#[derive(Debug)]
+struct OverlayFileSystemInner {
+    upper: Box<dyn FileSystem>,
+    lower: Box<dyn FileSystem>,
+    /// Upper layer paths hidden from the lower layer, along with
+    /// everything below them.
+    whiteouts: RwLock<HashSet<PathBuf>>,
+}
+
+impl OverlayFileSystem {
+    /// Creates a file system writing to `upper` on top of `lower`.
+    pub fn new(upper: impl FileSystem, lower: impl FileSystem) -> Self {
+        Self::from_boxed(Box::new(upper), Box::new(lower))
+    }
+
+    /// Like [`OverlayFileSystem::new`] with boxed file systems.
+    pub fn from_boxed(upper: Box<dyn FileSystem>, lower: Box<dyn FileSystem>) -> Self {
+        Self {
+            inner: Arc::new(OverlayFileSystemInner {
+                upper,
+                lower,
+                whiteouts: RwLock::new(HashSet::new()),
+            }),
+        }
+    }
+
+    /// The writable upper layer.
+    pub fn upper(&self) -> &dyn FileSystem {
+        self.inner.upper.as_ref()
+    }
+
+    /// The read-only lower layer.
+    pub fn lower(&self) -> &dyn FileSystem {
+        self.inner.lower.as_ref()
+    }
+
+    /// Whether `path` is hidden from the lower layer by a whiteout.
+    fn is_whited_out(&self, path: &Path) -> Result<bool> {
+        let whiteouts = self.inner.whiteouts.read().map_err(|_| FsError::Lock)?;
+        let path = upper_path(path)?;
+
+        Ok(path
+            .ancestors()
+            .any(|ancestor| whiteouts.contains(ancestor)))
+    }
+
+    fn add_whiteout(&self, path: &Path) -> Result<()> {
+        self.inner
+            .whiteouts
+            .write()
+            .map_err(|_| FsError::Lock)?
+            .insert(upper_path(path)?);
+
+        Ok(())
+    }
+
+    /// The metadata of `path` in the lower layer, if it isn't hidden.
+    fn lower_metadata(&self, path: &Path) -> Result<Metadata> {
+        if self.is_whited_out(path)? {
+            return Err(FsError::EntityNotFound);
+        }
+
+        self.inner.lower.symlink_metadata(path)
+    }
+
+    fn upper_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.upper.symlink_metadata(&upper_path(path)?)
+    }
+
+    /// Makes sure the directory `path` and its ancestors exist in the
+    /// upper layer, creating the ones only found in the lower layer.
+    ///
+    /// The relative directories, which the lower layer resolves against
+    /// a directory of its own, are created in the upper layer even when
+    /// the lower layer doesn't have them.
+    fn copy_up_directories(&self, path: &Path) -> Result<()> {
+        let mut ancestors = path.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            let upper = upper_path(ancestor)?;
+            match self.inner.upper.symlink_metadata(&upper) {
+                Ok(metadata) if metadata.is_dir() => continue,
+                Ok(_) => return Err(FsError::BaseNotDirectory),
+                Err(_) => {}
+            }
+
+            match self.lower_metadata(ancestor) {
+                Ok(metadata) if !metadata.is_dir() => return Err(FsError::BaseNotDirectory),
+                Err(_) if ancestor.has_root() => return Err(FsError::EntityNotFound),
+                _ => self.inner.upper.create_dir(&upper)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies `path` up from the lower layer, with its contents unless
+    /// `truncate` is set. Directories are copied recursively.
+    fn copy_up(&self, path: &Path, truncate: bool) -> Result<()> {
+        if self.upper_metadata(path).is_ok() {
+            return Ok(());
+        }
+
+        let metadata = self.lower_metadata(path)?;
+        if let Some(parent) = path.parent() {
+            self.copy_up_directories(parent)?;
+        }
+
+        if metadata.is_dir() {
+            self.copy_up_directories(path)?;
+            for entry in self.read_dir(path)? {
+                self.copy_up(&entry?.path, false)?;
+            }
+        } else if metadata.file_type().is_symlink() {
+            let target = self.inner.lower.readlink(path)?;
+            self.inner.upper.symlink(&target, &upper_path(path)?)?;
+        } else {
+            let mut contents = Vec::new();
+            if !truncate {
+                self.inner
+                    .lower
+                    .new_open_options()
+                    .read(true)
+                    .open(path)?
+                    .read_to_end(&mut contents)?;
+            }
+
+            self.inner
+                .upper
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&upper_path(path)?)?
+                .write_all(&contents)?;
+        }
+
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool> {
+        Ok(self.upper_metadata(path).is_ok() || self.lower_metadata(path).is_ok())
+    }
+}
+
+impl FileSystem for OverlayFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let upper = self.inner.upper.read_dir(&upper_path(path)?);
+        let lower = if self.is_whited_out(path)? {
+            Err(FsError::EntityNotFound)
+        } else {
+            self.inner.lower.read_dir(path)
+        };
+
+        let (upper, lower) = match (upper, lower) {
+            (Err(error), Err(_)) => return Err(error),
+            (upper, lower) => (upper.ok(), lower.ok()),
+        };
+
+        // The entries of the upper layer shadow the ones of the lower layer.
+        let mut entries = BTreeMap::new();
+        for entry in lower
+            .into_iter()
+            .flatten()
+            .chain(upper.into_iter().flatten())
+        {
+            let entry = entry?;
+            let name = entry.path.file_name().ok_or(FsError::InvalidData)?;
+            let path = path.join(name);
+            if upper_path(&path).is_err() {
+                continue;
+            }
+
+            if entry.metadata.is_ok() && self.exists(&path)? {
+                entries.insert(
+                    name.to_os_string(),
+                    DirEntry {
+                        path,
+                        metadata: entry.metadata,
+                    },
+                );
+            }
+        }
+
+        Ok(ReadDir::new(entries.into_values().collect()))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        if self.exists(path)? {
+            return Err(FsError::AlreadyExists);
+        }
+        if let Some(parent) = path.parent() {
+            self.copy_up_directories(parent)?;
+        }
+
+        self.inner.upper.create_dir(&upper_path(path)?)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if self.read_dir(path)?.next().is_some() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        if self.upper_metadata(path).is_ok() {
+            self.inner.upper.remove_dir(&upper_path(path)?)?;
+        }
+        if self.lower_metadata(path).is_ok() {
+            self.add_whiteout(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        if !self.exists(from)? {
+            return Err(FsError::EntityNotFound);
+        }
+        self.copy_up(from, false)?;
+        if let Some(parent) = to.parent() {
+            self.copy_up_directories(parent)?;
+        }
+
+        self.inner
+            .upper
+            .rename(&upper_path(from)?, &upper_path(to)?)?;
+
+        // Neither the source nor what the destination replaced may show
+        // through from the lower layer anymore.
+        if self.lower_metadata(from).is_ok() {
+            self.add_whiteout(from)?;
+        }
+        if self.lower_metadata(to).is_ok() {
+            self.add_whiteout(to)?;
+        }
+
+        Ok(())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        match self.inner.upper.metadata(&upper_path(path)?) {
+            Ok(metadata) => Ok(metadata),
+            Err(_) if !self.is_whited_out(path)? => self.inner.lower.metadata(path),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.upper_metadata(path)
+            .or_else(|_| self.lower_metadata(path))
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        if self.exists(link)? {
+            return Err(FsError::AlreadyExists);
+        }
+        if let Some(parent) = link.parent() {
+            self.copy_up_directories(parent)?;
+        }
+
+        self.inner.upper.symlink(target, &upper_path(link)?)
+    }
+
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        if self.upper_metadata(path).is_ok() {
+            return self.inner.upper.readlink(&upper_path(path)?);
+        }
+
+        self.lower_metadata(path)?;
+        self.inner.lower.readlink(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let in_upper = self.upper_metadata(path).is_ok();
+        let in_lower = self.lower_metadata(path).is_ok();
+        if !in_upper && !in_lower {
+            return Err(FsError::EntityNotFound);
+        }
+
+        if in_upper {
+            self.inner.upper.remove_file(&upper_path(path)?)?;
+        }
+        if in_lower {
+            self.add_whiteout(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(OverlayFileOpener {
+            filesystem: self.clone(),
+        }))
+    }
+}
+
+/// Opens the files of an [`OverlayFileSystem`], copying them up to the
+/// upper layer when they are opened for writing.
+#[derive(Debug, Clone)]
+pub struct OverlayFileOpener {
+    filesystem: OverlayFileSystem,
+}
+
+impl FileOpener for OverlayFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let fs = &self.filesystem;
+        let writes = conf.write() || conf.append() || conf.truncate();
+
+        if fs.upper_metadata(path).is_err() {
+            if fs.lower_metadata(path).is_ok() {
+                if conf.create_new() {
+                    return Err(FsError::AlreadyExists);
+                }
+                if !writes {
+                    let file = fs
+                        .inner
+                        .lower
+                        .new_open_options()
+                        .options(conf.clone())
+                        .open(path)?;
+                    return Ok(Box::new(OverlayFile {
+                        inner: Box::new(ReadOnlyFile::new(file)),
+                        filesystem: fs.clone(),
+                        path: path.to_path_buf(),
+                    }));
+                }
+                fs.copy_up(path, conf.truncate())?;
+            } else if let Some(parent) = path.parent() {
+                if conf.create() || conf.create_new() {
+                    fs.copy_up_directories(parent)?;
+                }
+            }
+        }
+
+        let file = fs
+            .inner
+            .upper
+            .new_open_options()
+            .options(conf.clone())
+            .open(&upper_path(path)?)?;
+        Ok(Box::new(OverlayFile {
+            inner: file,
+            filesystem: fs.clone(),
+            path: path.to_path_buf(),
+        }))
+    }
+}
+
+/// A file opened from an [`OverlayFileSystem`].
+///
+/// Unlinking it goes through the overlay, so that a file of the upper
+/// layer doesn't uncover the lower one it shadows, and a file of the
+/// lower layer is whited out instead of deleted. Files of the lower layer
+/// are wrapped in a [`ReadOnlyFile`] first.
+#[derive(Debug)]
+struct OverlayFile {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+    filesystem: OverlayFileSystem,
+    path: PathBuf,
+}
+
+impl VirtualFile for OverlayFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        self.inner.set_len(new_size)
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        self.filesystem.remove_file(&self.path)
+    }
+
+    fn sync_to_disk(&self) -> Result<()> {
+        self.inner.sync_to_disk()
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_write()
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+impl Read for OverlayFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for OverlayFile {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+impl Write for OverlayFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_overlay_fs {
+    use super::*;
+    use crate::mem_fs;
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    fn read(fs: &dyn FileSystem, path: &Path) -> Result<String> {
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path)?
+            .read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    fn write(fs: &dyn FileSystem, path: &Path, contents: &str) -> Result<()> {
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?
+            .write_all(contents.as_bytes())?;
+        Ok(())
+    }
+
+    fn names(fs: &dyn FileSystem, path: &Path) -> Vec<String> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    fn overlay() -> (OverlayFileSystem, mem_fs::FileSystem) {
+        let lower = mem_fs::FileSystem::default();
+        lower.create_dir(path!("/etc")).unwrap();
+        write(&lower, path!("/etc/hosts"), "localhost").unwrap();
+        write(&lower, path!("/etc/passwd"), "root").unwrap();
+
+        (
+            OverlayFileSystem::new(mem_fs::FileSystem::default(), lower.clone()),
+            lower,
+        )
+    }
+
+    #[test]
+    fn test_copy_up_on_write() {
+        let (fs, lower) = overlay();
+
+        assert_eq!(read(&fs, path!("/etc/hosts")), Ok("localhost".to_string()));
+
+        let mut file = fs
+            .new_open_options()
+            .append(true)
+            .open(path!("/etc/hosts"))
+            .unwrap();
+        file.write_all(b" guest").unwrap();
+        drop(file);
+
+        assert_eq!(
+            read(&fs, path!("/etc/hosts")),
+            Ok("localhost guest".to_string()),
+            "the overlay sees the modified file",
+        );
+        assert_eq!(
+            read(&lower, path!("/etc/hosts")),
+            Ok("localhost".to_string()),
+            "the lower layer is untouched",
+        );
+
+        write(&fs, path!("/etc/new"), "new").unwrap();
+        assert!(lower.metadata(path!("/etc/new")).is_err());
+        assert_eq!(names(&fs, path!("/etc")), vec!["hosts", "new", "passwd"]);
+    }
+
+    #[test]
+    fn test_whiteouts() {
+        let (fs, lower) = overlay();
+
+        assert_eq!(fs.remove_file(path!("/etc/passwd")), Ok(()));
+        assert!(fs.metadata(path!("/etc/passwd")).is_err());
+        assert!(lower.metadata(path!("/etc/passwd")).is_ok());
+        assert_eq!(names(&fs, path!("/etc")), vec!["hosts"]);
+
+        assert_eq!(
+            fs.remove_dir(path!("/etc")),
+            Err(FsError::DirectoryNotEmpty)
+        );
+        assert_eq!(fs.remove_file(path!("/etc/hosts")), Ok(()));
+        assert_eq!(fs.remove_dir(path!("/etc")), Ok(()));
+        assert!(fs.metadata(path!("/etc")).is_err());
+
+        // A directory recreated over a whiteout doesn't show the lower
+        // layer's contents again.
+        assert_eq!(fs.create_dir(path!("/etc")), Ok(()));
+        assert!(names(&fs, path!("/etc")).is_empty());
+    }
+
+    #[test]
+    fn test_rename() {
+        let (fs, lower) = overlay();
+
+        assert_eq!(fs.rename(path!("/etc"), path!("/config")), Ok(()));
+        assert!(fs.metadata(path!("/etc")).is_err());
+        assert_eq!(read(&fs, path!("/config/passwd")), Ok("root".to_string()));
+        assert_eq!(names(&fs, path!("/")), vec!["config"]);
+        assert!(lower.metadata(path!("/etc/passwd")).is_ok());
+    }
+
+    #[test]
+    fn test_lower_file_handles() {
+        let (fs, lower) = overlay();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/etc/hosts"))
+            .unwrap();
+        assert_eq!(file.set_len(0), Err(FsError::PermissionDenied));
+        assert!(file.write_all(b"guest").is_err());
+        assert_eq!(file.unlink(), Ok(()));
+        drop(file);
+
+        assert!(fs.metadata(path!("/etc/hosts")).is_err());
+        assert_eq!(
+            read(&lower, path!("/etc/hosts")),
+            Ok("localhost".to_string()),
+            "the lower layer is untouched",
+        );
+    }
+
+    #[test]
+    fn test_upper_file_unlink() {
+        let (fs, lower) = overlay();
+
+        write(&fs, path!("/etc/passwd"), "guest").unwrap();
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/etc/passwd"))
+            .unwrap();
+        assert_eq!(file.unlink(), Ok(()));
+        drop(file);
+
+        assert!(
+            fs.metadata(path!("/etc/passwd")).is_err(),
+            "the lower file doesn't show through again",
+        );
+        assert_eq!(read(&lower, path!("/etc/passwd")), Ok("root".to_string()));
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let (fs, lower) = overlay();
+
+        write(&fs, path!("./etc/hosts"), "relative").unwrap();
+        assert_eq!(read(&fs, path!("etc/hosts")), Ok("relative".to_string()));
+        assert_eq!(
+            read(&fs, path!("/etc/hosts")),
+            Ok("localhost".to_string()),
+            "relative paths don't collide with absolute ones",
+        );
+
+        assert_eq!(fs.remove_file(path!("/etc/hosts")), Ok(()));
+        assert_eq!(read(&fs, path!("etc/hosts")), Ok("relative".to_string()));
+        assert_eq!(names(&fs, path!("/")), vec!["etc"]);
+        assert_eq!(
+            read(&lower, path!("/etc/hosts")),
+            Ok("localhost".to_string())
+        );
+    }
+
+    #[test]
+    fn test_relative_root_is_unreachable() {
+        let (fs, _) = overlay();
+        write(&fs, path!("etc/hosts"), "relative").unwrap();
+
+        let relative_root = Path::new(RELATIVE_ROOT);
+        assert_eq!(
+            fs.metadata(relative_root).map(|_| ()),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.read_dir(relative_root).map(|_| ()),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            read(&fs, &relative_root.join("etc/hosts")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            write(&fs, &relative_root.join("etc/hosts"), "absolute"),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.create_dir(path!("/etc/../.overlay-relative/dir")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(read(&fs, path!("etc/hosts")), Ok("relative".to_string()));
+    }
+}
@@ -0,0 +1,581 @@
+//! A file system enforcing a quota on the bytes and the inodes stored in
+//! another one.
+//!
+//! Every file, directory and symlink created through the wrapper counts
+//! as an inode, and the bytes of the files are accounted as they grow,
+//! through writes as well as through [`VirtualFile::set_len`]. Going over
+//! a limit fails with [`FsError::QuotaExceeded`]. Mounting a quota file
+//! system in a [`MountFileSystem`](crate::mount_fs::MountFileSystem)
+//! bounds a single tree.
+
+use crate::{
+    FileDescriptor, FileOpener, FileSystem, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, Result, VirtualFile,
+};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The limits of a [`QuotaFileSystem`], `None` meaning unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaLimits {
+    /// The maximum number of bytes stored in the files.
+    pub max_bytes: Option<u64>,
+    /// The maximum number of files, directories and symlinks.
+    pub max_inodes: Option<u64>,
+}
+
+/// The resources used in a [`QuotaFileSystem`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    /// The number of bytes stored in the files.
+    pub bytes: u64,
+    /// The number of files, directories and symlinks.
+    pub inodes: u64,
+}
+
+#[derive(Debug, Default)]
+struct Accounting {
+    limits: QuotaLimits,
+    bytes: AtomicU64,
+    inodes: AtomicU64,
+}
+
+impl Accounting {
+    fn reserve(counter: &AtomicU64, limit: Option<u64>, amount: u64) -> Result<()> {
+        counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(amount)
+                    .filter(|total| limit.map_or(true, |limit| *total <= limit))
+            })
+            .map(|_| ())
+            .map_err(|_| FsError::QuotaExceeded)
+    }
+
+    fn release(counter: &AtomicU64, amount: u64) {
+        // Files that existed before the accounting started are released
+        // without having been reserved, hence the saturation.
+        let _ = counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+            Some(used.saturating_sub(amount))
+        });
+    }
+
+    fn reserve_bytes(&self, amount: u64) -> Result<()> {
+        Self::reserve(&self.bytes, self.limits.max_bytes, amount)
+    }
+
+    fn release_bytes(&self, amount: u64) {
+        Self::release(&self.bytes, amount)
+    }
+
+    fn reserve_inode(&self) -> Result<()> {
+        Self::reserve(&self.inodes, self.limits.max_inodes, 1)
+    }
+
+    fn release_inode(&self) {
+        Self::release(&self.inodes, 1)
+    }
+
+    /// Settles a reservation of `reserved` bytes once `actual` bytes were
+    /// really added. The reservation is the most a write can add, what the
+    /// file grew by beyond it comes from other handles, which account for
+    /// it themselves.
+    fn settle_bytes(&self, reserved: u64, actual: u64) {
+        self.release_bytes(reserved.saturating_sub(actual));
+    }
+}
+
+/// A file system enforcing [`QuotaLimits`] on the file system it wraps.
+///
+/// The usage starts at zero: what is already stored in the wrapped file
+/// system is only accounted after a call to
+/// [`QuotaFileSystem::count_existing`].
+///
+/// ```
+/// # #[cfg(feature = "mem-fs")]
+/// # {
+/// use wasmer_vfs::{mem_fs, quota_fs::{QuotaFileSystem, QuotaLimits}};
+///
+/// let fs = QuotaFileSystem::new(
+///     mem_fs::FileSystem::default(),
+///     QuotaLimits {
+///         max_bytes: Some(64 * 1024 * 1024),
+///         max_inodes: Some(1024),
+///     },
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct QuotaFileSystem {
+    inner: Arc<dyn FileSystem>,
+    accounting: Arc<Accounting>,
+}
+
+impl QuotaFileSystem {
+    /// Wraps `inner` with the given limits.
+    pub fn new(inner: impl FileSystem, limits: QuotaLimits) -> Self {
+        Self::from_boxed(Box::new(inner), limits)
+    }
+
+    /// Like [`QuotaFileSystem::new`] with a boxed file system.
+    pub fn from_boxed(inner: Box<dyn FileSystem>, limits: QuotaLimits) -> Self {
+        Self {
+            inner: Arc::from(inner),
+            accounting: Arc::new(Accounting {
+                limits,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// The wrapped file system.
+    pub fn inner(&self) -> &dyn FileSystem {
+        self.inner.as_ref()
+    }
+
+    /// The limits of this file system.
+    pub fn limits(&self) -> QuotaLimits {
+        self.accounting.limits
+    }
+
+    /// The resources currently used.
+    pub fn usage(&self) -> QuotaUsage {
+        QuotaUsage {
+            bytes: self.accounting.bytes.load(Ordering::SeqCst),
+            inodes: self.accounting.inodes.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Adds what is stored below the directory `path` of the wrapped file
+    /// system to the usage, without checking the limits.
+    pub fn count_existing(&self, path: &Path) -> Result<QuotaUsage> {
+        let mut usage = QuotaUsage::default();
+        let mut directories = vec![path.to_path_buf()];
+
+        while let Some(directory) = directories.pop() {
+            for entry in self.inner.read_dir(&directory)? {
+                let entry = entry?;
+                let metadata = self.inner.symlink_metadata(&entry.path)?;
+                usage.inodes += 1;
+                if metadata.is_dir() {
+                    directories.push(entry.path);
+                } else if metadata.is_file() {
+                    usage.bytes += metadata.len();
+                }
+            }
+        }
+
+        self.accounting
+            .bytes
+            .fetch_add(usage.bytes, Ordering::SeqCst);
+        self.accounting
+            .inodes
+            .fetch_add(usage.inodes, Ordering::SeqCst);
+
+        Ok(usage)
+    }
+
+    /// Releases the resources of a removed entry.
+    fn release_entry(&self, metadata: &Metadata) {
+        if metadata.is_file() {
+            self.accounting.release_bytes(metadata.len());
+        }
+        self.accounting.release_inode();
+    }
+}
+
+impl FileSystem for QuotaFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.accounting.reserve_inode()?;
+
+        self.inner.create_dir(path).map_err(|error| {
+            self.accounting.release_inode();
+            error
+        })
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.inner.remove_dir(path)?;
+        self.accounting.release_inode();
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let replaced = self.inner.symlink_metadata(to).ok();
+        self.inner.rename(from, to)?;
+
+        if let Some(replaced) = replaced {
+            if from != to {
+                self.release_entry(&replaced);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        self.accounting.reserve_inode()?;
+
+        self.inner.symlink(target, link).map_err(|error| {
+            self.accounting.release_inode();
+            error
+        })
+    }
+
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let metadata = self.inner.symlink_metadata(path)?;
+        self.inner.remove_file(path)?;
+        self.release_entry(&metadata);
+
+        Ok(())
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(QuotaFileOpener {
+            filesystem: self.clone(),
+        }))
+    }
+}
+
+/// Opens the files of a [`QuotaFileSystem`], accounting for the files
+/// they create.
+#[derive(Debug, Clone)]
+pub struct QuotaFileOpener {
+    filesystem: QuotaFileSystem,
+}
+
+impl FileOpener for QuotaFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let fs = &self.filesystem;
+        let existing = fs.inner.metadata(path).ok();
+
+        let creates = existing.is_none() && (conf.create() || conf.create_new());
+        if creates {
+            fs.accounting.reserve_inode()?;
+        }
+
+        let file = fs
+            .inner
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)
+            .map_err(|error| {
+                if creates {
+                    fs.accounting.release_inode();
+                }
+                error
+            })?;
+
+        if let Some(existing) = existing {
+            if conf.truncate() && existing.is_file() {
+                fs.accounting.release_bytes(existing.len());
+            }
+        }
+
+        Ok(Box::new(QuotaFile {
+            inner: file,
+            accounting: fs.accounting.clone(),
+            append: conf.append(),
+        }))
+    }
+}
+
+/// A file of a [`QuotaFileSystem`], accounting for its growth.
+#[derive(Debug)]
+pub struct QuotaFile {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+    accounting: Arc<Accounting>,
+    append: bool,
+}
+
+impl QuotaFile {
+    /// The most the file can grow by when writing `len` bytes.
+    ///
+    /// Some file systems, like [`mem_fs`](crate::mem_fs), insert the
+    /// written bytes at the cursor instead of overwriting the ones
+    /// there, so a write can always add all of its bytes.
+    fn growth(&mut self, len: usize) -> io::Result<u64> {
+        let len = len as u64;
+        if self.append {
+            return Ok(len);
+        }
+
+        let position = self.inner.seek(SeekFrom::Current(0))?;
+        Ok(len.max((position + len).saturating_sub(self.inner.size())))
+    }
+}
+
+impl VirtualFile for QuotaFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        let size = self.inner.size();
+        if new_size > size {
+            self.accounting.reserve_bytes(new_size - size)?;
+        }
+
+        match self.inner.set_len(new_size) {
+            Ok(()) => {
+                if new_size < size {
+                    self.accounting.release_bytes(size - new_size);
+                }
+                Ok(())
+            }
+            Err(error) => {
+                if new_size > size {
+                    self.accounting.release_bytes(new_size - size);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        let size = self.inner.size();
+        self.inner.unlink()?;
+        self.accounting.release_bytes(size);
+        self.accounting.release_inode();
+
+        Ok(())
+    }
+
+    fn sync_to_disk(&self) -> Result<()> {
+        self.inner.sync_to_disk()
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_write()
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+impl Read for QuotaFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for QuotaFile {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+impl Write for QuotaFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = self.inner.size();
+        let reserved = self.growth(buf.len())?;
+        self.accounting
+            .reserve_bytes(reserved)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+
+        let written = self.inner.write(buf);
+        self.accounting
+            .settle_bytes(reserved, self.inner.size().saturating_sub(size));
+
+        written
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_quota_fs {
+    use super::*;
+    use crate::mem_fs;
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    fn quota_fs(max_bytes: u64, max_inodes: u64) -> QuotaFileSystem {
+        QuotaFileSystem::new(
+            mem_fs::FileSystem::default(),
+            QuotaLimits {
+                max_bytes: Some(max_bytes),
+                max_inodes: Some(max_inodes),
+            },
+        )
+    }
+
+    #[test]
+    fn test_bytes() {
+        let fs = quota_fs(16, 8);
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+
+        file.write_all(b"0123456789").unwrap();
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 10,
+                inodes: 1
+            }
+        );
+
+        let error = file.write_all(b"0123456789").unwrap_err();
+        assert_eq!(
+            error
+                .get_ref()
+                .and_then(|error| error.downcast_ref::<FsError>()),
+            Some(&FsError::QuotaExceeded),
+        );
+        assert_eq!(fs.usage().bytes, 10, "the failed write isn't accounted");
+
+        assert_eq!(file.size(), 10);
+
+        // mem_fs inserts at the cursor, the write adds all of its bytes.
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(b"abcdef").unwrap();
+        assert_eq!(file.size(), 16);
+        assert_eq!(fs.usage().bytes, 16);
+        file.seek(SeekFrom::Start(0)).unwrap();
+        assert!(file.write_all(b"a").is_err());
+        assert_eq!(file.size(), 16);
+        assert_eq!(fs.usage().bytes, 16);
+
+        assert_eq!(file.set_len(32), Err(FsError::QuotaExceeded));
+        file.set_len(4).unwrap();
+        assert_eq!(fs.usage().bytes, 4);
+
+        fs.new_open_options()
+            .write(true)
+            .truncate(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+        assert_eq!(fs.usage().bytes, 0, "truncating releases the bytes");
+    }
+
+    #[test]
+    fn test_inodes() {
+        let fs = quota_fs(16, 2);
+
+        fs.create_dir(path!("/foo")).unwrap();
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap()
+            .write_all(b"bar")
+            .unwrap();
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 3,
+                inodes: 2
+            }
+        );
+
+        assert_eq!(fs.create_dir(path!("/baz")), Err(FsError::QuotaExceeded));
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open(path!("/baz.txt"))
+                .unwrap_err(),
+            FsError::QuotaExceeded,
+        );
+        assert_eq!(fs.usage().inodes, 2);
+
+        fs.remove_file(path!("/foo/bar.txt")).unwrap();
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 0,
+                inodes: 1
+            }
+        );
+        fs.create_dir(path!("/baz")).unwrap();
+        assert_eq!(fs.usage().inodes, 2);
+    }
+
+    #[test]
+    fn test_count_existing() {
+        let inner = mem_fs::FileSystem::default();
+        inner.create_dir(path!("/foo")).unwrap();
+        inner
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo/bar.txt"))
+            .unwrap()
+            .write_all(b"bar")
+            .unwrap();
+
+        let fs = QuotaFileSystem::new(inner, QuotaLimits::default());
+        assert_eq!(fs.usage(), QuotaUsage::default());
+        assert_eq!(
+            fs.count_existing(path!("/")).unwrap(),
+            QuotaUsage {
+                bytes: 3,
+                inodes: 2
+            },
+        );
+        assert_eq!(
+            fs.usage(),
+            QuotaUsage {
+                bytes: 3,
+                inodes: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! A read-only view of another file system.
+
+use crate::{
+    FileDescriptor, FileOpener, FileSystem, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, Result, VirtualFile,
+};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A file system denying every modification of the file system it wraps
+/// with [`FsError::PermissionDenied`].
+///
+/// It composes with [`ChrootFileSystem`](crate::chroot_fs::ChrootFileSystem)
+/// to expose a directory of the host that can't be escaped nor modified:
+///
+/// ```
+/// # #[cfg(feature = "host-fs")]
+/// # {
+/// use wasmer_vfs::{chroot_fs::ChrootFileSystem, host_fs, read_only_fs::ReadOnlyFileSystem};
+///
+/// let fs = ReadOnlyFileSystem::new(ChrootFileSystem::new("/srv/guest", host_fs::FileSystem));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReadOnlyFileSystem {
+    inner: Arc<dyn FileSystem>,
+}
+
+impl ReadOnlyFileSystem {
+    /// Creates a read-only view of `inner`.
+    pub fn new(inner: impl FileSystem) -> Self {
+        Self::from_boxed(Box::new(inner))
+    }
+
+    /// Like [`ReadOnlyFileSystem::new`] with a boxed file system.
+    pub fn from_boxed(inner: Box<dyn FileSystem>) -> Self {
+        Self {
+            inner: Arc::from(inner),
+        }
+    }
+
+    /// The wrapped file system.
+    pub fn inner(&self) -> &dyn FileSystem {
+        self.inner.as_ref()
+    }
+}
+
+impl FileSystem for ReadOnlyFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn symlink(&self, _target: &Path, _link: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path)
+    }
+
+    fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(ReadOnlyFileOpener {
+            filesystem: self.clone(),
+        }))
+    }
+}
+
+/// Opens the files of a [`ReadOnlyFileSystem`] for reading only.
+#[derive(Debug, Clone)]
+pub struct ReadOnlyFileOpener {
+    filesystem: ReadOnlyFileSystem,
+}
+
+impl FileOpener for ReadOnlyFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let fs = &self.filesystem;
+
+        if conf.write() || conf.append() || conf.truncate() || conf.create_new() {
+            return Err(FsError::PermissionDenied);
+        }
+        if conf.create() && fs.inner.metadata(path).is_err() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let file = fs
+            .inner
+            .new_open_options()
+            .options(OpenOptionsConfig {
+                create: false,
+                ..conf.clone()
+            })
+            .open(path)?;
+        Ok(Box::new(ReadOnlyFile::new(file)))
+    }
+}
+
+/// A file denying every modification with [`FsError::PermissionDenied`],
+/// even through a handle opened for reading: writing, changing its size
+/// and unlinking it.
+#[derive(Debug)]
+pub struct ReadOnlyFile {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+}
+
+impl ReadOnlyFile {
+    /// Creates a read-only view of the opened file `inner`.
+    pub fn new(inner: Box<dyn VirtualFile + Send + Sync + 'static>) -> Self {
+        Self { inner }
+    }
+}
+
+impl VirtualFile for ReadOnlyFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, _new_size: u64) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn bytes_available(&self) -> Result<usize> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>> {
+        Ok(Some(0))
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+impl Read for ReadOnlyFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for ReadOnlyFile {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+impl Write for ReadOnlyFile {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            FsError::PermissionDenied,
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_read_only_fs {
+    use super::*;
+    use crate::mem_fs;
+    use std::io::{Read, Write};
+
+    macro_rules! path {
+        ($path:expr) => {
+            std::path::Path::new($path)
+        };
+    }
+
+    #[test]
+    fn test_read_only() {
+        let inner = mem_fs::FileSystem::default();
+        inner
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo.txt"))
+            .unwrap()
+            .write_all(b"foo")
+            .unwrap();
+        let fs = ReadOnlyFileSystem::new(inner);
+
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/foo.txt"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "foo");
+
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .open(path!("/foo.txt"))
+                .unwrap_err(),
+            FsError::PermissionDenied,
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .read(true)
+                .create(true)
+                .open(path!("/bar.txt"))
+                .unwrap_err(),
+            FsError::PermissionDenied,
+        );
+        assert_eq!(fs.create_dir(path!("/dir")), Err(FsError::PermissionDenied));
+        assert_eq!(
+            fs.remove_file(path!("/foo.txt")),
+            Err(FsError::PermissionDenied),
+        );
+        assert_eq!(
+            fs.rename(path!("/foo.txt"), path!("/bar.txt")),
+            Err(FsError::PermissionDenied),
+        );
+        assert!(fs.metadata(path!("/foo.txt")).unwrap().is_file());
+    }
+
+    fn read_only_file() -> (mem_fs::FileSystem, Box<dyn VirtualFile + Send + Sync>) {
+        let inner = mem_fs::FileSystem::default();
+        inner
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path!("/foo.txt"))
+            .unwrap()
+            .write_all(b"foo")
+            .unwrap();
+        let file = ReadOnlyFileSystem::new(inner.clone())
+            .new_open_options()
+            .read(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+        (inner, file)
+    }
+
+    #[test]
+    fn test_read_only_file_write() {
+        let (inner, mut file) = read_only_file();
+        let err = file.write(b"bar").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(inner.metadata(path!("/foo.txt")).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_read_only_file_set_len() {
+        let (inner, mut file) = read_only_file();
+        assert_eq!(file.set_len(0), Err(FsError::PermissionDenied));
+        assert_eq!(inner.metadata(path!("/foo.txt")).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_read_only_file_unlink() {
+        let (inner, mut file) = read_only_file();
+        assert_eq!(file.unlink(), Err(FsError::PermissionDenied));
+        assert!(inner.metadata(path!("/foo.txt")).unwrap().is_file());
+    }
+}
@@ -360,6 +360,14 @@ impl FileSystem for StaticFileSystem {
             self.memory.symlink_metadata(Path::new(&path))
         }
     }
+    fn symlink(&self, target: &Path, link: &Path) -> Result<(), FsError> {
+        let link = normalizes_path(link);
+        self.memory.symlink(target, Path::new(&link))
+    }
+    fn readlink(&self, path: &Path) -> Result<PathBuf, FsError> {
+        let path = normalizes_path(path);
+        self.memory.readlink(Path::new(&path))
+    }
 }
 
 fn normalizes_path(path: &Path) -> String {
@@ -10,12 +10,13 @@ mod allocator;
 
 use crate::export::VMExtern;
 use crate::imports::Imports;
+use crate::parking::PARKING_LOT;
 use crate::store::{InternalStoreHandle, StoreObjects};
 use crate::table::TableElement;
 use crate::trap::{catch_traps, Trap, TrapCode};
 use crate::vmcontext::{
-    memory32_atomic_check32, memory32_atomic_check64, memory_copy, memory_fill,
-    VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionContext,
+    memory32_atomic_addr, memory32_atomic_check32, memory32_atomic_check64, memory_copy,
+    memory_fill, VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionContext,
     VMFunctionImport, VMFunctionKind, VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition,
     VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
 };
@@ -33,29 +34,16 @@ use std::fmt;
 use std::mem;
 use std::ptr::{self, NonNull};
 use std::slice;
-use std::sync::{Arc, Mutex};
-use std::thread::{current, park, park_timeout, Thread};
+use std::sync::Arc;
+use std::time::Duration;
 use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
-    DataIndex, DataInitializer, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, GlobalInit,
-    LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryError,
-    MemoryIndex, ModuleInfo, Pages, SignatureIndex, TableIndex, TableInitializer, VMOffsets,
+    ConstExpr, DataIndex, DataInitializer, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex,
+    GlobalInit, LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex,
+    MemoryError, MemoryIndex, ModuleInfo, Pages, SignatureIndex, TableIndex, TableInitializer,
+    Type, VMOffsets,
 };
 
-#[derive(Hash, Eq, PartialEq, Clone, Copy)]
-struct NotifyLocation {
-    memory_index: u32,
-    address: u32,
-}
-
-struct NotifyWaiter {
-    thread: Thread,
-    notified: bool,
-}
-struct NotifyMap {
-    map: HashMap<NotifyLocation, Vec<NotifyWaiter>>,
-}
-
 /// A WebAssembly instance.
 ///
 /// The type is dynamically-sized. Indeed, the `vmctx` field can
@@ -105,9 +93,6 @@ pub(crate) struct Instance {
     /// will point to elements here for functions imported by this instance.
     imported_funcrefs: BoxedSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
 
-    /// The Hasmap with the Notify for the Notify/wait opcodes
-    conditions: Arc<Mutex<NotifyMap>>,
-
     /// Additional context used by compiled WebAssembly code. This
     /// field is last, and represents a dynamically-sized array that
     /// extends beyond the nominal end of the struct (similar to a
@@ -797,51 +782,41 @@ impl Instance {
         }
     }
 
-    // To implement Wait / Notify, a HasMap, behind a mutex, will be used
-    // to track the address of waiter. The key of the hashmap is based on the memory
-    // and waiter threads are "park"'d (with or without timeout)
-    // Notify will wake the waiters by simply "unpark" the thread
-    // as the Thread info is stored on the HashMap
-    // once unparked, the waiter thread will remove it's mark on the HashMap
-    // timeout / awake is tracked with a boolean in the HashMap
-    // because `park_timeout` doesn't gives any information on why it returns
-    fn do_wait(&mut self, index: u32, dst: u32, timeout: i64) -> u32 {
-        // fetch the notifier
-        let key = NotifyLocation {
-            memory_index: index,
-            address: dst,
-        };
-        let mut conds = self.conditions.lock().unwrap();
-        let v = conds.map.entry(key).or_insert_with(Vec::new);
-        v.push(NotifyWaiter {
-            thread: current(),
-            notified: false,
-        });
-        drop(conds);
-        if timeout < 0 {
-            park();
-        } else {
-            park_timeout(std::time::Duration::from_nanos(timeout as u64));
-        }
-        let mut conds = self.conditions.lock().unwrap();
-        let v = conds.map.get_mut(&key).unwrap();
-        let id = current().id();
-        let mut ret = 0;
-        v.retain(|cond| {
-            if cond.thread.id() == id {
-                ret = if cond.notified { 0 } else { 2 };
-                false
-            } else {
-                true
-            }
-        });
-        if v.is_empty() {
-            conds.map.remove(&key);
-        }
-        if conds.map.len() > 1 << 32 {
-            ret = 0xffff;
-        }
-        ret
+    /// Convert the `timeout` operand of `memory.atomic.wait*`, in
+    /// nanoseconds, to a `Duration`. A negative timeout waits forever.
+    fn wait_timeout(timeout: i64) -> Option<Duration> {
+        u64::try_from(timeout).ok().map(Duration::from_nanos)
+    }
+
+    // Waiters from every instance are parked in the process-wide
+    // `PARKING_LOT`, keyed by the host address of the memory cell,
+    // so that notifies are seen across instances sharing a memory.
+    fn do_wait32(mem: &VMMemoryDefinition, dst: u32, val: u32, timeout: i64) -> Result<u32, Trap> {
+        //if ! memory.shared {
+        // We should trap according to spec, but official test rely on not trapping...
+        //}
+        let addr = unsafe { memory32_atomic_addr(mem, dst, 4)? };
+        let ret = PARKING_LOT.wait(
+            addr as usize,
+            || unsafe { memory32_atomic_check32(addr, val) } == 0,
+            Self::wait_timeout(timeout),
+        );
+        Ok(ret as u32)
+    }
+
+    fn do_wait64(mem: &VMMemoryDefinition, dst: u32, val: u64, timeout: i64) -> Result<u32, Trap> {
+        let addr = unsafe { memory32_atomic_addr(mem, dst, 8)? };
+        let ret = PARKING_LOT.wait(
+            addr as usize,
+            || unsafe { memory32_atomic_check64(addr, val) } == 0,
+            Self::wait_timeout(timeout),
+        );
+        Ok(ret as u32)
+    }
+
+    fn do_notify(mem: &VMMemoryDefinition, dst: u32, count: u32) -> Result<u32, Trap> {
+        let addr = unsafe { memory32_atomic_addr(mem, dst, 4)? };
+        Ok(PARKING_LOT.notify(addr as usize, count))
     }
 
     /// Perform an Atomic.Wait32
@@ -853,24 +828,7 @@ impl Instance {
         timeout: i64,
     ) -> Result<u32, Trap> {
         let memory = self.memory(memory_index);
-        //if ! memory.shared {
-        // We should trap according to spec, but official test rely on not trapping...
-        //}
-
-        let ret = unsafe { memory32_atomic_check32(&memory, dst, val) };
-
-        if let Ok(mut ret) = ret {
-            if ret == 0 {
-                ret = self.do_wait(memory_index.as_u32(), dst, timeout);
-            }
-            if ret == 0xffff {
-                // ret is 0xffff if there is more than 2^32 waiter in queue
-                return Err(Trap::lib(TrapCode::TableAccessOutOfBounds));
-            }
-            Ok(ret)
-        } else {
-            ret
-        }
+        Self::do_wait32(&memory, dst, val, timeout)
     }
 
     /// Perform an Atomic.Wait32
@@ -883,24 +841,7 @@ impl Instance {
     ) -> Result<u32, Trap> {
         let import = self.imported_memory(memory_index);
         let memory = unsafe { import.definition.as_ref() };
-        //if ! memory.shared {
-        // We should trap according to spec, but official test rely on not trapping...
-        //}
-
-        let ret = unsafe { memory32_atomic_check32(memory, dst, val) };
-
-        if let Ok(mut ret) = ret {
-            if ret == 0 {
-                ret = self.do_wait(memory_index.as_u32(), dst, timeout);
-            }
-            if ret == 0xffff {
-                // ret is 0xffff if there is more than 2^32 waiter in queue
-                return Err(Trap::lib(TrapCode::TableAccessOutOfBounds));
-            }
-            Ok(ret)
-        } else {
-            ret
-        }
+        Self::do_wait32(memory, dst, val, timeout)
     }
 
     /// Perform an Atomic.Wait64
@@ -912,24 +853,7 @@ impl Instance {
         timeout: i64,
     ) -> Result<u32, Trap> {
         let memory = self.memory(memory_index);
-        //if ! memory.shared {
-        // We should trap according to spec, but official test rely on not trapping...
-        //}
-
-        let ret = unsafe { memory32_atomic_check64(&memory, dst, val) };
-
-        if let Ok(mut ret) = ret {
-            if ret == 0 {
-                ret = self.do_wait(memory_index.as_u32(), dst, timeout);
-            }
-            if ret == 0xffff {
-                // ret is 0xffff if there is more than 2^32 waiter in queue
-                return Err(Trap::lib(TrapCode::TableAccessOutOfBounds));
-            }
-            Ok(ret)
-        } else {
-            ret
-        }
+        Self::do_wait64(&memory, dst, val, timeout)
     }
 
     /// Perform an Atomic.Wait64
@@ -942,39 +866,7 @@ impl Instance {
     ) -> Result<u32, Trap> {
         let import = self.imported_memory(memory_index);
         let memory = unsafe { import.definition.as_ref() };
-        //if ! memory.shared {
-        // We should trap according to spec, but official test rely on not trapping...
-        //}
-
-        let ret = unsafe { memory32_atomic_check64(memory, dst, val) };
-
-        if let Ok(mut ret) = ret {
-            if ret == 0 {
-                ret = self.do_wait(memory_index.as_u32(), dst, timeout);
-            }
-            if ret == 0xffff {
-                // ret is 0xffff if there is more than 2^32 waiter in queue
-                return Err(Trap::lib(TrapCode::TableAccessOutOfBounds));
-            }
-            Ok(ret)
-        } else {
-            ret
-        }
-    }
-
-    fn do_notify(&mut self, key: NotifyLocation, count: u32) -> Result<u32, Trap> {
-        let mut conds = self.conditions.lock().unwrap();
-        let mut cnt = 0u32;
-        if let Some(v) = conds.map.get_mut(&key) {
-            for waiter in v {
-                if cnt < count {
-                    waiter.notified = true; // mark as was waiked up
-                    waiter.thread.unpark(); // wakeup!
-                    cnt += 1;
-                }
-            }
-        }
-        Ok(cnt)
+        Self::do_wait64(memory, dst, val, timeout)
     }
 
     /// Perform an Atomic.Notify
@@ -984,17 +876,8 @@ impl Instance {
         dst: u32,
         count: u32,
     ) -> Result<u32, Trap> {
-        //let memory = self.memory(memory_index);
-        //if ! memory.shared {
-        // We should trap according to spec, but official test rely on not trapping...
-        //}
-
-        // fetch the notifier
-        let key = NotifyLocation {
-            memory_index: memory_index.as_u32(),
-            address: dst,
-        };
-        self.do_notify(key, count)
+        let memory = self.memory(memory_index);
+        Self::do_notify(&memory, dst, count)
     }
 
     /// Perform an Atomic.Notify
@@ -1004,18 +887,9 @@ impl Instance {
         dst: u32,
         count: u32,
     ) -> Result<u32, Trap> {
-        //let import = self.imported_memory(memory_index);
-        //let memory = unsafe { import.definition.as_ref() };
-        //if ! memory.shared {
-        // We should trap according to spec, but official test rely on not trapping...
-        //}
-
-        // fetch the notifier
-        let key = NotifyLocation {
-            memory_index: memory_index.as_u32(),
-            address: dst,
-        };
-        self.do_notify(key, count)
+        let import = self.imported_memory(memory_index);
+        let memory = unsafe { import.definition.as_ref() };
+        Self::do_notify(memory, dst, count)
     }
 }
 
@@ -1109,9 +983,6 @@ impl InstanceHandle {
                 funcrefs,
                 imported_funcrefs,
                 vmctx: VMContext {},
-                conditions: Arc::new(Mutex::new(NotifyMap {
-                    map: HashMap::new(),
-                })),
             };
 
             let mut instance_handle = allocator.write_instance(instance);
@@ -1381,8 +1252,30 @@ impl InstanceHandle {
     }
 }
 
+/// Read the raw bits of a global, for the evaluation of a constant expression.
+fn get_global_bits(instance: &Instance, index: GlobalIndex) -> u64 {
+    unsafe {
+        if let Some(def_index) = instance.module.local_global_index(index) {
+            instance.global(def_index).val.u64
+        } else {
+            instance.imported_global(index).definition.as_ref().val.u64
+        }
+    }
+}
+
+/// Evaluate the extended constant expression of an offset.
+fn eval_offset_expr(expr: &ConstExpr, instance: &Instance) -> usize {
+    let val = expr
+        .eval(|index| get_global_bits(instance, index))
+        .expect("constant expressions of validated modules are well-formed");
+    usize::try_from(val as u32).unwrap()
+}
+
 /// Compute the offset for a memory data initializer.
 fn get_memory_init_start(init: &DataInitializer<'_>, instance: &Instance) -> usize {
+    if let Some(expr) = &init.location.offset_expr {
+        return eval_offset_expr(expr, instance);
+    }
     let mut start = init.location.offset;
 
     if let Some(base) = init.location.base {
@@ -1420,6 +1313,9 @@ unsafe fn get_memory_slice<'instance>(
 
 /// Compute the offset for a table element initializer.
 fn get_table_init_start(init: &TableInitializer, instance: &Instance) -> usize {
+    if let Some(expr) = &init.offset_expr {
+        return eval_offset_expr(expr, instance);
+    }
     let mut start = init.offset;
 
     if let Some(base) = init.base {
@@ -1539,6 +1435,16 @@ fn initialize_globals(instance: &Instance) {
                     let funcref = instance.func_ref(*func_idx).unwrap();
                     (*to).val = funcref.into_raw();
                 }
+                GlobalInit::Expr(expr) => {
+                    let val = module.const_exprs[*expr]
+                        .eval(|index| get_global_bits(instance, index))
+                        .expect("constant expressions of validated modules are well-formed");
+                    let global_index = module.global_index(index);
+                    match module.globals[global_index].ty {
+                        Type::I32 => (*to).val.i32 = val as i32,
+                        _ => (*to).val.i64 = val as i64,
+                    }
+                }
             }
         }
     }
@@ -0,0 +1,121 @@
+//! Support for functions that are compiled on their first call.
+//!
+//! When an artifact is compiled lazily, every local function initially
+//! points to a small stub. The stub jumps through a slot in a writable
+//! table, which initially sends the call to a resolver that invokes
+//! [`wasmer_vm_lazy_compile`](crate::libcalls::wasmer_vm_lazy_compile).
+//! Once the function is compiled, its slot is patched so that subsequent
+//! calls go straight to the compiled body.
+
+use crate::trap::Trap;
+use crate::{FunctionBodyPtr, SectionBodyPtr, VMFunctionBody};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::LocalFunctionIndex;
+
+/// Compiles the functions of a lazily compiled artifact on demand.
+pub trait LazyFunctionCompiler: Send + Sync {
+    /// Compiles the given local function, returning a pointer to the
+    /// published, executable body.
+    fn compile_function(&self, index: LocalFunctionIndex) -> Result<FunctionBodyPtr, String>;
+}
+
+/// An error that happened while lazily compiling a function.
+#[derive(Debug)]
+pub struct LazyCompileError {
+    index: LocalFunctionIndex,
+    message: String,
+}
+
+impl fmt::Display for LazyCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to lazily compile function {}: {}",
+            self.index.index(),
+            self.message
+        )
+    }
+}
+
+impl Error for LazyCompileError {}
+
+/// The table of lazily compiled functions of an artifact.
+///
+/// The table lives in a read-write section allocated next to the artifact
+/// code. The first word of the section holds a pointer back to this
+/// structure, and it's followed by one slot per local function.
+pub struct LazyFunctionTable {
+    slots: *const AtomicUsize,
+    /// Whether each function is compiled. Every function has its own lock,
+    /// so that different functions can be resolved at the same time.
+    compiled: PrimaryMap<LocalFunctionIndex, Mutex<bool>>,
+    num_compiled: AtomicUsize,
+    compiler: Box<dyn LazyFunctionCompiler>,
+}
+
+/// # Safety
+/// The slots pointer refers to memory owned by the artifact code memory,
+/// which outlives this table, and it's only accessed atomically.
+unsafe impl Send for LazyFunctionTable {}
+/// # Safety
+/// The slots pointer refers to memory owned by the artifact code memory,
+/// which outlives this table, and it's only accessed atomically.
+unsafe impl Sync for LazyFunctionTable {}
+
+impl LazyFunctionTable {
+    /// The size in bytes of the header preceding the slots of the table.
+    pub const HEADER_SIZE: usize = std::mem::size_of::<usize>();
+
+    /// Creates a new table backed by the given section, and writes the
+    /// pointer to the table in the section header.
+    ///
+    /// # Safety
+    ///
+    /// `section` must point to a writable section of at least
+    /// `HEADER_SIZE + num_functions * size_of::<usize>()` bytes that
+    /// outlives the returned table.
+    pub unsafe fn new(
+        section: SectionBodyPtr,
+        num_functions: usize,
+        compiler: Box<dyn LazyFunctionCompiler>,
+    ) -> Box<Self> {
+        let table = Box::new(Self {
+            slots: section.0.add(Self::HEADER_SIZE) as *const AtomicUsize,
+            compiled: (0..num_functions).map(|_| Mutex::new(false)).collect(),
+            num_compiled: AtomicUsize::new(0),
+            compiler,
+        });
+        std::ptr::write(section.0 as *mut usize, &*table as *const Self as usize);
+        table
+    }
+
+    /// Returns the body of the given function, compiling it first if
+    /// needed.
+    ///
+    /// Only the lock of the given function is held while it's compiled:
+    /// the callers resolving it at the same time wait for its body, and
+    /// the other functions can be resolved meanwhile.
+    pub fn resolve(&self, index: LocalFunctionIndex) -> Result<*const VMFunctionBody, Trap> {
+        let mut compiled = self.compiled[index].lock().unwrap();
+        let slot = unsafe { &*self.slots.add(index.index()) };
+        if !*compiled {
+            let body = self
+                .compiler
+                .compile_function(index)
+                .map_err(|message| Trap::User(Box::new(LazyCompileError { index, message })))?;
+            slot.store(*body as usize, Ordering::Release);
+            *compiled = true;
+            self.num_compiled.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(slot.load(Ordering::Acquire) as *const VMFunctionBody)
+    }
+
+    /// Returns the number of functions that have been compiled so far.
+    pub fn num_compiled(&self) -> usize {
+        self.num_compiled.load(Ordering::Relaxed)
+    }
+}
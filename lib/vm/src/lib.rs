@@ -26,8 +26,10 @@ mod function_env;
 mod global;
 mod imports;
 mod instance;
+mod lazy;
 mod memory;
 mod mmap;
+mod parking;
 mod probestack;
 mod sig_registry;
 mod store;
@@ -45,6 +47,7 @@ pub use crate::function_env::VMFunctionEnvironment;
 pub use crate::global::*;
 pub use crate::imports::Imports;
 pub use crate::instance::{InstanceAllocator, InstanceHandle};
+pub use crate::lazy::{LazyCompileError, LazyFunctionCompiler, LazyFunctionTable};
 pub use crate::memory::{
     initialize_memory_with_data, LinearMemory, VMMemory, VMOwnedMemory, VMSharedMemory,
 };
@@ -37,15 +37,16 @@
 
 #![allow(missing_docs)] // For some reason lint fails saying that `LibCall` is not documented, when it actually is
 
+use crate::lazy::LazyFunctionTable;
 use crate::probestack::PROBESTACK;
 use crate::table::{RawTableElement, TableElement};
 use crate::trap::{raise_lib_trap, Trap, TrapCode};
 use crate::vmcontext::VMContext;
-use crate::{on_host_stack, VMFuncRef};
+use crate::{on_host_stack, VMFuncRef, VMFunctionBody};
 pub use wasmer_types::LibCall;
 use wasmer_types::{
-    DataIndex, ElemIndex, FunctionIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
-    TableIndex, Type,
+    DataIndex, ElemIndex, FunctionIndex, LocalFunctionIndex, LocalMemoryIndex, LocalTableIndex,
+    MemoryIndex, TableIndex, Type,
 };
 
 /// Implementation of f32.ceil
@@ -815,6 +816,29 @@ pub unsafe extern "C" fn wasmer_vm_imported_memory32_atomic_notify(
     result.unwrap()
 }
 
+/// Implementation of the lazy compilation resolver.
+///
+/// Compiles the given local function (if it wasn't already) and returns
+/// a pointer to its body.
+///
+/// # Safety
+///
+/// `table` must point to a live `LazyFunctionTable`.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_lazy_compile(
+    table: *const LazyFunctionTable,
+    function_index: u32,
+) -> *const VMFunctionBody {
+    let result = on_host_stack(|| {
+        let function_index = LocalFunctionIndex::from_u32(function_index);
+        (*table).resolve(function_index)
+    });
+    match result {
+        Ok(body) => body,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
 /// The function pointer to a libcall
 pub fn function_pointer(libcall: LibCall) -> usize {
     match libcall {
@@ -855,5 +879,6 @@ pub fn function_pointer(libcall: LibCall) -> usize {
         LibCall::ImportedMemory32AtomicWait64 => wasmer_vm_imported_memory32_atomic_wait64 as usize,
         LibCall::Memory32AtomicNotify => wasmer_vm_memory32_atomic_notify as usize,
         LibCall::ImportedMemory32AtomicNotify => wasmer_vm_imported_memory32_atomic_notify as usize,
+        LibCall::LazyCompile => wasmer_vm_lazy_compile as usize,
     }
 }
@@ -379,9 +379,10 @@ impl LinearMemory for VMSharedMemory {
         guard.vm_memory_definition.as_ptr()
     }
 
-    /// Owned memory can not be cloned (this will always return None)
+    /// Shared memory can be cloned, the clones all point to the same
+    /// memory (this will always return Some)
     fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
-        None
+        Some(Box::new(self.clone()))
     }
 }
 
@@ -0,0 +1,182 @@
+//! A process-wide parking lot backing `memory.atomic.wait*` and
+//! `memory.atomic.notify`.
+//!
+//! Waiters are keyed by the host address of the linear memory cell they
+//! wait on. Since a shared memory has a single backing allocation, every
+//! instance importing it agrees on the key, so a notify in one instance
+//! (or thread) wakes waiters parked through any other instance.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The outcome of a call to [`ParkingLot::wait`], encoded as the value
+/// returned by the `memory.atomic.wait*` instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub(crate) enum WaitResult {
+    /// The waiter was woken by a notify.
+    Ok = 0,
+    /// The value in memory did not match the expected value.
+    NotEqual = 1,
+    /// The timeout expired before a notify happened.
+    TimedOut = 2,
+}
+
+struct Waiter {
+    notified: Mutex<bool>,
+    condvar: Condvar,
+}
+
+/// A queue of parked waiters per memory address.
+pub(crate) struct ParkingLot {
+    queues: Mutex<HashMap<usize, VecDeque<Arc<Waiter>>>>,
+}
+
+lazy_static::lazy_static! {
+    /// The parking lot shared by every instance in the process.
+    pub(crate) static ref PARKING_LOT: ParkingLot = ParkingLot::new();
+}
+
+impl ParkingLot {
+    fn new() -> Self {
+        Self {
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Parks the current thread on `address` until it is notified or
+    /// `timeout` expires (`None` waits forever).
+    ///
+    /// `validate` is called while the queues are locked, so a concurrent
+    /// `notify` can't slip in between the value check and the thread
+    /// being queued. If it returns `false` the thread is not parked.
+    pub(crate) fn wait(
+        &self,
+        address: usize,
+        validate: impl FnOnce() -> bool,
+        timeout: Option<Duration>,
+    ) -> WaitResult {
+        let waiter = {
+            let mut queues = self.queues.lock().unwrap();
+            if !validate() {
+                return WaitResult::NotEqual;
+            }
+            let waiter = Arc::new(Waiter {
+                notified: Mutex::new(false),
+                condvar: Condvar::new(),
+            });
+            queues
+                .entry(address)
+                .or_insert_with(VecDeque::new)
+                .push_back(waiter.clone());
+            waiter
+        };
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut notified = waiter.notified.lock().unwrap();
+        while !*notified {
+            match deadline {
+                None => notified = waiter.condvar.wait(notified).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    notified = waiter
+                        .condvar
+                        .wait_timeout(notified, deadline - now)
+                        .unwrap()
+                        .0;
+                }
+            }
+        }
+        if *notified {
+            return WaitResult::Ok;
+        }
+        drop(notified);
+
+        // The timeout expired. A notify may still have dequeued us in the
+        // meantime, in which case it counted us as woken and so must we.
+        let mut queues = self.queues.lock().unwrap();
+        let queue = match queues.get_mut(&address) {
+            Some(queue) => queue,
+            None => return WaitResult::Ok,
+        };
+        match queue.iter().position(|w| Arc::ptr_eq(w, &waiter)) {
+            Some(position) => {
+                queue.remove(position);
+                if queue.is_empty() {
+                    queues.remove(&address);
+                }
+                WaitResult::TimedOut
+            }
+            None => WaitResult::Ok,
+        }
+    }
+
+    /// Wakes up to `count` threads parked on `address`, in the order they
+    /// started waiting, and returns how many were woken.
+    pub(crate) fn notify(&self, address: usize, count: u32) -> u32 {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = match queues.get_mut(&address) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+        let mut woken = 0;
+        while woken < count {
+            let waiter = match queue.pop_front() {
+                Some(waiter) => waiter,
+                None => break,
+            };
+            *waiter.notified.lock().unwrap() = true;
+            waiter.condvar.notify_one();
+            woken += 1;
+        }
+        if queue.is_empty() {
+            queues.remove(&address);
+        }
+        woken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn wait_not_equal() {
+        let lot = ParkingLot::new();
+        assert_eq!(lot.wait(8, || false, None), WaitResult::NotEqual);
+        assert_eq!(lot.notify(8, 1), 0);
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let lot = ParkingLot::new();
+        let result = lot.wait(8, || true, Some(Duration::from_millis(10)));
+        assert_eq!(result, WaitResult::TimedOut);
+        assert!(lot.queues.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn notify_wakes_waiters() {
+        let lot = Arc::new(ParkingLot::new());
+        let waiters = (0..3)
+            .map(|_| {
+                let lot = lot.clone();
+                thread::spawn(move || lot.wait(16, || true, None))
+            })
+            .collect::<Vec<_>>();
+        let mut woken = 0;
+        while woken < 3 {
+            woken += lot.notify(16, 2);
+            thread::yield_now();
+        }
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), WaitResult::Ok);
+        }
+        assert_eq!(lot.notify(16, 1), 0);
+    }
+}
@@ -14,7 +14,7 @@ use crate::VMTable;
 use crate::{VMBuiltinFunctionIndex, VMFunction};
 use std::convert::TryFrom;
 use std::ptr::{self, NonNull};
-use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::u32;
 use wasmer_types::RawValue;
 
@@ -377,66 +377,57 @@ pub(crate) unsafe fn memory_fill(
     Ok(())
 }
 
-/// Perform the `memory32.atomic.check32` operation for the memory. Return 0 if same, 1 if different
+/// Resolve the address of a `size`-byte atomic access at `dst` in the memory.
 ///
 /// # Errors
 ///
-/// Returns a `Trap` error if the memory range is out of bounds or 32bits unligned.
+/// Returns a `Trap` error if the access is out of bounds or not aligned to `size`.
 ///
 /// # Safety
-/// memory access is unsafe
-pub(crate) unsafe fn memory32_atomic_check32(
+/// `mem` must describe a valid linear memory
+pub(crate) unsafe fn memory32_atomic_addr(
     mem: &VMMemoryDefinition,
     dst: u32,
-    val: u32,
-) -> Result<u32, Trap> {
-    if usize::try_from(dst).unwrap() > mem.current_length {
+    size: u32,
+) -> Result<*mut u8, Trap> {
+    if u64::from(dst) + u64::from(size) > mem.current_length as u64 {
         return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
     }
-
-    let dst = isize::try_from(dst).unwrap();
-    if dst & 0b11 != 0 {
+    if dst % size != 0 {
         return Err(Trap::lib(TrapCode::UnalignedAtomic));
     }
 
-    // Bounds and casts are checked above, by this point we know that
-    // everything is safe.
-    let dst = mem.base.offset(dst) as *mut u32;
-    let atomic_dst = AtomicPtr::new(dst);
-    let read_val = *atomic_dst.load(Ordering::Acquire);
-    let ret = if read_val == val { 0 } else { 1 };
-    Ok(ret)
+    // Bounds and alignment are checked above, by this point we know that
+    // the address is valid.
+    Ok(mem.base.add(usize::try_from(dst).unwrap()))
 }
 
-/// Perform the `memory32.atomic.check64` operation for the memory. Return 0 if same, 1 if different
-///
-/// # Errors
-///
-/// Returns a `Trap` error if the memory range is out of bounds or 64bits unaligned.
+/// Perform the `memory32.atomic.check32` operation on an address returned by
+/// [`memory32_atomic_addr`]. Return 0 if same, 1 if different
 ///
 /// # Safety
-/// memory access is unsafe
-pub(crate) unsafe fn memory32_atomic_check64(
-    mem: &VMMemoryDefinition,
-    dst: u32,
-    val: u64,
-) -> Result<u32, Trap> {
-    if usize::try_from(dst).unwrap() > mem.current_length {
-        return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
+/// `addr` must be a valid, 4 bytes aligned address in a linear memory
+pub(crate) unsafe fn memory32_atomic_check32(addr: *mut u8, val: u32) -> u32 {
+    let atomic_dst = &*(addr as *const AtomicU32);
+    if atomic_dst.load(Ordering::SeqCst) == val {
+        0
+    } else {
+        1
     }
+}
 
-    let dst = isize::try_from(dst).unwrap();
-    if dst & 0b111 != 0 {
-        return Err(Trap::lib(TrapCode::UnalignedAtomic));
+/// Perform the `memory32.atomic.check64` operation on an address returned by
+/// [`memory32_atomic_addr`]. Return 0 if same, 1 if different
+///
+/// # Safety
+/// `addr` must be a valid, 8 bytes aligned address in a linear memory
+pub(crate) unsafe fn memory32_atomic_check64(addr: *mut u8, val: u64) -> u32 {
+    let atomic_dst = &*(addr as *const AtomicU64);
+    if atomic_dst.load(Ordering::SeqCst) == val {
+        0
+    } else {
+        1
     }
-
-    // Bounds and casts are checked above, by this point we know that
-    // everything is safe.
-    let dst = mem.base.offset(dst) as *mut u64;
-    let atomic_dst = AtomicPtr::new(dst);
-    let read_val = *atomic_dst.load(Ordering::Acquire);
-    let ret = if read_val == val { 0 } else { 1 };
-    Ok(ret)
 }
 
 /// The fields compiled code needs to access to utilize a WebAssembly table
@@ -0,0 +1,1049 @@
+//! A network living entirely in the memory of the process.
+//!
+//! Every clone of an [`InMemoryNetworking`] is attached to the same
+//! network, so that the WASI instances given a clone, and the host code
+//! holding another one, can reach each other over TCP and UDP without any
+//! real socket. Any IP address can be bound; a connection or a datagram
+//! to an address reaches what is bound at that exact address, or at the
+//! unspecified address of the same family and port.
+
+use crate::{
+    IpCidr, IpRoute, NetworkError, Result, SocketHttpRequest, SocketReadiness, SocketReceive,
+    SocketReceiveFrom, SocketStatus, SocketWaker, StreamSecurity, TimeType, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
+    VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
+};
+use bytes::Bytes;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The first port handed out to the sockets bound to the port 0.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// The largest chunk of data returned by a single receive.
+const MAX_RECV_SIZE: usize = 64 * 1024;
+
+/// A queue of items shared between the two ends of a connection, or
+/// between the senders and the receiver of a socket.
+struct Queue<T> {
+    state: Mutex<QueueState<T>>,
+    condvar: Condvar,
+    /// Called when items are pushed or the queue is closed, on behalf of
+    /// the receiver.
+    waker: Mutex<Option<SocketWaker>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Queue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("state", &self.state)
+            .field("condvar", &self.condvar)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                items: VecDeque::new(),
+                closed: false,
+            }),
+            condvar: Condvar::new(),
+            waker: Mutex::new(None),
+        }
+    }
+}
+
+impl<T> Queue<T> {
+    fn lock(&self) -> Result<MutexGuard<'_, QueueState<T>>> {
+        self.state.lock().map_err(|_| NetworkError::Lock)
+    }
+
+    /// Pushes items to the queue, failing if it's closed.
+    fn push(&self, items: impl IntoIterator<Item = T>) -> Result<()> {
+        let mut state = self.lock()?;
+        if state.closed {
+            return Err(NetworkError::BrokenPipe);
+        }
+        state.items.extend(items);
+        self.condvar.notify_all();
+        drop(state);
+        self.wake();
+
+        Ok(())
+    }
+
+    fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            self.condvar.notify_all();
+        }
+        self.wake();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().map(|state| state.closed).unwrap_or(true)
+    }
+
+    /// Whether a receive wouldn't block, because the queue has items
+    /// matching `filter` or is closed.
+    fn is_ready(&self, filter: impl Fn(&T) -> bool) -> bool {
+        self.state
+            .lock()
+            .map(|state| state.closed || state.items.iter().any(filter))
+            .unwrap_or(true)
+    }
+
+    fn set_waker(&self, waker: SocketWaker) {
+        if let Ok(mut current) = self.waker.lock() {
+            *current = Some(waker);
+        }
+    }
+
+    fn wake(&self) {
+        let waker = self.waker.lock().ok().and_then(|waker| waker.clone());
+        if let Some(waker) = waker {
+            waker();
+        }
+    }
+
+    /// Waits until the queue has items or is closed, for at most
+    /// `timeout`.
+    fn wait(&self, timeout: Option<Duration>) -> Result<MutexGuard<'_, QueueState<T>>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.lock()?;
+
+        while state.items.is_empty() && !state.closed {
+            state = match deadline {
+                None => self.condvar.wait(state).map_err(|_| NetworkError::Lock)?,
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(NetworkError::TimedOut);
+                    }
+                    self.condvar
+                        .wait_timeout(state, deadline - now)
+                        .map_err(|_| NetworkError::Lock)?
+                        .0
+                }
+            };
+        }
+
+        Ok(state)
+    }
+}
+
+type Backlog = Queue<(InMemoryTcpSocket, SocketAddr)>;
+type Mailbox = Queue<(Bytes, SocketAddr)>;
+
+#[derive(Debug, Default)]
+struct Network {
+    listeners: HashMap<SocketAddr, Arc<Backlog>>,
+    udp_sockets: HashMap<SocketAddr, Arc<Mailbox>>,
+    /// The ports used by the connected TCP sockets, per IP address.
+    tcp_ports: HashMap<SocketAddr, usize>,
+    hosts: HashMap<String, Vec<IpAddr>>,
+    next_port: u16,
+}
+
+impl Network {
+    /// Finds what is bound at `addr` in `bound`, falling back to the
+    /// unspecified address of the same family.
+    fn lookup<T: Clone>(bound: &HashMap<SocketAddr, T>, addr: SocketAddr) -> Option<T> {
+        let unspecified = match addr.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+
+        bound
+            .get(&addr)
+            .or_else(|| bound.get(&SocketAddr::new(unspecified, addr.port())))
+            .cloned()
+    }
+
+    fn is_used(&self, addr: SocketAddr) -> bool {
+        self.listeners.contains_key(&addr)
+            || self.udp_sockets.contains_key(&addr)
+            || self.tcp_ports.contains_key(&addr)
+    }
+
+    /// Gives `addr` a free port if it has none.
+    fn assign_port(&mut self, mut addr: SocketAddr) -> Result<SocketAddr> {
+        if addr.port() != 0 {
+            return Ok(addr);
+        }
+
+        for _ in FIRST_EPHEMERAL_PORT..=u16::MAX {
+            if self.next_port < FIRST_EPHEMERAL_PORT {
+                self.next_port = FIRST_EPHEMERAL_PORT;
+            }
+            addr.set_port(self.next_port);
+            self.next_port = self.next_port.wrapping_add(1);
+            if !self.is_used(addr) {
+                return Ok(addr);
+            }
+        }
+
+        Err(NetworkError::AddressInUse)
+    }
+}
+
+/// A virtual network shared by all its clones, see the [module
+/// documentation](self).
+///
+/// ```
+/// use std::net::SocketAddr;
+/// use wasmer_vnet::{in_memory::InMemoryNetworking, VirtualNetworking};
+///
+/// let network = InMemoryNetworking::new();
+/// let addr: SocketAddr = "10.0.0.1:80".parse().unwrap();
+/// let listener = network.listen_tcp(addr, false, false, false).unwrap();
+///
+/// let client = network.clone();
+/// let _connection = client
+///     .connect_tcp("0.0.0.0:0".parse().unwrap(), addr, None)
+///     .unwrap();
+/// let (_accepted, _peer) = listener.accept().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryNetworking {
+    network: Arc<Mutex<Network>>,
+}
+
+impl InMemoryNetworking {
+    /// Creates a new, empty, network.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes [`VirtualNetworking::resolve`] resolve `host` to `addrs`.
+    pub fn add_host(&self, host: &str, addrs: Vec<IpAddr>) -> Result<()> {
+        self.lock()?.hosts.insert(host.to_string(), addrs);
+
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Network>> {
+        self.network.lock().map_err(|_| NetworkError::Lock)
+    }
+}
+
+impl VirtualNetworking for InMemoryNetworking {
+    fn ws_connect(&self, _url: &str) -> Result<Box<dyn VirtualWebSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn http_request(
+        &self,
+        _url: &str,
+        _method: &str,
+        _headers: &str,
+        _gzip: bool,
+    ) -> Result<SocketHttpRequest> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn bridge(&self, _network: &str, _access_token: &str, _security: StreamSecurity) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn unbridge(&self) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ip_add(&self, _ip: IpAddr, _prefix: u8) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ip_remove(&self, _ip: IpAddr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn gateway_set(&self, _ip: IpAddr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn route_add(
+        &self,
+        _cidr: IpCidr,
+        _via_router: IpAddr,
+        _preferred_until: Option<Duration>,
+        _expires_at: Option<Duration>,
+    ) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn route_remove(&self, _cidr: IpAddr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        _only_v6: bool,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let mut network = self.lock()?;
+        let addr = network.assign_port(addr)?;
+        if network.listeners.contains_key(&addr) {
+            return Err(NetworkError::AddressInUse);
+        }
+
+        let backlog = Arc::new(Backlog::default());
+        network.listeners.insert(addr, backlog.clone());
+
+        Ok(Box::new(InMemoryTcpListener {
+            network: self.clone(),
+            addr,
+            backlog,
+            timeout: None,
+            ttl: 64,
+        }))
+    }
+
+    fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let mut network = self.lock()?;
+        let addr = network.assign_port(addr)?;
+        if network.udp_sockets.contains_key(&addr) {
+            return Err(NetworkError::AddressInUse);
+        }
+
+        let mailbox = Arc::new(Mailbox::default());
+        network.udp_sockets.insert(addr, mailbox.clone());
+
+        Ok(Box::new(InMemoryUdpSocket {
+            network: self.clone(),
+            addr,
+            peer: None,
+            mailbox,
+            ttl: 64,
+            broadcast: false,
+        }))
+    }
+
+    fn bind_icmp(&self, _addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+        _timeout: Option<Duration>,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let mut network = self.lock()?;
+        let backlog =
+            Network::lookup(&network.listeners, peer).ok_or(NetworkError::ConnectionRefused)?;
+
+        // An unspecified local address takes the IP of the peer, as if it
+        // was reached through the loopback interface.
+        let addr = if addr.ip().is_unspecified() {
+            SocketAddr::new(peer.ip(), addr.port())
+        } else {
+            addr
+        };
+        let addr = network.assign_port(addr)?;
+        *network.tcp_ports.entry(addr).or_default() += 1;
+        *network.tcp_ports.entry(peer).or_default() += 1;
+        drop(network);
+
+        let (client, server) = InMemoryTcpSocket::pair(self, addr, peer);
+        backlog
+            .push(Some((server, addr)))
+            .map_err(|_| NetworkError::ConnectionRefused)?;
+
+        Ok(Box::new(client))
+    }
+
+    fn resolve(
+        &self,
+        host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        if let Some(addrs) = self.lock()?.hosts.get(host) {
+            return Ok(addrs.clone());
+        }
+        if host == "localhost" {
+            return Ok(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        }
+
+        Err(NetworkError::AddressNotAvailable)
+    }
+}
+
+/// A TCP listener of an [`InMemoryNetworking`].
+#[derive(Debug)]
+pub struct InMemoryTcpListener {
+    network: InMemoryNetworking,
+    addr: SocketAddr,
+    backlog: Arc<Backlog>,
+    timeout: Option<Duration>,
+    ttl: u8,
+}
+
+impl InMemoryTcpListener {
+    fn accept_within(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let mut backlog = self.backlog.wait(timeout)?;
+        let (socket, addr) = backlog
+            .items
+            .pop_front()
+            .ok_or(NetworkError::ConnectionAborted)?;
+
+        Ok((Box::new(socket), addr))
+    }
+}
+
+impl VirtualTcpListener for InMemoryTcpListener {
+    fn accept(&self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        self.accept_within(self.timeout)
+    }
+
+    fn accept_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        self.accept_within(Some(timeout))
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn timeout(&self) -> Result<Option<Duration>> {
+        Ok(self.timeout)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(self.ttl)
+    }
+
+    fn readiness(&self) -> Result<SocketReadiness> {
+        Ok(SocketReadiness {
+            readable: self.backlog.is_ready(|_| true),
+            writable: false,
+            hangup: self.backlog.is_closed(),
+        })
+    }
+
+    fn set_waker(&mut self, waker: SocketWaker) -> bool {
+        self.backlog.set_waker(waker);
+        true
+    }
+}
+
+impl Drop for InMemoryTcpListener {
+    fn drop(&mut self) {
+        self.backlog.close();
+        if let Ok(mut network) = self.network.lock() {
+            network.listeners.remove(&self.addr);
+        }
+    }
+}
+
+/// One end of a TCP connection of an [`InMemoryNetworking`].
+#[derive(Debug)]
+pub struct InMemoryTcpSocket {
+    network: InMemoryNetworking,
+    addr: SocketAddr,
+    peer: SocketAddr,
+    /// The bytes sent by the peer.
+    incoming: Arc<Queue<u8>>,
+    /// The bytes sent to the peer.
+    outgoing: Arc<Queue<u8>>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    linger: Option<Duration>,
+    nodelay: bool,
+    ttl: u32,
+}
+
+impl InMemoryTcpSocket {
+    /// Creates the two ends of a connection from `addr` to `peer`.
+    fn pair(network: &InMemoryNetworking, addr: SocketAddr, peer: SocketAddr) -> (Self, Self) {
+        let to_peer = Arc::new(Queue::default());
+        let from_peer = Arc::new(Queue::default());
+        let end = |addr, peer, incoming, outgoing| Self {
+            network: network.clone(),
+            addr,
+            peer,
+            incoming,
+            outgoing,
+            read_timeout: None,
+            write_timeout: None,
+            connect_timeout: None,
+            linger: None,
+            nodelay: false,
+            ttl: 64,
+        };
+
+        (
+            end(addr, peer, from_peer.clone(), to_peer.clone()),
+            end(peer, addr, to_peer, from_peer),
+        )
+    }
+
+    fn receive(&mut self, consume: bool) -> Result<SocketReceive> {
+        let mut incoming = self.incoming.wait(self.read_timeout)?;
+        let len = incoming.items.len().min(MAX_RECV_SIZE);
+        let data = if consume {
+            incoming.items.drain(..len).collect::<Vec<_>>()
+        } else {
+            incoming.items.iter().take(len).copied().collect::<Vec<_>>()
+        };
+
+        // An empty receive is the end of the stream.
+        Ok(SocketReceive {
+            data: Bytes::from(data),
+            truncated: false,
+        })
+    }
+}
+
+impl VirtualTcpSocket for InMemoryTcpSocket {
+    fn set_opt_time(&mut self, ty: TimeType, timeout: Option<Duration>) -> Result<()> {
+        match ty {
+            TimeType::ReadTimeout => self.read_timeout = timeout,
+            TimeType::WriteTimeout => self.write_timeout = timeout,
+            TimeType::ConnectTimeout => self.connect_timeout = timeout,
+            TimeType::Linger => self.linger = timeout,
+            _ => return Err(NetworkError::InvalidInput),
+        }
+        Ok(())
+    }
+
+    fn opt_time(&self, ty: TimeType) -> Result<Option<Duration>> {
+        match ty {
+            TimeType::ReadTimeout => Ok(self.read_timeout),
+            TimeType::WriteTimeout => Ok(self.write_timeout),
+            TimeType::ConnectTimeout => Ok(self.connect_timeout),
+            TimeType::Linger => Ok(self.linger),
+            _ => Err(NetworkError::InvalidInput),
+        }
+    }
+
+    fn set_recv_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_send_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.nodelay = nodelay;
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(self.nodelay)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        if matches!(how, Shutdown::Read | Shutdown::Both) {
+            self.incoming.close();
+        }
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.outgoing.close();
+        }
+        Ok(())
+    }
+}
+
+impl VirtualConnectedSocket for InMemoryTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.linger = linger;
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(self.linger)
+    }
+
+    fn send(&mut self, data: Bytes) -> Result<usize> {
+        self.outgoing.push(data.iter().copied())?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<SocketReceive> {
+        self.receive(true)
+    }
+
+    fn peek(&mut self) -> Result<SocketReceive> {
+        self.receive(false)
+    }
+}
+
+impl VirtualSocket for InMemoryTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.ttl)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        if self.incoming.is_closed() && self.outgoing.is_closed() {
+            Ok(SocketStatus::Closed)
+        } else {
+            Ok(SocketStatus::Opened)
+        }
+    }
+
+    fn readiness(&self) -> Result<SocketReadiness> {
+        Ok(SocketReadiness {
+            readable: self.incoming.is_ready(|_| true),
+            writable: !self.outgoing.is_closed(),
+            hangup: self.incoming.is_closed(),
+        })
+    }
+
+    fn set_waker(&mut self, waker: SocketWaker) -> bool {
+        self.incoming.set_waker(waker);
+        true
+    }
+}
+
+impl Drop for InMemoryTcpSocket {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+        if let Ok(mut network) = self.network.lock() {
+            if let Some(count) = network.tcp_ports.get_mut(&self.addr) {
+                *count -= 1;
+                if *count == 0 {
+                    network.tcp_ports.remove(&self.addr);
+                }
+            }
+        }
+    }
+}
+
+/// A UDP socket of an [`InMemoryNetworking`].
+#[derive(Debug)]
+pub struct InMemoryUdpSocket {
+    network: InMemoryNetworking,
+    addr: SocketAddr,
+    peer: Option<SocketAddr>,
+    mailbox: Arc<Mailbox>,
+    ttl: u32,
+    broadcast: bool,
+}
+
+impl InMemoryUdpSocket {
+    fn receive_from(&mut self, consume: bool) -> Result<SocketReceiveFrom> {
+        let mut mailbox = loop {
+            let mut mailbox = self.mailbox.wait(None)?;
+            // A connected socket only receives the datagrams of its peer.
+            if let Some(peer) = self.peer {
+                mailbox.items.retain(|(_, addr)| *addr == peer);
+            }
+            if !mailbox.items.is_empty() || mailbox.closed {
+                break mailbox;
+            }
+        };
+
+        let (data, addr) = if consume {
+            mailbox.items.pop_front()
+        } else {
+            mailbox.items.front().cloned()
+        }
+        .ok_or(NetworkError::ConnectionAborted)?;
+
+        Ok(SocketReceiveFrom {
+            data,
+            truncated: false,
+            addr,
+        })
+    }
+}
+
+impl VirtualUdpSocket for InMemoryUdpSocket {
+    fn connect(&mut self, addr: SocketAddr) -> Result<()> {
+        self.peer = Some(addr);
+        Ok(())
+    }
+
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.broadcast = broadcast;
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(self.broadcast)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(self.peer)
+    }
+}
+
+impl VirtualConnectedSocket for InMemoryUdpSocket {
+    fn set_linger(&mut self, _linger: Option<Duration>) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn send(&mut self, data: Bytes) -> Result<usize> {
+        let peer = self.peer.ok_or(NetworkError::NotConnected)?;
+        self.send_to(data, peer)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<SocketReceive> {
+        let received = self.receive_from(true)?;
+        Ok(SocketReceive {
+            data: received.data,
+            truncated: received.truncated,
+        })
+    }
+
+    fn peek(&mut self) -> Result<SocketReceive> {
+        let received = self.receive_from(false)?;
+        Ok(SocketReceive {
+            data: received.data,
+            truncated: received.truncated,
+        })
+    }
+}
+
+impl VirtualConnectionlessSocket for InMemoryUdpSocket {
+    fn send_to(&mut self, data: Bytes, addr: SocketAddr) -> Result<usize> {
+        let len = data.len();
+        let mailbox = Network::lookup(&self.network.lock()?.udp_sockets, addr);
+
+        // Like on a real network, datagrams to nowhere are silently lost.
+        if let Some(mailbox) = mailbox {
+            let _ = mailbox.push(Some((data, self.addr)));
+        }
+
+        Ok(len)
+    }
+
+    fn recv_from(&mut self) -> Result<SocketReceiveFrom> {
+        self.receive_from(true)
+    }
+
+    fn peek_from(&mut self) -> Result<SocketReceiveFrom> {
+        self.receive_from(false)
+    }
+}
+
+impl VirtualSocket for InMemoryUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.ttl)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn readiness(&self) -> Result<SocketReadiness> {
+        let peer = self.peer;
+        Ok(SocketReadiness {
+            readable: self
+                .mailbox
+                .is_ready(|(_, addr)| peer.map_or(true, |peer| *addr == peer)),
+            writable: true,
+            hangup: false,
+        })
+    }
+
+    fn set_waker(&mut self, waker: SocketWaker) -> bool {
+        self.mailbox.set_waker(waker);
+        true
+    }
+}
+
+impl Drop for InMemoryUdpSocket {
+    fn drop(&mut self) {
+        self.mailbox.close();
+        if let Ok(mut network) = self.network.lock() {
+            network.udp_sockets.remove(&self.addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_tcp() {
+        let network = InMemoryNetworking::new();
+        let listener = network
+            .listen_tcp(addr("0.0.0.0:8080"), false, false, false)
+            .unwrap();
+
+        let client = network.clone();
+        let handle = thread::spawn(move || {
+            let mut socket = client
+                .connect_tcp(addr("0.0.0.0:0"), addr("10.0.0.1:8080"), None)
+                .unwrap();
+            socket.send(Bytes::from_static(b"ping")).unwrap();
+            let pong = socket.recv().unwrap().data;
+            socket.shutdown(Shutdown::Write).unwrap();
+            pong
+        });
+
+        let (mut socket, peer) = listener.accept().unwrap();
+        assert_eq!(peer.ip(), addr("10.0.0.1:0").ip());
+        assert!(peer.port() >= FIRST_EPHEMERAL_PORT);
+        assert_eq!(socket.addr_peer().unwrap(), peer);
+        assert_eq!(&socket.recv().unwrap().data[..], b"ping");
+        socket.send(Bytes::from_static(b"pong")).unwrap();
+
+        assert_eq!(&handle.join().unwrap()[..], b"pong");
+        assert!(
+            socket.recv().unwrap().data.is_empty(),
+            "the end of the stream",
+        );
+    }
+
+    #[test]
+    fn test_tcp_errors() {
+        let network = InMemoryNetworking::new();
+        assert_eq!(
+            network
+                .connect_tcp(addr("0.0.0.0:0"), addr("10.0.0.1:8080"), None)
+                .unwrap_err(),
+            NetworkError::ConnectionRefused,
+        );
+
+        let mut listener = network
+            .listen_tcp(addr("10.0.0.1:8080"), false, false, false)
+            .unwrap();
+        assert_eq!(
+            network
+                .listen_tcp(addr("10.0.0.1:8080"), false, false, false)
+                .unwrap_err(),
+            NetworkError::AddressInUse,
+        );
+        listener
+            .set_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        assert_eq!(listener.accept().unwrap_err(), NetworkError::TimedOut);
+
+        let mut socket = network
+            .connect_tcp(addr("0.0.0.0:0"), addr("10.0.0.1:8080"), None)
+            .unwrap();
+        drop(listener.accept().unwrap());
+        assert_eq!(
+            socket.send(Bytes::from_static(b"ping")).unwrap_err(),
+            NetworkError::BrokenPipe,
+        );
+
+        drop(listener);
+        network
+            .listen_tcp(addr("10.0.0.1:8080"), false, false, false)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_udp() {
+        let network = InMemoryNetworking::new();
+        let mut server = network.bind_udp(addr("10.0.0.1:53"), false, false).unwrap();
+        let mut client = network.bind_udp(addr("10.0.0.2:0"), false, false).unwrap();
+        let client_addr = client.addr_local().unwrap();
+
+        client.connect(addr("10.0.0.1:53")).unwrap();
+        client.send(Bytes::from_static(b"query")).unwrap();
+        let query = server.recv_from().unwrap();
+        assert_eq!(&query.data[..], b"query");
+        assert_eq!(query.addr, client_addr);
+
+        server
+            .send_to(Bytes::from_static(b"answer"), client_addr)
+            .unwrap();
+        assert_eq!(&client.recv().unwrap().data[..], b"answer");
+    }
+
+    #[test]
+    fn test_readiness() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let network = InMemoryNetworking::new();
+        let mut listener = network
+            .listen_tcp(addr("10.0.0.1:8080"), false, false, false)
+            .unwrap();
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let waker = {
+            let wakes = wakes.clone();
+            Arc::new(move || {
+                wakes.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        assert!(listener.set_waker(waker.clone()));
+        assert!(!listener.readiness().unwrap().readable);
+
+        let mut client = network
+            .connect_tcp(addr("0.0.0.0:0"), addr("10.0.0.1:8080"), None)
+            .unwrap();
+        assert_eq!(wakes.load(Ordering::SeqCst), 1);
+        assert!(listener.readiness().unwrap().readable);
+
+        let (mut socket, _) = listener.accept().unwrap();
+        assert!(socket.set_waker(waker));
+        assert_eq!(
+            socket.readiness().unwrap(),
+            SocketReadiness {
+                readable: false,
+                writable: true,
+                hangup: false,
+            }
+        );
+        client.send(Bytes::from_static(b"ping")).unwrap();
+        assert_eq!(wakes.load(Ordering::SeqCst), 2);
+        assert!(socket.readiness().unwrap().readable);
+
+        drop(client);
+        assert!(socket.readiness().unwrap().hangup);
+    }
+}
@@ -10,6 +10,9 @@ use std::sync::Mutex;
 use std::time::Duration;
 use thiserror::Error;
 
+pub mod in_memory;
+pub mod policy;
+
 pub use bytes::Bytes;
 pub use bytes::BytesMut;
 
@@ -193,6 +196,21 @@ pub struct SocketReceiveFrom {
     pub addr: SocketAddr,
 }
 
+/// Called by a socket when its readiness may have changed, see
+/// [`VirtualSocket::set_waker`]
+pub type SocketWaker = Arc<dyn Fn() + Send + Sync + 'static>;
+
+/// What can be done with a socket without blocking
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SocketReadiness {
+    /// Data can be received, or a connection accepted
+    pub readable: bool,
+    /// Data can be sent
+    pub writable: bool,
+    /// The connection is closed
+    pub hangup: bool,
+}
+
 pub trait VirtualTcpListener: fmt::Debug + Send + Sync + 'static {
     /// Accepts an connection attempt that was made to this listener
     fn accept(&self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>;
@@ -217,6 +235,26 @@ pub trait VirtualTcpListener: fmt::Debug + Send + Sync + 'static {
 
     /// Returns the maximum number of network hops before packets are dropped
     fn ttl(&self) -> Result<u8>;
+
+    /// Returns the host socket backing this listener, if any, which the
+    /// runtime waits on with the event loop of the host
+    fn poll_fd(&self) -> Option<SocketDescriptor> {
+        None
+    }
+
+    /// Returns what can be done with the listener without blocking, for
+    /// the listeners that aren't backed by a host socket
+    fn readiness(&self) -> Result<SocketReadiness> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Registers a waker that the listener calls whenever its readiness may
+    /// have changed, replacing the previous one. Returns `false` if the
+    /// listener never calls it, in which case the runtime checks the
+    /// readiness again from time to time.
+    fn set_waker(&mut self, _waker: SocketWaker) -> bool {
+        false
+    }
 }
 
 pub trait VirtualSocket: fmt::Debug + Send + Sync + 'static {
@@ -231,6 +269,26 @@ pub trait VirtualSocket: fmt::Debug + Send + Sync + 'static {
 
     /// Returns the status/state of the socket
     fn status(&self) -> Result<SocketStatus>;
+
+    /// Returns the host socket backing this socket, if any, which the
+    /// runtime waits on with the event loop of the host
+    fn poll_fd(&self) -> Option<SocketDescriptor> {
+        None
+    }
+
+    /// Returns what can be done with the socket without blocking, for
+    /// the sockets that aren't backed by a host socket
+    fn readiness(&self) -> Result<SocketReadiness> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Registers a waker that the socket calls whenever its readiness may
+    /// have changed, replacing the previous one. Returns `false` if the
+    /// socket never calls it, in which case the runtime checks the
+    /// readiness again from time to time.
+    fn set_waker(&mut self, _waker: SocketWaker) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -637,6 +695,9 @@ pub enum NetworkError {
     /// The operation is not supported.
     #[error("unsupported")]
     Unsupported,
+    /// There isn't enough memory, or room in a table, to complete the operation
+    #[error("insufficient memory")]
+    InsufficientMemory,
     /// Some other unhandled error. If you see this, it's probably a bug.
     #[error("unknown error found")]
     UnknownError,
@@ -666,6 +727,7 @@ pub fn net_error_into_io_err(net_error: NetworkError) -> std::io::Error {
         NetworkError::WouldBlock => ErrorKind::WouldBlock.into(),
         NetworkError::WriteZero => ErrorKind::WriteZero.into(),
         NetworkError::Unsupported => ErrorKind::Unsupported.into(),
+        NetworkError::InsufficientMemory => ErrorKind::OutOfMemory.into(),
         NetworkError::UnknownError => ErrorKind::BrokenPipe.into(),
     }
 }
@@ -690,6 +752,7 @@ pub fn io_err_into_net_error(net_error: std::io::Error) -> NetworkError {
         ErrorKind::WouldBlock => NetworkError::WouldBlock,
         ErrorKind::WriteZero => NetworkError::WriteZero,
         ErrorKind::Unsupported => NetworkError::Unsupported,
+        ErrorKind::OutOfMemory => NetworkError::InsufficientMemory,
         _ => NetworkError::UnknownError,
     }
 }
@@ -0,0 +1,732 @@
+//! Restricting what another [`VirtualNetworking`] can reach.
+//!
+//! A [`NetworkPolicy`] is a list of rules allowing accesses, and a list of
+//! rules denying them. An access is permitted when it matches no denying
+//! rule and, unless there are none, an allowing rule. The rules are
+//! checked when connecting a TCP socket, listening on one, binding a UDP
+//! socket and resolving a hostname; the datagrams sent by a bound UDP
+//! socket aren't filtered.
+//!
+//! Hostname rules apply to the addresses the hostnames were resolved to
+//! through the [`PolicyNetworking`], so that allowing `example.com`
+//! allows connecting to the addresses of `example.com`. IPv4-mapped IPv6
+//! addresses are checked as the IPv4 addresses they map.
+//!
+//! Web sockets and HTTP requests are checked against the host and port
+//! of their URL, and every address the host resolves to. When the host
+//! can't be resolved, they are denied unless the policy has no rules.
+
+use crate::{
+    IpCidr, IpRoute, NetworkError, Result, SocketHttpRequest, StreamSecurity, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualWebSocket,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+const ALL_PORTS: RangeInclusive<u16> = 0..=u16::MAX;
+
+/// What a [`NetworkRule`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkTarget {
+    /// Every address and hostname.
+    Any,
+    /// The addresses of a network.
+    Cidr(IpCidr),
+    /// A hostname, or with a `*.` prefix all the subdomains of a domain.
+    Host(String),
+}
+
+/// A rule of a [`NetworkPolicy`].
+///
+/// Rules are parsed from a target optionally followed by a port, or an
+/// inclusive range of ports, like `*`, `*:53`, `10.0.0.0/8`,
+/// `192.168.1.1:8000-9000`, `[fd00::/8]:443`, `example.com:443` or
+/// `*.example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkRule {
+    pub target: NetworkTarget,
+    pub ports: RangeInclusive<u16>,
+}
+
+/// A [`NetworkRule`] failed to parse.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid network rule `{0}`")]
+pub struct InvalidNetworkRule(String);
+
+impl FromStr for NetworkRule {
+    type Err = InvalidNetworkRule;
+
+    fn from_str(rule: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || InvalidNetworkRule(rule.to_string());
+
+        // IPv6 addresses have colons, and need brackets to take a port.
+        let (target, ports) = if let Some(rest) = rule.strip_prefix('[') {
+            let (target, rest) = rest.split_once(']').ok_or_else(invalid)?;
+            match rest {
+                "" => (target, None),
+                _ => (target, Some(rest.strip_prefix(':').ok_or_else(invalid)?)),
+            }
+        } else if rule.matches(':').count() == 1 {
+            let (target, ports) = rule.split_once(':').ok_or_else(invalid)?;
+            (target, Some(ports))
+        } else {
+            (rule, None)
+        };
+
+        let target = parse_target(target).ok_or_else(invalid)?;
+        let ports = match ports {
+            Some(ports) => parse_ports(ports).ok_or_else(invalid)?,
+            None => ALL_PORTS,
+        };
+
+        Ok(Self { target, ports })
+    }
+}
+
+fn parse_target(target: &str) -> Option<NetworkTarget> {
+    if target == "*" {
+        return Some(NetworkTarget::Any);
+    }
+
+    if let Some((ip, prefix)) = target.split_once('/') {
+        let ip = ip.parse::<IpAddr>().ok()?;
+        let prefix = prefix.parse::<u8>().ok()?;
+        if prefix > max_prefix(ip) {
+            return None;
+        }
+        return Some(NetworkTarget::Cidr(IpCidr { ip, prefix }));
+    }
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        let prefix = max_prefix(ip);
+        return Some(NetworkTarget::Cidr(IpCidr { ip, prefix }));
+    }
+
+    let host = target.trim_end_matches('.').to_ascii_lowercase();
+    let name = host.strip_prefix("*.").unwrap_or(&host);
+    let is_valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_');
+
+    if is_valid {
+        Some(NetworkTarget::Host(host))
+    } else {
+        None
+    }
+}
+
+fn parse_ports(ports: &str) -> Option<RangeInclusive<u16>> {
+    let (start, end) = ports.split_once('-').unwrap_or((ports, ports));
+    let start = start.parse::<u16>().ok()?;
+    let end = end.parse::<u16>().ok()?;
+
+    if start <= end {
+        Some(start..=end)
+    } else {
+        None
+    }
+}
+
+fn max_prefix(ip: IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// `ip`, or the IPv4 address it maps if it is an IPv4-mapped IPv6 one.
+fn unmap(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+fn cidr_contains(cidr: &IpCidr, ip: IpAddr) -> bool {
+    // A mapped network only keeps the part of its prefix covering the
+    // IPv4 address.
+    let (network, prefix) = match cidr.ip {
+        IpAddr::V6(v6) if cidr.prefix >= 96 && v6.to_ipv4_mapped().is_some() => (
+            unmap(cidr.ip),
+            cidr.prefix.min(max_prefix(cidr.ip)).saturating_sub(96),
+        ),
+        _ => (cidr.ip, cidr.prefix.min(max_prefix(cidr.ip))),
+    };
+    let (network, ip) = match (network, unmap(ip)) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => (
+            u128::from(u32::from(network)) << 96,
+            u128::from(u32::from(ip)) << 96,
+        ),
+        (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(ip)),
+        _ => return false,
+    };
+    let prefix = u32::from(prefix);
+
+    prefix == 0 || (network ^ ip) >> (128 - prefix) == 0
+}
+
+/// The host and port of `url`, the port defaulting to the one of its
+/// scheme.
+fn url_host_port(url: &str) -> Option<(&str, Option<u16>)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(|c| c == '/' || c == '?' || c == '#').next()?;
+    let authority = authority.rsplit('@').next()?;
+
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        (host, rest.strip_prefix(':'))
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    let port = match port {
+        Some(port) => Some(port.parse().ok()?),
+        None => match scheme.to_ascii_lowercase().as_str() {
+            "http" | "ws" => Some(80),
+            "https" | "wss" => Some(443),
+            _ => None,
+        },
+    };
+
+    if host.is_empty() {
+        None
+    } else {
+        Some((host, port))
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    match pattern.strip_prefix('*') {
+        Some(suffix) => host.ends_with(suffix),
+        None => host == pattern,
+    }
+}
+
+/// An access checked against the rules, the unknown or unspecified parts
+/// being `None`.
+#[derive(Debug, Clone, Copy)]
+struct Access<'a> {
+    ip: Option<IpAddr>,
+    host: Option<&'a str>,
+    port: Option<u16>,
+}
+
+impl<'a> Access<'a> {
+    fn to(addr: SocketAddr, host: Option<&'a str>) -> Self {
+        Self {
+            ip: Some(addr.ip()).filter(|ip| !ip.is_unspecified()),
+            host,
+            port: Some(addr.port()).filter(|port| *port != 0),
+        }
+    }
+}
+
+impl NetworkRule {
+    /// Whether the rule matches `access`. The unknown parts of the access
+    /// match anything unless `strict` is set, in which case they only
+    /// match rules covering all their possible values.
+    fn matches(&self, access: &Access, strict: bool) -> bool {
+        let ports = match access.port {
+            Some(port) => self.ports.contains(&port),
+            None => !strict || self.ports == ALL_PORTS,
+        };
+        let target = match (&self.target, access.ip) {
+            (NetworkTarget::Any, _) => true,
+            (NetworkTarget::Host(pattern), ip) => match access.host {
+                Some(host) => host_matches(pattern, host),
+                None => ip.is_none() && !strict,
+            },
+            (NetworkTarget::Cidr(cidr), None) => !strict || cidr.prefix == 0,
+            (NetworkTarget::Cidr(cidr), Some(ip)) => cidr_contains(cidr, ip),
+        };
+
+        ports && target
+    }
+}
+
+/// Rules allowing and denying network accesses, see the [module
+/// documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkPolicy {
+    pub allow: Vec<NetworkRule>,
+    pub deny: Vec<NetworkRule>,
+}
+
+impl NetworkPolicy {
+    /// Creates a policy from its allowing and denying rules.
+    pub fn new(allow: Vec<NetworkRule>, deny: Vec<NetworkRule>) -> Self {
+        Self { allow, deny }
+    }
+
+    fn check(&self, access: &Access) -> Result<()> {
+        let denied = self.deny.iter().any(|rule| rule.matches(access, true));
+        let allowed =
+            self.allow.is_empty() || self.allow.iter().any(|rule| rule.matches(access, false));
+
+        if allowed && !denied {
+            Ok(())
+        } else {
+            Err(NetworkError::PermissionDenied)
+        }
+    }
+}
+
+/// A [`VirtualNetworking`] enforcing a [`NetworkPolicy`] on another one,
+/// failing the denied accesses with [`NetworkError::PermissionDenied`].
+///
+/// ```
+/// use wasmer_vnet::policy::{NetworkPolicy, PolicyNetworking};
+/// use wasmer_vnet::UnsupportedVirtualNetworking;
+///
+/// let policy = NetworkPolicy::new(
+///     vec!["*.example.com:443".parse().unwrap()],
+///     vec!["10.0.0.0/8".parse().unwrap()],
+/// );
+/// let networking = PolicyNetworking::new(UnsupportedVirtualNetworking::default(), policy);
+/// ```
+#[derive(Debug)]
+pub struct PolicyNetworking {
+    inner: Box<dyn VirtualNetworking + Sync>,
+    policy: NetworkPolicy,
+    /// The hostnames the addresses were resolved from.
+    resolved: Mutex<HashMap<IpAddr, String>>,
+}
+
+impl PolicyNetworking {
+    /// Enforces `policy` on `inner`.
+    pub fn new(inner: impl VirtualNetworking + Sync, policy: NetworkPolicy) -> Self {
+        Self::from_boxed(Box::new(inner), policy)
+    }
+
+    /// Like [`PolicyNetworking::new`] with a boxed implementation.
+    pub fn from_boxed(inner: Box<dyn VirtualNetworking + Sync>, policy: NetworkPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            resolved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The enforced policy.
+    pub fn policy(&self) -> &NetworkPolicy {
+        &self.policy
+    }
+
+    /// The wrapped implementation.
+    pub fn inner(&self) -> &(dyn VirtualNetworking + Sync) {
+        self.inner.as_ref()
+    }
+
+    fn check_addr(&self, addr: SocketAddr) -> Result<()> {
+        let resolved = self.resolved.lock().map_err(|_| NetworkError::Lock)?;
+        let host = resolved.get(&unmap(addr.ip())).map(String::as_str);
+
+        self.policy.check(&Access::to(addr, host))
+    }
+
+    /// Checks the host and port of `url`, along with the addresses the
+    /// host resolves to.
+    fn check_url(&self, url: &str) -> Result<()> {
+        let has_rules = !self.policy.allow.is_empty() || !self.policy.deny.is_empty();
+        let (host, port) = match url_host_port(url) {
+            Some(host_port) => host_port,
+            None if has_rules => return Err(NetworkError::PermissionDenied),
+            None => return Ok(()),
+        };
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return self.policy.check(&Access {
+                ip: Some(ip),
+                host: None,
+                port,
+            });
+        }
+
+        let access = Access {
+            ip: None,
+            host: Some(host),
+            port,
+        };
+        self.policy.check(&access)?;
+        if !has_rules {
+            return Ok(());
+        }
+
+        match self.inner.resolve(host, port, None) {
+            Ok(addrs) if !addrs.is_empty() => addrs.into_iter().try_for_each(|ip| {
+                self.policy.check(&Access {
+                    ip: Some(ip),
+                    ..access
+                })
+            }),
+            _ => Err(NetworkError::PermissionDenied),
+        }
+    }
+}
+
+impl VirtualNetworking for PolicyNetworking {
+    fn ws_connect(&self, url: &str) -> Result<Box<dyn VirtualWebSocket + Sync>> {
+        self.check_url(url)?;
+        self.inner.ws_connect(url)
+    }
+
+    fn http_request(
+        &self,
+        url: &str,
+        method: &str,
+        headers: &str,
+        gzip: bool,
+    ) -> Result<SocketHttpRequest> {
+        self.check_url(url)?;
+        self.inner.http_request(url, method, headers, gzip)
+    }
+
+    fn bridge(&self, network: &str, access_token: &str, security: StreamSecurity) -> Result<()> {
+        self.inner.bridge(network, access_token, security)
+    }
+
+    fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge()
+    }
+
+    fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire()
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix)
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip)
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear()
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list()
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac()
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr)
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear()
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list()
+    }
+
+    fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        // Raw sockets would bypass every rule.
+        Err(NetworkError::PermissionDenied)
+    }
+
+    fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.check_addr(addr)?;
+        self.inner.listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+    }
+
+    fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.check_addr(addr)?;
+        self.inner.bind_udp(addr, reuse_port, reuse_addr)
+    }
+
+    fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.check_addr(SocketAddr::new(addr, 0))?;
+        self.inner.bind_icmp(addr)
+    }
+
+    fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+        timeout: Option<Duration>,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.check_addr(peer)?;
+        self.inner.connect_tcp(addr, peer, timeout)
+    }
+
+    fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        // Denied hostnames aren't even looked up.
+        let access = Access {
+            ip: host.parse().ok(),
+            host: Some(host),
+            port,
+        };
+        self.policy.check(&access)?;
+
+        let addrs = self.inner.resolve(host, port, dns_server)?;
+        let allowed = addrs
+            .iter()
+            .copied()
+            .filter(|ip| {
+                let access = Access {
+                    ip: Some(*ip),
+                    ..access
+                };
+                self.policy.check(&access).is_ok()
+            })
+            .collect::<Vec<_>>();
+        if allowed.is_empty() && !addrs.is_empty() {
+            return Err(NetworkError::PermissionDenied);
+        }
+
+        let mut resolved = self.resolved.lock().map_err(|_| NetworkError::Lock)?;
+        for ip in allowed.iter() {
+            resolved.insert(unmap(*ip), host.to_string());
+        }
+
+        Ok(allowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory::InMemoryNetworking;
+
+    fn rule(rule: &str) -> NetworkRule {
+        rule.parse().unwrap()
+    }
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(
+            rule("*:53"),
+            NetworkRule {
+                target: NetworkTarget::Any,
+                ports: 53..=53,
+            },
+        );
+        assert_eq!(
+            rule("10.0.0.0/8:8000-9000"),
+            NetworkRule {
+                target: NetworkTarget::Cidr(IpCidr {
+                    ip: "10.0.0.0".parse().unwrap(),
+                    prefix: 8,
+                }),
+                ports: 8000..=9000,
+            },
+        );
+        assert_eq!(
+            rule("[::1]:443"),
+            NetworkRule {
+                target: NetworkTarget::Cidr(IpCidr {
+                    ip: "::1".parse().unwrap(),
+                    prefix: 128,
+                }),
+                ports: 443..=443,
+            },
+        );
+        assert_eq!(rule("fd00::/8").ports, ALL_PORTS);
+        assert_eq!(
+            rule("*.Example.com.").target,
+            NetworkTarget::Host("*.example.com".to_string()),
+        );
+
+        for invalid in &[
+            "",
+            "10.0.0.0/33",
+            "*:90-80",
+            "[::1",
+            "exa mple.com",
+            "a:b:c",
+        ] {
+            assert!(invalid.parse::<NetworkRule>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let network = InMemoryNetworking::new();
+        let _listener = network
+            .listen_tcp(addr("0.0.0.0:80"), false, false, false)
+            .unwrap();
+        network
+            .add_host("www.example.com", vec!["10.0.0.1".parse().unwrap()])
+            .unwrap();
+        network
+            .add_host("example.org", vec!["10.0.0.2".parse().unwrap()])
+            .unwrap();
+
+        let policy = NetworkPolicy::new(
+            vec![rule("*.example.com:80"), rule("192.168.0.0/16")],
+            vec![rule("192.168.1.1")],
+        );
+        let networking = PolicyNetworking::new(network, policy);
+
+        assert_eq!(
+            networking
+                .connect_tcp(addr("0.0.0.0:0"), addr("10.0.0.1:80"), None)
+                .unwrap_err(),
+            NetworkError::PermissionDenied,
+            "the hostname isn't resolved yet",
+        );
+        let addrs = networking.resolve("www.example.com", None, None).unwrap();
+        networking
+            .connect_tcp(addr("0.0.0.0:0"), SocketAddr::new(addrs[0], 80), None)
+            .unwrap();
+        assert_eq!(
+            networking
+                .connect_tcp(addr("0.0.0.0:0"), SocketAddr::new(addrs[0], 8080), None)
+                .unwrap_err(),
+            NetworkError::PermissionDenied,
+        );
+        assert_eq!(
+            networking.resolve("example.org", None, None).unwrap_err(),
+            NetworkError::PermissionDenied,
+        );
+
+        networking
+            .connect_tcp(addr("0.0.0.0:0"), addr("192.168.1.2:80"), None)
+            .unwrap();
+        assert_eq!(
+            networking
+                .connect_tcp(addr("0.0.0.0:0"), addr("192.168.1.1:80"), None)
+                .unwrap_err(),
+            NetworkError::PermissionDenied,
+        );
+
+        networking
+            .bind_udp(addr("0.0.0.0:0"), false, false)
+            .unwrap();
+        assert_eq!(
+            networking
+                .bind_udp(addr("192.168.1.1:0"), false, false)
+                .unwrap_err(),
+            NetworkError::PermissionDenied,
+        );
+    }
+
+    #[test]
+    fn test_ipv4_mapped_addresses() {
+        let policy = NetworkPolicy::new(vec![], vec![rule("10.0.0.0/8")]);
+        let networking = PolicyNetworking::new(InMemoryNetworking::new(), policy);
+
+        assert_eq!(
+            networking
+                .connect_tcp(addr("[::]:0"), addr("[::ffff:10.0.0.1]:80"), None)
+                .unwrap_err(),
+            NetworkError::PermissionDenied,
+        );
+        assert!(cidr_contains(
+            &IpCidr {
+                ip: "::ffff:10.0.0.0".parse().unwrap(),
+                prefix: 104,
+            },
+            "10.1.2.3".parse().unwrap(),
+        ));
+        assert!(!cidr_contains(
+            &IpCidr {
+                ip: "10.0.0.0".parse().unwrap(),
+                prefix: 8,
+            },
+            "::10.0.0.1".parse().unwrap(),
+        ));
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(
+            url_host_port("https://user@example.com/path?query"),
+            Some(("example.com", Some(443))),
+        );
+        assert_eq!(url_host_port("ws://[::1]:8080/"), Some(("::1", Some(8080))),);
+        assert_eq!(url_host_port("example.com"), None);
+
+        let network = InMemoryNetworking::new();
+        network
+            .add_host("www.example.com", vec!["10.0.0.1".parse().unwrap()])
+            .unwrap();
+        network
+            .add_host("evil.example.com", vec!["192.168.1.1".parse().unwrap()])
+            .unwrap();
+        let policy = NetworkPolicy::new(
+            vec![rule("*.example.com:443")],
+            vec![rule("192.168.0.0/16")],
+        );
+        let networking = PolicyNetworking::new(network, policy);
+
+        // The allowed requests reach the in-memory network, which doesn't
+        // support them.
+        assert_eq!(
+            networking
+                .http_request("https://www.example.com/", "GET", "", false)
+                .unwrap_err(),
+            NetworkError::Unsupported,
+        );
+        for url in &[
+            "http://www.example.com/",
+            "https://evil.example.com/",
+            "https://unknown.example.com/",
+            "https://10.0.0.1/",
+            "not a url",
+        ] {
+            assert_eq!(
+                networking.http_request(url, "GET", "", false).unwrap_err(),
+                NetworkError::PermissionDenied,
+                "{}",
+                url,
+            );
+        }
+        assert_eq!(
+            networking
+                .ws_connect("wss://evil.example.com/")
+                .unwrap_err(),
+            NetworkError::PermissionDenied,
+        );
+    }
+}
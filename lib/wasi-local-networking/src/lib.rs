@@ -2,6 +2,8 @@
 use bytes::{Bytes, BytesMut};
 use std::io::{Read, Write};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
 use std::time::Duration;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
@@ -250,6 +252,13 @@ impl VirtualTcpListener for LocalTcpListener {
             .map(|ttl| ttl as u8)
             .map_err(io_err_into_net_error)
     }
+
+    #[cfg(unix)]
+    fn poll_fd(&self) -> Option<wasmer_vnet::SocketDescriptor> {
+        Some(wasmer_vnet::SocketDescriptor::from(
+            self.stream.as_raw_fd() as u32
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -408,6 +417,13 @@ impl VirtualSocket for LocalTcpStream {
     fn status(&self) -> Result<SocketStatus> {
         Ok(SocketStatus::Opened)
     }
+
+    #[cfg(unix)]
+    fn poll_fd(&self) -> Option<wasmer_vnet::SocketDescriptor> {
+        Some(wasmer_vnet::SocketDescriptor::from(
+            self.stream.as_raw_fd() as u32
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -581,4 +597,11 @@ impl VirtualSocket for LocalUdpSocket {
     fn status(&self) -> Result<SocketStatus> {
         Ok(SocketStatus::Opened)
     }
+
+    #[cfg(unix)]
+    fn poll_fd(&self) -> Option<wasmer_vnet::SocketDescriptor> {
+        Some(wasmer_vnet::SocketDescriptor::from(
+            self.0.as_raw_fd() as u32
+        ))
+    }
 }
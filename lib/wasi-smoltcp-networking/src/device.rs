@@ -0,0 +1,380 @@
+//! The devices the Ethernet frames of a stack go through.
+
+use smoltcp::phy::{self, DeviceCapabilities, Medium};
+use smoltcp::time::Instant;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// The MTU of the devices not telling theirs, the one of Ethernet.
+pub const DEFAULT_MTU: usize = 1514;
+
+/// A device sending and receiving Ethernet frames.
+pub trait FrameDevice: fmt::Debug + Send + 'static {
+    /// Receives a frame, if one is available, without blocking.
+    fn recv_frame(&mut self) -> Option<Vec<u8>>;
+
+    /// Sends a frame, dropping it if it can't be sent.
+    fn send_frame(&mut self, frame: Vec<u8>);
+
+    /// The maximum size of a frame, headers included.
+    fn mtu(&self) -> usize {
+        DEFAULT_MTU
+    }
+
+    /// Registers the waker to call once frames are available, and tells
+    /// whether the device calls it. The stack polls the devices that
+    /// don't every few milliseconds.
+    fn set_waker(&mut self, waker: DeviceWaker) -> bool {
+        let _ = waker;
+        false
+    }
+}
+
+/// Wakes up the operations of a stack waiting for something to happen.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceWaker(Arc<Signal>);
+
+#[derive(Debug, Default)]
+struct Signal {
+    /// Incremented by every wake up.
+    generation: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl DeviceWaker {
+    /// Wakes up the operations waiting on the stack, so they poll it.
+    pub fn wake(&self) {
+        let mut generation = self.lock();
+        *generation = generation.wrapping_add(1);
+        self.0.condvar.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, u64> {
+        self.0
+            .generation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The number of wake ups so far, to wait for the next ones with
+    /// [`DeviceWaker::wait`].
+    pub(crate) fn generation(&self) -> u64 {
+        *self.lock()
+    }
+
+    /// Waits for a wake up after `generation`, for at most `timeout`.
+    pub(crate) fn wait(&self, generation: u64, timeout: Option<Duration>) {
+        let guard = self.lock();
+        let woken = |current: &mut u64| *current == generation;
+        match timeout {
+            Some(timeout) => drop(self.0.condvar.wait_timeout_while(guard, timeout, woken)),
+            None => drop(self.0.condvar.wait_while(guard, woken)),
+        }
+    }
+}
+
+type WakerSlot = Arc<Mutex<Option<DeviceWaker>>>;
+
+/// A device exchanging its frames through channels, to link two stacks
+/// of the same process, or a stack and some code generating frames.
+///
+/// The devices created by [`ChannelDevice::pair`] wake each other up when
+/// they send frames, the others are polled.
+#[derive(Debug)]
+pub struct ChannelDevice {
+    tx: mpsc::Sender<Vec<u8>>,
+    rx: mpsc::Receiver<Vec<u8>>,
+    waker: WakerSlot,
+    /// The waker of the device receiving the frames, if it's known.
+    peer_waker: Option<WakerSlot>,
+}
+
+impl ChannelDevice {
+    /// Creates a device sending its frames to `tx`, and receiving the
+    /// ones from `rx`.
+    pub fn new(tx: mpsc::Sender<Vec<u8>>, rx: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self {
+            tx,
+            rx,
+            waker: WakerSlot::default(),
+            peer_waker: None,
+        }
+    }
+
+    /// Creates two devices linked to each other, like the two ends of a
+    /// cable.
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, b_rx) = mpsc::channel();
+        let (b_tx, a_rx) = mpsc::channel();
+
+        let mut a = Self::new(a_tx, a_rx);
+        let mut b = Self::new(b_tx, b_rx);
+        a.peer_waker = Some(b.waker.clone());
+        b.peer_waker = Some(a.waker.clone());
+        (a, b)
+    }
+}
+
+impl FrameDevice for ChannelDevice {
+    fn recv_frame(&mut self) -> Option<Vec<u8>> {
+        self.rx.try_recv().ok()
+    }
+
+    fn send_frame(&mut self, frame: Vec<u8>) {
+        // The other end being gone is like the cable being unplugged.
+        if self.tx.send(frame).is_err() {
+            return;
+        }
+        if let Some(peer_waker) = &self.peer_waker {
+            let peer_waker = peer_waker.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(peer_waker) = peer_waker.as_ref() {
+                peer_waker.wake();
+            }
+        }
+    }
+
+    fn set_waker(&mut self, waker: DeviceWaker) -> bool {
+        *self.waker.lock().unwrap_or_else(PoisonError::into_inner) = Some(waker);
+        // Only the other device of a pair knows when to wake this one.
+        self.peer_waker.is_some()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use self::tap::TapDevice;
+
+#[cfg(target_os = "linux")]
+mod tap {
+    use super::FrameDevice;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, ErrorKind, Read, Write};
+    use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+
+    const TUNSETIFF: libc::c_ulong = 0x4004_54ca;
+    const IFF_TAP: libc::c_short = 0x0002;
+    const IFF_NO_PI: libc::c_short = 0x1000;
+
+    #[repr(C)]
+    struct IfReq {
+        name: [libc::c_char; libc::IF_NAMESIZE],
+        flags: libc::c_short,
+        _padding: [u8; 22],
+    }
+
+    /// A TAP device of the host.
+    #[derive(Debug)]
+    pub struct TapDevice {
+        file: File,
+        mtu: usize,
+    }
+
+    impl TapDevice {
+        /// Attaches to the TAP interface `name`, which is created if it
+        /// doesn't exist and the process is allowed to.
+        pub fn open(name: &str) -> io::Result<Self> {
+            if name.len() >= libc::IF_NAMESIZE {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "the interface name is too long",
+                ));
+            }
+
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open("/dev/net/tun")?;
+            let mut request = IfReq {
+                name: [0; libc::IF_NAMESIZE],
+                flags: IFF_TAP | IFF_NO_PI,
+                _padding: [0; 22],
+            };
+            for (dst, src) in request.name.iter_mut().zip(name.bytes()) {
+                *dst = src as libc::c_char;
+            }
+
+            // SAFETY: the request is a valid `ifreq` outliving the call.
+            if unsafe { libc::ioctl(file.as_raw_fd(), TUNSETIFF as _, &mut request) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // SAFETY: the file descriptor is owned by `file`.
+            unsafe { Self::from_raw_fd(file.into_raw_fd()) }
+        }
+
+        /// Uses the file descriptor of a TAP interface already set up,
+        /// without packet information, taking its ownership.
+        ///
+        /// # Safety
+        ///
+        /// `fd` must be an open file descriptor not owned by anything
+        /// else.
+        pub unsafe fn from_raw_fd(fd: RawFd) -> io::Result<Self> {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self {
+                file: File::from_raw_fd(fd),
+                mtu: super::DEFAULT_MTU,
+            })
+        }
+    }
+
+    impl FrameDevice for TapDevice {
+        fn recv_frame(&mut self) -> Option<Vec<u8>> {
+            let mut frame = vec![0; self.mtu];
+            match self.file.read(&mut frame) {
+                Ok(len) => {
+                    frame.truncate(len);
+                    Some(frame)
+                }
+                Err(err) => {
+                    if err.kind() != ErrorKind::WouldBlock {
+                        tracing::debug!("failed to read from the TAP device: {}", err);
+                    }
+                    None
+                }
+            }
+        }
+
+        fn send_frame(&mut self, frame: Vec<u8>) {
+            if let Err(err) = self.file.write(&frame) {
+                tracing::debug!("failed to write to the TAP device: {}", err);
+            }
+        }
+
+        fn mtu(&self) -> usize {
+            self.mtu
+        }
+    }
+}
+
+/// The frames a raw socket keeps before dropping the new ones.
+const RAW_QUEUE_LEN: usize = 64;
+
+/// The frames received for a raw socket.
+#[derive(Debug, Default)]
+struct RawQueue {
+    frames: VecDeque<Vec<u8>>,
+    /// Whether the socket also gets the frames sent to other addresses.
+    promiscuous: bool,
+}
+
+/// Adapts a [`FrameDevice`] to smoltcp, and hands a copy of the frames it
+/// receives to the raw sockets.
+#[derive(Debug)]
+pub(crate) struct Adapter {
+    device: Box<dyn FrameDevice>,
+    mac: [u8; 6],
+    raw: BTreeMap<u64, RawQueue>,
+    next_raw: u64,
+}
+
+impl Adapter {
+    pub(crate) fn new(device: Box<dyn FrameDevice>, mac: [u8; 6]) -> Self {
+        Self {
+            device,
+            mac,
+            raw: BTreeMap::new(),
+            next_raw: 0,
+        }
+    }
+
+    /// Starts copying the received frames for a new raw socket, and
+    /// returns its identifier.
+    pub(crate) fn add_raw(&mut self) -> u64 {
+        let id = self.next_raw;
+        self.next_raw += 1;
+        self.raw.insert(id, RawQueue::default());
+        id
+    }
+
+    pub(crate) fn remove_raw(&mut self, id: u64) {
+        self.raw.remove(&id);
+    }
+
+    pub(crate) fn set_raw_promiscuous(&mut self, id: u64, promiscuous: bool) {
+        if let Some(queue) = self.raw.get_mut(&id) {
+            queue.promiscuous = promiscuous;
+        }
+    }
+
+    /// Takes the oldest frame received for the raw socket `id`.
+    pub(crate) fn recv_raw(&mut self, id: u64) -> Option<Vec<u8>> {
+        self.raw.get_mut(&id)?.frames.pop_front()
+    }
+
+    /// Sends a frame of a raw socket, bypassing the stack.
+    pub(crate) fn send_raw(&mut self, frame: Vec<u8>) {
+        self.device.send_frame(frame);
+    }
+
+    pub(crate) fn mtu(&self) -> usize {
+        self.device.mtu()
+    }
+
+    fn tap(&mut self, frame: &[u8]) {
+        let destination = match frame.get(..6) {
+            Some(destination) => destination,
+            None => return,
+        };
+        // The multicast bit also covers the broadcast address.
+        let for_us = destination == self.mac || destination[0] & 1 != 0;
+
+        for queue in self.raw.values_mut() {
+            if (for_us || queue.promiscuous) && queue.frames.len() < RAW_QUEUE_LEN {
+                queue.frames.push_back(frame.to_vec());
+            }
+        }
+    }
+}
+
+impl<'a> phy::Device<'a> for Adapter {
+    type RxToken = RxToken;
+    type TxToken = TxToken<'a>;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        let frame = self.device.recv_frame()?;
+        self.tap(&frame);
+        Some((RxToken(frame), TxToken(self.device.as_mut())))
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(TxToken(self.device.as_mut()))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+        capabilities.medium = Medium::Ethernet;
+        capabilities.max_transmission_unit = self.device.mtu();
+        capabilities
+    }
+}
+
+pub(crate) struct RxToken(Vec<u8>);
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(&mut self.0)
+    }
+}
+
+pub(crate) struct TxToken<'a>(&'a mut dyn FrameDevice);
+
+impl<'a> phy::TxToken for TxToken<'a> {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame)?;
+        self.0.send_frame(frame);
+        Ok(result)
+    }
+}
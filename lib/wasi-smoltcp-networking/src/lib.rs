@@ -0,0 +1,839 @@
+//! A [`VirtualNetworking`] running a TCP/IP stack in userspace, with
+//! [smoltcp](https://docs.rs/smoltcp), over a [`FrameDevice`].
+//!
+//! The guests get their own addresses, routes and neighbors, and reach
+//! the outside world only through the device: a TAP interface of the
+//! host, or a channel to another stack or to some test code.
+//!
+//! The stack has no thread of its own: it's polled by the operations on
+//! its sockets while they wait, and by [`SmoltcpNetworking::poll`]. The
+//! waiting operations sleep until the next timer of the stack, or until
+//! the device or another operation wakes them up with a [`DeviceWaker`].
+//!
+//! The raw sockets send their frames straight to the device, and get a
+//! copy of the ones it receives. The stack has no resolver: it only
+//! resolves IP literals and returns [`NetworkError::Unsupported`] for the
+//! names, which the guests can look up over UDP themselves. The stack
+//! isn't bridged to a remote network either, that is the role of its
+//! device.
+
+mod device;
+mod socket;
+
+#[cfg(target_os = "linux")]
+pub use crate::device::TapDevice;
+pub use crate::device::{ChannelDevice, DeviceWaker, FrameDevice, DEFAULT_MTU};
+pub use crate::socket::{
+    SmoltcpIcmpSocket, SmoltcpRawSocket, SmoltcpTcpListener, SmoltcpTcpSocket, SmoltcpUdpSocket,
+};
+
+use crate::device::Adapter;
+use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Route, Routes, SocketHandle};
+use smoltcp::socket::{
+    Dhcpv4Event, Dhcpv4Socket, IcmpEndpoint, IcmpPacketMetadata, IcmpSocket, IcmpSocketBuffer,
+    TcpSocket, TcpSocketBuffer, TcpState, UdpPacketMetadata, UdpSocket, UdpSocketBuffer,
+};
+use smoltcp::time::Instant;
+use smoltcp::wire::{
+    EthernetAddress, IpAddress, IpCidr as SmolIpCidr, IpEndpoint, Ipv4Address, Ipv6Address,
+};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use wasmer_vnet::{
+    IpCidr, IpRoute, NetworkError, Result, SocketHttpRequest, StreamSecurity, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualWebSocket,
+};
+
+/// The longest a waiting operation sleeps before polling again the
+/// devices that can't wake it up.
+const MAX_POLL_DELAY: Duration = Duration::from_millis(5);
+
+/// How long [`VirtualNetworking::dhcp_acquire`] waits for a lease.
+const DHCP_TIMEOUT: Duration = Duration::from_secs(30);
+
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// The connections waiting to be accepted by a listener.
+const LISTEN_BACKLOG: usize = 8;
+
+const TCP_BUFFER_SIZE: usize = 64 * 1024;
+const UDP_BUFFER_SIZE: usize = 64 * 1024;
+const UDP_PACKETS: usize = 64;
+
+/// A network stack of its own, see the [crate documentation](crate).
+///
+/// ```
+/// use wasmer_wasi_smoltcp_networking::{ChannelDevice, SmoltcpNetworking};
+/// use wasmer_vnet::VirtualNetworking;
+///
+/// let (guest, _host) = ChannelDevice::pair();
+/// let networking = SmoltcpNetworking::new(guest, [0x02, 0, 0, 0, 0, 1]);
+/// networking.ip_add("10.0.0.2".parse().unwrap(), 24).unwrap();
+/// networking.gateway_set("10.0.0.1".parse().unwrap()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SmoltcpNetworking {
+    stack: Arc<Stack>,
+}
+
+impl SmoltcpNetworking {
+    /// Creates a stack with no address sending and receiving its frames
+    /// through `device`, with the Ethernet address `mac`.
+    pub fn new(device: impl FrameDevice, mac: [u8; 6]) -> Self {
+        Self::from_boxed(Box::new(device), mac)
+    }
+
+    /// Like [`SmoltcpNetworking::new`] with a boxed device.
+    pub fn from_boxed(mut device: Box<dyn FrameDevice>, mac: [u8; 6]) -> Self {
+        let waker = DeviceWaker::default();
+        let device_wakes = device.set_waker(waker.clone());
+
+        let iface = InterfaceBuilder::new(Adapter::new(device, mac), Vec::new())
+            .hardware_addr(EthernetAddress(mac).into())
+            .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+            .ip_addrs(Vec::new())
+            .routes(Routes::new(BTreeMap::new()))
+            // The initial sequence numbers of TCP are derived from the seed.
+            .random_seed(RandomState::new().build_hasher().finish())
+            .finalize();
+
+        Self {
+            stack: Arc::new(Stack {
+                state: Mutex::new(State {
+                    iface,
+                    waker: waker.clone(),
+                    mac,
+                    next_port: FIRST_EPHEMERAL_PORT,
+                    next_ident: 1,
+                    closing: Vec::new(),
+                }),
+                waker,
+                device_wakes,
+            }),
+        }
+    }
+
+    /// Processes the frames received by the device, and sends the ones
+    /// that are due.
+    pub fn poll(&self) -> Result<()> {
+        self.stack.lock()?.poll();
+        Ok(())
+    }
+}
+
+pub(crate) struct Stack {
+    state: Mutex<State>,
+    waker: DeviceWaker,
+    /// Whether the device wakes up the waiting operations, instead of
+    /// being polled.
+    device_wakes: bool,
+}
+
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stack").finish()
+    }
+}
+
+pub(crate) struct State {
+    /// The interface, owning the device and the sockets.
+    pub(crate) iface: Interface<'static, Adapter>,
+    /// Wakes up the waiting operations when the stack did something.
+    waker: DeviceWaker,
+    mac: [u8; 6],
+    next_port: u16,
+    next_ident: u16,
+    /// The TCP sockets closed by their owner, removed once the connection
+    /// is over.
+    closing: Vec<SocketHandle>,
+}
+
+impl Stack {
+    pub(crate) fn lock(&self) -> Result<MutexGuard<'_, State>> {
+        self.state.lock().map_err(|_| NetworkError::Lock)
+    }
+
+    /// Polls the stack until `f` returns a value, for at most `timeout`.
+    pub(crate) fn wait<R>(
+        &self,
+        timeout: Option<Duration>,
+        mut f: impl FnMut(&mut State) -> Result<Option<R>>,
+    ) -> Result<R> {
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+
+        loop {
+            let (generation, mut delay) = {
+                let mut state = self.lock()?;
+                // Any wake up from now on is seen by the wait below.
+                let generation = self.waker.generation();
+                state.poll();
+                if let Some(result) = f(&mut state)? {
+                    // Sends what `f` queued right away, and lets the other
+                    // operations see what it changed.
+                    state.poll();
+                    self.waker.wake();
+                    return Ok(result);
+                }
+                (generation, state.poll_delay())
+            };
+
+            if !self.device_wakes {
+                delay = Some(delay.map_or(MAX_POLL_DELAY, |delay| delay.min(MAX_POLL_DELAY)));
+            }
+            if let Some(deadline) = deadline {
+                let now = std::time::Instant::now();
+                if now >= deadline {
+                    return Err(NetworkError::TimedOut);
+                }
+                let left = deadline - now;
+                delay = Some(delay.map_or(left, |delay| delay.min(left)));
+            }
+            self.waker.wait(generation, delay);
+        }
+    }
+}
+
+impl State {
+    pub(crate) fn poll(&mut self) {
+        match self.iface.poll(Instant::now()) {
+            Ok(true) => self.waker.wake(),
+            Ok(false) => (),
+            // The packet is dropped, the next poll carries on.
+            Err(err) => tracing::trace!("failed to process a packet: {}", err),
+        }
+
+        let iface = &mut self.iface;
+        self.closing.retain(|handle| {
+            let state = iface.get_socket::<TcpSocket>(*handle).state();
+            let closed = matches!(state, TcpState::Closed | TcpState::TimeWait);
+            if closed {
+                iface.remove_socket(*handle);
+            }
+            !closed
+        });
+    }
+
+    /// How long the stack can wait before being polled again, if it has
+    /// timers running.
+    fn poll_delay(&mut self) -> Option<Duration> {
+        self.iface
+            .poll_delay(Instant::now())
+            .map(|delay| Duration::from_millis(delay.total_millis()))
+    }
+
+    pub(crate) fn close_tcp(&mut self, handle: SocketHandle) {
+        self.iface.get_socket::<TcpSocket>(handle).close();
+        self.closing.push(handle);
+    }
+
+    /// Gives `addr` a free port if it has none.
+    pub(crate) fn assign_port(&mut self, mut addr: SocketAddr) -> SocketAddr {
+        if addr.port() == 0 {
+            addr.set_port(self.next_port);
+            self.next_port = match self.next_port {
+                u16::MAX => FIRST_EPHEMERAL_PORT,
+                port => port + 1,
+            };
+        }
+        addr
+    }
+
+    pub(crate) fn add_tcp_socket(&mut self) -> SocketHandle {
+        self.iface.add_socket(TcpSocket::new(
+            TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+            TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        ))
+    }
+
+    pub(crate) fn listen(&mut self, addr: SocketAddr) -> Result<SocketHandle> {
+        let handle = self.add_tcp_socket();
+        let socket = self.iface.get_socket::<TcpSocket>(handle);
+        if let Err(err) = socket.listen(listen_endpoint(addr)) {
+            self.iface.remove_socket(handle);
+            return Err(net_error(err));
+        }
+        no_ack_delay(socket);
+
+        Ok(handle)
+    }
+}
+
+/// Acknowledges the received data right away, as a delayed acknowledgement
+/// would only be sent if something happens to poll the stack later. This
+/// has to be done after `listen` and `connect`, which reset it.
+fn no_ack_delay(socket: &mut TcpSocket) {
+    socket.set_ack_delay(None);
+}
+
+/// Converts the errors of smoltcp.
+pub(crate) fn net_error(err: smoltcp::Error) -> NetworkError {
+    match err {
+        smoltcp::Error::Exhausted => NetworkError::WouldBlock,
+        smoltcp::Error::Illegal => NetworkError::InvalidInput,
+        smoltcp::Error::Unaddressable => NetworkError::AddressNotAvailable,
+        smoltcp::Error::Finished => NetworkError::ConnectionReset,
+        smoltcp::Error::Truncated => NetworkError::InvalidData,
+        smoltcp::Error::NotSupported => NetworkError::Unsupported,
+        _ => NetworkError::IOError,
+    }
+}
+
+pub(crate) fn ip_address(ip: IpAddr) -> IpAddress {
+    IpAddress::from(ip)
+}
+
+pub(crate) fn ip_addr(ip: IpAddress) -> IpAddr {
+    match ip {
+        IpAddress::Ipv4(ip) => IpAddr::V4(ip.into()),
+        IpAddress::Ipv6(ip) => IpAddr::V6(ip.into()),
+        // The address of the sockets not bound to one yet.
+        _ => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    }
+}
+
+pub(crate) fn endpoint(addr: SocketAddr) -> IpEndpoint {
+    IpEndpoint::new(ip_address(addr.ip()), addr.port())
+}
+
+/// The local endpoint of a socket, leaving the unspecified addresses for
+/// smoltcp to pick.
+pub(crate) fn listen_endpoint(addr: SocketAddr) -> IpEndpoint {
+    if addr.ip().is_unspecified() {
+        IpEndpoint::new(IpAddress::Unspecified, addr.port())
+    } else {
+        endpoint(addr)
+    }
+}
+
+pub(crate) fn socket_addr(endpoint: IpEndpoint) -> SocketAddr {
+    SocketAddr::new(ip_addr(endpoint.addr), endpoint.port)
+}
+
+fn cidr(ip: IpAddr, prefix: u8) -> Result<SmolIpCidr> {
+    let max_prefix = match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    // smoltcp only takes unicast addresses.
+    let address = ip_address(ip);
+    if prefix > max_prefix || !address.is_unicast() {
+        return Err(NetworkError::InvalidInput);
+    }
+
+    Ok(SmolIpCidr::new(address, prefix))
+}
+
+/// The times of the routes are durations since the epoch, like the
+/// clock of smoltcp.
+fn route_time(time: Option<Duration>) -> Option<Instant> {
+    time.map(|time| Instant::from_millis(time.as_millis() as i64))
+}
+
+fn route_duration(time: Option<Instant>) -> Option<Duration> {
+    time.map(|time| Duration::from_millis(time.total_millis().max(0) as u64))
+}
+
+impl VirtualNetworking for SmoltcpNetworking {
+    fn ws_connect(&self, _url: &str) -> Result<Box<dyn VirtualWebSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn http_request(
+        &self,
+        _url: &str,
+        _method: &str,
+        _headers: &str,
+        _gzip: bool,
+    ) -> Result<SocketHttpRequest> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn bridge(&self, _network: &str, _access_token: &str, _security: StreamSecurity) -> Result<()> {
+        // The device decides which network the stack is on.
+        Err(NetworkError::Unsupported)
+    }
+
+    fn unbridge(&self) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        let handle = self.stack.lock()?.iface.add_socket(Dhcpv4Socket::new());
+
+        let lease = self.stack.wait(Some(DHCP_TIMEOUT), |state| {
+            let socket = state.iface.get_socket::<Dhcpv4Socket>(handle);
+            Ok(match socket.poll() {
+                Some(Dhcpv4Event::Configured(config)) => Some((config.address, config.router)),
+                _ => None,
+            })
+        });
+
+        let mut state = self.stack.lock()?;
+        state.iface.remove_socket(handle);
+        let (address, router) = lease?;
+
+        state.iface.update_ip_addrs(|addrs| {
+            let mut list: Vec<_> = addrs
+                .iter()
+                .filter(|cidr| !matches!(cidr, SmolIpCidr::Ipv4(_)))
+                .copied()
+                .collect();
+            list.push(SmolIpCidr::Ipv4(address));
+            *addrs = list.into();
+        });
+        if let Some(router) = router {
+            state
+                .iface
+                .routes_mut()
+                .add_default_ipv4_route(router)
+                .map_err(net_error)?;
+        }
+
+        Ok(vec![IpAddr::V4(Ipv4Addr::from(address.address().0))])
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        let cidr = cidr(ip, prefix)?;
+        self.stack.lock()?.iface.update_ip_addrs(|addrs| {
+            let mut list: Vec<_> = addrs
+                .iter()
+                .filter(|existing| existing.address() != cidr.address())
+                .copied()
+                .collect();
+            list.push(cidr);
+            *addrs = list.into();
+        });
+
+        Ok(())
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        let ip = ip_address(ip);
+        self.stack.lock()?.iface.update_ip_addrs(|addrs| {
+            let list: Vec<_> = addrs
+                .iter()
+                .filter(|cidr| cidr.address() != ip)
+                .copied()
+                .collect();
+            *addrs = list.into();
+        });
+
+        Ok(())
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        self.stack
+            .lock()?
+            .iface
+            .update_ip_addrs(|addrs| *addrs = Vec::new().into());
+
+        Ok(())
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        Ok(self
+            .stack
+            .lock()?
+            .iface
+            .ip_addrs()
+            .iter()
+            .map(|cidr| IpCidr {
+                ip: ip_addr(cidr.address()),
+                prefix: cidr.prefix_len(),
+            })
+            .collect())
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        Ok(self.stack.lock()?.mac)
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        let mut state = self.stack.lock()?;
+        let routes = state.iface.routes_mut();
+
+        match ip {
+            IpAddr::V4(ip) => routes.add_default_ipv4_route(Ipv4Address(ip.octets())),
+            IpAddr::V6(ip) => routes.add_default_ipv6_route(Ipv6Address(ip.octets())),
+        }
+        .map_err(net_error)?;
+
+        Ok(())
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        let cidr = self::cidr(cidr.ip, cidr.prefix)?;
+        let route = Route {
+            via_router: ip_address(via_router),
+            preferred_until: route_time(preferred_until),
+            expires_at: route_time(expires_at),
+        };
+
+        let mut inserted = Ok(());
+        self.stack.lock()?.iface.routes_mut().update(|routes| {
+            inserted = routes
+                .insert(cidr, route)
+                .map(|_| ())
+                .map_err(|_| NetworkError::InsufficientMemory);
+        });
+
+        inserted
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        let ip = ip_address(cidr);
+        self.stack.lock()?.iface.routes_mut().update(|routes| {
+            let removed: Vec<_> = routes
+                .iter()
+                .map(|(cidr, _)| *cidr)
+                .filter(|cidr| cidr.address() == ip)
+                .collect();
+            for cidr in removed {
+                routes.remove(&cidr);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        self.stack
+            .lock()?
+            .iface
+            .routes_mut()
+            .update(|routes| routes.clear());
+
+        Ok(())
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        let mut list = Vec::new();
+        self.stack.lock()?.iface.routes_mut().update(|routes| {
+            list = routes
+                .iter()
+                .map(|(cidr, route)| IpRoute {
+                    cidr: IpCidr {
+                        ip: ip_addr(cidr.address()),
+                        prefix: cidr.prefix_len(),
+                    },
+                    via_router: ip_addr(route.via_router),
+                    preferred_until: route_duration(route.preferred_until),
+                    expires_at: route_duration(route.expires_at),
+                })
+                .collect();
+        });
+
+        Ok(list)
+    }
+
+    fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let id = self.stack.lock()?.iface.device_mut().add_raw();
+        Ok(Box::new(SmoltcpRawSocket::new(self.stack.clone(), id)))
+    }
+
+    fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        _only_v6: bool,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let mut state = self.stack.lock()?;
+        let addr = state.assign_port(addr);
+
+        let mut handles = Vec::with_capacity(LISTEN_BACKLOG);
+        for _ in 0..LISTEN_BACKLOG {
+            match state.listen(addr) {
+                Ok(handle) => handles.push(handle),
+                Err(err) => {
+                    for handle in handles {
+                        state.iface.remove_socket(handle);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(Box::new(SmoltcpTcpListener::new(
+            self.stack.clone(),
+            addr,
+            handles,
+        )))
+    }
+
+    fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let mut state = self.stack.lock()?;
+        let addr = state.assign_port(addr);
+
+        let handle = state.iface.add_socket(UdpSocket::new(
+            UdpSocketBuffer::new(
+                vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+            UdpSocketBuffer::new(
+                vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+        ));
+        if let Err(err) = state
+            .iface
+            .get_socket::<UdpSocket>(handle)
+            .bind(listen_endpoint(addr))
+        {
+            state.iface.remove_socket(handle);
+            return Err(net_error(err));
+        }
+
+        Ok(Box::new(SmoltcpUdpSocket::new(
+            self.stack.clone(),
+            handle,
+            addr,
+        )))
+    }
+
+    fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let mut state = self.stack.lock()?;
+        let ident = state.next_ident;
+        state.next_ident = state.next_ident.wrapping_add(1);
+
+        let handle = state.iface.add_socket(IcmpSocket::new(
+            IcmpSocketBuffer::new(
+                vec![IcmpPacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+            IcmpSocketBuffer::new(
+                vec![IcmpPacketMetadata::EMPTY; UDP_PACKETS],
+                vec![0; UDP_BUFFER_SIZE],
+            ),
+        ));
+        if let Err(err) = state
+            .iface
+            .get_socket::<IcmpSocket>(handle)
+            .bind(IcmpEndpoint::Ident(ident))
+        {
+            state.iface.remove_socket(handle);
+            return Err(net_error(err));
+        }
+
+        Ok(Box::new(SmoltcpIcmpSocket::new(
+            self.stack.clone(),
+            handle,
+            addr,
+        )))
+    }
+
+    fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+        timeout: Option<Duration>,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let handle = {
+            let mut state = self.stack.lock()?;
+            let state = &mut *state;
+            let addr = state.assign_port(addr);
+            let handle = state.add_tcp_socket();
+
+            let (socket, context) = state.iface.get_socket_and_context::<TcpSocket>(handle);
+            if let Err(err) = socket.connect(context, endpoint(peer), listen_endpoint(addr)) {
+                state.iface.remove_socket(handle);
+                return Err(net_error(err));
+            }
+            no_ack_delay(socket);
+            handle
+        };
+
+        let connected = self.stack.wait(timeout, |state| {
+            match state.iface.get_socket::<TcpSocket>(handle).state() {
+                TcpState::SynSent | TcpState::SynReceived => Ok(None),
+                TcpState::Closed => Err(NetworkError::ConnectionRefused),
+                _ => Ok(Some(())),
+            }
+        });
+        if let Err(err) = connected {
+            let mut state = self.stack.lock()?;
+            state.iface.get_socket::<TcpSocket>(handle).abort();
+            state.closing.push(handle);
+            return Err(err);
+        }
+
+        Ok(Box::new(SmoltcpTcpSocket::new(self.stack.clone(), handle)))
+    }
+
+    fn resolve(
+        &self,
+        host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        // Only the IP literals are resolved, guests are free to query DNS
+        // servers over UDP for the names.
+        host.parse::<IpAddr>()
+            .map(|ip| vec![ip])
+            .map_err(|_| NetworkError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::thread;
+    use wasmer_vnet::{TimeType, VirtualConnectedSocket};
+
+    fn pair() -> (SmoltcpNetworking, SmoltcpNetworking) {
+        let (a, b) = ChannelDevice::pair();
+        let a = SmoltcpNetworking::new(a, [0x02, 0, 0, 0, 0, 1]);
+        let b = SmoltcpNetworking::new(b, [0x02, 0, 0, 0, 0, 2]);
+        a.ip_add("10.0.0.1".parse().unwrap(), 24).unwrap();
+        b.ip_add("10.0.0.2".parse().unwrap(), 24).unwrap();
+        (a, b)
+    }
+
+    #[test]
+    fn test_addresses() {
+        let (a, _b) = pair();
+        a.ip_add("fd00::1".parse().unwrap(), 64).unwrap();
+        assert_eq!(a.ip_list().unwrap().len(), 2);
+        a.ip_remove("fd00::1".parse().unwrap()).unwrap();
+        assert_eq!(
+            a.ip_list().unwrap(),
+            vec![IpCidr {
+                ip: "10.0.0.1".parse().unwrap(),
+                prefix: 24,
+            }],
+        );
+
+        a.gateway_set("10.0.0.254".parse().unwrap()).unwrap();
+        assert_eq!(a.route_list().unwrap().len(), 1);
+        a.route_clear().unwrap();
+        assert!(a.route_list().unwrap().is_empty());
+        assert_eq!(a.mac().unwrap(), [0x02, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_tcp() {
+        let (a, b) = pair();
+        let listener = b
+            .listen_tcp("0.0.0.0:8080".parse().unwrap(), false, false, false)
+            .unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, peer) = listener.accept().unwrap();
+            let ping = socket.recv().unwrap().data;
+            socket.send(Bytes::from_static(b"pong")).unwrap();
+            VirtualConnectedSocket::flush(&mut *socket).unwrap();
+            (peer, ping)
+        });
+
+        let mut socket = a
+            .connect_tcp(
+                "0.0.0.0:0".parse().unwrap(),
+                "10.0.0.2:8080".parse().unwrap(),
+                Some(Duration::from_secs(10)),
+            )
+            .unwrap();
+        socket.send(Bytes::from_static(b"ping")).unwrap();
+        assert_eq!(&socket.recv().unwrap().data[..], b"pong");
+
+        let (peer, ping) = server.join().unwrap();
+        assert_eq!(&ping[..], b"ping");
+        assert_eq!(peer, socket.addr_local().unwrap());
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let (a, b) = pair();
+        let listener = b
+            .listen_tcp("0.0.0.0:8080".parse().unwrap(), false, false, false)
+            .unwrap();
+        let server = thread::spawn(move || listener.accept().unwrap());
+
+        let mut socket = a
+            .connect_tcp(
+                "0.0.0.0:0".parse().unwrap(),
+                "10.0.0.2:8080".parse().unwrap(),
+                Some(Duration::from_secs(10)),
+            )
+            .unwrap();
+        let _server = server.join().unwrap();
+
+        socket
+            .set_opt_time(TimeType::ReadTimeout, Some(Duration::from_millis(50)))
+            .unwrap();
+        assert_eq!(socket.recv().unwrap_err(), NetworkError::TimedOut);
+    }
+
+    #[test]
+    fn test_udp() {
+        let (a, b) = pair();
+        let mut server = b
+            .bind_udp("10.0.0.2:53".parse().unwrap(), false, false)
+            .unwrap();
+        let mut client = a
+            .bind_udp("10.0.0.1:0".parse().unwrap(), false, false)
+            .unwrap();
+
+        let server = thread::spawn(move || {
+            let query = server.recv_from().unwrap();
+            server
+                .send_to(Bytes::from_static(b"answer"), query.addr)
+                .unwrap();
+            query
+        });
+
+        client
+            .send_to(Bytes::from_static(b"query"), "10.0.0.2:53".parse().unwrap())
+            .unwrap();
+        let answer = client.recv_from().unwrap();
+        assert_eq!(&answer.data[..], b"answer");
+        assert_eq!(answer.addr, "10.0.0.2:53".parse().unwrap());
+
+        let query = server.join().unwrap();
+        assert_eq!(&query.data[..], b"query");
+        assert_eq!(query.addr, client.addr_local().unwrap());
+    }
+
+    #[test]
+    fn test_raw() {
+        let (a, b) = pair();
+        let mut sender = a.bind_raw().unwrap();
+        let mut receiver = b.bind_raw().unwrap();
+
+        // An Ethernet frame of an experimental type, which the stack drops.
+        let frame = |destination: [u8; 6], payload: &[u8]| {
+            let mut frame = destination.to_vec();
+            frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 1, 0x88, 0xb5]);
+            frame.extend_from_slice(payload);
+            Bytes::from(frame)
+        };
+        let elsewhere = frame([0x02, 0, 0, 0, 0, 3], b"elsewhere");
+        let here = frame([0x02, 0, 0, 0, 0, 2], b"here");
+
+        sender.send(elsewhere.clone()).unwrap();
+        sender.send(here.clone()).unwrap();
+        assert_eq!(receiver.recv().unwrap().data, here);
+
+        receiver.set_promiscuous(true).unwrap();
+        assert!(receiver.promiscuous().unwrap());
+        sender.send(elsewhere.clone()).unwrap();
+        assert_eq!(receiver.recv().unwrap().data, elsewhere);
+    }
+}
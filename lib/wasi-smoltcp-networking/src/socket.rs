@@ -0,0 +1,724 @@
+//! The sockets of a [`SmoltcpNetworking`](crate::SmoltcpNetworking).
+
+use crate::{endpoint, ip_addr, ip_address, net_error, socket_addr, Stack};
+use bytes::Bytes;
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::{IcmpSocket, TcpSocket, TcpState, UdpSocket};
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmer_vnet::{
+    NetworkError, Result, SocketReceive, SocketReceiveFrom, SocketStatus, TimeType,
+    VirtualConnectedSocket, VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualRawSocket,
+    VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// The largest chunk of data returned by a single receive.
+const MAX_RECV_SIZE: usize = 64 * 1024;
+
+/// The hop limit of the sockets not setting theirs.
+const DEFAULT_TTL: u8 = 64;
+
+/// A TCP listener, made of a few listening sockets as each smoltcp
+/// socket accepts a single connection.
+#[derive(Debug)]
+pub struct SmoltcpTcpListener {
+    stack: Arc<Stack>,
+    addr: SocketAddr,
+    handles: Mutex<Vec<SocketHandle>>,
+    timeout: Option<Duration>,
+    ttl: u8,
+}
+
+impl SmoltcpTcpListener {
+    pub(crate) fn new(stack: Arc<Stack>, addr: SocketAddr, handles: Vec<SocketHandle>) -> Self {
+        Self {
+            stack,
+            addr,
+            handles: Mutex::new(handles),
+            timeout: None,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    fn accept_within(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (handle, peer) = self.stack.wait(timeout, |state| {
+            let mut handles = self.handles.lock().map_err(|_| NetworkError::Lock)?;
+
+            for slot in handles.iter_mut() {
+                let socket = state.iface.get_socket::<TcpSocket>(*slot);
+                if !matches!(socket.state(), TcpState::Established | TcpState::CloseWait) {
+                    continue;
+                }
+                let peer = socket.remote_endpoint();
+                if !peer.is_specified() {
+                    continue;
+                }
+                let peer = socket_addr(peer);
+
+                // Another socket takes the place of the accepted one.
+                let accepted = std::mem::replace(slot, state.listen(self.addr)?);
+                return Ok(Some((accepted, peer)));
+            }
+
+            Ok(None)
+        })?;
+
+        let mut socket = SmoltcpTcpSocket::new(self.stack.clone(), handle);
+        socket.set_ttl(u32::from(self.ttl))?;
+
+        Ok((Box::new(socket), peer))
+    }
+}
+
+impl VirtualTcpListener for SmoltcpTcpListener {
+    fn accept(&self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        self.accept_within(self.timeout)
+    }
+
+    fn accept_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        self.accept_within(Some(timeout))
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn timeout(&self) -> Result<Option<Duration>> {
+        Ok(self.timeout)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(self.ttl)
+    }
+}
+
+impl Drop for SmoltcpTcpListener {
+    fn drop(&mut self) {
+        if let (Ok(mut state), Ok(handles)) = (self.stack.lock(), self.handles.get_mut()) {
+            for handle in handles.drain(..) {
+                state.close_tcp(handle);
+            }
+        }
+    }
+}
+
+/// A TCP connection.
+#[derive(Debug)]
+pub struct SmoltcpTcpSocket {
+    stack: Arc<Stack>,
+    handle: SocketHandle,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    linger: Option<Duration>,
+}
+
+impl SmoltcpTcpSocket {
+    pub(crate) fn new(stack: Arc<Stack>, handle: SocketHandle) -> Self {
+        Self {
+            stack,
+            handle,
+            read_timeout: None,
+            write_timeout: None,
+            connect_timeout: None,
+            linger: None,
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut TcpSocket<'static>) -> R) -> Result<R> {
+        let mut state = self.stack.lock()?;
+        Ok(f(state.iface.get_socket::<TcpSocket>(self.handle)))
+    }
+
+    fn receive(&mut self, consume: bool) -> Result<SocketReceive> {
+        let handle = self.handle;
+        let data = self.stack.wait(self.read_timeout, |state| {
+            let socket = state.iface.get_socket::<TcpSocket>(handle);
+            if socket.can_recv() {
+                let len = socket.recv_queue().min(MAX_RECV_SIZE);
+                let data = if consume {
+                    let mut data = vec![0; len];
+                    let len = socket.recv_slice(&mut data).map_err(net_error)?;
+                    data.truncate(len);
+                    data
+                } else {
+                    socket.peek(len).map_err(net_error)?.to_vec()
+                };
+                Ok(Some(data))
+            } else if !socket.may_recv() {
+                // An empty receive is the end of the stream.
+                Ok(Some(Vec::new()))
+            } else {
+                Ok(None)
+            }
+        })?;
+
+        Ok(SocketReceive {
+            data: Bytes::from(data),
+            truncated: false,
+        })
+    }
+}
+
+impl VirtualTcpSocket for SmoltcpTcpSocket {
+    fn set_opt_time(&mut self, ty: TimeType, timeout: Option<Duration>) -> Result<()> {
+        match ty {
+            TimeType::ReadTimeout => self.read_timeout = timeout,
+            TimeType::WriteTimeout => self.write_timeout = timeout,
+            TimeType::ConnectTimeout => self.connect_timeout = timeout,
+            TimeType::Linger => self.linger = timeout,
+            _ => return Err(NetworkError::InvalidInput),
+        }
+        Ok(())
+    }
+
+    fn opt_time(&self, ty: TimeType) -> Result<Option<Duration>> {
+        match ty {
+            TimeType::ReadTimeout => Ok(self.read_timeout),
+            TimeType::WriteTimeout => Ok(self.write_timeout),
+            TimeType::ConnectTimeout => Ok(self.connect_timeout),
+            TimeType::Linger => Ok(self.linger),
+            _ => Err(NetworkError::InvalidInput),
+        }
+    }
+
+    fn set_recv_buf_size(&mut self, _size: usize) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.with(|socket| socket.recv_capacity())
+    }
+
+    fn set_send_buf_size(&mut self, _size: usize) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.with(|socket| socket.send_capacity())
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.with(|socket| socket.set_nagle_enabled(!nodelay))
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.with(|socket| !socket.nagle_enabled())
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        let peer = self.with(|socket| socket.remote_endpoint())?;
+        if !peer.is_specified() {
+            return Err(NetworkError::NotConnected);
+        }
+        Ok(socket_addr(peer))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        VirtualConnectedSocket::flush(self)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        // smoltcp can only stop sending.
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.with(|socket| socket.close())?;
+            self.stack.lock()?.poll();
+        }
+        Ok(())
+    }
+}
+
+impl VirtualConnectedSocket for SmoltcpTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.linger = linger;
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(self.linger)
+    }
+
+    fn send(&mut self, data: Bytes) -> Result<usize> {
+        let handle = self.handle;
+        let mut sent = 0;
+
+        self.stack.wait(self.write_timeout, |state| {
+            let socket = state.iface.get_socket::<TcpSocket>(handle);
+            if !socket.may_send() {
+                return Err(NetworkError::BrokenPipe);
+            }
+            if socket.can_send() {
+                sent += socket.send_slice(&data[sent..]).map_err(net_error)?;
+            }
+            Ok(if sent == data.len() { Some(()) } else { None })
+        })?;
+
+        Ok(sent)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let handle = self.handle;
+        self.stack.wait(self.write_timeout, |state| {
+            let socket = state.iface.get_socket::<TcpSocket>(handle);
+            Ok(if socket.send_queue() == 0 || !socket.is_open() {
+                Some(())
+            } else {
+                None
+            })
+        })
+    }
+
+    fn recv(&mut self) -> Result<SocketReceive> {
+        self.receive(true)
+    }
+
+    fn peek(&mut self) -> Result<SocketReceive> {
+        self.receive(false)
+    }
+}
+
+impl VirtualSocket for SmoltcpTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = u8::try_from(ttl).map_err(|_| NetworkError::InvalidInput)?;
+        self.with(|socket| socket.set_hop_limit(Some(ttl)))
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.with(|socket| u32::from(socket.hop_limit().unwrap_or(DEFAULT_TTL)))
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        let local = self.with(|socket| socket.local_endpoint())?;
+        if local.port == 0 {
+            return Err(NetworkError::NotConnected);
+        }
+        Ok(socket_addr(local))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.with(|socket| match socket.state() {
+            TcpState::Closed | TcpState::TimeWait => SocketStatus::Closed,
+            TcpState::Listen | TcpState::SynSent | TcpState::SynReceived => SocketStatus::Opening,
+            _ => SocketStatus::Opened,
+        })
+    }
+}
+
+impl Drop for SmoltcpTcpSocket {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.stack.lock() {
+            state.close_tcp(self.handle);
+            state.poll();
+        }
+    }
+}
+
+/// A UDP socket.
+#[derive(Debug)]
+pub struct SmoltcpUdpSocket {
+    stack: Arc<Stack>,
+    handle: SocketHandle,
+    addr: SocketAddr,
+    peer: Option<SocketAddr>,
+    broadcast: bool,
+}
+
+impl SmoltcpUdpSocket {
+    pub(crate) fn new(stack: Arc<Stack>, handle: SocketHandle, addr: SocketAddr) -> Self {
+        Self {
+            stack,
+            handle,
+            addr,
+            peer: None,
+            broadcast: false,
+        }
+    }
+
+    fn receive_from(&mut self, consume: bool) -> Result<SocketReceiveFrom> {
+        let (handle, peer) = (self.handle, self.peer);
+
+        let (data, addr) = self.stack.wait(None, |state| {
+            let socket = state.iface.get_socket::<UdpSocket>(handle);
+            while socket.can_recv() {
+                let (data, from) = socket.peek().map_err(net_error)?;
+                let (data, from) = (data.to_vec(), socket_addr(*from));
+                // A connected socket only receives the datagrams of its
+                // peer.
+                let wanted = peer.map_or(true, |peer| peer == from);
+                if consume || !wanted {
+                    socket.recv().map_err(net_error)?;
+                }
+                if wanted {
+                    return Ok(Some((data, from)));
+                }
+            }
+            Ok(None)
+        })?;
+
+        Ok(SocketReceiveFrom {
+            data: Bytes::from(data),
+            truncated: false,
+            addr,
+        })
+    }
+}
+
+impl VirtualUdpSocket for SmoltcpUdpSocket {
+    fn connect(&mut self, addr: SocketAddr) -> Result<()> {
+        self.peer = Some(addr);
+        Ok(())
+    }
+
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.broadcast = broadcast;
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(self.broadcast)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(self.peer)
+    }
+}
+
+impl VirtualConnectedSocket for SmoltcpUdpSocket {
+    fn set_linger(&mut self, _linger: Option<Duration>) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn send(&mut self, data: Bytes) -> Result<usize> {
+        let peer = self.peer.ok_or(NetworkError::NotConnected)?;
+        self.send_to(data, peer)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<SocketReceive> {
+        let received = self.receive_from(true)?;
+        Ok(SocketReceive {
+            data: received.data,
+            truncated: received.truncated,
+        })
+    }
+
+    fn peek(&mut self) -> Result<SocketReceive> {
+        let received = self.receive_from(false)?;
+        Ok(SocketReceive {
+            data: received.data,
+            truncated: received.truncated,
+        })
+    }
+}
+
+impl VirtualConnectionlessSocket for SmoltcpUdpSocket {
+    fn send_to(&mut self, data: Bytes, addr: SocketAddr) -> Result<usize> {
+        let handle = self.handle;
+        self.stack.wait(None, |state| {
+            let socket = state.iface.get_socket::<UdpSocket>(handle);
+            if !socket.can_send() {
+                return Ok(None);
+            }
+            socket
+                .send_slice(&data, endpoint(addr))
+                .map_err(net_error)?;
+            Ok(Some(()))
+        })?;
+
+        Ok(data.len())
+    }
+
+    fn recv_from(&mut self) -> Result<SocketReceiveFrom> {
+        self.receive_from(true)
+    }
+
+    fn peek_from(&mut self) -> Result<SocketReceiveFrom> {
+        self.receive_from(false)
+    }
+}
+
+impl VirtualSocket for SmoltcpUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = u8::try_from(ttl).map_err(|_| NetworkError::InvalidInput)?;
+        let mut state = self.stack.lock()?;
+        state
+            .iface
+            .get_socket::<UdpSocket>(self.handle)
+            .set_hop_limit(Some(ttl));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        let mut state = self.stack.lock()?;
+        let socket = state.iface.get_socket::<UdpSocket>(self.handle);
+        Ok(u32::from(socket.hop_limit().unwrap_or(DEFAULT_TTL)))
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+}
+
+impl Drop for SmoltcpUdpSocket {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.stack.lock() {
+            state.iface.remove_socket(self.handle);
+        }
+    }
+}
+
+/// An ICMP socket, receiving the echo replies with its own identifier.
+#[derive(Debug)]
+pub struct SmoltcpIcmpSocket {
+    stack: Arc<Stack>,
+    handle: SocketHandle,
+    addr: IpAddr,
+    ttl: u8,
+}
+
+impl SmoltcpIcmpSocket {
+    pub(crate) fn new(stack: Arc<Stack>, handle: SocketHandle, addr: IpAddr) -> Self {
+        Self {
+            stack,
+            handle,
+            addr,
+            ttl: DEFAULT_TTL,
+        }
+    }
+}
+
+impl VirtualIcmpSocket for SmoltcpIcmpSocket {}
+
+impl VirtualConnectionlessSocket for SmoltcpIcmpSocket {
+    fn send_to(&mut self, data: Bytes, addr: SocketAddr) -> Result<usize> {
+        let handle = self.handle;
+        self.stack.wait(None, |state| {
+            let socket = state.iface.get_socket::<IcmpSocket>(handle);
+            if !socket.can_send() {
+                return Ok(None);
+            }
+            socket
+                .send_slice(&data, ip_address(addr.ip()))
+                .map_err(net_error)?;
+            Ok(Some(()))
+        })?;
+
+        Ok(data.len())
+    }
+
+    fn recv_from(&mut self) -> Result<SocketReceiveFrom> {
+        let handle = self.handle;
+        let (data, from) = self.stack.wait(None, |state| {
+            let socket = state.iface.get_socket::<IcmpSocket>(handle);
+            if !socket.can_recv() {
+                return Ok(None);
+            }
+            let (data, from) = socket.recv().map_err(net_error)?;
+            Ok(Some((data.to_vec(), ip_addr(from))))
+        })?;
+
+        Ok(SocketReceiveFrom {
+            data: Bytes::from(data),
+            truncated: false,
+            addr: SocketAddr::new(from, 0),
+        })
+    }
+
+    fn peek_from(&mut self) -> Result<SocketReceiveFrom> {
+        Err(NetworkError::Unsupported)
+    }
+}
+
+impl VirtualSocket for SmoltcpIcmpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.ttl = u8::try_from(ttl).map_err(|_| NetworkError::InvalidInput)?;
+        let mut state = self.stack.lock()?;
+        state
+            .iface
+            .get_socket::<IcmpSocket>(self.handle)
+            .set_hop_limit(Some(self.ttl));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(u32::from(self.ttl))
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(SocketAddr::new(self.addr, 0))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+}
+
+impl Drop for SmoltcpIcmpSocket {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.stack.lock() {
+            state.iface.remove_socket(self.handle);
+        }
+    }
+}
+
+/// A raw socket, sending and receiving whole Ethernet frames on the device
+/// of the stack.
+///
+/// It gets a copy of the frames the device receives for the stack, which
+/// still processes them, and its frames are sent as they are, the stack
+/// never seeing them.
+#[derive(Debug)]
+pub struct SmoltcpRawSocket {
+    stack: Arc<Stack>,
+    id: u64,
+    promiscuous: bool,
+}
+
+impl SmoltcpRawSocket {
+    pub(crate) fn new(stack: Arc<Stack>, id: u64) -> Self {
+        Self {
+            stack,
+            id,
+            promiscuous: false,
+        }
+    }
+}
+
+impl VirtualRawSocket for SmoltcpRawSocket {
+    fn send(&mut self, data: Bytes) -> Result<usize> {
+        let mut state = self.stack.lock()?;
+        let device = state.iface.device_mut();
+        if data.len() > device.mtu() {
+            return Err(NetworkError::InvalidInput);
+        }
+        device.send_raw(data.to_vec());
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // The frames are handed to the device right away.
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<SocketReceive> {
+        let id = self.id;
+        let frame = self
+            .stack
+            .wait(None, |state| Ok(state.iface.device_mut().recv_raw(id)))?;
+
+        Ok(SocketReceive {
+            data: Bytes::from(frame),
+            truncated: false,
+        })
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        let mut state = self.stack.lock()?;
+        state
+            .iface
+            .device_mut()
+            .set_raw_promiscuous(self.id, promiscuous);
+        self.promiscuous = promiscuous;
+        Ok(())
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        Ok(self.promiscuous)
+    }
+}
+
+impl VirtualSocket for SmoltcpRawSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        // The frames are sent with the headers given to the socket.
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+}
+
+impl Drop for SmoltcpRawSocket {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.stack.lock() {
+            state.iface.device_mut().remove_raw(self.id);
+        }
+    }
+}
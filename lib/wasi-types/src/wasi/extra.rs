@@ -533,6 +533,7 @@ impl core::fmt::Display for BusErrno {
 impl std::error::Error for BusErrno {}
 wit_bindgen_rust::bitflags::bitflags! {
   /// File descriptor rights, determining which actions may be performed.
+  #[cfg_attr(feature = "enable-serde", derive(serde::Serialize, serde::Deserialize))]
   pub struct Rights: u64 {
     /// The right to invoke `fd_datasync`.
     ///
@@ -639,6 +640,7 @@ impl Rights {
 /// The type of a file descriptor or file.
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "enable-serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Filetype {
     /// The type of the file descriptor or file is unknown or is different from any of the other types specified.
     Unknown,
@@ -751,6 +753,7 @@ impl core::fmt::Debug for Advice {
 }
 wit_bindgen_rust::bitflags::bitflags! {
   /// File descriptor flags.
+  #[cfg_attr(feature = "enable-serde", derive(serde::Serialize, serde::Deserialize))]
   pub struct Fdflags: u16 {
     /// Append mode: Data written to the file is always appended to the file's end.
     const APPEND = 1 << 0;
@@ -1318,6 +1321,7 @@ impl core::fmt::Debug for Snapshot0Filestat {
 }
 #[repr(C)]
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "enable-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Filestat {
     pub st_dev: Device,
     pub st_ino: Inode,
@@ -22,6 +22,16 @@ impl Rights {
         )
     }
 
+    pub const fn all_pipe() -> Self {
+        Self::from_bits_truncate(
+            Self::FD_FDSTAT_SET_FLAGS.bits()
+                | Self::FD_FILESTAT_GET.bits()
+                | Self::FD_READ.bits()
+                | Self::FD_WRITE.bits()
+                | Self::POLL_FD_READWRITE.bits(),
+        )
+    }
+
     /// expects a single right, returns None if out of bounds or > 1 bit set
     pub fn to_str(self) -> Option<&'static str> {
         Some(match self {
@@ -0,0 +1,408 @@
+//! A [`VirtualBus`] running the sub-processes in the local runtime.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll, Waker};
+use tracing::{debug, info, warn};
+use wasmer::{Engine, Instance, Module, Store};
+use wasmer_vbus::{
+    BusDataFormat, BusError, BusSpawnedProcess, FileDescriptor, Result, SpawnOptions,
+    SpawnOptionsConfig, StdioMode, VirtualBus, VirtualBusInvocation, VirtualBusInvokable,
+    VirtualBusListener, VirtualBusProcess, VirtualBusScope, VirtualBusSpawner,
+};
+use wasmer_vfs::{FileSystem, FsError, VirtualFile};
+
+use crate::state::WasiPipe;
+use crate::{
+    wasi_import_shared_memory, FsPolicy, PluggableRuntimeImplementation, WasiError,
+    WasiFunctionEnv, WasiState,
+};
+
+/// A bus spawning its sub-processes as new instances in threads of the
+/// local runtime.
+///
+/// The commands are resolved from the modules added with
+/// [`LocalVirtualBus::add_command`] and then from the `<name>.wasm` or
+/// `<name>` files in the directories added with
+/// [`LocalVirtualBus::add_search_path`], like the ones packages are
+/// installed in. The processes run with the same bus, so that they can
+/// spawn processes in turn.
+///
+/// The `Inherit` mode of the STDIO uses the STDIO of the host, as the
+/// bus doesn't know the calling process.
+///
+/// The processes get a new default filesystem backing, unless one is
+/// shared with them with [`LocalVirtualBus::set_fs`], which sandboxed
+/// callers must do. The directories they preopen are checked against the
+/// ones of the calling process by `proc_spawn`.
+#[derive(Clone)]
+pub struct LocalVirtualBus {
+    inner: Arc<LocalVirtualBusInner>,
+}
+
+type RuntimeFactory = dyn Fn() -> PluggableRuntimeImplementation + Send + Sync;
+
+struct LocalVirtualBusInner {
+    engine: Engine,
+    commands: RwLock<HashMap<String, Module>>,
+    search_paths: RwLock<Vec<PathBuf>>,
+    runtime_factory: RwLock<Option<Arc<RuntimeFactory>>>,
+    fs: RwLock<Option<(Arc<dyn FileSystem>, FsPolicy)>>,
+}
+
+impl LocalVirtualBus {
+    /// Creates a bus compiling and running the commands with `engine`.
+    pub fn new(engine: impl Into<Engine>) -> Self {
+        Self {
+            inner: Arc::new(LocalVirtualBusInner {
+                engine: engine.into(),
+                commands: RwLock::new(HashMap::new()),
+                search_paths: RwLock::new(Vec::new()),
+                runtime_factory: RwLock::new(None),
+                fs: RwLock::new(None),
+            }),
+        }
+    }
+
+    /// Makes `module` the command run for `name`.
+    pub fn add_command(&self, name: impl Into<String>, module: Module) {
+        let mut commands = self.inner.commands.write().unwrap();
+        commands.insert(name.into(), module);
+    }
+
+    /// Looks up the commands not added with [`Self::add_command`] in
+    /// the host directory `path`, after the directories added before.
+    pub fn add_search_path(&self, path: impl Into<PathBuf>) {
+        let mut search_paths = self.inner.search_paths.write().unwrap();
+        search_paths.push(path.into());
+    }
+
+    /// Creates the runtimes of the processes with `factory`, rather than
+    /// with the default one, for them to get the same networking as the
+    /// caller for instance. Their bus is replaced with this one.
+    pub fn set_runtime_factory<F>(&self, factory: F)
+    where
+        F: Fn() -> PluggableRuntimeImplementation + Send + Sync + 'static,
+    {
+        let mut runtime_factory = self.inner.runtime_factory.write().unwrap();
+        *runtime_factory = Some(Arc::new(factory));
+    }
+
+    /// Runs the processes on the filesystem backing `fs`, typically the
+    /// one of the caller, restricted by `policy`.
+    pub fn set_fs(&self, fs: Arc<dyn FileSystem>, policy: FsPolicy) {
+        let mut guard = self.inner.fs.write().unwrap();
+        *guard = Some((fs, policy));
+    }
+
+    /// Resolves the module of a command, compiling it the first time.
+    fn resolve(&self, name: &str) -> Result<Module> {
+        if let Some(module) = self.inner.commands.read().unwrap().get(name) {
+            return Ok(module.clone());
+        }
+
+        // The names are file names, not paths leaving the directories
+        if Path::new(name).file_name() != Some(OsStr::new(name)) {
+            return Err(BusError::InvalidWapm);
+        }
+        let path = {
+            let search_paths = self.inner.search_paths.read().unwrap();
+            search_paths
+                .iter()
+                .flat_map(|dir| [dir.join(format!("{}.wasm", name)), dir.join(name)])
+                .find(|path| path.is_file())
+                .ok_or(BusError::InvalidWapm)?
+        };
+        debug!("compiling the command {} from {}", name, path.display());
+
+        let module = Module::from_file(&self.inner.engine, &path).map_err(|err| {
+            warn!("failed to compile {}: {}", path.display(), err);
+            BusError::CompileError
+        })?;
+        self.add_command(name, module.clone());
+        Ok(module)
+    }
+}
+
+impl fmt::Debug for LocalVirtualBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalVirtualBus")
+            .field(
+                "commands",
+                &self
+                    .inner
+                    .commands
+                    .read()
+                    .unwrap()
+                    .keys()
+                    .collect::<Vec<_>>(),
+            )
+            .field("search_paths", &self.inner.search_paths.read().unwrap())
+            .finish()
+    }
+}
+
+impl VirtualBus for LocalVirtualBus {
+    fn new_spawn(&self) -> SpawnOptions {
+        SpawnOptions::new(Box::new(LocalVirtualBusSpawner { bus: self.clone() }))
+    }
+
+    fn listen(&self) -> Result<Box<dyn VirtualBusListener + Sync>> {
+        Err(BusError::Unsupported)
+    }
+}
+
+#[derive(Debug)]
+struct LocalVirtualBusSpawner {
+    bus: LocalVirtualBus,
+}
+
+impl VirtualBusSpawner for LocalVirtualBusSpawner {
+    fn spawn(&mut self, name: &str, config: &SpawnOptionsConfig) -> Result<BusSpawnedProcess> {
+        if config.remote_instance().is_some() {
+            return Err(BusError::Unsupported);
+        }
+        let module = self.bus.resolve(name)?;
+
+        let mut builder = WasiState::new(name);
+        builder.args(config.args().iter().filter(|arg| !arg.is_empty()));
+        if let Some((fs, policy)) = self.bus.inner.fs.read().unwrap().as_ref() {
+            builder.set_fs(Box::new(fs.clone()));
+            builder.fs_policy(policy.clone());
+        }
+        for dir in config.preopen().iter().filter(|dir| !dir.is_empty()) {
+            builder.preopen_dir(dir).map_err(|_| BusError::BadRequest)?;
+        }
+
+        let runtime_factory = self.bus.inner.runtime_factory.read().unwrap().clone();
+        let mut runtime = match runtime_factory {
+            Some(factory) => factory(),
+            None => PluggableRuntimeImplementation::default(),
+        };
+        runtime.set_bus_implementation(self.bus.clone());
+        builder.runtime(runtime);
+
+        let stdin = stdio(config.stdin_mode(), false, |file| {
+            builder.stdin(file);
+        });
+        let stdout = stdio(config.stdout_mode(), true, |file| {
+            builder.stdout(file);
+        });
+        let stderr = stdio(config.stderr_mode(), true, |file| {
+            builder.stderr(file);
+        });
+
+        let mut store = Store::new(self.bus.inner.engine.clone());
+        let env = builder
+            .finalize(&mut store)
+            .map_err(|_| BusError::BadRequest)?;
+        env.data_mut(&mut store)
+            .state
+            .fs
+            .set_current_dir(config.working_dir());
+
+        let exit = Arc::new(Mutex::new(ProcessExit::default()));
+        let process = LocalProcess {
+            exit: exit.clone(),
+            stdin,
+            stdout,
+            stderr,
+        };
+
+        let name = name.to_string();
+        std::thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || {
+                let code = run(&mut store, &module, env);
+                debug!("process {} exited with code {}", name, code);
+                // Closes the STDIO of the process before anyone is told
+                drop(store);
+
+                let mut exit = exit.lock().unwrap();
+                exit.code = Some(code);
+                for waker in exit.wakers.drain(..) {
+                    waker.wake();
+                }
+            })
+            .map_err(|_| BusError::InternalError)?;
+
+        Ok(BusSpawnedProcess {
+            inst: Box::new(process),
+        })
+    }
+}
+
+/// Gives the process the file its STDIO is wired to for `mode`, and
+/// returns the end of the pipe kept for the caller when it is piped.
+fn stdio<F>(mode: StdioMode, output: bool, set: F) -> Option<WasiPipe>
+where
+    F: FnOnce(Box<dyn VirtualFile + Send + Sync + 'static>),
+{
+    match mode {
+        StdioMode::Piped => {
+            let (caller, process) = WasiPipe::new();
+            set(Box::new(process));
+            Some(caller)
+        }
+        StdioMode::Inherit => None,
+        StdioMode::Null => {
+            set(Box::new(Sink { log: false }));
+            None
+        }
+        StdioMode::Log => {
+            set(Box::new(Sink { log: output }));
+            None
+        }
+    }
+}
+
+/// Runs the `_start` function of the process, returning its exit code.
+fn run(store: &mut Store, module: &Module, mut env: WasiFunctionEnv) -> u32 {
+    let result = (|| -> std::result::Result<u32, Box<dyn std::error::Error>> {
+        let mut imports = env.import_object_for_all_wasi_versions(store, module)?;
+        wasi_import_shared_memory(&mut imports, module, store);
+        let instance = Instance::new(store, module, &imports)?;
+        env.initialize(store, &instance)?;
+
+        let start = instance.exports.get_function("_start")?;
+        match start.call(store, &[]) {
+            Ok(_) => Ok(0),
+            Err(err) => match err.downcast::<WasiError>() {
+                Ok(WasiError::Exit(code)) => Ok(code),
+                Ok(err) => Err(err.into()),
+                Err(err) => Err(err.into()),
+            },
+        }
+    })();
+
+    result.unwrap_or_else(|err| {
+        warn!("process failed: {}", err);
+        1
+    })
+}
+
+#[derive(Debug, Default)]
+struct ProcessExit {
+    code: Option<u32>,
+    wakers: Vec<Waker>,
+}
+
+/// A process running in a thread of the local runtime.
+#[derive(Debug)]
+struct LocalProcess {
+    exit: Arc<Mutex<ProcessExit>>,
+    stdin: Option<WasiPipe>,
+    stdout: Option<WasiPipe>,
+    stderr: Option<WasiPipe>,
+}
+
+impl VirtualBusProcess for LocalProcess {
+    fn exit_code(&self) -> Option<u32> {
+        self.exit.lock().unwrap().code
+    }
+
+    fn stdin_fd(&self) -> Option<FileDescriptor> {
+        None
+    }
+
+    fn stdout_fd(&self) -> Option<FileDescriptor> {
+        None
+    }
+
+    fn stderr_fd(&self) -> Option<FileDescriptor> {
+        None
+    }
+
+    fn take_stdin(&mut self) -> Option<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        self.stdin.take().map(|pipe| Box::new(pipe) as _)
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        self.stdout.take().map(|pipe| Box::new(pipe) as _)
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        self.stderr.take().map(|pipe| Box::new(pipe) as _)
+    }
+}
+
+impl VirtualBusScope for LocalProcess {
+    fn poll_finished(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut exit = self.exit.lock().unwrap();
+        if exit.code.is_some() {
+            return Poll::Ready(());
+        }
+        if !exit.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            exit.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl VirtualBusInvokable for LocalProcess {
+    fn invoke(
+        &self,
+        _topic: String,
+        _format: BusDataFormat,
+        _buf: &[u8],
+    ) -> Result<Box<dyn VirtualBusInvocation + Sync>> {
+        Err(BusError::Unsupported)
+    }
+}
+
+/// The STDIO of a process which is dropped, or sent to the log.
+#[derive(Debug)]
+struct Sink {
+    log: bool,
+}
+
+impl Read for Sink {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.log {
+            info!("{}", String::from_utf8_lossy(buf).trim_end());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Sink {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+impl VirtualFile for Sink {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _len: u64) -> std::result::Result<(), FsError> {
+        Ok(())
+    }
+    fn unlink(&mut self) -> std::result::Result<(), FsError> {
+        Ok(())
+    }
+}
@@ -35,24 +35,54 @@ compile_error!(
 
 #[macro_use]
 mod macros;
+#[cfg(all(feature = "sys", feature = "compiler"))]
+mod bus;
+#[cfg(feature = "replay")]
+mod replay;
 mod runtime;
 mod state;
+#[cfg(feature = "sys")]
+mod strace;
 mod syscalls;
+mod tty;
 mod utils;
 
 /// Runners for WASI / Emscripten
 #[cfg(feature = "webc_runner")]
 pub mod runners;
 
+/// Host implementation of the WASI Preview 2 interfaces
+pub mod preview2;
+
 use crate::syscalls::*;
 
+#[cfg(all(feature = "sys", feature = "compiler"))]
+pub use crate::bus::LocalVirtualBus;
+pub use crate::preview2::WasiPreview2;
+#[cfg(all(feature = "replay", feature = "sys"))]
+pub use crate::replay::SyscallRecorder;
+#[cfg(feature = "replay")]
+pub use crate::replay::{
+    MemoryWrite, RecordedValue, SyscallEvent, SyscallOutcome, SyscallReplayer, SyscallTrace,
+};
 pub use crate::state::{
-    Fd, Pipe, Stderr, Stdin, Stdout, WasiFs, WasiInodes, WasiState, WasiStateBuilder,
-    WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
+    Fd, FsAccess, FsPolicy, Pipe, Stderr, Stdin, Stdout, WasiFs, WasiInodes, WasiState,
+    WasiStateBuilder, WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
 };
+#[cfg(feature = "checkpoint")]
+pub use crate::state::{
+    VirtualFileCheckpointHandler, WasiCheckpoint, WasiCheckpointError, WasiCheckpointer,
+};
+#[cfg(feature = "sys")]
+pub use crate::strace::{StraceFormat, SyscallTracer};
 pub use crate::syscalls::types;
+#[cfg(all(unix, feature = "sys"))]
+pub use crate::tty::HostTty;
+pub use crate::tty::{Pty, PtyMaster, PtyStdin, PtyStdout, TtyBridge};
+pub use crate::utils::is_preview2_module;
 #[cfg(feature = "wasix")]
 pub use crate::utils::is_wasix_module;
+pub use crate::utils::is_wasmedge_sockets_module;
 pub use crate::utils::wasi_import_shared_memory;
 pub use crate::utils::{get_wasi_version, get_wasi_versions, is_wasi_module, WasiVersion};
 
@@ -71,12 +101,13 @@ use tracing::trace;
 use wasmer::{
     imports, namespace, AsStoreMut, AsStoreRef, ExportError, Exports, Function, FunctionEnv,
     Imports, Instance, Memory, Memory32, MemoryAccessError, MemorySize, MemoryView, Module,
-    TypedFunction,
+    StoreMut, TypedFunction,
 };
-use wasmer_wasi_types::wasi::{BusErrno, Errno, Snapshot0Clockid};
+use wasmer_wasi_types::wasi::{BusErrno, Errno, Snapshot0Clockid, Timestamp};
 
 pub use runtime::{
-    PluggableRuntimeImplementation, WasiRuntimeImplementation, WasiThreadError, WasiTtyState,
+    FixedClock, HostClock, HostRandom, PluggableRuntimeImplementation, SeededRandom, StepClock,
+    WasiClock, WasiRandom, WasiRuntimeImplementation, WasiThreadError, WasiTtyState,
 };
 use std::sync::{mpsc, Arc, Mutex, RwLockReadGuard, RwLockWriteGuard};
 use std::time::Duration;
@@ -145,12 +176,24 @@ impl WasiThread {
     }
 }
 
+/// Builds the imports of the instance running a spawned thread, in the
+/// store of the thread (see [`WasiEnv::set_thread_imports`])
+pub type ThreadImports = dyn Fn(&mut StoreMut<'_>, &FunctionEnv<WasiEnv>, &Module) -> Result<Imports, WasiError>
+    + Send
+    + Sync;
+
+/// The canonical ABI `cabi_realloc(old_ptr, old_size, align, new_size)`
+type CabiRealloc = TypedFunction<(u32, u32, u32, u32), u32>;
+
 pub struct WasiFunctionEnv {
     pub env: FunctionEnv<WasiEnv>,
 }
 
 impl WasiFunctionEnv {
-    pub fn new(store: &mut impl AsStoreMut, env: WasiEnv) -> Self {
+    pub fn new(store: &mut impl AsStoreMut, mut env: WasiEnv) -> Self {
+        if engine_is_deterministic(&*store) {
+            env.forbid_host_sources();
+        }
         Self {
             env: FunctionEnv::new(store, env),
         }
@@ -195,8 +238,14 @@ impl WasiFunctionEnv {
         // First we get the malloc function which if it exists will be used to
         // create the pthread_self structure
         let memory = instance.exports.get_memory("memory")?.clone();
+        let cabi_realloc = instance
+            .exports
+            .get_typed_function(store, "cabi_realloc")
+            .ok();
         let env = self.data_mut(store);
         env.set_memory(memory);
+        env.module = Some(instance.module().clone());
+        env.cabi_realloc = cabi_realloc;
 
         Ok(())
     }
@@ -219,6 +268,9 @@ impl WasiFunctionEnv {
             }
         }
 
+        wasi_import_wasmedge_sockets(&mut resolver, module, store, &self.env);
+        wasi_import_preview2(&mut resolver, module, store, &self.env);
+
         #[cfg(feature = "wasix")]
         if is_wasix_module(module) {
             self.data_mut(store)
@@ -232,6 +284,40 @@ impl WasiFunctionEnv {
     }
 }
 
+/// Whether the store runs in a deterministic engine.
+#[cfg(all(feature = "sys", feature = "compiler"))]
+pub(crate) fn engine_is_deterministic(store: &impl AsStoreRef) -> bool {
+    store.as_store_ref().engine().is_deterministic()
+}
+
+/// Whether the store runs in a deterministic engine.
+#[cfg(not(all(feature = "sys", feature = "compiler")))]
+pub(crate) fn engine_is_deterministic(_store: &impl AsStoreRef) -> bool {
+    false
+}
+
+/// Measures how long a call waited with the monotonic clock of the module.
+///
+/// The clocks that don't move on their own, and the modules that can't
+/// read the time, also see the time go by on the host, so their waits
+/// still end.
+pub(crate) struct Stopwatch<'a> {
+    env: &'a WasiEnv,
+    start: Option<Timestamp>,
+    started: std::time::Instant,
+}
+
+impl Stopwatch<'_> {
+    /// The time elapsed since the stopwatch started.
+    pub(crate) fn elapsed(&self) -> Duration {
+        let moved = match (self.start, self.env.monotonic_time()) {
+            (Some(start), Some(now)) => Duration::from_nanos(now.saturating_sub(start)),
+            _ => Duration::ZERO,
+        };
+        moved.max(self.started.elapsed())
+    }
+}
+
 /// The environment provided to the WASI imports.
 #[derive(Derivative, Clone)]
 #[derivative(Debug)]
@@ -241,6 +327,12 @@ pub struct WasiEnv {
     id: WasiThreadId,
     /// Represents a reference to the memory
     memory: Option<Memory>,
+    /// Module being run, which the spawned threads instantiate again
+    #[derivative(Debug = "ignore")]
+    module: Option<Module>,
+    /// Builds the imports of the instances running the spawned threads
+    #[derivative(Debug = "ignore")]
+    thread_imports: Option<Arc<ThreadImports>>,
     /// If the module has it then map the thread start
     #[derivative(Debug = "ignore")]
     thread_start: Option<TypedFunction<u64, ()>>,
@@ -252,11 +344,29 @@ pub struct WasiEnv {
     malloc: Option<TypedFunction<u64, u64>>,
     #[derivative(Debug = "ignore")]
     free: Option<TypedFunction<(u64, u64), ()>>,
+    /// The allocator of the canonical ABI, which the Preview 2 imports
+    /// return their lists and strings in
+    #[derivative(Debug = "ignore")]
+    cabi_realloc: Option<CabiRealloc>,
+    /// The resources of the Preview 2 imports, created by the first call
+    /// to one of them
+    #[derivative(Debug = "ignore")]
+    preview2: Option<Arc<Mutex<WasiPreview2>>>,
     /// Shared state of the WASI system. Manages all the data that the
     /// executing WASI program can see.
     pub state: Arc<WasiState>,
     /// Implementation of the WASI runtime.
     pub(crate) runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    /// The clocks exposed to the module, if it may read the time.
+    pub(crate) clock: Option<Arc<dyn WasiClock>>,
+    /// The random source exposed to the module, if it may read it.
+    pub(crate) random: Option<Arc<dyn WasiRandom>>,
+    /// Whether the clock is still the one of the host picked by default,
+    /// which deterministic engines take away.
+    host_clock: bool,
+    /// Whether the random source is still the one of the host picked by
+    /// default, which deterministic engines take away.
+    host_random: bool,
 }
 
 impl WasiEnv {
@@ -266,12 +376,20 @@ impl WasiEnv {
             id: 0u32.into(),
             state: Arc::new(state),
             memory: None,
+            module: None,
+            thread_imports: None,
             thread_start: None,
             reactor_work: None,
             reactor_finish: None,
             malloc: None,
             free: None,
+            cabi_realloc: None,
+            preview2: None,
             runtime: Arc::new(PluggableRuntimeImplementation::default()),
+            clock: Some(Arc::new(HostClock)),
+            random: Some(Arc::new(HostRandom)),
+            host_clock: true,
+            host_random: true,
         }
     }
 
@@ -288,6 +406,83 @@ impl WasiEnv {
         self.runtime = Arc::new(runtime);
     }
 
+    /// Overrides how the imports of the instances running the spawned
+    /// threads are built, in the store of each thread.
+    ///
+    /// They must provide everything the module imports besides its memory,
+    /// which the threads share. By default they only are the WASI imports of
+    /// [`WasiFunctionEnv::import_object_for_all_wasi_versions`], so embedders
+    /// giving the module other imports must build them here as well.
+    pub fn set_thread_imports<F>(&mut self, imports: F)
+    where
+        F: Fn(&mut StoreMut<'_>, &FunctionEnv<WasiEnv>, &Module) -> Result<Imports, WasiError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.thread_imports = Some(Arc::new(imports));
+    }
+
+    /// Returns the clock the module reads the time from.
+    ///
+    /// Fails with `Errno::Notcapable` when the module may not read the time,
+    /// like in deterministic environments without an injected clock.
+    pub fn clock(&self) -> Result<&dyn WasiClock, Errno> {
+        self.clock.as_deref().ok_or(Errno::Notcapable)
+    }
+
+    /// Reads the monotonic clock of the module, in nanoseconds, which the
+    /// timeouts and sleeps are measured with, or `None` when the module
+    /// may not read the time.
+    pub(crate) fn monotonic_time(&self) -> Option<Timestamp> {
+        self.clock()
+            .and_then(|clock| clock.time_get(Snapshot0Clockid::Monotonic, 1))
+            .ok()
+    }
+
+    /// Overrides the clock the module reads the time from, `None`
+    /// forbids reading the time.
+    pub fn set_clock(&mut self, clock: Option<Arc<dyn WasiClock>>) {
+        self.clock = clock;
+        self.host_clock = false;
+    }
+
+    /// Returns the source of the random bytes read by the module.
+    ///
+    /// Fails with `Errno::Notcapable` when the module may not read random
+    /// bytes, like in deterministic environments without an injected
+    /// random source.
+    pub fn random(&self) -> Result<&dyn WasiRandom, Errno> {
+        self.random.as_deref().ok_or(Errno::Notcapable)
+    }
+
+    /// Overrides the source of the random bytes read by the module, `None`
+    /// forbids reading random bytes.
+    pub fn set_random(&mut self, random: Option<Arc<dyn WasiRandom>>) {
+        self.random = random;
+        self.host_random = false;
+    }
+
+    /// Forbids the module from reading the clock and the random source of
+    /// the host, keeping the ones that were injected.
+    pub(crate) fn forbid_host_sources(&mut self) {
+        if self.host_clock {
+            self.set_clock(None);
+        }
+        if self.host_random {
+            self.set_random(None);
+        }
+    }
+
+    /// Starts measuring how long a call waits, see [`Stopwatch`].
+    pub(crate) fn stopwatch(&self) -> Stopwatch<'_> {
+        Stopwatch {
+            env: self,
+            start: self.monotonic_time(),
+            started: std::time::Instant::now(),
+        }
+    }
+
     /// Returns the current thread ID
     pub fn current_thread_id(&self) -> WasiThreadId {
         self.id
@@ -321,32 +516,21 @@ impl WasiEnv {
     // Yields execution
     pub fn yield_now(&self) -> Result<(), WasiError> {
         self.runtime.yield_now(self.id)?;
+        if let Some(exit_code) = self.state.threading.lock().unwrap().exit_code {
+            return Err(WasiError::Exit(exit_code));
+        }
         Ok(())
     }
 
-    // Sleeps for a period of time
+    // Sleeps for a period of time, measured like the waits of a
+    // [`Stopwatch`]
     pub fn sleep(&self, duration: Duration) -> Result<(), WasiError> {
-        let duration = duration.as_nanos();
-        let start =
-            platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
+        let stopwatch = self.stopwatch();
         self.yield_now()?;
         loop {
-            let now =
-                platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
-            let delta = match now.checked_sub(start) {
-                Some(a) => a,
-                None => {
-                    break;
-                }
-            };
-            if delta >= duration {
-                break;
-            }
-            let remaining = match duration.checked_sub(delta) {
-                Some(a) => Duration::from_nanos(a as u64),
-                None => {
-                    break;
-                }
+            let remaining = match duration.checked_sub(stopwatch.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => remaining,
+                _ => break,
             };
             std::thread::sleep(remaining.min(Duration::from_millis(10)));
             self.yield_now()?;
@@ -383,11 +567,46 @@ impl WasiEnv {
         self.memory.as_ref().unwrap()
     }
 
+    /// The `cabi_realloc` exported by the module, if it has one
+    pub(crate) fn cabi_realloc(&self) -> Option<&CabiRealloc> {
+        self.cabi_realloc.as_ref()
+    }
+
+    /// The Preview 2 state behind the imports of the module, shared with
+    /// the threads it spawns
+    pub(crate) fn preview2(&mut self) -> Arc<Mutex<WasiPreview2>> {
+        if self.preview2.is_none() {
+            self.preview2 = Some(Arc::new(Mutex::new(WasiPreview2::new(self))));
+        }
+        self.preview2.clone().unwrap()
+    }
+
     /// Get the WASI state
     pub fn state(&self) -> &WasiState {
         &self.state
     }
 
+    /// Take a checkpoint of the WASI state and of the memory, if it was set.
+    ///
+    /// The guest should be paused (for instance in a host function) so that
+    /// the memory doesn't change while it's being copied.
+    #[cfg(feature = "checkpoint")]
+    pub fn checkpoint(
+        &self,
+        store: &impl AsStoreRef,
+        checkpointer: &WasiCheckpointer,
+    ) -> Result<WasiCheckpoint, WasiCheckpointError> {
+        let mut checkpoint = checkpointer.checkpoint(&self.state)?;
+        if let Some(memory) = self.memory.as_ref() {
+            let view = memory.view(store);
+            let mut data = vec![0; view.data_size() as usize];
+            view.read(0, &mut data)
+                .map_err(|err| WasiCheckpointError::MemoryError(err.to_string()))?;
+            checkpoint.set_memory(Some(data));
+        }
+        Ok(checkpoint)
+    }
+
     pub(crate) fn get_memory_and_wasi_state<'a>(
         &'a self,
         store: &'a impl AsStoreRef,
@@ -539,21 +758,111 @@ fn wasi_snapshot_preview1_exports(
         "proc_raise" => Function::new_typed_with_env(&mut store, env, proc_raise),
         "random_get" => Function::new_typed_with_env(&mut store, env, random_get::<Memory32>),
         "sched_yield" => Function::new_typed_with_env(&mut store, env, sched_yield),
+        "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept_v1::<Memory32>),
         "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv::<Memory32>),
         "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send::<Memory32>),
         "sock_shutdown" => Function::new_typed_with_env(&mut store, env, sock_shutdown),
     };
     namespace
 }
+
+/// The socket functions of WasmEdge, which extend `wasi_snapshot_preview1`.
+fn wasmedge_sock_exports(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>) -> Exports {
+    use self::wasmedge::*;
+    let namespace = namespace! {
+        "sock_open" => Function::new_typed_with_env(&mut store, env, sock_open),
+        "sock_bind" => Function::new_typed_with_env(&mut store, env, sock_bind),
+        "sock_listen" => Function::new_typed_with_env(&mut store, env, sock_listen),
+        "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept),
+        "sock_connect" => Function::new_typed_with_env(&mut store, env, sock_connect),
+        "sock_recv_from" => Function::new_typed_with_env(&mut store, env, sock_recv_from),
+        "sock_send_to" => Function::new_typed_with_env(&mut store, env, sock_send_to),
+        "sock_getlocaladdr" => Function::new_typed_with_env(&mut store, env, sock_getlocaladdr),
+        "sock_getpeeraddr" => Function::new_typed_with_env(&mut store, env, sock_getpeeraddr),
+        "sock_getsockopt" => Function::new_typed_with_env(&mut store, env, sock_getsockopt),
+        "sock_setsockopt" => Function::new_typed_with_env(&mut store, env, sock_setsockopt),
+        "sock_getaddrinfo" => Function::new_typed_with_env(&mut store, env, sock_getaddrinfo),
+    };
+    namespace
+}
+
+/// Adds the socket functions of WasmEdge to the `wasi_snapshot_preview1`
+/// imports, if the module uses them.
+///
+/// They share the sockets and the networking implementation of the
+/// WASIX socket functions. The standard `sock_accept` is kept for the
+/// modules importing it.
+pub fn wasi_import_wasmedge_sockets(
+    imports: &mut Imports,
+    module: &Module,
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+) {
+    if !is_wasmedge_sockets_module(module) {
+        return;
+    }
+    let standard_accept = module.imports().functions().any(|f| {
+        f.module() == "wasi_snapshot_preview1"
+            && f.name() == "sock_accept"
+            && f.ty().params().len() == 3
+    });
+    for (name, function) in wasmedge_sock_exports(store, env).iter() {
+        if name == "sock_accept" && standard_accept {
+            continue;
+        }
+        imports.define("wasi_snapshot_preview1", name, function.clone());
+    }
+}
+
+/// Adds the WASI Preview 2 interfaces to the imports, if the module uses
+/// them.
+///
+/// They're named like the imports of the Preview 1 component adapter
+/// (`wasi:io/streams@0.2.0`, ...) and lowered with the canonical ABI,
+/// returning the lists and strings in the memory the module allocates with
+/// its `cabi_realloc`. They share the file descriptors, the environment,
+/// the clocks and the random source of the Preview 1 functions.
+///
+/// The interfaces provided are `wasi:cli/{environment,exit,stdin,stdout,stderr}`,
+/// `wasi:clocks/{wall-clock,monotonic-clock}`, `wasi:random/{random,insecure,insecure-seed}`,
+/// `wasi:io/{error,poll,streams}` and `wasi:filesystem/{preopens,types}`,
+/// except for the `splice` and `write-zeroes` of the streams and for the
+/// `read`, `write`, `rename-at`, links and times of the descriptors.
+/// `wasi:sockets` isn't provided.
+pub fn wasi_import_preview2(
+    imports: &mut Imports,
+    module: &Module,
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+) {
+    if !is_preview2_module(module) {
+        return;
+    }
+    let preview2_imports = preview2::preview2_imports(store, env);
+    for ((namespace, name), function) in preview2_imports.into_iter() {
+        imports.define(&namespace, &name, function);
+    }
+}
+
+/// The `wasi-threads` extension, used by the pthreads of wasi-libc.
+fn wasi_threads_exports(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>) -> Exports {
+    let namespace = namespace! {
+        "thread-spawn" => Function::new_typed_with_env(&mut store, env, wasi_thread_spawn),
+    };
+    namespace
+}
+
 pub fn import_object_for_all_wasi_versions(
     store: &mut impl AsStoreMut,
     env: &FunctionEnv<WasiEnv>,
 ) -> Imports {
     let wasi_unstable_exports = wasi_unstable_exports(store, env);
     let wasi_snapshot_preview1_exports = wasi_snapshot_preview1_exports(store, env);
+    let wasi_threads_exports = wasi_threads_exports(store, env);
     imports! {
         "wasi_unstable" => wasi_unstable_exports,
         "wasi_snapshot_preview1" => wasi_snapshot_preview1_exports,
+        "wasi" => wasi_threads_exports,
     }
 }
 
@@ -573,8 +882,10 @@ fn generate_import_object_snapshot1(
     env: &FunctionEnv<WasiEnv>,
 ) -> Imports {
     let wasi_snapshot_preview1_exports = wasi_snapshot_preview1_exports(store, env);
+    let wasi_threads_exports = wasi_threads_exports(store, env);
     imports! {
-        "wasi_snapshot_preview1" => wasi_snapshot_preview1_exports
+        "wasi_snapshot_preview1" => wasi_snapshot_preview1_exports,
+        "wasi" => wasi_threads_exports,
     }
 }
 
@@ -0,0 +1,69 @@
+//! `wasi:cli/environment`, `wasi:cli/exit` and the `wasi:cli` stdio
+//! interfaces.
+
+use super::{FdStream, InputStream, OutputStream, WasiPreview2};
+use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
+use crate::WasiError;
+
+impl WasiPreview2 {
+    /// `environment.get-environment`
+    pub fn get_environment(&self) -> Vec<(String, String)> {
+        self.state
+            .envs
+            .iter()
+            .map(|env| {
+                let env = String::from_utf8_lossy(env);
+                match env.split_once('=') {
+                    Some((key, value)) => (key.to_string(), value.to_string()),
+                    None => (env.to_string(), String::new()),
+                }
+            })
+            .collect()
+    }
+
+    /// `environment.get-arguments`
+    pub fn get_arguments(&self) -> Vec<String> {
+        self.state
+            .args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect()
+    }
+
+    /// `environment.initial-cwd`
+    pub fn initial_cwd(&self) -> Option<String> {
+        Some(self.state.fs.current_dir.lock().unwrap().clone())
+    }
+
+    /// `stdin.get-stdin`
+    pub fn get_stdin(&mut self) -> InputStream {
+        self.push_input_stream(FdStream {
+            fd: __WASI_STDIN_FILENO,
+            position: None,
+            append: false,
+        })
+    }
+
+    /// `stdout.get-stdout`
+    pub fn get_stdout(&mut self) -> OutputStream {
+        self.push_output_stream(FdStream {
+            fd: __WASI_STDOUT_FILENO,
+            position: None,
+            append: false,
+        })
+    }
+
+    /// `stderr.get-stderr`
+    pub fn get_stderr(&mut self) -> OutputStream {
+        self.push_output_stream(FdStream {
+            fd: __WASI_STDERR_FILENO,
+            position: None,
+            append: false,
+        })
+    }
+
+    /// `exit.exit`: the error to unwind the guest with, like `proc_exit`.
+    pub fn exit(&self, status: Result<(), ()>) -> WasiError {
+        WasiError::Exit(if status.is_ok() { 0 } else { 1 })
+    }
+}
@@ -0,0 +1,64 @@
+//! `wasi:clocks/wall-clock` and `wasi:clocks/monotonic-clock`.
+
+use super::{Pollable, PollableKind, WasiPreview2};
+use crate::syscalls::types::wasi::{Errno, Snapshot0Clockid, Timestamp};
+use crate::WasiClock;
+
+/// A `wasi:clocks/wall-clock.datetime`, the time since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Datetime {
+    pub seconds: u64,
+    pub nanoseconds: u32,
+}
+
+impl Datetime {
+    pub(crate) fn from_nanos(nanos: Timestamp) -> Self {
+        Self {
+            seconds: nanos / 1_000_000_000,
+            nanoseconds: (nanos % 1_000_000_000) as u32,
+        }
+    }
+}
+
+impl WasiPreview2 {
+    fn clock(&self) -> Result<&dyn WasiClock, Errno> {
+        self.clock.as_deref().ok_or(Errno::Notcapable)
+    }
+
+    /// `wall-clock.now`
+    pub fn wall_clock_now(&self) -> Result<Datetime, Errno> {
+        self.clock()?
+            .time_get(Snapshot0Clockid::Realtime, 1)
+            .map(Datetime::from_nanos)
+    }
+
+    /// `wall-clock.resolution`
+    pub fn wall_clock_resolution(&self) -> Result<Datetime, Errno> {
+        self.clock()?
+            .res_get(Snapshot0Clockid::Realtime)
+            .map(Datetime::from_nanos)
+    }
+
+    /// `monotonic-clock.now`, in nanoseconds.
+    pub fn monotonic_clock_now(&self) -> Result<Timestamp, Errno> {
+        self.clock()?.time_get(Snapshot0Clockid::Monotonic, 1)
+    }
+
+    /// `monotonic-clock.resolution`, in nanoseconds.
+    pub fn monotonic_clock_resolution(&self) -> Result<Timestamp, Errno> {
+        self.clock()?.res_get(Snapshot0Clockid::Monotonic)
+    }
+
+    /// `monotonic-clock.subscribe-instant`: a pollable ready once the
+    /// monotonic clock reaches `when`.
+    pub fn subscribe_instant(&mut self, when: Timestamp) -> Pollable {
+        self.push_pollable(PollableKind::Deadline(when))
+    }
+
+    /// `monotonic-clock.subscribe-duration`: a pollable ready once `when`
+    /// nanoseconds have elapsed.
+    pub fn subscribe_duration(&mut self, when: Timestamp) -> Result<Pollable, Errno> {
+        let now = self.monotonic_clock_now()?;
+        Ok(self.push_pollable(PollableKind::Deadline(now.saturating_add(when))))
+    }
+}
@@ -0,0 +1,527 @@
+//! `wasi:filesystem/types` and `wasi:filesystem/preopens`.
+
+use super::io::{fd_read, fd_write};
+use super::{
+    Datetime, Descriptor, DirectoryEntryStream, FdStream, InputStream, OpenDescriptor,
+    OutputStream, Resource, WasiPreview2,
+};
+use crate::syscalls::types::wasi::{
+    Errno, Fd as WasiFd, Fdflags, Filestat, Filetype, LookupFlags, Oflags, Rights,
+};
+use crate::syscalls::types::__WASI_LOOKUP_SYMLINK_FOLLOW;
+use crate::syscalls::{
+    fd_readdir_entries, path_create_directory_internal, path_filestat_get_internal,
+    path_open_internal, path_remove_directory_internal, path_rename_internal,
+    path_unlink_file_internal,
+};
+use std::collections::VecDeque;
+use std::ops::DerefMut;
+
+/// The `wasi:filesystem/types.error-code` of a failed operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Access,
+    WouldBlock,
+    Already,
+    BadDescriptor,
+    Busy,
+    Deadlock,
+    Quota,
+    Exist,
+    FileTooLarge,
+    IllegalByteSequence,
+    InProgress,
+    Interrupted,
+    Invalid,
+    Io,
+    IsDirectory,
+    Loop,
+    TooManyLinks,
+    MessageSize,
+    NameTooLong,
+    NoDevice,
+    NoEntry,
+    NoLock,
+    InsufficientMemory,
+    InsufficientSpace,
+    NotDirectory,
+    NotEmpty,
+    NotRecoverable,
+    Unsupported,
+    NoTty,
+    NoSuchDevice,
+    Overflow,
+    NotPermitted,
+    Pipe,
+    ReadOnly,
+    InvalidSeek,
+    TextFileBusy,
+    CrossDevice,
+}
+
+impl From<Errno> for ErrorCode {
+    fn from(errno: Errno) -> Self {
+        match errno {
+            Errno::Access | Errno::Notcapable => Self::Access,
+            Errno::Again => Self::WouldBlock,
+            Errno::Already => Self::Already,
+            Errno::Badf => Self::BadDescriptor,
+            Errno::Busy => Self::Busy,
+            Errno::Deadlk => Self::Deadlock,
+            Errno::Dquot => Self::Quota,
+            Errno::Exist => Self::Exist,
+            Errno::Fbig => Self::FileTooLarge,
+            Errno::Ilseq => Self::IllegalByteSequence,
+            Errno::Inprogress => Self::InProgress,
+            Errno::Intr => Self::Interrupted,
+            Errno::Inval => Self::Invalid,
+            Errno::Isdir => Self::IsDirectory,
+            Errno::Loop => Self::Loop,
+            Errno::Mlink => Self::TooManyLinks,
+            Errno::Msgsize => Self::MessageSize,
+            Errno::Nametoolong => Self::NameTooLong,
+            Errno::Nodev => Self::NoDevice,
+            Errno::Noent => Self::NoEntry,
+            Errno::Nolck => Self::NoLock,
+            Errno::Nomem => Self::InsufficientMemory,
+            Errno::Nospc => Self::InsufficientSpace,
+            Errno::Notdir => Self::NotDirectory,
+            Errno::Notempty => Self::NotEmpty,
+            Errno::Notrecoverable => Self::NotRecoverable,
+            Errno::Notsup | Errno::Nosys => Self::Unsupported,
+            Errno::Notty => Self::NoTty,
+            Errno::Nxio => Self::NoSuchDevice,
+            Errno::Overflow => Self::Overflow,
+            Errno::Perm => Self::NotPermitted,
+            Errno::Pipe => Self::Pipe,
+            Errno::Rofs => Self::ReadOnly,
+            Errno::Spipe => Self::InvalidSeek,
+            Errno::Txtbsy => Self::TextFileBusy,
+            Errno::Xdev => Self::CrossDevice,
+            _ => Self::Io,
+        }
+    }
+}
+
+/// The `wasi:filesystem/types.descriptor-type` of a descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorType {
+    Unknown,
+    BlockDevice,
+    CharacterDevice,
+    Directory,
+    Fifo,
+    SymbolicLink,
+    RegularFile,
+    Socket,
+}
+
+impl From<Filetype> for DescriptorType {
+    fn from(filetype: Filetype) -> Self {
+        match filetype {
+            Filetype::Unknown => Self::Unknown,
+            Filetype::BlockDevice => Self::BlockDevice,
+            Filetype::CharacterDevice => Self::CharacterDevice,
+            Filetype::Directory => Self::Directory,
+            Filetype::RegularFile => Self::RegularFile,
+            Filetype::SocketDgram | Filetype::SocketStream => Self::Socket,
+            Filetype::SymbolicLink => Self::SymbolicLink,
+            Filetype::Fifo => Self::Fifo,
+        }
+    }
+}
+
+/// The `wasi:filesystem/types.descriptor-flags` of a descriptor.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DescriptorFlags {
+    pub read: bool,
+    pub write: bool,
+    pub mutate_directory: bool,
+}
+
+/// The `wasi:filesystem/types.path-flags` of a path lookup.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PathFlags {
+    pub symlink_follow: bool,
+}
+
+impl From<PathFlags> for LookupFlags {
+    fn from(flags: PathFlags) -> Self {
+        if flags.symlink_follow {
+            __WASI_LOOKUP_SYMLINK_FOLLOW
+        } else {
+            0
+        }
+    }
+}
+
+/// The `wasi:filesystem/types.open-flags` of `open-at`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OpenFlags {
+    pub create: bool,
+    pub directory: bool,
+    pub exclusive: bool,
+    pub truncate: bool,
+}
+
+impl From<OpenFlags> for Oflags {
+    fn from(flags: OpenFlags) -> Self {
+        let mut oflags = Oflags::empty();
+        oflags.set(Oflags::CREATE, flags.create);
+        oflags.set(Oflags::DIRECTORY, flags.directory);
+        oflags.set(Oflags::EXCL, flags.exclusive);
+        oflags.set(Oflags::TRUNC, flags.truncate);
+        oflags
+    }
+}
+
+/// The `wasi:filesystem/types.descriptor-stat` of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DescriptorStat {
+    pub type_: DescriptorType,
+    pub link_count: u64,
+    pub size: u64,
+    pub data_access_timestamp: Option<Datetime>,
+    pub data_modification_timestamp: Option<Datetime>,
+    pub status_change_timestamp: Option<Datetime>,
+}
+
+impl From<Filestat> for DescriptorStat {
+    fn from(stat: Filestat) -> Self {
+        // A zero timestamp means the backing file system doesn't track it.
+        let datetime = |timestamp| match timestamp {
+            0 => None,
+            timestamp => Some(Datetime::from_nanos(timestamp)),
+        };
+        Self {
+            type_: stat.st_filetype.into(),
+            link_count: stat.st_nlink,
+            size: stat.st_size,
+            data_access_timestamp: datetime(stat.st_atim),
+            data_modification_timestamp: datetime(stat.st_mtim),
+            status_change_timestamp: datetime(stat.st_ctim),
+        }
+    }
+}
+
+/// A `wasi:filesystem/types.directory-entry`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub type_: DescriptorType,
+    pub name: String,
+}
+
+fn errno_to_result(errno: Errno) -> Result<(), ErrorCode> {
+    match errno {
+        Errno::Success => Ok(()),
+        errno => Err(errno.into()),
+    }
+}
+
+impl WasiPreview2 {
+    fn descriptor_fd(&self, descriptor: Descriptor) -> Result<WasiFd, ErrorCode> {
+        match self.table.get(descriptor.into()) {
+            Some(Resource::Descriptor(descriptor)) => Ok(descriptor.fd),
+            _ => Err(ErrorCode::BadDescriptor),
+        }
+    }
+
+    fn fd_stream(
+        &mut self,
+        descriptor: Descriptor,
+        position: Option<u64>,
+        append: bool,
+    ) -> Result<FdStream, ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        Ok(FdStream {
+            fd,
+            position,
+            append,
+        })
+    }
+
+    /// `wasi:filesystem/preopens.get-directories`: the preopened
+    /// directories and the paths they are mounted at.
+    pub fn get_directories(&mut self) -> Vec<(Descriptor, String)> {
+        let state = self.state.clone();
+        let inodes = state.inodes.read().unwrap();
+        let preopens = state.fs.preopen_fds.read().unwrap();
+        preopens
+            .iter()
+            .filter_map(|fd| {
+                let inode = state.fs.get_fd_inode(*fd).ok()?;
+                Some((*fd, inodes.arena[inode].name.clone()))
+            })
+            .map(|(fd, name)| {
+                let descriptor = OpenDescriptor { fd, owned: false };
+                let handle = self.table.push(Resource::Descriptor(descriptor));
+                (handle.into(), name)
+            })
+            .collect()
+    }
+
+    /// `descriptor.open-at`: opens `path` relative to the directory
+    /// `descriptor`.
+    ///
+    /// The new descriptor gets the rights the directory passes on to its
+    /// children, narrowed down by `flags`.
+    pub fn open_at(
+        &mut self,
+        descriptor: Descriptor,
+        path_flags: PathFlags,
+        path: &str,
+        open_flags: OpenFlags,
+        flags: DescriptorFlags,
+    ) -> Result<Descriptor, ErrorCode> {
+        let dirfd = self.descriptor_fd(descriptor)?;
+        let state = self.state.clone();
+        let rights_inheriting = state.fs.get_fd(dirfd)?.rights_inheriting;
+        let mut rights = rights_inheriting;
+        if !flags.read {
+            rights.remove(Rights::FD_READ | Rights::FD_READDIR);
+        }
+        if !flags.write {
+            rights.remove(Rights::FD_WRITE | Rights::FD_ALLOCATE | Rights::FD_FILESTAT_SET_SIZE);
+        }
+        if !flags.mutate_directory {
+            rights.remove(
+                Rights::PATH_CREATE_DIRECTORY
+                    | Rights::PATH_CREATE_FILE
+                    | Rights::PATH_RENAME_SOURCE
+                    | Rights::PATH_RENAME_TARGET
+                    | Rights::PATH_REMOVE_DIRECTORY
+                    | Rights::PATH_UNLINK_FILE,
+            );
+        }
+
+        let mut inodes = state.inodes.write().unwrap();
+        let fd = path_open_internal(
+            &state,
+            inodes.deref_mut(),
+            dirfd,
+            path_flags.into(),
+            path,
+            open_flags.into(),
+            rights,
+            rights_inheriting,
+            Fdflags::empty(),
+        )?;
+        let descriptor = OpenDescriptor { fd, owned: true };
+        Ok(self.table.push(Resource::Descriptor(descriptor)).into())
+    }
+
+    /// `descriptor.read-via-stream`: a stream reading the file from
+    /// `offset`.
+    pub fn read_via_stream(
+        &mut self,
+        descriptor: Descriptor,
+        offset: u64,
+    ) -> Result<InputStream, ErrorCode> {
+        let stream = self.fd_stream(descriptor, Some(offset), false)?;
+        Ok(self.push_input_stream(stream))
+    }
+
+    /// `descriptor.write-via-stream`: a stream writing the file from
+    /// `offset`.
+    pub fn write_via_stream(
+        &mut self,
+        descriptor: Descriptor,
+        offset: u64,
+    ) -> Result<OutputStream, ErrorCode> {
+        let stream = self.fd_stream(descriptor, Some(offset), false)?;
+        Ok(self.push_output_stream(stream))
+    }
+
+    /// `descriptor.append-via-stream`: a stream appending to the file.
+    pub fn append_via_stream(&mut self, descriptor: Descriptor) -> Result<OutputStream, ErrorCode> {
+        let stream = self.fd_stream(descriptor, None, true)?;
+        Ok(self.push_output_stream(stream))
+    }
+
+    /// `descriptor.read`: reads up to `len` bytes at `offset`, along with
+    /// whether the end of the file was reached.
+    pub fn read_at(
+        &self,
+        descriptor: Descriptor,
+        len: u64,
+        offset: u64,
+    ) -> Result<(Vec<u8>, bool), ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let data = fd_read(&self.state, fd, Some(offset), len)?;
+        let end = (data.len() as u64) < len;
+        Ok((data, end))
+    }
+
+    /// `descriptor.write`: writes `buf` at `offset`.
+    pub fn write_at(
+        &self,
+        descriptor: Descriptor,
+        buf: &[u8],
+        offset: u64,
+    ) -> Result<u64, ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let written = fd_write(&self.state, fd, Some(offset), false, buf)?;
+        Ok(written as u64)
+    }
+
+    /// `descriptor.get-flags`
+    pub fn get_flags(&self, descriptor: Descriptor) -> Result<DescriptorFlags, ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let rights = self.state.fs.get_fd(fd)?.rights;
+        Ok(DescriptorFlags {
+            read: rights.contains(Rights::FD_READ),
+            write: rights.contains(Rights::FD_WRITE),
+            mutate_directory: rights.contains(Rights::PATH_CREATE_DIRECTORY),
+        })
+    }
+
+    /// `descriptor.get-type`
+    pub fn get_type(&self, descriptor: Descriptor) -> Result<DescriptorType, ErrorCode> {
+        self.stat(descriptor).map(|stat| stat.type_)
+    }
+
+    /// `descriptor.stat`
+    pub fn stat(&self, descriptor: Descriptor) -> Result<DescriptorStat, ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let inodes = self.state.inodes.read().unwrap();
+        let stat = self.state.fs.filestat_fd(&inodes, fd)?;
+        Ok(stat.into())
+    }
+
+    /// `descriptor.stat-at`
+    pub fn stat_at(
+        &self,
+        descriptor: Descriptor,
+        path_flags: PathFlags,
+        path: &str,
+    ) -> Result<DescriptorStat, ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let mut inodes = self.state.inodes.write().unwrap();
+        let stat = path_filestat_get_internal(
+            &self.state,
+            inodes.deref_mut(),
+            fd,
+            path_flags.into(),
+            path,
+        )?;
+        Ok(stat.into())
+    }
+
+    /// `descriptor.sync`
+    pub fn sync(&self, descriptor: Descriptor) -> Result<(), ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let inodes = self.state.inodes.read().unwrap();
+        Ok(self.state.fs.flush(&inodes, fd)?)
+    }
+
+    /// `descriptor.read-directory`: a stream over the entries of the
+    /// directory, without `.` and `..`.
+    pub fn read_directory(
+        &mut self,
+        descriptor: Descriptor,
+    ) -> Result<DirectoryEntryStream, ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let entries = {
+            let inodes = self.state.inodes.read().unwrap();
+            fd_readdir_entries(&self.state, &inodes, fd)?
+        };
+        let entries = entries
+            .into_iter()
+            .filter(|(name, _, _)| name != "." && name != "..")
+            .map(|(name, filetype, _)| DirectoryEntry {
+                type_: filetype.into(),
+                name,
+            })
+            .collect::<VecDeque<_>>();
+        Ok(self
+            .table
+            .push(Resource::DirectoryEntryStream(entries))
+            .into())
+    }
+
+    /// `directory-entry-stream.read-directory-entry`: the next entry, or
+    /// `None` once all of them were read.
+    pub fn read_directory_entry(
+        &mut self,
+        stream: DirectoryEntryStream,
+    ) -> Result<Option<DirectoryEntry>, ErrorCode> {
+        match self.table.get_mut(stream.into()) {
+            Some(Resource::DirectoryEntryStream(entries)) => Ok(entries.pop_front()),
+            _ => Err(ErrorCode::BadDescriptor),
+        }
+    }
+
+    /// `descriptor.create-directory-at`
+    pub fn create_directory_at(&self, descriptor: Descriptor, path: &str) -> Result<(), ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let mut inodes = self.state.inodes.write().unwrap();
+        errno_to_result(path_create_directory_internal(
+            &self.state,
+            inodes.deref_mut(),
+            fd,
+            path,
+        ))
+    }
+
+    /// `descriptor.remove-directory-at`
+    pub fn remove_directory_at(&self, descriptor: Descriptor, path: &str) -> Result<(), ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let mut inodes = self.state.inodes.write().unwrap();
+        errno_to_result(path_remove_directory_internal(
+            &self.state,
+            inodes.deref_mut(),
+            fd,
+            path,
+        ))
+    }
+
+    /// `descriptor.unlink-file-at`
+    pub fn unlink_file_at(&self, descriptor: Descriptor, path: &str) -> Result<(), ErrorCode> {
+        let fd = self.descriptor_fd(descriptor)?;
+        let mut inodes = self.state.inodes.write().unwrap();
+        errno_to_result(path_unlink_file_internal(
+            &self.state,
+            inodes.deref_mut(),
+            fd,
+            path,
+        ))
+    }
+
+    /// `descriptor.rename-at`
+    pub fn rename_at(
+        &self,
+        old_descriptor: Descriptor,
+        old_path: &str,
+        new_descriptor: Descriptor,
+        new_path: &str,
+    ) -> Result<(), ErrorCode> {
+        let old_fd = self.descriptor_fd(old_descriptor)?;
+        let new_fd = self.descriptor_fd(new_descriptor)?;
+        let mut inodes = self.state.inodes.write().unwrap();
+        errno_to_result(path_rename_internal(
+            &self.state,
+            inodes.deref_mut(),
+            old_fd,
+            old_path,
+            new_fd,
+            new_path,
+        ))
+    }
+
+    /// Drops a `descriptor`, closing the file descriptor it opened.
+    pub fn drop_descriptor(&mut self, descriptor: Descriptor) -> Result<(), ErrorCode> {
+        self.descriptor_fd(descriptor)?;
+        match self.table.remove(descriptor.into()) {
+            Some(Resource::Descriptor(OpenDescriptor { fd, owned: true })) => {
+                let inodes = self.state.inodes.read().unwrap();
+                Ok(self.state.fs.close_fd(&inodes, fd)?)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Drops a `directory-entry-stream`.
+    pub fn drop_directory_entry_stream(&mut self, stream: DirectoryEntryStream) {
+        self.table.remove(stream.into());
+    }
+}
@@ -0,0 +1,856 @@
+//! The Preview 2 interfaces as core module imports, under the names of the
+//! Preview 1 component adapter, lowered with the canonical ABI for wasm32.
+//!
+//! The results that don't fit in a single value are stored at the pointer
+//! given as the last argument, and the lists and strings are allocated in
+//! the guest with the `cabi_realloc` it exports.
+
+use super::{
+    Descriptor, DescriptorFlags, DescriptorStat, DirectoryEntryStream, ErrorCode, InputStream,
+    OpenFlags, OutputStream, PathFlags, Pollable, StreamError, WasiPreview2,
+};
+use crate::WasiEnv;
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use wasmer::{
+    imports, namespace, AsStoreMut, Function, FunctionEnv, FunctionEnvMut, Imports, RuntimeError,
+};
+use wasmer_wasi_types::wasi::Errno;
+
+type Ctx<'a> = FunctionEnvMut<'a, WasiEnv>;
+
+fn trap(err: impl Error + Send + Sync + 'static) -> RuntimeError {
+    RuntimeError::user(Box::new(err))
+}
+
+fn preview2<R>(ctx: &mut Ctx<'_>, f: impl FnOnce(&mut WasiPreview2) -> R) -> R {
+    let preview2 = ctx.data_mut().preview2();
+    let mut preview2 = preview2.lock().unwrap();
+    f(&mut preview2)
+}
+
+fn write(ctx: &Ctx<'_>, ptr: u32, data: &[u8]) -> Result<(), RuntimeError> {
+    let view = ctx.data().memory_view(ctx);
+    view.write(u64::from(ptr), data).map_err(trap)
+}
+
+fn write_u8(ctx: &Ctx<'_>, ptr: u32, value: u8) -> Result<(), RuntimeError> {
+    write(ctx, ptr, &[value])
+}
+
+fn write_u32(ctx: &Ctx<'_>, ptr: u32, value: u32) -> Result<(), RuntimeError> {
+    write(ctx, ptr, &value.to_le_bytes())
+}
+
+fn write_u64(ctx: &Ctx<'_>, ptr: u32, value: u64) -> Result<(), RuntimeError> {
+    write(ctx, ptr, &value.to_le_bytes())
+}
+
+fn read(ctx: &Ctx<'_>, ptr: u32, len: u32) -> Result<Vec<u8>, RuntimeError> {
+    let mut data = vec![0; len as usize];
+    let view = ctx.data().memory_view(ctx);
+    view.read(u64::from(ptr), &mut data).map_err(trap)?;
+    Ok(data)
+}
+
+fn read_string(ctx: &Ctx<'_>, ptr: u32, len: u32) -> Result<String, RuntimeError> {
+    String::from_utf8(read(ctx, ptr, len)?).map_err(trap)
+}
+
+fn alloc(ctx: &mut Ctx<'_>, align: u32, size: u32) -> Result<u32, RuntimeError> {
+    let realloc = ctx
+        .data()
+        .cabi_realloc()
+        .cloned()
+        .ok_or_else(|| RuntimeError::new("the module doesn't export `cabi_realloc`"))?;
+    realloc.call(ctx, 0, 0, align, size)
+}
+
+fn len_u32(len: usize) -> Result<u32, RuntimeError> {
+    u32::try_from(len).map_err(trap)
+}
+
+/// Copies `data` to the guest, returning its pointer and length.
+fn lower_bytes(ctx: &mut Ctx<'_>, data: &[u8]) -> Result<(u32, u32), RuntimeError> {
+    let len = len_u32(data.len())?;
+    let ptr = alloc(ctx, 1, len)?;
+    write(ctx, ptr, data)?;
+    Ok((ptr, len))
+}
+
+/// Allocates a list of `items` of `size` bytes each in the guest, and
+/// lowers them with `lower_item`.
+fn lower_list<T>(
+    ctx: &mut Ctx<'_>,
+    items: &[T],
+    align: u32,
+    size: u32,
+    mut lower_item: impl FnMut(&mut Ctx<'_>, u32, &T) -> Result<(), RuntimeError>,
+) -> Result<(u32, u32), RuntimeError> {
+    let len = len_u32(items.len())?;
+    let bytes = len
+        .checked_mul(size)
+        .ok_or_else(|| RuntimeError::new("the list is too long"))?;
+    let ptr = alloc(ctx, align, bytes)?;
+    for (index, item) in (0..len).zip(items) {
+        lower_item(ctx, ptr + index * size, item)?;
+    }
+    Ok((ptr, len))
+}
+
+fn store_list(ctx: &Ctx<'_>, ptr: u32, (list, len): (u32, u32)) -> Result<(), RuntimeError> {
+    write_u32(ctx, ptr, list)?;
+    write_u32(ctx, ptr + 4, len)
+}
+
+fn store_string(ctx: &mut Ctx<'_>, ptr: u32, string: &str) -> Result<(), RuntimeError> {
+    let list = lower_bytes(ctx, string.as_bytes())?;
+    store_list(ctx, ptr, list)
+}
+
+/// Stores a `stream-error`, whose case `last-operation-failed` owns an
+/// `error`.
+fn store_stream_error(ctx: &mut Ctx<'_>, ptr: u32, err: StreamError) -> Result<(), RuntimeError> {
+    match err {
+        StreamError::LastOperationFailed(errno) => {
+            let error = preview2(ctx, |preview2| preview2.push_error(errno));
+            write_u8(ctx, ptr, 0)?;
+            write_u32(ctx, ptr + 4, error)
+        }
+        StreamError::Closed => write_u8(ctx, ptr, 1),
+    }
+}
+
+/// Stores a `result<_, stream-error>`.
+fn store_stream_result(
+    ctx: &mut Ctx<'_>,
+    ptr: u32,
+    result: Result<(), StreamError>,
+) -> Result<(), RuntimeError> {
+    match result {
+        Ok(()) => write_u8(ctx, ptr, 0),
+        Err(err) => {
+            write_u8(ctx, ptr, 1)?;
+            store_stream_error(ctx, ptr + 4, err)
+        }
+    }
+}
+
+/// Stores a `result<u64, stream-error>`.
+fn store_stream_u64(
+    ctx: &mut Ctx<'_>,
+    ptr: u32,
+    result: Result<u64, StreamError>,
+) -> Result<(), RuntimeError> {
+    match result {
+        Ok(value) => {
+            write_u8(ctx, ptr, 0)?;
+            write_u64(ctx, ptr + 8, value)
+        }
+        Err(err) => {
+            write_u8(ctx, ptr, 1)?;
+            store_stream_error(ctx, ptr + 8, err)
+        }
+    }
+}
+
+/// Stores a `result<list<u8>, stream-error>`.
+fn store_stream_bytes(
+    ctx: &mut Ctx<'_>,
+    ptr: u32,
+    result: Result<Vec<u8>, StreamError>,
+) -> Result<(), RuntimeError> {
+    match result {
+        Ok(data) => {
+            let list = lower_bytes(ctx, &data)?;
+            write_u8(ctx, ptr, 0)?;
+            store_list(ctx, ptr + 4, list)
+        }
+        Err(err) => {
+            write_u8(ctx, ptr, 1)?;
+            store_stream_error(ctx, ptr + 4, err)
+        }
+    }
+}
+
+/// Stores a `result<T, error-code>` whose `T` is stored by `store_ok` at
+/// `offset`, the alignment of `T`.
+fn store_fs_result<T>(
+    ctx: &mut Ctx<'_>,
+    ptr: u32,
+    offset: u32,
+    result: Result<T, ErrorCode>,
+    store_ok: impl FnOnce(&mut Ctx<'_>, u32, T) -> Result<(), RuntimeError>,
+) -> Result<(), RuntimeError> {
+    match result {
+        Ok(value) => {
+            write_u8(ctx, ptr, 0)?;
+            store_ok(ctx, ptr + offset, value)
+        }
+        Err(code) => {
+            write_u8(ctx, ptr, 1)?;
+            write_u8(ctx, ptr + offset, code as u8)
+        }
+    }
+}
+
+/// Stores a `result<own<T>, error-code>`.
+fn store_fs_handle(
+    ctx: &mut Ctx<'_>,
+    ptr: u32,
+    result: Result<u32, ErrorCode>,
+) -> Result<(), RuntimeError> {
+    store_fs_result(ctx, ptr, 4, result, |ctx, ptr, handle| {
+        write_u32(ctx, ptr, handle)
+    })
+}
+
+/// Stores a `result<_, error-code>`.
+fn store_fs_unit(
+    ctx: &mut Ctx<'_>,
+    ptr: u32,
+    result: Result<(), ErrorCode>,
+) -> Result<(), RuntimeError> {
+    store_fs_result(ctx, ptr, 1, result, |_, _, ()| Ok(()))
+}
+
+fn store_descriptor_stat(
+    ctx: &mut Ctx<'_>,
+    ptr: u32,
+    stat: DescriptorStat,
+) -> Result<(), RuntimeError> {
+    write_u8(ctx, ptr, stat.type_ as u8)?;
+    write_u64(ctx, ptr + 8, stat.link_count)?;
+    write_u64(ctx, ptr + 16, stat.size)?;
+    let timestamps = [
+        stat.data_access_timestamp,
+        stat.data_modification_timestamp,
+        stat.status_change_timestamp,
+    ];
+    for (ptr, timestamp) in (0..).map(|index| ptr + 24 + index * 24).zip(timestamps) {
+        match timestamp {
+            Some(datetime) => {
+                write_u8(ctx, ptr, 1)?;
+                write_u64(ctx, ptr + 8, datetime.seconds)?;
+                write_u32(ctx, ptr + 16, datetime.nanoseconds)?;
+            }
+            None => write_u8(ctx, ptr, 0)?,
+        }
+    }
+    Ok(())
+}
+
+fn descriptor_flags(flags: u32) -> DescriptorFlags {
+    DescriptorFlags {
+        read: flags & (1 << 0) != 0,
+        write: flags & (1 << 1) != 0,
+        mutate_directory: flags & (1 << 5) != 0,
+    }
+}
+
+fn lower_descriptor_flags(flags: DescriptorFlags) -> u8 {
+    u8::from(flags.read) | u8::from(flags.write) << 1 | u8::from(flags.mutate_directory) << 5
+}
+
+fn path_flags(flags: u32) -> PathFlags {
+    PathFlags {
+        symlink_follow: flags & 1 != 0,
+    }
+}
+
+fn open_flags(flags: u32) -> OpenFlags {
+    OpenFlags {
+        create: flags & (1 << 0) != 0,
+        directory: flags & (1 << 1) != 0,
+        exclusive: flags & (1 << 2) != 0,
+        truncate: flags & (1 << 3) != 0,
+    }
+}
+
+// wasi:cli
+
+fn get_environment(mut ctx: Ctx<'_>, retptr: u32) -> Result<(), RuntimeError> {
+    let environment = preview2(&mut ctx, |preview2| preview2.get_environment());
+    let list = lower_list(&mut ctx, &environment, 4, 16, |ctx, ptr, (key, value)| {
+        store_string(ctx, ptr, key)?;
+        store_string(ctx, ptr + 8, value)
+    })?;
+    store_list(&ctx, retptr, list)
+}
+
+fn get_arguments(mut ctx: Ctx<'_>, retptr: u32) -> Result<(), RuntimeError> {
+    let arguments = preview2(&mut ctx, |preview2| preview2.get_arguments());
+    let list = lower_list(&mut ctx, &arguments, 4, 8, |ctx, ptr, argument| {
+        store_string(ctx, ptr, argument)
+    })?;
+    store_list(&ctx, retptr, list)
+}
+
+fn initial_cwd(mut ctx: Ctx<'_>, retptr: u32) -> Result<(), RuntimeError> {
+    match preview2(&mut ctx, |preview2| preview2.initial_cwd()) {
+        Some(cwd) => {
+            write_u8(&ctx, retptr, 1)?;
+            store_string(&mut ctx, retptr + 4, &cwd)
+        }
+        None => write_u8(&ctx, retptr, 0),
+    }
+}
+
+fn exit(mut ctx: Ctx<'_>, status: u32) -> Result<(), RuntimeError> {
+    let status = if status == 0 { Ok(()) } else { Err(()) };
+    Err(trap(preview2(&mut ctx, |preview2| preview2.exit(status))))
+}
+
+fn get_stdin(mut ctx: Ctx<'_>) -> u32 {
+    preview2(&mut ctx, |preview2| preview2.get_stdin()).into()
+}
+
+fn get_stdout(mut ctx: Ctx<'_>) -> u32 {
+    preview2(&mut ctx, |preview2| preview2.get_stdout()).into()
+}
+
+fn get_stderr(mut ctx: Ctx<'_>) -> u32 {
+    preview2(&mut ctx, |preview2| preview2.get_stderr()).into()
+}
+
+// wasi:clocks
+
+fn wall_clock_now(mut ctx: Ctx<'_>, retptr: u32) -> Result<(), RuntimeError> {
+    let now = preview2(&mut ctx, |preview2| preview2.wall_clock_now()).map_err(trap)?;
+    write_u64(&ctx, retptr, now.seconds)?;
+    write_u32(&ctx, retptr + 8, now.nanoseconds)
+}
+
+fn wall_clock_resolution(mut ctx: Ctx<'_>, retptr: u32) -> Result<(), RuntimeError> {
+    let resolution =
+        preview2(&mut ctx, |preview2| preview2.wall_clock_resolution()).map_err(trap)?;
+    write_u64(&ctx, retptr, resolution.seconds)?;
+    write_u32(&ctx, retptr + 8, resolution.nanoseconds)
+}
+
+fn monotonic_clock_now(mut ctx: Ctx<'_>) -> Result<u64, RuntimeError> {
+    preview2(&mut ctx, |preview2| preview2.monotonic_clock_now()).map_err(trap)
+}
+
+fn monotonic_clock_resolution(mut ctx: Ctx<'_>) -> Result<u64, RuntimeError> {
+    preview2(&mut ctx, |preview2| preview2.monotonic_clock_resolution()).map_err(trap)
+}
+
+fn subscribe_instant(mut ctx: Ctx<'_>, when: u64) -> u32 {
+    preview2(&mut ctx, |preview2| preview2.subscribe_instant(when)).into()
+}
+
+fn subscribe_duration(mut ctx: Ctx<'_>, when: u64) -> Result<u32, RuntimeError> {
+    preview2(&mut ctx, |preview2| preview2.subscribe_duration(when))
+        .map(u32::from)
+        .map_err(trap)
+}
+
+// wasi:random
+
+fn get_random_bytes(mut ctx: Ctx<'_>, len: u64, retptr: u32) -> Result<(), RuntimeError> {
+    let data = preview2(&mut ctx, |preview2| preview2.get_random_bytes(len)).map_err(trap)?;
+    let list = lower_bytes(&mut ctx, &data)?;
+    store_list(&ctx, retptr, list)
+}
+
+fn get_random_u64(mut ctx: Ctx<'_>) -> Result<u64, RuntimeError> {
+    preview2(&mut ctx, |preview2| preview2.get_random_u64()).map_err(trap)
+}
+
+fn get_insecure_random_bytes(mut ctx: Ctx<'_>, len: u64, retptr: u32) -> Result<(), RuntimeError> {
+    let data =
+        preview2(&mut ctx, |preview2| preview2.get_insecure_random_bytes(len)).map_err(trap)?;
+    let list = lower_bytes(&mut ctx, &data)?;
+    store_list(&ctx, retptr, list)
+}
+
+fn get_insecure_random_u64(mut ctx: Ctx<'_>) -> Result<u64, RuntimeError> {
+    preview2(&mut ctx, |preview2| preview2.get_insecure_random_u64()).map_err(trap)
+}
+
+fn insecure_seed(mut ctx: Ctx<'_>, retptr: u32) -> Result<(), RuntimeError> {
+    let (low, high) = preview2(&mut ctx, |preview2| preview2.insecure_seed()).map_err(trap)?;
+    write_u64(&ctx, retptr, low)?;
+    write_u64(&ctx, retptr + 8, high)
+}
+
+// wasi:io
+
+fn drop_error(mut ctx: Ctx<'_>, error: u32) {
+    preview2(&mut ctx, |preview2| preview2.drop_error(error))
+}
+
+fn error_to_debug_string(mut ctx: Ctx<'_>, error: u32, retptr: u32) -> Result<(), RuntimeError> {
+    let errno = preview2(&mut ctx, |preview2| preview2.error(error)).unwrap_or(Errno::Badf);
+    store_string(&mut ctx, retptr, &errno.to_string())
+}
+
+fn drop_pollable(mut ctx: Ctx<'_>, pollable: u32) {
+    preview2(&mut ctx, |preview2| preview2.drop_pollable(pollable.into()))
+}
+
+fn pollable_ready(mut ctx: Ctx<'_>, pollable: u32) -> Result<u32, RuntimeError> {
+    preview2(&mut ctx, |preview2| preview2.ready(pollable.into()))
+        .map(u32::from)
+        .map_err(trap)
+}
+
+fn pollable_block(mut ctx: Ctx<'_>, pollable: u32) -> Result<(), RuntimeError> {
+    preview2(&mut ctx, |preview2| preview2.block(pollable.into())).map_err(trap)
+}
+
+fn poll(mut ctx: Ctx<'_>, ptr: u32, len: u32, retptr: u32) -> Result<(), RuntimeError> {
+    let bytes = len
+        .checked_mul(4)
+        .ok_or_else(|| RuntimeError::new("the list is too long"))?;
+    let pollables = read(&ctx, ptr, bytes)?
+        .chunks(4)
+        .map(|handle| Pollable::from(u32::from_le_bytes(handle.try_into().unwrap())))
+        .collect::<Vec<_>>();
+    let ready = preview2(&mut ctx, |preview2| preview2.poll(&pollables)).map_err(trap)?;
+    let list = lower_list(&mut ctx, &ready, 4, 4, |ctx, ptr, index| {
+        write_u32(ctx, ptr, *index)
+    })?;
+    store_list(&ctx, retptr, list)
+}
+
+fn drop_input_stream(mut ctx: Ctx<'_>, stream: u32) {
+    preview2(&mut ctx, |preview2| {
+        preview2.drop_input_stream(stream.into())
+    })
+}
+
+fn drop_output_stream(mut ctx: Ctx<'_>, stream: u32) {
+    preview2(&mut ctx, |preview2| {
+        preview2.drop_output_stream(stream.into())
+    })
+}
+
+fn input_stream_read(
+    mut ctx: Ctx<'_>,
+    stream: u32,
+    len: u64,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let stream = InputStream::from(stream);
+    let result = preview2(&mut ctx, |preview2| preview2.read(stream, len));
+    store_stream_bytes(&mut ctx, retptr, result)
+}
+
+fn input_stream_blocking_read(
+    mut ctx: Ctx<'_>,
+    stream: u32,
+    len: u64,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let stream = InputStream::from(stream);
+    let result = preview2(&mut ctx, |preview2| preview2.blocking_read(stream, len));
+    store_stream_bytes(&mut ctx, retptr, result)
+}
+
+fn input_stream_skip(
+    mut ctx: Ctx<'_>,
+    stream: u32,
+    len: u64,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let stream = InputStream::from(stream);
+    let result = preview2(&mut ctx, |preview2| preview2.skip(stream, len));
+    store_stream_u64(&mut ctx, retptr, result)
+}
+
+fn input_stream_subscribe(mut ctx: Ctx<'_>, stream: u32) -> Result<u32, RuntimeError> {
+    let stream = InputStream::from(stream);
+    preview2(&mut ctx, |preview2| preview2.subscribe_input_stream(stream))
+        .map(u32::from)
+        .map_err(|err| RuntimeError::new(format!("{:?}", err)))
+}
+
+fn output_stream_check_write(
+    mut ctx: Ctx<'_>,
+    stream: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let stream = OutputStream::from(stream);
+    let result = preview2(&mut ctx, |preview2| preview2.check_write(stream));
+    store_stream_u64(&mut ctx, retptr, result)
+}
+
+fn output_stream_write(
+    mut ctx: Ctx<'_>,
+    stream: u32,
+    ptr: u32,
+    len: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let stream = OutputStream::from(stream);
+    let contents = read(&ctx, ptr, len)?;
+    let result = preview2(&mut ctx, |preview2| preview2.write(stream, &contents));
+    store_stream_result(&mut ctx, retptr, result)
+}
+
+fn output_stream_blocking_write_and_flush(
+    mut ctx: Ctx<'_>,
+    stream: u32,
+    ptr: u32,
+    len: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let stream = OutputStream::from(stream);
+    let contents = read(&ctx, ptr, len)?;
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.blocking_write_and_flush(stream, &contents)
+    });
+    store_stream_result(&mut ctx, retptr, result)
+}
+
+fn output_stream_flush(mut ctx: Ctx<'_>, stream: u32, retptr: u32) -> Result<(), RuntimeError> {
+    let stream = OutputStream::from(stream);
+    let result = preview2(&mut ctx, |preview2| preview2.flush(stream));
+    store_stream_result(&mut ctx, retptr, result)
+}
+
+fn output_stream_subscribe(mut ctx: Ctx<'_>, stream: u32) -> Result<u32, RuntimeError> {
+    let stream = OutputStream::from(stream);
+    preview2(&mut ctx, |preview2| {
+        preview2.subscribe_output_stream(stream)
+    })
+    .map(u32::from)
+    .map_err(|err| RuntimeError::new(format!("{:?}", err)))
+}
+
+// wasi:filesystem
+
+fn get_directories(mut ctx: Ctx<'_>, retptr: u32) -> Result<(), RuntimeError> {
+    let directories = preview2(&mut ctx, |preview2| preview2.get_directories());
+    let list = lower_list(
+        &mut ctx,
+        &directories,
+        4,
+        12,
+        |ctx, ptr, (descriptor, path)| {
+            write_u32(ctx, ptr, u32::from(*descriptor))?;
+            store_string(ctx, ptr + 4, path)
+        },
+    )?;
+    store_list(&ctx, retptr, list)
+}
+
+fn drop_descriptor(mut ctx: Ctx<'_>, descriptor: u32) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    // A resource drop can't fail, the errors of `close` are lost like with
+    // the `close` of POSIX
+    let _ = preview2(&mut ctx, |preview2| preview2.drop_descriptor(descriptor));
+    Ok(())
+}
+
+fn descriptor_read_via_stream(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    offset: u64,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.read_via_stream(descriptor, offset)
+    });
+    store_fs_handle(&mut ctx, retptr, result.map(u32::from))
+}
+
+fn descriptor_write_via_stream(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    offset: u64,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.write_via_stream(descriptor, offset)
+    });
+    store_fs_handle(&mut ctx, retptr, result.map(u32::from))
+}
+
+fn descriptor_append_via_stream(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| preview2.append_via_stream(descriptor));
+    store_fs_handle(&mut ctx, retptr, result.map(u32::from))
+}
+
+fn descriptor_get_flags(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| preview2.get_flags(descriptor));
+    store_fs_result(&mut ctx, retptr, 1, result, |ctx, ptr, flags| {
+        write_u8(ctx, ptr, lower_descriptor_flags(flags))
+    })
+}
+
+fn descriptor_get_type(mut ctx: Ctx<'_>, descriptor: u32, retptr: u32) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| preview2.get_type(descriptor));
+    store_fs_result(&mut ctx, retptr, 1, result, |ctx, ptr, type_| {
+        write_u8(ctx, ptr, type_ as u8)
+    })
+}
+
+fn descriptor_stat(mut ctx: Ctx<'_>, descriptor: u32, retptr: u32) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| preview2.stat(descriptor));
+    store_fs_result(&mut ctx, retptr, 8, result, store_descriptor_stat)
+}
+
+fn descriptor_stat_at(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    flags: u32,
+    path: u32,
+    path_len: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let path = read_string(&ctx, path, path_len)?;
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.stat_at(descriptor, path_flags(flags), &path)
+    });
+    store_fs_result(&mut ctx, retptr, 8, result, store_descriptor_stat)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn descriptor_open_at(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    path_flags_: u32,
+    path: u32,
+    path_len: u32,
+    open_flags_: u32,
+    flags: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let path = read_string(&ctx, path, path_len)?;
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.open_at(
+            descriptor,
+            path_flags(path_flags_),
+            &path,
+            open_flags(open_flags_),
+            descriptor_flags(flags),
+        )
+    });
+    store_fs_handle(&mut ctx, retptr, result.map(u32::from))
+}
+
+fn descriptor_read_directory(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| preview2.read_directory(descriptor));
+    store_fs_handle(&mut ctx, retptr, result.map(u32::from))
+}
+
+fn descriptor_create_directory_at(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    path: u32,
+    path_len: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let path = read_string(&ctx, path, path_len)?;
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.create_directory_at(descriptor, &path)
+    });
+    store_fs_unit(&mut ctx, retptr, result)
+}
+
+fn descriptor_remove_directory_at(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    path: u32,
+    path_len: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let path = read_string(&ctx, path, path_len)?;
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.remove_directory_at(descriptor, &path)
+    });
+    store_fs_unit(&mut ctx, retptr, result)
+}
+
+fn descriptor_unlink_file_at(
+    mut ctx: Ctx<'_>,
+    descriptor: u32,
+    path: u32,
+    path_len: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let path = read_string(&ctx, path, path_len)?;
+    let result = preview2(&mut ctx, |preview2| {
+        preview2.unlink_file_at(descriptor, &path)
+    });
+    store_fs_unit(&mut ctx, retptr, result)
+}
+
+fn descriptor_sync(mut ctx: Ctx<'_>, descriptor: u32, retptr: u32) -> Result<(), RuntimeError> {
+    let descriptor = Descriptor::from(descriptor);
+    let result = preview2(&mut ctx, |preview2| preview2.sync(descriptor));
+    store_fs_unit(&mut ctx, retptr, result)
+}
+
+fn drop_directory_entry_stream(mut ctx: Ctx<'_>, stream: u32) {
+    let stream = DirectoryEntryStream::from(stream);
+    preview2(&mut ctx, |preview2| {
+        preview2.drop_directory_entry_stream(stream)
+    })
+}
+
+/// Stores a `result<option<directory-entry>, error-code>`.
+fn directory_entry_stream_read_directory_entry(
+    mut ctx: Ctx<'_>,
+    stream: u32,
+    retptr: u32,
+) -> Result<(), RuntimeError> {
+    let stream = DirectoryEntryStream::from(stream);
+    let result = preview2(&mut ctx, |preview2| preview2.read_directory_entry(stream));
+    store_fs_result(&mut ctx, retptr, 4, result, |ctx, ptr, entry| match entry {
+        Some(entry) => {
+            write_u8(ctx, ptr, 1)?;
+            write_u8(ctx, ptr + 4, entry.type_ as u8)?;
+            store_string(ctx, ptr + 8, &entry.name)
+        }
+        None => write_u8(ctx, ptr, 0),
+    })
+}
+
+/// Stores an `option<error-code>`.
+fn filesystem_error_code(mut ctx: Ctx<'_>, error: u32, retptr: u32) -> Result<(), RuntimeError> {
+    match preview2(&mut ctx, |preview2| preview2.error(error)) {
+        Some(errno) => {
+            write_u8(&ctx, retptr, 1)?;
+            write_u8(&ctx, retptr + 1, ErrorCode::from(errno) as u8)
+        }
+        None => write_u8(&ctx, retptr, 0),
+    }
+}
+
+/// The imports of all the Preview 2 interfaces provided to core modules.
+pub(crate) fn preview2_imports(
+    mut store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+) -> Imports {
+    let cli_environment = namespace! {
+        "get-environment" => Function::new_typed_with_env(&mut store, env, get_environment),
+        "get-arguments" => Function::new_typed_with_env(&mut store, env, get_arguments),
+        "initial-cwd" => Function::new_typed_with_env(&mut store, env, initial_cwd),
+    };
+    let cli_exit = namespace! {
+        "exit" => Function::new_typed_with_env(&mut store, env, exit),
+    };
+    let cli_stdin = namespace! {
+        "get-stdin" => Function::new_typed_with_env(&mut store, env, get_stdin),
+    };
+    let cli_stdout = namespace! {
+        "get-stdout" => Function::new_typed_with_env(&mut store, env, get_stdout),
+    };
+    let cli_stderr = namespace! {
+        "get-stderr" => Function::new_typed_with_env(&mut store, env, get_stderr),
+    };
+    let clocks_wall_clock = namespace! {
+        "now" => Function::new_typed_with_env(&mut store, env, wall_clock_now),
+        "resolution" => Function::new_typed_with_env(&mut store, env, wall_clock_resolution),
+    };
+    let clocks_monotonic_clock = namespace! {
+        "now" => Function::new_typed_with_env(&mut store, env, monotonic_clock_now),
+        "resolution" => Function::new_typed_with_env(&mut store, env, monotonic_clock_resolution),
+        "subscribe-instant" => Function::new_typed_with_env(&mut store, env, subscribe_instant),
+        "subscribe-duration" => Function::new_typed_with_env(&mut store, env, subscribe_duration),
+    };
+    let random_random = namespace! {
+        "get-random-bytes" => Function::new_typed_with_env(&mut store, env, get_random_bytes),
+        "get-random-u64" => Function::new_typed_with_env(&mut store, env, get_random_u64),
+    };
+    let random_insecure = namespace! {
+        "get-insecure-random-bytes" => Function::new_typed_with_env(&mut store, env, get_insecure_random_bytes),
+        "get-insecure-random-u64" => Function::new_typed_with_env(&mut store, env, get_insecure_random_u64),
+    };
+    let random_insecure_seed = namespace! {
+        "insecure-seed" => Function::new_typed_with_env(&mut store, env, insecure_seed),
+    };
+    let io_error = namespace! {
+        "[resource-drop]error" => Function::new_typed_with_env(&mut store, env, drop_error),
+        "[method]error.to-debug-string" => Function::new_typed_with_env(&mut store, env, error_to_debug_string),
+    };
+    let io_poll = namespace! {
+        "[resource-drop]pollable" => Function::new_typed_with_env(&mut store, env, drop_pollable),
+        "[method]pollable.ready" => Function::new_typed_with_env(&mut store, env, pollable_ready),
+        "[method]pollable.block" => Function::new_typed_with_env(&mut store, env, pollable_block),
+        "poll" => Function::new_typed_with_env(&mut store, env, poll),
+    };
+    let io_streams = namespace! {
+        "[resource-drop]input-stream" => Function::new_typed_with_env(&mut store, env, drop_input_stream),
+        "[resource-drop]output-stream" => Function::new_typed_with_env(&mut store, env, drop_output_stream),
+        "[method]input-stream.read" => Function::new_typed_with_env(&mut store, env, input_stream_read),
+        "[method]input-stream.blocking-read" => Function::new_typed_with_env(&mut store, env, input_stream_blocking_read),
+        "[method]input-stream.skip" => Function::new_typed_with_env(&mut store, env, input_stream_skip),
+        "[method]input-stream.blocking-skip" => Function::new_typed_with_env(&mut store, env, input_stream_skip),
+        "[method]input-stream.subscribe" => Function::new_typed_with_env(&mut store, env, input_stream_subscribe),
+        "[method]output-stream.check-write" => Function::new_typed_with_env(&mut store, env, output_stream_check_write),
+        "[method]output-stream.write" => Function::new_typed_with_env(&mut store, env, output_stream_write),
+        "[method]output-stream.blocking-write-and-flush" => Function::new_typed_with_env(&mut store, env, output_stream_blocking_write_and_flush),
+        "[method]output-stream.flush" => Function::new_typed_with_env(&mut store, env, output_stream_flush),
+        "[method]output-stream.blocking-flush" => Function::new_typed_with_env(&mut store, env, output_stream_flush),
+        "[method]output-stream.subscribe" => Function::new_typed_with_env(&mut store, env, output_stream_subscribe),
+    };
+    let filesystem_preopens = namespace! {
+        "get-directories" => Function::new_typed_with_env(&mut store, env, get_directories),
+    };
+    let filesystem_types = namespace! {
+        "[resource-drop]descriptor" => Function::new_typed_with_env(&mut store, env, drop_descriptor),
+        "[resource-drop]directory-entry-stream" => Function::new_typed_with_env(&mut store, env, drop_directory_entry_stream),
+        "[method]descriptor.read-via-stream" => Function::new_typed_with_env(&mut store, env, descriptor_read_via_stream),
+        "[method]descriptor.write-via-stream" => Function::new_typed_with_env(&mut store, env, descriptor_write_via_stream),
+        "[method]descriptor.append-via-stream" => Function::new_typed_with_env(&mut store, env, descriptor_append_via_stream),
+        "[method]descriptor.get-flags" => Function::new_typed_with_env(&mut store, env, descriptor_get_flags),
+        "[method]descriptor.get-type" => Function::new_typed_with_env(&mut store, env, descriptor_get_type),
+        "[method]descriptor.stat" => Function::new_typed_with_env(&mut store, env, descriptor_stat),
+        "[method]descriptor.stat-at" => Function::new_typed_with_env(&mut store, env, descriptor_stat_at),
+        "[method]descriptor.open-at" => Function::new_typed_with_env(&mut store, env, descriptor_open_at),
+        "[method]descriptor.read-directory" => Function::new_typed_with_env(&mut store, env, descriptor_read_directory),
+        "[method]descriptor.create-directory-at" => Function::new_typed_with_env(&mut store, env, descriptor_create_directory_at),
+        "[method]descriptor.remove-directory-at" => Function::new_typed_with_env(&mut store, env, descriptor_remove_directory_at),
+        "[method]descriptor.unlink-file-at" => Function::new_typed_with_env(&mut store, env, descriptor_unlink_file_at),
+        "[method]descriptor.sync" => Function::new_typed_with_env(&mut store, env, descriptor_sync),
+        "[method]directory-entry-stream.read-directory-entry" => Function::new_typed_with_env(&mut store, env, directory_entry_stream_read_directory_entry),
+        "filesystem-error-code" => Function::new_typed_with_env(&mut store, env, filesystem_error_code),
+    };
+
+    imports! {
+        "wasi:cli/environment@0.2.0" => cli_environment,
+        "wasi:cli/exit@0.2.0" => cli_exit,
+        "wasi:cli/stdin@0.2.0" => cli_stdin,
+        "wasi:cli/stdout@0.2.0" => cli_stdout,
+        "wasi:cli/stderr@0.2.0" => cli_stderr,
+        "wasi:clocks/wall-clock@0.2.0" => clocks_wall_clock,
+        "wasi:clocks/monotonic-clock@0.2.0" => clocks_monotonic_clock,
+        "wasi:random/random@0.2.0" => random_random,
+        "wasi:random/insecure@0.2.0" => random_insecure,
+        "wasi:random/insecure-seed@0.2.0" => random_insecure_seed,
+        "wasi:io/error@0.2.0" => io_error,
+        "wasi:io/poll@0.2.0" => io_poll,
+        "wasi:io/streams@0.2.0" => io_streams,
+        "wasi:filesystem/preopens@0.2.0" => filesystem_preopens,
+        "wasi:filesystem/types@0.2.0" => filesystem_types,
+    }
+}
@@ -0,0 +1,466 @@
+//! `wasi:io/streams` and `wasi:io/poll`.
+
+use super::{FdStream, InputStream, OutputStream, Pollable, PollableKind, Resource, WasiPreview2};
+use crate::state::{
+    inode_readiness, Kind, PollEvent, PollEventBuilder, PollEventSet, Reactor, WasiState,
+};
+use crate::syscalls::types::wasi::{Errno, Fd as WasiFd, Rights, Timestamp};
+use crate::utils::map_io_err;
+use bytes::Bytes;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::DerefMut;
+use std::time::Duration;
+use wasmer::Memory32;
+
+/// The number of bytes an output stream accepts in a single write.
+const WRITE_BUDGET: u64 = 1024 * 1024;
+
+/// The most bytes an input stream returns from a single read, whatever
+/// the guest asks for.
+const READ_BUDGET: u64 = 1024 * 1024;
+
+/// An error of a stream operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamError {
+    /// The last operation failed, the stream is unusable.
+    LastOperationFailed(Errno),
+    /// The stream is closed, for input streams this is the end of the data.
+    Closed,
+}
+
+impl From<Errno> for StreamError {
+    fn from(errno: Errno) -> Self {
+        match errno {
+            Errno::Pipe => Self::Closed,
+            errno => Self::LastOperationFailed(errno),
+        }
+    }
+}
+
+/// Reads up to `len` bytes from `fd`, at `position` if given or at the
+/// current position of the file otherwise. At most [`READ_BUDGET`] bytes
+/// are read at once.
+pub(crate) fn fd_read(
+    state: &WasiState,
+    fd: WasiFd,
+    position: Option<u64>,
+    len: u64,
+) -> Result<Vec<u8>, Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !fd_entry.rights.contains(Rights::FD_READ) {
+        return Err(Errno::Access);
+    }
+    let mut buf = vec![0; len.min(READ_BUDGET) as usize];
+
+    let inodes = state.inodes.read().unwrap();
+    let mut guard = inodes.arena[fd_entry.inode].write();
+    let read = match guard.deref_mut() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => {
+            if let Some(position) = position {
+                handle.seek(SeekFrom::Start(position)).map_err(map_io_err)?;
+            }
+            handle.read(&mut buf).map_err(map_io_err)?
+        }
+        Kind::File { handle: None, .. } => return Err(Errno::Badf),
+        Kind::Pipe { pipe } => pipe.read(&mut buf).map_err(map_io_err)?,
+        Kind::Socket { socket } => socket.read(&mut buf).map_err(map_io_err)?,
+        Kind::Buffer { buffer } => {
+            let position = position.unwrap_or(fd_entry.offset) as usize;
+            let data = buffer.get(position..).unwrap_or_default();
+            let read = data.len().min(buf.len());
+            buf[..read].copy_from_slice(&data[..read]);
+            read
+        }
+        Kind::Dir { .. } | Kind::Root { .. } => return Err(Errno::Isdir),
+        _ => return Err(Errno::Notsup),
+    };
+    buf.truncate(read);
+    Ok(buf)
+}
+
+/// Writes `buf` to `fd`, at `position` if given, at the end of the file if
+/// `append` is set, or at the current position of the file otherwise.
+pub(crate) fn fd_write(
+    state: &WasiState,
+    fd: WasiFd,
+    position: Option<u64>,
+    append: bool,
+    buf: &[u8],
+) -> Result<usize, Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !fd_entry.rights.contains(Rights::FD_WRITE) {
+        return Err(Errno::Access);
+    }
+
+    let inodes = state.inodes.read().unwrap();
+    let mut guard = inodes.arena[fd_entry.inode].write();
+    match guard.deref_mut() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => {
+            if append {
+                handle.seek(SeekFrom::End(0)).map_err(map_io_err)?;
+            } else if let Some(position) = position {
+                handle.seek(SeekFrom::Start(position)).map_err(map_io_err)?;
+            }
+            handle.write_all(buf).map_err(map_io_err)?;
+            inodes.arena[fd_entry.inode].stat.write().unwrap().st_size = handle.size();
+        }
+        Kind::File { handle: None, .. } => return Err(Errno::Badf),
+        Kind::Pipe { pipe } => pipe.write_all(buf).map_err(map_io_err)?,
+        Kind::Socket { socket } => {
+            socket.send_bytes::<Memory32>(Bytes::copy_from_slice(buf))?;
+        }
+        Kind::Buffer { buffer } => {
+            let position = if append {
+                buffer.len()
+            } else {
+                position.unwrap_or(fd_entry.offset) as usize
+            };
+            if buffer.len() < position + buf.len() {
+                buffer.resize(position + buf.len(), 0);
+            }
+            buffer[position..position + buf.len()].copy_from_slice(buf);
+        }
+        Kind::Dir { .. } | Kind::Root { .. } => return Err(Errno::Isdir),
+        _ => return Err(Errno::Notsup),
+    }
+    Ok(buf.len())
+}
+
+/// Flushes the data written to `fd`.
+///
+/// Unlike `fd_datasync`, this only needs the right to write to `fd`, and
+/// it also flushes pipes and sockets.
+pub(crate) fn fd_flush(state: &WasiState, fd: WasiFd) -> Result<(), Errno> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !fd_entry.rights.contains(Rights::FD_WRITE) {
+        return Err(Errno::Access);
+    }
+
+    let inodes = state.inodes.read().unwrap();
+    let mut guard = inodes.arena[fd_entry.inode].write();
+    match guard.deref_mut() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => handle.flush().map_err(map_io_err),
+        Kind::File { handle: None, .. } => Err(Errno::Badf),
+        Kind::Pipe { pipe } => pipe.flush().map_err(map_io_err),
+        Kind::Socket { socket } => socket.flush(),
+        Kind::Buffer { .. } => Ok(()),
+        Kind::Dir { .. } | Kind::Root { .. } => Err(Errno::Isdir),
+        _ => Err(Errno::Notsup),
+    }
+}
+
+impl WasiPreview2 {
+    pub(crate) fn push_input_stream(&mut self, stream: FdStream) -> InputStream {
+        self.table.push(Resource::InputStream(stream)).into()
+    }
+
+    pub(crate) fn push_output_stream(&mut self, stream: FdStream) -> OutputStream {
+        self.table.push(Resource::OutputStream(stream)).into()
+    }
+
+    pub(crate) fn push_pollable(&mut self, pollable: PollableKind) -> Pollable {
+        self.table.push(Resource::Pollable(pollable)).into()
+    }
+
+    fn input_stream(&mut self, stream: InputStream) -> Result<&mut FdStream, StreamError> {
+        match self.table.get_mut(stream.into()) {
+            Some(Resource::InputStream(stream)) => Ok(stream),
+            _ => Err(StreamError::LastOperationFailed(Errno::Badf)),
+        }
+    }
+
+    fn output_stream(&mut self, stream: OutputStream) -> Result<&mut FdStream, StreamError> {
+        match self.table.get_mut(stream.into()) {
+            Some(Resource::OutputStream(stream)) => Ok(stream),
+            _ => Err(StreamError::LastOperationFailed(Errno::Badf)),
+        }
+    }
+
+    /// `input-stream.read`: reads up to `len` bytes from the stream.
+    ///
+    /// Fails with [`StreamError::Closed`] at the end of the data.
+    pub fn read(&mut self, stream: InputStream, len: u64) -> Result<Vec<u8>, StreamError> {
+        let state = self.state.clone();
+        let stream = self.input_stream(stream)?;
+        let data = fd_read(&state, stream.fd, stream.position, len)?;
+        if data.is_empty() && len > 0 {
+            return Err(StreamError::Closed);
+        }
+        if let Some(position) = stream.position.as_mut() {
+            *position += data.len() as u64;
+        }
+        Ok(data)
+    }
+
+    /// `input-stream.blocking-read`, the same as [`read`](Self::read) as
+    /// the reads of the underlying files block.
+    pub fn blocking_read(&mut self, stream: InputStream, len: u64) -> Result<Vec<u8>, StreamError> {
+        self.read(stream, len)
+    }
+
+    /// `input-stream.skip`: skips up to `len` bytes of the stream.
+    pub fn skip(&mut self, stream: InputStream, len: u64) -> Result<u64, StreamError> {
+        self.read(stream, len).map(|data| data.len() as u64)
+    }
+
+    /// `input-stream.subscribe`: ready once the stream has data to read,
+    /// or has reached its end.
+    pub fn subscribe_input_stream(&mut self, stream: InputStream) -> Result<Pollable, StreamError> {
+        let fd = self.input_stream(stream)?.fd;
+        Ok(self.push_pollable(PollableKind::Stream {
+            fd,
+            events: PollEventBuilder::new().add(PollEvent::PollIn).build(),
+        }))
+    }
+
+    /// `output-stream.check-write`: the number of bytes the next write
+    /// may contain.
+    pub fn check_write(&mut self, stream: OutputStream) -> Result<u64, StreamError> {
+        self.output_stream(stream)?;
+        Ok(WRITE_BUDGET)
+    }
+
+    /// `output-stream.write`
+    pub fn write(&mut self, stream: OutputStream, contents: &[u8]) -> Result<(), StreamError> {
+        if contents.len() as u64 > WRITE_BUDGET {
+            return Err(StreamError::LastOperationFailed(Errno::Fbig));
+        }
+        let state = self.state.clone();
+        let stream = self.output_stream(stream)?;
+        let written = fd_write(&state, stream.fd, stream.position, stream.append, contents)?;
+        if let Some(position) = stream.position.as_mut() {
+            *position += written as u64;
+        }
+        Ok(())
+    }
+
+    /// `output-stream.flush`
+    pub fn flush(&mut self, stream: OutputStream) -> Result<(), StreamError> {
+        let state = self.state.clone();
+        let fd = self.output_stream(stream)?.fd;
+        fd_flush(&state, fd).map_err(StreamError::from)
+    }
+
+    /// `output-stream.blocking-write-and-flush`
+    pub fn blocking_write_and_flush(
+        &mut self,
+        stream: OutputStream,
+        contents: &[u8],
+    ) -> Result<(), StreamError> {
+        for chunk in contents.chunks(WRITE_BUDGET as usize) {
+            self.write(stream, chunk)?;
+        }
+        self.flush(stream)
+    }
+
+    /// `output-stream.subscribe`: ready once the stream accepts a write.
+    pub fn subscribe_output_stream(
+        &mut self,
+        stream: OutputStream,
+    ) -> Result<Pollable, StreamError> {
+        let fd = self.output_stream(stream)?.fd;
+        Ok(self.push_pollable(PollableKind::Stream {
+            fd,
+            events: PollEventBuilder::new().add(PollEvent::PollOut).build(),
+        }))
+    }
+
+    /// Drops an `input-stream`.
+    pub fn drop_input_stream(&mut self, stream: InputStream) {
+        self.table.remove(stream.into());
+    }
+
+    /// Drops an `output-stream`.
+    pub fn drop_output_stream(&mut self, stream: OutputStream) {
+        self.table.remove(stream.into());
+    }
+
+    /// A `wasi:io/error.error` for the guests importing the interfaces,
+    /// which only see the errors of the streams as resources.
+    pub(crate) fn push_error(&mut self, errno: Errno) -> u32 {
+        self.table.push(Resource::Error(errno))
+    }
+
+    pub(crate) fn error(&self, error: u32) -> Option<Errno> {
+        match self.table.get(error) {
+            Some(Resource::Error(errno)) => Some(*errno),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn drop_error(&mut self, error: u32) {
+        self.table.remove(error);
+    }
+
+    /// Drops a `pollable`.
+    pub fn drop_pollable(&mut self, pollable: Pollable) {
+        self.table.remove(pollable.into());
+    }
+
+    fn pollable(&self, pollable: Pollable) -> Result<&PollableKind, Errno> {
+        match self.table.get(pollable.into()) {
+            Some(Resource::Pollable(kind)) => Ok(kind),
+            _ => Err(Errno::Badf),
+        }
+    }
+
+    /// Whether the events of a stream can be waited for are ready. The
+    /// streams failing to tell are ready, so that the guest finds out
+    /// about the error with the next operation.
+    fn stream_ready(&self, fd: WasiFd, events: PollEventSet) -> bool {
+        let inode = match self.state.fs.get_fd(fd) {
+            Ok(fd_entry) => fd_entry.inode,
+            Err(_) => return true,
+        };
+        let inodes = self.state.inodes.read().unwrap();
+        match inode_readiness(&inodes, inode, events) {
+            Ok((seen, _)) => seen != 0,
+            Err(_) => true,
+        }
+    }
+
+    fn is_ready(&self, pollable: &PollableKind, now: Timestamp) -> bool {
+        match pollable {
+            PollableKind::Deadline(deadline) => *deadline <= now,
+            PollableKind::Stream { fd, events } => self.stream_ready(*fd, *events),
+        }
+    }
+
+    /// A reactor waiting on the streams among `pollables` that have a host
+    /// file descriptor or a waker, the tokens being the indices of the
+    /// pollables. Also returns whether some streams can only be checked
+    /// again from time to time.
+    fn stream_reactor(&self, pollables: &[&PollableKind]) -> Result<(Reactor, bool), Errno> {
+        let mut reactor = Reactor::new()?;
+        let mut needs_polling = false;
+
+        let inodes = self.state.inodes.read().unwrap();
+        for (token, pollable) in pollables.iter().enumerate() {
+            let (fd, events) = match pollable {
+                PollableKind::Stream { fd, events } => (*fd, *events),
+                _ => continue,
+            };
+            let inode = self.state.fs.get_fd(fd)?.inode;
+            let mut guard = inodes.arena[inode].write();
+            let host_fd = match guard.deref_mut() {
+                Kind::File {
+                    handle: Some(handle),
+                    ..
+                } => handle.get_fd(),
+                Kind::Socket { socket } => socket.poll_fd(),
+                _ => None,
+            };
+            let registered = match host_fd {
+                Some(host_fd) => reactor.add_fd(host_fd, token, events)?,
+                None => false,
+            };
+            if !registered {
+                let wakes = match guard.deref_mut() {
+                    Kind::Socket { socket } => socket.set_waker(reactor.waker()),
+                    _ => false,
+                };
+                needs_polling |= !wakes;
+            }
+        }
+
+        Ok((reactor, needs_polling))
+    }
+
+    /// `pollable.ready`
+    pub fn ready(&self, pollable: Pollable) -> Result<bool, Errno> {
+        let pollable = self.pollable(pollable)?;
+        let now = match pollable {
+            PollableKind::Deadline(_) => self.monotonic_clock_now()?,
+            _ => 0,
+        };
+        Ok(self.is_ready(pollable, now))
+    }
+
+    /// `pollable.block`
+    pub fn block(&self, pollable: Pollable) -> Result<(), Errno> {
+        self.poll(&[pollable]).map(|_| ())
+    }
+
+    /// `poll`: waits until at least one of the pollables is ready and
+    /// returns the indices of the ready ones.
+    pub fn poll(&self, pollables: &[Pollable]) -> Result<Vec<u32>, Errno> {
+        if pollables.is_empty() {
+            return Err(Errno::Inval);
+        }
+        let pollables = pollables
+            .iter()
+            .map(|pollable| self.pollable(*pollable))
+            .collect::<Result<Vec<_>, _>>()?;
+        let first_deadline = pollables
+            .iter()
+            .filter_map(|pollable| match pollable {
+                PollableKind::Deadline(deadline) => Some(*deadline),
+                _ => None,
+            })
+            .min();
+
+        let mut seen = vec![false; pollables.len()];
+        // The instant the last wait lasted until, for the clocks that
+        // don't move on their own
+        let mut waited_until = 0;
+        let check = |seen: &[bool], waited_until: Timestamp| -> Result<_, Errno> {
+            let now = match first_deadline {
+                Some(_) => self.monotonic_clock_now()?.max(waited_until),
+                None => 0,
+            };
+            let ready = pollables
+                .iter()
+                .enumerate()
+                .filter(|(index, pollable)| seen[*index] || self.is_ready(pollable, now))
+                .map(|(index, _)| index as u32)
+                .collect::<Vec<_>>();
+            Ok((ready, now))
+        };
+
+        let (mut ready, mut now) = check(&seen, waited_until)?;
+        if !ready.is_empty() {
+            return Ok(ready);
+        }
+
+        // The reactor is woken up by the streams it waits on, the others
+        // are checked more and more rarely
+        let (mut reactor, needs_polling) = self.stream_reactor(&pollables)?;
+        let mut backoff = Duration::from_millis(1);
+        while ready.is_empty() {
+            let mut timeout = if needs_polling {
+                let slice = backoff;
+                backoff = (backoff * 2).min(Duration::from_millis(50));
+                Some(slice)
+            } else {
+                None
+            };
+            let mut until_deadline = false;
+            if let Some(deadline) = first_deadline {
+                let until = Duration::from_nanos(deadline - now);
+                if timeout.map_or(true, |timeout| until <= timeout) {
+                    timeout = Some(until);
+                    until_deadline = true;
+                }
+            }
+
+            let events = reactor.wait(timeout)?;
+            if events.is_empty() && until_deadline {
+                waited_until = first_deadline.unwrap_or(now);
+            }
+            for (token, _) in events {
+                seen[token] = true;
+            }
+            (ready, now) = check(&seen, waited_until)?;
+        }
+        Ok(ready)
+    }
+}
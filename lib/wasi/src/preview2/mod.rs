@@ -0,0 +1,215 @@
+//! Host implementation of the WASI Preview 2 interfaces.
+//!
+//! Preview 2 is specified as a set of component model worlds rather than
+//! as core module imports. [`WasiPreview2`] implements the host side of
+//! the `wasi:io`, `wasi:filesystem`, `wasi:sockets`, `wasi:clocks`,
+//! `wasi:random` and `wasi:cli` interfaces on top of the state of a
+//! [`WasiEnv`]: the descriptors and the sockets are regular
+//! [`WasiFs`](crate::WasiFs) file descriptors, so the preopens, rights and
+//! `wasmer_vfs::FileSystem` backing configured with the
+//! [`WasiStateBuilder`](crate::WasiStateBuilder) serve both ABIs, and the
+//! sockets use the networking implementation of the runtime, like the
+//! WASIX and WasmEdge socket functions.
+//!
+//! Resources are referred to by the `u32` handles they are lowered to by
+//! the canonical ABI, and are released with the matching `drop_*` method.
+//!
+//! Wasmer doesn't run components, but core modules can import the
+//! `wasi:io`, `wasi:filesystem`, `wasi:clocks`, `wasi:random` and
+//! `wasi:cli` interfaces under the names the Preview 1 component adapter
+//! uses, like `wasi:io/streams@0.2.0`, lowered with the canonical ABI.
+//! [`wasi_import_preview2`] adds the ones a module imports. The sockets,
+//! and the few functions of the other interfaces it doesn't list, are only
+//! available to the embedders calling [`WasiPreview2`] from their own
+//! bindings.
+//!
+//! [`wasi_import_preview2`]: crate::wasi_import_preview2
+
+mod cli;
+mod clocks;
+mod filesystem;
+mod imports;
+mod io;
+mod random;
+mod sockets;
+
+pub use self::clocks::Datetime;
+pub use self::filesystem::{
+    DescriptorFlags, DescriptorStat, DescriptorType, DirectoryEntry, ErrorCode, OpenFlags,
+    PathFlags,
+};
+pub(crate) use self::imports::preview2_imports;
+pub use self::io::StreamError;
+pub use self::sockets::{Datagram, IpAddressFamily, NetworkErrorCode};
+
+use crate::state::{PollEventSet, WasiState};
+use crate::syscalls::types::wasi::Fd as WasiFd;
+use crate::{WasiClock, WasiEnv, WasiRandom, WasiRuntimeImplementation};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+use wasmer_wasi_types::wasi::{Errno, Timestamp};
+
+macro_rules! handle {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(u32);
+
+        impl From<u32> for $name {
+            fn from(handle: u32) -> Self {
+                Self(handle)
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(handle: $name) -> u32 {
+                handle.0
+            }
+        }
+    };
+}
+
+handle!(
+    /// A `wasi:io/streams.input-stream` resource.
+    InputStream
+);
+handle!(
+    /// A `wasi:io/streams.output-stream` resource.
+    OutputStream
+);
+handle!(
+    /// A `wasi:io/poll.pollable` resource.
+    Pollable
+);
+handle!(
+    /// A `wasi:filesystem/types.descriptor` resource.
+    Descriptor
+);
+handle!(
+    /// A `wasi:filesystem/types.directory-entry-stream` resource.
+    DirectoryEntryStream
+);
+handle!(
+    /// A `wasi:sockets/network.network` resource.
+    Network
+);
+handle!(
+    /// A `wasi:sockets/tcp.tcp-socket` resource.
+    TcpSocket
+);
+handle!(
+    /// A `wasi:sockets/udp.udp-socket` resource.
+    UdpSocket
+);
+handle!(
+    /// A `wasi:sockets/ip-name-lookup.resolve-address-stream` resource.
+    ResolveAddressStream
+);
+
+/// A stream reading from or writing to a WASI file descriptor.
+#[derive(Debug)]
+pub(crate) struct FdStream {
+    fd: WasiFd,
+    /// The position of the stream in the file, `None` for streams that
+    /// aren't seekable (like stdio) or that append to the file.
+    position: Option<u64>,
+    append: bool,
+}
+
+#[derive(Debug)]
+pub(crate) enum PollableKind {
+    /// Ready once the monotonic clock reaches the given instant.
+    Deadline(Timestamp),
+    /// Ready once the `events` of a stream are, as seen by `poll_oneoff`.
+    Stream { fd: WasiFd, events: PollEventSet },
+}
+
+#[derive(Debug)]
+pub(crate) struct OpenDescriptor {
+    fd: WasiFd,
+    /// Whether the descriptor owns `fd` and closes it when dropped.
+    owned: bool,
+}
+
+#[derive(Debug)]
+enum Resource {
+    InputStream(FdStream),
+    OutputStream(FdStream),
+    Pollable(PollableKind),
+    Descriptor(OpenDescriptor),
+    DirectoryEntryStream(VecDeque<DirectoryEntry>),
+    Network,
+    Socket(sockets::OpenSocket),
+    ResolveAddressStream(VecDeque<IpAddr>),
+    /// A `wasi:io/error.error`, the cause of a failed stream operation.
+    Error(Errno),
+}
+
+/// The handles of the resources owned by the guest.
+#[derive(Debug, Default)]
+struct ResourceTable {
+    resources: HashMap<u32, Resource>,
+    next_handle: u32,
+}
+
+impl ResourceTable {
+    fn push(&mut self, resource: Resource) -> u32 {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.resources.insert(handle, resource);
+        handle
+    }
+
+    fn get(&self, handle: u32) -> Option<&Resource> {
+        self.resources.get(&handle)
+    }
+
+    fn get_mut(&mut self, handle: u32) -> Option<&mut Resource> {
+        self.resources.get_mut(&handle)
+    }
+
+    fn remove(&mut self, handle: u32) -> Option<Resource> {
+        self.resources.remove(&handle)
+    }
+}
+
+/// The host state of the WASI Preview 2 interfaces of a guest.
+pub struct WasiPreview2 {
+    state: Arc<WasiState>,
+    runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    clock: Option<Arc<dyn WasiClock>>,
+    random: Option<Arc<dyn WasiRandom>>,
+    table: ResourceTable,
+}
+
+impl fmt::Debug for WasiPreview2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasiPreview2")
+            .field("clock", &self.clock)
+            .field("random", &self.random)
+            .field("table", &self.table)
+            .finish()
+    }
+}
+
+impl WasiPreview2 {
+    /// Creates the Preview 2 host state sharing the file system, the
+    /// environment, the networking, the clocks and the random source of
+    /// `env`.
+    pub fn new(env: &WasiEnv) -> Self {
+        Self {
+            state: env.state.clone(),
+            runtime: env.runtime.clone(),
+            clock: env.clock.clone(),
+            random: env.random.clone(),
+            table: ResourceTable::default(),
+        }
+    }
+
+    /// Get the WASI state
+    pub fn state(&self) -> &WasiState {
+        &self.state
+    }
+}
@@ -0,0 +1,47 @@
+//! `wasi:random/random`, `wasi:random/insecure` and
+//! `wasi:random/insecure-seed`.
+//!
+//! All of them draw from the random source of the environment, so a
+//! seeded source makes the insecure interfaces reproducible as well.
+
+use super::WasiPreview2;
+use crate::syscalls::types::wasi::Errno;
+use std::convert::TryFrom;
+
+impl WasiPreview2 {
+    fn fill_random(&self, buf: &mut [u8]) -> Result<(), Errno> {
+        self.random
+            .as_deref()
+            .ok_or(Errno::Notcapable)?
+            .random_get(buf)
+    }
+
+    /// `random.get-random-bytes`
+    pub fn get_random_bytes(&self, len: u64) -> Result<Vec<u8>, Errno> {
+        let mut buf = vec![0; usize::try_from(len).map_err(|_| Errno::Overflow)?];
+        self.fill_random(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// `random.get-random-u64`
+    pub fn get_random_u64(&self) -> Result<u64, Errno> {
+        let mut buf = [0; 8];
+        self.fill_random(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    /// `insecure.get-insecure-random-bytes`
+    pub fn get_insecure_random_bytes(&self, len: u64) -> Result<Vec<u8>, Errno> {
+        self.get_random_bytes(len)
+    }
+
+    /// `insecure.get-insecure-random-u64`
+    pub fn get_insecure_random_u64(&self) -> Result<u64, Errno> {
+        self.get_random_u64()
+    }
+
+    /// `insecure-seed.insecure-seed`
+    pub fn insecure_seed(&self) -> Result<(u64, u64), Errno> {
+        Ok((self.get_random_u64()?, self.get_random_u64()?))
+    }
+}
@@ -0,0 +1,637 @@
+//! `wasi:sockets/network`, `wasi:sockets/instance-network`,
+//! `wasi:sockets/tcp`, `wasi:sockets/tcp-create-socket`,
+//! `wasi:sockets/udp`, `wasi:sockets/udp-create-socket` and
+//! `wasi:sockets/ip-name-lookup`.
+//!
+//! The operations split in a `start-*` and a `finish-*` method run in
+//! the `start-*` one, and the `finish-*` one returns their result.
+
+use super::{
+    FdStream, InputStream, Network, OutputStream, Pollable, PollableKind, ResolveAddressStream,
+    Resource, TcpSocket, UdpSocket, WasiPreview2,
+};
+use crate::state::{
+    net_error_into_wasi_err, InodeSocket, InodeSocketKind, Kind, PollEvent, PollEventBuilder,
+};
+use crate::syscalls::types::wasi::{
+    Addressfamily, Errno, Fd as WasiFd, Fdflags, Rights, SockProto, Socktype,
+};
+use bytes::Bytes;
+use std::net::{IpAddr, Shutdown, SocketAddr};
+use std::ops::DerefMut;
+use std::time::Duration;
+use wasmer_vnet::{NetworkError, VirtualNetworking};
+
+/// The backlog of the listening sockets.
+const LISTEN_BACKLOG: usize = 128;
+
+/// The `wasi:sockets/network.error-code` of a failed operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkErrorCode {
+    Unknown,
+    AccessDenied,
+    NotSupported,
+    InvalidArgument,
+    OutOfMemory,
+    Timeout,
+    ConcurrencyConflict,
+    NotInProgress,
+    WouldBlock,
+    InvalidState,
+    NewSocketLimit,
+    AddressNotBindable,
+    AddressInUse,
+    RemoteUnreachable,
+    ConnectionRefused,
+    ConnectionReset,
+    ConnectionAborted,
+    DatagramTooLarge,
+    NameUnresolvable,
+    TemporaryResolverFailure,
+    PermanentResolverFailure,
+}
+
+impl From<Errno> for NetworkErrorCode {
+    fn from(errno: Errno) -> Self {
+        match errno {
+            Errno::Access | Errno::Perm | Errno::Notcapable => Self::AccessDenied,
+            Errno::Notsup | Errno::Nosys | Errno::Afnosupport | Errno::Protonosupport => {
+                Self::NotSupported
+            }
+            Errno::Inval | Errno::Badf | Errno::Notsock => Self::InvalidArgument,
+            Errno::Nomem | Errno::Nobufs => Self::OutOfMemory,
+            Errno::Timedout => Self::Timeout,
+            Errno::Again => Self::WouldBlock,
+            Errno::Notconn | Errno::Isconn | Errno::Already | Errno::Inprogress => {
+                Self::InvalidState
+            }
+            Errno::Mfile | Errno::Nfile => Self::NewSocketLimit,
+            Errno::Addrnotavail => Self::AddressNotBindable,
+            Errno::Addrinuse => Self::AddressInUse,
+            Errno::Hostunreach | Errno::Netunreach | Errno::Netdown => Self::RemoteUnreachable,
+            Errno::Connrefused => Self::ConnectionRefused,
+            Errno::Connreset | Errno::Pipe => Self::ConnectionReset,
+            Errno::Connaborted => Self::ConnectionAborted,
+            Errno::Msgsize => Self::DatagramTooLarge,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// The `wasi:sockets/network.ip-address-family` of a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpAddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl From<IpAddressFamily> for Addressfamily {
+    fn from(family: IpAddressFamily) -> Self {
+        match family {
+            IpAddressFamily::Ipv4 => Addressfamily::Inet4,
+            IpAddressFamily::Ipv6 => Addressfamily::Inet6,
+        }
+    }
+}
+
+/// A `wasi:sockets/udp.datagram`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datagram {
+    pub data: Vec<u8>,
+    pub remote_address: SocketAddr,
+}
+
+/// An operation split in a `start-*` and a `finish-*` method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    Bind,
+    Connect,
+    Listen,
+}
+
+/// A socket, backed by a WASI socket file descriptor.
+#[derive(Debug)]
+pub(crate) struct OpenSocket {
+    fd: WasiFd,
+    ty: Socktype,
+    family: IpAddressFamily,
+    /// The result of the operation started last, until it is finished.
+    pending: Option<(Operation, Result<(), Errno>)>,
+}
+
+impl WasiPreview2 {
+    fn net(&self) -> &dyn VirtualNetworking {
+        self.runtime.networking()
+    }
+
+    fn socket(&mut self, handle: u32, ty: Socktype) -> Result<&mut OpenSocket, NetworkErrorCode> {
+        match self.table.get_mut(handle) {
+            Some(Resource::Socket(socket)) if socket.ty == ty => Ok(socket),
+            _ => Err(NetworkErrorCode::InvalidArgument),
+        }
+    }
+
+    fn check_network(&self, network: Network) -> Result<(), NetworkErrorCode> {
+        match self.table.get(network.into()) {
+            Some(Resource::Network) => Ok(()),
+            _ => Err(NetworkErrorCode::InvalidArgument),
+        }
+    }
+
+    /// Runs `actor` on the socket behind `fd`.
+    fn with_socket<T>(
+        &self,
+        fd: WasiFd,
+        actor: impl FnOnce(&mut InodeSocket) -> Result<T, Errno>,
+    ) -> Result<T, Errno> {
+        let fd_entry = self.state.fs.get_fd(fd)?;
+        let inodes = self.state.inodes.read().unwrap();
+        let mut guard = inodes.arena[fd_entry.inode].write();
+        match guard.deref_mut() {
+            Kind::Socket { socket } => actor(socket),
+            _ => Err(Errno::Notsock),
+        }
+    }
+
+    /// Replaces the socket behind `fd` with the one returned by `actor`,
+    /// if any.
+    fn upgrade_socket(
+        &self,
+        fd: WasiFd,
+        actor: impl FnOnce(&mut InodeSocket) -> Result<Option<InodeSocket>, Errno>,
+    ) -> Result<(), Errno> {
+        self.with_socket(fd, |socket| {
+            if let Some(mut new_socket) = actor(socket)? {
+                std::mem::swap(socket, &mut new_socket);
+            }
+            Ok(())
+        })
+    }
+
+    fn push_socket(
+        &mut self,
+        kind: InodeSocketKind,
+        ty: Socktype,
+        family: IpAddressFamily,
+    ) -> Result<u32, Errno> {
+        let fd = {
+            let mut inodes = self.state.inodes.write().unwrap();
+            let inode = self.state.fs.create_inode_with_default_stat(
+                inodes.deref_mut(),
+                Kind::Socket {
+                    socket: InodeSocket::new(kind),
+                },
+                false,
+                "socket".to_string(),
+            );
+            let rights = Rights::all_socket();
+            self.state
+                .fs
+                .create_fd(rights, rights, Fdflags::empty(), 0, inode)?
+        };
+        Ok(self.table.push(Resource::Socket(OpenSocket {
+            fd,
+            ty,
+            family,
+            pending: None,
+        })))
+    }
+
+    fn create_socket(&mut self, family: IpAddressFamily, ty: Socktype) -> Result<u32, Errno> {
+        let kind = InodeSocketKind::PreSocket {
+            family: family.into(),
+            ty,
+            pt: match ty {
+                Socktype::Dgram => SockProto::Udp,
+                _ => SockProto::Tcp,
+            },
+            addr: None,
+            only_v6: false,
+            reuse_port: false,
+            reuse_addr: false,
+            send_buf_size: None,
+            recv_buf_size: None,
+            send_timeout: None,
+            recv_timeout: None,
+            connect_timeout: None,
+            accept_timeout: None,
+        };
+        self.push_socket(kind, ty, family)
+    }
+
+    /// Runs an operation and keeps its result for the `finish-*` method.
+    fn start_operation(
+        &mut self,
+        handle: u32,
+        ty: Socktype,
+        operation: Operation,
+        run: impl FnOnce(&Self, WasiFd) -> Result<(), Errno>,
+    ) -> Result<(), NetworkErrorCode> {
+        let socket = self.socket(handle, ty)?;
+        if socket.pending.is_some() {
+            return Err(NetworkErrorCode::ConcurrencyConflict);
+        }
+        let fd = socket.fd;
+        let result = run(self, fd);
+        self.socket(handle, ty)?.pending = Some((operation, result));
+        Ok(())
+    }
+
+    fn finish_operation(
+        &mut self,
+        handle: u32,
+        ty: Socktype,
+        operation: Operation,
+    ) -> Result<WasiFd, NetworkErrorCode> {
+        let socket = self.socket(handle, ty)?;
+        match socket.pending.take() {
+            Some((pending, result)) if pending == operation => {
+                result.map(|_| socket.fd).map_err(Into::into)
+            }
+            pending => {
+                socket.pending = pending;
+                Err(NetworkErrorCode::NotInProgress)
+            }
+        }
+    }
+
+    fn socket_streams(&mut self, fd: WasiFd) -> (InputStream, OutputStream) {
+        let stream = || FdStream {
+            fd,
+            position: None,
+            append: false,
+        };
+        (
+            self.push_input_stream(stream()),
+            self.push_output_stream(stream()),
+        )
+    }
+
+    fn socket_pollable(&mut self, fd: WasiFd) -> Pollable {
+        self.push_pollable(PollableKind::Stream {
+            fd,
+            events: PollEventBuilder::new().add(PollEvent::PollIn).build(),
+        })
+    }
+
+    fn drop_socket(&mut self, handle: u32, ty: Socktype) -> Result<(), NetworkErrorCode> {
+        let fd = self.socket(handle, ty)?.fd;
+        self.table.remove(handle);
+        let inodes = self.state.inodes.read().unwrap();
+        Ok(self.state.fs.close_fd(&inodes, fd)?)
+    }
+
+    /// `instance-network.instance-network`: the network the sockets of
+    /// the runtime are attached to.
+    pub fn instance_network(&mut self) -> Network {
+        self.table.push(Resource::Network).into()
+    }
+
+    /// Drops a `network`.
+    pub fn drop_network(&mut self, network: Network) {
+        if let Some(Resource::Network) = self.table.get(network.into()) {
+            self.table.remove(network.into());
+        }
+    }
+
+    /// `tcp-create-socket.create-tcp-socket`
+    pub fn create_tcp_socket(
+        &mut self,
+        family: IpAddressFamily,
+    ) -> Result<TcpSocket, NetworkErrorCode> {
+        Ok(self.create_socket(family, Socktype::Stream)?.into())
+    }
+
+    /// `tcp-socket.start-bind`
+    pub fn tcp_start_bind(
+        &mut self,
+        socket: TcpSocket,
+        network: Network,
+        local_address: SocketAddr,
+    ) -> Result<(), NetworkErrorCode> {
+        self.check_network(network)?;
+        self.start_operation(
+            socket.into(),
+            Socktype::Stream,
+            Operation::Bind,
+            |this, fd| this.upgrade_socket(fd, |socket| socket.bind(this.net(), local_address)),
+        )
+    }
+
+    /// `tcp-socket.finish-bind`
+    pub fn tcp_finish_bind(&mut self, socket: TcpSocket) -> Result<(), NetworkErrorCode> {
+        self.finish_operation(socket.into(), Socktype::Stream, Operation::Bind)
+            .map(|_| ())
+    }
+
+    /// `tcp-socket.start-connect`
+    pub fn tcp_start_connect(
+        &mut self,
+        socket: TcpSocket,
+        network: Network,
+        remote_address: SocketAddr,
+    ) -> Result<(), NetworkErrorCode> {
+        self.check_network(network)?;
+        self.start_operation(
+            socket.into(),
+            Socktype::Stream,
+            Operation::Connect,
+            |this, fd| this.upgrade_socket(fd, |socket| socket.connect(this.net(), remote_address)),
+        )
+    }
+
+    /// `tcp-socket.finish-connect`: the streams of the connection.
+    pub fn tcp_finish_connect(
+        &mut self,
+        socket: TcpSocket,
+    ) -> Result<(InputStream, OutputStream), NetworkErrorCode> {
+        let fd = self.finish_operation(socket.into(), Socktype::Stream, Operation::Connect)?;
+        Ok(self.socket_streams(fd))
+    }
+
+    /// `tcp-socket.start-listen`
+    pub fn tcp_start_listen(&mut self, socket: TcpSocket) -> Result<(), NetworkErrorCode> {
+        self.start_operation(
+            socket.into(),
+            Socktype::Stream,
+            Operation::Listen,
+            |this, fd| this.upgrade_socket(fd, |socket| socket.listen(this.net(), LISTEN_BACKLOG)),
+        )
+    }
+
+    /// `tcp-socket.finish-listen`
+    pub fn tcp_finish_listen(&mut self, socket: TcpSocket) -> Result<(), NetworkErrorCode> {
+        self.finish_operation(socket.into(), Socktype::Stream, Operation::Listen)
+            .map(|_| ())
+    }
+
+    /// `tcp-socket.accept`: the socket and the streams of a pending
+    /// connection. Fails with [`NetworkErrorCode::WouldBlock`] when there
+    /// is none.
+    pub fn tcp_accept(
+        &mut self,
+        socket: TcpSocket,
+    ) -> Result<(TcpSocket, InputStream, OutputStream), NetworkErrorCode> {
+        let listener = self.socket(socket.into(), Socktype::Stream)?;
+        let (fd, family) = (listener.fd, listener.family);
+        let (child, _) = self
+            .with_socket(fd, |socket| {
+                socket.accept_timeout(Fdflags::empty(), Duration::ZERO)
+            })
+            .map_err(|errno| match errno {
+                Errno::Timedout => Errno::Again,
+                errno => errno,
+            })?;
+        let handle =
+            self.push_socket(InodeSocketKind::TcpStream(child), Socktype::Stream, family)?;
+        let fd = self.socket(handle, Socktype::Stream)?.fd;
+        let (input, output) = self.socket_streams(fd);
+        Ok((handle.into(), input, output))
+    }
+
+    /// `tcp-socket.local-address`
+    pub fn tcp_local_address(&mut self, socket: TcpSocket) -> Result<SocketAddr, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Stream)?.fd;
+        Ok(self.with_socket(fd, |socket| socket.addr_local())?)
+    }
+
+    /// `tcp-socket.remote-address`
+    pub fn tcp_remote_address(
+        &mut self,
+        socket: TcpSocket,
+    ) -> Result<SocketAddr, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Stream)?.fd;
+        Ok(self.with_socket(fd, |socket| socket.addr_peer())?)
+    }
+
+    /// `tcp-socket.address-family`
+    pub fn tcp_address_family(
+        &mut self,
+        socket: TcpSocket,
+    ) -> Result<IpAddressFamily, NetworkErrorCode> {
+        Ok(self.socket(socket.into(), Socktype::Stream)?.family)
+    }
+
+    /// `tcp-socket.subscribe`: ready once a connection can be accepted,
+    /// or data received.
+    pub fn tcp_subscribe(&mut self, socket: TcpSocket) -> Result<Pollable, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Stream)?.fd;
+        Ok(self.socket_pollable(fd))
+    }
+
+    /// `tcp-socket.shutdown`
+    pub fn tcp_shutdown(
+        &mut self,
+        socket: TcpSocket,
+        shutdown_type: Shutdown,
+    ) -> Result<(), NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Stream)?.fd;
+        Ok(self.with_socket(fd, |socket| socket.shutdown(shutdown_type))?)
+    }
+
+    /// Drops a `tcp-socket`, closing it. Its streams can't be used
+    /// anymore.
+    pub fn drop_tcp_socket(&mut self, socket: TcpSocket) -> Result<(), NetworkErrorCode> {
+        self.drop_socket(socket.into(), Socktype::Stream)
+    }
+
+    /// `udp-create-socket.create-udp-socket`
+    pub fn create_udp_socket(
+        &mut self,
+        family: IpAddressFamily,
+    ) -> Result<UdpSocket, NetworkErrorCode> {
+        Ok(self.create_socket(family, Socktype::Dgram)?.into())
+    }
+
+    /// `udp-socket.start-bind`
+    pub fn udp_start_bind(
+        &mut self,
+        socket: UdpSocket,
+        network: Network,
+        local_address: SocketAddr,
+    ) -> Result<(), NetworkErrorCode> {
+        self.check_network(network)?;
+        self.start_operation(
+            socket.into(),
+            Socktype::Dgram,
+            Operation::Bind,
+            |this, fd| this.upgrade_socket(fd, |socket| socket.bind(this.net(), local_address)),
+        )
+    }
+
+    /// `udp-socket.finish-bind`
+    pub fn udp_finish_bind(&mut self, socket: UdpSocket) -> Result<(), NetworkErrorCode> {
+        self.finish_operation(socket.into(), Socktype::Dgram, Operation::Bind)
+            .map(|_| ())
+    }
+
+    /// `udp-socket.start-connect`: sets the only address the socket
+    /// exchanges datagrams with. The socket must be bound first.
+    pub fn udp_start_connect(
+        &mut self,
+        socket: UdpSocket,
+        network: Network,
+        remote_address: SocketAddr,
+    ) -> Result<(), NetworkErrorCode> {
+        self.check_network(network)?;
+        self.start_operation(
+            socket.into(),
+            Socktype::Dgram,
+            Operation::Connect,
+            |this, fd| this.upgrade_socket(fd, |socket| socket.connect(this.net(), remote_address)),
+        )
+    }
+
+    /// `udp-socket.finish-connect`
+    pub fn udp_finish_connect(&mut self, socket: UdpSocket) -> Result<(), NetworkErrorCode> {
+        self.finish_operation(socket.into(), Socktype::Dgram, Operation::Connect)
+            .map(|_| ())
+    }
+
+    /// `udp-socket.receive`: up to `max_results` of the datagrams that
+    /// were received, without waiting for more.
+    pub fn udp_receive(
+        &mut self,
+        socket: UdpSocket,
+        max_results: u64,
+    ) -> Result<Vec<Datagram>, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Dgram)?.fd;
+        let mut datagrams = Vec::new();
+        while (datagrams.len() as u64) < max_results {
+            let received = self.with_socket(fd, |socket| {
+                if !socket.readiness()?.readable {
+                    return Ok(None);
+                }
+                socket.recv_from_bytes().map(Some)
+            })?;
+            match received {
+                Some((data, remote_address)) => datagrams.push(Datagram {
+                    data: data.to_vec(),
+                    remote_address,
+                }),
+                None => break,
+            }
+        }
+        Ok(datagrams)
+    }
+
+    /// `udp-socket.send`: sends the datagrams, and returns how many were
+    /// sent.
+    pub fn udp_send(
+        &mut self,
+        socket: UdpSocket,
+        datagrams: &[Datagram],
+    ) -> Result<u64, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Dgram)?.fd;
+        let mut sent = 0;
+        for datagram in datagrams {
+            let result = self.with_socket(fd, |socket| {
+                socket.send_to_bytes(
+                    Bytes::copy_from_slice(&datagram.data),
+                    datagram.remote_address,
+                )
+            });
+            match result {
+                Ok(_) => sent += 1,
+                // The datagrams sent so far are reported rather than the
+                // error, which the next call returns
+                Err(_) if sent > 0 => break,
+                Err(errno) => return Err(errno.into()),
+            }
+        }
+        Ok(sent)
+    }
+
+    /// `udp-socket.local-address`
+    pub fn udp_local_address(&mut self, socket: UdpSocket) -> Result<SocketAddr, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Dgram)?.fd;
+        Ok(self.with_socket(fd, |socket| socket.addr_local())?)
+    }
+
+    /// `udp-socket.remote-address`
+    pub fn udp_remote_address(
+        &mut self,
+        socket: UdpSocket,
+    ) -> Result<SocketAddr, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Dgram)?.fd;
+        Ok(self.with_socket(fd, |socket| socket.addr_peer())?)
+    }
+
+    /// `udp-socket.address-family`
+    pub fn udp_address_family(
+        &mut self,
+        socket: UdpSocket,
+    ) -> Result<IpAddressFamily, NetworkErrorCode> {
+        Ok(self.socket(socket.into(), Socktype::Dgram)?.family)
+    }
+
+    /// `udp-socket.subscribe`: ready once a datagram was received.
+    pub fn udp_subscribe(&mut self, socket: UdpSocket) -> Result<Pollable, NetworkErrorCode> {
+        let fd = self.socket(socket.into(), Socktype::Dgram)?.fd;
+        Ok(self.socket_pollable(fd))
+    }
+
+    /// Drops a `udp-socket`, closing it.
+    pub fn drop_udp_socket(&mut self, socket: UdpSocket) -> Result<(), NetworkErrorCode> {
+        self.drop_socket(socket.into(), Socktype::Dgram)
+    }
+
+    /// `ip-name-lookup.resolve-addresses`: the name is resolved right
+    /// away, the stream returns the addresses it resolved to.
+    pub fn resolve_addresses(
+        &mut self,
+        network: Network,
+        name: &str,
+    ) -> Result<ResolveAddressStream, NetworkErrorCode> {
+        self.check_network(network)?;
+        let addresses = self
+            .net()
+            .resolve(name, None, None)
+            .map_err(|err| match err {
+                NetworkError::Unsupported => NetworkErrorCode::NotSupported,
+                NetworkError::PermissionDenied => NetworkErrorCode::AccessDenied,
+                NetworkError::TimedOut => NetworkErrorCode::TemporaryResolverFailure,
+                err => match net_error_into_wasi_err(err) {
+                    Errno::Inval => NetworkErrorCode::InvalidArgument,
+                    _ => NetworkErrorCode::NameUnresolvable,
+                },
+            })?;
+        Ok(self
+            .table
+            .push(Resource::ResolveAddressStream(addresses.into()))
+            .into())
+    }
+
+    /// `resolve-address-stream.resolve-next-address`: `None` once all the
+    /// addresses were returned.
+    pub fn resolve_next_address(
+        &mut self,
+        stream: ResolveAddressStream,
+    ) -> Result<Option<IpAddr>, NetworkErrorCode> {
+        match self.table.get_mut(stream.into()) {
+            Some(Resource::ResolveAddressStream(addresses)) => Ok(addresses.pop_front()),
+            _ => Err(NetworkErrorCode::InvalidArgument),
+        }
+    }
+
+    /// `resolve-address-stream.subscribe`, always ready as the name is
+    /// already resolved.
+    pub fn subscribe_resolve_address_stream(
+        &mut self,
+        stream: ResolveAddressStream,
+    ) -> Result<Pollable, NetworkErrorCode> {
+        match self.table.get(stream.into()) {
+            Some(Resource::ResolveAddressStream(_)) => {
+                Ok(self.push_pollable(PollableKind::Deadline(0)))
+            }
+            _ => Err(NetworkErrorCode::InvalidArgument),
+        }
+    }
+
+    /// Drops a `resolve-address-stream`.
+    pub fn drop_resolve_address_stream(&mut self, stream: ResolveAddressStream) {
+        if let Some(Resource::ResolveAddressStream(_)) = self.table.get(stream.into()) {
+            self.table.remove(stream.into());
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Recording of the WASI syscalls made by a guest, and deterministic
+//! replay of such a recording.
+//!
+//! A [`SyscallRecorder`] wraps every function of an import object so that
+//! the parameters, the results and the bytes written to the memory of the
+//! guest by each call are logged to a [`SyscallTrace`]. This covers
+//! everything the guest can learn from the host: file and socket reads,
+//! clock values, random bytes, and so on.
+//!
+//! A [`SyscallReplayer`] wraps the import object the same way, but never
+//! calls the host: each call is answered with the next recorded one, so
+//! the guest runs exactly as it did while recording.
+//!
+//! The bytes written by a call are the ones the host writes through the
+//! views of the memory (see [`Memory::log_writes`]), so recording is only
+//! available with the `sys` feature. The order of the calls is only
+//! reproducible for guests that don't spawn threads.
+
+use crate::utils::wrap_imports;
+use crate::WasiEnv;
+use crate::WasiError;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use wasmer::{AsStoreMut, AsStoreRef, Function, FunctionEnv, Imports, Memory, RuntimeError, Value};
+
+/// A value passed to or returned by a syscall
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RecordedValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+impl RecordedValue {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::I32(v) => Ok(Self::I32(*v)),
+            Value::I64(v) => Ok(Self::I64(*v)),
+            Value::F32(v) => Ok(Self::F32(*v)),
+            Value::F64(v) => Ok(Self::F64(*v)),
+            other => Err(RuntimeError::new(format!(
+                "cannot record a syscall value of type {:?}",
+                other.ty()
+            ))),
+        }
+    }
+
+    fn to_value(self) -> Value {
+        match self {
+            Self::I32(v) => Value::I32(v),
+            Self::I64(v) => Value::I64(v),
+            Self::F32(v) => Value::F32(v),
+            Self::F64(v) => Value::F64(v),
+        }
+    }
+}
+
+/// Bytes written to the memory of the guest during a syscall
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryWrite {
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+/// How a syscall returned to the guest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SyscallOutcome {
+    /// The syscall returned these values
+    Return(Vec<RecordedValue>),
+    /// The syscall made the guest exit with this code
+    Exit(u32),
+    /// The syscall trapped with this message
+    Trap(String),
+}
+
+/// A single syscall made by the guest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyscallEvent {
+    pub namespace: String,
+    pub name: String,
+    pub params: Vec<RecordedValue>,
+    pub writes: Vec<MemoryWrite>,
+    pub outcome: SyscallOutcome,
+}
+
+/// The syscalls made by a guest, in order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyscallTrace {
+    pub events: Vec<SyscallEvent>,
+}
+
+impl SyscallTrace {
+    /// Write the trace, for instance to a file
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), bincode::Error> {
+        bincode::serialize_into(writer, self)
+    }
+
+    /// Read a trace written with [`Self::write_to`]
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, bincode::Error> {
+        bincode::deserialize_from(reader)
+    }
+}
+
+/// Records the syscalls made through the import objects it wraps
+#[cfg(feature = "sys")]
+#[derive(Debug, Clone, Default)]
+pub struct SyscallRecorder {
+    trace: Arc<Mutex<SyscallTrace>>,
+}
+
+#[cfg(feature = "sys")]
+impl SyscallRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap the functions of an import object, such as the one returned by
+    /// [`generate_import_object_from_env`](crate::generate_import_object_from_env),
+    /// so that the calls made to them are recorded.
+    pub fn wrap(
+        &self,
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<WasiEnv>,
+        imports: &Imports,
+    ) -> Imports {
+        wrap_imports(store, imports, |store, namespace, name, inner| {
+            let trace = self.trace.clone();
+            let ty = inner.ty(&*store);
+            Function::new_with_env(store, env, ty, move |mut ctx, args| {
+                let params = recorded_values(args)?;
+                let memory = ctx.data().memory.clone();
+                if let Some(memory) = memory.as_ref() {
+                    memory.log_writes(&mut ctx);
+                }
+
+                let result = inner.call(&mut ctx, args);
+
+                let writes = match memory {
+                    Some(memory) => memory
+                        .take_writes(&mut ctx)
+                        .into_iter()
+                        .map(|(offset, data)| MemoryWrite { offset, data })
+                        .collect(),
+                    None => Vec::new(),
+                };
+                let (outcome, result) = match result {
+                    Ok(results) => (
+                        SyscallOutcome::Return(recorded_values(&results)?),
+                        Ok(results.into_vec()),
+                    ),
+                    Err(err) => match err.downcast::<WasiError>() {
+                        Ok(WasiError::Exit(code)) => (
+                            SyscallOutcome::Exit(code),
+                            Err(RuntimeError::user(Box::new(WasiError::Exit(code)))),
+                        ),
+                        Ok(err) => (
+                            SyscallOutcome::Trap(err.to_string()),
+                            Err(RuntimeError::user(Box::new(err))),
+                        ),
+                        Err(err) => (SyscallOutcome::Trap(err.message()), Err(err)),
+                    },
+                };
+
+                trace.lock().unwrap().events.push(SyscallEvent {
+                    namespace: namespace.clone(),
+                    name: name.clone(),
+                    params,
+                    writes,
+                    outcome,
+                });
+                result
+            })
+        })
+    }
+
+    /// The syscalls recorded so far
+    pub fn trace(&self) -> SyscallTrace {
+        self.trace.lock().unwrap().clone()
+    }
+}
+
+/// Answers the syscalls made through the import objects it wraps with
+/// the ones of a recorded [`SyscallTrace`], without calling the host.
+#[derive(Debug, Clone, Default)]
+pub struct SyscallReplayer {
+    events: Arc<Mutex<VecDeque<SyscallEvent>>>,
+}
+
+impl SyscallReplayer {
+    pub fn new(trace: SyscallTrace) -> Self {
+        Self {
+            events: Arc::new(Mutex::new(trace.events.into())),
+        }
+    }
+
+    /// Wrap the functions of an import object so that they replay the trace.
+    ///
+    /// The guest traps if it makes a syscall that differs from the recorded
+    /// one, or more syscalls than were recorded.
+    pub fn wrap(
+        &self,
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<WasiEnv>,
+        imports: &Imports,
+    ) -> Imports {
+        wrap_imports(store, imports, |store, namespace, name, inner| {
+            let events = self.events.clone();
+            let ty = inner.ty(&*store);
+            Function::new_with_env(store, env, ty, move |ctx, args| {
+                let params = recorded_values(args)?;
+                let event = events.lock().unwrap().pop_front().ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "replay diverged: {}::{} was called after the end of the trace",
+                        namespace, name
+                    ))
+                })?;
+                if event.namespace != *namespace || event.name != *name || event.params != params {
+                    return Err(RuntimeError::new(format!(
+                        "replay diverged: {}::{}{:?} was called instead of {}::{}{:?}",
+                        namespace, name, params, event.namespace, event.name, event.params
+                    )));
+                }
+
+                if !event.writes.is_empty() {
+                    let memory = ctx.data().memory.clone().ok_or_else(|| {
+                        RuntimeError::new("replay diverged: the memory of the guest is not set")
+                    })?;
+                    write_memory(&ctx, &memory, &event.writes)?;
+                }
+
+                match event.outcome {
+                    SyscallOutcome::Return(results) => {
+                        Ok(results.into_iter().map(RecordedValue::to_value).collect())
+                    }
+                    SyscallOutcome::Exit(code) => {
+                        Err(RuntimeError::user(Box::new(WasiError::Exit(code))))
+                    }
+                    SyscallOutcome::Trap(message) => Err(RuntimeError::new(message)),
+                }
+            })
+        })
+    }
+
+    /// The number of recorded syscalls that were not replayed yet
+    pub fn remaining(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+}
+
+fn recorded_values(values: &[Value]) -> Result<Vec<RecordedValue>, RuntimeError> {
+    values.iter().map(RecordedValue::from_value).collect()
+}
+
+fn write_memory(
+    store: &impl AsStoreRef,
+    memory: &Memory,
+    writes: &[MemoryWrite],
+) -> Result<(), RuntimeError> {
+    let view = memory.view(store);
+    for write in writes {
+        view.write(write.offset, &write.data)
+            .map_err(|err| RuntimeError::new(format!("replay diverged: {}", err)))?;
+    }
+    Ok(())
+}
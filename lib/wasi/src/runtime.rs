@@ -1,13 +1,16 @@
 use std::fmt;
 use std::ops::Deref;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
 use thiserror::Error;
 use wasmer_vbus::{UnsupportedVirtualBus, VirtualBus};
 use wasmer_vnet::VirtualNetworking;
-use wasmer_wasi_types::wasi::Errno;
+use wasmer_wasi_types::wasi::{Errno, Snapshot0Clockid, Timestamp};
 
 use super::WasiError;
 use super::WasiThreadId;
+use crate::syscalls::{platform_clock_res_get, platform_clock_time_get};
+use crate::tty::TtyBridge;
 
 #[derive(Error, Debug)]
 pub enum WasiThreadError {
@@ -39,6 +42,22 @@ pub struct WasiTtyState {
     pub line_buffered: bool,
 }
 
+impl Default for WasiTtyState {
+    fn default() -> Self {
+        Self {
+            rows: 25,
+            cols: 80,
+            width: 800,
+            height: 600,
+            stdin_tty: false,
+            stdout_tty: false,
+            stderr_tty: false,
+            echo: true,
+            line_buffered: true,
+        }
+    }
+}
+
 /// Represents an implementation of the WASI runtime - by default everything is
 /// unimplemented.
 pub trait WasiRuntimeImplementation: fmt::Debug + Sync {
@@ -57,17 +76,7 @@ pub trait WasiRuntimeImplementation: fmt::Debug + Sync {
 
     /// Gets the TTY state
     fn tty_get(&self) -> WasiTtyState {
-        WasiTtyState {
-            rows: 25,
-            cols: 80,
-            width: 800,
-            height: 600,
-            stdin_tty: false,
-            stdout_tty: false,
-            stderr_tty: false,
-            echo: true,
-            line_buffered: true,
-        }
+        WasiTtyState::default()
     }
 
     /// Sets the TTY state
@@ -105,6 +114,7 @@ pub struct PluggableRuntimeImplementation {
     pub bus: Box<dyn VirtualBus + Sync>,
     pub networking: Box<dyn VirtualNetworking + Sync>,
     pub thread_id_seed: AtomicU32,
+    pub tty: Option<Arc<dyn TtyBridge>>,
 }
 
 impl PluggableRuntimeImplementation {
@@ -121,6 +131,13 @@ impl PluggableRuntimeImplementation {
     {
         self.networking = Box::new(net)
     }
+
+    pub fn set_tty_implementation<I>(&mut self, tty: I)
+    where
+        I: TtyBridge + 'static,
+    {
+        self.tty = Some(Arc::new(tty))
+    }
 }
 
 impl Default for PluggableRuntimeImplementation {
@@ -132,6 +149,7 @@ impl Default for PluggableRuntimeImplementation {
             networking: Box::new(wasmer_wasi_local_networking::LocalNetworking::default()),
             bus: Box::new(UnsupportedVirtualBus::default()),
             thread_id_seed: Default::default(),
+            tty: None,
         }
     }
 }
@@ -148,4 +166,218 @@ impl WasiRuntimeImplementation for PluggableRuntimeImplementation {
     fn thread_generate_id(&self) -> WasiThreadId {
         self.thread_id_seed.fetch_add(1, Ordering::Relaxed).into()
     }
+
+    fn tty_get(&self) -> WasiTtyState {
+        self.tty
+            .as_ref()
+            .map(|tty| tty.tty_get())
+            .unwrap_or_default()
+    }
+
+    fn tty_set(&self, tty_state: WasiTtyState) {
+        if let Some(tty) = self.tty.as_ref() {
+            tty.tty_set(tty_state);
+        }
+    }
+
+    #[cfg(feature = "sys")]
+    fn thread_spawn(
+        &self,
+        callback: Box<dyn FnOnce() + Send + 'static>,
+    ) -> Result<(), WasiThreadError> {
+        std::thread::Builder::new()
+            .spawn(callback)
+            .map(|_| ())
+            .map_err(|_| WasiThreadError::Unsupported)
+    }
+
+    #[cfg(feature = "sys")]
+    fn thread_parallelism(&self) -> Result<usize, WasiThreadError> {
+        std::thread::available_parallelism()
+            .map(|parallelism| parallelism.get())
+            .map_err(|_| WasiThreadError::Unsupported)
+    }
+}
+
+/// A source of time for the WASI clocks.
+pub trait WasiClock: fmt::Debug + Send + Sync {
+    /// Gets the resolution of the given clock, in nanoseconds.
+    fn res_get(&self, clock_id: Snapshot0Clockid) -> Result<Timestamp, Errno>;
+
+    /// Gets the time of the given clock, in nanoseconds.
+    fn time_get(
+        &self,
+        clock_id: Snapshot0Clockid,
+        precision: Timestamp,
+    ) -> Result<Timestamp, Errno>;
+}
+
+/// A source of random bytes for WASI.
+pub trait WasiRandom: fmt::Debug + Send + Sync {
+    /// Fills the buffer with random bytes.
+    fn random_get(&self, buf: &mut [u8]) -> Result<(), Errno>;
+}
+
+/// The clocks of the host.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HostClock;
+
+impl WasiClock for HostClock {
+    fn res_get(&self, clock_id: Snapshot0Clockid) -> Result<Timestamp, Errno> {
+        platform_clock_res_get(clock_id).map(|t| t as Timestamp)
+    }
+
+    fn time_get(
+        &self,
+        clock_id: Snapshot0Clockid,
+        precision: Timestamp,
+    ) -> Result<Timestamp, Errno> {
+        platform_clock_time_get(clock_id, precision).map(|t| t as Timestamp)
+    }
+}
+
+/// The random source of the host.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HostRandom;
+
+impl WasiRandom for HostRandom {
+    fn random_get(&self, buf: &mut [u8]) -> Result<(), Errno> {
+        getrandom::getrandom(buf).map_err(|_| Errno::Io)
+    }
+}
+
+/// A clock that is stopped at a given time.
+///
+/// All the clocks read the same time, in nanoseconds.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FixedClock {
+    time: Timestamp,
+}
+
+impl FixedClock {
+    /// Creates a clock that always reads `time`, in nanoseconds
+    pub fn new(time: Timestamp) -> Self {
+        Self { time }
+    }
+}
+
+impl WasiClock for FixedClock {
+    fn res_get(&self, _clock_id: Snapshot0Clockid) -> Result<Timestamp, Errno> {
+        Ok(1)
+    }
+
+    fn time_get(
+        &self,
+        _clock_id: Snapshot0Clockid,
+        _precision: Timestamp,
+    ) -> Result<Timestamp, Errno> {
+        Ok(self.time)
+    }
+}
+
+/// A clock that starts at a given time and moves forward by a fixed
+/// step every time it is read, so that the module sees the time pass
+/// the same way on every run.
+///
+/// All the clocks share the same time, in nanoseconds.
+#[derive(Debug, Default)]
+pub struct StepClock {
+    time: AtomicU64,
+    step: Timestamp,
+}
+
+impl StepClock {
+    /// Creates a clock that first reads `start`, then `step` nanoseconds
+    /// more at every read
+    pub fn new(start: Timestamp, step: Timestamp) -> Self {
+        Self {
+            time: AtomicU64::new(start),
+            step,
+        }
+    }
+}
+
+impl WasiClock for StepClock {
+    fn res_get(&self, _clock_id: Snapshot0Clockid) -> Result<Timestamp, Errno> {
+        Ok(self.step.max(1))
+    }
+
+    fn time_get(
+        &self,
+        _clock_id: Snapshot0Clockid,
+        _precision: Timestamp,
+    ) -> Result<Timestamp, Errno> {
+        Ok(self.time.fetch_add(self.step, Ordering::Relaxed))
+    }
+}
+
+/// A random source producing the same bytes for the same seed.
+///
+/// This uses SplitMix64, which is fast and good enough for tests and
+/// replays, but must not be used where the randomness has to be secure.
+#[derive(Debug, Default)]
+pub struct SeededRandom {
+    state: AtomicU64,
+}
+
+impl SeededRandom {
+    /// Creates a random source whose bytes all derive from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    fn next_u64(&self) -> u64 {
+        const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut z = self
+            .state
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl WasiRandom for SeededRandom {
+    fn random_get(&self, buf: &mut [u8]) -> Result<(), Errno> {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_clock_moves_forward() {
+        let clock = StepClock::new(100, 10);
+        let times: Vec<_> = (0..3)
+            .map(|_| clock.time_get(Snapshot0Clockid::Monotonic, 1).unwrap())
+            .collect();
+        assert_eq!(times, vec![100, 110, 120]);
+        assert_eq!(
+            FixedClock::new(42)
+                .time_get(Snapshot0Clockid::Realtime, 1)
+                .unwrap(),
+            42
+        );
+    }
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        let mut a = [0u8; 13];
+        let mut b = [0u8; 13];
+        SeededRandom::new(7).random_get(&mut a).unwrap();
+        SeededRandom::new(7).random_get(&mut b).unwrap();
+        assert_eq!(a, b);
+
+        SeededRandom::new(8).random_get(&mut b).unwrap();
+        assert_ne!(a, b);
+    }
 }
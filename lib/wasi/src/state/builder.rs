@@ -1,8 +1,8 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
-use crate::state::{default_fs_backing, WasiFs, WasiState};
+use crate::state::{default_fs_backing, FsPolicy, WasiFs, WasiState};
 use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
-use crate::{WasiEnv, WasiFunctionEnv, WasiInodes};
+use crate::{WasiClock, WasiEnv, WasiFunctionEnv, WasiInodes, WasiRandom};
 use generational_arena::Arena;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
@@ -11,6 +11,7 @@ use std::sync::Arc;
 use std::sync::RwLock;
 use thiserror::Error;
 use wasmer::AsStoreMut;
+use wasmer_vfs::mount_fs::MountFileSystem;
 use wasmer_vfs::{FsError, VirtualFile};
 
 /// Creates an empty [`WasiStateBuilder`].
@@ -51,7 +52,12 @@ pub struct WasiStateBuilder {
     stderr_override: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     stdin_override: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     fs_override: Option<Box<dyn wasmer_vfs::FileSystem>>,
+    mounts: Vec<(PathBuf, Box<dyn wasmer_vfs::FileSystem>)>,
     runtime_override: Option<Arc<dyn crate::WasiRuntimeImplementation + Send + Sync + 'static>>,
+    deterministic: bool,
+    clock_override: Option<Arc<dyn WasiClock>>,
+    random_override: Option<Arc<dyn WasiRandom>>,
+    fs_policy: Option<FsPolicy>,
 }
 
 impl std::fmt::Debug for WasiStateBuilder {
@@ -65,7 +71,15 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("stdout_override exists", &self.stdout_override.is_some())
             .field("stderr_override exists", &self.stderr_override.is_some())
             .field("stdin_override exists", &self.stdin_override.is_some())
+            .field(
+                "mounts",
+                &self.mounts.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            )
             .field("runtime_override_exists", &self.runtime_override.is_some())
+            .field("deterministic", &self.deterministic)
+            .field("clock_override", &self.clock_override)
+            .field("random_override", &self.random_override)
+            .field("fs_policy", &self.fs_policy)
             .finish()
     }
 }
@@ -312,12 +326,51 @@ impl WasiStateBuilder {
     /// Sets the FileSystem to be used with this WASI instance.
     ///
     /// This is usually used in case a custom `wasmer_vfs::FileSystem` is needed.
+    /// Wrapping the host file system in a
+    /// [`ChrootFileSystem`](wasmer_vfs::chroot_fs::ChrootFileSystem), and
+    /// possibly a [`ReadOnlyFileSystem`](wasmer_vfs::read_only_fs::ReadOnlyFileSystem),
+    /// confines the module on top of the preopened directories.
     pub fn set_fs(&mut self, fs: Box<dyn wasmer_vfs::FileSystem>) -> &mut Self {
         self.fs_override = Some(fs);
 
         self
     }
 
+    /// Mounts `fs` at `path` and preopens it under that name.
+    ///
+    /// Once something is mounted, the WASI instance sees a
+    /// [`MountFileSystem`](wasmer_vfs::mount_fs::MountFileSystem): every
+    /// path goes to the file system mounted at its longest prefix, and
+    /// the paths outside every mount point go to the file system given
+    /// to [`Self::set_fs`], if any.
+    ///
+    /// **The mounted file system is seen from its root**: `<path>/x` is
+    /// `/x` in `fs`. Mounting the host file system would therefore give
+    /// the module the whole host, so it is refused; mount a
+    /// [`ChrootFileSystem`](wasmer_vfs::chroot_fs::ChrootFileSystem) of the
+    /// host directory to share instead.
+    pub fn mount<FilePath>(
+        &mut self,
+        path: FilePath,
+        fs: Box<dyn wasmer_vfs::FileSystem>,
+    ) -> Result<&mut Self, WasiStateCreationError>
+    where
+        FilePath: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        #[cfg(feature = "host-fs")]
+        if wasmer_vfs::Upcastable::upcast_any_ref(&*fs).is::<wasmer_vfs::host_fs::FileSystem>() {
+            return Err(WasiStateCreationError::WasiFsSetupError(format!(
+                "mounting the host file system at `{}` would expose the root of the host, mount a `ChrootFileSystem` instead",
+                path.display()
+            )));
+        }
+        self.map_dir(&path.to_string_lossy(), path)?;
+        self.mounts.push((path.to_path_buf(), fs));
+
+        Ok(self)
+    }
+
     /// Configure the WASI filesystem before running.
     // TODO: improve ergonomics on this function
     pub fn setup_fs(&mut self, setup_fs_fn: SetupFsFn) -> &mut Self {
@@ -336,6 +389,48 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Forbids the module from reading the clocks and the random source of
+    /// the host, unless a clock or a random source is injected with
+    /// [`Self::clock`] or [`Self::random`].
+    ///
+    /// This is always the case for environments created in a
+    /// deterministic engine.
+    pub fn deterministic(&mut self, deterministic: bool) -> &mut Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Sets the clock the module reads the time from, instead of the
+    /// clocks of the host.
+    pub fn clock<C>(&mut self, clock: C) -> &mut Self
+    where
+        C: WasiClock + 'static,
+    {
+        self.clock_override = Some(Arc::new(clock));
+        self
+    }
+
+    /// Sets the source of the random bytes read by the module, instead of
+    /// the random source of the host.
+    pub fn random<R>(&mut self, random: R) -> &mut Self
+    where
+        R: WasiRandom + 'static,
+    {
+        self.random_override = Some(Arc::new(random));
+        self
+    }
+
+    /// Sets the policy restricting what the module can do with its files,
+    /// beyond the rights given to the preopened directories.
+    ///
+    /// The rules of the policy use the paths of the file system backing,
+    /// the same as [`Self::preopen_dir`]. The policy can still be changed
+    /// while the module runs through [`WasiFs::revoke_preopen`].
+    pub fn fs_policy(&mut self, policy: FsPolicy) -> &mut Self {
+        self.fs_policy = Some(policy);
+        self
+    }
+
     /// Consumes the [`WasiStateBuilder`] and produces a [`WasiState`]
     ///
     /// Returns the error from `WasiFs::new` if there's an error
@@ -348,6 +443,7 @@ impl WasiStateBuilder {
     /// reset to their defaults:
     ///
     /// * [Self::set_fs],
+    /// * [Self::mount],
     /// * [Self::stdin],
     /// * [Self::stdout],
     /// * [Self::stderr].
@@ -418,7 +514,18 @@ impl WasiStateBuilder {
             }
         }
 
-        let fs_backing = self.fs_override.take().unwrap_or_else(default_fs_backing);
+        let fs_backing = if self.mounts.is_empty() {
+            self.fs_override.take().unwrap_or_else(default_fs_backing)
+        } else {
+            let mount_fs = MountFileSystem::new();
+            let mounts = self.fs_override.take().map(|fs| (PathBuf::from("/"), fs));
+            for (path, fs) in mounts.into_iter().chain(self.mounts.drain(..)) {
+                mount_fs
+                    .mount(&path, fs)
+                    .map_err(WasiStateCreationError::FileSystemError)?;
+            }
+            Box::new(mount_fs)
+        };
 
         // self.preopens are checked in [`PreopenDirBuilder::build`]
         let inodes = RwLock::new(crate::state::WasiInodes {
@@ -436,6 +543,9 @@ impl WasiStateBuilder {
                 fs_backing,
             )
             .map_err(WasiStateCreationError::WasiFsCreationError)?;
+            if let Some(policy) = self.fs_policy.take() {
+                wasi_fs.policy = policy;
+            }
 
             // set up the file system, overriding base files and calling the setup function
             if let Some(stdin_override) = self.stdin_override.take() {
@@ -503,6 +613,18 @@ impl WasiStateBuilder {
         if let Some(runtime) = self.runtime_override.as_ref() {
             env.runtime = runtime.clone();
         }
+
+        if let Some(clock) = self.clock_override.as_ref() {
+            env.set_clock(Some(clock.clone()));
+        }
+        if let Some(random) = self.random_override.as_ref() {
+            env.set_random(Some(random.clone()));
+        }
+        // The environments of deterministic engines lose the host sources
+        // when they are added to the store
+        if self.deterministic {
+            env.forbid_host_sources();
+        }
         Ok(WasiFunctionEnv::new(store, env))
     }
 }
@@ -671,4 +793,20 @@ mod test {
             _ => assert!(false),
         }
     }
+
+    #[cfg(feature = "host-fs")]
+    #[test]
+    fn mount_host_fs_requires_chroot() {
+        use wasmer_vfs::{chroot_fs::ChrootFileSystem, host_fs};
+
+        let mut builder = create_wasi_state("test_prog");
+        let output = builder.mount("/host", Box::new(host_fs::FileSystem::default()));
+        assert!(matches!(
+            output,
+            Err(WasiStateCreationError::WasiFsSetupError(_))
+        ));
+
+        let chroot = ChrootFileSystem::new(std::env::temp_dir(), host_fs::FileSystem::default());
+        assert!(builder.mount("/host", Box::new(chroot)).is_ok());
+    }
 }
@@ -0,0 +1,520 @@
+//! Checkpoints of a [`WasiState`] that can be moved to another process
+//! (or another machine) and resumed there.
+//!
+//! [`WasiState::freeze`] only records the data structures of the state,
+//! which leaves out everything that lives behind a `VirtualFile` handle
+//! or inside the filesystem backing. A [`WasiCheckpoint`] also captures:
+//!
+//! - the contents of the in-memory filesystem, when that is the backing;
+//! - the size, modification time and position of the files open in any
+//!   other backing, which are opened again with the same flags when
+//!   restoring, and must not have changed in the meantime (their contents
+//!   are never rewritten, since the backing usually is the host);
+//! - the standard devices, which are recreated on the other side;
+//! - any other kind of file a [`VirtualFileCheckpointHandler`] knows about;
+//! - optionally, the linear memory of the instance (see [`WasiEnv::checkpoint`]).
+//!
+//! Pipes and sockets are connected to something outside of the guest and
+//! can't be captured, so checkpointing a state that has any fails.
+//!
+//! This is available with the `checkpoint` feature.
+//!
+//! [`WasiEnv::checkpoint`]: crate::WasiEnv::checkpoint
+
+use super::{default_fs_backing, Fd, InodeVal, Kind, WasiState};
+use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
+use generational_arena::Index as Inode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Seek, SeekFrom};
+use std::sync::Arc;
+use thiserror::Error;
+use wasmer::{AsStoreMut, Memory, MemoryError, Pages, WASM_PAGE_SIZE};
+use wasmer_vfs::{FileSystem, FsError, VirtualFile};
+
+/// Error type returned when a [`WasiCheckpoint`] can't be taken or restored.
+#[derive(Error, Debug)]
+pub enum WasiCheckpointError {
+    #[error("cannot checkpoint `{0}`: pipes and sockets are not supported")]
+    Unsupported(String),
+    #[error("no checkpoint handler named `{0}`")]
+    MissingHandler(String),
+    #[error("the filesystem backing can't hold the captured in-memory filesystem")]
+    IncompatibleFileSystem,
+    #[error("`{0}` changed since the checkpoint was taken")]
+    FileChanged(String),
+    #[error("checkpoint serialization error: `{0}`")]
+    SerializationError(String),
+    #[error("memory error: `{0}`")]
+    MemoryError(String),
+    #[error(transparent)]
+    FileSystemError(FsError),
+}
+
+impl From<FsError> for WasiCheckpointError {
+    fn from(err: FsError) -> Self {
+        Self::FileSystemError(err)
+    }
+}
+
+impl From<bincode::Error> for WasiCheckpointError {
+    fn from(err: bincode::Error) -> Self {
+        Self::SerializationError(err.to_string())
+    }
+}
+
+/// Saves and restores the `VirtualFile`s that can't be captured by
+/// reading their contents, such as files implemented by the embedder.
+pub trait VirtualFileCheckpointHandler: fmt::Debug + Send + Sync {
+    /// Name of the handler, recorded in the checkpoint so that the same
+    /// handler is picked when restoring it.
+    fn name(&self) -> &str;
+
+    /// Saves the state of `file`, or returns `None` if this handler
+    /// doesn't know about this kind of file.
+    fn save(
+        &self,
+        file: &mut (dyn VirtualFile + Send + Sync + 'static),
+    ) -> Option<Result<Vec<u8>, FsError>>;
+
+    /// Recreates a file from the state returned by [`Self::save`].
+    fn restore(&self, data: &[u8])
+        -> Result<Box<dyn VirtualFile + Send + Sync + 'static>, FsError>;
+}
+
+/// How the handle of an open file was captured
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum FileCheckpoint {
+    /// One of the standard devices, which is recreated
+    Stdio(u32),
+    /// The file is part of the captured filesystem and is opened again
+    Reopen,
+    /// The file lives outside of the checkpoint, and is opened again if
+    /// it still has the same size and modification time
+    Unchanged {
+        len: u64,
+        last_modified: u64,
+        position: u64,
+    },
+    /// The state saved by a [`VirtualFileCheckpointHandler`]
+    Custom { handler: String, data: Vec<u8> },
+}
+
+/// A checkpoint of a [`WasiState`] and, optionally, of the memory of the
+/// instance it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasiCheckpoint {
+    /// The frozen state: fd table, inode tree, preopens, args and envs
+    state: Vec<u8>,
+    /// The handles of the open files, by the inode that holds them
+    files: Vec<(Inode, FileCheckpoint)>,
+    /// The in-memory filesystem backing, as a tar archive
+    fs: Option<Vec<u8>>,
+    /// The contents of the linear memory
+    memory: Option<Vec<u8>>,
+}
+
+impl WasiCheckpoint {
+    /// Turn the checkpoint into bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WasiCheckpointError> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Get a checkpoint from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WasiCheckpointError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// The captured contents of the linear memory, if any
+    pub fn memory(&self) -> Option<&[u8]> {
+        self.memory.as_deref()
+    }
+
+    /// Set the contents of the linear memory, for instance when the memory
+    /// snapshot is taken separately from the rest of the state
+    pub fn set_memory(&mut self, memory: Option<Vec<u8>>) {
+        self.memory = memory;
+    }
+
+    /// Write the captured linear memory into `memory`, growing it if needed
+    pub fn restore_memory(
+        &self,
+        store: &mut impl AsStoreMut,
+        memory: &Memory,
+    ) -> Result<(), WasiCheckpointError> {
+        let data = match self.memory.as_ref() {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+
+        let current = memory.view(store).data_size();
+        if current < data.len() as u64 {
+            let missing =
+                (data.len() as u64 - current + WASM_PAGE_SIZE as u64 - 1) / WASM_PAGE_SIZE as u64;
+            memory
+                .grow(store, Pages(missing as u32))
+                .map_err(|err: MemoryError| WasiCheckpointError::MemoryError(err.to_string()))?;
+        }
+        memory
+            .view(store)
+            .write(0, data)
+            .map_err(|err| WasiCheckpointError::MemoryError(err.to_string()))
+    }
+}
+
+/// Takes and restores [`WasiCheckpoint`]s
+///
+/// Usage:
+///
+/// ```no_run
+/// # use wasmer_wasi::{WasiCheckpoint, WasiCheckpointer, WasiState};
+/// # fn run(state: &WasiState) -> Result<(), Box<dyn std::error::Error>> {
+/// let checkpointer = WasiCheckpointer::new();
+/// let bytes = checkpointer.checkpoint(state)?.to_bytes()?;
+///
+/// // ... possibly in another process
+/// let checkpoint = WasiCheckpoint::from_bytes(&bytes)?;
+/// let state = checkpointer.restore(&checkpoint, None)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct WasiCheckpointer {
+    handlers: Vec<Arc<dyn VirtualFileCheckpointHandler>>,
+}
+
+impl WasiCheckpointer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a handler for files that can't be captured by reading them.
+    ///
+    /// Handlers are tried in the order they were added.
+    pub fn add_handler<H>(&mut self, handler: H) -> &mut Self
+    where
+        H: VirtualFileCheckpointHandler + 'static,
+    {
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+
+    /// Take a checkpoint of the state.
+    ///
+    /// The position of the open files is not changed.
+    pub fn checkpoint(&self, state: &WasiState) -> Result<WasiCheckpoint, WasiCheckpointError> {
+        let fs = capture_fs_backing(state.fs.fs_backing.as_ref())?;
+
+        let mut files = Vec::new();
+        {
+            let mut guard = state.inodes.write().unwrap();
+            let inodes = &mut *guard;
+            let vals = inodes
+                .arena
+                .iter_mut()
+                .chain(inodes.orphan_fds.iter_mut().map(|(k, v)| (*k, v)));
+            for (inode, val) in vals {
+                if let Some(file) = self.capture_inode(val, fs.is_some())? {
+                    files.push((inode, file));
+                }
+            }
+        }
+
+        Ok(WasiCheckpoint {
+            state: bincode::serialize(state)?,
+            files,
+            fs,
+            memory: None,
+        })
+    }
+
+    /// Restore a state from a checkpoint.
+    ///
+    /// When no filesystem backing is given, the default one is used, or a
+    /// new in-memory filesystem if one was captured.
+    pub fn restore(
+        &self,
+        checkpoint: &WasiCheckpoint,
+        fs_backing: Option<Box<dyn FileSystem>>,
+    ) -> Result<WasiState, WasiCheckpointError> {
+        let mut state: WasiState = bincode::deserialize(&checkpoint.state)?;
+        state.fs.fs_backing = match checkpoint.fs.as_ref() {
+            Some(archive) => restore_fs_backing(fs_backing, archive)?,
+            None => fs_backing.unwrap_or_else(default_fs_backing),
+        };
+
+        let open_flags = inode_open_flags(&state.fs.fd_map.read().unwrap());
+        {
+            let mut inodes = state.inodes.write().unwrap();
+            for (inode, file) in checkpoint.files.iter() {
+                let val = inodes
+                    .get_inodeval_mut(*inode)
+                    .map_err(|_| FsError::EntityNotFound)?;
+                let flags = open_flags.get(inode).copied().unwrap_or(Fd::READ);
+                if let Kind::File { handle, path, .. } = val.kind.get_mut().unwrap() {
+                    let file =
+                        self.restore_file(file, path, flags, state.fs.fs_backing.as_ref())?;
+                    *handle = Some(file);
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
+    fn capture_inode(
+        &self,
+        val: &mut InodeVal,
+        fs_captured: bool,
+    ) -> Result<Option<FileCheckpoint>, WasiCheckpointError> {
+        let name = val.name.clone();
+        match val.kind.get_mut().unwrap() {
+            Kind::Socket { .. } | Kind::Pipe { .. } => Err(WasiCheckpointError::Unsupported(name)),
+            Kind::File {
+                handle: Some(handle),
+                fd,
+                ..
+            } => {
+                let handle = handle.as_mut();
+                for handler in self.handlers.iter() {
+                    if let Some(data) = handler.save(handle) {
+                        return Ok(Some(FileCheckpoint::Custom {
+                            handler: handler.name().to_string(),
+                            data: data?,
+                        }));
+                    }
+                }
+                match *fd {
+                    Some(raw_fd @ __WASI_STDIN_FILENO)
+                    | Some(raw_fd @ __WASI_STDOUT_FILENO)
+                    | Some(raw_fd @ __WASI_STDERR_FILENO) => {
+                        Ok(Some(FileCheckpoint::Stdio(raw_fd)))
+                    }
+                    _ if fs_captured => Ok(Some(FileCheckpoint::Reopen)),
+                    _ => Ok(Some(FileCheckpoint::Unchanged {
+                        len: handle.size(),
+                        last_modified: handle.last_modified(),
+                        position: handle.seek(SeekFrom::Current(0)).map_err(FsError::from)?,
+                    })),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn restore_file(
+        &self,
+        file: &FileCheckpoint,
+        path: &std::path::Path,
+        open_flags: u16,
+        fs_backing: &dyn FileSystem,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>, WasiCheckpointError> {
+        let write = open_flags & (Fd::WRITE | Fd::APPEND) != 0;
+        let reopen = || {
+            fs_backing
+                .new_open_options()
+                .read(open_flags & Fd::READ != 0)
+                .write(write)
+                .append(open_flags & Fd::APPEND != 0)
+                .open(path)
+        };
+        Ok(match file {
+            FileCheckpoint::Stdio(__WASI_STDIN_FILENO) => Box::new(super::Stdin::default()),
+            FileCheckpoint::Stdio(__WASI_STDOUT_FILENO) => Box::new(super::Stdout::default()),
+            FileCheckpoint::Stdio(_) => Box::new(super::Stderr::default()),
+            FileCheckpoint::Reopen => reopen()?,
+            FileCheckpoint::Unchanged {
+                len,
+                last_modified,
+                position,
+            } => {
+                let mut handle = reopen()?;
+                if handle.size() != *len || handle.last_modified() != *last_modified {
+                    return Err(WasiCheckpointError::FileChanged(
+                        path.to_string_lossy().into_owned(),
+                    ));
+                }
+                handle
+                    .seek(SeekFrom::Start(*position))
+                    .map_err(FsError::from)?;
+                handle
+            }
+            FileCheckpoint::Custom { handler, data } => self
+                .handlers
+                .iter()
+                .find(|h| h.name() == handler)
+                .ok_or_else(|| WasiCheckpointError::MissingHandler(handler.clone()))?
+                .restore(data)?,
+        })
+    }
+}
+
+/// The union of the open flags of the fds that point to each inode
+fn inode_open_flags(fd_map: &HashMap<u32, Fd>) -> HashMap<Inode, u16> {
+    let mut flags = HashMap::new();
+    for fd in fd_map.values() {
+        *flags.entry(fd.inode).or_insert(0) |= fd.open_flags;
+    }
+    flags
+}
+
+#[cfg(feature = "mem-fs")]
+fn capture_fs_backing(fs_backing: &dyn FileSystem) -> Result<Option<Vec<u8>>, FsError> {
+    match fs_backing
+        .upcast_any_ref()
+        .downcast_ref::<wasmer_vfs::mem_fs::FileSystem>()
+    {
+        Some(fs) => {
+            let mut archive = Vec::new();
+            fs.export_tar(&mut archive)?;
+            Ok(Some(archive))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "mem-fs"))]
+fn capture_fs_backing(_fs_backing: &dyn FileSystem) -> Result<Option<Vec<u8>>, FsError> {
+    Ok(None)
+}
+
+#[cfg(feature = "mem-fs")]
+fn restore_fs_backing(
+    fs_backing: Option<Box<dyn FileSystem>>,
+    archive: &[u8],
+) -> Result<Box<dyn FileSystem>, WasiCheckpointError> {
+    let fs_backing =
+        fs_backing.unwrap_or_else(|| Box::new(wasmer_vfs::mem_fs::FileSystem::default()));
+    fs_backing
+        .upcast_any_ref()
+        .downcast_ref::<wasmer_vfs::mem_fs::FileSystem>()
+        .ok_or(WasiCheckpointError::IncompatibleFileSystem)?
+        .import_tar(archive)?;
+    Ok(fs_backing)
+}
+
+#[cfg(not(feature = "mem-fs"))]
+fn restore_fs_backing(
+    _fs_backing: Option<Box<dyn FileSystem>>,
+    _archive: &[u8],
+) -> Result<Box<dyn FileSystem>, WasiCheckpointError> {
+    Err(WasiCheckpointError::IncompatibleFileSystem)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::Pipe;
+    use std::io::{Read, Write};
+
+    /// Saves a [`Pipe`] with the bytes it holds
+    #[derive(Debug)]
+    struct PipeHandler;
+
+    impl VirtualFileCheckpointHandler for PipeHandler {
+        fn name(&self) -> &str {
+            "pipe"
+        }
+
+        fn save(
+            &self,
+            file: &mut (dyn VirtualFile + Send + Sync + 'static),
+        ) -> Option<Result<Vec<u8>, FsError>> {
+            let pipe = file.upcast_any_mut().downcast_mut::<Pipe>()?;
+            let mut data = Vec::new();
+            Some(
+                pipe.read_to_end(&mut data)
+                    .map(|_| data)
+                    .map_err(FsError::from),
+            )
+        }
+
+        fn restore(
+            &self,
+            data: &[u8],
+        ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>, FsError> {
+            let mut pipe = Pipe::new();
+            pipe.write_all(data)?;
+            Ok(Box::new(pipe))
+        }
+    }
+
+    fn read_stdout(state: &WasiState) -> Vec<u8> {
+        let mut stdout = state.stdout().unwrap().unwrap();
+        let mut data = Vec::new();
+        stdout.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let mut stdout = Pipe::new();
+        stdout.write_all(b"buffered output").unwrap();
+        let state = WasiState::new("checkpoint")
+            .arg("--flag")
+            .env("KEY", "value")
+            .stdout(Box::new(stdout))
+            .build()
+            .unwrap();
+
+        let mut checkpointer = WasiCheckpointer::new();
+        checkpointer.add_handler(PipeHandler);
+        let bytes = checkpointer.checkpoint(&state).unwrap().to_bytes().unwrap();
+        let checkpoint = WasiCheckpoint::from_bytes(&bytes).unwrap();
+
+        assert!(matches!(
+            WasiCheckpointer::new().restore(&checkpoint, None),
+            Err(WasiCheckpointError::MissingHandler(_))
+        ));
+
+        let restored = checkpointer.restore(&checkpoint, None).unwrap();
+        assert_eq!(restored.args, state.args);
+        assert_eq!(restored.envs, state.envs);
+        assert_eq!(
+            *restored.fs.fd_map.read().unwrap().keys().max().unwrap(),
+            *state.fs.fd_map.read().unwrap().keys().max().unwrap()
+        );
+        assert_eq!(read_stdout(&restored), b"buffered output");
+    }
+
+    #[cfg(feature = "mem-fs")]
+    #[test]
+    fn files_outside_of_the_checkpoint_are_not_rewritten() {
+        let path = std::path::Path::new("/a.txt");
+        let fs = wasmer_vfs::mem_fs::FileSystem::default();
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)
+            .unwrap();
+        file.write_all(b"data").unwrap();
+        file.seek(SeekFrom::Start(2)).unwrap();
+        let checkpoint = FileCheckpoint::Unchanged {
+            len: file.size(),
+            last_modified: file.last_modified(),
+            position: 2,
+        };
+
+        let checkpointer = WasiCheckpointer::new();
+        let mut restored = checkpointer
+            .restore_file(&checkpoint, path, Fd::READ, &fs)
+            .unwrap();
+        let mut data = Vec::new();
+        restored.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"ta");
+
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b"more").unwrap();
+        assert!(matches!(
+            checkpointer.restore_file(&checkpoint, path, Fd::READ, &fs),
+            Err(WasiCheckpointError::FileChanged(_))
+        ));
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"datamore");
+    }
+}
@@ -16,14 +16,22 @@
 #![allow(clippy::cognitive_complexity, clippy::too_many_arguments)]
 
 mod builder;
+#[cfg(feature = "checkpoint")]
+mod checkpoint;
 mod guard;
 mod pipe;
+mod policy;
+mod reactor;
 mod socket;
 mod types;
 
 pub use self::builder::*;
+#[cfg(feature = "checkpoint")]
+pub use self::checkpoint::*;
 pub use self::guard::*;
 pub use self::pipe::*;
+pub use self::policy::*;
+pub(crate) use self::reactor::{inode_readiness, Reactor};
 pub use self::socket::*;
 pub use self::types::*;
 use crate::syscalls::types::*;
@@ -131,16 +139,6 @@ pub enum Kind {
         /// TOOD: clarify here?
         fd: Option<u32>,
     },
-    #[cfg_attr(feature = "enable-serde", serde(skip))]
-    Socket {
-        /// Represents a networking socket
-        socket: InodeSocket,
-    },
-    #[cfg_attr(feature = "enable-serde", serde(skip))]
-    Pipe {
-        /// Reference to the pipe
-        pipe: WasiPipe,
-    },
     Dir {
         /// Parent directory
         parent: Option<Inode>,
@@ -183,6 +181,18 @@ pub enum Kind {
         #[cfg_attr(feature = "enable-serde", serde(skip))]
         wakers: Arc<Mutex<VecDeque<mpsc::Sender<()>>>>,
     },
+    // The variants that aren't serialized come last, as serde numbers the
+    // variants it deserializes without them.
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    Socket {
+        /// Represents a networking socket
+        socket: InodeSocket,
+    },
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    Pipe {
+        /// Reference to the pipe
+        pipe: WasiPipe,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -248,13 +258,6 @@ impl WasiInodes {
         }
     }
 
-    /// Get the `VirtualFile` object at stdout
-    pub(crate) fn stdout(
-        &self,
-        fd_map: &RwLock<HashMap<u32, Fd>>,
-    ) -> Result<InodeValFileReadGuard, FsError> {
-        self.std_dev_get(fd_map, __WASI_STDOUT_FILENO)
-    }
     /// Get the `VirtualFile` object at stdout mutably
     pub(crate) fn stdout_mut(
         &self,
@@ -263,13 +266,6 @@ impl WasiInodes {
         self.std_dev_get_mut(fd_map, __WASI_STDOUT_FILENO)
     }
 
-    /// Get the `VirtualFile` object at stderr
-    pub(crate) fn stderr(
-        &self,
-        fd_map: &RwLock<HashMap<u32, Fd>>,
-    ) -> Result<InodeValFileReadGuard, FsError> {
-        self.std_dev_get(fd_map, __WASI_STDERR_FILENO)
-    }
     /// Get the `VirtualFile` object at stderr mutably
     pub(crate) fn stderr_mut(
         &self,
@@ -278,13 +274,6 @@ impl WasiInodes {
         self.std_dev_get_mut(fd_map, __WASI_STDERR_FILENO)
     }
 
-    /// Get the `VirtualFile` object at stdin
-    pub(crate) fn stdin(
-        &self,
-        fd_map: &RwLock<HashMap<u32, Fd>>,
-    ) -> Result<InodeValFileReadGuard, FsError> {
-        self.std_dev_get(fd_map, __WASI_STDIN_FILENO)
-    }
     /// Get the `VirtualFile` object at stdin mutably
     pub(crate) fn stdin_mut(
         &self,
@@ -293,26 +282,6 @@ impl WasiInodes {
         self.std_dev_get_mut(fd_map, __WASI_STDIN_FILENO)
     }
 
-    /// Internal helper function to get a standard device handle.
-    /// Expects one of `__WASI_STDIN_FILENO`, `__WASI_STDOUT_FILENO`, `__WASI_STDERR_FILENO`.
-    fn std_dev_get<'a>(
-        &'a self,
-        fd_map: &RwLock<HashMap<u32, Fd>>,
-        fd: WasiFd,
-    ) -> Result<InodeValFileReadGuard<'a>, FsError> {
-        if let Some(fd) = fd_map.read().unwrap().get(&fd) {
-            let guard = self.arena[fd.inode].read();
-            if let Kind::File { .. } = guard.deref() {
-                Ok(InodeValFileReadGuard { guard })
-            } else {
-                // Our public API should ensure that this is not possible
-                Err(FsError::NotAFile)
-            }
-        } else {
-            // this should only trigger if we made a mistake in this crate
-            Err(FsError::NoDevice)
-        }
-    }
     /// Internal helper function to mutably get a standard device handle.
     /// Expects one of `__WASI_STDIN_FILENO`, `__WASI_STDOUT_FILENO`, `__WASI_STDERR_FILENO`.
     fn std_dev_get_mut<'a>(
@@ -350,6 +319,9 @@ pub struct WasiFs {
     pub is_wasix: AtomicBool,
     #[cfg_attr(feature = "enable-serde", serde(skip, default = "default_fs_backing"))]
     pub fs_backing: Box<dyn FileSystem>,
+    /// Restricts what the module can do with its files
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub policy: FsPolicy,
 }
 
 /// Returns the default filesystem backing
@@ -597,6 +569,7 @@ impl WasiFs {
             current_dir: Mutex::new("/".to_string()),
             is_wasix: AtomicBool::new(false),
             fs_backing,
+            policy: FsPolicy::default(),
         };
         wasi_fs.create_stdin(inodes);
         wasi_fs.create_stdout(inodes);
@@ -749,6 +722,19 @@ impl WasiFs {
                     return Err(FsError::AlreadyExists);
                 }
 
+                // The file is supplied by the host, but it still shows up
+                // in the directory as if the module had created it
+                let file = match deref {
+                    Kind::Dir { path, .. } => {
+                        let file_path = path.join(&name);
+                        self.policy
+                            .check_write(&file_path)
+                            .map_err(fs_error_from_wasi_err)?;
+                        self.policy
+                            .limit_file(&file_path, file, flags.contains(Fdflags::APPEND))
+                    }
+                    _ => file,
+                };
                 let kind = Kind::File {
                     handle: Some(file),
                     path: PathBuf::from(""),
@@ -940,6 +926,8 @@ impl WasiFs {
                             "." => continue 'path_iter,
                             _ => (),
                         }
+                        // denied files can't be reached, even if they were loaded before
+                        self.policy.check_read(&path.join(component))?;
                         // used for full resolution of symlinks
                         let mut loop_for_symlink = false;
                         if let Some(entry) =
@@ -980,14 +968,18 @@ impl WasiFs {
                                 }
                             } else if file_type.is_symlink() {
                                 should_insert = false;
-                                let link_value = file.read_link().map_err(map_io_err)?;
+                                let link_value = self
+                                    .fs_backing
+                                    .readlink(&file)
+                                    .map_err(fs_error_into_wasi_err)?;
                                 debug!("attempting to decompose path {:?}", link_value);
 
-                                let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
-                                    self.path_into_pre_open_and_relative_path(inodes, &file)?
-                                } else {
-                                    unimplemented!("Absolute symlinks are not yet supported");
-                                };
+                                // absolute symlinks would lead out of the preopened directories
+                                if !link_value.is_relative() {
+                                    return Err(Errno::Notcapable);
+                                }
+                                let (pre_open_dir_fd, relative_path) =
+                                    self.path_into_pre_open_and_relative_path(inodes, &file)?;
                                 loop_for_symlink = true;
                                 symlink_count += 1;
                                 Kind::Symlink {
@@ -1754,6 +1746,36 @@ impl WasiFs {
 
         Ok(())
     }
+
+    /// Revokes the preopened directory `name` (its alias, if it has one)
+    /// while the module runs.
+    ///
+    /// The directory is removed from the preopens and its file descriptor
+    /// is closed, and [`Self::policy`] stops the module from reaching any
+    /// file under it from now on, including through the directories the
+    /// module opened in it. The files the module already opened stay
+    /// open, so that it can keep reading and writing them.
+    pub fn revoke_preopen(&self, inodes: &WasiInodes, name: &str) -> Result<(), Errno> {
+        let root_inode = self.get_fd_inode(VIRTUAL_ROOT_FD)?;
+        let inode = match inodes.arena[root_inode].write().deref_mut() {
+            Kind::Root { entries } => entries.remove(name).ok_or(Errno::Noent)?,
+            _ => return Err(Errno::Inval),
+        };
+        match inodes.arena[inode].read().deref() {
+            Kind::Dir { path, .. } => self.policy.revoke(path.clone()),
+            _ => return Err(Errno::Notdir),
+        }
+
+        let mut fd_map = self.fd_map.write().unwrap();
+        self.preopen_fds.write().unwrap().retain(|fd| {
+            let is_revoked = fd_map.get(fd).map(|fd| fd.inode) == Some(inode);
+            if is_revoked {
+                fd_map.remove(fd);
+            }
+            !is_revoked
+        });
+        Ok(())
+    }
 }
 
 // Implementations of direct to FS calls so that we can easily change their implementation
@@ -1762,13 +1784,23 @@ impl WasiState {
         &self,
         path: P,
     ) -> Result<wasmer_vfs::ReadDir, Errno> {
-        self.fs
+        self.fs.policy.check_read(path.as_ref())?;
+        let read_dir = self
+            .fs
             .fs_backing
             .read_dir(path.as_ref())
-            .map_err(fs_error_into_wasi_err)
+            .map_err(fs_error_into_wasi_err)?;
+        // Hide the entries the module can't reach
+        Ok(wasmer_vfs::ReadDir::new(
+            read_dir
+                .filter_map(Result::ok)
+                .filter(|entry| self.fs.policy.check_read(&entry.path).is_ok())
+                .collect(),
+        ))
     }
 
     pub(crate) fn fs_create_dir<P: AsRef<Path>>(&self, path: P) -> Result<(), Errno> {
+        self.fs.policy.check_write(path.as_ref())?;
         self.fs
             .fs_backing
             .create_dir(path.as_ref())
@@ -1776,6 +1808,7 @@ impl WasiState {
     }
 
     pub(crate) fn fs_remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<(), Errno> {
+        self.fs.policy.check_write(path.as_ref())?;
         self.fs
             .fs_backing
             .remove_dir(path.as_ref())
@@ -1787,6 +1820,8 @@ impl WasiState {
         from: P,
         to: Q,
     ) -> Result<(), Errno> {
+        self.fs.policy.check_write(from.as_ref())?;
+        self.fs.policy.check_write(to.as_ref())?;
         self.fs
             .fs_backing
             .rename(from.as_ref(), to.as_ref())
@@ -1794,12 +1829,25 @@ impl WasiState {
     }
 
     pub(crate) fn fs_remove_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Errno> {
+        self.fs.policy.check_write(path.as_ref())?;
         self.fs
             .fs_backing
             .remove_file(path.as_ref())
             .map_err(fs_error_into_wasi_err)
     }
 
+    pub(crate) fn fs_symlink<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        target: P,
+        link: Q,
+    ) -> Result<(), Errno> {
+        self.fs.policy.check_write(link.as_ref())?;
+        self.fs
+            .fs_backing
+            .symlink(target.as_ref(), link.as_ref())
+            .map_err(fs_error_into_wasi_err)
+    }
+
     pub(crate) fn fs_new_open_options(&self) -> OpenOptions {
         self.fs.fs_backing.new_open_options()
     }
@@ -1820,6 +1868,9 @@ pub(crate) struct WasiStateThreading {
     #[cfg_attr(feature = "enable-serde", serde(skip))]
     pub process_reuse: HashMap<Cow<'static, str>, WasiBusProcessId>,
     pub process_seed: u32,
+    /// Exit code of the process when a thread other than the main one
+    /// called `proc_exit`, which the other threads pick up when they yield
+    pub exit_code: Option<__wasi_exitcode_t>,
 }
 
 /// Top level data type containing all* the state with which WASI can
@@ -1869,6 +1920,9 @@ impl WasiState {
     }
 
     /// Turn the WasiState into bytes
+    ///
+    /// Open files and the contents of the filesystem are not included, use a
+    /// `WasiCheckpointer` (with the `checkpoint` feature) to capture them as well.
     #[cfg(feature = "enable-serde")]
     pub fn freeze(&self) -> Option<Vec<u8>> {
         bincode::serialize(self).ok()
@@ -2,12 +2,13 @@ use crate::syscalls::types::*;
 use crate::syscalls::{read_bytes, write_bytes};
 use bytes::{Buf, Bytes};
 use std::convert::TryInto;
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, Write};
 use std::ops::DerefMut;
 use std::sync::mpsc;
 use std::sync::Mutex;
 use wasmer::WasmSlice;
 use wasmer::{MemorySize, MemoryView};
+use wasmer_vfs::{FsError, VirtualFile};
 use wasmer_wasi_types::wasi::Errno;
 
 #[derive(Debug)]
@@ -50,13 +51,17 @@ impl WasiPipe {
                 let buf_len = buf.len();
                 if buf_len > 0 {
                     let reader = buf.as_ref();
-                    let read = read_bytes(reader, memory, iov).map(|_| buf_len as usize)?;
+                    let read = read_bytes(reader, memory, iov)?;
                     buf.advance(read);
                     return Ok(read);
                 }
             }
             let rx = self.rx.lock().unwrap();
-            let data = rx.recv().map_err(|_| Errno::Io)?;
+            // The other end being gone is the end of the stream
+            let data = match rx.recv() {
+                Ok(data) => data,
+                Err(_) => return Ok(0),
+            };
             self.read_buffer.replace(Bytes::from(data));
         }
     }
@@ -79,6 +84,27 @@ impl WasiPipe {
         Ok(buf_len)
     }
 
+    /// Whether a read wouldn't block, because the pipe has bytes to read
+    /// or its other end is gone
+    pub fn is_read_ready(&mut self) -> bool {
+        if self
+            .read_buffer
+            .as_ref()
+            .map_or(false, |buf| !buf.is_empty())
+        {
+            return true;
+        }
+        let rx = self.rx.lock().unwrap();
+        match rx.try_recv() {
+            Ok(data) => {
+                self.read_buffer.replace(Bytes::from(data));
+                true
+            }
+            Err(mpsc::TryRecvError::Empty) => false,
+            Err(mpsc::TryRecvError::Disconnected) => true,
+        }
+    }
+
     pub fn close(&mut self) {
         let (mut null_tx, _) = mpsc::channel();
         let (_, mut null_rx) = mpsc::channel();
@@ -101,19 +127,67 @@ impl Read for WasiPipe {
                 let buf_len = inner_buf.len();
                 if buf_len > 0 {
                     let mut reader = inner_buf.as_ref();
-                    let read = reader.read(buf).map(|_| buf_len as usize)?;
+                    let read = reader.read(buf)?;
                     inner_buf.advance(read);
                     return Ok(read);
                 }
             }
             let rx = self.rx.lock().unwrap();
-            let data = rx.recv().map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::BrokenPipe,
-                    "the wasi pipe is not connected".to_string(),
-                )
-            })?;
+            let data = match rx.recv() {
+                Ok(data) => data,
+                Err(_) => return Ok(0),
+            };
             self.read_buffer.replace(Bytes::from(data));
         }
     }
 }
+
+impl Write for WasiPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let tx = self.tx.lock().unwrap();
+        tx.send(buf.to_vec()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the wasi pipe is not connected".to_string(),
+            )
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for WasiPipe {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek in a pipe",
+        ))
+    }
+}
+
+impl VirtualFile for WasiPipe {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _len: u64) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), FsError> {
+        Ok(())
+    }
+    fn bytes_available_read(&self) -> Result<Option<usize>, FsError> {
+        Ok(self.read_buffer.as_ref().map(|buf| buf.len()))
+    }
+}
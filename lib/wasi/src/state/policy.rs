@@ -0,0 +1,393 @@
+//! Restricting what the module can do with the files of its preopened
+//! directories, beyond the rights of its file descriptors.
+//!
+//! A [`FsPolicy`] is a list of rules, each applying to the files under a
+//! path of the filesystem backing, spelled the same way as the preopened
+//! directories. The rules are checked while resolving paths, so that
+//! denied files can't be reached in any way, and before every change
+//! made to the filesystem: opening a file for writing, creating,
+//! removing and renaming entries. When several rules of the same kind
+//! apply to a path, the access of the closest directory wins, while all
+//! the denied patterns and the smallest size limit apply.
+//!
+//! The host can also revoke a directory while the module runs, after
+//! which its files can't be reached anymore. Files the module already
+//! opened in it stay open.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use wasmer_vfs::{FileDescriptor, FsError, VirtualFile};
+use wasmer_wasi_types::wasi::Errno;
+
+/// What the module may do with the files under a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsAccess {
+    /// The files can't be reached.
+    None,
+    /// The files can be read but not changed.
+    ReadOnly,
+    /// The files can be read and changed, as far as the rights of the
+    /// file descriptors allow.
+    ReadWrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FsRule {
+    Access(FsAccess),
+    Deny(String),
+    MaxFileSize(u64),
+}
+
+/// The rules restricting the accesses of the module to its files.
+///
+/// The clones of a policy, like the ones given to the processes spawned
+/// by the module, share the revoked directories.
+#[derive(Debug, Default, Clone)]
+pub struct FsPolicy {
+    rules: Vec<(PathBuf, FsRule)>,
+    revoked: Arc<RwLock<Vec<PathBuf>>>,
+}
+
+impl FsPolicy {
+    /// Create a policy without any rule, which lets the module read and
+    /// change all its files as far as the rights of its fds allow.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what the module may do with the files under `path`.
+    pub fn access(&mut self, path: impl Into<PathBuf>, access: FsAccess) -> &mut Self {
+        self.rules.push((path.into(), FsRule::Access(access)));
+        self
+    }
+
+    /// Hide the files and directories under `path` whose name matches
+    /// `pattern`, where `*` matches any sequence of characters and `?`
+    /// any single character, such as `*.key` or `.env`.
+    pub fn deny(&mut self, path: impl Into<PathBuf>, pattern: impl Into<String>) -> &mut Self {
+        self.rules.push((path.into(), FsRule::Deny(pattern.into())));
+        self
+    }
+
+    /// Fail the writes making a file under `path` larger than `bytes`.
+    pub fn max_file_size(&mut self, path: impl Into<PathBuf>, bytes: u64) -> &mut Self {
+        self.rules.push((path.into(), FsRule::MaxFileSize(bytes)));
+        self
+    }
+
+    /// Stop the module from reaching the files under `path`, typically a
+    /// preopened directory, from now on.
+    pub fn revoke(&self, path: impl Into<PathBuf>) {
+        self.revoked.write().unwrap().push(path.into());
+    }
+
+    /// Whether `path` is under a revoked directory.
+    pub fn is_revoked(&self, path: &Path) -> bool {
+        self.revoked
+            .read()
+            .unwrap()
+            .iter()
+            .any(|revoked| path.starts_with(revoked))
+    }
+
+    /// Checks that the module may reach `path`.
+    pub(crate) fn check_read(&self, path: &Path) -> Result<(), Errno> {
+        if self.is_revoked(path) || self.access_of(path) == FsAccess::None {
+            return Err(Errno::Access);
+        }
+        for (prefix, rule) in self.rules_for(path) {
+            if let FsRule::Deny(pattern) = rule {
+                let denied =
+                    path.strip_prefix(prefix)
+                        .unwrap_or(path)
+                        .components()
+                        .any(|component| match component {
+                            Component::Normal(name) => {
+                                glob_match(pattern.as_bytes(), name.to_string_lossy().as_bytes())
+                            }
+                            _ => false,
+                        });
+                if denied {
+                    return Err(Errno::Access);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the module may change `path`.
+    pub(crate) fn check_write(&self, path: &Path) -> Result<(), Errno> {
+        self.check_read(path)?;
+        match self.access_of(path) {
+            FsAccess::ReadOnly => Err(Errno::Rofs),
+            _ => Ok(()),
+        }
+    }
+
+    /// The largest size a file at `path` may grow to.
+    pub(crate) fn max_file_size_of(&self, path: &Path) -> Option<u64> {
+        self.rules_for(path)
+            .filter_map(|(_, rule)| match rule {
+                FsRule::MaxFileSize(bytes) => Some(*bytes),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Wraps a file opened at `path` so that it can't grow beyond the
+    /// size limit of the path, if there is one.
+    pub(crate) fn limit_file(
+        &self,
+        path: &Path,
+        file: Box<dyn VirtualFile + Send + Sync + 'static>,
+        append: bool,
+    ) -> Box<dyn VirtualFile + Send + Sync + 'static> {
+        match self.max_file_size_of(path) {
+            Some(max_size) => Box::new(SizeLimitedFile {
+                inner: file,
+                max_size,
+                append,
+            }),
+            None => file,
+        }
+    }
+
+    fn access_of(&self, path: &Path) -> FsAccess {
+        self.rules_for(path)
+            .filter_map(|(prefix, rule)| match rule {
+                FsRule::Access(access) => Some((prefix.components().count(), *access)),
+                _ => None,
+            })
+            // The last of the rules of the closest directory wins
+            .fold(
+                None,
+                |closest: Option<(usize, FsAccess)>, (depth, access)| match closest {
+                    Some((closest_depth, _)) if closest_depth > depth => closest,
+                    _ => Some((depth, access)),
+                },
+            )
+            .map(|(_, access)| access)
+            .unwrap_or(FsAccess::ReadWrite)
+    }
+
+    fn rules_for<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a (PathBuf, FsRule)> {
+        self.rules
+            .iter()
+            .filter(move |(prefix, _)| path.starts_with(prefix))
+    }
+}
+
+/// Matches a name against a pattern of `*` and `?` wildcards.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*` when a match fails
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(b'?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// A file failing the writes that would make it larger than a size.
+#[derive(Debug)]
+struct SizeLimitedFile {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+    max_size: u64,
+    append: bool,
+}
+
+impl VirtualFile for SizeLimitedFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<(), FsError> {
+        if new_size > self.max_size {
+            return Err(FsError::FileTooLarge);
+        }
+        self.inner.set_len(new_size)
+    }
+
+    fn unlink(&mut self) -> Result<(), FsError> {
+        self.inner.unlink()
+    }
+
+    fn sync_to_disk(&self) -> Result<(), FsError> {
+        self.inner.sync_to_disk()
+    }
+
+    fn bytes_available(&self) -> Result<usize, FsError> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>, FsError> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>, FsError> {
+        self.inner.bytes_available_write()
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+impl Read for SizeLimitedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for SizeLimitedFile {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+impl Write for SizeLimitedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The largest the file can be after the write: some file systems,
+        // like mem_fs, insert the written bytes at the cursor instead of
+        // overwriting the ones there.
+        let size = self.inner.size();
+        let start = if self.append {
+            size
+        } else {
+            size.max(self.inner.seek(SeekFrom::Current(0))?)
+        };
+        if start + buf.len() as u64 > self.max_size {
+            return Err(io::Error::new(io::ErrorKind::Other, FsError::FileTooLarge));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match(b"*.key", b"server.key"));
+        assert!(glob_match(b".env", b".env"));
+        assert!(glob_match(b"id_?sa*", b"id_rsa.pub"));
+        assert!(glob_match(b"*a*b", b"xaayb"));
+        assert!(!glob_match(b"*.key", b"server.keys"));
+        assert!(!glob_match(b"?", b""));
+    }
+
+    #[test]
+    fn read_only_except_tmp() {
+        let mut policy = FsPolicy::new();
+        policy
+            .access("/", FsAccess::ReadOnly)
+            .access("/tmp", FsAccess::ReadWrite)
+            .access("/secrets", FsAccess::None)
+            .deny("/", "*.key")
+            .max_file_size("/tmp", 10)
+            .max_file_size("/tmp/small", 5);
+
+        assert_eq!(policy.check_read(Path::new("/etc/hosts")), Ok(()));
+        assert_eq!(
+            policy.check_write(Path::new("/etc/hosts")),
+            Err(Errno::Rofs)
+        );
+        assert_eq!(policy.check_write(Path::new("/tmp/out")), Ok(()));
+        assert_eq!(
+            policy.check_read(Path::new("/secrets/a")),
+            Err(Errno::Access)
+        );
+        assert_eq!(
+            policy.check_read(Path::new("/tmp/server.key")),
+            Err(Errno::Access)
+        );
+        assert_eq!(
+            policy.check_read(Path::new("/etc/x.key/inner")),
+            Err(Errno::Access)
+        );
+
+        assert_eq!(policy.max_file_size_of(Path::new("/etc/hosts")), None);
+        assert_eq!(policy.max_file_size_of(Path::new("/tmp/a")), Some(10));
+        assert_eq!(policy.max_file_size_of(Path::new("/tmp/small/a")), Some(5));
+
+        policy.revoke("/tmp");
+        assert_eq!(policy.check_read(Path::new("/tmp/out")), Err(Errno::Access));
+        assert_eq!(policy.check_read(Path::new("/tmpfile")), Ok(()));
+    }
+
+    #[test]
+    fn size_limited_writes() {
+        use wasmer_vfs::FileSystem;
+
+        let fs = wasmer_vfs::mem_fs::FileSystem::default();
+        let mut policy = FsPolicy::new();
+        policy.max_file_size("/", 4);
+        let is_too_large = |err: io::Error| {
+            err.into_inner().unwrap().downcast_ref::<FsError>() == Some(&FsError::FileTooLarge)
+        };
+
+        for append in [false, true] {
+            let path = Path::new(if append { "/append" } else { "/write" });
+            let file = fs
+                .new_open_options()
+                .read(true)
+                .write(true)
+                .append(append)
+                .create(true)
+                .open(path)
+                .unwrap();
+            let mut file = policy.limit_file(path, file, append);
+
+            assert_eq!(file.write(b"abcd").unwrap(), 4);
+            assert!(is_too_large(file.write(b"e").unwrap_err()));
+            // mem_fs would insert the byte rather than overwrite the first one.
+            file.seek(SeekFrom::Start(0)).unwrap();
+            assert!(is_too_large(file.write(b"e").unwrap_err()));
+            assert_eq!(file.size(), 4);
+            assert_eq!(file.set_len(5), Err(FsError::FileTooLarge));
+        }
+    }
+}
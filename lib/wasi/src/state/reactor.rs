@@ -0,0 +1,432 @@
+//! The event loop `poll_oneoff` waits on.
+//!
+//! A [`Reactor`] waits until one of the host file descriptors registered
+//! to it is ready, until one of its wakers is called, or until a timeout.
+//! On Linux it is built on `epoll`, with an `eventfd` for the wakers, and
+//! waits on the host file descriptors of the files, like the standard
+//! streams, and of the sockets. Elsewhere it only waits on its wakers.
+//!
+//! The sources without a host file descriptor, like the virtual files,
+//! the pipes and the virtual sockets, are checked by [`inode_readiness`].
+//! The sockets among them call a waker of the reactor when they may have
+//! become ready, the others have to be checked again from time to time.
+
+use super::{Inode, Kind, WasiInodes};
+use crate::state::types::{fs_error_into_wasi_err, PollEvent, PollEventBuilder, PollEventSet};
+use std::ops::DerefMut;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use wasmer_vfs::FileDescriptor;
+use wasmer_vnet::SocketWaker;
+use wasmer_wasi_types::wasi::Errno;
+
+#[cfg(all(target_os = "linux", feature = "sys-poll"))]
+pub(crate) use self::epoll::Reactor;
+#[cfg(not(all(target_os = "linux", feature = "sys-poll")))]
+pub(crate) use self::portable::Reactor;
+
+#[cfg(all(target_os = "linux", feature = "sys-poll"))]
+mod epoll {
+    use super::*;
+    use crate::utils::map_io_err;
+    use std::io;
+    use std::os::unix::io::RawFd;
+    use std::sync::Arc;
+
+    /// The token of the `eventfd` of the wakers
+    const WAKER_TOKEN: u64 = u64::MAX;
+
+    /// An `eventfd`, closed once the reactor and all its wakers are gone
+    #[derive(Debug)]
+    struct EventFd(RawFd);
+
+    impl EventFd {
+        fn wake(&self) {
+            let one = 1u64;
+            unsafe { libc::write(self.0, &one as *const u64 as *const libc::c_void, 8) };
+        }
+
+        fn reset(&self) {
+            let mut count = 0u64;
+            unsafe { libc::read(self.0, &mut count as *mut u64 as *mut libc::c_void, 8) };
+        }
+    }
+
+    impl Drop for EventFd {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.0) };
+        }
+    }
+
+    /// A host file descriptor registered to the `epoll` instance, with the
+    /// tokens and the events each subscription to it is interested in
+    #[derive(Debug)]
+    struct Registration {
+        fd: RawFd,
+        subscriptions: Vec<(usize, PollEventSet)>,
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct Reactor {
+        epoll: RawFd,
+        event: Arc<EventFd>,
+        registrations: Vec<Registration>,
+    }
+
+    impl Reactor {
+        pub(crate) fn new() -> Result<Self, Errno> {
+            let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+            if epoll < 0 {
+                return Err(map_io_err(io::Error::last_os_error()));
+            }
+            let event = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+            if event < 0 {
+                unsafe { libc::close(epoll) };
+                return Err(map_io_err(io::Error::last_os_error()));
+            }
+            let reactor = Self {
+                epoll,
+                event: Arc::new(EventFd(event)),
+                registrations: Vec::new(),
+            };
+            reactor
+                .ctl(
+                    libc::EPOLL_CTL_ADD,
+                    event,
+                    libc::EPOLLIN as u32,
+                    WAKER_TOKEN,
+                )
+                .map_err(map_io_err)?;
+            Ok(reactor)
+        }
+
+        /// Registers a host file descriptor, whose `events` are reported
+        /// with `token`. Returns `false` if the file descriptor can't be
+        /// waited on, like the ones of regular files.
+        pub(crate) fn add_fd(
+            &mut self,
+            fd: FileDescriptor,
+            token: usize,
+            events: PollEventSet,
+        ) -> Result<bool, Errno> {
+            let fd = u32::from(fd) as RawFd;
+            // The same file descriptor can be subscribed to several times
+            let index = match self.registrations.iter().position(|r| r.fd == fd) {
+                Some(index) => index,
+                None => {
+                    self.registrations.push(Registration {
+                        fd,
+                        subscriptions: Vec::new(),
+                    });
+                    self.registrations.len() - 1
+                }
+            };
+            let registration = &mut self.registrations[index];
+            let op = if registration.subscriptions.is_empty() {
+                libc::EPOLL_CTL_ADD
+            } else {
+                libc::EPOLL_CTL_MOD
+            };
+            let interest = registration
+                .subscriptions
+                .iter()
+                .fold(events, |interest, (_, events)| interest | events);
+            let result = self.ctl(op, fd, to_epoll_events(interest), index as u64);
+            if result.is_ok() {
+                self.registrations[index]
+                    .subscriptions
+                    .push((token, events));
+            } else if self.registrations[index].subscriptions.is_empty() {
+                self.registrations.pop();
+            }
+            match result {
+                Ok(()) => Ok(true),
+                // Regular files are always ready, and aren't host file
+                // descriptors for some file systems
+                Err(err) if matches!(err.raw_os_error(), Some(libc::EPERM | libc::EBADF)) => {
+                    Ok(false)
+                }
+                Err(err) => Err(map_io_err(err)),
+            }
+        }
+
+        pub(crate) fn waker(&self) -> SocketWaker {
+            let event = self.event.clone();
+            Arc::new(move || event.wake())
+        }
+
+        /// Waits until a registered file descriptor is ready, a waker is
+        /// called, or for at most `timeout`, and returns the tokens and the
+        /// events of the ready file descriptors.
+        pub(crate) fn wait(
+            &mut self,
+            timeout: Option<Duration>,
+        ) -> Result<Vec<(usize, PollEventSet)>, Errno> {
+            let timeout = match timeout {
+                // Rounded up, to not wake up right before the deadline
+                Some(timeout) => {
+                    ((timeout.as_nanos() + 999_999) / 1_000_000).min(i32::MAX as u128) as i32
+                }
+                None => -1,
+            };
+            let mut events =
+                vec![libc::epoll_event { events: 0, u64: 0 }; self.registrations.len() + 1];
+            let n = unsafe {
+                libc::epoll_wait(
+                    self.epoll,
+                    events.as_mut_ptr(),
+                    events.len() as i32,
+                    timeout,
+                )
+            };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    return Ok(Vec::new());
+                }
+                return Err(map_io_err(err));
+            }
+
+            let mut ready = Vec::new();
+            for event in &events[..n as usize] {
+                let (token, revents) = (event.u64, event.events);
+                if token == WAKER_TOKEN {
+                    self.event.reset();
+                    continue;
+                }
+                let revents = from_epoll_events(revents);
+                for (token, events) in &self.registrations[token as usize].subscriptions {
+                    let always = PollEventBuilder::new()
+                        .add(PollEvent::PollError)
+                        .add(PollEvent::PollHangUp)
+                        .build();
+                    let seen = revents & (events | always);
+                    if seen != 0 {
+                        ready.push((*token, seen));
+                    }
+                }
+            }
+            Ok(ready)
+        }
+
+        fn ctl(&self, op: i32, fd: RawFd, events: u32, token: u64) -> io::Result<()> {
+            let mut event = libc::epoll_event { events, u64: token };
+            if unsafe { libc::epoll_ctl(self.epoll, op, fd, &mut event) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Reactor {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.epoll) };
+        }
+    }
+
+    fn to_epoll_events(events: PollEventSet) -> u32 {
+        let mut out = 0;
+        if events & PollEvent::PollIn as PollEventSet != 0 {
+            out |= libc::EPOLLIN;
+        }
+        if events & PollEvent::PollOut as PollEventSet != 0 {
+            out |= libc::EPOLLOUT;
+        }
+        out as u32
+    }
+
+    fn from_epoll_events(events: u32) -> PollEventSet {
+        let events = events as i32;
+        let mut builder = PollEventBuilder::new();
+        if events & libc::EPOLLIN != 0 {
+            builder = builder.add(PollEvent::PollIn);
+        }
+        if events & libc::EPOLLOUT != 0 {
+            builder = builder.add(PollEvent::PollOut);
+        }
+        if events & libc::EPOLLERR != 0 {
+            builder = builder.add(PollEvent::PollError);
+        }
+        if events & (libc::EPOLLHUP | libc::EPOLLRDHUP) != 0 {
+            builder = builder.add(PollEvent::PollHangUp);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "sys-poll")))]
+mod portable {
+    use super::*;
+    use std::sync::{Arc, Condvar, Mutex};
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Reactor {
+        woken: Arc<(Mutex<bool>, Condvar)>,
+    }
+
+    impl Reactor {
+        pub(crate) fn new() -> Result<Self, Errno> {
+            Ok(Self::default())
+        }
+
+        /// The host file descriptors can't be waited on, they are checked
+        /// like the other sources
+        pub(crate) fn add_fd(
+            &mut self,
+            _fd: FileDescriptor,
+            _token: usize,
+            _events: PollEventSet,
+        ) -> Result<bool, Errno> {
+            Ok(false)
+        }
+
+        pub(crate) fn waker(&self) -> SocketWaker {
+            let woken = self.woken.clone();
+            Arc::new(move || {
+                let (flag, condvar) = &*woken;
+                *flag.lock().unwrap() = true;
+                condvar.notify_all();
+            })
+        }
+
+        /// Waits until a waker is called, or for at most `timeout`
+        #[cfg(not(target_arch = "wasm32"))]
+        pub(crate) fn wait(
+            &mut self,
+            timeout: Option<Duration>,
+        ) -> Result<Vec<(usize, PollEventSet)>, Errno> {
+            let (flag, condvar) = &*self.woken;
+            let mut woken = flag.lock().unwrap();
+            match timeout {
+                Some(timeout) => {
+                    woken = condvar
+                        .wait_timeout_while(woken, timeout, |woken| !*woken)
+                        .unwrap()
+                        .0;
+                }
+                None => woken = condvar.wait_while(woken, |woken| !*woken).unwrap(),
+            }
+            *woken = false;
+            Ok(Vec::new())
+        }
+
+        /// Threads can't block on the web, the caller yields instead
+        #[cfg(target_arch = "wasm32")]
+        pub(crate) fn wait(
+            &mut self,
+            _timeout: Option<Duration>,
+        ) -> Result<Vec<(usize, PollEventSet)>, Errno> {
+            *self.woken.0.lock().unwrap() = false;
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Checks which of the `events` of a source without a host file descriptor
+/// are ready, and how many bytes can be read or written.
+///
+/// The files that can't tell how many bytes can be read or written, like
+/// regular files, are always ready.
+pub(crate) fn inode_readiness(
+    inodes: &WasiInodes,
+    inode: Inode,
+    events: PollEventSet,
+) -> Result<(PollEventSet, usize), Errno> {
+    let mut guard = inodes.arena[inode].write();
+    let (readable, writable, hangup, nbytes) = match guard.deref_mut() {
+        Kind::File {
+            handle: Some(file), ..
+        } => {
+            let read = file
+                .bytes_available_read()
+                .map_err(fs_error_into_wasi_err)?;
+            let write = file
+                .bytes_available_write()
+                .map_err(fs_error_into_wasi_err)?;
+            let nbytes = if events & PollEvent::PollIn as PollEventSet != 0 {
+                read
+            } else {
+                write
+            };
+            (
+                read != Some(0),
+                write != Some(0),
+                !file.is_open(),
+                nbytes.unwrap_or(0),
+            )
+        }
+        Kind::Pipe { pipe } => (pipe.is_read_ready(), true, false, 0),
+        Kind::Socket { socket } => match socket.readiness() {
+            Ok(readiness) => (readiness.readable, readiness.writable, readiness.hangup, 0),
+            // The sockets that can't tell are reported ready, so that the
+            // module finds out by itself
+            Err(Errno::Notsup) => (true, true, false, 0),
+            Err(err) => return Err(err),
+        },
+        Kind::EventNotifications { counter, .. } => {
+            (counter.load(Ordering::Acquire) > 0, true, false, 0)
+        }
+        _ => return Err(Errno::Badf),
+    };
+
+    let mut builder = PollEventBuilder::new();
+    if readable && events & PollEvent::PollIn as PollEventSet != 0 {
+        builder = builder.add(PollEvent::PollIn);
+    }
+    if writable && events & PollEvent::PollOut as PollEventSet != 0 {
+        builder = builder.add(PollEvent::PollOut);
+    }
+    if hangup {
+        builder = builder.add(PollEvent::PollHangUp);
+    }
+    Ok((builder.build(), nbytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waker_wakes_up_the_reactor() {
+        let mut reactor = Reactor::new().unwrap();
+        let waker = reactor.waker();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            waker();
+        });
+        // Would otherwise wait for a minute
+        let ready = reactor.wait(Some(Duration::from_secs(60))).unwrap();
+        assert!(ready.is_empty());
+    }
+
+    #[cfg(all(target_os = "linux", feature = "sys-poll"))]
+    #[test]
+    fn host_pipe_readiness() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let read = PollEventBuilder::new().add(PollEvent::PollIn).build();
+        let write = PollEventBuilder::new().add(PollEvent::PollOut).build();
+
+        let mut reactor = Reactor::new().unwrap();
+        assert!(reactor
+            .add_fd(FileDescriptor::from(fds[0] as u32), 0, read)
+            .unwrap());
+        assert!(reactor
+            .add_fd(FileDescriptor::from(fds[1] as u32), 1, write)
+            .unwrap());
+        assert_eq!(
+            reactor.wait(Some(Duration::ZERO)).unwrap(),
+            vec![(1, write)]
+        );
+
+        unsafe { libc::write(fds[1], b"x".as_ptr() as *const libc::c_void, 1) };
+        let mut ready = reactor.wait(Some(Duration::ZERO)).unwrap();
+        ready.sort_unstable();
+        assert_eq!(ready, vec![(0, read), (1, write)]);
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+}
@@ -13,8 +13,9 @@ use tracing::{debug, error, info, warn};
 use wasmer::{MemorySize, MemoryView, WasmPtr, WasmSlice};
 use wasmer_vnet::{net_error_into_io_err, TimeType};
 use wasmer_vnet::{
-    IpCidr, IpRoute, SocketHttpRequest, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
-    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
+    IpCidr, IpRoute, NetworkError, SocketDescriptor, SocketHttpRequest, SocketReadiness,
+    SocketWaker, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
 };
 use wasmer_wasi_types::wasi::{Addressfamily, Errno, Fdflags, OptionTag, Sockoption, Socktype};
 
@@ -341,6 +342,63 @@ impl InodeSocket {
         })
     }
 
+    /// The host socket backing this socket, if any, which `poll_oneoff`
+    /// waits on with the event loop of the host
+    pub fn poll_fd(&self) -> Option<SocketDescriptor> {
+        match &self.kind {
+            InodeSocketKind::TcpListener(socket) => socket.poll_fd(),
+            InodeSocketKind::TcpStream(socket) => socket.poll_fd(),
+            InodeSocketKind::UdpSocket(socket) => socket.poll_fd(),
+            InodeSocketKind::Raw(socket) => socket.poll_fd(),
+            InodeSocketKind::Icmp(socket) => socket.poll_fd(),
+            _ => None,
+        }
+    }
+
+    /// What can be done with the socket without blocking, for the sockets
+    /// that aren't backed by a host socket
+    pub fn readiness(&self) -> Result<SocketReadiness, Errno> {
+        let mut readiness = match &self.kind {
+            InodeSocketKind::TcpListener(socket) => socket.readiness(),
+            InodeSocketKind::TcpStream(socket) => socket.readiness(),
+            InodeSocketKind::UdpSocket(socket) => socket.readiness(),
+            InodeSocketKind::Raw(socket) => socket.readiness(),
+            InodeSocketKind::Icmp(socket) => socket.readiness(),
+            InodeSocketKind::PreSocket { .. } => return Err(Errno::Notconn),
+            InodeSocketKind::Closed => Ok(SocketReadiness {
+                hangup: true,
+                ..Default::default()
+            }),
+            _ => Err(NetworkError::Unsupported),
+        }
+        .map_err(net_error_into_wasi_err)?;
+        if self.has_buffered_data() {
+            readiness.readable = true;
+        }
+        Ok(readiness)
+    }
+
+    /// Whether some data was received but not read yet, which can be read
+    /// right away even if the host socket has nothing more to read
+    pub fn has_buffered_data(&self) -> bool {
+        self.read_buffer
+            .as_ref()
+            .map_or(false, |buf| !buf.is_empty())
+    }
+
+    /// Registers a waker called whenever the readiness of the socket may
+    /// have changed, returning `false` if the socket never calls it
+    pub fn set_waker(&mut self, waker: SocketWaker) -> bool {
+        match &mut self.kind {
+            InodeSocketKind::TcpListener(socket) => socket.set_waker(waker),
+            InodeSocketKind::TcpStream(socket) => socket.set_waker(waker),
+            InodeSocketKind::UdpSocket(socket) => socket.set_waker(waker),
+            InodeSocketKind::Raw(socket) => socket.set_waker(waker),
+            InodeSocketKind::Icmp(socket) => socket.set_waker(waker),
+            _ => false,
+        }
+    }
+
     pub fn http_status(&self) -> Result<WasiHttpStatus, Errno> {
         Ok(match &self.kind {
             InodeSocketKind::HttpRequest(http, ..) => {
@@ -511,6 +569,23 @@ impl InodeSocket {
         })
     }
 
+    pub fn socktype(&self) -> Result<Socktype, Errno> {
+        Ok(match &self.kind {
+            InodeSocketKind::PreSocket { ty, .. } => *ty,
+            InodeSocketKind::HttpRequest(..)
+            | InodeSocketKind::WebSocket(_)
+            | InodeSocketKind::TcpListener(_)
+            | InodeSocketKind::TcpStream(_) => Socktype::Stream,
+            InodeSocketKind::UdpSocket(_) => Socktype::Dgram,
+            InodeSocketKind::Icmp(_) | InodeSocketKind::Raw(_) => Socktype::Raw,
+            InodeSocketKind::Closed => return Err(Errno::Io),
+        })
+    }
+
+    pub fn is_listening(&self) -> bool {
+        matches!(self.kind, InodeSocketKind::TcpListener(_))
+    }
+
     pub fn set_send_buf_size(&mut self, size: usize) -> Result<(), Errno> {
         match &mut self.kind {
             InodeSocketKind::PreSocket { send_buf_size, .. } => {
@@ -831,6 +906,21 @@ impl InodeSocket {
         .map(|_| buf_len)
     }
 
+    /// Flushes the data sent to a connected socket.
+    pub fn flush(&mut self) -> Result<(), Errno> {
+        match &mut self.kind {
+            InodeSocketKind::WebSocket(sock) => sock.flush().map_err(net_error_into_wasi_err),
+            InodeSocketKind::Raw(sock) => sock.flush().map_err(net_error_into_wasi_err),
+            InodeSocketKind::TcpStream(sock) => {
+                VirtualTcpSocket::flush(sock.as_mut()).map_err(net_error_into_wasi_err)
+            }
+            InodeSocketKind::HttpRequest(..) | InodeSocketKind::UdpSocket(..) => Ok(()),
+            InodeSocketKind::PreSocket { .. } => Err(Errno::Notconn),
+            InodeSocketKind::Closed => Err(Errno::Io),
+            _ => Err(Errno::Notsup),
+        }
+    }
+
     pub fn send_to<M: MemorySize>(
         &mut self,
         memory: &MemoryView,
@@ -838,7 +928,16 @@ impl InodeSocket {
         addr: WasmPtr<__wasi_addr_port_t, M>,
     ) -> Result<usize, Errno> {
         let (addr_ip, addr_port) = read_ip_port(memory, addr)?;
-        let addr = SocketAddr::new(addr_ip, addr_port);
+        self.send_to_addr(memory, iov, SocketAddr::new(addr_ip, addr_port))
+    }
+
+    /// Sends the data to an address already read from the memory
+    pub fn send_to_addr<M: MemorySize>(
+        &mut self,
+        memory: &MemoryView,
+        iov: WasmSlice<__wasi_ciovec_t<M>>,
+        addr: SocketAddr,
+    ) -> Result<usize, Errno> {
         let buf_len: M::Offset = iov
             .iter()
             .filter_map(|a| a.read().ok())
@@ -861,6 +960,21 @@ impl InodeSocket {
         .map(|_| buf_len)
     }
 
+    /// Sends a datagram held by the host to an address
+    pub fn send_to_bytes(&mut self, buf: Bytes, addr: SocketAddr) -> Result<usize, Errno> {
+        let buf_len = buf.len();
+        match &mut self.kind {
+            InodeSocketKind::Icmp(sock) => sock.send_to(buf, addr).map_err(net_error_into_wasi_err),
+            InodeSocketKind::UdpSocket(sock) => {
+                sock.send_to(buf, addr).map_err(net_error_into_wasi_err)
+            }
+            InodeSocketKind::PreSocket { .. } => Err(Errno::Notconn),
+            InodeSocketKind::Closed => Err(Errno::Io),
+            _ => Err(Errno::Notsup),
+        }
+        .map(|_| buf_len)
+    }
+
     pub fn recv<M: MemorySize>(
         &mut self,
         memory: &MemoryView,
@@ -936,6 +1050,18 @@ impl InodeSocket {
         iov: WasmSlice<__wasi_iovec_t<M>>,
         addr: WasmPtr<__wasi_addr_port_t, M>,
     ) -> Result<usize, Errno> {
+        let (ret, peer) = self.recv_from_addr(memory, iov)?;
+        write_ip_port(memory, addr, peer.ip(), peer.port())?;
+        Ok(ret)
+    }
+
+    /// Receives data, returning the address it came from rather than
+    /// writing it to the memory
+    pub fn recv_from_addr<M: MemorySize>(
+        &mut self,
+        memory: &MemoryView,
+        iov: WasmSlice<__wasi_iovec_t<M>>,
+    ) -> Result<(usize, SocketAddr), Errno> {
         loop {
             if let Some(buf) = self.read_buffer.as_mut() {
                 if !buf.is_empty() {
@@ -944,8 +1070,10 @@ impl InodeSocket {
                     let peer = self
                         .read_addr
                         .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
-                    write_ip_port(memory, addr, peer.ip(), peer.port())?;
-                    return Ok(ret);
+                    // What doesn't fit in the buffers is dropped, as for
+                    // any datagram
+                    buf.clear();
+                    return Ok((ret, peer));
                 }
             }
             let rcv = match &mut self.kind {
@@ -962,6 +1090,28 @@ impl InodeSocket {
         }
     }
 
+    /// Receives a datagram for the host, with the address it came from
+    pub fn recv_from_bytes(&mut self) -> Result<(Bytes, SocketAddr), Errno> {
+        if let Some(buf) = self.read_buffer.take() {
+            if !buf.is_empty() {
+                let peer = self
+                    .read_addr
+                    .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+                return Ok((buf, peer));
+            }
+        }
+        let rcv = match &mut self.kind {
+            InodeSocketKind::Icmp(sock) => sock.recv_from().map_err(net_error_into_wasi_err)?,
+            InodeSocketKind::UdpSocket(sock) => {
+                sock.recv_from().map_err(net_error_into_wasi_err)?
+            }
+            InodeSocketKind::PreSocket { .. } => return Err(Errno::Notconn),
+            InodeSocketKind::Closed => return Err(Errno::Io),
+            _ => return Err(Errno::Notsup),
+        };
+        Ok((rcv.data, rcv.addr))
+    }
+
     pub fn shutdown(&mut self, how: std::net::Shutdown) -> Result<(), Errno> {
         use std::net::Shutdown;
         match &mut self.kind {
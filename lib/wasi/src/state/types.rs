@@ -1,13 +1,10 @@
 /// types for use in the WASI filesystem
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
-#[cfg(all(unix, feature = "sys-poll"))]
-use std::convert::TryInto;
 use std::{
     collections::VecDeque,
     io::{self, Read, Seek, Write},
     sync::{Arc, Mutex},
-    time::Duration,
 };
 use wasmer_vbus::BusError;
 use wasmer_wasi_types::wasi::{BusErrno, Errno};
@@ -42,6 +39,9 @@ pub fn fs_error_from_wasi_err(err: Errno) -> FsError {
         Errno::Again => FsError::WouldBlock,
         Errno::Nospc => FsError::WriteZero,
         Errno::Notempty => FsError::DirectoryNotEmpty,
+        Errno::Xdev => FsError::CrossDevice,
+        Errno::Dquot => FsError::QuotaExceeded,
+        Errno::Fbig => FsError::FileTooLarge,
         _ => FsError::UnknownError,
     }
 }
@@ -71,6 +71,10 @@ pub fn fs_error_into_wasi_err(fs_error: FsError) -> Errno {
         FsError::WouldBlock => Errno::Again,
         FsError::WriteZero => Errno::Nospc,
         FsError::DirectoryNotEmpty => Errno::Notempty,
+        FsError::SymlinkLoop => Errno::Loop,
+        FsError::CrossDevice => Errno::Xdev,
+        FsError::QuotaExceeded => Errno::Dquot,
+        FsError::FileTooLarge => Errno::Fbig,
         FsError::Lock | FsError::UnknownError => Errno::Io,
     }
 }
@@ -98,6 +102,7 @@ pub fn net_error_into_wasi_err(net_error: NetworkError) -> Errno {
         NetworkError::WouldBlock => Errno::Again,
         NetworkError::WriteZero => Errno::Nospc,
         NetworkError::Unsupported => Errno::Notsup,
+        NetworkError::InsufficientMemory => Errno::Nomem,
         NetworkError::UnknownError => Errno::Io,
     }
 }
@@ -219,40 +224,6 @@ pub fn iterate_poll_events(pes: PollEventSet) -> PollEventIter {
     PollEventIter { pes, i: 0 }
 }
 
-#[cfg(all(unix, feature = "sys-poll"))]
-fn poll_event_set_to_platform_poll_events(mut pes: PollEventSet) -> i16 {
-    let mut out = 0;
-    for i in 0..16 {
-        out |= match PollEvent::from_i16(pes & (1 << i)) {
-            Some(PollEvent::PollIn) => libc::POLLIN,
-            Some(PollEvent::PollOut) => libc::POLLOUT,
-            Some(PollEvent::PollError) => libc::POLLERR,
-            Some(PollEvent::PollHangUp) => libc::POLLHUP,
-            Some(PollEvent::PollInvalid) => libc::POLLNVAL,
-            _ => 0,
-        };
-        pes &= !(1 << i);
-    }
-    out
-}
-
-#[cfg(all(unix, feature = "sys-poll"))]
-fn platform_poll_events_to_pollevent_set(mut num: i16) -> PollEventSet {
-    let mut peb = PollEventBuilder::new();
-    for i in 0..16 {
-        peb = match num & (1 << i) {
-            libc::POLLIN => peb.add(PollEvent::PollIn),
-            libc::POLLOUT => peb.add(PollEvent::PollOut),
-            libc::POLLERR => peb.add(PollEvent::PollError),
-            libc::POLLHUP => peb.add(PollEvent::PollHangUp),
-            libc::POLLNVAL => peb.add(PollEvent::PollInvalid),
-            _ => peb,
-        };
-        num &= !(1 << i);
-    }
-    peb.build()
-}
-
 #[allow(dead_code)]
 impl PollEventBuilder {
     pub fn new() -> PollEventBuilder {
@@ -269,111 +240,6 @@ impl PollEventBuilder {
     }
 }
 
-#[cfg(all(unix, feature = "sys-poll"))]
-pub(crate) fn poll(
-    selfs: &[&(dyn VirtualFile + Send + Sync + 'static)],
-    events: &[PollEventSet],
-    seen_events: &mut [PollEventSet],
-    timeout: Duration,
-) -> Result<u32, FsError> {
-    if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
-        return Err(FsError::InvalidInput);
-    }
-    let mut fds = selfs
-        .iter()
-        .enumerate()
-        .filter_map(|(i, s)| s.get_fd().map(|rfd| (i, rfd)))
-        .map(|(i, host_fd)| libc::pollfd {
-            fd: host_fd.try_into().unwrap(),
-            events: poll_event_set_to_platform_poll_events(events[i]),
-            revents: 0,
-        })
-        .collect::<Vec<_>>();
-    let result = unsafe {
-        libc::poll(
-            fds.as_mut_ptr(),
-            selfs.len() as _,
-            timeout.as_millis() as i32,
-        )
-    };
-
-    if result < 0 {
-        // TODO: check errno and return value
-        return Err(FsError::IOError);
-    }
-    // convert result and write back values
-    for (i, fd) in fds.into_iter().enumerate() {
-        seen_events[i] = platform_poll_events_to_pollevent_set(fd.revents);
-    }
-    // unwrap is safe because we check for negative values above
-    Ok(result.try_into().unwrap())
-}
-
-#[cfg(any(not(unix), not(feature = "sys-poll")))]
-pub(crate) fn poll(
-    files: &[&(dyn VirtualFile + Send + Sync + 'static)],
-    events: &[PollEventSet],
-    seen_events: &mut [PollEventSet],
-    timeout: Duration,
-) -> Result<u32, FsError> {
-    if !(files.len() == events.len() && events.len() == seen_events.len()) {
-        tracing::debug!("the slice length of 'files', 'events' and 'seen_events' must be the same (files={}, events={}, seen_events={})", files.len(), events.len(), seen_events.len());
-        return Err(FsError::InvalidInput);
-    }
-
-    let mut ret = 0;
-    for n in 0..files.len() {
-        let mut builder = PollEventBuilder::new();
-
-        let file = files[n];
-        let can_read = file.bytes_available_read()?.map(|_| true).unwrap_or(false);
-        let can_write = file
-            .bytes_available_write()?
-            .map(|s| s > 0)
-            .unwrap_or(false);
-        let is_closed = file.is_open() == false;
-
-        tracing::debug!(
-            "poll_evt can_read={} can_write={} is_closed={}",
-            can_read,
-            can_write,
-            is_closed
-        );
-
-        for event in iterate_poll_events(events[n]) {
-            match event {
-                PollEvent::PollIn if can_read => {
-                    builder = builder.add(PollEvent::PollIn);
-                }
-                PollEvent::PollOut if can_write => {
-                    builder = builder.add(PollEvent::PollOut);
-                }
-                PollEvent::PollHangUp if is_closed => {
-                    builder = builder.add(PollEvent::PollHangUp);
-                }
-                PollEvent::PollInvalid if is_closed => {
-                    builder = builder.add(PollEvent::PollInvalid);
-                }
-                PollEvent::PollError if is_closed => {
-                    builder = builder.add(PollEvent::PollError);
-                }
-                _ => {}
-            }
-        }
-        let revents = builder.build();
-        if revents != 0 {
-            ret += 1;
-        }
-        seen_events[n] = revents;
-    }
-
-    if ret == 0 && timeout > Duration::ZERO {
-        return Err(FsError::WouldBlock);
-    }
-
-    Ok(ret)
-}
-
 pub trait WasiPath {}
 
 /// For piping stdio. Stores all output / input in a byte-vector.
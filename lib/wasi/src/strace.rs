@@ -0,0 +1,1046 @@
+//! Tracing of the WASI syscalls made by a guest, like `strace` does for
+//! the system calls of a native process.
+//!
+//! A [`SyscallTracer`] wraps every function of an import object and writes
+//! one line per call, with the decoded arguments (file descriptors, paths,
+//! flags, ...), the returned [`Errno`] and the time the call took.
+
+use crate::utils::wrap_imports;
+use crate::WasiEnv;
+use crate::WasiError;
+use std::fmt::{self, Write as _};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wasmer::{
+    AsStoreMut, AsStoreRef, FromToNativeWasmType, Function, FunctionEnv, Imports, Memory,
+    RuntimeError, Value,
+};
+use wasmer_wasi_types::wasi::Errno;
+
+/// The largest path read from the memory of the guest
+const MAX_PATH_LEN: u64 = 4096;
+
+/// How the traced syscalls are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StraceFormat {
+    /// One human readable line per syscall, such as
+    /// `[1] fd_close(fd=3) = success <0.000004s>`, where `1` is the thread
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl FromStr for StraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown strace format `{}`, expected `text` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// The type of an argument of a syscall, which tells how to decode it
+#[derive(Debug, Clone, Copy)]
+enum Param {
+    Fd,
+    Int,
+    Ptr,
+    /// A pointer to a string, whose length is the next argument
+    Path,
+    Clock,
+    Whence,
+    Fdflags,
+    Lookup,
+    Oflags,
+    Rights,
+}
+
+use Param::*;
+
+/// The arguments of the syscalls that are decoded, by syscall name and
+/// number of arguments.
+///
+/// Syscalls missing here, or whose arguments don't match the listed ones,
+/// are traced with raw arguments.
+const SYSCALLS: &[(&str, &[(&str, Param)])] = &[
+    ("args_get", &[("argv", Ptr), ("argv_buf", Ptr)]),
+    ("args_sizes_get", &[("argc", Ptr), ("argv_buf_size", Ptr)]),
+    ("environ_get", &[("environ", Ptr), ("environ_buf", Ptr)]),
+    (
+        "environ_sizes_get",
+        &[("environ_count", Ptr), ("environ_buf_size", Ptr)],
+    ),
+    ("clock_res_get", &[("clock_id", Clock), ("resolution", Ptr)]),
+    (
+        "clock_time_get",
+        &[("clock_id", Clock), ("precision", Int), ("time", Ptr)],
+    ),
+    (
+        "fd_advise",
+        &[("fd", Fd), ("offset", Int), ("len", Int), ("advice", Int)],
+    ),
+    ("fd_allocate", &[("fd", Fd), ("offset", Int), ("len", Int)]),
+    ("fd_close", &[("fd", Fd)]),
+    ("fd_datasync", &[("fd", Fd)]),
+    ("fd_fdstat_get", &[("fd", Fd), ("buf", Ptr)]),
+    ("fd_fdstat_set_flags", &[("fd", Fd), ("flags", Fdflags)]),
+    (
+        "fd_fdstat_set_rights",
+        &[
+            ("fd", Fd),
+            ("fs_rights_base", Rights),
+            ("fs_rights_inheriting", Rights),
+        ],
+    ),
+    ("fd_filestat_get", &[("fd", Fd), ("buf", Ptr)]),
+    ("fd_filestat_set_size", &[("fd", Fd), ("size", Int)]),
+    (
+        "fd_filestat_set_times",
+        &[("fd", Fd), ("atim", Int), ("mtim", Int), ("fst_flags", Int)],
+    ),
+    (
+        "fd_pread",
+        &[
+            ("fd", Fd),
+            ("iovs", Ptr),
+            ("iovs_len", Int),
+            ("offset", Int),
+            ("nread", Ptr),
+        ],
+    ),
+    ("fd_prestat_get", &[("fd", Fd), ("buf", Ptr)]),
+    (
+        "fd_prestat_dir_name",
+        &[("fd", Fd), ("path", Ptr), ("path_len", Int)],
+    ),
+    (
+        "fd_pwrite",
+        &[
+            ("fd", Fd),
+            ("iovs", Ptr),
+            ("iovs_len", Int),
+            ("offset", Int),
+            ("nwritten", Ptr),
+        ],
+    ),
+    (
+        "fd_read",
+        &[("fd", Fd), ("iovs", Ptr), ("iovs_len", Int), ("nread", Ptr)],
+    ),
+    (
+        "fd_readdir",
+        &[
+            ("fd", Fd),
+            ("buf", Ptr),
+            ("buf_len", Int),
+            ("cookie", Int),
+            ("bufused", Ptr),
+        ],
+    ),
+    ("fd_renumber", &[("fd", Fd), ("to", Fd)]),
+    (
+        "fd_seek",
+        &[
+            ("fd", Fd),
+            ("offset", Int),
+            ("whence", Whence),
+            ("newoffset", Ptr),
+        ],
+    ),
+    ("fd_sync", &[("fd", Fd)]),
+    ("fd_tell", &[("fd", Fd), ("offset", Ptr)]),
+    (
+        "fd_write",
+        &[
+            ("fd", Fd),
+            ("iovs", Ptr),
+            ("iovs_len", Int),
+            ("nwritten", Ptr),
+        ],
+    ),
+    ("path_create_directory", &[("fd", Fd), ("path", Path)]),
+    (
+        "path_filestat_get",
+        &[("fd", Fd), ("flags", Lookup), ("path", Path), ("buf", Ptr)],
+    ),
+    (
+        "path_filestat_set_times",
+        &[
+            ("fd", Fd),
+            ("flags", Lookup),
+            ("path", Path),
+            ("atim", Int),
+            ("mtim", Int),
+            ("fst_flags", Int),
+        ],
+    ),
+    (
+        "path_link",
+        &[
+            ("old_fd", Fd),
+            ("old_flags", Lookup),
+            ("old_path", Path),
+            ("new_fd", Fd),
+            ("new_path", Path),
+        ],
+    ),
+    (
+        "path_open",
+        &[
+            ("fd", Fd),
+            ("dirflags", Lookup),
+            ("path", Path),
+            ("oflags", Oflags),
+            ("fs_rights_base", Rights),
+            ("fs_rights_inheriting", Rights),
+            ("fdflags", Fdflags),
+            ("opened_fd", Ptr),
+        ],
+    ),
+    (
+        "path_readlink",
+        &[
+            ("fd", Fd),
+            ("path", Path),
+            ("buf", Ptr),
+            ("buf_len", Int),
+            ("bufused", Ptr),
+        ],
+    ),
+    ("path_remove_directory", &[("fd", Fd), ("path", Path)]),
+    (
+        "path_rename",
+        &[
+            ("old_fd", Fd),
+            ("old_path", Path),
+            ("new_fd", Fd),
+            ("new_path", Path),
+        ],
+    ),
+    (
+        "path_symlink",
+        &[("old_path", Path), ("fd", Fd), ("new_path", Path)],
+    ),
+    ("path_unlink_file", &[("fd", Fd), ("path", Path)]),
+    (
+        "poll_oneoff",
+        &[
+            ("in", Ptr),
+            ("out", Ptr),
+            ("nsubscriptions", Int),
+            ("nevents", Ptr),
+        ],
+    ),
+    ("proc_exit", &[("rval", Int)]),
+    ("proc_raise", &[("sig", Int)]),
+    ("sched_yield", &[]),
+    ("random_get", &[("buf", Ptr), ("buf_len", Int)]),
+    (
+        "sock_recv",
+        &[
+            ("fd", Fd),
+            ("ri_data", Ptr),
+            ("ri_data_len", Int),
+            ("ri_flags", Int),
+            ("ro_datalen", Ptr),
+            ("ro_flags", Ptr),
+        ],
+    ),
+    (
+        "sock_send",
+        &[
+            ("fd", Fd),
+            ("si_data", Ptr),
+            ("si_data_len", Int),
+            ("si_flags", Int),
+            ("so_datalen", Ptr),
+        ],
+    ),
+    ("sock_shutdown", &[("fd", Fd), ("how", Int)]),
+    (
+        "sock_accept",
+        &[("fd", Fd), ("flags", Fdflags), ("ro_fd", Ptr)],
+    ),
+    ("thread-spawn", &[("start_arg", Ptr)]),
+    // WASIX
+    ("fd_dup", &[("fd", Fd), ("ret_fd", Ptr)]),
+    (
+        "fd_event",
+        &[("initial_val", Int), ("flags", Int), ("ret_fd", Ptr)],
+    ),
+    ("fd_pipe", &[("ro_fd1", Ptr), ("ro_fd2", Ptr)]),
+    ("tty_get", &[("tty_state", Ptr)]),
+    ("tty_set", &[("tty_state", Ptr)]),
+    ("getcwd", &[("path", Ptr), ("path_len", Ptr)]),
+    ("chdir", &[("path", Path)]),
+    (
+        "thread_spawn",
+        &[
+            ("method", Path),
+            ("user_data", Int),
+            ("reactor", Int),
+            ("ret_tid", Ptr),
+        ],
+    ),
+    ("thread_sleep", &[("duration", Int)]),
+    ("thread_id", &[("ret_tid", Ptr)]),
+    ("thread_join", &[("tid", Int)]),
+    ("thread_parallelism", &[("ret_parallelism", Ptr)]),
+    ("thread_exit", &[("exitcode", Int)]),
+    ("getpid", &[("ret_pid", Ptr)]),
+    (
+        "process_spawn",
+        &[
+            ("name", Path),
+            ("chroot", Int),
+            ("args", Path),
+            ("preopen", Path),
+            ("stdin", Int),
+            ("stdout", Int),
+            ("stderr", Int),
+            ("working_dir", Path),
+            ("ret_handles", Ptr),
+        ],
+    ),
+    (
+        "bus_open_local",
+        &[("name", Path), ("reuse", Int), ("ret_bid", Ptr)],
+    ),
+    (
+        "bus_open_remote",
+        &[
+            ("name", Path),
+            ("reuse", Int),
+            ("instance", Path),
+            ("token", Path),
+            ("ret_bid", Ptr),
+        ],
+    ),
+    ("bus_close", &[("bid", Int)]),
+    (
+        "bus_call",
+        &[
+            ("bid", Int),
+            ("keep_alive", Int),
+            ("topic", Path),
+            ("format", Int),
+            ("buf", Ptr),
+            ("buf_len", Int),
+            ("ret_cid", Ptr),
+        ],
+    ),
+    (
+        "bus_subcall",
+        &[
+            ("parent", Int),
+            ("keep_alive", Int),
+            ("topic", Path),
+            ("format", Int),
+            ("buf", Ptr),
+            ("buf_len", Int),
+            ("ret_cid", Ptr),
+        ],
+    ),
+    (
+        "bus_poll",
+        &[
+            ("timeout", Int),
+            ("events", Ptr),
+            ("nevents", Int),
+            ("malloc", Ptr),
+            ("malloc_len", Int),
+            ("ret_nevents", Ptr),
+        ],
+    ),
+    (
+        "call_reply",
+        &[
+            ("cid", Int),
+            ("format", Int),
+            ("buf", Ptr),
+            ("buf_len", Int),
+        ],
+    ),
+    ("call_fault", &[("cid", Int), ("fault", Int)]),
+    ("call_close", &[("cid", Int)]),
+    ("ws_connect", &[("url", Path), ("ret_sock", Ptr)]),
+    (
+        "http_request",
+        &[
+            ("url", Path),
+            ("method", Path),
+            ("headers", Path),
+            ("gzip", Int),
+            ("ret_handles", Ptr),
+        ],
+    ),
+    ("http_status", &[("fd", Fd), ("status", Ptr)]),
+    (
+        "port_bridge",
+        &[("network", Path), ("token", Path), ("security", Int)],
+    ),
+    ("port_unbridge", &[]),
+    ("port_dhcp_acquire", &[]),
+    ("port_addr_add", &[("ip", Ptr)]),
+    ("port_addr_remove", &[("ip", Ptr)]),
+    ("port_addr_clear", &[]),
+    ("port_addr_list", &[("addrs", Ptr), ("naddrs", Ptr)]),
+    ("port_mac", &[("ret_mac", Ptr)]),
+    ("port_gateway_set", &[("ip", Ptr)]),
+    (
+        "port_route_add",
+        &[
+            ("cidr", Ptr),
+            ("via_router", Ptr),
+            ("preferred_until", Ptr),
+            ("expires_at", Ptr),
+        ],
+    ),
+    ("port_route_remove", &[("ip", Ptr)]),
+    ("port_route_clear", &[]),
+    ("port_route_list", &[("routes", Ptr), ("nroutes", Ptr)]),
+    ("sock_status", &[("fd", Fd), ("ret_status", Ptr)]),
+    ("sock_addr_local", &[("fd", Fd), ("ret_addr", Ptr)]),
+    ("sock_addr_peer", &[("fd", Fd), ("ro_addr", Ptr)]),
+    (
+        "sock_open",
+        &[("af", Int), ("ty", Int), ("pt", Int), ("ro_sock", Ptr)],
+    ),
+    (
+        "sock_set_opt_flag",
+        &[("fd", Fd), ("opt", Int), ("flag", Int)],
+    ),
+    (
+        "sock_get_opt_flag",
+        &[("fd", Fd), ("opt", Int), ("ret_flag", Ptr)],
+    ),
+    (
+        "sock_set_opt_time",
+        &[("fd", Fd), ("opt", Int), ("time", Ptr)],
+    ),
+    (
+        "sock_get_opt_time",
+        &[("fd", Fd), ("opt", Int), ("ret_time", Ptr)],
+    ),
+    (
+        "sock_set_opt_size",
+        &[("fd", Fd), ("opt", Int), ("size", Int)],
+    ),
+    (
+        "sock_get_opt_size",
+        &[("fd", Fd), ("opt", Int), ("ret_size", Ptr)],
+    ),
+    (
+        "sock_join_multicast_v4",
+        &[("fd", Fd), ("multiaddr", Ptr), ("iface", Ptr)],
+    ),
+    (
+        "sock_leave_multicast_v4",
+        &[("fd", Fd), ("multiaddr", Ptr), ("iface", Ptr)],
+    ),
+    (
+        "sock_join_multicast_v6",
+        &[("fd", Fd), ("multiaddr", Ptr), ("iface", Int)],
+    ),
+    (
+        "sock_leave_multicast_v6",
+        &[("fd", Fd), ("multiaddr", Ptr), ("iface", Int)],
+    ),
+    ("sock_bind", &[("fd", Fd), ("addr", Ptr)]),
+    ("sock_listen", &[("fd", Fd), ("backlog", Int)]),
+    (
+        "sock_accept",
+        &[
+            ("fd", Fd),
+            ("fd_flags", Fdflags),
+            ("ro_fd", Ptr),
+            ("ro_addr", Ptr),
+        ],
+    ),
+    ("sock_connect", &[("fd", Fd), ("addr", Ptr)]),
+    (
+        "sock_recv_from",
+        &[
+            ("fd", Fd),
+            ("ri_data", Ptr),
+            ("ri_data_len", Int),
+            ("ri_flags", Int),
+            ("ro_data_len", Ptr),
+            ("ro_flags", Ptr),
+            ("ro_addr", Ptr),
+        ],
+    ),
+    (
+        "sock_send_to",
+        &[
+            ("fd", Fd),
+            ("si_data", Ptr),
+            ("si_data_len", Int),
+            ("si_flags", Int),
+            ("addr", Ptr),
+            ("ret_data_len", Ptr),
+        ],
+    ),
+    (
+        "sock_send_file",
+        &[
+            ("out_fd", Fd),
+            ("in_fd", Fd),
+            ("offset", Int),
+            ("count", Int),
+            ("ret_sent", Ptr),
+        ],
+    ),
+    (
+        "resolve",
+        &[
+            ("host", Path),
+            ("port", Int),
+            ("addrs", Ptr),
+            ("naddrs", Int),
+            ("ret_naddrs", Ptr),
+        ],
+    ),
+    // The socket functions of WasmEdge, told apart from the WASIX ones
+    // of the same name by their number of arguments
+    ("sock_open", &[("af", Int), ("ty", Int), ("ro_sock", Ptr)]),
+    ("sock_bind", &[("fd", Fd), ("addr", Ptr), ("port", Int)]),
+    ("sock_accept", &[("fd", Fd), ("ro_fd", Ptr)]),
+    ("sock_connect", &[("fd", Fd), ("addr", Ptr), ("port", Int)]),
+    (
+        "sock_recv_from",
+        &[
+            ("fd", Fd),
+            ("ri_data", Ptr),
+            ("ri_data_len", Int),
+            ("ro_addr", Ptr),
+            ("ri_flags", Int),
+            ("ro_port", Ptr),
+            ("ro_data_len", Ptr),
+            ("ro_flags", Ptr),
+        ],
+    ),
+    (
+        "sock_send_to",
+        &[
+            ("fd", Fd),
+            ("si_data", Ptr),
+            ("si_data_len", Int),
+            ("addr", Ptr),
+            ("port", Int),
+            ("si_flags", Int),
+            ("ret_data_len", Ptr),
+        ],
+    ),
+    (
+        "sock_getlocaladdr",
+        &[
+            ("fd", Fd),
+            ("ro_addr", Ptr),
+            ("ro_addr_type", Ptr),
+            ("ro_port", Ptr),
+        ],
+    ),
+    (
+        "sock_getpeeraddr",
+        &[
+            ("fd", Fd),
+            ("ro_addr", Ptr),
+            ("ro_addr_type", Ptr),
+            ("ro_port", Ptr),
+        ],
+    ),
+    (
+        "sock_getsockopt",
+        &[
+            ("fd", Fd),
+            ("level", Int),
+            ("name", Int),
+            ("ro_flag", Ptr),
+            ("ro_flag_size", Ptr),
+        ],
+    ),
+    (
+        "sock_setsockopt",
+        &[
+            ("fd", Fd),
+            ("level", Int),
+            ("name", Int),
+            ("flag", Ptr),
+            ("flag_size", Int),
+        ],
+    ),
+    (
+        "sock_getaddrinfo",
+        &[
+            ("node", Path),
+            ("service", Path),
+            ("hints", Ptr),
+            ("res", Ptr),
+            ("max_len", Int),
+            ("res_len", Ptr),
+        ],
+    ),
+];
+
+/// A decoded argument of a syscall
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    Int(i128),
+    Ptr(u64),
+    Float(f64),
+    Str(String),
+    Name(String),
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(v) => write!(f, "{}", v),
+            Self::Ptr(v) => write!(f, "{:#x}", v),
+            Self::Float(v) => write!(f, "{}", v),
+            Self::Str(v) => write!(f, "{:?}", v),
+            Self::Name(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// How a traced syscall returned to the guest
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Errno(Errno),
+    Values(Vec<Arg>),
+    Exit(u32),
+    Trap(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Errno(errno) => write!(f, "{}", errno.name()),
+            Self::Values(values) if values.is_empty() => write!(f, "()"),
+            Self::Values(values) => {
+                let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}", values.join(", "))
+            }
+            Self::Exit(code) => write!(f, "exit({})", code),
+            Self::Trap(message) => write!(f, "trap({:?})", message),
+        }
+    }
+}
+
+/// A traced syscall
+#[derive(Debug, Clone)]
+struct Event {
+    tid: u32,
+    name: String,
+    args: Vec<(String, Arg)>,
+    outcome: Outcome,
+    duration: Duration,
+}
+
+impl Event {
+    fn to_text(&self) -> String {
+        let args: Vec<_> = self
+            .args
+            .iter()
+            .map(|(name, arg)| format!("{}={}", name, arg))
+            .collect();
+        format!(
+            "[{}] {}({}) = {} <{:.6}s>",
+            self.tid,
+            self.name,
+            args.join(", "),
+            self.outcome,
+            self.duration.as_secs_f64()
+        )
+    }
+
+    fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"tid\":{},\"syscall\":{},\"args\":{{",
+            self.tid,
+            json_string(&self.name)
+        );
+        for (i, (name, arg)) in self.args.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{}:{}", json_string(name), json_arg(arg));
+        }
+        json.push_str("},");
+        match &self.outcome {
+            Outcome::Errno(errno) => {
+                let _ = write!(json, "\"errno\":{}", json_string(errno.name()));
+            }
+            Outcome::Values(values) => {
+                let values: Vec<_> = values.iter().map(json_arg).collect();
+                let _ = write!(json, "\"results\":[{}]", values.join(","));
+            }
+            Outcome::Exit(code) => {
+                let _ = write!(json, "\"exit\":{}", code);
+            }
+            Outcome::Trap(message) => {
+                let _ = write!(json, "\"trap\":{}", json_string(message));
+            }
+        }
+        let _ = write!(json, ",\"duration_ns\":{}}}", self.duration.as_nanos());
+        json
+    }
+}
+
+fn json_arg(arg: &Arg) -> String {
+    match arg {
+        Arg::Int(v) => v.to_string(),
+        Arg::Ptr(v) => v.to_string(),
+        Arg::Float(v) if v.is_finite() => v.to_string(),
+        Arg::Float(v) => json_string(&v.to_string()),
+        Arg::Str(v) | Arg::Name(v) => json_string(v),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Writes the syscalls made through the import objects it wraps
+#[derive(Clone)]
+pub struct SyscallTracer {
+    format: StraceFormat,
+    output: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl fmt::Debug for SyscallTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyscallTracer")
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl SyscallTracer {
+    pub fn new<W>(format: StraceFormat, output: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            format,
+            output: Arc::new(Mutex::new(Box::new(output))),
+        }
+    }
+
+    /// Wrap the functions of an import object, such as the one returned by
+    /// [`generate_import_object_from_env`](crate::generate_import_object_from_env),
+    /// so that the calls made to them are traced.
+    pub fn wrap(
+        &self,
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<WasiEnv>,
+        imports: &Imports,
+    ) -> Imports {
+        wrap_imports(store, imports, |store, namespace, name, inner| {
+            let tracer = self.clone();
+            let ty = inner.ty(&*store);
+            let params = syscall_params(&name, ty.params().len());
+            let returns_errno = namespace != crate::utils::WASI_THREADS_NAMESPACE
+                && !returns_bus_errno(&name)
+                && ty.results() == [wasmer::Type::I32];
+            Function::new_with_env(store, env, ty, move |mut ctx, args| {
+                let memory = ctx.data().memory.clone();
+                let tid = u32::from(ctx.data().id);
+                let decoded = decode_args(&ctx, memory.as_ref(), &namespace, params, args);
+
+                let start = Instant::now();
+                let result = inner.call(&mut ctx, args);
+                let duration = start.elapsed();
+
+                let (outcome, result) = match result {
+                    Ok(results) => (
+                        decode_results(&results, returns_errno),
+                        Ok(results.into_vec()),
+                    ),
+                    Err(err) => match err.downcast::<WasiError>() {
+                        Ok(WasiError::Exit(code)) => (
+                            Outcome::Exit(code),
+                            Err(RuntimeError::user(Box::new(WasiError::Exit(code)))),
+                        ),
+                        Ok(err) => (
+                            Outcome::Trap(err.to_string()),
+                            Err(RuntimeError::user(Box::new(err))),
+                        ),
+                        Err(err) => (Outcome::Trap(err.message()), Err(err)),
+                    },
+                };
+
+                tracer.emit(&Event {
+                    tid,
+                    name: name.clone(),
+                    args: decoded,
+                    outcome,
+                    duration,
+                });
+                result
+            })
+        })
+    }
+
+    fn emit(&self, event: &Event) {
+        let line = match self.format {
+            StraceFormat::Text => event.to_text(),
+            StraceFormat::Json => event.to_json(),
+        };
+        let mut output = self.output.lock().unwrap();
+        // Tracing must not change the behavior of the guest
+        let _ = writeln!(output, "{}", line);
+        let _ = output.flush();
+    }
+}
+
+impl Param {
+    /// The number of arguments of the function this parameter takes
+    fn arity(self) -> usize {
+        match self {
+            Path => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// The parameters of the syscall with this name that takes `arity`
+/// arguments
+fn syscall_params(name: &str, arity: usize) -> Option<&'static [(&'static str, Param)]> {
+    SYSCALLS
+        .iter()
+        .find(|(syscall, params)| {
+            *syscall == name && params.iter().map(|(_, p)| p.arity()).sum::<usize>() == arity
+        })
+        .map(|(_, params)| *params)
+}
+
+/// The bus syscalls return a `BusErrno`, which is traced as a number
+fn returns_bus_errno(name: &str) -> bool {
+    name.starts_with("bus_") || name.starts_with("call_") || name == "process_spawn"
+}
+
+fn decode_args(
+    store: &impl AsStoreRef,
+    memory: Option<&Memory>,
+    namespace: &str,
+    params: Option<&'static [(&'static str, Param)]>,
+    args: &[Value],
+) -> Vec<(String, Arg)> {
+    let params = match params {
+        Some(params) => params,
+        None => {
+            return args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| (format!("arg{}", i), raw_arg(arg)))
+                .collect();
+        }
+    };
+
+    let mut decoded = Vec::with_capacity(params.len());
+    let mut args = args.iter();
+    for (name, param) in params.iter() {
+        let arg = match args.next() {
+            Some(arg) => arg,
+            None => break,
+        };
+        let value = match arg {
+            Value::I32(v) => *v as u32 as u64,
+            Value::I64(v) => *v as u64,
+            other => {
+                decoded.push((name.to_string(), raw_arg(other)));
+                continue;
+            }
+        };
+        let arg = match param {
+            Fd | Int => raw_arg(arg),
+            Ptr => Arg::Ptr(value),
+            Path => {
+                let len = args.next().map(|len| match len {
+                    Value::I32(v) => *v as u32 as u64,
+                    Value::I64(v) => *v as u64,
+                    _ => 0,
+                });
+                read_path(store, memory, value, len.unwrap_or(0))
+                    .map(Arg::Str)
+                    .unwrap_or(Arg::Ptr(value))
+            }
+            Clock => Arg::Name(clock_name(value).to_string()),
+            Whence => Arg::Name(whence_name(namespace, value).to_string()),
+            Fdflags => Arg::Name(format!(
+                "{:?}",
+                wasmer_wasi_types::wasi::Fdflags::from_bits_truncate(value as u16)
+            )),
+            Lookup => Arg::Name(format!(
+                "{:?}",
+                wasmer_wasi_types::wasi::Lookup::from_bits_truncate(value as u32)
+            )),
+            Oflags => Arg::Name(format!(
+                "{:?}",
+                wasmer_wasi_types::wasi::Oflags::from_bits_truncate(value as u16)
+            )),
+            Rights => Arg::Name(format!(
+                "{:?}",
+                wasmer_wasi_types::wasi::Rights::from_bits_truncate(value)
+            )),
+        };
+        decoded.push((name.to_string(), arg));
+    }
+    decoded
+}
+
+fn decode_results(results: &[Value], returns_errno: bool) -> Outcome {
+    match results {
+        // The conversion panics on unknown values
+        [Value::I32(errno @ 0..=76)] if returns_errno => Outcome::Errno(Errno::from_native(*errno)),
+        results => Outcome::Values(results.iter().map(raw_arg).collect()),
+    }
+}
+
+fn raw_arg(value: &Value) -> Arg {
+    match value {
+        Value::I32(v) => Arg::Int(*v as i128),
+        Value::I64(v) => Arg::Int(*v as i128),
+        Value::F32(v) => Arg::Float(*v as f64),
+        Value::F64(v) => Arg::Float(*v),
+        other => Arg::Name(format!("{:?}", other)),
+    }
+}
+
+fn read_path(
+    store: &impl AsStoreRef,
+    memory: Option<&Memory>,
+    ptr: u64,
+    len: u64,
+) -> Option<String> {
+    let view = memory?.view(store);
+    let mut data = vec![0; len.min(MAX_PATH_LEN) as usize];
+    view.read(ptr, &mut data).ok()?;
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn clock_name(value: u64) -> &'static str {
+    match value {
+        0 => "REALTIME",
+        1 => "MONOTONIC",
+        2 => "PROCESS_CPUTIME_ID",
+        3 => "THREAD_CPUTIME_ID",
+        _ => "UNKNOWN",
+    }
+}
+
+fn whence_name(namespace: &str, value: u64) -> &'static str {
+    // The values were reordered after the first snapshot
+    let names = if namespace == "wasi_unstable" {
+        ["CUR", "END", "SET"]
+    } else {
+        ["SET", "CUR", "END"]
+    };
+    names.get(value as usize).copied().unwrap_or("UNKNOWN")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(outcome: Outcome) -> Event {
+        Event {
+            tid: 1,
+            name: "path_open".to_string(),
+            args: vec![
+                ("fd".to_string(), Arg::Int(3)),
+                ("path".to_string(), Arg::Str("a \"b\".txt".to_string())),
+                (
+                    "oflags".to_string(),
+                    Arg::Name("CREATE | TRUNC".to_string()),
+                ),
+                ("opened_fd".to_string(), Arg::Ptr(16)),
+            ],
+            outcome,
+            duration: Duration::from_micros(12),
+        }
+    }
+
+    #[test]
+    fn text_lines() {
+        assert_eq!(
+            event(Outcome::Errno(Errno::Noent)).to_text(),
+            r#"[1] path_open(fd=3, path="a \"b\".txt", oflags=CREATE | TRUNC, opened_fd=0x10) = noent <0.000012s>"#
+        );
+        assert_eq!(
+            event(Outcome::Exit(2)).to_text(),
+            r#"[1] path_open(fd=3, path="a \"b\".txt", oflags=CREATE | TRUNC, opened_fd=0x10) = exit(2) <0.000012s>"#
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        assert_eq!(
+            event(Outcome::Errno(Errno::Success)).to_json(),
+            r#"{"tid":1,"syscall":"path_open","args":{"fd":3,"path":"a \"b\".txt","oflags":"CREATE | TRUNC","opened_fd":16},"errno":"success","duration_ns":12000}"#
+        );
+    }
+
+    #[test]
+    fn syscalls_by_arity() {
+        let arity = |params: &[(&str, Param)]| params.iter().map(|(_, p)| p.arity()).sum::<usize>();
+        for (i, (name, params)) in SYSCALLS.iter().enumerate() {
+            assert!(
+                !SYSCALLS[..i]
+                    .iter()
+                    .any(|(other, other_params)| other == name
+                        && arity(other_params) == arity(params)),
+                "{} is listed twice with {} arguments",
+                name,
+                arity(params)
+            );
+        }
+
+        // The WASIX and the WasmEdge `sock_bind`
+        let names =
+            |params: &[(&'static str, Param)]| params.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+        assert_eq!(
+            names(syscall_params("sock_bind", 2).unwrap()),
+            ["fd", "addr"]
+        );
+        assert_eq!(
+            names(syscall_params("sock_bind", 3).unwrap()),
+            ["fd", "addr", "port"]
+        );
+        assert!(syscall_params("sock_bind", 4).is_none());
+    }
+
+    #[test]
+    fn format_from_str() {
+        assert_eq!("json".parse(), Ok(StraceFormat::Json));
+        assert_eq!("text".parse(), Ok(StraceFormat::Text));
+        assert!("yaml".parse::<StraceFormat>().is_err());
+    }
+}
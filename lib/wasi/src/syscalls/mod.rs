@@ -18,6 +18,7 @@ pub mod wasm32;
 pub mod windows;
 
 pub mod legacy;
+pub mod wasmedge;
 //pub mod wasi;
 #[cfg(feature = "wasix")]
 pub mod wasix32;
@@ -30,8 +31,8 @@ use self::types::{
         Dirent, Errno, Event, EventEnum, EventFdReadwrite, Eventrwflags, Eventtype, Fd as WasiFd,
         Fdflags, Fdstat, Filesize, Filestat, Filetype, Fstflags, Linkcount, OptionFd, Pid, Prestat,
         Rights, Snapshot0Clockid, Sockoption, Sockstatus, Socktype, StdioMode as WasiStdioMode,
-        Streamsecurity, Subscription, SubscriptionEnum, SubscriptionFsReadwrite, Tid, Timestamp,
-        Tty, Whence,
+        Streamsecurity, Subclockflags, Subscription, SubscriptionEnum, SubscriptionFsReadwrite,
+        Tid, Timestamp, Tty, Whence,
     },
     *,
 };
@@ -41,11 +42,12 @@ use crate::WasiBusProcessId;
 use crate::{
     mem_error_to_wasi,
     state::{
-        self, fs_error_into_wasi_err, iterate_poll_events, net_error_into_wasi_err, poll,
-        virtual_file_type_to_wasi_file_type, Inode, InodeSocket, InodeSocketKind, InodeVal, Kind,
-        PollEvent, PollEventBuilder, WasiPipe, WasiState, MAX_SYMLINKS,
+        self, fs_error_into_wasi_err, inode_readiness, iterate_poll_events,
+        net_error_into_wasi_err, virtual_file_type_to_wasi_file_type, Inode, InodeSocket,
+        InodeSocketKind, InodeVal, Kind, PollEvent, PollEventBuilder, PollEventSet, Reactor,
+        WasiPipe, WasiState, MAX_SYMLINKS,
     },
-    Fd, WasiEnv, WasiError, WasiThread, WasiThreadId,
+    Fd, WasiEnv, WasiError, WasiFunctionEnv, WasiThread, WasiThreadError, WasiThreadId,
 };
 use bytes::Bytes;
 use std::borrow::{Borrow, Cow};
@@ -54,9 +56,11 @@ use std::io::{self, Read, Seek, Write};
 use std::mem::transmute;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
 use std::sync::atomic::AtomicU64;
 use std::sync::{atomic::Ordering, Mutex};
 use std::sync::{mpsc, Arc};
+use std::task::{Context, Wake, Waker};
 use std::time::Duration;
 use tracing::{debug, error, trace, warn};
 use wasmer::{
@@ -64,7 +68,7 @@ use wasmer::{
     MemorySize, MemoryView, Module, RuntimeError, Value, WasmPtr, WasmSlice,
 };
 use wasmer_vbus::{FileDescriptor, StdioMode};
-use wasmer_vfs::{FsError, VirtualFile};
+use wasmer_vfs::{FsError, Upcastable, VirtualFile};
 use wasmer_vnet::{SocketHttpRequest, StreamSecurity};
 
 #[cfg(any(
@@ -287,12 +291,8 @@ fn write_buffer_array<M: MemorySize>(
     Errno::Success
 }
 
-fn get_current_time_in_nanos() -> Result<Timestamp, Errno> {
-    let now = std::time::SystemTime::now();
-    let duration = now
-        .duration_since(std::time::SystemTime::UNIX_EPOCH)
-        .map_err(|_| Errno::Io)?;
-    Ok(duration.as_nanos() as Timestamp)
+fn get_current_time_in_nanos(env: &WasiEnv) -> Result<Timestamp, Errno> {
+    env.clock()?.time_get(Snapshot0Clockid::Realtime, 1)
 }
 
 /// ### `args_get()`
@@ -377,9 +377,8 @@ pub fn clock_res_get<M: MemorySize>(
     let env = ctx.data();
     let memory = env.memory_view(&ctx);
 
-    let out_addr = resolution.deref(&memory);
-    let t_out = wasi_try!(platform_clock_res_get(clock_id, out_addr));
-    wasi_try_mem!(resolution.write(&memory, t_out as Timestamp));
+    let t_out = wasi_try!(env.clock().and_then(|clock| clock.res_get(clock_id)));
+    wasi_try_mem!(resolution.write(&memory, t_out));
     Errno::Success
 }
 
@@ -406,8 +405,10 @@ pub fn clock_time_get<M: MemorySize>(
     let env = ctx.data();
     let memory = env.memory_view(&ctx);
 
-    let t_out = wasi_try!(platform_clock_time_get(clock_id, precision));
-    wasi_try_mem!(time.write(&memory, t_out as Timestamp));
+    let t_out = wasi_try!(env
+        .clock()
+        .and_then(|clock| clock.time_get(clock_id, precision)));
+    wasi_try_mem!(time.write(&memory, t_out));
 
     let result = Errno::Success;
     trace!(
@@ -797,7 +798,7 @@ pub fn fd_filestat_set_times(
         let time_to_set = if fst_flags.contains(Fstflags::SET_ATIM) {
             st_atim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_atim = time_to_set;
     }
@@ -806,7 +807,7 @@ pub fn fd_filestat_set_times(
         let time_to_set = if fst_flags.contains(Fstflags::SET_MTIM) {
             st_mtim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_mtim = time_to_set;
     }
@@ -1284,78 +1285,10 @@ pub fn fd_readdir<M: MemorySize>(
 
     let buf_arr = wasi_try_mem!(buf.slice(&memory, buf_len));
     let bufused_ref = bufused.deref(&memory);
-    let working_dir = wasi_try!(state.fs.get_fd(fd));
     let mut cur_cookie = cookie;
     let mut buf_idx = 0usize;
 
-    let entries: Vec<(String, Filetype, u64)> = {
-        let guard = inodes.arena[working_dir.inode].read();
-        let deref = guard.deref();
-        match deref {
-            Kind::Dir { path, entries, .. } => {
-                debug!("Reading dir {:?}", path);
-                // TODO: refactor this code
-                // we need to support multiple calls,
-                // simple and obviously correct implementation for now:
-                // maintain consistent order via lexacographic sorting
-                let fs_info = wasi_try!(wasi_try!(state.fs_read_dir(path))
-                    .collect::<Result<Vec<_>, _>>()
-                    .map_err(fs_error_into_wasi_err));
-                let mut entry_vec = wasi_try!(fs_info
-                    .into_iter()
-                    .map(|entry| {
-                        let filename = entry.file_name().to_string_lossy().to_string();
-                        debug!("Getting file: {:?}", filename);
-                        let filetype = virtual_file_type_to_wasi_file_type(
-                            entry.file_type().map_err(fs_error_into_wasi_err)?,
-                        );
-                        Ok((
-                            filename, filetype, 0, // TODO: inode
-                        ))
-                    })
-                    .collect::<Result<Vec<(String, Filetype, u64)>, _>>());
-                entry_vec.extend(
-                    entries
-                        .iter()
-                        .filter(|(_, inode)| inodes.arena[**inode].is_preopened)
-                        .map(|(name, inode)| {
-                            let entry = &inodes.arena[*inode];
-                            let stat = entry.stat.read().unwrap();
-                            (entry.name.to_string(), stat.st_filetype, stat.st_ino)
-                        }),
-                );
-                // adding . and .. special folders
-                // TODO: inode
-                entry_vec.push((".".to_string(), Filetype::Directory, 0));
-                entry_vec.push(("..".to_string(), Filetype::Directory, 0));
-                entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
-                entry_vec
-            }
-            Kind::Root { entries } => {
-                debug!("Reading root");
-                let sorted_entries = {
-                    let mut entry_vec: Vec<(String, Inode)> =
-                        entries.iter().map(|(a, b)| (a.clone(), *b)).collect();
-                    entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
-                    entry_vec
-                };
-                sorted_entries
-                    .into_iter()
-                    .map(|(name, inode)| {
-                        let entry = &inodes.arena[inode];
-                        let stat = entry.stat.read().unwrap();
-                        (format!("/{}", entry.name), stat.st_filetype, stat.st_ino)
-                    })
-                    .collect()
-            }
-            Kind::File { .. }
-            | Kind::Symlink { .. }
-            | Kind::Buffer { .. }
-            | Kind::Socket { .. }
-            | Kind::Pipe { .. }
-            | Kind::EventNotifications { .. } => return Errno::Notdir,
-        }
-    };
+    let entries = wasi_try!(fd_readdir_entries(state, &inodes, fd));
 
     for (entry_path_str, wasi_file_type, ino) in entries.iter().skip(cookie as usize) {
         cur_cookie += 1;
@@ -1395,6 +1328,83 @@ pub fn fd_readdir<M: MemorySize>(
     Errno::Success
 }
 
+/// Lists the entries of the directory opened as `fd`, sorted by name, as
+/// `(name, file type, inode number)`.
+pub(crate) fn fd_readdir_entries(
+    state: &WasiState,
+    inodes: &crate::WasiInodes,
+    fd: WasiFd,
+) -> Result<Vec<(String, Filetype, u64)>, Errno> {
+    let working_dir = state.fs.get_fd(fd)?;
+    let guard = inodes.arena[working_dir.inode].read();
+    let deref = guard.deref();
+    match deref {
+        Kind::Dir { path, entries, .. } => {
+            debug!("Reading dir {:?}", path);
+            // TODO: refactor this code
+            // we need to support multiple calls,
+            // simple and obviously correct implementation for now:
+            // maintain consistent order via lexacographic sorting
+            let fs_info = state
+                .fs_read_dir(path)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(fs_error_into_wasi_err)?;
+            let mut entry_vec = fs_info
+                .into_iter()
+                .map(|entry| {
+                    let filename = entry.file_name().to_string_lossy().to_string();
+                    debug!("Getting file: {:?}", filename);
+                    let filetype = virtual_file_type_to_wasi_file_type(
+                        entry.file_type().map_err(fs_error_into_wasi_err)?,
+                    );
+                    Ok((
+                        filename, filetype, 0, // TODO: inode
+                    ))
+                })
+                .collect::<Result<Vec<(String, Filetype, u64)>, Errno>>()?;
+            entry_vec.extend(
+                entries
+                    .iter()
+                    .filter(|(_, inode)| inodes.arena[**inode].is_preopened)
+                    .map(|(name, inode)| {
+                        let entry = &inodes.arena[*inode];
+                        let stat = entry.stat.read().unwrap();
+                        (entry.name.to_string(), stat.st_filetype, stat.st_ino)
+                    }),
+            );
+            // adding . and .. special folders
+            // TODO: inode
+            entry_vec.push((".".to_string(), Filetype::Directory, 0));
+            entry_vec.push(("..".to_string(), Filetype::Directory, 0));
+            entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(entry_vec)
+        }
+        Kind::Root { entries } => {
+            debug!("Reading root");
+            let sorted_entries = {
+                let mut entry_vec: Vec<(String, Inode)> =
+                    entries.iter().map(|(a, b)| (a.clone(), *b)).collect();
+                entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
+                entry_vec
+            };
+            Ok(sorted_entries
+                .into_iter()
+                .map(|(name, inode)| {
+                    let entry = &inodes.arena[inode];
+                    let stat = entry.stat.read().unwrap();
+                    (format!("/{}", entry.name), stat.st_filetype, stat.st_ino)
+                })
+                .collect())
+        }
+        Kind::File { .. }
+        | Kind::Symlink { .. }
+        | Kind::Buffer { .. }
+        | Kind::Socket { .. }
+        | Kind::Pipe { .. }
+        | Kind::EventNotifications { .. } => Err(Errno::Notdir),
+    }
+}
+
 /// ### `fd_renumber()`
 /// Atomically copy file descriptor
 /// Inputs:
@@ -1850,6 +1860,16 @@ pub fn path_create_directory<M: MemorySize>(
     let env = ctx.data();
     let (memory, state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
 
+    let path_string = unsafe { get_input_str!(&memory, path, path_len) };
+    path_create_directory_internal(state, inodes.deref_mut(), fd, &path_string)
+}
+
+pub(crate) fn path_create_directory_internal(
+    state: &WasiState,
+    inodes: &mut crate::WasiInodes,
+    fd: WasiFd,
+    path_string: &str,
+) -> Errno {
     let working_dir = wasi_try!(state.fs.get_fd(fd));
     {
         let guard = inodes.arena[working_dir.inode].read();
@@ -1860,10 +1880,9 @@ pub fn path_create_directory<M: MemorySize>(
     if !working_dir.rights.contains(Rights::PATH_CREATE_DIRECTORY) {
         return Errno::Access;
     }
-    let path_string = unsafe { get_input_str!(&memory, path, path_len) };
-    debug!("=> fd: {}, path: {}", fd, &path_string);
+    debug!("=> fd: {}, path: {}", fd, path_string);
 
-    let path = std::path::PathBuf::from(&path_string);
+    let path = std::path::PathBuf::from(path_string);
     let path_vec = wasi_try!(path
         .components()
         .map(|comp| {
@@ -1909,9 +1928,8 @@ pub fn path_create_directory<M: MemorySize>(
                     // TODO: double check this doesn't risk breaking the sandbox
                     adjusted_path.push(comp);
                     if let Ok(adjusted_path_stat) = path_filestat_get_internal(
-                        &memory,
                         state,
-                        inodes.deref_mut(),
+                        inodes,
                         fd,
                         0,
                         &adjusted_path.to_string_lossy(),
@@ -1927,12 +1945,8 @@ pub fn path_create_directory<M: MemorySize>(
                         path: adjusted_path,
                         entries: Default::default(),
                     };
-                    let new_inode = wasi_try!(state.fs.create_inode(
-                        inodes.deref_mut(),
-                        kind,
-                        false,
-                        comp.to_string()
-                    ));
+                    let new_inode =
+                        wasi_try!(state.fs.create_inode(inodes, kind, false, comp.to_string()));
 
                     // reborrow to insert
                     {
@@ -1984,7 +1998,6 @@ pub fn path_filestat_get<M: MemorySize>(
     let path_string = unsafe { get_input_str!(&memory, path, path_len) };
 
     let stat = wasi_try!(path_filestat_get_internal(
-        &memory,
         state,
         inodes.deref_mut(),
         fd,
@@ -2012,7 +2025,6 @@ pub fn path_filestat_get<M: MemorySize>(
 /// - `__wasi_file_stat_t *buf`
 ///     The location where the metadata will be stored
 pub fn path_filestat_get_internal(
-    memory: &MemoryView,
     state: &WasiState,
     inodes: &mut crate::WasiInodes,
     fd: WasiFd,
@@ -2101,7 +2113,7 @@ pub fn path_filestat_set_times<M: MemorySize>(
         let time_to_set = if fst_flags.contains(Fstflags::SET_ATIM) {
             st_atim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_atim = time_to_set;
     }
@@ -2109,7 +2121,7 @@ pub fn path_filestat_set_times<M: MemorySize>(
         let time_to_set = if fst_flags.contains(Fstflags::SET_MTIM) {
             st_mtim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_mtim = time_to_set;
     }
@@ -2255,30 +2267,56 @@ pub fn path_open<M: MemorySize>(
     }
 
     let fd_ref = fd.deref(&memory);
+    let path_string = unsafe { get_input_str!(&memory, path, path_len) };
+    let out_fd = wasi_try!(path_open_internal(
+        state,
+        inodes.deref_mut(),
+        dirfd,
+        dirflags,
+        &path_string,
+        o_flags,
+        fs_rights_base,
+        fs_rights_inheriting,
+        fs_flags,
+    ));
+    wasi_try_mem!(fd_ref.write(out_fd));
+
+    Errno::Success
+}
 
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn path_open_internal(
+    state: &WasiState,
+    inodes: &mut crate::WasiInodes,
+    dirfd: WasiFd,
+    dirflags: LookupFlags,
+    path_string: &str,
+    o_flags: Oflags,
+    fs_rights_base: Rights,
+    fs_rights_inheriting: Rights,
+    fs_flags: Fdflags,
+) -> Result<WasiFd, Errno> {
     // o_flags:
     // - __WASI_O_CREAT (create if it does not exist)
     // - __WASI_O_DIRECTORY (fail if not dir)
     // - __WASI_O_EXCL (fail if file exists)
     // - __WASI_O_TRUNC (truncate size to 0)
 
-    let working_dir = wasi_try!(state.fs.get_fd(dirfd));
+    let working_dir = state.fs.get_fd(dirfd)?;
     let working_dir_rights_inheriting = working_dir.rights_inheriting;
 
     // ASSUMPTION: open rights apply recursively
     if !working_dir.rights.contains(Rights::PATH_OPEN) {
-        return Errno::Access;
+        return Err(Errno::Access);
     }
 
-    let path_string = unsafe { get_input_str!(&memory, path, path_len) };
-
-    debug!("=> path_open(): fd: {}, path: {}", dirfd, &path_string);
+    debug!("=> path_open(): fd: {}, path: {}", dirfd, path_string);
 
-    let path_arg = std::path::PathBuf::from(&path_string);
+    let path_arg = std::path::PathBuf::from(path_string);
     let maybe_inode = state.fs.get_inode_at_path(
-        inodes.deref_mut(),
+        inodes,
         dirfd,
-        &path_string,
+        path_string,
         dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
     );
 
@@ -2354,18 +2392,28 @@ pub fn path_open<M: MemorySize>(
                 if let Some(special_fd) = fd {
                     // short circuit if we're dealing with a special file
                     assert!(handle.is_some());
-                    wasi_try_mem!(fd_ref.write(*special_fd));
-                    return Errno::Success;
+                    return Ok(*special_fd);
                 }
                 if o_flags.contains(Oflags::DIRECTORY) {
-                    return Errno::Notdir;
+                    return Err(Errno::Notdir);
                 }
                 if o_flags.contains(Oflags::EXCL) {
-                    return Errno::Exist;
+                    return Err(Errno::Exist);
                 }
 
+                let writes = fs_rights_base.contains(Rights::FD_WRITE)
+                    || minimum_rights.append
+                    || minimum_rights.truncate;
+                if writes {
+                    state.fs.policy.check_write(path)?;
+                }
+                // the rights of the directory open the files for writing even
+                // when the module only reads them, which read-only paths deny
+                let write =
+                    minimum_rights.write && (writes || state.fs.policy.check_write(path).is_ok());
+
                 let open_options = open_options
-                    .write(minimum_rights.write)
+                    .write(write)
                     .create(minimum_rights.create)
                     .append(minimum_rights.append)
                     .truncate(minimum_rights.truncate);
@@ -2373,7 +2421,7 @@ pub fn path_open<M: MemorySize>(
                 if minimum_rights.read {
                     open_flags |= Fd::READ;
                 }
-                if minimum_rights.write {
+                if write {
                     open_flags |= Fd::WRITE;
                 }
                 if minimum_rights.create {
@@ -2383,14 +2431,18 @@ pub fn path_open<M: MemorySize>(
                     open_flags |= Fd::TRUNCATE;
                 }
 
-                *handle = Some(wasi_try!(open_options
-                    .open(&path)
-                    .map_err(fs_error_into_wasi_err)));
+                let file = open_options.open(&path).map_err(fs_error_into_wasi_err)?;
+                *handle = Some(
+                    state
+                        .fs
+                        .policy
+                        .limit_file(path, file, minimum_rights.append),
+                );
             }
             Kind::Buffer { .. } => unimplemented!("wasi::path_open for Buffer type files"),
             Kind::Root { .. } => {
                 if !o_flags.contains(Oflags::DIRECTORY) {
-                    return Errno::Notcapable;
+                    return Err(Errno::Notcapable);
                 }
             }
             Kind::Dir { .. }
@@ -2413,17 +2465,17 @@ pub fn path_open<M: MemorySize>(
         debug!("Maybe creating file");
         if o_flags.contains(Oflags::CREATE) {
             if o_flags.contains(Oflags::DIRECTORY) {
-                return Errno::Notdir;
+                return Err(Errno::Notdir);
             }
             debug!("Creating file");
             // strip end file name
 
-            let (parent_inode, new_entity_name) = wasi_try!(state.fs.get_parent_inode_at_path(
-                inodes.deref_mut(),
+            let (parent_inode, new_entity_name) = state.fs.get_parent_inode_at_path(
+                inodes,
                 dirfd,
                 &path_arg,
-                dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0
-            ));
+                dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+            )?;
             let new_file_host_path = {
                 let guard = inodes.arena[parent_inode].read();
                 let deref = guard.deref();
@@ -2438,9 +2490,10 @@ pub fn path_open<M: MemorySize>(
                         new_path.push(&new_entity_name);
                         new_path
                     }
-                    _ => return Errno::Inval,
+                    _ => return Err(Errno::Inval),
                 }
             };
+            state.fs.policy.check_write(&new_file_host_path)?;
             // once we got the data we need from the parent, we lookup the host file
             // todo: extra check that opening with write access is okay
             let handle = {
@@ -2463,12 +2516,16 @@ pub fn path_open<M: MemorySize>(
                     open_flags |= Fd::TRUNCATE;
                 }
 
-                Some(wasi_try!(open_options.open(&new_file_host_path).map_err(
-                    |e| {
-                        debug!("Error opening file {}", e);
-                        fs_error_into_wasi_err(e)
-                    }
-                )))
+                let file = open_options.open(&new_file_host_path).map_err(|e| {
+                    debug!("Error opening file {}", e);
+                    fs_error_into_wasi_err(e)
+                })?;
+                Some(
+                    state
+                        .fs
+                        .policy
+                        .limit_file(&new_file_host_path, file, minimum_rights.append),
+                )
             };
 
             let new_inode = {
@@ -2477,12 +2534,9 @@ pub fn path_open<M: MemorySize>(
                     path: new_file_host_path,
                     fd: None,
                 };
-                wasi_try!(state.fs.create_inode(
-                    inodes.deref_mut(),
-                    kind,
-                    false,
-                    new_entity_name.clone()
-                ))
+                state
+                    .fs
+                    .create_inode(inodes, kind, false, new_entity_name.clone())?
             };
 
             {
@@ -2497,7 +2551,7 @@ pub fn path_open<M: MemorySize>(
 
             new_inode
         } else {
-            return maybe_inode.unwrap_err();
+            return Err(maybe_inode.unwrap_err());
         }
     };
 
@@ -2507,18 +2561,17 @@ pub fn path_open<M: MemorySize>(
 
     // TODO: check and reduce these
     // TODO: ensure a mutable fd to root can never be opened
-    let out_fd = wasi_try!(state.fs.create_fd(
+    let out_fd = state.fs.create_fd(
         adjusted_rights,
         fs_rights_inheriting,
         fs_flags,
         open_flags,
-        inode
-    ));
+        inode,
+    )?;
 
-    wasi_try_mem!(fd_ref.write(out_fd));
     debug!("wasi::path_open returning fd {}", out_fd);
 
-    Errno::Success
+    Ok(out_fd)
 }
 
 /// ### `path_readlink()`
@@ -2598,16 +2651,23 @@ pub fn path_remove_directory<M: MemorySize>(
     let env = ctx.data();
     let (memory, mut state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
 
-    let base_dir = wasi_try!(state.fs.get_fd(fd));
     let path_str = unsafe { get_input_str!(&memory, path, path_len) };
+    path_remove_directory_internal(state, inodes.deref_mut(), fd, &path_str)
+}
 
-    let inode = wasi_try!(state
-        .fs
-        .get_inode_at_path(inodes.deref_mut(), fd, &path_str, false));
+pub(crate) fn path_remove_directory_internal(
+    state: &WasiState,
+    inodes: &mut crate::WasiInodes,
+    fd: WasiFd,
+    path_str: &str,
+) -> Errno {
+    let base_dir = wasi_try!(state.fs.get_fd(fd));
+
+    let inode = wasi_try!(state.fs.get_inode_at_path(inodes, fd, path_str, false));
     let (parent_inode, childs_name) = wasi_try!(state.fs.get_parent_inode_at_path(
-        inodes.deref_mut(),
+        inodes,
         fd,
-        std::path::Path::new(&path_str),
+        std::path::Path::new(path_str),
         false
     ));
 
@@ -2690,10 +2750,28 @@ pub fn path_rename<M: MemorySize>(
     let env = ctx.data();
     let (memory, mut state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
     let source_str = unsafe { get_input_str!(&memory, old_path, old_path_len) };
-    let source_path = std::path::Path::new(&source_str);
     let target_str = unsafe { get_input_str!(&memory, new_path, new_path_len) };
-    let target_path = std::path::Path::new(&target_str);
-    debug!("=> rename from {} to {}", &source_str, &target_str);
+    path_rename_internal(
+        state,
+        inodes.deref_mut(),
+        old_fd,
+        &source_str,
+        new_fd,
+        &target_str,
+    )
+}
+
+pub(crate) fn path_rename_internal(
+    state: &WasiState,
+    inodes: &mut crate::WasiInodes,
+    old_fd: WasiFd,
+    source_str: &str,
+    new_fd: WasiFd,
+    target_str: &str,
+) -> Errno {
+    let source_path = std::path::Path::new(source_str);
+    let target_path = std::path::Path::new(target_str);
+    debug!("=> rename from {} to {}", source_str, target_str);
 
     {
         let source_fd = wasi_try!(state.fs.get_fd(old_fd));
@@ -2708,26 +2786,24 @@ pub fn path_rename<M: MemorySize>(
 
     // this is to be sure the source file is fetch from filesystem if needed
     wasi_try!(state.fs.get_inode_at_path(
-        inodes.deref_mut(),
+        inodes,
         old_fd,
         source_path.to_str().as_ref().unwrap(),
         true
     ));
     // Create the destination inode if the file exists.
-    let _ = state.fs.get_inode_at_path(
-        inodes.deref_mut(),
-        new_fd,
-        target_path.to_str().as_ref().unwrap(),
-        true,
-    );
+    let _ =
+        state
+            .fs
+            .get_inode_at_path(inodes, new_fd, target_path.to_str().as_ref().unwrap(), true);
     let (source_parent_inode, source_entry_name) =
         wasi_try!(state
             .fs
-            .get_parent_inode_at_path(inodes.deref_mut(), old_fd, source_path, true));
+            .get_parent_inode_at_path(inodes, old_fd, source_path, true));
     let (target_parent_inode, target_entry_name) =
         wasi_try!(state
             .fs
-            .get_parent_inode_at_path(inodes.deref_mut(), new_fd, target_path, true));
+            .get_parent_inode_at_path(inodes, new_fd, target_path, true));
     let mut need_create = true;
     let host_adjusted_target_path = {
         let guard = inodes.arena[target_parent_inode].read();
@@ -2772,38 +2848,24 @@ pub fn path_rename<M: MemorySize>(
         let mut guard = inodes.arena[source_entry].write();
         let deref_mut = guard.deref_mut();
         match deref_mut {
-            Kind::File {
-                handle, ref path, ..
-            } => {
-                // TODO: investigate why handle is not always there, it probably should be.
-                // My best guess is the fact that a handle means currently open and a path
-                // just means reference to host file on disk. But ideally those concepts
-                // could just be unified even if there's a `Box<dyn VirtualFile>` which just
-                // implements the logic of "I'm not actually a file, I'll try to be as needed".
-                let result = if let Some(h) = handle {
-                    drop(guard);
-                    state.fs_rename(&source_path, &host_adjusted_target_path)
-                } else {
-                    let path_clone = path.clone();
-                    drop(guard);
-                    let out = state.fs_rename(&path_clone, &host_adjusted_target_path);
-                    {
-                        let mut guard = inodes.arena[source_entry].write();
-                        if let Kind::File { ref mut path, .. } = guard.deref_mut() {
-                            *path = host_adjusted_target_path;
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                    out
-                };
-                // if the above operation failed we have to revert the previous change and then fail
-                if let Err(e) = result {
+            Kind::File { ref path, .. } => {
+                // The file is renamed through its host path, whether it's
+                // open or not: `source_path` is relative to `old_fd`.
+                let path_clone = path.clone();
+                drop(guard);
+                // if the operation failed we have to revert the previous change and then fail
+                if let Err(e) = state.fs_rename(&path_clone, &host_adjusted_target_path) {
                     let mut guard = inodes.arena[source_parent_inode].write();
                     if let Kind::Dir { entries, .. } = guard.deref_mut() {
                         entries.insert(source_entry_name, source_entry);
-                        return e;
                     }
+                    return e;
+                }
+                let mut guard = inodes.arena[source_entry].write();
+                if let Kind::File { ref mut path, .. } = guard.deref_mut() {
+                    *path = host_adjusted_target_path;
+                } else {
+                    unreachable!()
                 }
             }
             Kind::Dir { ref path, .. } => {
@@ -2875,6 +2937,10 @@ pub fn path_symlink<M: MemorySize>(
 
     // get the depth of the parent + 1 (UNDER INVESTIGATION HMMMMMMMM THINK FISH ^ THINK FISH)
     let old_path_path = std::path::Path::new(&old_path_str);
+    // absolute symlinks would point outside of the preopened directories
+    if old_path_path.has_root() {
+        return Errno::Notcapable;
+    }
     let (source_inode, _) =
         wasi_try!(state
             .fs
@@ -2896,14 +2962,15 @@ pub fn path_symlink<M: MemorySize>(
             .get_parent_inode_at_path(inodes.deref_mut(), fd, new_path_path, true));
 
     // short circuit if anything is wrong, before we create an inode
-    {
+    let host_path_of_link = {
         let guard = inodes.arena[target_parent_inode].read();
         let deref = guard.deref();
         match deref {
-            Kind::Dir { entries, .. } => {
+            Kind::Dir { entries, path, .. } => {
                 if entries.contains_key(&entry_name) {
                     return Errno::Exist;
                 }
+                path.join(&entry_name)
             }
             Kind::Root { .. } => return Errno::Notcapable,
             Kind::Socket { .. } | Kind::Pipe { .. } | Kind::EventNotifications { .. } => {
@@ -2913,7 +2980,7 @@ pub fn path_symlink<M: MemorySize>(
                 unreachable!("get_parent_inode_at_path returned something other than a Dir or Root")
             }
         }
-    }
+    };
 
     let mut source_path = std::path::Path::new(&old_path_str);
     let mut relative_path = std::path::PathBuf::new();
@@ -2921,11 +2988,18 @@ pub fn path_symlink<M: MemorySize>(
         relative_path.push("..");
     }
     relative_path.push(source_path);
+    // the symlink is followed from the directory it is in, which must not
+    // lead out of the preopened directory
+    let link_dir = new_path_path.parent().unwrap_or(new_path_path);
+    if !path_stays_within_base(&link_dir.join(&relative_path)) {
+        return Errno::Notcapable;
+    }
     debug!(
         "Symlinking {} to {}",
         new_path_str,
         relative_path.to_string_lossy()
     );
+    wasi_try!(state.fs_symlink(&relative_path, &host_path_of_link));
 
     let kind = Kind::Symlink {
         base_po_dir: fd,
@@ -2952,6 +3026,24 @@ pub fn path_symlink<M: MemorySize>(
     Errno::Success
 }
 
+/// Tells whether `path`, relative to a preopened directory, stays inside
+/// of it once its `..` are applied.
+fn path_stays_within_base(path: &std::path::Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => match depth.checked_sub(1) {
+                Some(parent_depth) => depth = parent_depth,
+                None => return false,
+            },
+            std::path::Component::RootDir | std::path::Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
 /// ### `path_unlink_file()`
 /// Unlink a file, deleting if the number of hardlinks is 1
 /// Inputs:
@@ -2971,35 +3063,56 @@ pub fn path_unlink_file<M: MemorySize>(
     let env = ctx.data();
     let (memory, mut state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
 
+    let path_str = unsafe { get_input_str!(&memory, path, path_len) };
+    path_unlink_file_internal(state, inodes.deref_mut(), fd, &path_str)
+}
+
+pub(crate) fn path_unlink_file_internal(
+    state: &WasiState,
+    inodes: &mut crate::WasiInodes,
+    fd: WasiFd,
+    path_str: &str,
+) -> Errno {
     let base_dir = wasi_try!(state.fs.get_fd(fd));
     if !base_dir.rights.contains(Rights::PATH_UNLINK_FILE) {
         return Errno::Access;
     }
-    let path_str = unsafe { get_input_str!(&memory, path, path_len) };
     debug!("Requested file: {}", path_str);
 
-    let inode = wasi_try!(state
-        .fs
-        .get_inode_at_path(inodes.deref_mut(), fd, &path_str, false));
+    let inode = wasi_try!(state.fs.get_inode_at_path(inodes, fd, path_str, false));
     let (parent_inode, childs_name) = wasi_try!(state.fs.get_parent_inode_at_path(
-        inodes.deref_mut(),
+        inodes,
         fd,
-        std::path::Path::new(&path_str),
+        std::path::Path::new(path_str),
         false
     ));
 
-    let removed_inode = {
+    let is_symlink = matches!(inodes.arena[inode].read().deref(), Kind::Symlink { .. });
+    // An open handle unlinks the file without going through
+    // `fs_remove_file`, so the policy is checked before anything changes.
+    if let Kind::File { path, .. } = inodes.arena[inode].read().deref() {
+        wasi_try!(state.fs.policy.check_write(path));
+    }
+    let (removed_inode, host_path_of_parent) = {
         let mut guard = inodes.arena[parent_inode].write();
         let deref_mut = guard.deref_mut();
         match deref_mut {
             Kind::Dir {
-                ref mut entries, ..
+                ref mut entries,
+                path,
+                ..
             } => {
-                let removed_inode = wasi_try!(entries.remove(&childs_name).ok_or(Errno::Inval));
+                // Symlinks found in the backing file system are resolved
+                // each time, without being cached in the entries.
+                let removed_inode = match entries.remove(&childs_name) {
+                    Some(removed_inode) => removed_inode,
+                    None if is_symlink => inode,
+                    None => return Errno::Inval,
+                };
                 // TODO: make this a debug assert in the future
                 assert!(inode == removed_inode);
                 debug_assert!(inodes.arena[inode].stat.read().unwrap().st_nlink > 0);
-                removed_inode
+                (removed_inode, path.clone())
             }
             Kind::Root { .. } => return Errno::Access,
             _ => unreachable!(
@@ -3031,7 +3144,7 @@ pub fn path_unlink_file<M: MemorySize>(
                 }
                 Kind::Dir { .. } | Kind::Root { .. } => return Errno::Isdir,
                 Kind::Symlink { .. } => {
-                    // TODO: actually delete real symlinks and do nothing for virtual symlinks
+                    wasi_try!(state.fs_remove_file(host_path_of_parent.join(&childs_name)));
                 }
                 _ => unimplemented!("wasi::path_unlink_file for Buffer"),
             }
@@ -3046,7 +3159,7 @@ pub fn path_unlink_file<M: MemorySize>(
                 false
             }
         };
-        let removed_inode_val = unsafe { state.fs.remove_inode(inodes.deref_mut(), removed_inode) };
+        let removed_inode_val = unsafe { state.fs.remove_inode(inodes, removed_inode) };
         assert!(
             removed_inode_val.is_some(),
             "Inode could not be removed because it doesn't exist"
@@ -3084,229 +3197,211 @@ pub fn poll_oneoff<M: MemorySize>(
     trace!("wasi::poll_oneoff");
     trace!("  => nsubscriptions = {}", nsubscriptions);
     let env = ctx.data();
-    let (memory, mut state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
+    let memory = env.memory_view(&ctx);
+    let state = env.state.deref();
 
     let subscription_array = wasi_try_mem_ok!(in_.slice(&memory, nsubscriptions));
     let event_array = wasi_try_mem_ok!(out_.slice(&memory, nsubscriptions));
     let mut events_seen: u32 = 0;
     let out_ptr = nevents.deref(&memory);
 
-    let mut fd_guards = vec![];
-    let mut clock_subs = vec![];
-    let mut in_events = vec![];
-    let mut time_to_sleep = Duration::from_millis(5);
-
-    for sub in subscription_array.iter() {
-        let s: Subscription = wasi_try_mem_ok!(sub.read());
-        let mut peb = PollEventBuilder::new();
-
-        let fd = match s.data {
-            SubscriptionEnum::Read(SubscriptionFsReadwrite { file_descriptor }) => {
-                match file_descriptor {
-                    __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
-                    _ => {
-                        let fd_entry = wasi_try_ok!(state.fs.get_fd(file_descriptor), env);
-                        if !fd_entry.rights.contains(Rights::FD_READ) {
-                            return Ok(Errno::Access);
-                        }
-                    }
-                }
-                in_events.push(peb.add(PollEvent::PollIn).build());
-                Some(file_descriptor)
-            }
-            SubscriptionEnum::Write(SubscriptionFsReadwrite { file_descriptor }) => {
-                match file_descriptor {
-                    __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
-                    _ => {
-                        let fd_entry = wasi_try_ok!(state.fs.get_fd(file_descriptor), env);
-                        if !fd_entry.rights.contains(Rights::FD_WRITE) {
-                            return Ok(Errno::Access);
-                        }
+    let mut reactor = wasi_try_ok!(Reactor::new(), env);
+    let stopwatch = env.stopwatch();
+
+    // The file descriptors subscribed to, with whether they are waited on
+    // by the reactor, and the deadlines of the clocks subscribed to,
+    // relative to the start of the call
+    let mut subscriptions = vec![];
+    let mut deadlines = vec![];
+    // The events seen on each subscription and the number of bytes that
+    // can be read or written
+    let mut seen_events: Vec<(PollEventSet, usize)> = vec![];
+    // Whether some sources can only be checked again from time to time
+    let mut needs_polling = false;
+
+    {
+        let inodes = state.inodes.read().unwrap();
+        for (token, sub) in subscription_array.iter().enumerate() {
+            let s: Subscription = wasi_try_mem_ok!(sub.read());
+            seen_events.push((0, 0));
+
+            let (fd, events, right) = match s.data {
+                SubscriptionEnum::Read(SubscriptionFsReadwrite { file_descriptor }) => (
+                    file_descriptor,
+                    PollEventBuilder::new().add(PollEvent::PollIn).build(),
+                    Rights::FD_READ,
+                ),
+                SubscriptionEnum::Write(SubscriptionFsReadwrite { file_descriptor }) => (
+                    file_descriptor,
+                    PollEventBuilder::new().add(PollEvent::PollOut).build(),
+                    Rights::FD_WRITE,
+                ),
+                SubscriptionEnum::Clock(clock_info) => {
+                    let clock_id = match clock_info.clock_id {
+                        Clockid::Realtime => Snapshot0Clockid::Realtime,
+                        Clockid::Monotonic => Snapshot0Clockid::Monotonic,
+                        _ => return Ok(Errno::Inval),
+                    };
+                    let mut timeout = clock_info.timeout;
+                    if clock_info
+                        .flags
+                        .contains(Subclockflags::SUBSCRIPTION_CLOCK_ABSTIME)
+                    {
+                        let now =
+                            wasi_try_ok!(env.clock().and_then(|clock| clock.time_get(clock_id, 1)));
+                        timeout = timeout.saturating_sub(now);
                     }
+                    deadlines.push((token, Duration::from_nanos(timeout)));
+                    continue;
                 }
-                in_events.push(peb.add(PollEvent::PollOut).build());
-                Some(file_descriptor)
-            }
-            SubscriptionEnum::Clock(clock_info) => {
-                if matches!(clock_info.clock_id, Clockid::Realtime | Clockid::Monotonic) {
-                    // this is a hack
-                    // TODO: do this properly
-                    time_to_sleep = Duration::from_nanos(clock_info.timeout);
-                    clock_subs.push((clock_info, s.userdata));
-                    None
-                } else {
-                    unimplemented!("Polling not implemented for clocks yet");
-                }
-            }
-        };
+            };
 
-        if let Some(fd) = fd {
-            let wasi_file_ref = match fd {
-                __WASI_STDERR_FILENO => {
-                    wasi_try_ok!(
-                        inodes
-                            .stderr(&state.fs.fd_map)
-                            .map_err(fs_error_into_wasi_err),
-                        env
-                    )
-                }
-                __WASI_STDIN_FILENO => {
-                    wasi_try_ok!(
-                        inodes
-                            .stdin(&state.fs.fd_map)
-                            .map_err(fs_error_into_wasi_err),
-                        env
-                    )
-                }
-                __WASI_STDOUT_FILENO => {
-                    wasi_try_ok!(
-                        inodes
-                            .stdout(&state.fs.fd_map)
-                            .map_err(fs_error_into_wasi_err),
-                        env
-                    )
-                }
+            let fd_entry = wasi_try_ok!(state.fs.get_fd(fd), env);
+            match fd {
+                __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
                 _ => {
-                    let fd_entry = wasi_try_ok!(state.fs.get_fd(fd), env);
-                    let inode = fd_entry.inode;
-                    if !fd_entry.rights.contains(Rights::POLL_FD_READWRITE) {
+                    if !fd_entry.rights.contains(right)
+                        || !fd_entry.rights.contains(Rights::POLL_FD_READWRITE)
+                    {
                         return Ok(Errno::Access);
                     }
+                }
+            }
 
-                    {
-                        let guard = inodes.arena[inode].read();
-                        let deref = guard.deref();
-                        match deref {
-                            Kind::File { handle, .. } => {
-                                if let Some(h) = handle {
-                                    crate::state::InodeValFileReadGuard { guard }
-                                } else {
-                                    return Ok(Errno::Badf);
-                                }
-                            }
-                            Kind::Socket { .. }
-                            | Kind::Pipe { .. }
-                            | Kind::EventNotifications { .. } => {
-                                return Ok(Errno::Badf);
-                            }
-                            Kind::Dir { .. }
-                            | Kind::Root { .. }
-                            | Kind::Buffer { .. }
-                            | Kind::Symlink { .. } => {
-                                unimplemented!("polling read on non-files not yet supported")
-                            }
-                        }
+            let inode = fd_entry.inode;
+            let mut guard = inodes.arena[inode].write();
+            let host_fd = match guard.deref_mut() {
+                Kind::File {
+                    handle: Some(handle),
+                    ..
+                } => handle.get_fd(),
+                Kind::Socket { socket } => {
+                    // The host socket doesn't know about what was already
+                    // received
+                    if socket.has_buffered_data() {
+                        seen_events[token].0 |= events & PollEvent::PollIn as PollEventSet;
                     }
+                    socket.poll_fd()
                 }
+                Kind::Pipe { .. } | Kind::EventNotifications { .. } => None,
+                Kind::File { handle: None, .. }
+                | Kind::Dir { .. }
+                | Kind::Root { .. }
+                | Kind::Buffer { .. }
+                | Kind::Symlink { .. } => return Ok(Errno::Badf),
+            };
+            let registered = match host_fd {
+                Some(host_fd) => wasi_try_ok!(reactor.add_fd(host_fd, token, events), env),
+                None => false,
             };
-            fd_guards.push(wasi_file_ref);
+            if !registered {
+                let wakes = match guard.deref_mut() {
+                    Kind::Socket { socket } => socket.set_waker(reactor.waker()),
+                    _ => false,
+                };
+                needs_polling |= !wakes;
+            }
+            subscriptions.push((token, inode, events, registered));
         }
     }
 
-    #[allow(clippy::significant_drop_in_scrutinee)]
-    let fds = {
-        let mut f = vec![];
-        for fd in fd_guards.iter() {
-            f.push(wasi_try_ok!(fd.as_ref().ok_or(Errno::Badf)).deref());
+    // Waits on the reactor, checking the sources it can't wait on after
+    // every wake up, until an event is seen or a deadline passes
+    let mut timeout = Duration::ZERO;
+    let mut backoff = Duration::from_millis(1);
+    loop {
+        for (token, events) in wasi_try_ok!(reactor.wait(Some(timeout)), env) {
+            seen_events[token].0 |= events;
+        }
+        {
+            let inodes = state.inodes.read().unwrap();
+            for (token, inode, events, registered) in subscriptions.iter() {
+                if *registered {
+                    continue;
+                }
+                seen_events[*token] = match inode_readiness(&inodes, *inode, *events) {
+                    Ok((seen, nbytes)) => (seen_events[*token].0 | seen, nbytes),
+                    Err(_) => (PollEventBuilder::new().add(PollEvent::PollError).build(), 0),
+                };
+            }
         }
-        f
-    };
 
-    let mut seen_events = vec![Default::default(); in_events.len()];
+        let now = stopwatch.elapsed();
+        let triggered = seen_events.iter().any(|(events, _)| *events != 0);
+        let next_deadline = deadlines.iter().map(|(_, deadline)| *deadline).min();
+        if triggered || next_deadline.map_or(false, |deadline| deadline <= now) {
+            break;
+        }
 
-    let start = platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
-    let mut triggered = 0;
-    while triggered == 0 {
-        let now = platform_clock_time_get(Snapshot0Clockid::Monotonic, 1_000_000).unwrap() as u128;
-        let delta = match now.checked_sub(start) {
-            Some(a) => Duration::from_nanos(a as u64),
-            None => Duration::ZERO,
+        env.yield_now()?;
+        // The reactor is woken up by the sources it waits on, the others
+        // are checked more and more rarely
+        timeout = if needs_polling {
+            let slice = backoff;
+            backoff = (backoff * 2).min(Duration::from_millis(50));
+            slice
+        } else {
+            Duration::from_millis(100)
         };
-        match poll(
-            fds.as_slice(),
-            in_events.as_slice(),
-            seen_events.as_mut_slice(),
-            Duration::from_millis(1),
-        ) {
-            Ok(0) => {
-                env.yield_now()?;
-            }
-            Ok(a) => {
-                triggered = a;
-            }
-            Err(FsError::WouldBlock) => {
-                env.sleep(Duration::from_millis(1))?;
-            }
-            Err(err) => {
-                return Ok(fs_error_into_wasi_err(err));
+        if let Some(deadline) = next_deadline {
+            timeout = timeout.min(deadline - now);
+        }
+    }
+
+    let now = stopwatch.elapsed();
+    for (i, (seen_event, nbytes)) in seen_events.into_iter().enumerate() {
+        let s = wasi_try_mem_ok!(subscription_array.index(i as u64).read());
+        let data = match s.data {
+            SubscriptionEnum::Clock(_) => {
+                let expired = deadlines
+                    .iter()
+                    .any(|(token, deadline)| *token == i && *deadline <= now);
+                if !expired {
+                    continue;
+                }
+                let event = Event {
+                    userdata: s.userdata,
+                    error: Errno::Success,
+                    data: EventEnum::Clock,
+                };
+                wasi_try_mem_ok!(event_array.index(events_seen as u64).write(event));
+                events_seen += 1;
+                continue;
             }
+            data => data,
         };
-        if delta > time_to_sleep {
-            break;
+        if seen_event == 0 {
+            continue;
         }
-    }
 
-    for (i, seen_event) in seen_events.into_iter().enumerate() {
         let mut flags = Eventrwflags::empty();
         let mut error = Errno::Again;
-        let mut bytes_available = 0;
-        let event_iter = iterate_poll_events(seen_event);
-        for event in event_iter {
+        for event in iterate_poll_events(seen_event) {
             match event {
                 PollEvent::PollError => error = Errno::Io,
                 PollEvent::PollHangUp => flags = Eventrwflags::FD_READWRITE_HANGUP,
                 PollEvent::PollInvalid => error = Errno::Inval,
-                PollEvent::PollIn => {
-                    bytes_available = wasi_try_ok!(
-                        fds[i]
-                            .bytes_available_read()
-                            .map_err(fs_error_into_wasi_err),
-                        env
-                    )
-                    .unwrap_or(0usize);
-                    error = Errno::Success;
-                }
-                PollEvent::PollOut => {
-                    bytes_available = wasi_try_ok!(
-                        fds[i]
-                            .bytes_available_write()
-                            .map_err(fs_error_into_wasi_err),
-                        env
-                    )
-                    .unwrap_or(0usize);
-                    error = Errno::Success;
-                }
+                PollEvent::PollIn | PollEvent::PollOut => error = Errno::Success,
             }
         }
+        if flags.contains(Eventrwflags::FD_READWRITE_HANGUP) && error == Errno::Again {
+            error = Errno::Success;
+        }
+        let readwrite = EventFdReadwrite {
+            nbytes: nbytes as u64,
+            flags,
+        };
         let event = Event {
-            userdata: wasi_try_mem_ok!(subscription_array.index(i as u64).read()).userdata,
+            userdata: s.userdata,
             error,
-            data: match wasi_try_mem_ok!(subscription_array.index(i as u64).read()).data {
-                SubscriptionEnum::Read(d) => EventEnum::FdRead(EventFdReadwrite {
-                    nbytes: bytes_available as u64,
-                    flags,
-                }),
-                SubscriptionEnum::Write(d) => EventEnum::FdWrite(EventFdReadwrite {
-                    nbytes: bytes_available as u64,
-                    flags,
-                }),
-                SubscriptionEnum::Clock(_) => EventEnum::Clock,
+            data: match data {
+                SubscriptionEnum::Read(_) => EventEnum::FdRead(readwrite),
+                _ => EventEnum::FdWrite(readwrite),
             },
         };
         wasi_try_mem_ok!(event_array.index(events_seen as u64).write(event));
         events_seen += 1;
     }
-    if triggered == 0 {
-        for (clock_info, userdata) in clock_subs {
-            let event = Event {
-                userdata,
-                error: Errno::Success,
-                data: EventEnum::Clock,
-            };
-            wasi_try_mem_ok!(event_array.index(events_seen as u64).write(event));
-            events_seen += 1;
-        }
-    }
     let events_seen: M::Offset = wasi_try_ok!(events_seen.try_into().map_err(|_| Errno::Overflow));
     wasi_try_mem_ok!(out_ptr.write(events_seen));
     Ok(Errno::Success)
@@ -3316,6 +3411,13 @@ pub fn poll_oneoff<M: MemorySize>(
 /// Terminate the process normally. An exit code of 0 indicates successful
 /// termination of the program. The meanings of other values is dependent on
 /// the environment.
+///
+/// The calling thread ends right away, but the other threads are not
+/// interrupted: they only end the next time they yield in a syscall that
+/// blocks, like `sched_yield`, `poll_oneoff`, `thread_sleep` or a blocking
+/// read. A thread running guest code without making such syscalls keeps
+/// running, so a spawned thread calling `proc_exit` doesn't end a main
+/// thread which is busy computing.
 /// Inputs:
 /// - `__wasi_exitcode_t`
 ///   Exit code to return to the operating system
@@ -3324,6 +3426,8 @@ pub fn proc_exit(
     code: __wasi_exitcode_t,
 ) -> Result<(), WasiError> {
     debug!("wasi::proc_exit, {}", code);
+    // The other threads exit as well the next time they yield
+    ctx.data().state.threading.lock().unwrap().exit_code = Some(code);
     Err(WasiError::Exit(code))
 }
 
@@ -3364,15 +3468,12 @@ pub fn random_get<M: MemorySize>(
     let memory = env.memory_view(&ctx);
     let buf_len64: u64 = buf_len.into();
     let mut u8_buffer = vec![0; buf_len64 as usize];
-    let res = getrandom::getrandom(&mut u8_buffer);
-    match res {
-        Ok(()) => {
-            let buf = wasi_try_mem!(buf.slice(&memory, buf_len));
-            wasi_try_mem!(buf.write_slice(&u8_buffer));
-            Errno::Success
-        }
-        Err(_) => Errno::Io,
-    }
+    wasi_try!(env
+        .random()
+        .and_then(|random| random.random_get(&mut u8_buffer)));
+    let buf = wasi_try_mem!(buf.slice(&memory, buf_len));
+    wasi_try_mem!(buf.write_slice(&u8_buffer));
+    Errno::Success
 }
 
 /// ### `tty_get()`
@@ -3525,68 +3626,263 @@ pub fn thread_spawn<M: MemorySize>(
     if method.as_str() != "_thread_start" {
         return Errno::Notcapable;
     };
-    /*
-    let funct = unsafe {
-        if env.thread_start_ref().is_none() {
-            return Errno::Addrnotavail;
-        }
-        env.thread_start_ref_unchecked()
-    };
-    */
 
-    let reactor = match reactor {
-        Bool::False => false,
-        Bool::True => true,
+    // Reactors are not supported yet
+    match reactor {
+        Bool::False => {}
+        Bool::True => return Errno::Notsup,
         _ => return Errno::Inval,
-    };
+    }
+
+    let child: Tid = wasi_try!(spawn_thread(&ctx, ThreadStart::Wasix(user_data))).into();
+    wasi_try_mem!(ret_tid.write(&memory, child));
+    Errno::Success
+}
+
+/// ### `thread-spawn()`
+/// Creates a new thread of the `wasi-threads` proposal, which starts
+/// in the `wasi_thread_start` function exported by the module with
+/// the ID of the thread and `start_arg`.
+///
+/// ## Parameters
+///
+/// * `start_arg` - Argument that will be supplied to `wasi_thread_start`
+///
+/// ## Return
+///
+/// Returns the ID of the newly created thread, or a negative value on
+/// failure
+pub fn wasi_thread_spawn(ctx: FunctionEnvMut<'_, WasiEnv>, start_arg: i32) -> i32 {
+    debug!("wasi::thread-spawn");
+    match spawn_thread(&ctx, ThreadStart::WasiThreads(start_arg)) {
+        Ok(tid) => u32::from(tid) as i32,
+        Err(err) => -(err as i32),
+    }
+}
+
+/// The function a spawned thread starts in
+#[derive(Debug, Clone, Copy)]
+enum ThreadStart {
+    /// `_thread_start(user_data)` of WASIX
+    Wasix(u64),
+    /// `wasi_thread_start(tid, start_arg)` of `wasi-threads`
+    WasiThreads(i32),
+}
+
+impl ThreadStart {
+    fn export_name(&self) -> &'static str {
+        match self {
+            ThreadStart::Wasix(_) => "_thread_start",
+            ThreadStart::WasiThreads(_) => "wasi_thread_start",
+        }
+    }
+}
 
-    // Create the sub-thread
+/// A spawned thread, which is removed from the threading state and
+/// releases its joiners when dropped (even if it never got to run)
+struct SpawnedThread {
+    state: Arc<WasiState>,
+    thread: WasiThread,
+}
+
+impl Drop for SpawnedThread {
+    fn drop(&mut self) {
+        let mut guard = self.state.threading.lock().unwrap();
+        guard.threads.remove(&self.thread.id);
+        drop(guard);
+
+        self.thread.exit.lock().unwrap().take();
+    }
+}
+
+/// Tunables giving the shared memory of the calling instance to the new
+/// instance of a spawned thread, for modules defining their memory instead
+/// of importing it.
+#[cfg(all(feature = "sys", feature = "compiler"))]
+struct SharedMemoryTunables {
+    base: wasmer::Engine,
+    memory: Mutex<Option<wasmer::vm::VMMemory>>,
+}
+
+#[cfg(all(feature = "sys", feature = "compiler"))]
+impl wasmer::Tunables for SharedMemoryTunables {
+    fn memory_style(&self, memory: &wasmer::MemoryType) -> wasmer::vm::MemoryStyle {
+        self.base.tunables().memory_style(memory)
+    }
+
+    fn table_style(&self, table: &wasmer::TableType) -> wasmer::vm::TableStyle {
+        self.base.tunables().table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &wasmer::MemoryType,
+        style: &wasmer::vm::MemoryStyle,
+    ) -> Result<wasmer::vm::VMMemory, wasmer::vm::MemoryError> {
+        self.base.tunables().create_host_memory(ty, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &wasmer::MemoryType,
+        style: &wasmer::vm::MemoryStyle,
+        vm_definition_location: std::ptr::NonNull<wasmer::vm::VMMemoryDefinition>,
+    ) -> Result<wasmer::vm::VMMemory, wasmer::vm::MemoryError> {
+        use wasmer::vm::LinearMemory;
+
+        match self.memory.lock().unwrap().take() {
+            Some(memory) => {
+                // The code of the instance reads the memory through its own
+                // copy of the definition. The base of a static memory never
+                // moves, and its size is always read from the memory itself.
+                *vm_definition_location.as_ptr() = *memory.vmmemory().as_ptr();
+                Ok(memory)
+            }
+            None => self
+                .base
+                .tunables()
+                .create_vm_memory(ty, style, vm_definition_location),
+        }
+    }
+
+    fn create_host_table(
+        &self,
+        ty: &wasmer::TableType,
+        style: &wasmer::vm::TableStyle,
+    ) -> Result<wasmer::vm::VMTable, String> {
+        self.base.tunables().create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &wasmer::TableType,
+        style: &wasmer::vm::TableStyle,
+        vm_definition_location: std::ptr::NonNull<wasmer::vm::VMTableDefinition>,
+    ) -> Result<wasmer::vm::VMTable, String> {
+        self.base
+            .tunables()
+            .create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
+/// Spawns a thread running a new instance of the module, which shares
+/// the memory of the calling instance.
+///
+/// The memory must be shared, and either imported by the module or defined
+/// by it with a static style (its base address must never move). The new
+/// instance is given the imports built by [`WasiEnv::set_thread_imports`],
+/// or only the WASI imports by default.
+#[cfg(all(feature = "sys", feature = "compiler"))]
+fn spawn_thread(
+    ctx: &FunctionEnvMut<'_, WasiEnv>,
+    start: ThreadStart,
+) -> Result<WasiThreadId, Errno> {
+    use wasmer::vm::{LinearMemory, MemoryStyle};
+    use wasmer::{AsStoreRef, Store};
+
+    let env = ctx.data();
+    let module = env.module.clone().ok_or(Errno::Notsup)?;
+    if !module
+        .exports()
+        .functions()
+        .any(|export| export.name() == start.export_name())
+    {
+        return Err(WasiThreadError::MethodNotFound.into());
+    }
+    let memory_import = module
+        .imports()
+        .memories()
+        .next()
+        .map(|import| (import.module().to_string(), import.name().to_string()));
+    let memory = env.memory().try_clone(ctx).ok_or(Errno::Notsup)?;
+    if memory_import.is_none() && !matches!(memory.style(), MemoryStyle::Static { .. }) {
+        return Err(Errno::Notsup);
+    }
+    let engine = ctx.as_store_ref().engine().clone();
+
+    let thread = SpawnedThread {
+        state: env.state.clone(),
+        thread: env.new_thread(),
+    };
+    let id = thread.thread.id;
     let mut sub_env = env.clone();
-    let mut sub_thread = env.new_thread();
-    sub_env.id = sub_thread.id;
-
-    let child = {
-        let id = sub_thread.id;
-        wasi_try!(env
-            .runtime
-            .thread_spawn(Box::new(move || {
-                /*
-                if let Some(funct) = sub_env.thread_start_ref() {
-                    if let Err(err) = funct.call(user_data) {
-                        warn!("thread failed: {}", err);
-                        std::mem::forget(sub_thread);
-                        return;
-                    }
-                } else {
-                    warn!("failed to start thread: missing callback '__wasix_thread_start'");
-                    std::mem::forget(sub_thread);
-                    return;
+    sub_env.id = id;
+    sub_env.memory = None;
+    let thread_imports = sub_env.thread_imports.clone();
+
+    env.runtime
+        .thread_spawn(Box::new(move || {
+            // A module defining its memory gets the shared one when it is
+            // instantiated, instead of a new one
+            let (mut store, memory) = match memory_import {
+                Some(import) => (Store::new(engine), Some((import, memory))),
+                None => {
+                    let tunables = SharedMemoryTunables {
+                        base: engine.clone(),
+                        memory: Mutex::new(Some(memory)),
+                    };
+                    (Store::new_with_tunables(engine, tunables), None)
                 }
-                */
-
-                let thread = {
-                    let mut guard = sub_env.state.threading.lock().unwrap();
-                    let thread = guard.threads.remove(&id);
-                    drop(guard);
-                    thread
+            };
+            let env = WasiFunctionEnv::new(&mut store, sub_env);
+            let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+                let mut imports = match thread_imports {
+                    Some(thread_imports) => {
+                        thread_imports(&mut store.as_store_mut(), &env.env, &module)?
+                    }
+                    None => env.import_object_for_all_wasi_versions(&mut store, &module)?,
                 };
-
-                if let Some(thread) = thread {
-                    let mut thread_guard = thread.exit.lock().unwrap();
-                    thread_guard.take();
+                let memory = match memory {
+                    Some(((namespace, name), memory)) => {
+                        let memory = Memory::new_from_existing(&mut store, memory);
+                        imports.define(&namespace, &name, memory.clone());
+                        Some(memory)
+                    }
+                    None => None,
+                };
+                let instance = Instance::new(&mut store, &module, &imports)?;
+                let memory = match memory {
+                    Some(memory) => memory,
+                    None => instance.exports.get_memory("memory")?.clone(),
+                };
+                env.data_mut(&mut store).set_memory(memory);
+
+                let result = match start {
+                    ThreadStart::Wasix(user_data) => instance
+                        .exports
+                        .get_typed_function::<u64, ()>(&store, start.export_name())?
+                        .call(&mut store, user_data),
+                    ThreadStart::WasiThreads(start_arg) => instance
+                        .exports
+                        .get_typed_function::<(i32, i32), ()>(&store, start.export_name())?
+                        .call(&mut store, u32::from(id) as i32, start_arg),
+                };
+                match result {
+                    Ok(()) => Ok(()),
+                    // Both `thread_exit` and `proc_exit` end the thread
+                    Err(err) => match err.downcast::<WasiError>() {
+                        Ok(WasiError::Exit(_)) => Ok(()),
+                        Ok(err) => Err(err.into()),
+                        Err(err) => Err(err.into()),
+                    },
                 }
-                drop(sub_thread);
-            }))
-            .map_err(|err| {
-                let err: Errno = err.into();
-                err
-            }));
-        id
-    };
-    let child: Tid = child.into();
+            })();
+            if let Err(err) = result {
+                warn!("thread {} failed: {}", u32::from(id), err);
+            }
+            drop(thread);
+        }))
+        .map_err(Errno::from)?;
 
-    wasi_try_mem!(ret_tid.write(&memory, child));
-    Errno::Success
+    Ok(id)
+}
+
+#[cfg(not(all(feature = "sys", feature = "compiler")))]
+fn spawn_thread(
+    _ctx: &FunctionEnvMut<'_, WasiEnv>,
+    _start: ThreadStart,
+) -> Result<WasiThreadId, Errno> {
+    Err(Errno::Notsup)
 }
 
 /// ### `thread_sleep()`
@@ -3756,6 +4052,17 @@ pub fn process_spawn<M: MemorySize>(
         .split(&['\n', '\r'])
         .map(|a| a.to_string())
         .collect();
+    {
+        let inodes = env.state.inodes.read().unwrap();
+        if let Some(dir) = preopen
+            .iter()
+            .filter(|dir| !dir.is_empty())
+            .find(|dir| !may_preopen(&env.state, inodes.deref(), dir))
+        {
+            debug!("wasi::process_spawn denied the preopen {}", dir);
+            return BusErrno::Denied;
+        }
+    }
 
     let conv_stdio_mode = |mode: WasiStdioMode| match mode {
         WasiStdioMode::Piped => StdioMode::Piped,
@@ -3764,7 +4071,7 @@ pub fn process_spawn<M: MemorySize>(
         /*__WASI_STDIO_MODE_NULL |*/ _ => StdioMode::Null,
     };
 
-    let process = wasi_try_bus!(bus
+    let mut process = wasi_try_bus!(bus
         .new_spawn()
         .chroot(chroot)
         .args(args)
@@ -3787,10 +4094,36 @@ pub fn process_spawn<M: MemorySize>(
         },
     };
 
-    // Convert the stdio
-    let stdin = conv_stdio_fd(process.inst.stdin_fd());
-    let stdout = conv_stdio_fd(process.inst.stdout_fd());
-    let stderr = conv_stdio_fd(process.inst.stderr_fd());
+    // Convert the stdio, giving file descriptors to the ends of the
+    // pipes kept for us by the processes which don't have their own
+    let (stdin, stdout, stderr) = {
+        let mut inodes = env.state.inodes.write().unwrap();
+        let mut stdio_fd =
+            |fd: Option<FileDescriptor>,
+             file: Option<Box<dyn VirtualFile + Send + Sync + 'static>>| {
+                match (fd, file) {
+                    (Some(fd), _) => Ok(Some(fd)),
+                    (None, Some(file)) => bus_stdio_fd(&env.state, inodes.deref_mut(), file)
+                        .map(|fd| Some(fd.into()))
+                        .map_err(|_| BusErrno::Internal),
+                    (None, None) => Ok(None),
+                }
+            };
+        let stdin = wasi_try_bus!(stdio_fd(process.inst.stdin_fd(), process.inst.take_stdin()));
+        let stdout = wasi_try_bus!(stdio_fd(
+            process.inst.stdout_fd(),
+            process.inst.take_stdout()
+        ));
+        let stderr = wasi_try_bus!(stdio_fd(
+            process.inst.stderr_fd(),
+            process.inst.take_stderr()
+        ));
+        (
+            conv_stdio_fd(stdin),
+            conv_stdio_fd(stdout),
+            conv_stdio_fd(stderr),
+        )
+    };
 
     // Add the process to the environment state
     let bid = {
@@ -3813,6 +4146,84 @@ pub fn process_spawn<M: MemorySize>(
     BusErrno::Success
 }
 
+/// Creates a file descriptor for an end of the STDIO of a spawned
+/// process, as a pipe when it is one
+fn bus_stdio_fd(
+    state: &WasiState,
+    inodes: &mut crate::WasiInodes,
+    file: Box<dyn VirtualFile + Send + Sync + 'static>,
+) -> Result<WasiFd, Errno> {
+    let kind = if (*file).upcast_any_ref().is::<WasiPipe>() {
+        let pipe = file.upcast_any_box().downcast::<WasiPipe>().unwrap();
+        Kind::Pipe { pipe: *pipe }
+    } else {
+        Kind::File {
+            handle: Some(file),
+            path: std::path::PathBuf::new(),
+            fd: None,
+        }
+    };
+    let inode = state
+        .fs
+        .create_inode_with_default_stat(inodes, kind, false, "stdio".to_string());
+
+    let rights = Rights::all_pipe();
+    state
+        .fs
+        .create_fd(rights, rights, Fdflags::empty(), 0, inode)
+}
+
+/// Wakes up a thread blocked waiting for an event
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+thread_local! {
+    /// The waker of the current thread, the same every time so that the
+    /// processes can tell it's already registered
+    static THREAD_WAKER: Waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+}
+
+/// Whether the process may hand `dir` to the processes it spawns, which
+/// is only when it's under one of its own preopened directories and its
+/// policy lets it reach it
+fn may_preopen(state: &WasiState, inodes: &crate::WasiInodes, dir: &str) -> bool {
+    // The paths are compared without `.`, and any `..` is refused
+    let normalize = |path: &std::path::Path| -> Option<std::path::PathBuf> {
+        let mut normalized = std::path::PathBuf::new();
+        for component in path.components() {
+            match component {
+                std::path::Component::CurDir => {}
+                std::path::Component::ParentDir => return None,
+                component => normalized.push(component),
+            }
+        }
+        Some(normalized)
+    };
+    let dir = match normalize(std::path::Path::new(dir)) {
+        Some(dir) => dir,
+        None => return false,
+    };
+    if state.fs.policy.check_read(&dir).is_err() {
+        return false;
+    }
+
+    let preopen_fds = state.fs.preopen_fds.read().unwrap();
+    let fd_map = state.fs.fd_map.read().unwrap();
+    preopen_fds
+        .iter()
+        .filter_map(|fd| fd_map.get(fd))
+        .filter_map(|fd| match inodes.arena[fd.inode].read().deref() {
+            Kind::Dir { path, .. } => normalize(path),
+            _ => None,
+        })
+        .any(|preopen| preopen.is_absolute() == dir.is_absolute() && dir.starts_with(preopen))
+}
+
 /// Spawns a new bus process for a particular web WebAssembly
 /// binary that is referenced by its process name.
 ///
@@ -4059,7 +4470,91 @@ pub fn bus_poll<M: MemorySize>(
     let malloc = unsafe { get_input_str_bus!(&memory, malloc, malloc_len) };
     trace!("wasi::bus_poll (timeout={}, malloc={})", timeout, malloc);
 
-    BusErrno::Unsupported
+    let nevents = wasi_try_bus!(from_offset::<M>(nevents).map_err(|_| BusErrno::Badrequest));
+    // The timeout is measured with the clock of the module, and never
+    // expires when the module can't read the time
+    let start = env.monotonic_time();
+    let waker = THREAD_WAKER.with(|waker| waker.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    // Only the exits of the processes are reported, as the calls are not
+    // supported yet
+    let exited = loop {
+        let finished = {
+            let mut guard = env.state.threading.lock().unwrap();
+            let exited: Vec<_> = guard
+                .processes
+                .iter()
+                .filter_map(|(bid, process)| process.inst.exit_code().map(|rval| (*bid, rval)))
+                .take(nevents)
+                .collect();
+            for (bid, _) in exited.iter() {
+                guard.processes.remove(bid);
+                guard.process_reuse.retain(|_, reused| reused != bid);
+            }
+            if !exited.is_empty() || timeout == 0 {
+                break exited;
+            }
+
+            // Asks the processes to wake this thread up when they exit
+            guard.processes.values_mut().any(|process| {
+                // The process stays in its box until it's dropped, it's
+                // never moved out of it
+                unsafe { Pin::new_unchecked(process.inst.as_mut()) }
+                    .poll_finished(&mut cx)
+                    .is_ready()
+            })
+        };
+        if finished {
+            continue;
+        }
+
+        // The timeout is measured with the clock of the module, and never
+        // expires when the module can't read the time
+        match start {
+            Some(start) => {
+                let elapsed = env.monotonic_time().unwrap_or(start).saturating_sub(start);
+                match timeout.checked_sub(elapsed) {
+                    Some(remaining) if remaining > 0 => {
+                        std::thread::park_timeout(Duration::from_nanos(remaining))
+                    }
+                    _ => break Vec::new(),
+                }
+            }
+            None => std::thread::park(),
+        }
+    };
+
+    let event_size = std::mem::size_of::<types::bus::__wasi_busevent_t<M>>() as u64;
+    let base: u64 = events.offset().into();
+    for (n, (bid, rval)) in exited.iter().enumerate() {
+        let event = bus_exit_event::<M>(*bid, *rval);
+        wasi_try_mem_bus!(memory.write(base + n as u64 * event_size, &event));
+    }
+    let nevents = wasi_try_bus!(to_offset::<M>(exited.len()).map_err(|_| BusErrno::Internal));
+    wasi_try_mem_bus!(ret_nevents.write(&memory, nevents));
+
+    BusErrno::Success
+}
+
+/// Serializes the event of a bus process which exited
+fn bus_exit_event<M: MemorySize>(bid: WasiBusProcessId, rval: u32) -> Vec<u8> {
+    let mut event = std::mem::MaybeUninit::<types::bus::__wasi_busevent_t<M>>::zeroed();
+    let ptr = event.as_mut_ptr();
+    // SAFETY: the fields are written in place and every byte of the
+    // event, the padding included, was initialized by the zeroing
+    unsafe {
+        std::ptr::addr_of_mut!((*ptr).tag).write(types::wasi::BusEventType::Exit);
+        std::ptr::addr_of_mut!((*ptr).u.exit).write(types::wasi::BusEventExit {
+            bid: bid.into(),
+            rval,
+        });
+        std::slice::from_raw_parts(
+            ptr as *const u8,
+            std::mem::size_of::<types::bus::__wasi_busevent_t<M>>(),
+        )
+        .to_vec()
+    }
 }
 
 /// Replies to a call that was made to this process
@@ -5187,35 +5682,68 @@ pub fn sock_accept<M: MemorySize>(
 ) -> Result<Errno, WasiError> {
     debug!("wasi::sock_accept");
 
+    let (fd, addr) = wasi_try_ok!(sock_accept_internal(&ctx, sock, fd_flags)?);
+
+    let memory = ctx.data().memory_view(&ctx);
+    wasi_try_mem_ok!(ro_fd.write(&memory, fd));
+    wasi_try_ok!(super::state::write_ip_port(
+        &memory,
+        ro_addr,
+        addr.ip(),
+        addr.port()
+    ));
+
+    Ok(Errno::Success)
+}
+
+/// ### `sock_accept()`
+/// Accept a new incoming connection, as specified by `wasi_snapshot_preview1`
+/// which doesn't return the address of the peer.
+///
+/// ## Parameters
+///
+/// * `fd` - The listening socket.
+/// * `flags` - The desired values of the file descriptor flags.
+///
+/// ## Return
+///
+/// New socket connection
+pub fn sock_accept_v1<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    fd_flags: Fdflags,
+    ro_fd: WasmPtr<WasiFd, M>,
+) -> Result<Errno, WasiError> {
+    debug!("wasi::sock_accept_v1");
+
+    let (fd, _) = wasi_try_ok!(sock_accept_internal(&ctx, sock, fd_flags)?);
+
+    let memory = ctx.data().memory_view(&ctx);
+    wasi_try_mem_ok!(ro_fd.write(&memory, fd));
+
+    Ok(Errno::Success)
+}
+
+/// Waits for a connection on a listening socket and opens a file
+/// descriptor for it
+pub(crate) fn sock_accept_internal(
+    ctx: &FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    fd_flags: Fdflags,
+) -> Result<Result<(WasiFd, SocketAddr), Errno>, WasiError> {
     let env = ctx.data();
-    let (child, addr) = {
-        let mut ret;
-        let (_, state) = env.get_memory_and_wasi_state(&ctx, 0);
-        loop {
-            wasi_try_ok!(
-                match __sock_actor(&ctx, sock, Rights::SOCK_ACCEPT, |socket| socket
-                    .accept_timeout(fd_flags, Duration::from_millis(5)))
-                {
-                    Ok(a) => {
-                        ret = a;
-                        break;
-                    }
-                    Err(Errno::Timedout) => {
-                        env.yield_now()?;
-                        continue;
-                    }
-                    Err(Errno::Again) => {
-                        env.sleep(Duration::from_millis(5))?;
-                        continue;
-                    }
-                    Err(err) => Err(err),
-                }
-            );
+    let (child, addr) = loop {
+        match __sock_actor(ctx, sock, Rights::SOCK_ACCEPT, |socket| {
+            socket.accept_timeout(fd_flags, Duration::from_millis(5))
+        }) {
+            Ok(ret) => break ret,
+            Err(Errno::Timedout) => env.yield_now()?,
+            Err(Errno::Again) => env.sleep(Duration::from_millis(5))?,
+            Err(err) => return Ok(Err(err)),
         }
-        ret
     };
 
-    let (memory, state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
+    let (_, state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(ctx, 0);
 
     let kind = Kind::Socket {
         socket: InodeSocket::new(InodeSocketKind::TcpStream(child)),
@@ -5228,19 +5756,10 @@ pub fn sock_accept<M: MemorySize>(
     );
 
     let rights = Rights::all_socket();
-    let fd = wasi_try_ok!(state
+    Ok(state
         .fs
-        .create_fd(rights, rights, Fdflags::empty(), 0, inode));
-
-    wasi_try_mem_ok!(ro_fd.write(&memory, fd));
-    wasi_try_ok!(super::state::write_ip_port(
-        &memory,
-        ro_addr,
-        addr.ip(),
-        addr.port()
-    ));
-
-    Ok(Errno::Success)
+        .create_fd(rights, rights, Fdflags::empty(), 0, inode)
+        .map(|fd| (fd, addr)))
 }
 
 /// ### `sock_connect()`
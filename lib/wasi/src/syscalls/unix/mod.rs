@@ -4,13 +4,9 @@ use libc::{
     CLOCK_REALTIME, CLOCK_THREAD_CPUTIME_ID,
 };
 use std::mem;
-use wasmer::WasmRef;
 use wasmer_wasi_types::wasi::{Errno, Snapshot0Clockid, Timestamp};
 
-pub fn platform_clock_res_get(
-    clock_id: Snapshot0Clockid,
-    resolution: WasmRef<Timestamp>,
-) -> Result<i64, Errno> {
+pub fn platform_clock_res_get(clock_id: Snapshot0Clockid) -> Result<i64, Errno> {
     let unix_clock_id = match clock_id {
         Snapshot0Clockid::Monotonic => CLOCK_MONOTONIC,
         Snapshot0Clockid::ProcessCputimeId => CLOCK_PROCESS_CPUTIME_ID,
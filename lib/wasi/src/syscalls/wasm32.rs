@@ -4,12 +4,8 @@ use crate::syscalls::types::{
 };
 use chrono::prelude::*;
 use std::mem;
-use wasmer::WasmRef;
 
-pub fn platform_clock_res_get(
-    clock_id: Snapshot0Clockid,
-    resolution: WasmRef<Timestamp>,
-) -> Result<i64, Errno> {
+pub fn platform_clock_res_get(clock_id: Snapshot0Clockid) -> Result<i64, Errno> {
     let t_out = match clock_id {
         Snapshot0Clockid::Monotonic => 10_000_000,
         Snapshot0Clockid::Realtime => 1,
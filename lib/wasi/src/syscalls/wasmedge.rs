@@ -0,0 +1,639 @@
+//! The socket functions of WasmEdge, which guests built with
+//! `wasmedge_wasi_socket` (and the toolchains based on it) import from
+//! `wasi_snapshot_preview1`.
+//!
+//! They do the same as the socket functions of WASIX, on the same
+//! sockets, with different signatures: an address is a `{ buf, size }`
+//! pair pointing to the raw bytes of the IP address, possibly prefixed by
+//! its family, and the port is passed separately. The enumerations use
+//! the numbering of WasmEdge. The functions that are the same as the ones
+//! of `wasi_snapshot_preview1`, like `sock_recv`, aren't repeated here.
+
+use super::*;
+use wasmer::Memory32;
+
+type MemoryType = Memory32;
+
+/// `address_family::inet4`
+const AF_INET4: u32 = 1;
+/// `address_family::inet6`
+const AF_INET6: u32 = 2;
+
+/// `sock_type::any`
+const SOCK_ANY: u32 = 0;
+/// `sock_type::dgram`
+const SOCK_DGRAM: u32 = 1;
+/// `sock_type::stream`
+const SOCK_STREAM: u32 = 2;
+
+/// `sock_opt_level::sol_socket`, the only level
+const SOL_SOCKET: u32 = 0;
+
+/// `sock_opt_so`, in the order of WasmEdge
+const SO_REUSEADDR: u32 = 0;
+const SO_TYPE: u32 = 1;
+const SO_ERROR: u32 = 2;
+const SO_BROADCAST: u32 = 4;
+const SO_SNDBUF: u32 = 5;
+const SO_RCVBUF: u32 = 6;
+const SO_KEEPALIVE: u32 = 7;
+const SO_OOBINLINE: u32 = 8;
+const SO_ACCEPTCONN: u32 = 13;
+
+/// The size of the address buffers prefixed by the address family
+const PREFIXED_ADDRESS_SIZE: u32 = 128;
+
+fn address_family(af: u32) -> Result<Addressfamily, Errno> {
+    match af {
+        AF_INET4 => Ok(Addressfamily::Inet4),
+        AF_INET6 => Ok(Addressfamily::Inet6),
+        _ => Err(Errno::Afnosupport),
+    }
+}
+
+fn wasmedge_socktype(ty: Socktype) -> u32 {
+    match ty {
+        Socktype::Dgram => SOCK_DGRAM,
+        Socktype::Stream => SOCK_STREAM,
+        _ => SOCK_ANY,
+    }
+}
+
+fn read_u32(memory: &MemoryView, offset: u32) -> Result<u32, Errno> {
+    WasmPtr::<u32, MemoryType>::new(offset)
+        .read(memory)
+        .map_err(mem_error_to_wasi)
+}
+
+fn read_slice(memory: &MemoryView, offset: u32, len: u32) -> Result<Vec<u8>, Errno> {
+    WasmPtr::<u8, MemoryType>::new(offset)
+        .slice(memory, len)
+        .and_then(|slice| slice.read_to_vec())
+        .map_err(mem_error_to_wasi)
+}
+
+fn write_slice(memory: &MemoryView, offset: u32, data: &[u8]) -> Result<(), Errno> {
+    memory.write(offset as u64, data).map_err(mem_error_to_wasi)
+}
+
+/// Reads the IP address of an address buffer, made of its 4 or 16 bytes
+/// or of its family followed by its bytes
+fn read_address(memory: &MemoryView, addr: WasmPtr<u8, MemoryType>) -> Result<IpAddr, Errno> {
+    let buf = read_u32(memory, addr.offset())?;
+    let size = read_u32(memory, addr.offset() + 4)?;
+    let (family, bytes) = match size {
+        4 => (AF_INET4, read_slice(memory, buf, 4)?),
+        16 => (AF_INET6, read_slice(memory, buf, 16)?),
+        PREFIXED_ADDRESS_SIZE => {
+            let data = read_slice(memory, buf, 18)?;
+            (
+                u16::from_le_bytes([data[0], data[1]]) as u32,
+                data[2..].to_vec(),
+            )
+        }
+        _ => return Err(Errno::Inval),
+    };
+    match family {
+        AF_INET4 => Ok(IpAddr::V4(Ipv4Addr::new(
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ))),
+        AF_INET6 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes[..16]);
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => Err(Errno::Afnosupport),
+    }
+}
+
+/// Reads the location and the size of an address buffer, and checks that
+/// an address of the family of `ip` fits in it
+fn address_buffer(
+    memory: &MemoryView,
+    addr: WasmPtr<u8, MemoryType>,
+    ip: IpAddr,
+) -> Result<(u32, u32), Errno> {
+    let buf = read_u32(memory, addr.offset())?;
+    let size = read_u32(memory, addr.offset() + 4)?;
+    let len = match ip {
+        IpAddr::V4(_) => 4,
+        IpAddr::V6(_) => 16,
+    };
+    let written = match size {
+        PREFIXED_ADDRESS_SIZE => 2 + len,
+        size if size >= len => size,
+        _ => return Err(Errno::Inval),
+    };
+    if buf as u64 + written as u64 > memory.data_size() {
+        return Err(Errno::Fault);
+    }
+    Ok((buf, size))
+}
+
+/// Writes an IP address to an address buffer, in the format its size
+/// calls for, and returns its family as `4` or `6`
+fn write_address(
+    memory: &MemoryView,
+    addr: WasmPtr<u8, MemoryType>,
+    ip: IpAddr,
+) -> Result<u32, Errno> {
+    let (buf, size) = address_buffer(memory, addr, ip)?;
+    let (family, bytes, version) = match ip {
+        IpAddr::V4(ip) => (AF_INET4 as u16, ip.octets().to_vec(), 4),
+        IpAddr::V6(ip) => (AF_INET6 as u16, ip.octets().to_vec(), 6),
+    };
+    if size == PREFIXED_ADDRESS_SIZE {
+        write_slice(memory, buf, &family.to_le_bytes())?;
+        write_slice(memory, buf + 2, &bytes)?;
+    } else {
+        let mut padded = vec![0; size as usize];
+        padded[..bytes.len()].copy_from_slice(&bytes);
+        write_slice(memory, buf, &padded)?;
+    }
+    Ok(version)
+}
+
+/// ### `sock_open()`
+/// Create a socket, with the address families and the socket types of
+/// WasmEdge
+pub fn sock_open(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    af: u32,
+    ty: u32,
+    ro_sock: WasmPtr<WasiFd, MemoryType>,
+) -> Errno {
+    debug!("wasmedge::sock_open");
+    let af = wasi_try!(address_family(af));
+    let ty = match ty {
+        SOCK_DGRAM => Socktype::Dgram,
+        SOCK_STREAM | SOCK_ANY => Socktype::Stream,
+        _ => return Errno::Inval,
+    };
+    super::sock_open::<MemoryType>(ctx, af, ty, SockProto::Ip, ro_sock)
+}
+
+/// ### `sock_bind()`
+/// Bind a socket to an address and a port
+pub fn sock_bind(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    addr: WasmPtr<u8, MemoryType>,
+    port: u32,
+) -> Errno {
+    debug!("wasmedge::sock_bind");
+    let env = ctx.data();
+    let memory = env.memory_view(&ctx);
+    let ip = wasi_try!(read_address(&memory, addr));
+    let addr = SocketAddr::new(ip, port as u16);
+    wasi_try!(__sock_upgrade(&ctx, sock, Rights::SOCK_BIND, |socket| {
+        socket.bind(env.net(), addr)
+    }));
+    Errno::Success
+}
+
+/// ### `sock_listen()`
+/// Listen for connections on a socket
+pub fn sock_listen(ctx: FunctionEnvMut<'_, WasiEnv>, sock: WasiFd, backlog: u32) -> Errno {
+    super::sock_listen::<MemoryType>(ctx, sock, backlog)
+}
+
+/// ### `sock_accept()`
+/// Accept a new incoming connection, for the versions of WasmEdge that
+/// don't take file descriptor flags
+pub fn sock_accept(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    ro_fd: WasmPtr<WasiFd, MemoryType>,
+) -> Result<Errno, WasiError> {
+    super::sock_accept_v1::<MemoryType>(ctx, sock, Fdflags::empty(), ro_fd)
+}
+
+/// ### `sock_connect()`
+/// Connect a socket to an address and a port
+pub fn sock_connect(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    addr: WasmPtr<u8, MemoryType>,
+    port: u32,
+) -> Errno {
+    debug!("wasmedge::sock_connect");
+    let env = ctx.data();
+    let memory = env.memory_view(&ctx);
+    let ip = wasi_try!(read_address(&memory, addr));
+    let addr = SocketAddr::new(ip, port as u16);
+    wasi_try!(__sock_upgrade(&ctx, sock, Rights::SOCK_CONNECT, |socket| {
+        socket.connect(env.net(), addr)
+    }));
+    Errno::Success
+}
+
+/// ### `sock_recv_from()`
+/// Receive a message and the address and the port it was sent from
+#[allow(clippy::too_many_arguments)]
+pub fn sock_recv_from(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    ri_data: WasmPtr<__wasi_iovec_t<MemoryType>, MemoryType>,
+    ri_data_len: u32,
+    ro_addr: WasmPtr<u8, MemoryType>,
+    _ri_flags: RiFlags,
+    ro_port: WasmPtr<u32, MemoryType>,
+    ro_data_len: WasmPtr<u32, MemoryType>,
+    ro_flags: WasmPtr<u32, MemoryType>,
+) -> Result<Errno, WasiError> {
+    debug!("wasmedge::sock_recv_from");
+    let env = ctx.data();
+    let memory = env.memory_view(&ctx);
+    let iovs_arr = wasi_try_mem_ok!(ri_data.slice(&memory, ri_data_len));
+
+    let (bytes_read, peer) = wasi_try_ok!(__sock_actor_mut(
+        &ctx,
+        sock,
+        Rights::SOCK_RECV_FROM,
+        |socket| {
+            // The received data is consumed, so the address of the peer
+            // has to fit in the buffer before anything is received
+            address_buffer(&memory, ro_addr, socket.addr_local()?.ip())?;
+            socket.recv_from_addr(&memory, iovs_arr)
+        }
+    ));
+    let bytes_read: u32 = wasi_try_ok!(bytes_read.try_into().map_err(|_| Errno::Overflow));
+
+    wasi_try_ok!(write_address(&memory, ro_addr, peer.ip()));
+    wasi_try_mem_ok!(ro_port.write(&memory, peer.port() as u32));
+    wasi_try_mem_ok!(ro_flags.write(&memory, 0));
+    wasi_try_mem_ok!(ro_data_len.write(&memory, bytes_read));
+
+    Ok(Errno::Success)
+}
+
+/// ### `sock_send_to()`
+/// Send a message to an address and a port
+#[allow(clippy::too_many_arguments)]
+pub fn sock_send_to(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    si_data: WasmPtr<__wasi_ciovec_t<MemoryType>, MemoryType>,
+    si_data_len: u32,
+    addr: WasmPtr<u8, MemoryType>,
+    port: u32,
+    _si_flags: SiFlags,
+    ret_data_len: WasmPtr<u32, MemoryType>,
+) -> Result<Errno, WasiError> {
+    debug!("wasmedge::sock_send_to");
+    let env = ctx.data();
+    let memory = env.memory_view(&ctx);
+    let iovs_arr = wasi_try_mem_ok!(si_data.slice(&memory, si_data_len));
+    let ip = wasi_try_ok!(read_address(&memory, addr));
+    let addr = SocketAddr::new(ip, port as u16);
+
+    let bytes_written = wasi_try_ok!(__sock_actor_mut(
+        &ctx,
+        sock,
+        Rights::SOCK_SEND_TO,
+        |socket| socket.send_to_addr(&memory, iovs_arr, addr)
+    ));
+    let bytes_written: u32 = wasi_try_ok!(bytes_written.try_into().map_err(|_| Errno::Overflow));
+    wasi_try_mem_ok!(ret_data_len.write(&memory, bytes_written));
+
+    Ok(Errno::Success)
+}
+
+fn write_socket_address(
+    ctx: &FunctionEnvMut<'_, WasiEnv>,
+    addr: SocketAddr,
+    ro_addr: WasmPtr<u8, MemoryType>,
+    ro_addr_type: WasmPtr<u32, MemoryType>,
+    ro_port: WasmPtr<u32, MemoryType>,
+) -> Result<(), Errno> {
+    let memory = ctx.data().memory_view(ctx);
+    let version = write_address(&memory, ro_addr, addr.ip())?;
+    ro_addr_type
+        .write(&memory, version)
+        .map_err(mem_error_to_wasi)?;
+    ro_port
+        .write(&memory, addr.port() as u32)
+        .map_err(mem_error_to_wasi)
+}
+
+/// ### `sock_getlocaladdr()`
+/// Get the local address and port of a socket
+pub fn sock_getlocaladdr(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    ro_addr: WasmPtr<u8, MemoryType>,
+    ro_addr_type: WasmPtr<u32, MemoryType>,
+    ro_port: WasmPtr<u32, MemoryType>,
+) -> Errno {
+    debug!("wasmedge::sock_getlocaladdr");
+    let addr = wasi_try!(__sock_actor(&ctx, sock, Rights::empty(), |socket| {
+        socket.addr_local()
+    }));
+    wasi_try!(write_socket_address(
+        &ctx,
+        addr,
+        ro_addr,
+        ro_addr_type,
+        ro_port
+    ));
+    Errno::Success
+}
+
+/// ### `sock_getpeeraddr()`
+/// Get the address and port of the peer of a socket
+pub fn sock_getpeeraddr(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    ro_addr: WasmPtr<u8, MemoryType>,
+    ro_addr_type: WasmPtr<u32, MemoryType>,
+    ro_port: WasmPtr<u32, MemoryType>,
+) -> Errno {
+    debug!("wasmedge::sock_getpeeraddr");
+    let addr = wasi_try!(__sock_actor(&ctx, sock, Rights::empty(), |socket| {
+        socket.addr_peer()
+    }));
+    wasi_try!(write_socket_address(
+        &ctx,
+        addr,
+        ro_addr,
+        ro_addr_type,
+        ro_port
+    ));
+    Errno::Success
+}
+
+/// ### `sock_getsockopt()`
+/// Get a socket option, as an `i32`
+pub fn sock_getsockopt(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    level: u32,
+    name: u32,
+    ro_flag: WasmPtr<i32, MemoryType>,
+    ro_flag_size: WasmPtr<u32, MemoryType>,
+) -> Errno {
+    debug!("wasmedge::sock_getsockopt(name={})", name);
+    if level != SOL_SOCKET {
+        return Errno::Noprotoopt;
+    }
+    let flag = |option: Sockoption| {
+        __sock_actor(&ctx, sock, Rights::empty(), |socket| {
+            socket.get_opt_flag(option.into()).map(|flag| flag as i32)
+        })
+    };
+    let value = wasi_try!(match name {
+        SO_REUSEADDR => flag(Sockoption::ReuseAddr),
+        SO_BROADCAST => flag(Sockoption::Broadcast),
+        SO_KEEPALIVE => flag(Sockoption::KeepAlive),
+        SO_OOBINLINE => flag(Sockoption::OobInline),
+        SO_TYPE => __sock_actor(&ctx, sock, Rights::empty(), |socket| {
+            socket.socktype().map(|ty| wasmedge_socktype(ty) as i32)
+        }),
+        SO_ERROR => Ok(0),
+        SO_SNDBUF => __sock_actor(&ctx, sock, Rights::empty(), |socket| {
+            socket.send_buf_size().map(|size| size as i32)
+        }),
+        SO_RCVBUF => __sock_actor(&ctx, sock, Rights::empty(), |socket| {
+            socket.recv_buf_size().map(|size| size as i32)
+        }),
+        SO_ACCEPTCONN => __sock_actor(&ctx, sock, Rights::empty(), |socket| {
+            Ok(socket.is_listening() as i32)
+        }),
+        _ => Err(Errno::Noprotoopt),
+    });
+
+    let memory = ctx.data().memory_view(&ctx);
+    wasi_try_mem!(ro_flag.write(&memory, value));
+    wasi_try_mem!(ro_flag_size.write(&memory, std::mem::size_of::<i32>() as u32));
+    Errno::Success
+}
+
+/// ### `sock_setsockopt()`
+/// Set a socket option, from an `i32`
+pub fn sock_setsockopt(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    level: u32,
+    name: u32,
+    flag: WasmPtr<i32, MemoryType>,
+    flag_size: u32,
+) -> Errno {
+    debug!("wasmedge::sock_setsockopt(name={})", name);
+    if level != SOL_SOCKET {
+        return Errno::Noprotoopt;
+    }
+    if (flag_size as usize) < std::mem::size_of::<i32>() {
+        return Errno::Inval;
+    }
+    let memory = ctx.data().memory_view(&ctx);
+    let value = wasi_try_mem!(flag.read(&memory));
+
+    let set_flag = |option: Sockoption| {
+        __sock_actor_mut(&ctx, sock, Rights::empty(), |socket| {
+            socket.set_opt_flag(option.into(), value != 0)
+        })
+    };
+    let size = value.max(0) as usize;
+    wasi_try!(match name {
+        SO_REUSEADDR => set_flag(Sockoption::ReuseAddr),
+        SO_BROADCAST => set_flag(Sockoption::Broadcast),
+        SO_KEEPALIVE => set_flag(Sockoption::KeepAlive),
+        SO_OOBINLINE => set_flag(Sockoption::OobInline),
+        SO_SNDBUF => __sock_actor_mut(&ctx, sock, Rights::empty(), |socket| {
+            socket.set_send_buf_size(size)
+        }),
+        SO_RCVBUF => __sock_actor_mut(&ctx, sock, Rights::empty(), |socket| {
+            socket.set_recv_buf_size(size)
+        }),
+        _ => Err(Errno::Noprotoopt),
+    });
+    Errno::Success
+}
+
+/// The offsets of the fields of a `WasiAddrinfo`
+mod addrinfo {
+    pub(super) const FAMILY: u32 = 2;
+    pub(super) const SOCKTYPE: u32 = 3;
+    pub(super) const ADDRLEN: u32 = 8;
+    pub(super) const ADDR: u32 = 12;
+    pub(super) const CANONNAME: u32 = 16;
+    pub(super) const CANONNAMELEN: u32 = 20;
+    pub(super) const NEXT: u32 = 24;
+}
+
+/// The offsets of the fields of a `WasiSockaddr`
+mod sockaddr {
+    pub(super) const FAMILY: u32 = 0;
+    pub(super) const DATA_LEN: u32 = 4;
+    pub(super) const DATA: u32 = 8;
+}
+
+/// ### `sock_getaddrinfo()`
+/// Resolve a host name and a service, made of a port number, into the
+/// list of `WasiAddrinfo` allocated by the guest.
+///
+/// `res` points to the first of at most `max_len` results, which are
+/// linked by their `ai_next` field. The address of each result is written
+/// to its `ai_addr`, as the port followed by the IP address, both in
+/// network byte order, and truncated to the size of its buffer.
+#[allow(clippy::too_many_arguments)]
+pub fn sock_getaddrinfo(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    node: WasmPtr<u8, MemoryType>,
+    node_len: u32,
+    service: WasmPtr<u8, MemoryType>,
+    service_len: u32,
+    hints: WasmPtr<u8, MemoryType>,
+    res: WasmPtr<u32, MemoryType>,
+    max_len: u32,
+    res_len: WasmPtr<u32, MemoryType>,
+) -> Errno {
+    debug!("wasmedge::sock_getaddrinfo");
+    let env = ctx.data();
+    let memory = env.memory_view(&ctx);
+
+    let host = wasi_try_mem!(node.read_utf8_string(&memory, node_len));
+    let service = wasi_try_mem!(service.read_utf8_string(&memory, service_len));
+    let port = match service.as_str() {
+        "" => 0,
+        "http" => 80,
+        "https" => 443,
+        service => wasi_try!(service.parse::<u16>().map_err(|_| Errno::Inval)),
+    };
+    let (family, socktype) = if hints.is_null() {
+        (0, SOCK_ANY as u8)
+    } else {
+        let hint = wasi_try!(read_slice(&memory, hints.offset(), 4));
+        (
+            hint[addrinfo::FAMILY as usize] as u32,
+            hint[addrinfo::SOCKTYPE as usize],
+        )
+    };
+
+    let ips = wasi_try!(env
+        .net()
+        .resolve(host.as_str(), Some(port), None)
+        .map_err(net_error_into_wasi_err));
+    let ips = ips.into_iter().filter(|ip| match family {
+        AF_INET4 => ip.is_ipv4(),
+        AF_INET6 => ip.is_ipv6(),
+        _ => true,
+    });
+
+    let mut entry = wasi_try_mem!(res.read(&memory));
+    let mut count = 0;
+    for ip in ips.take(max_len as usize) {
+        if entry == 0 {
+            break;
+        }
+        let (family, data) = match ip {
+            IpAddr::V4(ip) => {
+                let mut data = port.to_be_bytes().to_vec();
+                data.extend_from_slice(&ip.octets());
+                (AF_INET4 as u8, data)
+            }
+            IpAddr::V6(ip) => {
+                // Like a `sockaddr_in6`: port, flow info, address and scope
+                let mut data = port.to_be_bytes().to_vec();
+                data.extend_from_slice(&[0; 4]);
+                data.extend_from_slice(&ip.octets());
+                data.extend_from_slice(&[0; 4]);
+                (AF_INET6 as u8, data)
+            }
+        };
+
+        wasi_try!(write_slice(&memory, entry + addrinfo::FAMILY, &[family]));
+        wasi_try!(write_slice(
+            &memory,
+            entry + addrinfo::SOCKTYPE,
+            &[socktype]
+        ));
+        let addr = wasi_try!(read_u32(&memory, entry + addrinfo::ADDR));
+        if addr != 0 {
+            let capacity = wasi_try!(read_u32(&memory, addr + sockaddr::DATA_LEN));
+            let data = &data[..data.len().min(capacity as usize)];
+            let buf = wasi_try!(read_u32(&memory, addr + sockaddr::DATA));
+            wasi_try!(write_slice(&memory, buf, data));
+            wasi_try!(write_slice(&memory, addr + sockaddr::FAMILY, &[family]));
+            wasi_try!(write_slice(
+                &memory,
+                addr + sockaddr::DATA_LEN,
+                &(data.len() as u32).to_le_bytes()
+            ));
+            wasi_try!(write_slice(
+                &memory,
+                entry + addrinfo::ADDRLEN,
+                &(data.len() as u32).to_le_bytes()
+            ));
+        }
+        let canonname = wasi_try!(read_u32(&memory, entry + addrinfo::CANONNAME));
+        if canonname != 0 {
+            let capacity = wasi_try!(read_u32(&memory, entry + addrinfo::CANONNAMELEN));
+            let name = &host.as_bytes()[..host.len().min(capacity as usize)];
+            wasi_try!(write_slice(&memory, canonname, name));
+            wasi_try!(write_slice(
+                &memory,
+                entry + addrinfo::CANONNAMELEN,
+                &(name.len() as u32).to_le_bytes()
+            ));
+        }
+
+        count += 1;
+        entry = wasi_try!(read_u32(&memory, entry + addrinfo::NEXT));
+    }
+
+    wasi_try_mem!(res_len.write(&memory, count));
+    Errno::Success
+}
+
+#[cfg(all(test, feature = "sys"))]
+mod tests {
+    use super::*;
+    use wasmer::{Memory, MemoryType as WasmMemoryType, Store};
+
+    #[test]
+    fn addresses() {
+        let mut store = Store::default();
+        let memory = Memory::new(&mut store, WasmMemoryType::new(1, None, false)).unwrap();
+        let view = memory.view(&store);
+        let addr = WasmPtr::<u8, MemoryType>::new(0);
+
+        // A 16 bytes buffer, as used by `sock_getlocaladdr`
+        view.write(0, &[16, 0, 0, 0, 16, 0, 0, 0]).unwrap();
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(write_address(&view, addr, ip).unwrap(), 4);
+        let mut bytes = [0; 4];
+        view.read(16, &mut bytes).unwrap();
+        assert_eq!(bytes, [10, 0, 0, 1]);
+
+        // The same bytes read back as an IPv6 address
+        assert_eq!(
+            read_address(&view, addr).unwrap(),
+            IpAddr::V6(Ipv6Addr::new(0x0a00, 0x0001, 0, 0, 0, 0, 0, 0))
+        );
+
+        // A buffer prefixed by the address family
+        view.write(4, &PREFIXED_ADDRESS_SIZE.to_le_bytes()).unwrap();
+        assert_eq!(
+            write_address(&view, addr, Ipv6Addr::LOCALHOST.into()).unwrap(),
+            6
+        );
+        assert_eq!(read_address(&view, addr).unwrap(), Ipv6Addr::LOCALHOST);
+        view.write(16, &[1, 0, 127, 0, 0, 1]).unwrap();
+        assert_eq!(read_address(&view, addr).unwrap(), Ipv4Addr::LOCALHOST);
+
+        view.write(4, &3u32.to_le_bytes()).unwrap();
+        assert_eq!(read_address(&view, addr), Err(Errno::Inval));
+        assert_eq!(
+            address_buffer(&view, addr, Ipv4Addr::LOCALHOST.into()),
+            Err(Errno::Inval)
+        );
+
+        // A buffer running past the end of the memory
+        view.write(0, &[0xf8, 0xff, 0, 0, 16, 0, 0, 0]).unwrap();
+        assert_eq!(
+            address_buffer(&view, addr, Ipv6Addr::LOCALHOST.into()),
+            Err(Errno::Fault)
+        );
+    }
+}
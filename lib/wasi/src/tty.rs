@@ -0,0 +1,734 @@
+//! The terminals seen by the modules through `tty_get` and `tty_set`.
+//!
+//! [`HostTty`] reflects the terminal `wasmer` itself runs in, while
+//! [`Pty`] is an in-process pseudo-terminal for embedders that draw the
+//! terminal themselves.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use wasmer_vfs::{FsError, VirtualFile};
+
+use crate::WasiTtyState;
+
+/// The terminal behind the standard streams of a module.
+pub trait TtyBridge: fmt::Debug + Send + Sync {
+    /// Gets the current state of the terminal
+    fn tty_get(&self) -> WasiTtyState;
+
+    /// Applies the modes requested by the module. The size of the
+    /// terminal belongs to the host and is not changed.
+    fn tty_set(&self, tty_state: WasiTtyState);
+}
+
+#[cfg(all(unix, feature = "sys"))]
+pub use self::host::HostTty;
+
+#[cfg(all(unix, feature = "sys"))]
+mod host {
+    use super::*;
+    use std::mem;
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::Once;
+
+    /// Bumped by the `SIGWINCH` handler every time the terminal is resized
+    static RESIZES: AtomicU64 = AtomicU64::new(0);
+    static INSTALL_SIGWINCH: Once = Once::new();
+    static INSTALL_TERMINATION: Once = Once::new();
+
+    // The handlers that were installed before ours, which ours chain to
+    static mut PREV_SIGWINCH: Option<libc::sigaction> = None;
+    static mut PREV_SIGINT: Option<libc::sigaction> = None;
+    static mut PREV_SIGTERM: Option<libc::sigaction> = None;
+    /// The original modes of the terminal, for the signal handlers which
+    /// can't lock `ORIGINAL_MODES`. Only read while `SIGNAL_MODES_SET` is.
+    static mut SIGNAL_MODES: Option<libc::termios> = None;
+    static SIGNAL_MODES_SET: AtomicBool = AtomicBool::new(false);
+
+    /// Sets the modes the signal handlers put back, if any
+    fn set_signal_modes(modes: Option<libc::termios>) {
+        SIGNAL_MODES_SET.store(false, Ordering::SeqCst);
+        if let Some(modes) = modes {
+            unsafe { SIGNAL_MODES = Some(modes) };
+            SIGNAL_MODES_SET.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Calls the handler that `previous` installed, if it is a function
+    unsafe fn chain(
+        previous: &libc::sigaction,
+        signum: libc::c_int,
+        siginfo: *mut libc::siginfo_t,
+        context: *mut libc::c_void,
+    ) {
+        if previous.sa_flags & libc::SA_SIGINFO != 0 {
+            mem::transmute::<
+                usize,
+                extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
+            >(previous.sa_sigaction)(signum, siginfo, context)
+        } else if previous.sa_sigaction != libc::SIG_DFL && previous.sa_sigaction != libc::SIG_IGN {
+            mem::transmute::<usize, extern "C" fn(libc::c_int)>(previous.sa_sigaction)(signum)
+        }
+    }
+
+    unsafe fn current_action(signum: libc::c_int) -> libc::sigaction {
+        let mut action: libc::sigaction = mem::zeroed();
+        libc::sigaction(signum, ptr::null(), &mut action);
+        action
+    }
+
+    unsafe fn install(
+        signum: libc::c_int,
+        handler: unsafe extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
+    ) {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signum, &action, ptr::null_mut());
+    }
+
+    unsafe extern "C" fn on_sigwinch(
+        signum: libc::c_int,
+        siginfo: *mut libc::siginfo_t,
+        context: *mut libc::c_void,
+    ) {
+        RESIZES.fetch_add(1, Ordering::Relaxed);
+        if let Some(previous) = ptr::addr_of!(PREV_SIGWINCH).read() {
+            chain(&previous, signum, siginfo, context);
+        }
+    }
+
+    /// Puts the terminal back as it was before the module changed its
+    /// modes, then hands the signal to the handler installed before, or
+    /// raises it again with its default action, which ends the process.
+    unsafe extern "C" fn on_termination(
+        signum: libc::c_int,
+        siginfo: *mut libc::siginfo_t,
+        context: *mut libc::c_void,
+    ) {
+        if SIGNAL_MODES_SET.load(Ordering::SeqCst) {
+            if let Some(modes) = ptr::addr_of!(SIGNAL_MODES).read() {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &modes);
+            }
+        }
+        let previous = match signum {
+            libc::SIGINT => ptr::addr_of!(PREV_SIGINT).read(),
+            libc::SIGTERM => ptr::addr_of!(PREV_SIGTERM).read(),
+            _ => None,
+        };
+        if let Some(previous) = previous {
+            if previous.sa_sigaction == libc::SIG_DFL {
+                // The signal is blocked while it is handled, and is
+                // delivered again once this returns
+                libc::sigaction(signum, &previous, ptr::null_mut());
+                libc::raise(signum);
+            } else {
+                chain(&previous, signum, siginfo, context);
+            }
+        }
+    }
+
+    /// Counts the resizes of the terminal, along with the handler that was
+    /// installed for them, if any.
+    fn install_sigwinch_handler() {
+        INSTALL_SIGWINCH.call_once(|| unsafe {
+            PREV_SIGWINCH = Some(current_action(libc::SIGWINCH));
+            install(libc::SIGWINCH, on_sigwinch);
+        });
+    }
+
+    /// Makes `SIGINT` and `SIGTERM` restore the modes of the terminal
+    /// before they are handled. The signals that are ignored are left as
+    /// they are.
+    fn install_termination_handlers() {
+        INSTALL_TERMINATION.call_once(|| unsafe {
+            for (signum, previous) in [
+                (libc::SIGINT, ptr::addr_of_mut!(PREV_SIGINT)),
+                (libc::SIGTERM, ptr::addr_of_mut!(PREV_SIGTERM)),
+            ] {
+                let current = current_action(signum);
+                if current.sa_sigaction != libc::SIG_IGN {
+                    *previous = Some(current);
+                    install(signum, on_termination);
+                }
+            }
+        });
+    }
+
+    fn is_tty(fd: libc::c_int) -> bool {
+        unsafe { libc::isatty(fd) == 1 }
+    }
+
+    fn window_size() -> Option<libc::winsize> {
+        [libc::STDOUT_FILENO, libc::STDIN_FILENO, libc::STDERR_FILENO]
+            .iter()
+            .find_map(|fd| {
+                let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+                if unsafe { libc::ioctl(*fd, libc::TIOCGWINSZ as _, &mut size) } == 0
+                    && size.ws_col > 0
+                {
+                    Some(size)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// The modes of the terminal before the module changed them
+    static ORIGINAL_MODES: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+    #[derive(Debug)]
+    struct HostTtyState {
+        /// The state last handed out, with the number of resizes it saw
+        tty: WasiTtyState,
+        resizes: Option<u64>,
+    }
+
+    #[derive(Debug)]
+    struct HostTtyInner {
+        state: Mutex<HostTtyState>,
+    }
+
+    impl Drop for HostTtyInner {
+        fn drop(&mut self) {
+            HostTty::restore();
+        }
+    }
+
+    /// The terminal of the host process.
+    ///
+    /// The size follows the terminal as it is resized, and the echo and
+    /// line buffering modes set by the module are applied to the terminal
+    /// of the standard input, until the last clone of this is dropped and
+    /// the original modes are restored. They are also restored when
+    /// `SIGINT` or `SIGTERM` end the process.
+    ///
+    /// The handlers of `SIGWINCH`, `SIGINT` and `SIGTERM` that are
+    /// installed for this are process wide, and call the ones that were
+    /// installed before them.
+    #[derive(Debug, Clone)]
+    pub struct HostTty {
+        inner: Arc<HostTtyInner>,
+    }
+
+    impl HostTty {
+        /// Creates a handle to the terminal of the host process.
+        ///
+        /// The first call installs the process wide `SIGWINCH` handler
+        /// that tracks the size of the terminal.
+        pub fn new() -> Self {
+            install_sigwinch_handler();
+            Self {
+                inner: Arc::new(HostTtyInner {
+                    state: Mutex::new(HostTtyState {
+                        tty: WasiTtyState::default(),
+                        resizes: None,
+                    }),
+                }),
+            }
+        }
+
+        /// Puts back the modes the terminal had before the module changed
+        /// them, for when the process exits without dropping the runtime
+        pub fn restore() {
+            if let Some(original) = ORIGINAL_MODES.lock().unwrap().take() {
+                // A later signal must not put these modes back over the
+                // ones the embedder sets from now on
+                set_signal_modes(None);
+                unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original) };
+            }
+        }
+    }
+
+    impl Default for HostTty {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl TtyBridge for HostTty {
+        fn tty_get(&self) -> WasiTtyState {
+            let mut state = self.inner.state.lock().unwrap();
+            let resizes = RESIZES.load(Ordering::Relaxed);
+            if state.resizes != Some(resizes) {
+                state.resizes = Some(resizes);
+                if let Some(size) = window_size() {
+                    state.tty.cols = size.ws_col as u32;
+                    state.tty.rows = size.ws_row as u32;
+                    state.tty.width = size.ws_xpixel as u32;
+                    state.tty.height = size.ws_ypixel as u32;
+                }
+            }
+            state.tty.stdin_tty = is_tty(libc::STDIN_FILENO);
+            state.tty.stdout_tty = is_tty(libc::STDOUT_FILENO);
+            state.tty.stderr_tty = is_tty(libc::STDERR_FILENO);
+            state.tty.clone()
+        }
+
+        fn tty_set(&self, tty_state: WasiTtyState) {
+            let mut state = self.inner.state.lock().unwrap();
+            state.tty.echo = tty_state.echo;
+            state.tty.line_buffered = tty_state.line_buffered;
+            if !is_tty(libc::STDIN_FILENO) {
+                return;
+            }
+
+            let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+                return;
+            }
+            {
+                let mut original = ORIGINAL_MODES.lock().unwrap();
+                if original.is_none() {
+                    *original = Some(termios);
+                    set_signal_modes(Some(termios));
+                }
+            }
+            install_termination_handlers();
+            // The signals are left to the terminal, so that Ctrl-C still
+            // interrupts `wasmer` in raw mode, and puts it back as it was
+            if tty_state.echo {
+                termios.c_lflag |= libc::ECHO;
+            } else {
+                termios.c_lflag &= !libc::ECHO;
+            }
+            if tty_state.line_buffered {
+                termios.c_lflag |= libc::ICANON;
+            } else {
+                termios.c_lflag &= !libc::ICANON;
+                termios.c_cc[libc::VMIN] = 1;
+                termios.c_cc[libc::VTIME] = 0;
+            }
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+        }
+    }
+}
+
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+const END_OF_TRANSMISSION: u8 = 0x04;
+
+#[derive(Debug)]
+struct PtyState {
+    tty: WasiTtyState,
+    /// The bytes the module can read
+    input: VecDeque<u8>,
+    /// The line being typed, in line buffered mode
+    line: Vec<u8>,
+    /// Set by Ctrl-D on an empty line, to make the next read return 0
+    end_of_input: bool,
+    /// The bytes the module wrote, for the terminal to display
+    output: VecDeque<u8>,
+    master_open: bool,
+    writers: usize,
+}
+
+impl PtyState {
+    fn echo(&mut self, bytes: &[u8]) {
+        if self.tty.echo {
+            self.output.extend(bytes);
+        }
+    }
+
+    /// Runs the line discipline on the bytes typed in the terminal
+    fn type_in(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if !self.tty.line_buffered {
+                self.input.push_back(byte);
+                self.echo(&[byte]);
+                continue;
+            }
+            match byte {
+                b'\r' | b'\n' => {
+                    self.line.push(b'\n');
+                    self.input.extend(self.line.drain(..));
+                    self.echo(b"\r\n");
+                }
+                BACKSPACE | DELETE => {
+                    if self.line.pop().is_some() {
+                        self.echo(&[BACKSPACE, b' ', BACKSPACE]);
+                    }
+                }
+                END_OF_TRANSMISSION => {
+                    if self.line.is_empty() {
+                        self.end_of_input = true;
+                    }
+                    self.input.extend(self.line.drain(..));
+                }
+                _ => {
+                    self.line.push(byte);
+                    self.echo(&[byte]);
+                }
+            }
+        }
+    }
+
+    /// Writes the output of the module, turning its newlines into the
+    /// carriage return and newline pairs terminals expect
+    fn write_out(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                self.output.push_back(b'\r');
+            }
+            self.output.push_back(byte);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PtyInner {
+    state: Mutex<PtyState>,
+    changed: Condvar,
+}
+
+impl PtyInner {
+    fn update<T>(&self, f: impl FnOnce(&mut PtyState) -> T) -> T {
+        let ret = f(&mut self.state.lock().unwrap());
+        self.changed.notify_all();
+        ret
+    }
+}
+
+/// An in-process pseudo-terminal.
+///
+/// The module gets the [`PtyStdin`] and [`PtyStdout`] ends as its standard
+/// streams, and the runtime uses the `Pty` itself as its [`TtyBridge`],
+/// while the terminal drawn by the embedder types in and reads the output
+/// through the [`PtyMaster`].
+///
+/// ```
+/// # use wasmer_wasi::{PluggableRuntimeImplementation, Pty, WasiState};
+/// let pty = Pty::new(80, 25);
+/// let master = pty.master();
+/// let mut runtime = PluggableRuntimeImplementation::default();
+/// runtime.set_tty_implementation(pty.clone());
+///
+/// let mut builder = WasiState::new("shell");
+/// builder
+///     .stdin(Box::new(pty.stdin()))
+///     .stdout(Box::new(pty.stdout()))
+///     .stderr(Box::new(pty.stdout()))
+///     .runtime(runtime);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pty {
+    inner: Arc<PtyInner>,
+}
+
+impl Pty {
+    pub fn new(cols: u32, rows: u32) -> Self {
+        let tty = WasiTtyState {
+            cols,
+            rows,
+            stdin_tty: true,
+            stdout_tty: true,
+            stderr_tty: true,
+            ..Default::default()
+        };
+        Self {
+            inner: Arc::new(PtyInner {
+                state: Mutex::new(PtyState {
+                    tty,
+                    input: VecDeque::new(),
+                    line: Vec::new(),
+                    end_of_input: false,
+                    output: VecDeque::new(),
+                    master_open: true,
+                    writers: 0,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// The end of the terminal, to type in and to read the output of the
+    /// module. There should be only one, as dropping it ends the input.
+    pub fn master(&self) -> PtyMaster {
+        PtyMaster {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// The standard input of the module
+    pub fn stdin(&self) -> PtyStdin {
+        PtyStdin {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// The standard output, or error, of the module
+    pub fn stdout(&self) -> PtyStdout {
+        self.inner.update(|state| state.writers += 1);
+        PtyStdout {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl TtyBridge for Pty {
+    fn tty_get(&self) -> WasiTtyState {
+        self.inner.state.lock().unwrap().tty.clone()
+    }
+
+    fn tty_set(&self, tty_state: WasiTtyState) {
+        self.inner.update(|state| {
+            // Leaving line buffered mode hands the line typed so far
+            if state.tty.line_buffered && !tty_state.line_buffered {
+                let line = std::mem::take(&mut state.line);
+                state.input.extend(line);
+            }
+            state.tty.echo = tty_state.echo;
+            state.tty.line_buffered = tty_state.line_buffered;
+        })
+    }
+}
+
+/// The terminal side of a [`Pty`].
+///
+/// Writing types in, as if on a keyboard, and reading blocks until the
+/// module writes some output, or returns 0 once all its outputs are closed.
+#[derive(Debug)]
+pub struct PtyMaster {
+    inner: Arc<PtyInner>,
+}
+
+impl PtyMaster {
+    /// Changes the size of the terminal, as seen by the module
+    pub fn resize(&self, cols: u32, rows: u32) {
+        self.inner.update(|state| {
+            state.tty.cols = cols;
+            state.tty.rows = rows;
+        })
+    }
+
+    /// Reads the output written so far, without blocking
+    pub fn try_read(&mut self, buf: &mut [u8]) -> usize {
+        let mut state = self.inner.state.lock().unwrap();
+        read_from(&mut state.output, buf)
+    }
+}
+
+impl Drop for PtyMaster {
+    fn drop(&mut self) {
+        self.inner.update(|state| state.master_open = false)
+    }
+}
+
+impl Read for PtyMaster {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.inner.state.lock().unwrap();
+        while state.output.is_empty() && state.writers > 0 {
+            state = self.inner.changed.wait(state).unwrap();
+        }
+        Ok(read_from(&mut state.output, buf))
+    }
+}
+
+impl Write for PtyMaster {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.update(|state| state.type_in(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn read_from(queue: &mut VecDeque<u8>, buf: &mut [u8]) -> usize {
+    let read = buf.len().min(queue.len());
+    for (dst, src) in buf.iter_mut().zip(queue.drain(..read)) {
+        *dst = src;
+    }
+    read
+}
+
+/// The standard input of a module attached to a [`Pty`]
+#[derive(Debug)]
+pub struct PtyStdin {
+    inner: Arc<PtyInner>,
+}
+
+impl Read for PtyStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if !state.input.is_empty() {
+                return Ok(read_from(&mut state.input, buf));
+            }
+            if state.end_of_input {
+                state.end_of_input = false;
+                return Ok(0);
+            }
+            if !state.master_open {
+                return Ok(0);
+            }
+            state = self.inner.changed.wait(state).unwrap();
+        }
+    }
+}
+
+impl Write for PtyStdin {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not write to stdin",
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The standard output of a module attached to a [`Pty`]
+#[derive(Debug)]
+pub struct PtyStdout {
+    inner: Arc<PtyInner>,
+}
+
+impl Drop for PtyStdout {
+    fn drop(&mut self) {
+        self.inner.update(|state| state.writers -= 1)
+    }
+}
+
+impl Read for PtyStdout {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not read from stdout",
+        ))
+    }
+}
+
+impl Write for PtyStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.update(|state| state.write_out(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_pty_file {
+    ($name:ident, $bytes_available_read:ident) => {
+        impl Seek for $name {
+            fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "can not seek in a terminal",
+                ))
+            }
+        }
+
+        impl VirtualFile for $name {
+            fn last_accessed(&self) -> u64 {
+                0
+            }
+            fn last_modified(&self) -> u64 {
+                0
+            }
+            fn created_time(&self) -> u64 {
+                0
+            }
+            fn size(&self) -> u64 {
+                0
+            }
+            fn set_len(&mut self, _len: u64) -> Result<(), FsError> {
+                Err(FsError::PermissionDenied)
+            }
+            fn unlink(&mut self) -> Result<(), FsError> {
+                Ok(())
+            }
+            fn bytes_available_read(&self) -> Result<Option<usize>, FsError> {
+                let state = self.inner.state.lock().unwrap();
+                Ok($bytes_available_read(&state))
+            }
+        }
+    };
+}
+
+// A closed input is reported with no count, so that it polls as readable
+fn stdin_bytes_available(state: &PtyState) -> Option<usize> {
+    if state.input.is_empty() && (state.end_of_input || !state.master_open) {
+        None
+    } else {
+        Some(state.input.len())
+    }
+}
+
+fn stdout_bytes_available(_state: &PtyState) -> Option<usize> {
+    Some(0)
+}
+
+impl_pty_file!(PtyStdin, stdin_bytes_available);
+impl_pty_file!(PtyStdout, stdout_bytes_available);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(master: &mut PtyMaster) -> Vec<u8> {
+        let mut buf = [0u8; 256];
+        let read = master.try_read(&mut buf);
+        buf[..read].to_vec()
+    }
+
+    #[test]
+    fn pty_line_discipline() {
+        let pty = Pty::new(80, 25);
+        let mut master = pty.master();
+        let mut stdin = pty.stdin();
+        let mut stdout = pty.stdout();
+
+        master.write_all(b"lx\x7fs\r").unwrap();
+        assert_eq!(read_all(&mut master), b"lx\x08 \x08s\r\n");
+        let mut buf = [0u8; 16];
+        let read = stdin.read(&mut buf).unwrap();
+        assert_eq!(&buf[..read], b"ls\n");
+
+        // Raw mode hands the bytes over as they are typed, without echo
+        pty.tty_set(WasiTtyState {
+            echo: false,
+            line_buffered: false,
+            ..pty.tty_get()
+        });
+        master.write_all(b"q").unwrap();
+        assert_eq!(read_all(&mut master), b"");
+        assert_eq!(stdin.bytes_available_read().unwrap(), Some(1));
+        let read = stdin.read(&mut buf).unwrap();
+        assert_eq!(&buf[..read], b"q");
+
+        stdout.write_all(b"a\nb").unwrap();
+        assert_eq!(read_all(&mut master), b"a\r\nb");
+
+        master.resize(120, 40);
+        let state = pty.tty_get();
+        assert_eq!((state.cols, state.rows), (120, 40));
+        assert!(state.stdin_tty && !state.echo);
+
+        drop(master);
+        assert_eq!(stdin.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn pty_end_of_input() {
+        let pty = Pty::new(80, 25);
+        let mut master = pty.master();
+        let mut stdin = pty.stdin();
+
+        master.write_all(b"\x04").unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(stdin.read(&mut buf).unwrap(), 0);
+
+        master.write_all(b"ab\x04").unwrap();
+        let read = stdin.read(&mut buf).unwrap();
+        assert_eq!(&buf[..read], b"ab");
+    }
+}
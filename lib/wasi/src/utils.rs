@@ -1,7 +1,7 @@
 use std::collections::BTreeSet;
 #[cfg(not(feature = "js"))]
 use wasmer::vm::VMSharedMemory;
-use wasmer::{AsStoreMut, Imports, Memory, Module};
+use wasmer::{AsStoreMut, Extern, Function, Imports, Memory, Module};
 use wasmer_wasi_types::wasi::Errno;
 
 #[allow(dead_code)]
@@ -24,8 +24,59 @@ pub fn is_wasix_module(module: &Module) -> bool {
     }
 }
 
+/// The functions only WasmEdge provides in `wasi_snapshot_preview1`, to
+/// tell apart the guests using its socket functions.
+const WASMEDGE_SOCK_FUNCTIONS: &[&str] = &[
+    "sock_open",
+    "sock_bind",
+    "sock_listen",
+    "sock_connect",
+    "sock_recv_from",
+    "sock_send_to",
+    "sock_getlocaladdr",
+    "sock_getpeeraddr",
+    "sock_getsockopt",
+    "sock_setsockopt",
+    "sock_getaddrinfo",
+];
+
+/// Returns if the module uses the socket functions of WasmEdge, which
+/// have to be imported with [`wasi_import_wasmedge_sockets`](crate::wasi_import_wasmedge_sockets)
+pub fn is_wasmedge_sockets_module(module: &Module) -> bool {
+    module.imports().functions().any(|f| {
+        f.module() == SNAPSHOT1_NAMESPACE
+            && (WASMEDGE_SOCK_FUNCTIONS.contains(&f.name())
+                // The standard `sock_accept` also takes the flags of the
+                // new file descriptor
+                || (f.name() == "sock_accept" && f.ty().params().len() == 2))
+    })
+}
+
+/// Whether `namespace` is one of the WASI Preview 2 interfaces, named like
+/// `wasi:io/streams@0.2.0`.
+pub(crate) fn is_preview2_namespace(namespace: &str) -> bool {
+    namespace.starts_with("wasi:") && namespace.ends_with("@0.2.0")
+}
+
+/// Returns if the module imports WASI Preview 2 interfaces, which have to
+/// be imported with [`wasi_import_preview2`](crate::wasi_import_preview2)
+pub fn is_preview2_module(module: &Module) -> bool {
+    module
+        .imports()
+        .functions()
+        .any(|f| is_preview2_namespace(f.module()))
+}
+
 pub fn map_io_err(err: std::io::Error) -> Errno {
     use std::io::ErrorKind;
+    // The file systems report some of their errors, like exceeded
+    // quotas, wrapped in an I/O error.
+    if let Some(fs_error) = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<wasmer_vfs::FsError>())
+    {
+        return crate::state::fs_error_into_wasi_err(*fs_error);
+    }
     match err.kind() {
         ErrorKind::NotFound => Errno::Noent,
         ErrorKind::PermissionDenied => Errno::Perm,
@@ -88,6 +139,25 @@ pub fn wasi_import_shared_memory(
 ) {
 }
 
+/// Replaces the functions of the import object with the result of `wrap`
+pub(crate) fn wrap_imports<S, F>(store: &mut S, imports: &Imports, mut wrap: F) -> Imports
+where
+    S: AsStoreMut,
+    F: FnMut(&mut S, String, String, Function) -> Function,
+{
+    let mut wrapped = Imports::new();
+    for ((namespace, name), ext) in imports {
+        let ext = match ext {
+            Extern::Function(inner) => {
+                Extern::Function(wrap(store, namespace.clone(), name.clone(), inner))
+            }
+            other => other,
+        };
+        wrapped.define(&namespace, &name, ext);
+    }
+    wrapped
+}
+
 /// The version of WASI. This is determined by the imports namespace
 /// string.
 #[derive(Debug, Clone, Copy, Eq)]
@@ -186,6 +256,10 @@ const WASIX_32V1_NAMESPACE: &str = "wasix_32v1";
 /// Namespace for the `wasix` version.
 const WASIX_64V1_NAMESPACE: &str = "wasix_64v1";
 
+/// Namespace of the `wasi-threads` proposal, which extends the other
+/// versions with `thread-spawn` rather than being a version on its own.
+pub(crate) const WASI_THREADS_NAMESPACE: &str = "wasi";
+
 /// Detect the version of WASI being used based on the import
 /// namespaces.
 ///
@@ -245,6 +319,8 @@ pub fn get_wasi_versions(module: &Module, strict: bool) -> Option<BTreeSet<WasiV
             WASIX_64V1_NAMESPACE => {
                 out.insert(WasiVersion::Wasix64v1);
             }
+            WASI_THREADS_NAMESPACE => {}
+            ns if is_preview2_namespace(ns) => {}
             _ => {
                 non_wasi_seen = true;
             }
@@ -0,0 +1,64 @@
+#![cfg(all(feature = "sys", feature = "compiler"))]
+
+use std::io::Read;
+use std::time::Duration;
+
+use wasmer::{Module, Store};
+use wasmer_vbus::{BusError, StdioMode};
+use wasmer_wasi::{LocalVirtualBus, VirtualBus};
+
+#[test]
+fn test_local_bus_spawn() {
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        br#"
+    (module
+        (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_unstable" "proc_exit" (func $proc_exit (param i32)))
+
+        (memory 1)
+        (export "memory" (memory 0))
+
+        (data (i32.const 8) "hello world\n")
+
+        (func $main (export "_start")
+            (i32.store (i32.const 0) (i32.const 8))
+            (i32.store (i32.const 4) (i32.const 12))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+            (call $proc_exit (i32.const 3))
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    let bus = LocalVirtualBus::new(store.engine().clone());
+    bus.add_command("hello", module);
+
+    let mut process = bus
+        .new_spawn()
+        .stdout_mode(StdioMode::Piped)
+        .spawn("hello")
+        .unwrap();
+
+    let mut stdout = process.inst.take_stdout().unwrap();
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "hello world\n");
+
+    let mut exit_code = None;
+    for _ in 0..100 {
+        exit_code = process.inst.exit_code();
+        if exit_code.is_some() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(exit_code, Some(3));
+
+    assert_eq!(
+        bus.new_spawn().spawn("missing").unwrap_err(),
+        BusError::InvalidWapm
+    );
+}
@@ -0,0 +1,178 @@
+#![cfg(all(feature = "sys", feature = "compiler", feature = "checkpoint"))]
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use wasmer::{
+    imports, AsStoreMut, Function, FunctionEnvMut, FunctionType, Imports, Instance, Module, Store,
+};
+use wasmer_vfs::{mem_fs, FileSystem};
+use wasmer_wasi::{
+    generate_import_object_from_env, WasiCheckpoint, WasiCheckpointer, WasiEnv, WasiFunctionEnv,
+    WasiState, WasiVersion,
+};
+
+/// Opens `a.txt` in the preopened `/data`, reads two bytes of it, then
+/// pauses. `resume` reads the rest of the file from the same fd.
+const WAT: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "path_open"
+        (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_read"
+        (func $fd_read (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+    (import "env" "pause" (func $pause))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    (data (i32.const 32) "a.txt")
+
+    (func (export "_start")
+        (i32.store (i32.const 0)
+            (call $path_open
+                (i32.const 4) (i32.const 0) (i32.const 32) (i32.const 5) (i32.const 0)
+                (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 24)))
+        (i32.store (i32.const 8) (i32.const 64))
+        (i32.store (i32.const 12) (i32.const 2))
+        (i32.store (i32.const 4)
+            (call $fd_read (i32.load (i32.const 24)) (i32.const 8) (i32.const 1) (i32.const 16)))
+        (call $pause)
+        (call $proc_exit (i32.const 0))
+    )
+
+    (func (export "resume") (result i32)
+        (i32.store (i32.const 8) (i32.const 80))
+        (i32.store (i32.const 12) (i32.const 16))
+        (call $fd_read (i32.load (i32.const 24)) (i32.const 8) (i32.const 1) (i32.const 20))
+    )
+)
+"#;
+
+/// Instantiates the module with the WASI imports and `pause`
+fn instantiate(
+    store: &mut Store,
+    module: &Module,
+    wasi_env: &mut WasiFunctionEnv,
+    pause: impl FnOnce(&mut Store, &WasiFunctionEnv) -> Function,
+) -> Instance {
+    let mut imports: Imports =
+        generate_import_object_from_env(store, &wasi_env.env, WasiVersion::Snapshot1);
+    let pause = pause(store, wasi_env);
+    imports.extend(&imports! { "env" => { "pause" => pause } });
+    let instance = Instance::new(store, module, &imports).unwrap();
+    wasi_env.initialize(store, &instance).unwrap();
+    instance
+}
+
+fn read(store: &mut impl AsStoreMut, instance: &Instance, offset: u64, len: usize) -> Vec<u8> {
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut data = vec![0; len];
+    memory.view(store).read(offset, &mut data).unwrap();
+    data
+}
+
+#[test]
+fn test_checkpoint_restore() {
+    let fs = mem_fs::FileSystem::default();
+    fs.create_dir(Path::new("/data")).unwrap();
+    fs.new_open_options()
+        .write(true)
+        .create(true)
+        .open(Path::new("/data/a.txt"))
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    let mut store = Store::default();
+    let module = Module::new(&store, WAT).unwrap();
+    let mut wasi_env = WasiState::new("checkpoint")
+        .set_fs(Box::new(fs.clone()))
+        .preopen_dir("/data")
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+
+    // Captures the instance while it's paused in the host function
+    let checkpoint = Arc::new(Mutex::new(None::<WasiCheckpoint>));
+    let captured = checkpoint.clone();
+    let instance = instantiate(&mut store, &module, &mut wasi_env, |store, wasi_env| {
+        Function::new_with_env(
+            store,
+            &wasi_env.env,
+            FunctionType::new([], []),
+            move |env: FunctionEnvMut<WasiEnv>, _| {
+                let taken = env
+                    .data()
+                    .checkpoint(&env, &WasiCheckpointer::new())
+                    .unwrap();
+                *captured.lock().unwrap() = Some(taken);
+                Ok(vec![])
+            },
+        )
+    });
+    let start = instance.exports.get_function("_start").unwrap();
+    let err = start.call(&mut store, &[]).unwrap_err();
+    assert_eq!(err.message(), "WASI exited with code: 0");
+    assert_eq!(read(&mut store, &instance, 0, 8), [0; 8]);
+    assert_eq!(read(&mut store, &instance, 64, 2), b"he");
+
+    let bytes = checkpoint
+        .lock()
+        .unwrap()
+        .take()
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let checkpoint = WasiCheckpoint::from_bytes(&bytes).unwrap();
+
+    // Without the `mem-fs` feature the in-memory filesystem isn't captured,
+    // and the file is opened again in the one it is in
+    let fs_backing = if cfg!(feature = "mem-fs") {
+        mem_fs::FileSystem::default()
+    } else {
+        fs
+    };
+    let state = WasiCheckpointer::new()
+        .restore(&checkpoint, Some(Box::new(fs_backing)))
+        .unwrap();
+    let original = wasi_env.data_mut(&mut store).state();
+    assert_eq!(
+        *state.fs.preopen_fds.read().unwrap(),
+        *original.fs.preopen_fds.read().unwrap()
+    );
+    let mut fds = state
+        .fs
+        .fd_map
+        .read()
+        .unwrap()
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+    let mut original_fds = original
+        .fs
+        .fd_map
+        .read()
+        .unwrap()
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+    fds.sort_unstable();
+    original_fds.sort_unstable();
+    assert_eq!(fds, original_fds);
+
+    let mut store = Store::default();
+    let mut wasi_env = WasiFunctionEnv::new(&mut store, WasiEnv::new(state));
+    let instance = instantiate(&mut store, &module, &mut wasi_env, |store, _| {
+        Function::new_typed(store, || panic!("the restored instance doesn't pause"))
+    });
+    let memory = instance.exports.get_memory("memory").unwrap();
+    checkpoint.restore_memory(&mut store, memory).unwrap();
+    assert_eq!(read(&mut store, &instance, 64, 2), b"he");
+
+    // The file is read on from where the instance left it
+    let resume = instance.exports.get_function("resume").unwrap();
+    let errno = resume.call(&mut store, &[]).unwrap();
+    assert_eq!(errno[0].unwrap_i32(), 0);
+    assert_eq!(read(&mut store, &instance, 20, 4), [3, 0, 0, 0]);
+    assert_eq!(read(&mut store, &instance, 80, 3), b"llo");
+}
@@ -0,0 +1,123 @@
+#![cfg(all(feature = "sys", feature = "compiler"))]
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use wasmer::{Instance, Module, Store, TypedFunction};
+use wasmer_wasi::types::wasi::{Errno, Snapshot0Clockid, Timestamp};
+use wasmer_wasi::{
+    generate_import_object_from_env, WasiClock, WasiFunctionEnv, WasiState, WasiStateBuilder,
+    WasiVersion,
+};
+
+/// Sleeps with `poll_oneoff` on a relative timeout of the monotonic clock,
+/// storing the number of events at 128 and the first event at 64
+const WAT: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "poll_oneoff"
+        (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func (export "sleep") (param i64) (result i32)
+        (i64.store (i32.const 0) (i64.const 42))
+        (i32.store8 (i32.const 8) (i32.const 0))
+        (i32.store (i32.const 16) (i32.const 1))
+        (i64.store (i32.const 24) (local.get 0))
+        (i64.store (i32.const 32) (i64.const 0))
+        (i32.store16 (i32.const 40) (i32.const 0))
+        (call $poll_oneoff (i32.const 0) (i32.const 64) (i32.const 1) (i32.const 128))
+    )
+)
+"#;
+
+/// A clock standing still.
+#[derive(Debug)]
+struct StoppedClock;
+
+impl WasiClock for StoppedClock {
+    fn res_get(&self, _clock_id: Snapshot0Clockid) -> Result<Timestamp, Errno> {
+        Ok(1)
+    }
+
+    fn time_get(
+        &self,
+        _clock_id: Snapshot0Clockid,
+        _precision: Timestamp,
+    ) -> Result<Timestamp, Errno> {
+        Ok(1_000)
+    }
+}
+
+/// A clock moving an hour forward every time it's read.
+#[derive(Debug, Default)]
+struct HastyClock(AtomicU64);
+
+impl WasiClock for HastyClock {
+    fn res_get(&self, _clock_id: Snapshot0Clockid) -> Result<Timestamp, Errno> {
+        Ok(1)
+    }
+
+    fn time_get(
+        &self,
+        _clock_id: Snapshot0Clockid,
+        _precision: Timestamp,
+    ) -> Result<Timestamp, Errno> {
+        const HOUR: u64 = 3_600_000_000_000;
+        Ok(self.0.fetch_add(HOUR, Ordering::SeqCst) + HOUR)
+    }
+}
+
+/// Sleeps for `timeout` in a module of the environment built by `builder`,
+/// returning how long it took on the host.
+fn sleep(builder: &mut WasiStateBuilder, timeout: Duration) -> Duration {
+    let mut store = Store::default();
+    let module = Module::new(&store, WAT).unwrap();
+    let mut wasi_env: WasiFunctionEnv = builder.finalize(&mut store).unwrap();
+    let imports =
+        generate_import_object_from_env(&mut store, &wasi_env.env, WasiVersion::Snapshot1);
+    let instance = Instance::new(&mut store, &module, &imports).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let sleep: TypedFunction<u64, i32> = instance
+        .exports
+        .get_typed_function(&store, "sleep")
+        .unwrap();
+    let start = Instant::now();
+    let errno = sleep.call(&mut store, timeout.as_nanos() as u64).unwrap();
+    let elapsed = start.elapsed();
+    assert_eq!(errno, Errno::Success as i32);
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut nevents = [0; 4];
+    memory.view(&store).read(128, &mut nevents).unwrap();
+    assert_eq!(u32::from_le_bytes(nevents), 1);
+    let mut userdata = [0; 8];
+    memory.view(&store).read(64, &mut userdata).unwrap();
+    assert_eq!(u64::from_le_bytes(userdata), 42);
+
+    elapsed
+}
+
+#[test]
+fn test_sleep_with_stopped_clock() {
+    let timeout = Duration::from_millis(20);
+    let elapsed = sleep(WasiState::new("clock").clock(StoppedClock), timeout);
+    assert!(elapsed >= timeout);
+}
+
+#[test]
+fn test_sleep_with_hasty_clock() {
+    let elapsed = sleep(
+        WasiState::new("clock").clock(HastyClock::default()),
+        Duration::from_secs(60),
+    );
+    assert!(elapsed < Duration::from_secs(30));
+}
+
+#[test]
+fn test_sleep_without_clock() {
+    let timeout = Duration::from_millis(20);
+    let elapsed = sleep(WasiState::new("clock").deterministic(true), timeout);
+    assert!(elapsed >= timeout);
+}
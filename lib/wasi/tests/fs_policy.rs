@@ -0,0 +1,76 @@
+#![cfg(all(feature = "sys", feature = "compiler", feature = "mem-fs"))]
+
+use std::io::Write;
+use std::path::Path;
+use wasmer::{Instance, Module, Store};
+use wasmer_vfs::{mem_fs, FileSystem};
+use wasmer_wasi::{generate_import_object_from_env, FsAccess, FsPolicy, WasiState, WasiVersion};
+use wasmer_wasi_types::wasi::Errno;
+
+/// Opens `/data/a.txt` for reading, then unlinks it while it is open, and
+/// stores the results of both syscalls at 0 and 4
+const WAT: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "path_open"
+        (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_unlink_file"
+        (func $path_unlink_file (param i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    (data (i32.const 16) "a.txt")
+
+    (func (export "_start")
+        (i32.store (i32.const 0)
+            (call $path_open
+                (i32.const 4) (i32.const 0) (i32.const 16) (i32.const 5) (i32.const 0)
+                (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 8)))
+        (i32.store (i32.const 4)
+            (call $path_unlink_file (i32.const 4) (i32.const 16) (i32.const 5)))
+        (call $proc_exit (i32.const 0))
+    )
+)
+"#;
+
+#[test]
+fn test_unlink_open_file_under_read_only_rule() {
+    let fs = mem_fs::FileSystem::default();
+    fs.create_dir(Path::new("/data")).unwrap();
+    fs.new_open_options()
+        .write(true)
+        .create(true)
+        .open(Path::new("/data/a.txt"))
+        .unwrap()
+        .write_all(b"data")
+        .unwrap();
+
+    let mut policy = FsPolicy::new();
+    policy.access("/data", FsAccess::ReadOnly);
+
+    let mut store = Store::default();
+    let module = Module::new(&store, WAT).unwrap();
+    let mut wasi_env = WasiState::new("fs_policy")
+        .set_fs(Box::new(fs.clone()))
+        .fs_policy(policy)
+        .preopen_dir("/data")
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+    let imports =
+        generate_import_object_from_env(&mut store, &wasi_env.env, WasiVersion::Snapshot1);
+    let instance = Instance::new(&mut store, &module, &imports).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    let err = start.call(&mut store, &[]).unwrap_err();
+    assert_eq!(err.message(), "WASI exited with code: 0");
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut results = [0; 8];
+    memory.view(&store).read(0, &mut results).unwrap();
+    assert_eq!(results[0..4], [Errno::Success as u8, 0, 0, 0]);
+    assert_eq!(results[4..8], [Errno::Rofs as u8, 0, 0, 0]);
+
+    assert!(fs.metadata(Path::new("/data/a.txt")).is_ok());
+}
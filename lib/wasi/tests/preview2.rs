@@ -0,0 +1,392 @@
+#![cfg(not(feature = "js"))]
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use wasmer::Store;
+use wasmer_vfs::{mem_fs, FileSystem};
+use wasmer_vnet::in_memory::InMemoryNetworking;
+use wasmer_wasi::preview2::{
+    Datagram, DescriptorFlags, DescriptorType, IpAddressFamily, NetworkErrorCode, OpenFlags,
+    PathFlags, StreamError,
+};
+use wasmer_wasi::{Pipe, PluggableRuntimeImplementation, WasiPreview2, WasiState};
+
+#[test]
+fn test_preview2_cli() {
+    let mut store = Store::default();
+    let mut stdout = Pipe::default();
+    let wasi_env = WasiState::new("command-name")
+        .args(&["Gordon"])
+        .env("DOG", "X")
+        .stdout(Box::new(stdout.clone()))
+        .finalize(&mut store)
+        .unwrap();
+    let mut wasi = WasiPreview2::new(wasi_env.data_mut(&mut store));
+
+    assert!(wasi.get_arguments().contains(&"Gordon".to_string()));
+    assert_eq!(
+        wasi.get_environment(),
+        vec![("DOG".to_string(), "X".to_string())]
+    );
+
+    let out = wasi.get_stdout();
+    wasi.blocking_write_and_flush(out, b"hello world\n")
+        .unwrap();
+    wasi.drop_output_stream(out);
+    assert_eq!(
+        wasi.write(out, b"dropped"),
+        Err(StreamError::LastOperationFailed(
+            wasmer_wasi::types::wasi::Errno::Badf
+        ))
+    );
+
+    let mut stdout_str = String::new();
+    stdout.read_to_string(&mut stdout_str).unwrap();
+    assert_eq!(stdout_str, "hello world\n");
+
+    assert_eq!(wasi.get_random_bytes(16).unwrap().len(), 16);
+    let start = wasi.monotonic_clock_now().unwrap();
+    let pollable = wasi.subscribe_duration(1_000_000).unwrap();
+    wasi.block(pollable).unwrap();
+    assert!(wasi.ready(pollable).unwrap());
+    assert!(wasi.monotonic_clock_now().unwrap() >= start + 1_000_000);
+}
+
+#[test]
+fn test_preview2_filesystem() {
+    let fs = mem_fs::FileSystem::default();
+    fs.create_dir(Path::new("/sandbox")).unwrap();
+
+    let mut store = Store::default();
+    let wasi_env = WasiState::new("command-name")
+        .set_fs(Box::new(fs))
+        .map_dir("sandbox", "/sandbox")
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+    let mut wasi = WasiPreview2::new(wasi_env.data_mut(&mut store));
+
+    let (root, name) = wasi.get_directories().pop().unwrap();
+    assert_eq!(name, "sandbox");
+    assert_eq!(wasi.get_type(root).unwrap(), DescriptorType::Directory);
+
+    wasi.create_directory_at(root, "sub").unwrap();
+    let file = wasi
+        .open_at(
+            root,
+            PathFlags::default(),
+            "sub/file.txt",
+            OpenFlags {
+                create: true,
+                ..Default::default()
+            },
+            DescriptorFlags {
+                read: true,
+                write: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let out = wasi.write_via_stream(file, 0).unwrap();
+    wasi.blocking_write_and_flush(out, b"hello").unwrap();
+    let out = wasi.append_via_stream(file).unwrap();
+    wasi.blocking_write_and_flush(out, b" world").unwrap();
+
+    let input = wasi.read_via_stream(file, 6).unwrap();
+    assert_eq!(wasi.blocking_read(input, 64).unwrap(), b"world");
+    assert_eq!(wasi.blocking_read(input, 64), Err(StreamError::Closed));
+    assert_eq!(
+        wasi.read_at(file, 64, 0).unwrap(),
+        (b"hello world".to_vec(), true)
+    );
+    assert_eq!(wasi.stat(file).unwrap().size, 11);
+
+    let sub = wasi
+        .open_at(
+            root,
+            PathFlags::default(),
+            "sub",
+            OpenFlags {
+                directory: true,
+                ..Default::default()
+            },
+            DescriptorFlags {
+                read: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let entries = wasi.read_directory(sub).unwrap();
+    let entry = wasi.read_directory_entry(entries).unwrap().unwrap();
+    assert_eq!(entry.name, "file.txt");
+    assert_eq!(entry.type_, DescriptorType::RegularFile);
+    assert_eq!(wasi.read_directory_entry(entries).unwrap(), None);
+
+    wasi.drop_descriptor(file).unwrap();
+    wasi.rename_at(root, "sub/file.txt", root, "moved.txt")
+        .unwrap();
+    assert_eq!(
+        wasi.stat_at(root, PathFlags::default(), "moved.txt")
+            .unwrap()
+            .size,
+        11
+    );
+    wasi.unlink_file_at(root, "moved.txt").unwrap();
+    wasi.drop_descriptor(sub).unwrap();
+    wasi.remove_directory_at(root, "sub").unwrap();
+    wasi.drop_descriptor(root).unwrap();
+}
+
+#[test]
+fn test_preview2_stream_readiness() {
+    let mut store = Store::default();
+    let stdin = Pipe::default();
+    let wasi_env = WasiState::new("command-name")
+        .stdin(Box::new(stdin.clone()))
+        .finalize(&mut store)
+        .unwrap();
+    let mut wasi = WasiPreview2::new(wasi_env.data_mut(&mut store));
+
+    let input = wasi.get_stdin();
+    let pollable = wasi.subscribe_input_stream(input).unwrap();
+    assert!(!wasi.ready(pollable).unwrap(), "nothing to read yet");
+
+    let out = wasi.get_stdout();
+    let writable = wasi.subscribe_output_stream(out).unwrap();
+    assert_eq!(wasi.poll(&[pollable, writable]).unwrap(), vec![1]);
+
+    let writer = std::thread::spawn(move || {
+        let mut stdin = stdin;
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        stdin.write_all(b"input").unwrap();
+    });
+    assert_eq!(wasi.poll(&[pollable]).unwrap(), vec![0]);
+    writer.join().unwrap();
+
+    // The length asked for by the guest isn't allocated up front
+    assert_eq!(wasi.read(input, u64::MAX).unwrap(), b"input");
+}
+
+#[test]
+fn test_preview2_sockets() {
+    let mut store = Store::default();
+    let mut runtime = PluggableRuntimeImplementation::default();
+    runtime.set_networking_implementation(InMemoryNetworking::new());
+    let wasi_env = WasiState::new("command-name")
+        .runtime(runtime)
+        .finalize(&mut store)
+        .unwrap();
+    let mut wasi = WasiPreview2::new(wasi_env.data_mut(&mut store));
+    let network = wasi.instance_network();
+
+    let server_address = "127.0.0.1:8080".parse().unwrap();
+    let server = wasi.create_tcp_socket(IpAddressFamily::Ipv4).unwrap();
+    assert_eq!(
+        wasi.tcp_finish_bind(server),
+        Err(NetworkErrorCode::NotInProgress)
+    );
+    wasi.tcp_start_bind(server, network, server_address)
+        .unwrap();
+    wasi.tcp_finish_bind(server).unwrap();
+    wasi.tcp_start_listen(server).unwrap();
+    wasi.tcp_finish_listen(server).unwrap();
+    assert_eq!(wasi.tcp_local_address(server), Ok(server_address));
+    assert_eq!(
+        wasi.tcp_accept(server).map(|_| ()),
+        Err(NetworkErrorCode::WouldBlock)
+    );
+
+    let client = wasi.create_tcp_socket(IpAddressFamily::Ipv4).unwrap();
+    wasi.tcp_start_connect(client, network, server_address)
+        .unwrap();
+    let (_, client_out) = wasi.tcp_finish_connect(client).unwrap();
+    assert_eq!(wasi.tcp_remote_address(client), Ok(server_address));
+
+    let (accepted, accepted_in, _) = wasi.tcp_accept(server).unwrap();
+    assert_eq!(wasi.tcp_address_family(accepted), Ok(IpAddressFamily::Ipv4));
+    wasi.blocking_write_and_flush(client_out, b"hello").unwrap();
+    assert_eq!(wasi.blocking_read(accepted_in, 16).unwrap(), b"hello");
+    wasi.drop_tcp_socket(accepted).unwrap();
+    wasi.drop_tcp_socket(client).unwrap();
+    wasi.drop_tcp_socket(server).unwrap();
+
+    let receiver_address = "127.0.0.1:9000".parse().unwrap();
+    let sender_address = "127.0.0.1:9001".parse().unwrap();
+    let receiver = wasi.create_udp_socket(IpAddressFamily::Ipv4).unwrap();
+    wasi.udp_start_bind(receiver, network, receiver_address)
+        .unwrap();
+    wasi.udp_finish_bind(receiver).unwrap();
+    let sender = wasi.create_udp_socket(IpAddressFamily::Ipv4).unwrap();
+    wasi.udp_start_bind(sender, network, sender_address)
+        .unwrap();
+    wasi.udp_finish_bind(sender).unwrap();
+    assert_eq!(wasi.udp_receive(receiver, 8), Ok(Vec::new()));
+
+    let datagrams = [b"ping".to_vec(), b"pong".to_vec()]
+        .iter()
+        .map(|data| Datagram {
+            data: data.clone(),
+            remote_address: receiver_address,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(wasi.udp_send(sender, &datagrams), Ok(2));
+    let received = wasi.udp_receive(receiver, 8).unwrap();
+    assert_eq!(
+        received,
+        datagrams
+            .into_iter()
+            .map(|datagram| Datagram {
+                remote_address: sender_address,
+                ..datagram
+            })
+            .collect::<Vec<_>>()
+    );
+    wasi.drop_udp_socket(sender).unwrap();
+    wasi.drop_udp_socket(receiver).unwrap();
+    wasi.drop_network(network);
+}
+
+/// Writes its argument and a file it reads back to stdout, through the
+/// Preview 2 imports only.
+#[cfg(all(feature = "sys", feature = "compiler"))]
+const GUEST: &[u8] = br#"
+(module
+    (import "wasi:cli/environment@0.2.0" "get-arguments" (func $get_arguments (param i32)))
+    (import "wasi:cli/stdout@0.2.0" "get-stdout" (func $get_stdout (result i32)))
+    (import "wasi:cli/exit@0.2.0" "exit" (func $exit (param i32)))
+    (import "wasi:filesystem/preopens@0.2.0" "get-directories" (func $get_directories (param i32)))
+    (import "wasi:filesystem/types@0.2.0" "[method]descriptor.open-at"
+        (func $open_at (param i32 i32 i32 i32 i32 i32 i32)))
+    (import "wasi:filesystem/types@0.2.0" "[method]descriptor.write-via-stream"
+        (func $write_via_stream (param i32 i64 i32)))
+    (import "wasi:filesystem/types@0.2.0" "[method]descriptor.read-via-stream"
+        (func $read_via_stream (param i32 i64 i32)))
+    (import "wasi:io/streams@0.2.0" "[method]output-stream.blocking-write-and-flush"
+        (func $blocking_write_and_flush (param i32 i32 i32 i32)))
+    (import "wasi:io/streams@0.2.0" "[method]input-stream.blocking-read"
+        (func $blocking_read (param i32 i64 i32)))
+    (import "wasi:io/streams@0.2.0" "[resource-drop]output-stream"
+        (func $drop_output_stream (param i32)))
+
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (data (i32.const 200) "out.txt")
+    (data (i32.const 208) "preview2")
+    (data (i32.const 216) "missing")
+
+    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr
+            (i32.and
+                (i32.add (global.get $heap) (i32.sub (local.get 2) (i32.const 1)))
+                (i32.sub (i32.const 0) (local.get 2))))
+        (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+        (local.get $ptr)
+    )
+
+    ;; Traps unless the result at `ptr` is ok
+    (func $ok (param $ptr i32)
+        (if (i32.load8_u (local.get $ptr)) (then unreachable))
+    )
+
+    (func $print (param $ptr i32) (param $len i32)
+        (call $blocking_write_and_flush (call $get_stdout) (local.get $ptr) (local.get $len) (i32.const 16))
+        (call $ok (i32.const 16))
+    )
+
+    (func (export "_start")
+        (local $dir i32)
+        (local $file i32)
+        (local $stream i32)
+
+        ;; The second argument
+        (call $get_arguments (i32.const 0))
+        (call $print
+            (i32.load (i32.add (i32.load (i32.const 0)) (i32.const 8)))
+            (i32.load (i32.add (i32.load (i32.const 0)) (i32.const 12))))
+
+        ;; The descriptor of the last preopen, /data
+        (call $get_directories (i32.const 32))
+        (local.set $dir (i32.load (i32.add (i32.load (i32.const 32))
+            (i32.mul (i32.sub (i32.load (i32.const 36)) (i32.const 1)) (i32.const 12)))))
+
+        ;; Creates out.txt, readable and writable
+        (call $open_at (local.get $dir) (i32.const 0) (i32.const 200) (i32.const 7)
+            (i32.const 1) (i32.const 3) (i32.const 48))
+        (call $ok (i32.const 48))
+        (local.set $file (i32.load (i32.const 52)))
+
+        (call $write_via_stream (local.get $file) (i64.const 0) (i32.const 64))
+        (call $ok (i32.const 64))
+        (local.set $stream (i32.load (i32.const 68)))
+        (call $blocking_write_and_flush (local.get $stream) (i32.const 208) (i32.const 8) (i32.const 16))
+        (call $ok (i32.const 16))
+        (call $drop_output_stream (local.get $stream))
+
+        (call $read_via_stream (local.get $file) (i64.const 0) (i32.const 80))
+        (call $ok (i32.const 80))
+        (call $blocking_read (i32.load (i32.const 84)) (i64.const 100) (i32.const 96))
+        (call $ok (i32.const 96))
+        (call $print (i32.load (i32.const 100)) (i32.load (i32.const 104)))
+
+        ;; Opening a missing file without creating it fails, at 112
+        (call $open_at (local.get $dir) (i32.const 0) (i32.const 216) (i32.const 7)
+            (i32.const 0) (i32.const 1) (i32.const 112))
+
+        (call $exit (i32.const 0))
+    )
+)
+"#;
+
+#[cfg(all(feature = "sys", feature = "compiler"))]
+#[test]
+fn test_preview2_guest() {
+    use wasmer::Module;
+
+    let fs = mem_fs::FileSystem::default();
+    fs.create_dir(Path::new("/data")).unwrap();
+
+    let mut store = Store::default();
+    let module = Module::new(&store, GUEST).unwrap();
+    assert!(wasmer_wasi::is_preview2_module(&module));
+
+    let mut stdout = Pipe::default();
+    let mut wasi_env = WasiState::new("command-name")
+        .args(&["Gordon"])
+        .stdout(Box::new(stdout.clone()))
+        .set_fs(Box::new(fs.clone()))
+        .preopen_dir("/data")
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+    let imports = wasi_env
+        .import_object_for_all_wasi_versions(&mut store, &module)
+        .unwrap();
+    let instance = wasmer::Instance::new(&mut store, &module, &imports).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    let err = start.call(&mut store, &[]).unwrap_err();
+    assert_eq!(err.message(), "WASI exited with code: 0");
+
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "Gordonpreview2");
+
+    let mut contents = String::new();
+    fs.new_open_options()
+        .read(true)
+        .open(Path::new("/data/out.txt"))
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "preview2");
+
+    // The `no-entry` error code of the failed `open-at`
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut result = [0; 5];
+    memory.view(&store).read(112, &mut result).unwrap();
+    assert_eq!(result, [1, 0, 0, 0, 20]);
+}
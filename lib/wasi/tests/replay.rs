@@ -0,0 +1,78 @@
+#![cfg(all(feature = "sys", feature = "compiler", feature = "replay"))]
+
+use wasmer::{Imports, Instance, Module, Store};
+use wasmer_wasi::{
+    generate_import_object_from_env, SyscallOutcome, SyscallRecorder, SyscallReplayer,
+    SyscallTrace, WasiFunctionEnv, WasiState, WasiVersion,
+};
+
+const WAT: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func (export "_start")
+        (i32.store (i32.const 0) (call $random_get (i32.const 16) (i32.const 32)))
+        (call $proc_exit (i32.const 7))
+    )
+)
+"#;
+
+/// Runs the module with the import object wrapped by `wrap`, and returns
+/// the memory where the module stored the results of its syscalls
+fn run(
+    deterministic: bool,
+    wrap: impl FnOnce(&mut Store, &WasiFunctionEnv, &Imports) -> Imports,
+) -> Vec<u8> {
+    let mut store = Store::default();
+    let module = Module::new(&store, WAT).unwrap();
+    let mut wasi_env = WasiState::new("replay")
+        .deterministic(deterministic)
+        .finalize(&mut store)
+        .unwrap();
+    let imports =
+        generate_import_object_from_env(&mut store, &wasi_env.env, WasiVersion::Snapshot1);
+    let imports = wrap(&mut store, &wasi_env, &imports);
+    let instance = Instance::new(&mut store, &module, &imports).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    let err = start.call(&mut store, &[]).unwrap_err();
+    assert_eq!(err.message(), "WASI exited with code: 7");
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut data = vec![0; 48];
+    memory.view(&store).read(0, &mut data).unwrap();
+    data
+}
+
+#[test]
+fn test_syscall_record_replay() {
+    let recorder = SyscallRecorder::new();
+    let recorded = run(false, |store, env, imports| {
+        recorder.wrap(store, &env.env, imports)
+    });
+
+    let trace = recorder.trace();
+    assert_eq!(trace.events.len(), 2);
+    assert_eq!(trace.events[0].name, "random_get");
+    assert_eq!(trace.events[0].writes.len(), 1);
+    assert_eq!(trace.events[0].writes[0].offset, 16);
+    assert_eq!(trace.events[0].writes[0].data, recorded[16..48]);
+    assert_eq!(trace.events[1].outcome, SyscallOutcome::Exit(7));
+
+    let mut bytes = Vec::new();
+    trace.write_to(&mut bytes).unwrap();
+    let trace = SyscallTrace::read_from(&bytes[..]).unwrap();
+
+    // Without the replay, `random_get` fails in a deterministic environment
+    let replayer = SyscallReplayer::new(trace);
+    let replayed = run(true, |store, env, imports| {
+        replayer.wrap(store, &env.env, imports)
+    });
+    assert_eq!(replayed, recorded);
+    assert_eq!(replayer.remaining(), 0);
+}
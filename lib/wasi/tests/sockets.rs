@@ -0,0 +1,132 @@
+#![cfg(all(feature = "sys", feature = "compiler"))]
+
+use wasmer::{Instance, Module, Store};
+use wasmer_vnet::in_memory::InMemoryNetworking;
+use wasmer_wasi::{
+    import_object_for_all_wasi_versions, wasi_import_wasmedge_sockets,
+    PluggableRuntimeImplementation, WasiState,
+};
+
+/// A guest using the WasmEdge socket functions. The errno of the `i`-th
+/// call is stored at `200 + 4 * i`.
+const WAT: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "sock_open" (func $sock_open (param i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_bind" (func $sock_bind (param i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_listen" (func $sock_listen (param i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_accept" (func $sock_accept (param i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_connect" (func $sock_connect (param i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_send" (func $sock_send (param i32 i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_recv" (func $sock_recv (param i32 i32 i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_send_to" (func $sock_send_to (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "sock_recv_from" (func $sock_recv_from (param i32 i32 i32 i32 i32 i32 i32 i32) (result i32)))
+
+    (memory (export "memory") 1)
+
+    ;; The address 127.0.0.1 at 32, as a 4 bytes buffer and as a buffer too
+    ;; small to hold it
+    (data (i32.const 16) "\20\00\00\00\04\00\00\00")
+    (data (i32.const 24) "\20\00\00\00\02\00\00\00")
+    (data (i32.const 32) "\7f\00\00\01")
+    ;; The buffer receiving the address of a peer, at 128
+    (data (i32.const 40) "\80\00\00\00\04\00\00\00")
+    ;; The data sent, "hello" and "ping"
+    (data (i32.const 48) "\40\00\00\00\05\00\00\00")
+    (data (i32.const 56) "\45\00\00\00\04\00\00\00")
+    (data (i32.const 64) "helloping")
+    ;; The buffers receiving the data, at 96 and 112
+    (data (i32.const 80) "\60\00\00\00\10\00\00\00")
+    (data (i32.const 88) "\70\00\00\00\10\00\00\00")
+
+    (func $errno (param $i i32) (param $errno i32)
+        (i32.store
+            (i32.add (i32.const 200) (i32.shl (local.get $i) (i32.const 2)))
+            (local.get $errno))
+    )
+
+    (func (export "_start")
+        ;; A TCP connection on 127.0.0.1:8080, the server socket at 0, the
+        ;; client socket at 4 and the accepted socket at 8
+        (call $errno (i32.const 0) (call $sock_open (i32.const 1) (i32.const 2) (i32.const 0)))
+        (call $errno (i32.const 1)
+            (call $sock_bind (i32.load (i32.const 0)) (i32.const 16) (i32.const 8080)))
+        (call $errno (i32.const 2) (call $sock_listen (i32.load (i32.const 0)) (i32.const 1)))
+        (call $errno (i32.const 3) (call $sock_open (i32.const 1) (i32.const 2) (i32.const 4)))
+        (call $errno (i32.const 4)
+            (call $sock_connect (i32.load (i32.const 4)) (i32.const 16) (i32.const 8080)))
+        (call $errno (i32.const 5) (call $sock_accept (i32.load (i32.const 0)) (i32.const 8)))
+        (call $errno (i32.const 6)
+            (call $sock_send (i32.load (i32.const 4)) (i32.const 48) (i32.const 1)
+                (i32.const 0) (i32.const 144)))
+        (call $errno (i32.const 7)
+            (call $sock_recv (i32.load (i32.const 8)) (i32.const 80) (i32.const 1)
+                (i32.const 0) (i32.const 136) (i32.const 140)))
+
+        ;; A datagram from 127.0.0.1:9001 to 127.0.0.1:9000, the receiving
+        ;; socket at 12 and the sending one at 160
+        (call $errno (i32.const 8) (call $sock_open (i32.const 1) (i32.const 1) (i32.const 12)))
+        (call $errno (i32.const 9)
+            (call $sock_bind (i32.load (i32.const 12)) (i32.const 16) (i32.const 9000)))
+        (call $errno (i32.const 10) (call $sock_open (i32.const 1) (i32.const 1) (i32.const 160)))
+        (call $errno (i32.const 11)
+            (call $sock_bind (i32.load (i32.const 160)) (i32.const 16) (i32.const 9001)))
+        (call $errno (i32.const 12)
+            (call $sock_send_to (i32.load (i32.const 160)) (i32.const 56) (i32.const 1)
+                (i32.const 16) (i32.const 9000) (i32.const 0) (i32.const 144)))
+        ;; Nothing is received while the address doesn't fit
+        (call $errno (i32.const 13)
+            (call $sock_recv_from (i32.load (i32.const 12)) (i32.const 88) (i32.const 1)
+                (i32.const 24) (i32.const 0) (i32.const 152) (i32.const 148) (i32.const 140)))
+        (call $errno (i32.const 14)
+            (call $sock_recv_from (i32.load (i32.const 12)) (i32.const 88) (i32.const 1)
+                (i32.const 40) (i32.const 0) (i32.const 152) (i32.const 148) (i32.const 140)))
+    )
+)
+"#;
+
+#[test]
+fn test_wasmedge_sockets_in_memory() {
+    let mut store = Store::default();
+    let module = Module::new(&store, WAT).unwrap();
+
+    let mut runtime = PluggableRuntimeImplementation::default();
+    runtime.set_networking_implementation(InMemoryNetworking::new());
+    let mut wasi_env = WasiState::new("sockets")
+        .runtime(runtime)
+        .finalize(&mut store)
+        .unwrap();
+    let mut imports = import_object_for_all_wasi_versions(&mut store, &wasi_env.env);
+    wasi_import_wasmedge_sockets(&mut imports, &module, &mut store, &wasi_env.env);
+    let instance = Instance::new(&mut store, &module, &imports).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let view = memory.view(&store);
+    let read_u32 = |offset| {
+        let mut bytes = [0; 4];
+        view.read(offset, &mut bytes).unwrap();
+        u32::from_le_bytes(bytes)
+    };
+
+    let errnos = (0..15).map(|i| read_u32(200 + 4 * i)).collect::<Vec<_>>();
+    // `Errno::Inval` for the address buffer that is too small
+    let mut expected = vec![0; 15];
+    expected[13] = 28;
+    assert_eq!(errnos, expected);
+
+    let mut data = [0; 5];
+    assert_eq!(read_u32(136), 5);
+    view.read(96, &mut data).unwrap();
+    assert_eq!(&data, b"hello");
+
+    let mut data = [0; 4];
+    assert_eq!(read_u32(148), 4);
+    view.read(112, &mut data).unwrap();
+    assert_eq!(&data, b"ping");
+    view.read(128, &mut data).unwrap();
+    assert_eq!(data, [127, 0, 0, 1]);
+    assert_eq!(read_u32(152), 9001);
+}
@@ -0,0 +1,95 @@
+#![cfg(all(feature = "sys", feature = "compiler", feature = "mem-fs"))]
+
+use std::io::Write;
+use std::path::Path;
+use wasmer::{Instance, Module, Store};
+use wasmer_vfs::{mem_fs, FileSystem};
+use wasmer_wasi::{generate_import_object_from_env, WasiState, WasiVersion};
+use wasmer_wasi_types::wasi::Errno;
+
+/// Creates an absolute symlink and one leading out of `/data`, opens the
+/// absolute symlink `link`, then creates a symlink to `a.txt`, and stores
+/// the results of the four syscalls from 0
+const WAT: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "path_symlink"
+        (func $path_symlink (param i32 i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_open"
+        (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    (data (i32.const 32) "/etc/passwd")
+    (data (i32.const 48) "abs")
+    (data (i32.const 64) "../../outside")
+    (data (i32.const 80) "escape")
+    (data (i32.const 96) "link")
+    (data (i32.const 112) "a.txt")
+    (data (i32.const 128) "rel")
+
+    (func (export "_start")
+        (i32.store (i32.const 0)
+            (call $path_symlink
+                (i32.const 32) (i32.const 11) (i32.const 4) (i32.const 48) (i32.const 3)))
+        (i32.store (i32.const 4)
+            (call $path_symlink
+                (i32.const 64) (i32.const 13) (i32.const 4) (i32.const 80) (i32.const 6)))
+        (i32.store (i32.const 8)
+            (call $path_open
+                (i32.const 4) (i32.const 1) (i32.const 96) (i32.const 4) (i32.const 0)
+                (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 24)))
+        (i32.store (i32.const 12)
+            (call $path_symlink
+                (i32.const 112) (i32.const 5) (i32.const 4) (i32.const 128) (i32.const 3)))
+        (call $proc_exit (i32.const 0))
+    )
+)
+"#;
+
+#[test]
+fn test_absolute_symlinks() {
+    let fs = mem_fs::FileSystem::default();
+    fs.create_dir(Path::new("/data")).unwrap();
+    fs.new_open_options()
+        .write(true)
+        .create(true)
+        .open(Path::new("/data/a.txt"))
+        .unwrap()
+        .write_all(b"data")
+        .unwrap();
+    fs.symlink(Path::new("/data/a.txt"), Path::new("/data/link"))
+        .unwrap();
+
+    let mut store = Store::default();
+    let module = Module::new(&store, WAT).unwrap();
+    let mut wasi_env = WasiState::new("symlinks")
+        .set_fs(Box::new(fs.clone()))
+        .preopen_dir("/data")
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+    let imports =
+        generate_import_object_from_env(&mut store, &wasi_env.env, WasiVersion::Snapshot1);
+    let instance = Instance::new(&mut store, &module, &imports).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    let err = start.call(&mut store, &[]).unwrap_err();
+    assert_eq!(err.message(), "WASI exited with code: 0");
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let mut results = [0; 16];
+    memory.view(&store).read(0, &mut results).unwrap();
+    assert_eq!(results[0..4], [Errno::Notcapable as u8, 0, 0, 0]);
+    assert_eq!(results[4..8], [Errno::Notcapable as u8, 0, 0, 0]);
+    assert_eq!(results[8..12], [Errno::Notcapable as u8, 0, 0, 0]);
+    assert_eq!(results[12..16], [Errno::Success as u8, 0, 0, 0]);
+
+    assert!(fs.symlink_metadata(Path::new("/data/abs")).is_err());
+    assert!(fs.symlink_metadata(Path::new("/data/escape")).is_err());
+    assert_eq!(
+        fs.readlink(Path::new("/data/rel")).unwrap(),
+        Path::new("a.txt")
+    );
+}
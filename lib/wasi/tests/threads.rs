@@ -0,0 +1,120 @@
+#![cfg(all(feature = "sys", feature = "compiler"))]
+
+use wasmer::{AsStoreMut, Function, FunctionEnv, Imports, Instance, Module, Store};
+use wasmer_wasi::{
+    import_object_for_all_wasi_versions, wasi_import_shared_memory, WasiEnv, WasiState,
+};
+
+#[test]
+fn test_wasi_thread_spawn() {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        br#"
+    (module
+        (import "wasi" "thread-spawn" (func $thread_spawn (param i32) (result i32)))
+        (import "wasi_snapshot_preview1" "sched_yield" (func $sched_yield (result i32)))
+        (import "env" "memory" (memory 1 1 shared))
+        (export "memory" (memory 0))
+
+        ;; Stores the ID of the thread where it was told to
+        (func (export "wasi_thread_start") (param $tid i32) (param $start_arg i32)
+            (i32.atomic.store (local.get $start_arg) (local.get $tid))
+        )
+
+        (func (export "_start")
+            (i32.store (i32.const 0) (call $thread_spawn (i32.const 16)))
+            (block $done
+                (loop $wait
+                    (br_if $done (i32.atomic.load (i32.const 16)))
+                    (drop (call $sched_yield))
+                    (br $wait)
+                )
+            )
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    let mut wasi_env = WasiState::new("threads").finalize(&mut store).unwrap();
+    let mut import_object = import_object_for_all_wasi_versions(&mut store, &wasi_env.env);
+    wasi_import_shared_memory(&mut import_object, &module, &mut store);
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let view = memory.view(&store);
+    let mut tid = [0; 4];
+    view.read(0, &mut tid).unwrap();
+    let mut stored = [0; 4];
+    view.read(16, &mut stored).unwrap();
+
+    assert!(i32::from_le_bytes(tid) > 0);
+    assert_eq!(tid, stored);
+}
+
+/// The WASI imports, and a function doubling its argument
+fn imports_with_double(store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>) -> Imports {
+    let mut import_object = import_object_for_all_wasi_versions(store, env);
+    import_object.define("env", "double", Function::new_typed(store, |x: i32| x * 2));
+    import_object
+}
+
+#[test]
+fn test_wasi_thread_spawn_with_defined_memory() {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        br#"
+    (module
+        (import "wasi" "thread-spawn" (func $thread_spawn (param i32) (result i32)))
+        (import "wasi_snapshot_preview1" "sched_yield" (func $sched_yield (result i32)))
+        (import "env" "double" (func $double (param i32) (result i32)))
+        (memory (export "memory") 1 1 shared)
+
+        ;; Stores twice the ID of the thread where it was told to
+        (func (export "wasi_thread_start") (param $tid i32) (param $start_arg i32)
+            (i32.atomic.store (local.get $start_arg) (call $double (local.get $tid)))
+        )
+
+        (func (export "_start")
+            (i32.store (i32.const 0) (call $thread_spawn (i32.const 16)))
+            (block $done
+                (loop $wait
+                    (br_if $done (i32.atomic.load (i32.const 16)))
+                    (drop (call $sched_yield))
+                    (br $wait)
+                )
+            )
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    let mut wasi_env = WasiState::new("threads").finalize(&mut store).unwrap();
+    wasi_env
+        .data_mut(&mut store)
+        .set_thread_imports(|store, env, _module| Ok(imports_with_double(store, env)));
+    let import_object = imports_with_double(&mut store, &wasi_env.env);
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    wasi_env.initialize(&mut store, &instance).unwrap();
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let view = memory.view(&store);
+    let mut tid = [0; 4];
+    view.read(0, &mut tid).unwrap();
+    let mut stored = [0; 4];
+    view.read(16, &mut stored).unwrap();
+
+    let tid = i32::from_le_bytes(tid);
+    assert!(tid > 0);
+    assert_eq!(i32::from_le_bytes(stored), tid * 2);
+}
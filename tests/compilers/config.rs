@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use wasmer::{CompilerConfig, Features, ModuleMiddleware, Store};
-use wasmer_compiler::Engine;
+use wasmer_compiler::{Engine, FunctionCache};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Compiler {
@@ -15,6 +15,8 @@ pub struct Config {
     pub features: Option<Features>,
     pub middlewares: Vec<Arc<dyn ModuleMiddleware>>,
     pub canonicalize_nans: bool,
+    pub lazy_compilation: bool,
+    pub function_cache: Option<Arc<dyn FunctionCache>>,
 }
 
 impl Config {
@@ -23,6 +25,8 @@ impl Config {
             compiler,
             features: None,
             canonicalize_nans: false,
+            lazy_compilation: false,
+            function_cache: None,
             middlewares: vec![],
         }
     }
@@ -39,6 +43,14 @@ impl Config {
         self.canonicalize_nans = canonicalize_nans;
     }
 
+    pub fn set_lazy_compilation(&mut self, lazy_compilation: bool) {
+        self.lazy_compilation = lazy_compilation;
+    }
+
+    pub fn set_function_cache(&mut self, function_cache: Arc<dyn FunctionCache>) {
+        self.function_cache = Some(function_cache);
+    }
+
     pub fn store(&self) -> Store {
         let compiler_config = self.compiler_config(self.canonicalize_nans);
         let engine = self.engine(compiler_config);
@@ -55,6 +67,8 @@ impl Config {
         if let Some(ref features) = self.features {
             engine = engine.set_features(Some(features.clone()));
         }
+        engine = engine.set_lazy_compilation(self.lazy_compilation);
+        engine = engine.set_function_cache(self.function_cache.clone());
         engine.engine()
     }
 
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use wasmer::{wat2wasm, Module, Store};
+use wasmer::*;
 
 fn compile_and_compare(wasm: &[u8]) -> Result<()> {
     let store = Store::default();
@@ -42,3 +42,39 @@ fn deterministic_table() -> Result<()> {
 
     compile_and_compare(&wasm_bytes)
 }
+
+#[compiler_test(deterministic)]
+fn deterministic_features_canonicalize_nans(mut config: crate::Config) -> Result<()> {
+    config.set_features(Features::deterministic());
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (func (export "div") (param f32 f32) (result f32)
+                local.get 0
+                local.get 1
+                f32.div))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let div: TypedFunction<(f32, f32), f32> = instance.exports.get_typed_function(&store, "div")?;
+    let result = div.call(&mut store, f32::from_bits(0x7fc0_1234), 1.0)?;
+    assert_eq!(result.to_bits(), 0x7fc0_0000);
+    Ok(())
+}
+
+#[compiler_test(deterministic)]
+fn deterministic_features_reject_threads(mut config: crate::Config) -> Result<()> {
+    config.set_features(Features::deterministic());
+    let store = config.store();
+    let wat = r#"
+        (module
+            (memory 1 1 shared)
+            (func (export "wait") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i64.const -1
+                memory.atomic.wait32))
+    "#;
+    assert!(Module::new(&store, wat).is_err());
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_compiler::{FileSystemFunctionCache, FunctionCache, FunctionCacheKey};
+use wasmer_types::SerializableFunction;
+
+/// A [`FunctionCache`] counting the functions loaded from another cache.
+struct CountingCache {
+    inner: FileSystemFunctionCache,
+    hits: AtomicUsize,
+}
+
+unsafe impl FunctionCache for CountingCache {
+    fn load(&self, key: &FunctionCacheKey) -> Option<SerializableFunction> {
+        let function = self.inner.load(key)?;
+        self.hits.fetch_add(1, Ordering::SeqCst);
+        Some(function)
+    }
+
+    fn store(
+        &self,
+        key: &FunctionCacheKey,
+        function: &SerializableFunction,
+    ) -> Result<(), SerializeError> {
+        self.inner.store(key, function)
+    }
+}
+
+fn module_wat(constant: i32) -> String {
+    format!(
+        r#"
+        (module
+            (func $constant (result i32)
+                i32.const {})
+            (func $double (param i32) (result i32)
+                local.get 0
+                i32.const 2
+                i32.mul)
+            (func (export "run") (result i32)
+                call $constant
+                call $double)
+        )
+    "#,
+        constant
+    )
+}
+
+fn run(store: &mut Store, wat: &str) -> Result<i32> {
+    let module = Module::new(&*store, wat)?;
+    let instance = Instance::new(&mut *store, &module, &imports! {})?;
+    let run: TypedFunction<(), i32> = instance.exports.get_typed_function(&*store, "run")?;
+    Ok(run.call(&mut *store)?)
+}
+
+#[compiler_test(function_cache)]
+fn function_cache_reuses_unchanged_functions(mut config: crate::Config) -> Result<()> {
+    if config.compiler == crate::Compiler::LLVM {
+        // LLVM doesn't compile functions one at a time, so it has no
+        // function cache fingerprint.
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let cache = Arc::new(CountingCache {
+        inner: unsafe { FileSystemFunctionCache::new(dir.path())? },
+        hits: AtomicUsize::new(0),
+    });
+    config.set_function_cache(cache.clone());
+    let num_cached = || std::fs::read_dir(dir.path()).unwrap().count();
+    let hits = || cache.hits.load(Ordering::SeqCst);
+
+    let mut store = config.store();
+    assert_eq!(run(&mut store, &module_wat(21))?, 42);
+    assert_eq!(num_cached(), 3);
+    assert_eq!(hits(), 0);
+
+    // Compiling the same module again only loads functions from the cache.
+    let mut store = config.store();
+    assert_eq!(run(&mut store, &module_wat(21))?, 42);
+    assert_eq!(num_cached(), 3);
+    assert_eq!(hits(), 3);
+
+    // Only the changed function is compiled again.
+    let mut store = config.store();
+    assert_eq!(run(&mut store, &module_wat(50))?, 100);
+    assert_eq!(num_cached(), 4);
+    assert_eq!(hits(), 5);
+    Ok(())
+}
+
+fn moved_module_wat(padding: bool) -> String {
+    format!(
+        r#"
+        (module $moved_mod
+            {}
+            (func $check (param i32) (result i32)
+                local.get 0
+                i32.eqz
+                if
+                    unreachable
+                end
+                local.get 0)
+            (func (export "run") (param i32) (result i32)
+                local.get 0
+                call $check)
+        )
+    "#,
+        if padding {
+            "(func $padding (param i32) (result i32) local.get 0)"
+        } else {
+            ""
+        }
+    )
+}
+
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(function_cache)]
+fn function_cache_reuses_moved_functions(mut config: crate::Config) -> Result<()> {
+    if config.compiler == crate::Compiler::LLVM {
+        return Ok(());
+    }
+    let dir = tempfile::tempdir()?;
+    let cache = Arc::new(CountingCache {
+        inner: unsafe { FileSystemFunctionCache::new(dir.path())? },
+        hits: AtomicUsize::new(0),
+    });
+    config.set_function_cache(cache.clone());
+
+    let store = config.store();
+    Module::new(&store, moved_module_wat(false))?;
+    assert_eq!(cache.hits.load(Ordering::SeqCst), 0);
+
+    // Adding a function of an existing type moves `$check`, which is
+    // still loaded from the cache. `run` calls it at its new index, so
+    // it's compiled again.
+    let mut store = config.store();
+    let module = Module::new(&store, moved_module_wat(true))?;
+    assert_eq!(cache.hits.load(Ordering::SeqCst), 1);
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "run")?;
+    assert_eq!(run.call(&mut store, 3)?, 3);
+
+    // The unwind information of `$check` follows it to its new index.
+    let e = run.call(&mut store, 0).unwrap_err();
+    let trace = e.trace();
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].func_index(), 1);
+    assert_eq!(trace[0].function_name(), Some("check"));
+    assert_eq!(trace[1].func_index(), 2);
+    Ok(())
+}
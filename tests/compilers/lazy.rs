@@ -0,0 +1,145 @@
+use anyhow::Result;
+use wasmer::*;
+use wasmer_types::TrapCode;
+
+#[compiler_test(lazy)]
+fn lazy_functions_are_compiled_on_call(mut config: crate::Config) -> Result<()> {
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (func $double (param i32) (result i32)
+                local.get 0
+                i32.const 2
+                i32.mul)
+            (func (export "add_doubled") (param i32 f64 i32) (result f64)
+                local.get 1
+                local.get 0
+                call $double
+                f64.convert_i32_s
+                f64.add
+                local.get 2
+                call $double
+                f64.convert_i32_s
+                f64.add)
+            (func (export "unused") (result i32)
+                i32.const 42)
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    assert_eq!(module.num_lazily_compiled_functions(), Some(0));
+
+    let add_doubled: TypedFunction<(i32, f64, i32), f64> = instance
+        .exports
+        .get_typed_function(&mut store, "add_doubled")?;
+    assert_eq!(add_doubled.call(&mut store, 1, 0.5, 3)?, 8.5);
+    assert_eq!(add_doubled.call(&mut store, -4, 1.0, 2)?, -3.0);
+    // `unused` is never called, so it's never compiled
+    assert_eq!(module.num_lazily_compiled_functions(), Some(2));
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn lazy_functions_trap(mut config: crate::Config) -> Result<()> {
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (func $boom unreachable)
+            (func (export "run") call $boom)
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let run = instance.exports.get_function("run")?;
+    let e = run.call(&mut store, &[]).unwrap_err();
+    assert_eq!(e.to_trap(), Some(TrapCode::UnreachableCodeReached));
+    Ok(())
+}
+
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(lazy)]
+fn lazy_functions_trap_trace(mut config: crate::Config) -> Result<()> {
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let wat = r#"
+        (module $hello_mod
+            (func (export "run") (call $outer))
+            (func $outer (call $hello))
+            (func $hello (unreachable))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let run = instance.exports.get_function("run")?;
+    let e = run.call(&mut store, &[]).unwrap_err();
+    // The backtrace goes through the lazily compiled functions, which
+    // needs their unwind information.
+    let trace = e.trace();
+    assert_eq!(trace.len(), 3);
+    assert_eq!(trace[0].module_name(), "hello_mod");
+    assert_eq!(trace[0].func_index(), 2);
+    assert_eq!(trace[0].function_name(), Some("hello"));
+    assert_eq!(trace[1].func_index(), 1);
+    assert_eq!(trace[1].function_name(), Some("outer"));
+    assert_eq!(trace[2].func_index(), 0);
+    assert_eq!(module.num_lazily_compiled_functions(), Some(3));
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn lazy_functions_are_resolved_concurrently(mut config: crate::Config) -> Result<()> {
+    config.set_lazy_compilation(true);
+    let store = config.store();
+    let wat = r#"
+        (module
+            (func $square (param i32) (result i32)
+                local.get 0
+                local.get 0
+                i32.mul)
+            (func (export "square") (param i32) (result i32)
+                local.get 0
+                call $square)
+            (func (export "cube") (param i32) (result i32)
+                local.get 0
+                call $square
+                local.get 0
+                i32.mul)
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+
+    let threads = (0..8)
+        .map(|i| {
+            let engine = store.engine().clone();
+            let module = module.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let mut store = Store::new(engine);
+                let instance = Instance::new(&mut store, &module, &imports! {})?;
+                let name = if i % 2 == 0 { "square" } else { "cube" };
+                let f: TypedFunction<i32, i32> =
+                    instance.exports.get_typed_function(&mut store, name)?;
+                let expected = if i % 2 == 0 { i * i } else { i * i * i };
+                assert_eq!(f.call(&mut store, i)?, expected);
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+    // Every function is compiled exactly once
+    assert_eq!(module.num_lazily_compiled_functions(), Some(3));
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn eager_modules_have_no_lazy_functions(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, "(module (func (export \"f\")))")?;
+    assert_eq!(module.num_lazily_compiled_functions(), None);
+    Ok(())
+}
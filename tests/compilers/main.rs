@@ -7,8 +7,10 @@ extern crate compiler_test_derive;
 
 mod config;
 mod deterministic;
+mod function_cache;
 mod imports;
 mod issues;
+mod lazy;
 mod metering;
 mod middlewares;
 // mod multi_value_imports;
@@ -23,6 +23,8 @@ pub fn run_wast(mut config: crate::Config, wast_path: &str) -> anyhow::Result<()
     let is_bulkmemory = wast_path.contains("bulk-memory");
     let is_simd = wast_path.contains("simd");
     let is_threads = wast_path.contains("threads");
+    let is_relaxed_simd = wast_path.contains("relaxed-simd");
+    let is_extended_const = wast_path.contains("extended-const");
     if is_bulkmemory {
         features.bulk_memory(true);
     }
@@ -32,6 +34,12 @@ pub fn run_wast(mut config: crate::Config, wast_path: &str) -> anyhow::Result<()
     if is_threads {
         features.threads(true);
     }
+    if is_relaxed_simd {
+        features.relaxed_simd(true);
+    }
+    if is_extended_const {
+        features.extended_const(true);
+    }
     if config.compiler == crate::Compiler::Singlepass {
         features.multi_value(false);
     }
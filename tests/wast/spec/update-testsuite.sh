@@ -18,6 +18,8 @@ repos='
   sign-extension-ops
   reference-types
   annotations
+  relaxed-simd
+  extended-const
 '
 
 log_and_run() {
@@ -0,0 +1,31 @@
+(module
+    (memory 1 1 shared)
+    (func (export "wait32") (param i32 i32 i64) (result i32)
+        (memory.atomic.wait32 offset=8 (local.get 0) (local.get 1) (local.get 2)))
+    (func (export "wait64") (param i32 i64 i64) (result i32)
+        (memory.atomic.wait64 offset=8 (local.get 0) (local.get 1) (local.get 2)))
+    (func (export "notify") (param i32 i32) (result i32)
+        (memory.atomic.notify offset=8 (local.get 0) (local.get 1)))
+    (func (export "store32") (param i32 i32)
+        (i32.atomic.store offset=8 (local.get 0) (local.get 1)))
+)
+
+;; The static offset is part of the address
+(invoke "store32" (i32.const 0) (i32.const 42))
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 0) (i64.const 0)) (i32.const 1))
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 42) (i64.const 0)) (i32.const 2))
+(assert_return (invoke "wait64" (i32.const 0) (i64.const 0) (i64.const 0)) (i32.const 1))
+(assert_return (invoke "wait64" (i32.const 0) (i64.const 42) (i64.const 1000)) (i32.const 2))
+(assert_return (invoke "notify" (i32.const 0) (i32.const 1)) (i32.const 0))
+
+;; Out of bounds, including when the offset overflows the address
+(assert_trap (invoke "wait32" (i32.const 65528) (i32.const 0) (i64.const 0)) "out of bound")
+(assert_trap (invoke "wait64" (i32.const 65528) (i64.const 0) (i64.const 0)) "out of bound")
+(assert_trap (invoke "notify" (i32.const 65528) (i32.const 1)) "out of bound")
+(assert_trap (invoke "notify" (i32.const 0xffff_fffc) (i32.const 1)) "out of bound")
+(assert_trap (invoke "wait32" (i32.const 0xffff_fffc) (i32.const 0) (i64.const 0)) "out of bound")
+
+;; Misaligned accesses
+(assert_trap (invoke "wait32" (i32.const 1) (i32.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "wait64" (i32.const 4) (i64.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "notify" (i32.const 2) (i32.const 1)) "unaligned atomic")
@@ -0,0 +1,33 @@
+;; Data segments with extended constant expression offsets
+
+(module
+  (global (import "spectest" "global_i32") i32)
+  (memory 1)
+
+  (data (offset (i32.add (i32.const 1) (i32.const 2))) "a")
+  (data (offset (i32.sub (global.get 0) (i32.const 656))) "b")
+  (data (offset (i32.mul (i32.const 4) (i32.const 5))) "c")
+
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+)
+
+(assert_return (invoke "load" (i32.const 3)) (i32.const 0x61))
+(assert_return (invoke "load" (i32.const 10)) (i32.const 0x62))
+(assert_return (invoke "load" (i32.const 20)) (i32.const 0x63))
+(assert_return (invoke "load" (i32.const 0)) (i32.const 0))
+
+(assert_trap
+  (module
+    (memory 1)
+    (data (offset (i32.add (i32.const 65535) (i32.const 1))) "a")
+  )
+  "out of bounds memory access"
+)
+
+(assert_invalid
+  (module
+    (memory 1)
+    (data (offset (i64.add (i64.const 0) (i64.const 1))) "a")
+  )
+  "type mismatch"
+)
@@ -0,0 +1,30 @@
+;; Element segments with extended constant expression offsets
+
+(module
+  (global (import "spectest" "global_i32") i32)
+  (table 10 funcref)
+
+  (func $f (result i32) (i32.const 1))
+  (func $g (result i32) (i32.const 2))
+
+  (elem (offset (i32.add (i32.const 1) (i32.const 2))) $f)
+  (elem (offset (i32.sub (global.get 0) (i32.const 659))) $g)
+
+  (type $t (func (result i32)))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (type $t) (local.get 0))
+  )
+)
+
+(assert_return (invoke "call" (i32.const 3)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 7)) (i32.const 2))
+(assert_trap (invoke "call" (i32.const 0)) "uninitialized element")
+
+(assert_trap
+  (module
+    (table 1 funcref)
+    (func $f)
+    (elem (offset (i32.mul (i32.const 1) (i32.const 2))) $f)
+  )
+  "out of bounds table access"
+)
@@ -0,0 +1,63 @@
+;; Globals initialized with extended constant expressions
+
+(module
+  (global (import "spectest" "global_i32") i32)
+  (global (import "spectest" "global_i64") i64)
+
+  (global $add-i32 i32 (i32.add (global.get 0) (i32.const 1)))
+  (global $sub-i32 i32 (i32.sub (global.get 0) (i32.const 667)))
+  (global $mul-i32 i32 (i32.mul (i32.const 3) (i32.add (global.get 0) (i32.const 4))))
+  (global $wrap-i32 i32 (i32.add (i32.const 0x7fffffff) (i32.const 1)))
+
+  (global $add-i64 i64 (i64.add (global.get 1) (i64.const 1)))
+  (global $sub-i64 i64 (i64.sub (global.get 1) (i64.const 6)))
+  (global $mul-i64 i64 (i64.mul (i64.add (global.get 1) (i64.const 1)) (i64.const -1)))
+  (global $wrap-i64 i64 (i64.mul (i64.const 0x4000000000000000) (i64.const 4)))
+
+  (func (export "add-i32") (result i32) (global.get $add-i32))
+  (func (export "sub-i32") (result i32) (global.get $sub-i32))
+  (func (export "mul-i32") (result i32) (global.get $mul-i32))
+  (func (export "wrap-i32") (result i32) (global.get $wrap-i32))
+  (func (export "add-i64") (result i64) (global.get $add-i64))
+  (func (export "sub-i64") (result i64) (global.get $sub-i64))
+  (func (export "mul-i64") (result i64) (global.get $mul-i64))
+  (func (export "wrap-i64") (result i64) (global.get $wrap-i64))
+)
+
+(assert_return (invoke "add-i32") (i32.const 667))
+(assert_return (invoke "sub-i32") (i32.const -1))
+(assert_return (invoke "mul-i32") (i32.const 2010))
+(assert_return (invoke "wrap-i32") (i32.const 0x80000000))
+(assert_return (invoke "add-i64") (i64.const 667))
+(assert_return (invoke "sub-i64") (i64.const 660))
+(assert_return (invoke "mul-i64") (i64.const -667))
+(assert_return (invoke "wrap-i64") (i64.const 0))
+
+;; Globals defined with extended constant expressions can be exported and
+;; read from other modules.
+
+(module $M
+  (global (export "g") i32 (i32.mul (i32.const 6) (i32.const 7)))
+)
+(register "M" $M)
+
+(module
+  (global (import "M" "g") i32)
+  (global $g i32 (i32.sub (global.get 0) (i32.const 2)))
+  (func (export "get") (result i32) (global.get $g))
+)
+
+(assert_return (invoke "get") (i32.const 40))
+
+(assert_invalid
+  (module (global i32 (i32.div_s (i32.const 6) (i32.const 3))))
+  "constant expression required"
+)
+(assert_invalid
+  (module (global i32 (i32.add (i32.const 1) (i64.const 1))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (global i64 (i32.add (i32.const 1) (i32.const 1))))
+  "type mismatch"
+)
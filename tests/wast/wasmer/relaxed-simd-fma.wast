@@ -0,0 +1,79 @@
+;; Tests for the relaxed fused multiply-add instructions.
+;; The proposal allows rounding the intermediate product or not; Wasmer
+;; always rounds it, in every compiler, which the last tests check.
+
+;; Written in binary, since the pinned `wast` doesn't parse the relaxed
+;; SIMD instructions yet:
+;;
+;; (module
+;;   (func (export "f32x4.fma") (param v128 v128 v128) (result v128)
+;;     (f32x4.fma (local.get 0) (local.get 1) (local.get 2)))
+;;   (func (export "f32x4.fms") (param v128 v128 v128) (result v128)
+;;     (f32x4.fms (local.get 0) (local.get 1) (local.get 2)))
+;;   (func (export "f64x2.fma") (param v128 v128 v128) (result v128)
+;;     (f64x2.fma (local.get 0) (local.get 1) (local.get 2)))
+;;   (func (export "f64x2.fms") (param v128 v128 v128) (result v128)
+;;     (f64x2.fms (local.get 0) (local.get 1) (local.get 2)))
+;; )
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\08\01\60\03\7b\7b\7b\01\7b"
+  "\03\05\04\00\00\00\00"
+  "\07\31\04\09f32x4.fma\00\00\09f32x4.fms\00\01\09f64x2.fma\00\02\09f64x2.fms\00\03"
+  "\0a\31\04"
+  "\0b\00\20\00\20\01\20\02\fd\af\01\0b"
+  "\0b\00\20\00\20\01\20\02\fd\b0\01\0b"
+  "\0b\00\20\00\20\01\20\02\fd\cf\01\0b"
+  "\0b\00\20\00\20\01\20\02\fd\d0\01\0b"
+)
+
+(assert_return (invoke "f32x4.fma"
+                       (v128.const f32x4 2.0 -1.5 0.0 10.0)
+                       (v128.const f32x4 3.0 2.0 5.0 0.25)
+                       (v128.const f32x4 1.0 1.0 -2.0 0.5))
+               (v128.const f32x4 7.0 -2.0 -2.0 3.0))
+
+(assert_return (invoke "f32x4.fms"
+                       (v128.const f32x4 2.0 -1.5 0.0 10.0)
+                       (v128.const f32x4 3.0 2.0 5.0 0.25)
+                       (v128.const f32x4 1.0 1.0 -2.0 0.5))
+               (v128.const f32x4 -5.0 4.0 -2.0 -2.0))
+
+(assert_return (invoke "f64x2.fma"
+                       (v128.const f64x2 2.0 -1.5)
+                       (v128.const f64x2 3.0 2.0)
+                       (v128.const f64x2 1.0 1.0))
+               (v128.const f64x2 7.0 -2.0))
+
+(assert_return (invoke "f64x2.fms"
+                       (v128.const f64x2 2.0 -1.5)
+                       (v128.const f64x2 3.0 2.0)
+                       (v128.const f64x2 1.0 1.0))
+               (v128.const f64x2 -5.0 4.0))
+
+;; The product (1 + 2^-13)^2 = 1 + 2^-12 + 2^-26 is rounded to 1 + 2^-12
+;; before the addition, giving 0 instead of the fused result 2^-26.
+(assert_return (invoke "f32x4.fma"
+                       (v128.const f32x4 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0)
+                       (v128.const f32x4 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0)
+                       (v128.const f32x4 -0x1.001p+0 -0x1.001p+0 -0x1.001p+0 -0x1.001p+0))
+               (v128.const f32x4 0.0 0.0 0.0 0.0))
+
+(assert_return (invoke "f32x4.fms"
+                       (v128.const f32x4 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0)
+                       (v128.const f32x4 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0 0x1.0008p+0)
+                       (v128.const f32x4 0x1.001p+0 0x1.001p+0 0x1.001p+0 0x1.001p+0))
+               (v128.const f32x4 0.0 0.0 0.0 0.0))
+
+;; Likewise (1 + 2^-27)^2 = 1 + 2^-26 + 2^-54 is rounded to 1 + 2^-26.
+(assert_return (invoke "f64x2.fma"
+                       (v128.const f64x2 0x1.0000002p+0 0x1.0000002p+0)
+                       (v128.const f64x2 0x1.0000002p+0 0x1.0000002p+0)
+                       (v128.const f64x2 -0x1.0000004p+0 -0x1.0000004p+0))
+               (v128.const f64x2 0.0 0.0))
+
+(assert_return (invoke "f64x2.fms"
+                       (v128.const f64x2 0x1.0000002p+0 0x1.0000002p+0)
+                       (v128.const f64x2 0x1.0000002p+0 0x1.0000002p+0)
+                       (v128.const f64x2 0x1.0000004p+0 0x1.0000004p+0))
+               (v128.const f64x2 0.0 0.0))
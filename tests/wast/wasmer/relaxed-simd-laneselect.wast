@@ -0,0 +1,52 @@
+;; Tests for the relaxed lane select instructions.
+;; Only masks with all the bits of each lane set or cleared are tested,
+;; since the result for other masks depends on the implementation.
+
+;; Written in binary, since the pinned `wast` doesn't parse the relaxed
+;; SIMD instructions yet:
+;;
+;; (module
+;;   (func (export "i8x16.laneselect") (param v128 v128 v128) (result v128)
+;;     (i8x16.laneselect (local.get 0) (local.get 1) (local.get 2)))
+;;   (func (export "i16x8.laneselect") (param v128 v128 v128) (result v128)
+;;     (i16x8.laneselect (local.get 0) (local.get 1) (local.get 2)))
+;;   (func (export "i32x4.laneselect") (param v128 v128 v128) (result v128)
+;;     (i32x4.laneselect (local.get 0) (local.get 1) (local.get 2)))
+;;   (func (export "i64x2.laneselect") (param v128 v128 v128) (result v128)
+;;     (i64x2.laneselect (local.get 0) (local.get 1) (local.get 2)))
+;; )
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\08\01\60\03\7b\7b\7b\01\7b"
+  "\03\05\04\00\00\00\00"
+  "\07\4d\04\10i8x16.laneselect\00\00\10i16x8.laneselect\00\01\10i32x4.laneselect\00\02\10i64x2.laneselect\00\03"
+  "\0a\31\04"
+  "\0b\00\20\00\20\01\20\02\fd\b2\01\0b"
+  "\0b\00\20\00\20\01\20\02\fd\b3\01\0b"
+  "\0b\00\20\00\20\01\20\02\fd\d2\01\0b"
+  "\0b\00\20\00\20\01\20\02\fd\d3\01\0b"
+)
+
+(assert_return (invoke "i8x16.laneselect"
+                       (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+                       (v128.const i8x16 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31)
+                       (v128.const i8x16 0xff 0 0xff 0 0xff 0 0xff 0 0xff 0 0xff 0 0xff 0 0xff 0))
+               (v128.const i8x16 0 17 2 19 4 21 6 23 8 25 10 27 12 29 14 31))
+
+(assert_return (invoke "i16x8.laneselect"
+                       (v128.const i16x8 0 1 2 3 4 5 6 7)
+                       (v128.const i16x8 8 9 10 11 12 13 14 15)
+                       (v128.const i16x8 0 0xffff 0 0xffff 0 0xffff 0 0xffff))
+               (v128.const i16x8 8 1 10 3 12 5 14 7))
+
+(assert_return (invoke "i32x4.laneselect"
+                       (v128.const i32x4 1 2 3 4)
+                       (v128.const i32x4 5 6 7 8)
+                       (v128.const i32x4 0xffffffff 0xffffffff 0 0))
+               (v128.const i32x4 1 2 7 8))
+
+(assert_return (invoke "i64x2.laneselect"
+                       (v128.const i64x2 1 2)
+                       (v128.const i64x2 3 4)
+                       (v128.const i64x2 0 0xffffffffffffffff))
+               (v128.const i64x2 3 2))
@@ -0,0 +1,48 @@
+;; Tests for the relaxed min and max instructions.
+;; NaNs and zeros of different signs are not tested, since the result for
+;; them depends on the implementation.
+
+;; Written in binary, since the pinned `wast` doesn't parse the relaxed
+;; SIMD instructions yet:
+;;
+;; (module
+;;   (func (export "f32x4.relaxed_min") (param v128 v128) (result v128)
+;;     (f32x4.relaxed_min (local.get 0) (local.get 1)))
+;;   (func (export "f32x4.relaxed_max") (param v128 v128) (result v128)
+;;     (f32x4.relaxed_max (local.get 0) (local.get 1)))
+;;   (func (export "f64x2.relaxed_min") (param v128 v128) (result v128)
+;;     (f64x2.relaxed_min (local.get 0) (local.get 1)))
+;;   (func (export "f64x2.relaxed_max") (param v128 v128) (result v128)
+;;     (f64x2.relaxed_max (local.get 0) (local.get 1)))
+;; )
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\07\01\60\02\7b\7b\01\7b"
+  "\03\05\04\00\00\00\00"
+  "\07\51\04\11f32x4.relaxed_min\00\00\11f32x4.relaxed_max\00\01\11f64x2.relaxed_min\00\02\11f64x2.relaxed_max\00\03"
+  "\0a\29\04"
+  "\09\00\20\00\20\01\fd\b4\01\0b"
+  "\09\00\20\00\20\01\fd\e2\01\0b"
+  "\09\00\20\00\20\01\fd\d4\01\0b"
+  "\09\00\20\00\20\01\fd\ee\01\0b"
+)
+
+(assert_return (invoke "f32x4.relaxed_min"
+                       (v128.const f32x4 -1.0 2.0 -inf 4.5)
+                       (v128.const f32x4 1.0 -2.0 0.0 inf))
+               (v128.const f32x4 -1.0 -2.0 -inf 4.5))
+
+(assert_return (invoke "f32x4.relaxed_max"
+                       (v128.const f32x4 -1.0 2.0 -inf 4.5)
+                       (v128.const f32x4 1.0 -2.0 0.0 inf))
+               (v128.const f32x4 1.0 2.0 0.0 inf))
+
+(assert_return (invoke "f64x2.relaxed_min"
+                       (v128.const f64x2 -1.0 inf)
+                       (v128.const f64x2 1.0 3.5))
+               (v128.const f64x2 -1.0 3.5))
+
+(assert_return (invoke "f64x2.relaxed_max"
+                       (v128.const f64x2 -1.0 inf)
+                       (v128.const f64x2 1.0 3.5))
+               (v128.const f64x2 1.0 inf))
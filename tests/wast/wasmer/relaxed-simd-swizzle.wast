@@ -0,0 +1,29 @@
+;; Tests for i8x16.relaxed_swizzle.
+;; Only indices in range are tested, since the result for out of range
+;; indices depends on the implementation.
+
+;; Written in binary, since the pinned `wast` doesn't parse the relaxed
+;; SIMD instructions yet:
+;;
+;; (module
+;;   (func (export "i8x16.relaxed_swizzle") (param v128 v128) (result v128)
+;;     (i8x16.relaxed_swizzle (local.get 0) (local.get 1)))
+;; )
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\07\01\60\02\7b\7b\01\7b"
+  "\03\02\01\00"
+  "\07\19\01\15i8x16.relaxed_swizzle\00\00"
+  "\0a\0b\01"
+  "\09\00\20\00\20\01\fd\a2\01\0b"
+)
+
+(assert_return (invoke "i8x16.relaxed_swizzle"
+                       (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+                       (v128.const i8x16 15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 0))
+               (v128.const i8x16 15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 0))
+
+(assert_return (invoke "i8x16.relaxed_swizzle"
+                       (v128.const i8x16 0x10 0x11 0x12 0x13 0x14 0x15 0x16 0x17 0x18 0x19 0x1a 0x1b 0x1c 0x1d 0x1e 0x1f)
+                       (v128.const i8x16 0 0 0 0 1 1 1 1 2 2 2 2 15 15 15 15))
+               (v128.const i8x16 0x10 0x10 0x10 0x10 0x11 0x11 0x11 0x11 0x12 0x12 0x12 0x12 0x1f 0x1f 0x1f 0x1f))
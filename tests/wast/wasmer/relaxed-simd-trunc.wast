@@ -0,0 +1,47 @@
+;; Tests for the relaxed truncating conversions.
+;; Only values in range are tested, since the result for NaNs and out of
+;; range values depends on the implementation.
+
+;; Written in binary, since the pinned `wast` doesn't parse the relaxed
+;; SIMD instructions yet:
+;;
+;; (module
+;;   (func (export "i32x4.relaxed_trunc_f32x4_s") (param v128) (result v128)
+;;     (i32x4.relaxed_trunc_f32x4_s (local.get 0)))
+;;   (func (export "i32x4.relaxed_trunc_f32x4_u") (param v128) (result v128)
+;;     (i32x4.relaxed_trunc_f32x4_u (local.get 0)))
+;;   (func (export "i32x4.relaxed_trunc_f64x2_s_zero") (param v128) (result v128)
+;;     (i32x4.relaxed_trunc_f64x2_s_zero (local.get 0)))
+;;   (func (export "i32x4.relaxed_trunc_f64x2_u_zero") (param v128) (result v128)
+;;     (i32x4.relaxed_trunc_f64x2_u_zero (local.get 0)))
+;; )
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\06\01\60\01\7b\01\7b"
+  "\03\05\04\00\00\00\00"
+  "\07\83\01\04\1bi32x4.relaxed_trunc_f32x4_s\00\00\1bi32x4.relaxed_trunc_f32x4_u\00\01\20i32x4.relaxed_trunc_f64x2_s_zero\00\02\20i32x4.relaxed_trunc_f64x2_u_zero\00\03"
+  "\0a\21\04"
+  "\07\00\20\00\fd\a5\01\0b"
+  "\07\00\20\00\fd\a6\01\0b"
+  "\07\00\20\00\fd\c5\01\0b"
+  "\07\00\20\00\fd\c6\01\0b"
+)
+
+(assert_return (invoke "i32x4.relaxed_trunc_f32x4_s"
+                       (v128.const f32x4 -1.5 -0.5 0.5 1.5))
+               (v128.const i32x4 -1 0 0 1))
+(assert_return (invoke "i32x4.relaxed_trunc_f32x4_s"
+                       (v128.const f32x4 -2147483648.0 1000.9 -1000.9 2147483520.0))
+               (v128.const i32x4 -2147483648 1000 -1000 2147483520))
+
+(assert_return (invoke "i32x4.relaxed_trunc_f32x4_u"
+                       (v128.const f32x4 0.0 0.5 1.5 4294967040.0))
+               (v128.const i32x4 0 0 1 4294967040))
+
+(assert_return (invoke "i32x4.relaxed_trunc_f64x2_s_zero"
+                       (v128.const f64x2 -2147483648.0 2147483647.9))
+               (v128.const i32x4 -2147483648 2147483647 0 0))
+
+(assert_return (invoke "i32x4.relaxed_trunc_f64x2_u_zero"
+                       (v128.const f64x2 0.5 4294967295.9))
+               (v128.const i32x4 0 4294967295 0 0))